@@ -1,8 +1,9 @@
 //! A lightweight and configurable status bar for penrose
 use crate::{core::Draw, Result};
 use penrose::{
-    core::{State, WindowManager},
-    pure::geometry::Rect,
+    core::{scale_px, State, WindowManager},
+    extensions::hooks::theme::{ActiveTheme, Theme, ThemeMode},
+    pure::{geometry::Rect, ScreenTarget},
     x::{event::XEvent, Atom, ClientConfig, Prop, WinType, XConn},
     Color, Xid,
 };
@@ -23,14 +24,26 @@ pub enum Position {
 }
 
 /// A simple text based status bar that renders a user defined array of [`Widget`]s.
+///
+/// By default a bar is shown on every screen: use [StatusBar::on_screen] to only show it on
+/// a single screen (such as the screen showing the primary output). Bars are never shown on
+/// screens where they have been disabled using [MonitorConfig::bar][1].
+///
+/// When [Config::dpi_scaling][0] is set, the height of the bar on each screen is scaled
+/// according to the DPI of that screen.
+///
+///   [0]: penrose::core::Config::dpi_scaling
+///   [1]: penrose::core::MonitorConfig::bar
 pub struct StatusBar<X: XConn> {
     draw: Draw,
     position: Position,
     widgets: Vec<Box<dyn Widget<X>>>,
-    screens: Vec<(Xid, u32)>,
+    screens: Vec<(usize, Xid, u32, u32)>,
+    screen: Option<ScreenTarget>,
     h: u32,
     bg: Color,
     active_screen: usize,
+    theme_mode: Option<ThemeMode>,
 }
 
 impl<X: XConn> fmt::Debug for StatusBar<X> {
@@ -39,9 +52,11 @@ impl<X: XConn> fmt::Debug for StatusBar<X> {
             .field("position", &self.position)
             .field("widgets", &stringify!(self.widgets))
             .field("screens", &self.screens)
+            .field("screen", &self.screen)
             .field("h", &self.h)
             .field("bg", &self.bg)
             .field("active_screen", &self.active_screen)
+            .field("theme_mode", &self.theme_mode)
             .finish()
     }
 }
@@ -65,12 +80,24 @@ impl<X: XConn> StatusBar<X> {
             position,
             widgets,
             screens: vec![],
+            screen: None,
             h,
             bg,
             active_screen: 0,
+            theme_mode: None,
         })
     }
 
+    /// Only show this [`StatusBar`] on the given screen rather than on every screen.
+    ///
+    /// If the target screen is not connected then the bar is shown on the screen showing the
+    /// primary output. Bars targeting [ScreenTarget::Primary] move to follow the primary
+    /// output when it changes.
+    pub fn on_screen(mut self, screen: impl Into<ScreenTarget>) -> Self {
+        self.screen = Some(screen.into());
+        self
+    }
+
     /// Add this [`StatusBar`] into the given [`WindowManager`] along with the required
     /// hooks for driving it from the main WindowManager event loop.
     pub fn add_to(self, mut wm: WindowManager<X>) -> WindowManager<X>
@@ -82,26 +109,43 @@ impl<X: XConn> StatusBar<X> {
         wm.state.config.compose_or_set_manage_hook(manage_hook);
         wm.state.config.compose_or_set_refresh_hook(refresh_hook);
         wm.state.config.compose_or_set_startup_hook(startup_hook);
+        wm.state
+            .config
+            .compose_or_set_screens_changed_hook(screens_changed_hook);
 
         wm
     }
 
-    fn init_for_screens(&mut self) -> Result<()> {
+    // The index of the only screen this bar should be shown on, if it has a target screen
+    fn target_screen(&self, state: &State<X>) -> Option<usize> {
+        let cs = &state.client_set;
+
+        self.screen.map(|t| {
+            cs.screen_index_for(t)
+                .unwrap_or_else(|| cs.primary_screen())
+        })
+    }
+
+    fn init_for_screens(&mut self, state: &State<X>) -> Result<()> {
         info!("initialising per screen status bar windows");
         let screen_details = self.draw.conn.screen_details()?;
+        let target = self.target_screen(state);
 
         self.screens = screen_details
             .iter()
-            .map(|&Rect { x, y, w, h }| {
+            .enumerate()
+            .filter(|&(i, _)| target.is_none_or(|t| t == i) && state.bar_enabled(i))
+            .map(|(i, &Rect { x, y, w, h })| {
+                let bar_h = scale_px(self.h, state.screen_scale(i));
                 let y = match self.position {
                     Position::Top => y,
-                    Position::Bottom => h - self.h,
+                    Position::Bottom => h - bar_h,
                 };
 
                 debug!("creating new window");
                 let id = self.draw.new_window(
                     WinType::InputOutput(Atom::NetWindowTypeDock),
-                    Rect::new(x, y, w, self.h),
+                    Rect::new(x, y, w, bar_h),
                     false,
                 )?;
 
@@ -117,16 +161,28 @@ impl<X: XConn> StatusBar<X> {
                 debug!("flushing");
                 self.draw.flush(id)?;
 
-                Ok((id, w))
+                Ok((i, id, w, bar_h))
             })
-            .collect::<Result<Vec<(Xid, u32)>>>()?;
+            .collect::<Result<Vec<(usize, Xid, u32, u32)>>>()?;
 
         Ok(())
     }
 
+    // Remove the current status bar windows and create new ones for the current screens
+    fn reinit_for_screens(&mut self, state: &State<X>) -> Result<()> {
+        for (_, id, _, _) in self.screens.drain(0..).collect::<Vec<_>>() {
+            info!(%id, "removing previous status bar");
+            if let Err(e) = self.draw.destroy_window_and_surface(id) {
+                error!(%e, "error when removing previous status bar state");
+            }
+        }
+
+        self.init_for_screens(state)
+    }
+
     /// Re-render all widgets in this status bar
     pub fn redraw(&mut self) -> Result<()> {
-        for (i, &(id, w)) in self.screens.clone().iter().enumerate() {
+        for &(i, id, w, h) in self.screens.clone().iter() {
             let screen_has_focus = self.active_screen == i;
             let mut ctx = self.draw.context_for(id)?;
 
@@ -134,7 +190,7 @@ impl<X: XConn> StatusBar<X> {
             let mut greedy_indices = vec![];
 
             for (i, w) in self.widgets.iter_mut().enumerate() {
-                extents.push(w.current_extent(&mut ctx, self.h)?);
+                extents.push(w.current_extent(&mut ctx, h)?);
                 if w.is_greedy() {
                     greedy_indices.push(i)
                 }
@@ -153,7 +209,7 @@ impl<X: XConn> StatusBar<X> {
 
             let mut x = 0;
             for (wd, (w, _)) in self.widgets.iter_mut().zip(extents) {
-                wd.draw(&mut ctx, self.active_screen, screen_has_focus, w, self.h)?;
+                wd.draw(&mut ctx, self.active_screen, screen_has_focus, w, h)?;
                 x += w;
                 ctx.flush();
                 ctx.set_x_offset(x as i32);
@@ -165,10 +221,41 @@ impl<X: XConn> StatusBar<X> {
         Ok(())
     }
 
+    /// Restyle this status bar and all of its widgets using the given [Theme].
+    ///
+    /// The bar will need to be redrawn for the change to become visible.
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.bg = theme.bg;
+        self.draw.set_bg(theme.bg);
+        for w in self.widgets.iter_mut() {
+            w.on_theme_change(theme);
+        }
+    }
+
+    // Pick up any change to the theme set by penrose::extensions::hooks::theme
+    fn sync_theme(&mut self, state: &State<X>) -> bool {
+        let at = match state.extension::<ActiveTheme>() {
+            Ok(at) => at,
+            Err(_) => return false,
+        };
+        let at = at.borrow();
+
+        match (at.mode(), at.theme()) {
+            (Some(mode), Some(theme)) if self.theme_mode != Some(mode) => {
+                info!(?mode, "applying theme to status bar");
+                self.theme_mode = Some(mode);
+                self.set_theme(theme);
+                true
+            }
+
+            _ => false,
+        }
+    }
+
     fn redraw_if_needed(&mut self) -> Result<()> {
         if self.widgets.iter().any(|w| w.require_draw()) {
             self.redraw()?;
-            for (id, _) in self.screens.iter() {
+            for (_, id, _, _) in self.screens.iter() {
                 self.draw.flush(*id)?;
             }
         }
@@ -182,7 +269,7 @@ pub fn startup_hook<X: XConn + 'static>(state: &mut State<X>, x: &X) -> penrose:
     let s = state.extension::<StatusBar<X>>()?;
     let mut bar = s.borrow_mut();
 
-    if let Err(e) = bar.init_for_screens() {
+    if let Err(e) = bar.init_for_screens(state) {
         error!(%e, "unabled to initialise for screens");
         return Err(penrose::Error::NoScreens);
     }
@@ -207,6 +294,16 @@ pub fn refresh_hook<X: XConn + 'static>(state: &mut State<X>, x: &X) -> penrose:
     let mut bar = s.borrow_mut();
 
     bar.active_screen = state.client_set.current_screen().index();
+    let theme_changed = bar.sync_theme(state);
+
+    let target = bar.target_screen(state);
+    let moved = target.is_some() && bar.screens.first().map(|s| s.0) != target;
+    if moved {
+        info!(?target, "target screen has changed: moving status bar");
+        if let Err(e) = bar.reinit_for_screens(state) {
+            error!(%e, "unable to move status bar");
+        }
+    }
 
     for w in bar.widgets.iter_mut() {
         if let Err(e) = w.on_refresh(state, x) {
@@ -214,7 +311,13 @@ pub fn refresh_hook<X: XConn + 'static>(state: &mut State<X>, x: &X) -> penrose:
         }
     }
 
-    if let Err(e) = bar.redraw_if_needed() {
+    let res = if theme_changed || moved {
+        bar.redraw()
+    } else {
+        bar.redraw_if_needed()
+    };
+
+    if let Err(e) = res {
         error!(%e, "error redrawing status bar");
     }
 
@@ -227,28 +330,9 @@ pub fn event_hook<X: XConn + 'static>(
     state: &mut State<X>,
     x: &X,
 ) -> penrose::Result<bool> {
-    use XEvent::{ConfigureNotify, RandrNotify};
-
     let s = state.extension::<StatusBar<X>>()?;
     let mut bar = s.borrow_mut();
 
-    if matches!(event, RandrNotify) || matches!(event, ConfigureNotify(e) if e.is_root) {
-        info!("screens have changed: recreating status bars");
-        let screens: Vec<_> = bar.screens.drain(0..).collect();
-
-        for (id, _) in screens {
-            info!(%id, "removing previous status bar");
-            if let Err(e) = bar.draw.destroy_window_and_surface(id) {
-                error!(%e, "error when removing previous status bar state");
-            }
-        }
-
-        if let Err(e) = bar.init_for_screens() {
-            error!(%e, "unabled to initialise for screens");
-            return Err(penrose::Error::NoScreens);
-        }
-    }
-
     bar.active_screen = state.client_set.current_screen().index();
 
     for w in bar.widgets.iter_mut() {
@@ -285,3 +369,25 @@ pub fn manage_hook<X: XConn + 'static>(
 
     Ok(())
 }
+
+/// Recreate the status bar windows for the new set of screens and then redraw
+pub fn screens_changed_hook<X: XConn + 'static>(
+    state: &mut State<X>,
+    _: &X,
+) -> penrose::Result<()> {
+    let s = state.extension::<StatusBar<X>>()?;
+    let mut bar = s.borrow_mut();
+
+    info!("screens have changed: recreating status bars");
+    if let Err(e) = bar.reinit_for_screens(state) {
+        error!(%e, "unabled to initialise for screens");
+        return Err(penrose::Error::NoScreens);
+    }
+
+    bar.active_screen = state.client_set.current_screen().index();
+    if let Err(e) = bar.redraw() {
+        error!(%e, "error redrawing status bar");
+    }
+
+    Ok(())
+}
@@ -2,7 +2,10 @@
 use crate::bar::widgets::{Context, Result, Text, TextStyle, Widget};
 use penrose::{
     core::State,
-    extensions::util::debug::{summarise_state, CurrentStateConfig},
+    extensions::{
+        hooks::theme::Theme,
+        util::debug::{summarise_state, CurrentStateConfig},
+    },
     x::XConn,
 };
 
@@ -47,6 +50,10 @@ impl<X: XConn> Widget<X> for ActiveWindowId {
 
         Ok(())
     }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
 }
 
 /// A text widget that shows a summary of the current Window Manager state.
@@ -93,4 +100,8 @@ impl<X: XConn> Widget<X> for StateSummary {
 
         Ok(())
     }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
 }
@@ -2,13 +2,20 @@
 use crate::{Context, Result, TextStyle};
 use penrose::{
     core::State,
+    extensions::{
+        hooks::{power_profiles::PowerSourceHook, theme::Theme},
+        util::power::PowerSource,
+    },
     pure::geometry::Rect,
     x::{XConn, XEvent},
     Color, Xid,
 };
 use std::{
     fmt,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     thread,
     time::Duration,
 };
@@ -19,9 +26,11 @@ mod simple;
 mod sys;
 mod workspaces;
 
-pub use simple::{ActiveWindowName, CurrentLayout, RootWindowName};
+pub use simple::{
+    ActiveBindingMode, ActiveWindowName, CurrentLayout, KeyChordHints, RootWindowName,
+};
 pub use sys::{amixer_volume, battery_summary, current_date_and_time, wifi_network};
-pub use workspaces::Workspaces;
+pub use workspaces::{GridPager, Workspaces};
 
 /// A status bar widget that can be rendered using a [Context]
 pub trait Widget<X>
@@ -72,6 +81,11 @@ where
     fn on_new_client(&mut self, id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
         Ok(())
     }
+
+    #[allow(unused_variables)]
+    /// Update the colors used by this Widget when the active [Theme] changes. Widgets that
+    /// restyle themselves should also mark themselves as requiring a redraw.
+    fn on_theme_change(&mut self, theme: &Theme) {}
 }
 
 /// A simple piece of static text with an optional background color.
@@ -129,6 +143,15 @@ impl Text {
             self.require_draw = true;
         }
     }
+
+    /// Restyle this widget using the colors from the given [Theme] and trigger a redraw.
+    ///
+    /// The background color is only updated if this widget has one set.
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.fg = theme.fg;
+        self.bg = self.bg.map(|_| theme.bg);
+        self.require_draw = true;
+    }
 }
 
 impl<X: XConn> Widget<X> for Text {
@@ -174,6 +197,10 @@ impl<X: XConn> Widget<X> for Text {
     fn is_greedy(&self) -> bool {
         self.is_greedy
     }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.set_theme(theme);
+    }
 }
 
 /// A simple widget that does not care about window manager state.
@@ -267,6 +294,108 @@ impl<X: XConn> Widget<X> for RefreshText {
 
         Ok(())
     }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
+}
+
+/// A text widget that renders the most recently published value of type `T`.
+///
+/// Extensions publish values using [State::publish] and this widget formats the latest one
+/// using the provided function each time the window manager state refreshes or an event is
+/// processed. This gives any extension state (such as the current key binding mode or the
+/// number of minimized clients) a presence in the status bar without needing to write a
+/// dedicated [Widget] implementation. The widget is empty until a value has been published.
+///
+/// # Example
+/// ```no_run
+/// use penrose_ui::{bar::widgets::StateText, core::TextStyle};
+///
+/// // Published by an extension using `state.publish(DoNotDisturb(true))`
+/// struct DoNotDisturb(bool);
+///
+/// let style = TextStyle {
+///     fg: 0xebdbb2ff.into(),
+///     bg: Some(0x282828ff.into()),
+///     padding: (2, 2),
+/// };
+///
+/// let my_widget = StateText::new(style, |dnd: &DoNotDisturb| {
+///     if dnd.0 { "DND".to_string() } else { String::new() }
+/// });
+/// ```
+pub struct StateText<T> {
+    inner: Text,
+    format: Box<dyn Fn(&T) -> String>,
+}
+
+impl<T> fmt::Debug for StateText<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateText")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<T: 'static> StateText<T> {
+    /// Construct a new [`StateText`] using the specified styling and a function for
+    /// formatting published values.
+    pub fn new<F>(style: TextStyle, format: F) -> Self
+    where
+        F: Fn(&T) -> String + 'static,
+    {
+        Self {
+            inner: Text::new("", style, false, false),
+            format: Box::new(format),
+        }
+    }
+
+    fn update<X: XConn>(&mut self, state: &State<X>) {
+        let txt = state.published::<T>().map(|v| (self.format)(v));
+
+        self.inner.set_text(txt.unwrap_or_default());
+    }
+}
+
+impl<X: XConn, T: 'static> Widget<X> for StateText<T> {
+    fn draw(&mut self, ctx: &mut Context<'_>, s: usize, f: bool, w: u32, h: u32) -> Result<()> {
+        Widget::<X>::draw(&mut self.inner, ctx, s, f, w, h)
+    }
+
+    fn current_extent(&mut self, ctx: &mut Context<'_>, h: u32) -> Result<(u32, u32)> {
+        Widget::<X>::current_extent(&mut self.inner, ctx, h)
+    }
+
+    fn is_greedy(&self) -> bool {
+        Widget::<X>::is_greedy(&self.inner)
+    }
+
+    fn require_draw(&self) -> bool {
+        Widget::<X>::require_draw(&self.inner)
+    }
+
+    fn on_startup(&mut self, state: &mut State<X>, _: &X) -> Result<()> {
+        self.update(state);
+
+        Ok(())
+    }
+
+    fn on_event(&mut self, _: &XEvent, state: &mut State<X>, _: &X) -> Result<()> {
+        self.update(state);
+
+        Ok(())
+    }
+
+    fn on_refresh(&mut self, state: &mut State<X>, _: &X) -> Result<()> {
+        self.update(state);
+
+        Ok(())
+    }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
 }
 
 /// A simple widget that does not care about window manager state and refreshes on a
@@ -317,12 +446,16 @@ impl IntervalText {
     /// Construct a new [`IntervalText`] using the specified styling and a function for
     /// generating the widget contents. The function for updating the widget contents
     /// will be run in its own thread on the interval provided.
-    pub fn new<F>(style: TextStyle, get_text: F, interval: Duration) -> Self
+    ///
+    /// Passing a [`PollInterval`] rather than a [`Duration`] allows for the interval to be
+    /// changed while the widget is running.
+    pub fn new<F>(style: TextStyle, get_text: F, interval: impl Into<PollInterval>) -> Self
     where
         F: Fn() -> String + 'static + Send,
     {
         let inner = Arc::new(Mutex::new(Text::new("", style, false, false)));
         let txt = Arc::clone(&inner);
+        let interval = interval.into();
 
         thread::spawn(move || loop {
             trace!("updating text for IntervalText widget");
@@ -336,13 +469,77 @@ impl IntervalText {
                 t.set_text(s);
             }
 
-            thread::sleep(interval);
+            thread::sleep(interval.get());
         });
 
         Self { inner }
     }
 }
 
+/// A shared handle on the update interval of one or more [`IntervalText`] widgets.
+///
+/// Changes to the interval take effect once the current wait between updates has elapsed.
+///
+/// # Example
+/// ```no_run
+/// use penrose::{core::WindowManager, extensions::hooks::on_power_source_change, x11rb::RustConn};
+/// use penrose_ui::bar::widgets::PollInterval;
+/// use std::time::Duration;
+///
+/// // Poll every 5 seconds on mains power and every minute on battery
+/// # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+/// let interval = PollInterval::new(Duration::from_secs(5));
+/// on_power_source_change(
+///     wm,
+///     interval.power_source_hook(Duration::from_secs(5), Duration::from_secs(60)),
+/// )
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PollInterval(Arc<AtomicU64>);
+
+impl PollInterval {
+    /// Create a new interval with the given initial value.
+    pub fn new(interval: Duration) -> Self {
+        Self(Arc::new(AtomicU64::new(interval.as_millis() as u64)))
+    }
+
+    /// The current value of this interval.
+    pub fn get(&self) -> Duration {
+        Duration::from_millis(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Set a new value for this interval.
+    pub fn set(&self, interval: Duration) {
+        self.0.store(interval.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// A [`PowerSourceHook`] that sets this interval to `ac` when running on mains power and
+    /// `battery` when running on battery.
+    pub fn power_source_hook<X: XConn>(
+        &self,
+        ac: Duration,
+        battery: Duration,
+    ) -> impl PowerSourceHook<X> {
+        let interval = self.clone();
+
+        move |source: PowerSource, _: &mut State<X>, _: &X| {
+            interval.set(match source {
+                PowerSource::Ac => ac,
+                PowerSource::Battery => battery,
+            });
+
+            Ok(())
+        }
+    }
+}
+
+impl From<Duration> for PollInterval {
+    fn from(interval: Duration) -> Self {
+        Self::new(interval)
+    }
+}
+
 impl<X: XConn> Widget<X> for IntervalText {
     fn draw(&mut self, ctx: &mut Context<'_>, s: usize, f: bool, w: u32, h: u32) -> Result<()> {
         let mut inner = match self.inner.lock() {
@@ -379,4 +576,12 @@ impl<X: XConn> Widget<X> for IntervalText {
 
         Widget::<X>::require_draw(&*inner)
     }
+    fn on_theme_change(&mut self, theme: &Theme) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        inner.set_theme(theme);
+    }
 }
@@ -5,7 +5,8 @@ use crate::{
     Result,
 };
 use penrose::{
-    core::State,
+    core::{bindings::KeyChordHint, State},
+    extensions::{hooks::theme::Theme, util::title::client_title},
     pure::geometry::Rect,
     x::{event::PropertyEvent, Atom, XConn, XConnExt, XEvent},
 };
@@ -57,9 +58,18 @@ impl<X: XConn> Widget<X> for RootWindowName {
 
         Ok(())
     }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
 }
 
 /// A text widget that shows the name of the currently focused window
+///
+/// Nothing is shown while the focused window has `_NET_WM_STATE_SKIP_TASKBAR` set. Titles are
+/// formatted using the [TitleFormatter][0] registered with the window manager if there is one.
+///
+///   [0]: penrose::extensions::util::title::TitleFormatter
 #[derive(Clone, Debug, PartialEq)]
 pub struct ActiveWindowName {
     inner: Text,
@@ -109,10 +119,9 @@ impl<X: XConn> Widget<X> for ActiveWindowName {
     }
 
     fn on_refresh(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
-        if let Some(id) = state.client_set.current_client() {
-            self.set_text(&x.window_title(*id)?)
-        } else {
-            self.set_text("")
+        match state.client_set.current_client() {
+            Some(&id) if !x.skips_taskbar(id) => self.set_text(&client_title(id, state, x)),
+            _ => self.set_text(""),
         }
 
         Ok(())
@@ -124,9 +133,11 @@ impl<X: XConn> Widget<X> for ActiveWindowName {
         if let Some(focused) = state.client_set.current_client() {
             match event {
                 XEvent::PropertyNotify(PropertyEvent { id, atom, .. })
-                    if id == focused && name_props.contains(&atom.as_ref()) =>
+                    if id == focused
+                        && name_props.contains(&atom.as_ref())
+                        && !x.skips_taskbar(*id) =>
                 {
-                    self.inner.set_text(x.window_title(*id)?)
+                    self.set_text(&client_title(*id, state, x))
                 }
 
                 _ => (),
@@ -135,6 +146,10 @@ impl<X: XConn> Widget<X> for ActiveWindowName {
 
         Ok(())
     }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
 }
 
 /// A text widget that shows the current layout name
@@ -175,4 +190,111 @@ impl<X: XConn> Widget<X> for CurrentLayout {
 
         Ok(())
     }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
+}
+
+/// A text widget that shows the follow-up keys of the currently active [KeyChord][0]
+///
+/// Nothing is shown unless a key chord is waiting for a key.
+///
+///   [0]: penrose::core::bindings::KeyChord
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyChordHints {
+    inner: Text,
+}
+
+impl KeyChordHints {
+    /// Create a new KeyChordHints widget
+    pub fn new(style: TextStyle, is_greedy: bool, right_justified: bool) -> Self {
+        Self {
+            inner: Text::new("", style, is_greedy, right_justified),
+        }
+    }
+}
+
+impl<X: XConn> Widget<X> for KeyChordHints {
+    fn draw(&mut self, ctx: &mut Context<'_>, s: usize, f: bool, w: u32, h: u32) -> Result<()> {
+        Widget::<X>::draw(&mut self.inner, ctx, s, f, w, h)
+    }
+
+    fn current_extent(&mut self, ctx: &mut Context<'_>, h: u32) -> Result<(u32, u32)> {
+        Widget::<X>::current_extent(&mut self.inner, ctx, h)
+    }
+
+    fn is_greedy(&self) -> bool {
+        Widget::<X>::is_greedy(&self.inner)
+    }
+
+    fn require_draw(&self) -> bool {
+        Widget::<X>::require_draw(&self.inner)
+    }
+
+    fn on_refresh(&mut self, state: &mut State<X>, _: &X) -> Result<()> {
+        let txt = match state.published::<KeyChordHint>() {
+            Some(KeyChordHint {
+                name: Some(name),
+                keys,
+            }) => format!("{name}: {}", keys.join(" ")),
+            Some(KeyChordHint { name: None, keys }) => keys.join(" "),
+            None => String::new(),
+        };
+
+        self.inner.set_text(txt);
+
+        Ok(())
+    }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
+}
+
+/// A text widget that shows the name of the active [BindingMode][0]
+///
+/// Nothing is shown unless a binding mode is active.
+///
+///   [0]: penrose::core::bindings::BindingMode
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveBindingMode {
+    inner: Text,
+}
+
+impl ActiveBindingMode {
+    /// Create a new ActiveBindingMode widget
+    pub fn new(style: TextStyle, is_greedy: bool, right_justified: bool) -> Self {
+        Self {
+            inner: Text::new("", style, is_greedy, right_justified),
+        }
+    }
+}
+
+impl<X: XConn> Widget<X> for ActiveBindingMode {
+    fn draw(&mut self, ctx: &mut Context<'_>, s: usize, f: bool, w: u32, h: u32) -> Result<()> {
+        Widget::<X>::draw(&mut self.inner, ctx, s, f, w, h)
+    }
+
+    fn current_extent(&mut self, ctx: &mut Context<'_>, h: u32) -> Result<(u32, u32)> {
+        Widget::<X>::current_extent(&mut self.inner, ctx, h)
+    }
+
+    fn is_greedy(&self) -> bool {
+        Widget::<X>::is_greedy(&self.inner)
+    }
+
+    fn require_draw(&self) -> bool {
+        Widget::<X>::require_draw(&self.inner)
+    }
+
+    fn on_refresh(&mut self, state: &mut State<X>, _: &X) -> Result<()> {
+        self.inner.set_text(state.active_mode().unwrap_or_default());
+
+        Ok(())
+    }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.inner.set_theme(theme);
+    }
 }
@@ -1,6 +1,9 @@
 //! System monitor widgets
 use crate::bar::widgets::{RefreshText, TextStyle};
-use penrose::util::{spawn_for_output, spawn_for_output_with_args};
+use penrose::{
+    extensions::util::power::BatteryStatus,
+    util::{spawn_for_output, spawn_for_output_with_args},
+};
 use std::fs;
 
 /// Display the current charge level and status of a named battery.
@@ -12,11 +15,7 @@ pub fn battery_summary(bat: &'static str, style: TextStyle) -> RefreshText {
 }
 
 fn battery_text(bat: &str) -> Option<String> {
-    let status = read_sys_file(bat, "status")?;
-    let energy_now: u32 = read_sys_file(bat, "energy_now")?.parse().ok()?;
-    let energy_full: u32 = read_sys_file(bat, "energy_full")?.parse().ok()?;
-
-    let charge = energy_now * 100 / energy_full;
+    let BatteryStatus { status, charge } = BatteryStatus::read(bat)?;
 
     let icon = if status == "Charging" {
         ""
@@ -35,12 +34,6 @@ fn battery_text(bat: &str) -> Option<String> {
     Some(format!("{icon} {charge}%"))
 }
 
-fn read_sys_file(bat: &str, fname: &str) -> Option<String> {
-    fs::read_to_string(format!("/sys/class/power_supply/{bat}/{fname}"))
-        .ok()
-        .map(|s| s.trim().to_string())
-}
-
 /// Display the current date and time in YYYY-MM-DD HH:MM format
 ///
 /// This widget shells out to the `date` tool to generate its output
@@ -6,26 +6,38 @@ use crate::{
 };
 use penrose::{
     core::{ClientSpace, State},
+    extensions::{
+        actions::WorkspaceGrid,
+        hooks::{client_quotas::CrowdedWorkspaces, theme::Theme},
+    },
     pure::geometry::Rect,
     x::XConn,
     Color,
 };
 
 const PADDING: u32 = 3;
+const CROWDED_FG: u32 = 0xfb4934ff;
 
 #[derive(Clone, Debug, PartialEq)]
 struct WsMeta {
     tag: String,
     occupied: bool,
+    crowded: bool,
     extent: (u32, u32),
 }
 
 impl WsMeta {
     fn from_state<X: XConn>(state: &State<X>) -> Vec<Self> {
+        let crowded = state.published::<CrowdedWorkspaces>();
+
         state
             .client_set
             .ordered_workspaces()
-            .map(WsMeta::from)
+            .map(|w| {
+                let mut meta = WsMeta::from(w);
+                meta.crowded = crowded.is_some_and(|c| c.contains(&meta.tag));
+                meta
+            })
             .collect()
     }
 }
@@ -35,6 +47,7 @@ impl From<&ClientSpace> for WsMeta {
         Self {
             tag: w.tag().to_owned(),
             occupied: !w.is_empty(),
+            crowded: false,
             extent: (0, 0),
         }
     }
@@ -53,6 +66,10 @@ fn focused_workspaces<X: XConn>(state: &State<X>) -> Vec<String> {
 }
 
 /// A simple workspace indicator for a status bar
+///
+/// Workspaces that are over their client quota (see the `client_quotas` hook in
+/// `penrose::extensions::hooks`) have their tag drawn using a warning color which can be set
+/// using [Workspaces::with_crowded_color].
 #[derive(Clone, Debug, PartialEq)]
 pub struct Workspaces {
     workspaces: Vec<WsMeta>,
@@ -62,6 +79,7 @@ pub struct Workspaces {
     fg_2: Color,
     bg_1: Color,
     bg_2: Color,
+    crowded_fg: Color,
     require_draw: bool,
 }
 
@@ -76,10 +94,17 @@ impl Workspaces {
             fg_2: empty_fg.into(),
             bg_1: highlight.into(),
             bg_2: style.bg.unwrap_or_else(|| 0x000000.into()),
+            crowded_fg: CROWDED_FG.into(),
             require_draw: true,
         }
     }
 
+    /// Set the color used for the tags of workspaces that are over their client quota.
+    pub fn with_crowded_color(mut self, color: impl Into<Color>) -> Self {
+        self.crowded_fg = color.into();
+        self
+    }
+
     fn tags(&self) -> Vec<&str> {
         self.workspaces.iter().map(|w| w.tag.as_ref()).collect()
     }
@@ -106,7 +131,7 @@ impl Workspaces {
     fn tags_changed(&self, workspaces: &[WsMeta]) -> bool {
         let new_tags: Vec<&str> = workspaces.iter().map(|w| w.tag.as_ref()).collect();
 
-        self.tags() == new_tags
+        self.tags() != new_tags
     }
 
     // Called after tags_changed above so we assume that tags are matching
@@ -114,7 +139,7 @@ impl Workspaces {
         self.workspaces
             .iter()
             .zip(workspaces)
-            .any(|(l, r)| l.occupied != r.occupied)
+            .any(|(l, r)| l.occupied != r.occupied || l.crowded != r.crowded)
     }
 
     fn ws_colors(
@@ -123,6 +148,7 @@ impl Workspaces {
         screen: usize,
         screen_has_focus: bool,
         occupied: bool,
+        crowded: bool,
     ) -> (Color, Color) {
         let focused_on_this_screen = match &self.focused_ws.get(screen) {
             &Some(focused_tag) => tag == focused_tag,
@@ -132,7 +158,7 @@ impl Workspaces {
         let focused = self.focused_ws.iter().any(|t| t == tag);
         let focused_other = focused && !focused_on_this_screen;
 
-        if focused_on_this_screen && screen_has_focus {
+        let (fg, bg) = if focused_on_this_screen && screen_has_focus {
             let fg = if occupied { self.fg_1 } else { self.fg_2 };
 
             (fg, self.bg_1)
@@ -144,6 +170,13 @@ impl Workspaces {
             let fg = if occupied { self.fg_1 } else { self.fg_2 };
 
             (fg, self.bg_2)
+        };
+
+        // Workspaces focused on another screen keep their outline color
+        if crowded && !focused_other {
+            (self.crowded_fg, bg)
+        } else {
+            (fg, bg)
         }
     }
 }
@@ -162,7 +195,8 @@ impl<X: XConn> Widget<X> for Workspaces {
         let (_, eh) = <Self as Widget<X>>::current_extent(self, ctx, h)?;
 
         for ws in self.workspaces.iter() {
-            let (fg, bg) = self.ws_colors(&ws.tag, screen, screen_has_focus, ws.occupied);
+            let (fg, bg) =
+                self.ws_colors(&ws.tag, screen, screen_has_focus, ws.occupied, ws.crowded);
             ctx.fill_rect(Rect::new(0, 0, ws.extent.0, h), bg)?;
             ctx.draw_text(&ws.tag, h - eh, (PADDING, PADDING), fg)?;
             ctx.translate(ws.extent.0 as i32, 0);
@@ -213,4 +247,170 @@ impl<X: XConn> Widget<X> for Workspaces {
 
         Ok(())
     }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.fg_1 = theme.fg;
+        self.fg_2 = theme.dim;
+        self.bg_1 = theme.highlight;
+        self.bg_2 = theme.bg;
+        self.require_draw = true;
+    }
+}
+
+/// A pager showing the workspaces of a [WorkspaceGrid] as a grid of cells.
+///
+/// The workspace focused on the current screen is highlighted, workspaces visible on other
+/// screens are outlined and workspaces containing clients are filled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridPager {
+    grid: WorkspaceGrid,
+    tags: Vec<String>,
+    occupied: Vec<bool>,
+    focused_ws: Vec<String>, // focused ws per screen
+    highlight: Color,
+    occupied_fg: Color,
+    empty_fg: Color,
+    bg: Color,
+    require_draw: bool,
+}
+
+impl GridPager {
+    /// Construct a new GridPager for the given grid.
+    pub fn new(
+        grid: WorkspaceGrid,
+        style: TextStyle,
+        highlight: impl Into<Color>,
+        empty_fg: impl Into<Color>,
+    ) -> Self {
+        Self {
+            grid,
+            tags: vec![],
+            occupied: vec![],
+            focused_ws: vec![], // set in startup hook
+            highlight: highlight.into(),
+            occupied_fg: style.fg,
+            empty_fg: empty_fg.into(),
+            bg: style.bg.unwrap_or_else(|| 0x000000.into()),
+            require_draw: true,
+        }
+    }
+
+    fn update_from_state<X: XConn>(&mut self, state: &State<X>) {
+        let tags = state.client_set.ordered_tags();
+        let occupied: Vec<bool> = tags
+            .iter()
+            .map(|t| {
+                state
+                    .client_set
+                    .workspace(t)
+                    .map(|w| !w.is_empty())
+                    .unwrap_or(false)
+            })
+            .collect();
+        let focused_ws = focused_workspaces(state);
+
+        if tags != self.tags || occupied != self.occupied || focused_ws != self.focused_ws {
+            self.require_draw = true;
+        }
+
+        self.tags = tags;
+        self.occupied = occupied;
+        self.focused_ws = focused_ws;
+    }
+
+    // The (width, height) of a single cell in the grid for a bar of the given height
+    fn cell_size(&self, h: u32) -> (u32, u32) {
+        let rows = self.grid.rows() as u32;
+        let ch = (h.saturating_sub(2 * PADDING) / rows).max(2);
+
+        (ch * 3 / 2, ch)
+    }
+}
+
+impl<X: XConn> Widget<X> for GridPager {
+    fn draw(
+        &mut self,
+        ctx: &mut Context<'_>,
+        screen: usize,
+        _screen_has_focus: bool,
+        w: u32,
+        h: u32,
+    ) -> Result<()> {
+        ctx.fill_rect(Rect::new(0, 0, w, h), self.bg)?;
+        let (cw, ch) = self.cell_size(h);
+        let y_offset = h.saturating_sub(ch * self.grid.rows() as u32) / 2;
+
+        for row in 0..self.grid.rows() {
+            for col in 0..self.grid.cols() {
+                let tag = match self.grid.tag_at(&self.tags, row, col) {
+                    Some(tag) => tag,
+                    None => continue,
+                };
+
+                let occupied = self
+                    .tags
+                    .iter()
+                    .position(|t| t == tag)
+                    .and_then(|ix| self.occupied.get(ix).copied())
+                    .unwrap_or(false);
+                let r = Rect::new(
+                    PADDING + col as u32 * cw,
+                    y_offset + row as u32 * ch,
+                    cw - 1,
+                    ch - 1,
+                );
+
+                let focused = self.focused_ws.get(screen).map(|t| t.as_str()) == Some(tag);
+                let visible = self.focused_ws.iter().any(|t| t == tag);
+
+                if focused {
+                    ctx.fill_rect(r, self.highlight)?;
+                } else if occupied {
+                    ctx.fill_rect(r, self.occupied_fg)?;
+                } else if visible {
+                    ctx.draw_rect(r, self.highlight)?;
+                } else {
+                    ctx.draw_rect(r, self.empty_fg)?;
+                }
+            }
+        }
+
+        self.require_draw = false;
+
+        Ok(())
+    }
+
+    fn current_extent(&mut self, _ctx: &mut Context<'_>, h: u32) -> Result<(u32, u32)> {
+        let (cw, _) = self.cell_size(h);
+
+        Ok((cw * self.grid.cols() as u32 + 2 * PADDING, h))
+    }
+
+    fn is_greedy(&self) -> bool {
+        false
+    }
+
+    fn require_draw(&self) -> bool {
+        self.require_draw
+    }
+
+    fn on_startup(&mut self, state: &mut State<X>, _: &X) -> Result<()> {
+        self.update_from_state(state);
+
+        Ok(())
+    }
+
+    fn on_refresh(&mut self, state: &mut State<X>, _: &X) -> Result<()> {
+        self.update_from_state(state);
+
+        Ok(())
+    }
+
+    fn on_theme_change(&mut self, theme: &Theme) {
+        self.highlight = theme.highlight;
+        self.occupied_fg = theme.fg;
+        self.empty_fg = theme.dim;
+        self.bg = theme.bg;
+        self.require_draw = true;
+    }
 }
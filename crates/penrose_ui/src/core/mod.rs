@@ -214,6 +214,11 @@ impl Draw {
         Ok(())
     }
 
+    /// Set the background color used when clearing surfaces.
+    pub fn set_bg(&mut self, bg: impl Into<Color>) {
+        self.bg = bg.into();
+    }
+
     /// Retrieve the drawing [Context] for the given window `Xid`.
     ///
     /// This method will error if the requested id does not already have an initialised surface.
@@ -11,6 +11,7 @@ use penrose::{
         bindings::{parse_keybindings_with_xmodmap, KeyEventHandler},
         Config, WindowManager,
     },
+    extensions::util::check::{check_requested, ConfigCheck},
     map,
     x11rb::RustConn,
     Result,
@@ -63,9 +64,19 @@ fn main() -> Result<()> {
         .finish()
         .init();
 
+    let config = Config::default();
+    let raw_bindings = raw_key_bindings();
+
+    if check_requested() {
+        ConfigCheck::new(&config)
+            .key_bindings(raw_bindings.keys())
+            .report()
+            .exit();
+    }
+
     let conn = RustConn::new()?;
-    let key_bindings = parse_keybindings_with_xmodmap(raw_key_bindings())?;
-    let wm = WindowManager::new(Config::default(), key_bindings, HashMap::new(), conn)?;
+    let key_bindings = parse_keybindings_with_xmodmap(raw_bindings)?;
+    let wm = WindowManager::new(config, key_bindings, HashMap::new(), conn)?;
 
     wm.run()
 }
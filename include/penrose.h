@@ -0,0 +1,38 @@
+/* C bindings for the pure tiling engine provided by penrose (see src/ffi.rs).
+ *
+ * Build penrose with the `ffi` feature as a static or dynamic library in order to link
+ * against these functions.
+ */
+#ifndef PENROSE_H
+#define PENROSE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef struct PenroseEngine PenroseEngine;
+
+typedef struct PenroseRect {
+    uint32_t x;
+    uint32_t y;
+    uint32_t w;
+    uint32_t h;
+} PenroseRect;
+
+typedef struct PenrosePosition {
+    uint32_t id;
+    PenroseRect rect;
+} PenrosePosition;
+
+PenroseEngine *penrose_engine_new(size_t n_workspaces, const PenroseRect *screens, size_t n_screens);
+void penrose_engine_free(PenroseEngine *engine);
+
+int32_t penrose_engine_add_client(PenroseEngine *engine, uint32_t id);
+int32_t penrose_engine_remove_client(PenroseEngine *engine, uint32_t id);
+int32_t penrose_engine_focus_client(PenroseEngine *engine, uint32_t id);
+int32_t penrose_engine_focus_workspace(PenroseEngine *engine, size_t index);
+uint32_t penrose_engine_focused_client(PenroseEngine *engine);
+int32_t penrose_engine_next_layout(PenroseEngine *engine);
+
+size_t penrose_engine_layout(PenroseEngine *engine, PenroseRect r, PenrosePosition *out, size_t capacity);
+
+#endif /* PENROSE_H */
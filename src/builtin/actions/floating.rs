@@ -1,15 +1,45 @@
 //! Actions for manipulating floating windows.
 use crate::{
     builtin::actions::{key_handler, modify_with},
-    core::bindings::KeyEventHandler,
-    x::{XConn, XConnExt},
+    core::{
+        bindings::{
+            KeyEventHandler, ModifierKey, MouseBindings, MouseButton, MouseEvent,
+            MouseEventHandler, MouseEventKind, MouseState,
+        },
+        ClientSet, State,
+    },
+    pure::geometry::{Point, Rect},
+    x::{
+        atom::Atom,
+        property::{Prop, WmNormalHints},
+        XConn, XConnExt,
+    },
+    Result, Xid,
 };
+use std::collections::HashMap;
 use tracing::error;
 
 /// Resize a currently floating window by a given (width, height) delta
 ///
-/// Screen coordinates are 0-indexed from the top left corner of the sceen.
+/// Screen coordinates are 0-indexed from the top left corner of the sceen. If the window
+/// has specified aspect ratio limits in its size hints then these are respected (see
+/// [resize_unconstrained] for ignoring them).
 pub fn resize<X: XConn>(dw: i32, dh: i32) -> Box<dyn KeyEventHandler<X>> {
+    resize_floating(dw, dh, None, true)
+}
+
+/// Resize a currently floating window by a given (width, height) delta, ignoring any aspect
+/// ratio limits specified in its size hints.
+///
+/// Screen coordinates are 0-indexed from the top left corner of the sceen.
+pub fn resize_unconstrained<X: XConn>(dw: i32, dh: i32) -> Box<dyn KeyEventHandler<X>> {
+    resize_floating(dw, dh, None, false)
+}
+
+/// Move a currently floating window by a given (x, y) delta
+///
+/// Screen coordinates are 0-indexed from the top left corner of the sceen.
+pub fn reposition<X: XConn>(dx: i32, dy: i32) -> Box<dyn KeyEventHandler<X>> {
     modify_with(move |cs| {
         let id = match cs.current_client() {
             Some(&id) => id,
@@ -18,32 +48,651 @@ pub fn resize<X: XConn>(dw: i32, dh: i32) -> Box<dyn KeyEventHandler<X>> {
 
         cs.floating.entry(id).and_modify(|r| {
             *r = r.apply_as_rect(&cs.screens.focus.r, |mut r| {
-                r.resize(dw, dh);
+                r.reposition(dx, dy);
                 r
             });
         });
     })
 }
 
-/// Move a currently floating window by a given (x, y) delta
+/// Move a currently floating window by a given (x, y) delta, snapping its edges to the edges
+/// of the screen, any bars and other visible floating windows that are within `threshold`
+/// pixels of its new position.
 ///
-/// Screen coordinates are 0-indexed from the top left corner of the sceen.
-pub fn reposition<X: XConn>(dx: i32, dy: i32) -> Box<dyn KeyEventHandler<X>> {
+/// Only the axes being moved along are snapped, and only in the direction of motion so that
+/// windows are able to move away from an edge they are already snapped to.
+pub fn reposition_snapping<X: XConn>(
+    dx: i32,
+    dy: i32,
+    threshold: u32,
+) -> Box<dyn KeyEventHandler<X>> {
     modify_with(move |cs| {
         let id = match cs.current_client() {
             Some(&id) => id,
             None => return,
         };
 
+        let (xs, ys) = snap_targets(cs, id);
+
         cs.floating.entry(id).and_modify(|r| {
             *r = r.apply_as_rect(&cs.screens.focus.r, |mut r| {
                 r.reposition(dx, dy);
+                let dx_snap = snap_delta_towards(r.x, r.x + r.w, &xs, threshold, dx);
+                let dy_snap = snap_delta_towards(r.y, r.y + r.h, &ys, threshold, dy);
+                r.reposition(dx_snap, dy_snap);
                 r
             });
         });
     })
 }
 
+/// Resize a currently floating window by a given (width, height) delta, snapping its right and
+/// bottom edges to the edges of the screen, any bars and other visible floating windows that
+/// are within `threshold` pixels of its new size.
+///
+/// Only the dimensions being resized are snapped, and only in the direction of the resize. As
+/// with [resize], any aspect ratio limits
+/// specified in the size hints of the window are respected.
+pub fn resize_snapping<X: XConn>(dw: i32, dh: i32, threshold: u32) -> Box<dyn KeyEventHandler<X>> {
+    resize_floating(dw, dh, Some(threshold), true)
+}
+
+fn resize_floating<X: XConn>(
+    dw: i32,
+    dh: i32,
+    threshold: Option<u32>,
+    respect_aspect: bool,
+) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state, x: &X| {
+        let id = match state.client_set.current_client() {
+            Some(&id) if state.client_set.floating.contains_key(&id) => id,
+            _ => return Ok(()),
+        };
+
+        let hints = match x.get_prop(id, Atom::WmNormalHints.as_ref()) {
+            Ok(Some(Prop::WmNormalHints(hints))) if respect_aspect => Some(hints),
+            _ => None,
+        };
+
+        x.modify_and_refresh(state, |cs| {
+            let (xs, ys) = snap_targets(cs, id);
+
+            cs.floating.entry(id).and_modify(|r| {
+                *r = r.apply_as_rect(&cs.screens.focus.r, |mut r| {
+                    r.resize(dw, dh);
+
+                    if let Some(threshold) = threshold {
+                        let right = r.x + r.w;
+                        let bottom = r.y + r.h;
+                        let dw_snap = snap_delta_towards(right, right, &xs, threshold, dw);
+                        let dh_snap = snap_delta_towards(bottom, bottom, &ys, threshold, dh);
+                        r.resize(dw_snap, dh_snap);
+                    }
+
+                    match &hints {
+                        Some(h) => h.apply_aspect_ratio(r, dw != 0),
+                        None => r,
+                    }
+                });
+            });
+        })
+    })
+}
+
+// The x and y coordinates of all edges that the given floating client should snap to: the edges
+// of the current screen along with the edges of any bars and other floating clients on it.
+fn snap_targets(cs: &ClientSet, id: Xid) -> (Vec<u32>, Vec<u32>) {
+    let screen = &cs.screens.focus;
+    let mut rects = vec![screen.r];
+
+    rects.extend(
+        cs.bars
+            .iter()
+            .filter(|(c, _)| cs.screen_for_client(c).map(|s| s.index) == Some(screen.index))
+            .map(|(_, rr)| rr.applied_to(&screen.r)),
+    );
+
+    rects.extend(
+        screen
+            .workspace
+            .clients()
+            .filter(|&&c| c != id)
+            .flat_map(|c| cs.floating.get(c))
+            .map(|rr| rr.applied_to(&screen.r)),
+    );
+
+    let xs = rects.iter().flat_map(|r| [r.x, r.x + r.w]).collect();
+    let ys = rects.iter().flat_map(|r| [r.y, r.y + r.h]).collect();
+
+    (xs, ys)
+}
+
+// The offset required to move the closest of `lo` or `hi` onto the nearest target within
+// `threshold` pixels, or None if there are no targets in range.
+fn snap_delta(lo: u32, hi: u32, targets: &[u32], threshold: u32) -> Option<i32> {
+    targets
+        .iter()
+        .flat_map(|&t| [t as i32 - lo as i32, t as i32 - hi as i32])
+        .filter(|d| d.unsigned_abs() <= threshold)
+        .min_by_key(|d| d.unsigned_abs())
+}
+
+// As for [snap_delta] but only snapping in the direction of motion given by the sign of
+// `motion`, so that edges that were already aligned with a target before moving are not pulled
+// straight back to it. Returns 0 if there is nothing to snap to.
+fn snap_delta_towards(lo: u32, hi: u32, targets: &[u32], threshold: u32, motion: i32) -> i32 {
+    targets
+        .iter()
+        .flat_map(|&t| [t as i32 - lo as i32, t as i32 - hi as i32])
+        .filter(|d| d.unsigned_abs() <= threshold && d.signum() == motion.signum())
+        .min_by_key(|d| d.unsigned_abs())
+        .unwrap_or(0)
+}
+
+/// How floating windows should snap while they are being dragged or resized with the mouse
+/// using a [MouseDragHandler] or [MouseResizeHandler].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MouseSnapping {
+    /// Window edges within this many pixels of the edges of the screen, a bar or another
+    /// floating window are snapped to them.
+    pub threshold: u32,
+    /// If set, window edges that are not in range of another edge are snapped to a grid of
+    /// this many pixels, aligned with the top left corner of the screen.
+    pub grid: Option<u32>,
+    /// If set, holding this modifier key while dragging temporarily disables snapping.
+    pub disable_modifier: Option<ModifierKey>,
+}
+
+impl Default for MouseSnapping {
+    fn default() -> Self {
+        Self {
+            threshold: 10,
+            grid: None,
+            disable_modifier: None,
+        }
+    }
+}
+
+impl MouseSnapping {
+    /// Disable snapping entirely.
+    pub fn disabled() -> Self {
+        Self {
+            threshold: 0,
+            grid: None,
+            disable_modifier: None,
+        }
+    }
+
+    fn is_enabled(&self, state: &MouseState) -> bool {
+        let held = |m| state.modifiers.contains(m);
+
+        (self.threshold > 0 || self.grid.is_some())
+            && !self.disable_modifier.as_ref().is_some_and(held)
+    }
+
+    // The offset required to snap `lo` or `hi` to the nearest target, falling back to snapping
+    // `lo` to the grid (relative to `origin`) if there are no targets in range.
+    fn offset(&self, lo: u32, hi: u32, targets: &[u32], origin: u32) -> i32 {
+        if let Some(d) = snap_delta(lo, hi, targets, self.threshold) {
+            return d;
+        }
+
+        match self.grid {
+            Some(g) if g > 0 => {
+                let rel = lo as i32 - origin as i32;
+                let snapped = (rel as f64 / g as f64).round() as i32 * g as i32;
+
+                snapped - rel
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// How a window being resized using a [MouseResizeHandler] is updated as the mouse moves.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeFeedback {
+    /// Resize the window itself on every motion event
+    #[default]
+    Live,
+    /// Draw an outline of the new size of the window on every motion event, only resizing
+    /// the window itself once the mouse button is released.
+    ///
+    /// This avoids the lag seen when live resizing clients that are slow to redraw (such as web
+    /// browsers). The outline uses the focused border color and border width from the
+    /// [Config][crate::core::Config].
+    Outline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DragKind {
+    Move,
+    Resize(Edges),
+}
+
+// The edges of a window that follow the pointer while it is being resized. Edges on opposite
+// sides of the window should not both be set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Edges {
+    pub(crate) left: bool,
+    pub(crate) top: bool,
+    pub(crate) right: bool,
+    pub(crate) bottom: bool,
+}
+
+impl Edges {
+    pub(crate) const BOTTOM_RIGHT: Self = Self {
+        left: false,
+        top: false,
+        right: true,
+        bottom: true,
+    };
+
+    // Move the selected edges of `r` by (dx, dy), leaving the opposite edges in place.
+    fn apply(&self, mut r: Rect, dx: i32, dy: i32) -> Rect {
+        let (right, bottom) = (r.x + r.w, r.y + r.h);
+
+        if self.left {
+            let x = (r.x as i32 + dx).clamp(0, right as i32 - 1) as u32;
+            r = Rect::new(x, r.y, right - x, r.h);
+        } else if self.right {
+            r.resize(dx, 0);
+        }
+
+        if self.top {
+            let y = (r.y as i32 + dy).clamp(0, bottom as i32 - 1) as u32;
+            r = Rect::new(r.x, y, r.w, bottom - y);
+        } else if self.bottom {
+            r.resize(0, dy);
+        }
+
+        r
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Drag {
+    id: Xid,
+    kind: DragKind,
+    feedback: ResizeFeedback,
+    start: Point,
+    initial: Rect,
+    preview: Option<Rect>,
+    // Size hints of the client being resized so that aspect ratio limits are respected
+    hints: Option<WmNormalHints>,
+    // Drags requested by the client itself are driven by an active pointer grab rather than
+    // by mouse bindings.
+    from_client: bool,
+}
+
+// State extension tracking the drag (if any) currently in progress so that the handlers bound
+// to press, motion and release events are able to coordinate.
+#[derive(Debug, Default)]
+struct ActiveDrag(Option<Drag>);
+
+/// Whether or not a client is currently being moved or resized using the mouse.
+///
+/// The pointer is not warped while a drag is in progress as the dragged client will be
+/// following the pointer.
+pub(crate) fn drag_in_progress<X: XConn>(state: &State<X>) -> bool {
+    state
+        .extension::<ActiveDrag>()
+        .is_ok_and(|active| active.borrow().0.is_some())
+}
+
+/// A [MouseEventHandler] for moving windows by clicking and dragging them.
+///
+/// Pressing the bound mouse button over a window floats it in its current position, subsequent
+/// motion events move it (snapping its edges as specified by the [MouseSnapping] in use) and
+/// releasing the button ends the drag. This handler needs to be bound to the press, motion and
+/// release events for the mouse state being used: see [drag_and_resize_bindings].
+#[derive(Debug, Clone, Default)]
+pub struct MouseDragHandler {
+    snapping: MouseSnapping,
+}
+
+impl MouseDragHandler {
+    /// Create a new boxed [MouseDragHandler] ready for adding to your mouse bindings.
+    pub fn boxed<X: XConn>(snapping: MouseSnapping) -> Box<dyn MouseEventHandler<X>> {
+        Box::new(Self { snapping })
+    }
+}
+
+impl<X: XConn> MouseEventHandler<X> for MouseDragHandler {
+    fn call(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        let kind = DragKind::Move;
+        handle_drag(kind, ResizeFeedback::Live, &self.snapping, evt, state, x)
+    }
+}
+
+/// A [MouseEventHandler] for resizing windows by clicking and dragging them.
+///
+/// Pressing the bound mouse button over a window floats it in its current position, subsequent
+/// motion events move its bottom right corner (snapping its edges as specified by the
+/// [MouseSnapping] in use) and releasing the button ends the resize. This handler needs to be
+/// bound to the press, motion and release events for the mouse state being used: see
+/// [drag_and_resize_bindings]. As with [resize], any aspect ratio limits specified in the size
+/// hints of the window are respected.
+///
+/// By default the window is resized as the mouse moves: see [ResizeFeedback] for only
+/// resizing the window once the button is released.
+#[derive(Debug, Clone, Default)]
+pub struct MouseResizeHandler {
+    snapping: MouseSnapping,
+    feedback: ResizeFeedback,
+}
+
+impl MouseResizeHandler {
+    /// Create a new boxed [MouseResizeHandler] ready for adding to your mouse bindings.
+    pub fn boxed<X: XConn>(snapping: MouseSnapping) -> Box<dyn MouseEventHandler<X>> {
+        Self::boxed_with_feedback(snapping, ResizeFeedback::Live)
+    }
+
+    /// Create a new boxed [MouseResizeHandler] using the given [ResizeFeedback] ready for
+    /// adding to your mouse bindings.
+    pub fn boxed_with_feedback<X: XConn>(
+        snapping: MouseSnapping,
+        feedback: ResizeFeedback,
+    ) -> Box<dyn MouseEventHandler<X>> {
+        Box::new(Self { snapping, feedback })
+    }
+}
+
+impl<X: XConn> MouseEventHandler<X> for MouseResizeHandler {
+    fn call(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        let kind = DragKind::Resize(Edges::BOTTOM_RIGHT);
+        handle_drag(kind, self.feedback, &self.snapping, evt, state, x)
+    }
+}
+
+fn handle_drag<X: XConn>(
+    kind: DragKind,
+    feedback: ResizeFeedback,
+    snapping: &MouseSnapping,
+    evt: &MouseEvent,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let active = state.extension_or_default::<ActiveDrag>();
+
+    match evt.kind {
+        MouseEventKind::Press | MouseEventKind::DoubleClick => {
+            // Positions are ordered bottom to top so we want the last match
+            let positions = state.visible_client_positions(x);
+            let id = match positions
+                .iter()
+                .rev()
+                .find(|(_, r)| r.contains_point(evt.rpt))
+            {
+                Some(&(id, _)) => id,
+                None => return Ok(()),
+            };
+
+            start_drag(id, kind, feedback, evt.rpt, false, state, x)
+        }
+
+        MouseEventKind::Motion => {
+            // The drag itself determines whether we are moving or resizing so that a single
+            // motion binding is able to service both.
+            let drag = match &active.borrow().0 {
+                Some(drag) => drag.clone(),
+                None => return Ok(()),
+            };
+
+            let dx = evt.rpt.x as i32 - drag.start.x as i32;
+            let dy = evt.rpt.y as i32 - drag.start.y as i32;
+            let snap = snapping.is_enabled(&evt.state);
+            let r = dragged_rect(&state.client_set, &drag, dx, dy, snap.then_some(snapping));
+
+            if drag.feedback == ResizeFeedback::Outline {
+                let width = state.config.border_width.max(1);
+                x.show_outline(r, width, state.config.focused_border)?;
+                if let Some(d) = active.borrow_mut().0.as_mut() {
+                    d.preview = Some(r);
+                }
+
+                return Ok(());
+            }
+
+            x.modify_and_refresh(state, |cs| {
+                if let Err(err) = cs.float(drag.id, r) {
+                    error!(%err, id = %drag.id, "unable to move dragged client window");
+                }
+            })
+        }
+
+        // When previewing with an outline, the real resize only happens once the button is
+        // released. The drag is only cleared after the final refresh so that the pointer is
+        // left where it is.
+        MouseEventKind::Release => {
+            let preview = match &active.borrow().0 {
+                Some(drag) => drag.preview.map(|r| (drag.id, r)),
+                None => None,
+            };
+
+            if let Some((id, r)) = preview {
+                x.hide_outline()?;
+                x.modify_and_refresh(state, |cs| {
+                    if let Err(err) = cs.float(id, r) {
+                        error!(%err, %id, "unable to resize dragged client window");
+                    }
+                })?;
+            }
+
+            active.borrow_mut().0 = None;
+
+            Ok(())
+        }
+    }
+}
+
+fn start_drag<X: XConn>(
+    id: Xid,
+    kind: DragKind,
+    feedback: ResizeFeedback,
+    start: Point,
+    from_client: bool,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let initial = x.client_geometry(id)?;
+    let hints = match kind {
+        DragKind::Resize(_) => match x.get_prop(id, Atom::WmNormalHints.as_ref()) {
+            Ok(Some(Prop::WmNormalHints(hints))) => Some(hints),
+            _ => None,
+        },
+        DragKind::Move => None,
+    };
+
+    state.extension_or_default::<ActiveDrag>().borrow_mut().0 = Some(Drag {
+        id,
+        kind,
+        feedback,
+        start,
+        initial,
+        preview: None,
+        hints,
+        from_client,
+    });
+
+    x.modify_and_refresh(state, |cs| {
+        cs.focus_client(&id);
+        if let Err(err) = cs.float(id, initial) {
+            error!(%err, %id, "unable to float dragged client window");
+        }
+    })
+}
+
+/// Start moving or resizing a managed client on its own behalf (i.e. in response to a
+/// `_NET_WM_MOVERESIZE` client message) with the pointer at `start`.
+///
+/// The pointer is grabbed until the next button release so that the drag can be serviced by
+/// [handle_client_drag] without the need for any mouse bindings.
+pub(crate) fn start_client_drag<X: XConn>(
+    id: Xid,
+    kind: DragKind,
+    start: Point,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    if !state.client_set.contains(&id) {
+        return Ok(());
+    }
+
+    x.grab_pointer()?;
+    start_drag(id, kind, ResizeFeedback::Live, start, true, state, x)
+}
+
+/// Update the drag started by [start_client_drag] (if there is one) in response to a mouse
+/// event, returning whether or not the event was consumed.
+pub(crate) fn handle_client_drag<X: XConn>(
+    evt: &MouseEvent,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<bool> {
+    let active = state.extension_or_default::<ActiveDrag>();
+    let kind = match &active.borrow().0 {
+        Some(drag) if drag.from_client => drag.kind,
+        _ => return Ok(false),
+    };
+
+    match evt.kind {
+        // Presses of other buttons while the pointer is grabbed are ignored
+        MouseEventKind::Press | MouseEventKind::DoubleClick => (),
+        MouseEventKind::Motion => {
+            let snapping = MouseSnapping::default();
+            handle_drag(kind, ResizeFeedback::Live, &snapping, evt, state, x)?;
+        }
+        MouseEventKind::Release => cancel_client_drag(state, x)?,
+    }
+
+    Ok(true)
+}
+
+/// End the drag started by [start_client_drag] (if there is one), leaving the client where it
+/// currently is and releasing the pointer.
+pub(crate) fn cancel_client_drag<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let active = state.extension_or_default::<ActiveDrag>();
+    let is_client_drag = matches!(&active.borrow().0, Some(drag) if drag.from_client);
+    if is_client_drag {
+        active.borrow_mut().0 = None;
+        x.ungrab_pointer()?;
+    }
+
+    Ok(())
+}
+
+// The new position of a dragged client after the pointer has moved by (dx, dy) since the drag
+// began, snapped to nearby edges if requested. Resized clients are kept within the aspect ratio
+// limits in their size hints, with the edges opposite those being dragged held in place.
+fn dragged_rect(
+    cs: &ClientSet,
+    drag: &Drag,
+    dx: i32,
+    dy: i32,
+    snapping: Option<&MouseSnapping>,
+) -> Rect {
+    let mut r = drag.initial;
+    let (ox, oy) = (cs.screens.focus.r.x, cs.screens.focus.r.y);
+
+    match drag.kind {
+        DragKind::Move => {
+            r.reposition(dx, dy);
+            if let Some(s) = snapping {
+                let (xs, ys) = snap_targets(cs, drag.id);
+                let dx_snap = s.offset(r.x, r.x + r.w, &xs, ox);
+                let dy_snap = s.offset(r.y, r.y + r.h, &ys, oy);
+                r.reposition(dx_snap, dy_snap);
+            }
+        }
+
+        DragKind::Resize(edges) => {
+            r = edges.apply(r, dx, dy);
+            if let Some(s) = snapping {
+                let (xs, ys) = snap_targets(cs, drag.id);
+                let (right, bottom) = (r.x + r.w, r.y + r.h);
+                let x_edge = if edges.left { r.x } else { right };
+                let y_edge = if edges.top { r.y } else { bottom };
+                let dx_snap = s.offset(x_edge, x_edge, &xs, ox);
+                let dy_snap = s.offset(y_edge, y_edge, &ys, oy);
+                r = edges.apply(r, dx_snap, dy_snap);
+            }
+
+            if let Some(hints) = &drag.hints {
+                let (right, bottom) = (r.x + r.w, r.y + r.h);
+                let horizontal = edges.left || edges.right;
+                let vertical = edges.top || edges.bottom;
+                let keep_width = horizontal && (!vertical || dx.abs() >= dy.abs());
+
+                r = hints.apply_aspect_ratio(r, keep_width);
+                if edges.left {
+                    r.x = right.saturating_sub(r.w);
+                }
+                if edges.top {
+                    r.y = bottom.saturating_sub(r.h);
+                }
+            }
+        }
+    }
+
+    r
+}
+
+/// Mouse bindings for moving windows by dragging them with the left mouse button and resizing
+/// them by dragging with the right mouse button while holding `modifiers`.
+///
+/// If `snapping` specifies a modifier key for disabling snapping then bindings are also added
+/// for dragging with that key held in addition to `modifiers`.
+pub fn drag_and_resize_bindings<X: XConn>(
+    modifiers: Vec<ModifierKey>,
+    snapping: MouseSnapping,
+) -> MouseBindings<X> {
+    drag_and_resize_bindings_with_feedback(modifiers, snapping, ResizeFeedback::Live)
+}
+
+/// The same as [drag_and_resize_bindings] but using the given [ResizeFeedback] when resizing.
+pub fn drag_and_resize_bindings_with_feedback<X: XConn>(
+    modifiers: Vec<ModifierKey>,
+    snapping: MouseSnapping,
+    feedback: ResizeFeedback,
+) -> MouseBindings<X> {
+    let mut mod_sets = vec![modifiers.clone()];
+    if let Some(m) = snapping.disable_modifier {
+        if !modifiers.contains(&m) {
+            let mut mods = modifiers;
+            mods.push(m);
+            mod_sets.push(mods);
+        }
+    }
+
+    let mut bindings: MouseBindings<X> = HashMap::new();
+
+    for mods in mod_sets {
+        let left = MouseState::new(MouseButton::Left, mods.clone());
+        let right = MouseState::new(MouseButton::Right, mods.clone());
+        // Motion events are always reported as ScrollDown: see the x11rb event conversions
+        let motion = MouseState::new(MouseButton::ScrollDown, mods);
+
+        for kind in [MouseEventKind::Press, MouseEventKind::Release] {
+            bindings.insert(
+                (kind, left.clone()),
+                MouseDragHandler::boxed(snapping.clone()),
+            );
+            bindings.insert(
+                (kind, right.clone()),
+                MouseResizeHandler::boxed_with_feedback(snapping.clone(), feedback),
+            );
+        }
+
+        bindings.insert(
+            (MouseEventKind::Motion, motion),
+            MouseDragHandler::boxed(snapping.clone()),
+        );
+    }
+
+    bindings
+}
+
 /// Move the currently focused windo to the floating layer in its current on screen position
 pub fn float_focused<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     key_handler(|state, x: &X| {
@@ -93,3 +742,278 @@ pub fn float_all<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
 pub fn sink_all<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     modify_with(|cs| cs.floating.clear())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{Config, WindowManager},
+        pure::test_xid_stack_set,
+        testing::{HeadlessConn, Scenario},
+        x::{StubXConn, XEvent},
+    };
+    use simple_test_case::test_case;
+
+    #[test_case(50, 200, None; "no targets in range")]
+    #[test_case(100, 200, Some(0); "already aligned")]
+    #[test_case(95, 200, Some(5); "low edge snaps forward")]
+    #[test_case(105, 200, Some(-5); "low edge snaps back")]
+    #[test_case(0, 297, Some(3); "high edge snaps")]
+    #[test_case(96, 297, Some(3); "closest edge wins")]
+    #[test]
+    fn snap_delta_finds_nearest_target(lo: u32, hi: u32, expected: Option<i32>) {
+        assert_eq!(snap_delta(lo, hi, &[100, 300], 5), expected);
+    }
+
+    #[test_case(95, 200, 5, 5; "snaps in direction of motion")]
+    #[test_case(105, 200, 5, 0; "does not snap back against motion")]
+    #[test_case(105, 200, -5, -5; "snaps back when moving back")]
+    #[test_case(95, 200, 0, 0; "no motion")]
+    #[test]
+    fn snap_delta_towards_only_snaps_with_motion(lo: u32, hi: u32, motion: i32, expected: i32) {
+        assert_eq!(snap_delta_towards(lo, hi, &[100, 300], 5, motion), expected);
+    }
+
+    #[test]
+    fn windows_can_be_nudged_away_from_a_snapped_edge() {
+        let bindings = HashMap::from([
+            ("M-l", reposition_snapping(5, 0, 10)),
+            ("M-h", reposition_snapping(-5, 0, 10)),
+        ]);
+        let s = Scenario::with_bindings(Config::default(), bindings)
+            .unwrap()
+            .open_window("a", "a")
+            .act(|state, _| {
+                let id = *state.client_set.current_client().unwrap();
+                state.client_set.float(id, Rect::new(0, 100, 200, 200))
+            });
+
+        let s = s
+            .press("M-l")
+            .expect_geometry("a", Rect::new(5, 100, 196, 196));
+        s.press("M-h")
+            .expect_geometry("a", Rect::new(0, 100, 196, 196));
+    }
+
+    #[test]
+    fn snap_targets_include_screen_and_other_floating_clients() {
+        let mut cs = test_xid_stack_set(1, 1);
+        let r_s = cs.screens.focus.r;
+        for n in 1..=3 {
+            cs.insert(Xid(n));
+        }
+        cs.float_unchecked(Xid(1), Rect::new(10, 20, 30, 40));
+        cs.float_unchecked(Xid(2), Rect::new(50, 60, 70, 80));
+
+        let (xs, ys) = snap_targets(&cs, Xid(1));
+
+        assert_eq!(xs, vec![r_s.x, r_s.x + r_s.w, 50, 120]);
+        assert_eq!(ys, vec![r_s.y, r_s.y + r_s.h, 60, 140]);
+    }
+
+    #[test_case(None, 37, 0; "no grid no snap")]
+    #[test_case(Some(20), 37, 3; "rounds up to grid")]
+    #[test_case(Some(20), 44, -4; "rounds down to grid")]
+    #[test_case(Some(20), 97, 3; "edges take priority over grid")]
+    #[test]
+    fn mouse_snapping_offset(grid: Option<u32>, lo: u32, expected: i32) {
+        let snapping = MouseSnapping {
+            threshold: 5,
+            grid,
+            disable_modifier: None,
+        };
+
+        assert_eq!(snapping.offset(lo, lo + 50, &[100], 0), expected);
+    }
+
+    #[test_case(vec![], true; "no modifiers")]
+    #[test_case(vec![ModifierKey::Meta], true; "bound modifier")]
+    #[test_case(vec![ModifierKey::Meta, ModifierKey::Shift], false; "disable modifier held")]
+    #[test]
+    fn mouse_snapping_disable_modifier(modifiers: Vec<ModifierKey>, expected: bool) {
+        let snapping = MouseSnapping {
+            disable_modifier: Some(ModifierKey::Shift),
+            ..Default::default()
+        };
+        let state = MouseState::new(MouseButton::Left, modifiers);
+
+        assert_eq!(snapping.is_enabled(&state), expected);
+    }
+
+    #[test_case(DragKind::Move, Rect::new(100, 100, 50, 50); "move snaps to floating neighbour")]
+    #[test_case(DragKind::Resize(Edges::BOTTOM_RIGHT), Rect::new(10, 10, 140, 140); "resize snaps to floating neighbour")]
+    #[test]
+    fn dragged_rect_snaps_to_other_windows(kind: DragKind, expected: Rect) {
+        let mut cs = test_xid_stack_set(1, 1);
+        cs.insert(Xid(1));
+        cs.insert(Xid(2));
+        cs.float_unchecked(Xid(2), Rect::new(150, 150, 50, 50));
+
+        let drag = Drag {
+            id: Xid(1),
+            kind,
+            feedback: ResizeFeedback::Live,
+            start: Point::new(0, 0),
+            initial: Rect::new(10, 10, 50, 50),
+            preview: None,
+            hints: None,
+            from_client: false,
+        };
+        let snapping = MouseSnapping::default();
+
+        assert_eq!(dragged_rect(&cs, &drag, 88, 88, Some(&snapping)), expected);
+        assert_eq!(
+            dragged_rect(&cs, &drag, 88, 88, None),
+            match kind {
+                DragKind::Move => Rect::new(98, 98, 50, 50),
+                DragKind::Resize(_) => Rect::new(10, 10, 138, 138),
+            }
+        );
+    }
+
+    #[test_case(Edges::BOTTOM_RIGHT, 50, 10, Rect::new(100, 100, 100, 50); "bottom right keeps top left")]
+    #[test_case(Edges { left: true, top: true, right: false, bottom: false }, -50, -10, Rect::new(50, 75, 100, 50); "top left keeps bottom right")]
+    #[test_case(Edges { left: false, top: false, right: false, bottom: true }, 0, 50, Rect::new(100, 100, 150, 75); "vertical drag keeps height")]
+    #[test]
+    fn dragged_rect_respects_aspect_ratio(edges: Edges, dx: i32, dy: i32, expected: Rect) {
+        let mut cs = test_xid_stack_set(1, 1);
+        cs.insert(Xid(1));
+        let hints = WmNormalHints::new(Default::default(), None, None, None, None)
+            .with_aspect_ratios(Some((2, 1)), Some((2, 1)));
+
+        let drag = Drag {
+            id: Xid(1),
+            kind: DragKind::Resize(edges),
+            feedback: ResizeFeedback::Live,
+            start: Point::new(0, 0),
+            initial: Rect::new(100, 100, 50, 25),
+            preview: None,
+            hints: Some(hints),
+            from_client: false,
+        };
+
+        assert_eq!(dragged_rect(&cs, &drag, dx, dy, None), expected);
+    }
+
+    #[test_case((false, false, false, false), 20, 30, Rect::new(100, 100, 50, 50); "no edges")]
+    #[test_case((false, false, true, true), -20, -30, Rect::new(100, 100, 30, 20); "bottom right")]
+    #[test_case((true, false, false, true), 20, 30, Rect::new(120, 100, 30, 80); "bottom left")]
+    #[test_case((true, true, false, false), 20, 30, Rect::new(120, 130, 30, 20); "top left shrinks")]
+    #[test_case((true, true, false, false), -20, -30, Rect::new(80, 70, 70, 80); "top left grows")]
+    #[test_case((true, true, false, false), 80, 80, Rect::new(149, 149, 1, 1); "top left clamps to opposite edge")]
+    #[test_case((true, true, false, false), -200, -200, Rect::new(0, 0, 150, 150); "top left clamps to origin")]
+    #[test]
+    fn edges_apply(edges: (bool, bool, bool, bool), dx: i32, dy: i32, expected: Rect) {
+        let (left, top, right, bottom) = edges;
+        let edges = Edges {
+            left,
+            top,
+            right,
+            bottom,
+        };
+
+        assert_eq!(edges.apply(Rect::new(100, 100, 50, 50), dx, dy), expected);
+    }
+
+    fn mouse_event(
+        id: Xid,
+        initial: Rect,
+        button: MouseButton,
+        kind: MouseEventKind,
+        offset: u32,
+    ) -> XEvent {
+        let state = MouseState::new(button, vec![ModifierKey::Meta]);
+        let (x, y) = ((initial.x + offset) as i16, (initial.y + offset) as i16);
+
+        XEvent::MouseEvent(MouseEvent::new(id, x, y, 10, 10, state, kind))
+    }
+
+    #[test_case(MouseButton::Left; "moving")]
+    #[test_case(MouseButton::Right; "resizing")]
+    #[test]
+    fn dragging_does_not_warp_the_pointer(button: MouseButton) {
+        let mouse_bindings =
+            drag_and_resize_bindings(vec![ModifierKey::Meta], MouseSnapping::disabled());
+        let conn = HeadlessConn::default();
+        let wm = WindowManager::new(Config::default(), HashMap::new(), mouse_bindings, conn)
+            .expect("valid window manager");
+
+        let s = Scenario::new(wm).unwrap().open_window("a", "a");
+        let id = s.id("a");
+        let initial = s.conn().window(id).unwrap().geometry;
+        let pointer = s.conn().cursor_position().unwrap();
+
+        let s = s.send(mouse_event(id, initial, button, MouseEventKind::Press, 100));
+        let s = s.send(mouse_event(
+            id,
+            initial,
+            MouseButton::ScrollDown,
+            MouseEventKind::Motion,
+            50,
+        ));
+        let s = s.send(mouse_event(
+            id,
+            initial,
+            button,
+            MouseEventKind::Release,
+            50,
+        ));
+
+        assert_ne!(s.conn().window(id).unwrap().geometry, initial);
+        assert_eq!(s.conn().cursor_position().unwrap(), pointer);
+    }
+
+    #[test]
+    fn outline_resize_only_resizes_on_release() {
+        let mouse_bindings = drag_and_resize_bindings_with_feedback(
+            vec![ModifierKey::Meta],
+            MouseSnapping::disabled(),
+            ResizeFeedback::Outline,
+        );
+        let conn = HeadlessConn::default();
+        let wm = WindowManager::new(Config::default(), HashMap::new(), mouse_bindings, conn)
+            .expect("valid window manager");
+
+        let s = Scenario::new(wm).unwrap().open_window("a", "a");
+        let id = s.id("a");
+        let initial = s.conn().window(id).unwrap().geometry;
+        let mouse_event = |button, kind, offset| mouse_event(id, initial, button, kind, offset);
+
+        let s = s.send(mouse_event(MouseButton::Right, MouseEventKind::Press, 100));
+        let r_s = s.state().client_set.screens.focus.r;
+        let floating = |s: &Scenario| s.state().client_set.floating[&id].applied_to(&r_s);
+        let (before, geometry) = (floating(&s), s.conn().window(id).unwrap().geometry);
+
+        let s = s.send(mouse_event(
+            MouseButton::ScrollDown,
+            MouseEventKind::Motion,
+            50,
+        ));
+        let mut expected = before;
+        expected.resize(-50, -50);
+
+        assert_eq!(s.conn().outline(), Some(expected));
+        assert_eq!(floating(&s), before);
+        assert_eq!(s.conn().window(id).unwrap().geometry, geometry);
+
+        let s = s.send(mouse_event(MouseButton::Right, MouseEventKind::Release, 50));
+
+        assert_eq!(s.conn().outline(), None);
+        assert_eq!(floating(&s), expected);
+    }
+
+    #[test]
+    fn disable_modifier_adds_additional_bindings() {
+        let snapping = MouseSnapping {
+            disable_modifier: Some(ModifierKey::Shift),
+            ..Default::default()
+        };
+
+        let plain =
+            drag_and_resize_bindings::<StubXConn>(vec![ModifierKey::Meta], Default::default());
+        let with_disable = drag_and_resize_bindings::<StubXConn>(vec![ModifierKey::Meta], snapping);
+
+        assert_eq!(plain.len(), 5);
+        assert_eq!(with_disable.len(), 10);
+    }
+}
@@ -1,13 +1,19 @@
 //! Helpers and pre-defined actions for use in user defined key bindings
 use crate::{
-    core::{bindings::KeyEventHandler, layout::IntoMessage, ClientSet, State},
-    util,
-    x::{XConn, XConnExt},
-    Result,
+    core::{bindings::KeyEventHandler, layout::IntoMessage, request_restart, ClientSet, State},
+    pure::{
+        geometry::{Direction, RelativeRect},
+        Stack,
+    },
+    util::{self, CommandOutput},
+    x::{PointerWarp, XConn, XConnExt},
+    Result, Xid,
 };
+use std::collections::HashMap;
 use tracing::info;
 
 pub mod floating;
+pub mod workspaces;
 
 // NOTE: this is here to force the correct lifetime requirements on closures being
 //       used as handlers. The generic impl in crate::bindings for functions of the
@@ -31,6 +37,152 @@ where
     Box::new(move |s: &mut State<X>, x: &X| x.modify_and_refresh(s, f.clone()))
 }
 
+/// The largest count prefix that can be entered using [count_digit].
+pub const MAX_COUNT: usize = 999;
+
+/// Mutate the [ClientSet] and refresh the on screen state, repeating the modification for
+/// the number of times given by the current count prefix (see [State::count]).
+///
+/// The modification is run once if there is no count prefix and repeating stops early once
+/// the modification no longer changes the arrangement of clients.
+pub fn modify_with_count<F, X>(mut f: F) -> Box<dyn KeyEventHandler<X>>
+where
+    F: FnMut(&mut ClientSet) + 'static,
+    X: XConn,
+{
+    Box::new(move |s: &mut State<X>, x: &X| {
+        let n = s.count().unwrap_or(1);
+        x.modify_and_refresh(s, |cs| {
+            for _ in 0..n {
+                let before = arrangement(cs);
+                f(cs);
+                if arrangement(cs) == before {
+                    break;
+                }
+            }
+        })
+    })
+}
+
+// The placement and focus of each client, used to detect when repeating a modification is no
+// longer having any effect.
+fn arrangement(cs: &ClientSet) -> (usize, Vec<Option<Stack<Xid>>>, HashMap<Xid, RelativeRect>) {
+    let stacks = cs.workspaces().map(|w| w.stack.clone()).collect();
+
+    (cs.screens.focus.index, stacks, cs.floating.clone())
+}
+
+/// Add a digit to the count prefix that is passed to the next key binding that is run (see
+/// [State::count]).
+///
+/// Entering digits one after another builds up multi-digit counts in the same way as in vi,
+/// so binding `M-A-1` to `count_digit(1)` and `M-A-2` to `count_digit(2)` allows for pressing
+/// `M-A-1 M-A-2 M-j` to run the `M-j` binding with a count of 12. Counts are capped at
+/// [MAX_COUNT].
+pub fn count_digit<X: XConn>(digit: u8) -> Box<dyn KeyEventHandler<X>> {
+    let digit = digit.min(9) as usize;
+
+    key_handler(move |s: &mut State<X>, _: &X| {
+        let count = s.count().unwrap_or(0);
+        s.bindings.pending_count = Some((count * 10 + digit).min(MAX_COUNT));
+
+        Ok(())
+    })
+}
+
+/// Run an external command in the background, passing its output and exit status to `f` once
+/// it exits (see [State::spawn_and_capture]).
+pub fn spawn_and_capture<S, X, F>(cmd: S, f: F) -> Box<dyn KeyEventHandler<X>>
+where
+    S: Into<String>,
+    X: XConn,
+    F: FnMut(CommandOutput, &mut State<X>, &X) -> Result<()> + Clone + 'static,
+{
+    let cmd = cmd.into();
+
+    key_handler(move |s: &mut State<X>, _: &X| {
+        let mut f = f.clone();
+        s.spawn_and_capture(cmd.clone(), None, move |out, s, x| f(out, s, x));
+
+        Ok(())
+    })
+}
+
+/// The same as [spawn_and_capture] but writing `input` to the stdin of the command.
+///
+/// ```no_run
+/// # use penrose::{
+/// #     builtin::actions::spawn_and_capture_with_input,
+/// #     core::State,
+/// #     x::XConnExt,
+/// #     x11rb::RustConn,
+/// # };
+/// // Pick a workspace to focus using dmenu
+/// let handler = spawn_and_capture_with_input(
+///     "dmenu -p workspace:",
+///     "1\n2\n3",
+///     |out, s: &mut State<RustConn>, x: &RustConn| {
+///         if !out.success() {
+///             return Ok(()); // dmenu was cancelled
+///         }
+///         let tag = out.stdout.trim().to_string();
+///         x.modify_and_refresh(s, |cs| cs.focus_tag(&tag))
+///     },
+/// );
+/// ```
+pub fn spawn_and_capture_with_input<S, I, X, F>(
+    cmd: S,
+    input: I,
+    f: F,
+) -> Box<dyn KeyEventHandler<X>>
+where
+    S: Into<String>,
+    I: Into<String>,
+    X: XConn,
+    F: FnMut(CommandOutput, &mut State<X>, &X) -> Result<()> + Clone + 'static,
+{
+    let cmd = cmd.into();
+    let input = input.into();
+
+    key_handler(move |s: &mut State<X>, _: &X| {
+        let mut f = f.clone();
+        s.spawn_and_capture(cmd.clone(), Some(input.clone()), move |out, s, x| {
+            f(out, s, x)
+        });
+
+        Ok(())
+    })
+}
+
+/// Start recording a key macro with the given name, or stop and save the recording if one is
+/// already in progress (see [State::start_recording_macro]).
+///
+/// The key binding used to toggle recording is not itself recorded.
+pub fn toggle_macro_recording<X: XConn>(name: impl Into<String>) -> Box<dyn KeyEventHandler<X>> {
+    let name = name.into();
+
+    key_handler(move |s: &mut State<X>, _: &X| {
+        if s.stop_recording_macro().is_none() {
+            s.start_recording_macro(name.clone());
+        }
+
+        Ok(())
+    })
+}
+
+/// Replay the key macro with the given name, repeating it for the number of times given by
+/// the current count prefix (see [State::count]).
+///
+/// The macro is replayed once if there is no count prefix.
+pub fn replay_macro<X: XConn>(name: impl Into<String>) -> Box<dyn KeyEventHandler<X>> {
+    let name = name.into();
+
+    key_handler(move |s: &mut State<X>, _: &X| {
+        let n = s.count().unwrap_or(1);
+        s.replay_macro(&name, n)
+    })
+}
+
 /// Send a message to the currently active layout
 pub fn send_layout_message<F, M, X>(f: F) -> Box<dyn KeyEventHandler<X>>
 where
@@ -59,7 +211,391 @@ where
     })
 }
 
+/// Run the given action using `warp` in place of [Config::pointer_warp][0] to determine
+/// whether or not the pointer is warped following any focus changes the action makes.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::{with_pointer_warp, modify_with}, x::PointerWarp};
+/// # use penrose::x11rb::RustConn;
+/// // Move focus to the next screen, always taking the pointer with it
+/// let action = with_pointer_warp::<RustConn>(
+///     PointerWarp::Screen,
+///     modify_with(|cs| cs.next_screen()),
+/// );
+/// ```
+///
+///   [0]: crate::core::Config::pointer_warp
+pub fn with_pointer_warp<X: XConn + 'static>(
+    warp: PointerWarp,
+    mut action: Box<dyn KeyEventHandler<X>>,
+) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |s: &mut State<X>, x: &X| {
+        let prev = std::mem::replace(&mut s.config.pointer_warp, warp);
+        let res = action.call(s, x);
+        s.config.pointer_warp = prev;
+
+        res
+    })
+}
+
+/// Warp the pointer to the center of the focused client.
+pub fn warp_pointer_to_focused_client<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, x: &X| {
+        let id = match s.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        x.warp_pointer_to_window(id)
+    })
+}
+
+/// Warp the pointer to the focused screen: the center of its focused client if there is one,
+/// otherwise the center of the screen itself.
+pub fn warp_pointer_to_focused_screen<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, x: &X| {
+        let index = s.client_set.current_screen().index();
+        x.warp_pointer_to_screen(s, index)
+    })
+}
+
+/// Jump to the oldest client that is currently flagged as urgent, focusing its workspace.
+pub fn focus_urgent<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, x: &X| {
+        let id = match s.urgent_clients().first() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        x.modify_and_refresh(s, |cs| cs.focus_client(&id))
+    })
+}
+
+/// Focus the most recently focused client other than the current one, focusing its workspace.
+///
+/// Repeatedly calling this action will bounce focus between the two most recently
+/// focused clients. Clients that have `_NET_WM_STATE_SKIP_TASKBAR` set are skipped.
+pub fn focus_last_client<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, x: &X| {
+        let focused = s.client_set.current_client();
+        let id =
+            match s.focus_history().iter().find(|&id| {
+                Some(id) != focused && s.client_set.contains(id) && !x.skips_taskbar(*id)
+            }) {
+                Some(&id) => id,
+                None => return Ok(()),
+            };
+
+        x.modify_and_refresh(s, |cs| cs.focus_client(&id))
+    })
+}
+
+/// Move focus to the nearest visible client in the given [Direction] from the focused client,
+/// based on the positions of clients from the last layout pass (including floating clients
+/// and clients on other screens).
+///
+/// This is a no-op if there is no client in the requested direction.
+pub fn focus_direction<X: XConn>(d: Direction) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |s: &mut State<X>, x: &X| {
+        let target = client_in_direction(s, d, |_| true);
+        match target {
+            Some((_, id)) => x.modify_and_refresh(s, |cs| cs.focus_client(&id)),
+            None => Ok(()),
+        }
+    })
+}
+
+/// Swap the focused client with the nearest tiled client in the given [Direction], based on
+/// the positions of clients from the last layout pass (including clients on other screens).
+///
+/// The two clients exchange positions in their respective stacks so the swap is preserved
+/// when the layout is next run. This is a no-op if the focused client is floating or there
+/// is no tiled client in the requested direction.
+pub fn swap_direction<X: XConn>(d: Direction) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |s: &mut State<X>, x: &X| {
+        let floating = &s.client_set.floating;
+        match client_in_direction(s, d, |id| !floating.contains_key(id)) {
+            Some((focused, id)) => x.modify_and_refresh(s, |cs| cs.swap_clients(&focused, &id)),
+            None => Ok(()),
+        }
+    })
+}
+
+// The focused client and the nearest visible client in the given direction from it that
+// satisfies `pred`. Returns `None` if the focused client does not satisfy `pred`.
+fn client_in_direction<X, F>(s: &State<X>, d: Direction, pred: F) -> Option<(Xid, Xid)>
+where
+    X: XConn,
+    F: Fn(&Xid) -> bool,
+{
+    let focused = *s.client_set.current_client()?;
+    if !pred(&focused) {
+        return None;
+    }
+
+    let positions = s.client_positions();
+    let &(_, r) = positions.iter().find(|&&(id, _)| id == focused)?;
+
+    positions
+        .iter()
+        .filter(|&(id, _)| *id != focused && pred(id))
+        .flat_map(|&(id, other)| r.distance_in_direction(&other, d).map(|dist| (dist, id)))
+        .min_by_key(|&(dist, _)| dist)
+        .map(|(_, id)| (focused, id))
+}
+
+/// Move focus to the nearest screen in the given [Direction] from the focused screen.
+///
+/// Screens are located using their position as reported by the X server rather than their
+/// index, so bindings using this action work regardless of how your outputs are arranged.
+/// This is a no-op if there is no screen in the requested direction.
+pub fn focus_screen_direction<X: XConn>(d: Direction) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| cs.focus_screen_in_direction(d))
+}
+
+/// Move the focused client to the workspace on the nearest screen in the given [Direction]
+/// from the focused screen, leaving focus on the current screen.
+///
+/// See [focus_screen_direction] for details of how the target screen is selected. This is a
+/// no-op if there is no screen in the requested direction.
+pub fn send_to_screen_direction<X: XConn>(d: Direction) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| {
+        let tag = match cs.screen_in_direction(d).and_then(|i| cs.tag_for_screen(i)) {
+            Some(tag) => tag.to_string(),
+            None => return,
+        };
+
+        cs.move_focused_to_tag(tag);
+    })
+}
+
+/// Move focus to the screen showing the primary output.
+///
+/// The primary output is set using RandR (e.g. `xrandr --output eDP-1 --primary`). If there is
+/// no primary output then focus moves to the screen with index 0.
+pub fn focus_primary<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.focus_primary_screen())
+}
+
+/// Move the focused client to the workspace on the screen showing the primary output, leaving
+/// focus on the current screen.
+///
+/// See [focus_primary] for details of how the primary screen is selected.
+pub fn send_to_primary<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| {
+        let tag = match cs.tag_for_screen(cs.primary_screen()) {
+            Some(tag) => tag.to_string(),
+            None => return,
+        };
+
+        cs.move_focused_to_tag(tag);
+    })
+}
+
+/// Swap the workspaces shown on the screens with the given indices, with focus following the
+/// focused workspace.
+///
+/// This is a no-op if either screen does not exist or when running with per-screen workspaces.
+pub fn swap_screens<X: XConn>(a: usize, b: usize) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| cs.swap_screen_workspaces(a, b))
+}
+
+/// Swap the workspace on the focused screen with the workspace on the nearest screen in the
+/// given [Direction], with focus following the focused workspace onto the other screen.
+///
+/// See [focus_screen_direction] for details of how the target screen is selected. This is a
+/// no-op if there is no screen in the requested direction.
+pub fn swap_screen_direction<X: XConn>(d: Direction) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| {
+        if let Some(index) = cs.screen_in_direction(d) {
+            let focused = cs.current_screen().index();
+            cs.swap_screen_workspaces(focused, index);
+        }
+    })
+}
+
+/// Move the workspace shown on each screen to the next screen, with focus following the
+/// focused workspace.
+pub fn rotate_screens_forward<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.rotate_screen_workspaces_forward())
+}
+
+/// Move the workspace shown on each screen to the previous screen, with focus following the
+/// focused workspace.
+pub fn rotate_screens_backward<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.rotate_screen_workspaces_backward())
+}
+
+/// Swap the focused client with the client in the main position of the current workspace,
+/// leaving focus in the main position.
+///
+/// If the focused client is already in the main position then it is swapped with the next
+/// client in the stack.
+pub fn swap_focused_with_main<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.swap_focused_with_main())
+}
+
+/// Promote the focused client to the main position of the current workspace, leaving focus
+/// in the main position.
+///
+/// If the focused client is already in the main position then it is swapped with the client
+/// that it previously displaced from the main position, allowing this action to toggle between
+/// the two most recently promoted clients (the `zoom` behaviour of dwm). If there is no such
+/// client it is swapped with the next client in the stack.
+pub fn swap_with_main<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    let mut previous: HashMap<String, Xid> = HashMap::new();
+
+    key_handler(move |s: &mut State<X>, x: &X| {
+        x.modify_and_refresh(s, |cs| {
+            let tag = cs.current_tag().to_string();
+            match cs.swap_with_main(previous.get(&tag)) {
+                Some(id) => previous.insert(tag, id),
+                None => previous.remove(&tag),
+            };
+        })
+    })
+}
+
+/// Toggle the gaps applied by any [Gaps][0] transformers on the current workspace.
+///
+///   [0]: crate::builtin::layout::transformers::Gaps
+pub fn toggle_gaps<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.current_workspace_mut().toggle_gaps())
+}
+
+/// Toggle whether or not client borders are drawn on the current workspace.
+pub fn toggle_borders<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.current_workspace_mut().toggle_borders())
+}
+
+/// Hide the focused client from the layout of the current workspace, moving focus to the next
+/// client that is still shown.
+///
+/// See [State::hide_client] for details.
+pub fn hide_focused_client<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, x: &X| {
+        let id = match s.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        s.hide_client(id);
+        let hidden = s.hidden_clients(s.client_set.current_tag()).to_vec();
+        let n = s.client_set.current_workspace().clients().count();
+
+        x.modify_and_refresh(s, |cs| {
+            for _ in 0..n {
+                cs.focus_down();
+                match cs.current_client() {
+                    Some(c) if *c != id && hidden.contains(c) => continue,
+                    _ => break,
+                }
+            }
+        })
+    })
+}
+
+/// Restore all clients on the current workspace that were hidden using [hide_focused_client].
+pub fn show_all_clients<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, x: &X| {
+        let tag = s.client_set.current_tag().to_owned();
+        s.show_all_clients(&tag);
+
+        x.refresh(s)
+    })
+}
+
+/// Toggle whether or not the focused client is skipped when cycling focus.
+///
+/// See [StackSet::toggle_boring][0] for details.
+///
+///   [0]: crate::pure::StackSet::toggle_boring
+pub fn toggle_boring<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| {
+        if let Some(&id) = cs.current_client() {
+            cs.toggle_boring(&id);
+        }
+    })
+}
+
+/// Run the given action without skipping boring clients when cycling focus.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::{including_boring, modify_with}, x11rb::RustConn};
+/// // Move focus to the next client in the stack, even if it is boring
+/// let action = including_boring::<RustConn>(modify_with(|cs| cs.focus_down()));
+/// ```
+pub fn including_boring<X: XConn + 'static>(
+    mut action: Box<dyn KeyEventHandler<X>>,
+) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |s: &mut State<X>, x: &X| {
+        let boring = s.client_set.take_boring();
+        let res = action.call(s, x);
+        s.client_set.restore_boring(boring);
+
+        res
+    })
+}
+
+/// Toggle whether or not focus follows the mouse.
+///
+/// See [Config::focus_follow_mouse][0] for details.
+///
+///   [0]: crate::core::Config::focus_follow_mouse
+pub fn toggle_focus_follow_mouse<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, _: &X| {
+        s.config.focus_follow_mouse = !s.config.focus_follow_mouse;
+        s.focus.pending = None;
+        s.focus.pointer_at_refresh = None;
+        info!(
+            enabled = s.config.focus_follow_mouse,
+            "toggled focus follows mouse"
+        );
+
+        Ok(())
+    })
+}
+
+/// Move focus to the client in the main position of the current workspace.
+pub fn focus_main<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.focus_head())
+}
+
+/// Rotate all clients on the current workspace forward through the layout positions, with
+/// the last client wrapping round to the main position.
+///
+/// Focus remains at the same position in the layout rather than following the client that
+/// was focused.
+pub fn rotate_stack_forward<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.rotate_contents_down())
+}
+
+/// Rotate all clients on the current workspace backward through the layout positions, with
+/// the client in the main position wrapping round to the last position.
+///
+/// Focus remains at the same position in the layout rather than following the client that
+/// was focused.
+pub fn rotate_stack_backward<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.rotate_contents_up())
+}
+
+/// Kill all clients on the current workspace.
+///
+/// If [Config::kill_timeout][0] is set then any clients that are still running once the
+/// timeout has elapsed are forcibly killed.
+///
+///   [0]: crate::core::Config::kill_timeout
+pub fn kill_all_on_workspace<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.kill_all_on_current_workspace())
+}
+
 /// Spawn an external program as part of a key binding
+///
+/// If a [CommandWhitelist][0] has been set in the [Config][1] then programs that it does not
+/// permit are not run.
+///
+///   [0]: crate::core::CommandWhitelist
+///   [1]: crate::core::Config::command_whitelist
 pub fn spawn<X>(program: &'static str) -> Box<dyn KeyEventHandler<X>>
 where
     X: XConn,
@@ -74,6 +610,19 @@ pub fn exit<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     key_handler(|_, _| std::process::exit(0))
 }
 
+/// Restart penrose in place
+///
+/// Re-executes the window manager binary while keeping all clients where they are. See
+/// [request_restart][0] for details.
+///
+///   [0]: crate::core::request_restart
+pub fn restart<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|_, _| {
+        request_restart();
+        Ok(())
+    })
+}
+
 /// Info log the current window manager [State] for debugging purposes.
 pub fn log_current_state<X: XConn + std::fmt::Debug>() -> Box<dyn KeyEventHandler<X>> {
     key_handler(|s: &mut State<X>, _| {
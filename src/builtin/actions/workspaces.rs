@@ -0,0 +1,162 @@
+//! Actions for switching between, creating, removing and renaming workspaces at runtime.
+use crate::{
+    builtin::actions::modify_with,
+    core::{bindings::KeyEventHandler, layout::LayoutStack},
+    x::XConn,
+};
+use tracing::error;
+
+/// Create a new [Workspace][0] with a tag provided by `get_name` and focus it.
+///
+/// If `get_name` returns `None` then no workspace is created. If the tag is already
+/// in use then the existing workspace is focused instead.
+///
+///   [0]: crate::pure::Workspace
+pub fn create_workspace<X>(
+    get_name: fn() -> Option<String>,
+    layouts: LayoutStack,
+) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn,
+{
+    modify_with(move |cs| {
+        if let Some(tag) = get_name() {
+            if let Err(e) = cs.add_workspace(&tag, layouts.clone()) {
+                error!(%e, %tag, "unable to create workspace");
+            }
+            cs.focus_tag(&tag);
+        }
+    })
+}
+
+/// Focus the `n`th (0-indexed) [Workspace][0] available to the current screen.
+///
+/// When running with per-screen workspaces (see [Config::per_screen_workspaces][1]) this only
+/// considers the workspaces owned by the focused screen, so the same binding can be used to
+/// switch between the workspaces of whichever screen has focus. Otherwise this focuses the
+/// `n`th workspace in tag order. If there is no such workspace then this is a no-op.
+///
+///   [0]: crate::pure::Workspace
+///   [1]: crate::core::Config::per_screen_workspaces
+pub fn focus_nth_workspace<X: XConn>(n: usize) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| {
+        if let Some(tag) = cs.tag_for_current_screen(n) {
+            cs.focus_tag(tag);
+        }
+    })
+}
+
+/// Move the focused client to the `n`th (0-indexed) [Workspace][0] available to the current
+/// screen.
+///
+/// Workspaces are selected in the same way as for [focus_nth_workspace].
+///
+///   [0]: crate::pure::Workspace
+pub fn move_focused_to_nth_workspace<X: XConn>(n: usize) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| {
+        if let Some(tag) = cs.tag_for_current_screen(n) {
+            cs.move_focused_to_tag(tag);
+        }
+    })
+}
+
+/// Toggle focus back to the previously focused [Workspace][0].
+///
+///   [0]: crate::pure::Workspace
+pub fn toggle_last_workspace<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| cs.toggle_tag())
+}
+
+/// Remove the currently focused [Workspace][0] if it is empty.
+///
+/// The workspace will be replaced on screen by the first available hidden workspace.
+/// If the workspace still contains clients or there are no other workspaces available
+/// to display then this is a no-op.
+///
+///   [0]: crate::pure::Workspace
+pub fn remove_focused_workspace<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    modify_with(|cs| {
+        let tag = cs.current_tag().to_owned();
+        if let Err(e) = cs.remove_workspace(&tag) {
+            error!(%e, %tag, "unable to remove workspace");
+        }
+    })
+}
+
+/// Rename the currently focused [Workspace][0] using a tag provided by `get_name`.
+///
+/// `get_name` is called with the current tag of the workspace. If it returns `None`
+/// or a tag that is already in use then the workspace is left unchanged.
+///
+///   [0]: crate::pure::Workspace
+pub fn rename_focused_workspace<X>(
+    get_name: fn(&str) -> Option<String>,
+) -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn,
+{
+    modify_with(move |cs| {
+        let tag = cs.current_tag().to_owned();
+        if let Some(new_tag) = get_name(&tag) {
+            if let Err(e) = cs.rename_workspace(&tag, new_tag) {
+                error!(%e, %tag, "unable to rename workspace");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{Config, WindowManager},
+        pure::geometry::Rect,
+        testing::{parse_keybindings, HeadlessConn, Scenario},
+    };
+    use simple_test_case::test_case;
+    use std::collections::HashMap;
+
+    fn per_screen_scenario(per_screen_workspaces: bool) -> Scenario {
+        let config = Config {
+            per_screen_workspaces,
+            ..Config::default()
+        };
+        let bindings = parse_keybindings(HashMap::from([
+            ("M-2", focus_nth_workspace(1)),
+            ("M-S-3", move_focused_to_nth_workspace(2)),
+        ]))
+        .unwrap();
+        let screens = vec![Rect::new(0, 0, 1000, 800), Rect::new(1000, 0, 1000, 800)];
+        let conn = HeadlessConn::new(screens);
+        let wm = WindowManager::new(config, bindings, HashMap::new(), conn).unwrap();
+
+        Scenario::new(wm).unwrap()
+    }
+
+    #[test_case(true, "1:2"; "per screen")]
+    #[test_case(false, "2"; "shared")]
+    #[test]
+    fn focus_nth_workspace_uses_current_screen(per_screen: bool, expected: &str) {
+        per_screen_scenario(per_screen)
+            .act(|s, _| {
+                s.client_set.focus_screen(1);
+                Ok(())
+            })
+            .press("M-2")
+            .expect_workspace(expected);
+    }
+
+    #[test_case(true, "1:3"; "per screen")]
+    #[test_case(false, "3"; "shared")]
+    #[test]
+    fn move_focused_to_nth_workspace_uses_current_screen(per_screen: bool, expected: &str) {
+        per_screen_scenario(per_screen)
+            .act(|s, _| {
+                s.client_set.focus_screen(1);
+                Ok(())
+            })
+            .given_windows(["a"])
+            .press("M-S-3")
+            .expect_on_workspace("a", expected);
+    }
+}
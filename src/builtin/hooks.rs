@@ -1,6 +1,6 @@
 //! Built-in hooks
 use crate::{
-    core::{hooks::LayoutHook, State},
+    core::{hooks::LayoutHook, scale_px, MonitorConfig, State},
     pure::geometry::Rect,
     x::XConn,
     Xid,
@@ -11,6 +11,12 @@ use crate::{
 /// `outer_px` controls the width of the gap around the edge of the screen and `inner_px`
 /// controls the gap around each individual window. Set both equal to one another to have
 /// a consistant gap size in all places.
+///
+/// When [Config::dpi_scaling][crate::core::Config::dpi_scaling] is set, all sizes are scaled
+/// according to the DPI of the screen being laid out.
+///
+/// Gap sizes can be set for individual monitors using
+/// [Config::monitor_overrides][crate::core::Config::monitor_overrides].
 #[derive(Debug, Clone, Default)]
 pub struct SpacingHook {
     /// The desired outer gap size in pixels
@@ -24,31 +30,56 @@ pub struct SpacingHook {
 }
 
 impl<X: XConn> LayoutHook<X> for SpacingHook {
-    fn transform_initial(&mut self, mut r: Rect, _: &State<X>, _: &X) -> Rect {
+    fn transform_initial(&mut self, mut r: Rect, state: &State<X>, _: &X) -> Rect {
         if r.w == 0 || r.h == 0 {
             return r;
         }
 
-        r.y += self.top_px;
-        r.h = r.h - self.top_px - self.bottom_px;
+        let (scale, conf) = screen_details(r, state);
+        let (top, bottom) = (
+            scale_px(self.top_px, scale),
+            scale_px(self.bottom_px, scale),
+        );
+        r.y += top;
+        r.h = r.h - top - bottom;
 
-        shrink(r, self.outer_px)
+        let outer = conf.and_then(|c| c.outer_gap).unwrap_or(self.outer_px);
+
+        shrink(r, scale_px(outer, scale))
     }
 
     fn transform_positions(
         &mut self,
-        _: Rect,
+        r: Rect,
         positions: Vec<(Xid, Rect)>,
-        _: &State<X>,
+        state: &State<X>,
         _: &X,
     ) -> Vec<(Xid, Rect)> {
+        let (scale, conf) = screen_details(r, state);
+        let inner = conf.and_then(|c| c.inner_gap).unwrap_or(self.inner_px);
+        let inner = scale_px(inner, scale);
+
         positions
             .into_iter()
-            .map(|(id, r)| (id, shrink(r, self.inner_px)))
+            .map(|(id, r)| (id, shrink(r, inner)))
             .collect()
     }
 }
 
+// The scale factor and any per-monitor config overrides of the screen containing the given
+// region
+fn screen_details<X: XConn>(r: Rect, state: &State<X>) -> (f64, Option<&MonitorConfig>) {
+    let p = r.midpoint();
+
+    match state.client_set.screens().find(|s| s.r.contains_point(p)) {
+        Some(s) => (
+            state.screen_scale(s.index()),
+            state.monitor_config(s.index()),
+        ),
+        None => (1.0, None),
+    }
+}
+
 fn shrink(r: Rect, px: u32) -> Rect {
     if r.w == 0 || r.h == 0 {
         return r;
@@ -6,6 +6,7 @@
 //! layout you are writing.
 //!
 //!   [0]: crate::core::layout::Layout
+use crate::pure::geometry::Rotation;
 
 /// Mark a type as being usable as a [Message][0] for sending to a [Layout][1]
 /// ```
@@ -48,6 +49,46 @@ impl_message!(ExpandMain);
 pub struct ShrinkMain;
 impl_message!(ShrinkMain);
 
+/// Alter the size of the outer gap (in pixels) of a [Gaps][0] transformer.
+///
+///   [0]: crate::builtin::layout::transformers::Gaps
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IncOuterGap(pub i32);
+impl_message!(IncOuterGap);
+
+/// Alter the size of the inner gap (in pixels) of a [Gaps][0] transformer.
+///
+///   [0]: crate::builtin::layout::transformers::Gaps
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IncInnerGap(pub i32);
+impl_message!(IncInnerGap);
+
+/// Enable or disable the gaps applied by a [Gaps][0] transformer without losing the
+/// configured gap sizes.
+///
+/// This is sent to the [Layout][3] of each visible [Workspace][1] before it is run, reflecting
+/// the current value of [Workspace::gaps_enabled][2] for the workspace. Use
+/// [Workspace::set_gaps_enabled][4] rather than sending it directly.
+///
+///   [0]: crate::builtin::layout::transformers::Gaps
+///   [1]: crate::pure::Workspace
+///   [2]: crate::pure::Workspace::gaps_enabled
+///   [3]: crate::core::layout::Layout
+///   [4]: crate::pure::Workspace::set_gaps_enabled
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SetGaps(pub bool);
+impl_message!(SetGaps);
+
+/// Reset the [Layout][0] to its initial proportions (e.g. the ratio between the main and
+/// secondary areas) without modifying other state such as the number of main clients.
+///
+/// Layouts without any adjustable proportions are free to ignore this message.
+///
+///   [0]: crate::core::layout::Layout
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Equalize;
+impl_message!(Equalize);
+
 /// Rotate the [Layout][0] to a new orientation
 ///
 ///   [0]: crate::core::layout::Layout
@@ -55,6 +96,21 @@ impl_message!(ShrinkMain);
 pub struct Rotate;
 impl_message!(Rotate);
 
+/// A [Message][0] sent to the [Layout][1] of each visible [Workspace][2] before it is run,
+/// giving the RandR [Rotation] of the screen it is being shown on.
+///
+/// Layouts that do not care about the orientation of the screen are free to ignore this
+/// message. See the [RotationAware][3] transformer for automatically rotating layouts when
+/// they are shown on a screen in portrait mode.
+///
+///   [0]: crate::core::layout::Message
+///   [1]: crate::core::layout::Layout
+///   [2]: crate::pure::Workspace
+///   [3]: crate::builtin::layout::transformers::RotationAware
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ScreenRotation(pub Rotation);
+impl_message!(ScreenRotation);
+
 /// Mirror the [Layout][0] over either the horizontal or vertical axis.
 ///
 ///   [0]: crate::core::layout::Layout
@@ -1,6 +1,6 @@
 //! Built-in layouts.
 use crate::{
-    builtin::layout::messages::{ExpandMain, IncMain, Mirror, Rotate, ShrinkMain},
+    builtin::layout::messages::{Equalize, ExpandMain, IncMain, Mirror, Rotate, ShrinkMain},
     core::layout::{Layout, Message},
     pure::{geometry::Rect, Stack},
     Xid,
@@ -35,9 +35,9 @@ impl StackPosition {
 /// - `MainAndStack::bottom` give a main region to the top and remaining clients to the bottom.
 ///
 /// The ratio between the main and secondary stack regions can be adjusted by sending [ShrinkMain]
-/// and [ExpandMain] messages to this layout. The number of clients in the main area can be
-/// increased or decreased by sending an [IncMain] message. To flip between the side and bottom
-/// behaviours you can send a [Rotate] message.
+/// and [ExpandMain] messages to this layout and reset with an [Equalize] message. The number of
+/// clients in the main area can be increased or decreased by sending an [IncMain] message. To
+/// flip between the side and bottom behaviours you can send a [Rotate] message.
 ///
 /// ```text
 /// ..................................
@@ -59,6 +59,7 @@ pub struct MainAndStack {
     pos: StackPosition,
     max_main: u32,
     ratio: f32,
+    initial_ratio: f32,
     ratio_step: f32,
     mirrored: bool,
 }
@@ -69,6 +70,7 @@ impl Default for MainAndStack {
             pos: StackPosition::Side,
             max_main: 1,
             ratio: 0.6,
+            initial_ratio: 0.6,
             ratio_step: 0.1,
             mirrored: false,
         }
@@ -101,6 +103,7 @@ impl MainAndStack {
             pos: StackPosition::Side,
             max_main,
             ratio,
+            initial_ratio: ratio,
             ratio_step,
             mirrored,
         }
@@ -125,6 +128,7 @@ impl MainAndStack {
             pos: StackPosition::Bottom,
             max_main,
             ratio,
+            initial_ratio: ratio,
             ratio_step,
             mirrored,
         }
@@ -217,6 +221,13 @@ impl Layout for MainAndStack {
         (None, positions)
     }
 
+    fn parameters(&self) -> Vec<(String, String)> {
+        vec![
+            ("ratio".to_owned(), format!("{:.2}", self.ratio)),
+            ("max_main".to_owned(), self.max_main.to_string()),
+        ]
+    }
+
     fn handle_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
         if let Some(&ExpandMain) = m.downcast_ref() {
             self.ratio += self.ratio_step;
@@ -228,6 +239,8 @@ impl Layout for MainAndStack {
             if self.ratio < 0.0 {
                 self.ratio = 0.0;
             }
+        } else if let Some(&Equalize) = m.downcast_ref() {
+            self.ratio = self.initial_ratio;
         } else if let Some(&IncMain(n)) = m.downcast_ref() {
             if n < 0 {
                 self.max_main = self.max_main.saturating_sub((-n) as u32);
@@ -250,9 +263,9 @@ impl Layout for MainAndStack {
 /// - `CenteredMain::horizontal` places the secondary regions to the top and bottom.
 ///
 /// The ratio between the main and secondary stack regions can be adjusted by sending [ShrinkMain]
-/// and [ExpandMain] messages to this layout. The number of clients in the main area can be
-/// increased or decreased by sending an [IncMain] message. To flip between the vertical and
-/// horizontal behaviours you can send a [Rotate] message.
+/// and [ExpandMain] messages to this layout and reset with an [Equalize] message. The number of
+/// clients in the main area can be increased or decreased by sending an [IncMain] message. To
+/// flip between the vertical and horizontal behaviours you can send a [Rotate] message.
 ///
 /// ```text
 /// ...................................
@@ -276,6 +289,7 @@ pub struct CenteredMain {
     pos: StackPosition,
     max_main: u32,
     ratio: f32,
+    initial_ratio: f32,
     ratio_step: f32,
 }
 
@@ -285,6 +299,7 @@ impl Default for CenteredMain {
             pos: StackPosition::Side,
             max_main: 1,
             ratio: 0.6,
+            initial_ratio: 0.6,
             ratio_step: 0.1,
         }
     }
@@ -310,6 +325,7 @@ impl CenteredMain {
             pos: StackPosition::Side,
             max_main,
             ratio,
+            initial_ratio: ratio,
             ratio_step,
         }
     }
@@ -327,6 +343,7 @@ impl CenteredMain {
             pos: StackPosition::Bottom,
             max_main,
             ratio,
+            initial_ratio: ratio,
             ratio_step,
         }
     }
@@ -437,6 +454,13 @@ impl Layout for CenteredMain {
         (None, positions)
     }
 
+    fn parameters(&self) -> Vec<(String, String)> {
+        vec![
+            ("ratio".to_owned(), format!("{:.2}", self.ratio)),
+            ("max_main".to_owned(), self.max_main.to_string()),
+        ]
+    }
+
     fn handle_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
         if let Some(&ExpandMain) = m.downcast_ref() {
             self.ratio += self.ratio_step;
@@ -448,6 +472,8 @@ impl Layout for CenteredMain {
             if self.ratio < 0.0 {
                 self.ratio = 0.0;
             }
+        } else if let Some(&Equalize) = m.downcast_ref() {
+            self.ratio = self.initial_ratio;
         } else if let Some(&IncMain(n)) = m.downcast_ref() {
             if n < 0 {
                 self.max_main = self.max_main.saturating_sub((-n) as u32);
@@ -480,6 +506,8 @@ impl Layout for CenteredMain {
 /// .                                .
 /// ..................................
 /// ```
+///
+/// As only a single client is visible at a time, window borders are not drawn for this layout.
 #[derive(Debug, Clone, Copy)]
 pub struct Monocle;
 
@@ -503,6 +531,10 @@ impl Layout for Monocle {
         (None, vec![(s.focus, r)])
     }
 
+    fn border_width(&self) -> Option<u32> {
+        Some(0)
+    }
+
     fn handle_message(&mut self, _: &Message) -> Option<Box<dyn Layout>> {
         None
     }
@@ -591,7 +623,10 @@ impl Layout for Grid {
 #[cfg(test)]
 mod tests {
     use crate::{
-        builtin::layout::{messages::IncMain, *},
+        builtin::layout::{
+            messages::{Equalize, ExpandMain, IncMain},
+            *,
+        },
         core::layout::IntoMessage,
     };
 
@@ -603,4 +638,16 @@ mod tests {
 
         assert_eq!(l.max_main, 3);
     }
+
+    #[test]
+    fn equalize_resets_ratio() {
+        let mut l = MainAndStack::side_unboxed(1, 0.6, 0.1, false);
+
+        l.handle_message(&ExpandMain.into_message());
+        l.handle_message(&IncMain(1).into_message());
+        l.handle_message(&Equalize.into_message());
+
+        assert_eq!(l.ratio, 0.6);
+        assert_eq!(l.max_main, 2);
+    }
 }
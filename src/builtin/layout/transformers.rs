@@ -1,6 +1,7 @@
 //! Built-in layout transformers.
 use crate::{
-    core::layout::{Layout, LayoutTransformer},
+    builtin::layout::messages::{IncInnerGap, IncOuterGap, Rotate, ScreenRotation, SetGaps},
+    core::layout::{IntoMessage, Layout, LayoutTransformer, Message},
     pure::geometry::Rect,
     simple_transformer, Xid,
 };
@@ -48,6 +49,15 @@ fn reflect_vertical(r: Rect, positions: Vec<(Xid, Rect)>) -> Vec<(Xid, Rect)> {
 /// `outer_px` controls the width of the gap around the edge of the screen and `inner_px`
 /// controls the gap around each individual window. Set both equal to one another to have
 /// a consistant gap size in all places.
+///
+/// Gaps can be temporarily disabled (retaining the configured sizes) by sending a [SetGaps]
+/// message.
+///
+/// Layouts are not aware of which screen they are running on so these gaps are not scaled by
+/// [Config::dpi_scaling][0]. Use a [SpacingHook][1] for gaps that follow the DPI of each screen.
+///
+///   [0]: crate::core::Config::dpi_scaling
+///   [1]: crate::builtin::hooks::SpacingHook
 #[derive(Debug, Clone)]
 pub struct Gaps {
     /// The inner [Layout] having gaps applied to it.
@@ -56,6 +66,8 @@ pub struct Gaps {
     pub outer_px: u32,
     /// The desired inner gap size in pixels
     pub inner_px: u32,
+    /// Whether or not gaps are currently being applied
+    pub enabled: bool,
 }
 
 impl Gaps {
@@ -65,8 +77,25 @@ impl Gaps {
             layout,
             outer_px,
             inner_px,
+            enabled: true,
         })
     }
+
+    fn outer(&self) -> u32 {
+        if self.enabled {
+            self.outer_px
+        } else {
+            0
+        }
+    }
+
+    fn inner(&self) -> u32 {
+        if self.enabled {
+            self.inner_px
+        } else {
+            0
+        }
+    }
 }
 
 fn shrink(r: Rect, px: u32) -> Rect {
@@ -77,8 +106,8 @@ fn shrink(r: Rect, px: u32) -> Rect {
     Rect {
         x: r.x + px,
         y: r.y + px,
-        w: r.w - 2 * px,
-        h: r.h - 2 * px,
+        w: r.w.saturating_sub(2 * px),
+        h: r.h.saturating_sub(2 * px),
     }
 }
 
@@ -91,16 +120,103 @@ impl LayoutTransformer for Gaps {
         &mut self.layout
     }
 
+    fn transformed_border_width(&self) -> Option<u32> {
+        self.layout.border_width()
+    }
+
+    fn transformed_parameters(&self) -> Vec<(String, String)> {
+        let mut params = self.layout.parameters();
+        params.push(("outer_gap".to_owned(), self.outer_px.to_string()));
+        params.push(("inner_gap".to_owned(), self.inner_px.to_string()));
+
+        params
+    }
+
     fn transform_initial(&self, r: Rect) -> Rect {
-        shrink(r, self.outer_px)
+        shrink(r, self.outer())
     }
 
     fn transform_positions(&mut self, _: Rect, positions: Vec<(Xid, Rect)>) -> Vec<(Xid, Rect)> {
         positions
             .into_iter()
-            .map(|(id, r)| (id, shrink(r, self.inner_px)))
+            .map(|(id, r)| (id, shrink(r, self.inner())))
             .collect()
     }
+
+    fn passthrough_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
+        if let Some(&IncOuterGap(n)) = m.downcast_ref() {
+            self.outer_px = self.outer_px.saturating_add_signed(n);
+        } else if let Some(&IncInnerGap(n)) = m.downcast_ref() {
+            self.inner_px = self.inner_px.saturating_add_signed(n);
+        } else if let Some(&SetGaps(enabled)) = m.downcast_ref() {
+            self.enabled = enabled;
+        } else if let Some(new) = self.layout.handle_message(m) {
+            self.swap_inner(new);
+        }
+
+        None
+    }
+}
+
+/// Automatically rotate the enclosed [Layout] when it is shown on a screen in portrait mode.
+///
+/// The [ScreenRotation] of the screen being laid out is tracked and a [Rotate] message is sent
+/// to the inner layout whenever the screen switches between landscape and portrait, so that
+/// layouts such as [MainAndStack][0] swap between their side and bottom orientations to make
+/// better use of rotated displays.
+///
+///   [0]: crate::builtin::layout::MainAndStack
+#[derive(Debug, Clone)]
+pub struct RotationAware {
+    /// The inner [Layout] being rotated.
+    pub layout: Box<dyn Layout>,
+    /// Whether or not the inner layout is currently rotated
+    pub rotated: bool,
+}
+
+impl RotationAware {
+    /// Wrap an existing [Layout] so that it is rotated on screens in portrait mode.
+    pub fn wrap(layout: Box<dyn Layout>) -> Box<dyn Layout> {
+        Box::new(Self {
+            layout,
+            rotated: false,
+        })
+    }
+}
+
+impl LayoutTransformer for RotationAware {
+    fn transformed_name(&self) -> String {
+        self.layout.name()
+    }
+
+    fn inner_mut(&mut self) -> &mut Box<dyn Layout> {
+        &mut self.layout
+    }
+
+    fn transformed_border_width(&self) -> Option<u32> {
+        self.layout.border_width()
+    }
+
+    fn transformed_parameters(&self) -> Vec<(String, String)> {
+        self.layout.parameters()
+    }
+
+    fn passthrough_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
+        if let Some(&ScreenRotation(rotation)) = m.downcast_ref() {
+            if rotation.is_portrait() != self.rotated {
+                self.rotated = !self.rotated;
+                if let Some(new) = self.layout.handle_message(&Rotate.into_message()) {
+                    self.swap_inner(new);
+                }
+            }
+        }
+
+        if let Some(new) = self.layout.handle_message(m) {
+            self.swap_inner(new);
+        }
+
+        None
+    }
 }
 
 /// Reserve `px` pixels at the top of the screen.
@@ -130,6 +246,14 @@ impl LayoutTransformer for ReserveTop {
         &mut self.layout
     }
 
+    fn transformed_border_width(&self) -> Option<u32> {
+        self.layout.border_width()
+    }
+
+    fn transformed_parameters(&self) -> Vec<(String, String)> {
+        self.layout.parameters()
+    }
+
     fn transform_initial(&self, mut r: Rect) -> Rect {
         if r.w == 0 || r.h == 0 {
             return r;
@@ -145,8 +269,67 @@ impl LayoutTransformer for ReserveTop {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        builtin::layout::{MainAndStack, Monocle},
+        core::layout::IntoMessage,
+        pure::geometry::Rotation,
+    };
     use simple_test_case::test_case;
 
+    #[test_case(Gaps::wrap(Monocle::boxed(), 5, 5), Some(0); "gaps")]
+    #[test_case(ReserveTop::wrap(Monocle::boxed(), 10), Some(0); "reserve top")]
+    #[test_case(ReflectHorizontal::wrap(Monocle::boxed()), Some(0); "simple transformer")]
+    #[test_case(Gaps::wrap(MainAndStack::boxed_default(), 5, 5), None; "no override")]
+    #[test]
+    fn border_width_is_taken_from_inner_layout(l: Box<dyn Layout>, expected: Option<u32>) {
+        assert_eq!(l.border_width(), expected);
+    }
+
+    #[test]
+    fn gaps_can_be_adjusted_using_messages() {
+        let mut l = Gaps::wrap(MainAndStack::boxed_default(), 5, 5);
+        l.handle_message(&IncOuterGap(3).into_message());
+        l.handle_message(&IncInnerGap(-10).into_message());
+
+        let params: Vec<_> = l
+            .parameters()
+            .into_iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect();
+
+        assert_eq!(
+            params,
+            vec!["ratio=0.60", "max_main=1", "outer_gap=8", "inner_gap=0"]
+        );
+    }
+
+    #[test_case(true, Rect::new(15, 15, 70, 70); "enabled")]
+    #[test_case(false, Rect::new(0, 0, 100, 100); "disabled")]
+    #[test]
+    fn gaps_can_be_disabled(enabled: bool, expected: Rect) {
+        let mut l = Gaps::wrap(Monocle::boxed(), 10, 5);
+        l.handle_message(&SetGaps(enabled).into_message());
+
+        let (_, positions) = l.layout(&crate::stack!(Xid(1)), Rect::new(0, 0, 100, 100));
+
+        assert_eq!(positions, vec![(Xid(1), expected)]);
+    }
+
+    #[test_case(&[], "Side"; "no rotation")]
+    #[test_case(&[Rotation::Left], "Bottom"; "portrait")]
+    #[test_case(&[Rotation::Inverted], "Side"; "inverted landscape")]
+    #[test_case(&[Rotation::Right, Rotation::Left], "Bottom"; "portrait to portrait")]
+    #[test_case(&[Rotation::Left, Rotation::Normal], "Side"; "back to landscape")]
+    #[test]
+    fn rotation_aware_rotates_in_portrait(rotations: &[Rotation], expected: &str) {
+        let mut l = RotationAware::wrap(MainAndStack::boxed_default());
+        for &rotation in rotations {
+            l.handle_message(&ScreenRotation(rotation).into_message());
+        }
+
+        assert_eq!(l.name(), expected);
+    }
+
     #[test_case(Rect::new(0, 0, 100, 200), Rect::new(0, 0, 100, 200); "fullscreen is idempotent")]
     #[test_case(Rect::new(0, 0, 40, 100), Rect::new(60, 0, 40, 100); "not crossing midpoint left")]
     #[test_case(Rect::new(60, 0, 40, 100), Rect::new(0, 0, 40, 100); "not crossing midpoint right")]
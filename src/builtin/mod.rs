@@ -5,6 +5,8 @@
 //! window manager.
 //!
 //! More advanced functionality is available via the `extensions` module.
+#[cfg(feature = "wm")]
 pub mod actions;
+#[cfg(feature = "wm")]
 pub mod hooks;
 pub mod layout;
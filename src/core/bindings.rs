@@ -1,17 +1,28 @@
 //! Setting up and responding to user defined key/mouse bindings
 use crate::{
-    core::{State, Xid},
+    core::{
+        handle::{run_mode_change_hook, ModalGrab},
+        State, Xid,
+    },
     pure::geometry::Point,
-    x::XConn,
+    util::helper_output_with_args,
+    x::{XConn, XConnExt, XEvent},
     Error, Result,
 };
 #[cfg(feature = "keysyms")]
 use penrose_keysyms::XKeySym;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::TryFrom, fmt, process::Command};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 use strum::{EnumIter, IntoEnumIterator};
-use tracing::trace;
+use tracing::{trace, warn};
 
 /// Run the xmodmap command to dump the system keymap table.
 ///
@@ -20,30 +31,180 @@ use tracing::trace;
 /// would expect while also ensuring that it is east to debug any odd
 /// issues with bindings by referring the user to the xmodmap output.
 ///
-/// # Panics
-/// This function will panic if it is unable to fetch keycodes using the xmodmap
+/// See [Keymap] for details of how key names are resolved to key codes.
+///
+/// # Errors
+/// This function will return an error if it is unable to fetch keycodes using the xmodmap
 /// binary on your system or if the output of `xmodmap -pke` is not valid
 pub fn keycodes_from_xmodmap() -> Result<HashMap<String, u8>> {
-    let output = Command::new("xmodmap").arg("-pke").output()?;
-    let m = String::from_utf8(output.stdout)?
-        .lines()
-        .flat_map(|l| {
-            let mut words = l.split_whitespace(); // keycode <code> = <names ...>
-            let key_code: u8 = match words.nth(1) {
-                Some(word) => match word.parse() {
-                    Ok(val) => val,
-                    Err(e) => panic!("{}", e),
-                },
-                None => panic!("unexpected output format from xmodmap -pke"),
+    Keymap::from_xmodmap().map(|keymap| keymap.keycodes())
+}
+
+/// The names of the keysyms bound to each key code by a keyboard mapping.
+///
+/// The names for each key code are held in the order reported by `xmodmap -pke`: the
+/// unshifted keysym first, followed by the shifted keysym and then those of any additional
+/// layout groups. This covers every keysym known to the X server, including media keys (such
+/// as `XF86AudioMute`), dead keys (such as `dead_acute`) and the keysyms of non-Latin layouts
+/// (such as `Cyrillic_a`).
+///
+/// When the keyboard mapping changes (for example after running `setxkbmap`) the current
+/// [Keymap] is used to find the new key codes for the keysyms of each key binding.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    names: BTreeMap<u8, Vec<String>>,
+}
+
+impl Keymap {
+    /// Create a new [Keymap] from key codes and the keysym names bound to each of them.
+    pub fn new(names: impl IntoIterator<Item = (u8, Vec<String>)>) -> Self {
+        Self {
+            names: names.into_iter().collect(),
+        }
+    }
+
+    /// Fetch the current keyboard mapping using the command line `xmodmap` utility.
+    pub fn from_xmodmap() -> Result<Self> {
+        let output = helper_output_with_args("xmodmap", &["-pke"])?;
+
+        Self::parse_xmodmap(&output)
+    }
+
+    // Each line of output is of the form `keycode <code> = <names ...>`
+    pub(crate) fn parse_xmodmap(output: &str) -> Result<Self> {
+        let mut names = BTreeMap::new();
+
+        for line in output.lines().filter(|l| !l.trim().is_empty()) {
+            let mut words = line.split_whitespace();
+            let code = match (words.next(), words.next().map(str::parse), words.next()) {
+                (Some("keycode"), Some(Ok(code)), Some("=")) => code,
+                _ => {
+                    return Err(Error::Custom(format!(
+                        "unexpected output format from xmodmap -pke: {line:?}"
+                    )))
+                }
             };
-            words.skip(1).map(move |name| (name.into(), key_code))
-        })
-        .collect();
 
-    Ok(m)
+            let keysyms: Vec<String> = words
+                .filter(|&w| w != "NoSymbol")
+                .map(String::from)
+                .collect();
+            if !keysyms.is_empty() {
+                names.insert(code, keysyms);
+            }
+        }
+
+        Ok(Self { names })
+    }
+
+    /// The keysym names bound to the given key code.
+    pub fn names_for(&self, code: KeyCodeValue) -> &[String] {
+        self.names
+            .get(&code)
+            .map(|v| v.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The key code that produces the keysym with the given name.
+    ///
+    /// If the keysym is bound to multiple keys then the one requiring the fewest modifiers is
+    /// used, followed by the one with the lowest key code.
+    pub fn code_for(&self, name: &str) -> Option<KeyCodeValue> {
+        self.names
+            .iter()
+            .flat_map(|(&code, names)| names.iter().position(|n| n == name).map(|i| (i, code)))
+            .min()
+            .map(|(_, code)| code)
+    }
+
+    /// A lookup table from keysym names to key codes, resolved in the same way as
+    /// [Keymap::code_for], for use when parsing string format key bindings.
+    pub fn keycodes(&self) -> HashMap<String, u8> {
+        let mut best: HashMap<&str, (usize, u8)> = HashMap::new();
+        for (&code, names) in self.names.iter() {
+            for (i, name) in names.iter().enumerate() {
+                let current = best.entry(name).or_insert((i, code));
+                if i < current.0 {
+                    *current = (i, code);
+                }
+            }
+        }
+
+        best.into_iter()
+            .map(|(name, (_, code))| (name.to_string(), code))
+            .collect()
+    }
+
+    /// The key in this keymap that produces the same keysym as the given key did under the
+    /// `previous` keymap, holding the same modifiers.
+    pub fn remap(&self, key: KeyCode, previous: &Keymap) -> Option<KeyCode> {
+        previous
+            .names_for(key.code)
+            .iter()
+            .find_map(|name| self.code_for(name))
+            .map(|code| KeyCode {
+                mask: key.mask,
+                code,
+            })
+    }
+
+    /// A string format description of the given key, such as `"M-S-j"`, using the first
+    /// keysym bound to its key code.
+    ///
+    /// Modifiers are listed in the order Meta, Ctrl, Alt, Shift so that the result can be
+    /// parsed back into the same [KeyCode]. If no keysym is bound to the key code then the
+    /// numeric key code is used in place of a name.
+    pub fn describe(&self, key: KeyCode) -> String {
+        let name = match self.names_for(key.code).first() {
+            Some(name) => name.clone(),
+            None => key.code.to_string(),
+        };
+
+        [
+            (ModifierKey::Meta, "M"),
+            (ModifierKey::Ctrl, "C"),
+            (ModifierKey::Alt, "A"),
+            (ModifierKey::Shift, "S"),
+        ]
+        .iter()
+        .filter(|(m, _)| m.was_held(key.mask))
+        .map(|&(_, s)| s)
+        .chain(std::iter::once(name.as_str()))
+        .collect::<Vec<_>>()
+        .join("-")
+    }
+
+    // Remap a key, leaving it unchanged if its keysym is no longer available.
+    fn remap_or_keep(&self, key: KeyCode, previous: &Keymap) -> KeyCode {
+        self.remap(key, previous).unwrap_or(key)
+    }
+}
+
+/// Update the key codes of the given key bindings following a change to the keyboard
+/// mapping from `previous` to `keymap`.
+///
+/// Each binding is moved to the key that now produces the keysym it was originally bound
+/// to, and any bindings held by the handlers themselves (such as those of a [KeyChord]) are
+/// updated as well. Bindings whose keysym is no longer available are left unchanged.
+pub fn remap_keybindings<X: XConn>(
+    bindings: &mut KeyBindings<X>,
+    keymap: &Keymap,
+    previous: &Keymap,
+) {
+    for (key, mut handler) in std::mem::take(bindings) {
+        handler.remap_keys(keymap, previous);
+        let new_key = keymap.remap(key, previous).unwrap_or_else(|| {
+            warn!(
+                ?key,
+                "keysym for key binding is missing from the new keyboard mapping"
+            );
+            key
+        });
+        bindings.insert(new_key, handler);
+    }
 }
 
-fn parse_binding(pattern: &str, known_codes: &HashMap<String, u8>) -> Result<KeyCode> {
+pub(crate) fn parse_binding(pattern: &str, known_codes: &HashMap<String, u8>) -> Result<KeyCode> {
     let mut parts: Vec<&str> = pattern.split('-').collect();
     let name = parts.remove(parts.len() - 1);
 
@@ -64,6 +225,36 @@ fn parse_binding(pattern: &str, known_codes: &HashMap<String, u8>) -> Result<Key
     }
 }
 
+/// A key binding along with the description of what it does, if one has been given.
+///
+/// The descriptions of the current key bindings can be found using
+/// [State::binding_descriptions].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingDescription {
+    /// The key binding in string format, such as `"M-S-j"`
+    pub keys: String,
+    /// The description of the binding (see [Described])
+    pub description: Option<String>,
+}
+
+/// Describe each of the given key bindings using the key names from the given [Keymap],
+/// sorted by key.
+pub fn describe_keybindings<X: XConn>(
+    bindings: &KeyBindings<X>,
+    keymap: &Keymap,
+) -> Vec<BindingDescription> {
+    let mut descriptions: Vec<_> = bindings
+        .iter()
+        .map(|(&k, handler)| BindingDescription {
+            keys: keymap.describe(k),
+            description: handler.description(),
+        })
+        .collect();
+    descriptions.sort_by(|a, b| a.keys.cmp(&b.keys));
+
+    descriptions
+}
+
 /// Parse string format key bindings into [KeyCode] based [KeyBindings] using
 /// the command line `xmodmap` utility.
 ///
@@ -83,6 +274,39 @@ where
         .collect()
 }
 
+/// Parse string format key bindings into a [KeyChord] using the command line `xmodmap`
+/// utility.
+///
+/// See [keycodes_from_xmodmap] for details of how `xmodmap` is used.
+pub fn parse_key_chord_with_xmodmap<S, X>(
+    str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
+) -> Result<KeyChord<X>>
+where
+    S: AsRef<str>,
+    X: XConn,
+{
+    let m = keycodes_from_xmodmap()?;
+
+    KeyChord::try_from_str_bindings(str_bindings, &m)
+}
+
+/// Parse string format key bindings into a named [BindingMode] using the command line
+/// `xmodmap` utility.
+///
+/// See [keycodes_from_xmodmap] for details of how `xmodmap` is used.
+pub fn parse_binding_mode_with_xmodmap<S, X>(
+    name: impl Into<String>,
+    str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
+) -> Result<BindingMode<X>>
+where
+    S: AsRef<str>,
+    X: XConn,
+{
+    let m = keycodes_from_xmodmap()?;
+
+    BindingMode::try_from_str_bindings(name, str_bindings, &m)
+}
+
 /// Some action to be run by a user key binding
 pub trait KeyEventHandler<X>
 where
@@ -90,6 +314,31 @@ where
 {
     /// Call this handler with the current window manager state
     fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()>;
+
+    /// Whether this handler should be run when its key is released rather than when it is
+    /// pressed. See [OnRelease] for details.
+    fn runs_on_release(&self) -> bool {
+        false
+    }
+
+    /// How this handler should be repeated while its key is held down, if at all. See
+    /// [HoldToRepeat] for details.
+    fn repeat_rate(&self) -> Option<RepeatRate> {
+        None
+    }
+
+    /// Update any key codes held by this handler following a change to the keyboard mapping.
+    ///
+    /// This only needs to be implemented by handlers that hold key bindings of their own
+    /// (see [remap_keybindings]). The default implementation does nothing.
+    #[allow(unused_variables)]
+    fn remap_keys(&mut self, keymap: &Keymap, previous: &Keymap) {}
+
+    /// A short description of what this handler does for listing in a cheatsheet of the
+    /// current key bindings. See [Described] for details.
+    fn description(&self) -> Option<String> {
+        None
+    }
 }
 
 impl<X: XConn> fmt::Debug for Box<dyn KeyEventHandler<X>> {
@@ -137,8 +386,818 @@ where
 }
 
 /// User defined mouse bindings
+///
+/// Bindings that include at least one [ModifierKey] are grabbed by the window manager and are
+/// triggered anywhere on the screen, including over client windows. Bindings without any
+/// modifiers are _not_ grabbed: they are only triggered when the pointer is over the root
+/// window itself (i.e. the desktop) so that they do not prevent clients from receiving normal
+/// clicks and scrolling.
+///
+/// > **NOTE**: previous versions of penrose grabbed all mouse bindings, so bindings without any
+/// > modifiers were triggered over client windows (which then never saw those clicks). If you
+/// > were relying on this you will need to add a modifier to the binding. The [MouseEvent]
+/// > passed to each binding also now identifies the client window under the pointer rather
+/// > than always being the root window: see [MouseEvent::id] for details.
 pub type MouseBindings<X> = HashMap<(MouseEventKind, MouseState), Box<dyn MouseEventHandler<X>>>;
 
+/// How long a [KeyChord] waits for a follow-up key by default.
+pub const DEFAULT_CHORD_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A [KeyEventHandler] that opens a sub-map of follow-up key bindings.
+///
+/// When the key binding for a chord is pressed, the keys in its sub-map are grabbed in
+/// place of the normal key bindings until one of them is pressed, `Escape` is pressed or
+/// the chord times out. The normal key and mouse bindings are restored once the chord
+/// finishes. Sub-maps can contain further chords in order to build up longer key sequences.
+///
+/// While a chord is waiting for a key, a [KeyChordHint] listing the available follow-up keys
+/// is published on the [State] so that it can be shown in a status bar or elsewhere.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::modify_with, core::bindings::*, map, x11rb::RustConn};
+/// # use std::collections::HashMap;
+/// # fn example() -> penrose::Result<KeyBindings<RustConn>> {
+/// // M-w followed by j / k / q to move focus or close the focused window
+/// let window_ops: HashMap<&str, Box<dyn KeyEventHandler<RustConn>>> = map! {
+///     "j" => modify_with(|cs| cs.focus_down()),
+///     "k" => modify_with(|cs| cs.focus_up()),
+///     "q" => modify_with(|cs| cs.kill_focused()),
+/// };
+/// let chord: Box<dyn KeyEventHandler<RustConn>> =
+///     Box::new(parse_key_chord_with_xmodmap(window_ops)?.with_name("window"));
+///
+/// let raw_bindings: HashMap<&str, Box<dyn KeyEventHandler<RustConn>>> = map! {
+///     "M-w" => chord,
+/// };
+///
+/// parse_keybindings_with_xmodmap(raw_bindings)
+/// # }
+/// ```
+pub struct KeyChord<X: XConn> {
+    name: Option<String>,
+    bindings: Rc<RefCell<KeyBindings<X>>>,
+    keys: Vec<String>,
+    escape: Option<KeyCode>,
+    timeout: Option<Duration>,
+}
+
+impl<X: XConn> fmt::Debug for KeyChord<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyChord")
+            .field("name", &self.name)
+            .field("keys", &self.keys)
+            .field("escape", &self.escape)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<X: XConn> KeyChord<X> {
+    /// Create a new [KeyChord] from already parsed key bindings.
+    ///
+    /// Chords created this way can only be cancelled by timing out unless an escape key is
+    /// set using [KeyChord::with_escape] and have no key names to show in their
+    /// [KeyChordHint]. Use [parse_key_chord_with_xmodmap] to create a chord from string
+    /// format key bindings instead.
+    pub fn new(bindings: KeyBindings<X>) -> Self {
+        Self {
+            name: None,
+            bindings: Rc::new(RefCell::new(bindings)),
+            keys: Vec::new(),
+            escape: None,
+            timeout: Some(DEFAULT_CHORD_TIMEOUT),
+        }
+    }
+
+    pub(crate) fn try_from_str_bindings<S>(
+        str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
+        known_codes: &HashMap<String, u8>,
+    ) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let mut keys = Vec::with_capacity(str_bindings.len());
+        let mut bindings = HashMap::with_capacity(str_bindings.len());
+        for (s, v) in str_bindings {
+            bindings.insert(parse_binding(s.as_ref(), known_codes)?, v);
+            keys.push(s.as_ref().to_string());
+        }
+        keys.sort();
+
+        let mut chord = Self::new(bindings);
+        chord.keys = keys;
+        chord.escape = parse_binding("Escape", known_codes).ok();
+
+        Ok(chord)
+    }
+
+    /// Set a name for this chord to be shown in its [KeyChordHint].
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the key used to cancel this chord without running any of its bindings.
+    pub fn with_escape(mut self, escape: KeyCode) -> Self {
+        self.escape = Some(escape);
+        self
+    }
+
+    /// Set how long this chord waits for a follow-up key before it is cancelled. If this is
+    /// `None` then the chord waits until one of its keys or the escape key is pressed.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl<X: XConn> KeyEventHandler<X> for KeyChord<X> {
+    fn remap_keys(&mut self, keymap: &Keymap, previous: &Keymap) {
+        remap_keybindings(&mut self.bindings.borrow_mut(), keymap, previous);
+        self.escape = self.escape.map(|k| keymap.remap_or_keep(k, previous));
+    }
+
+    fn description(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        let codes: Vec<KeyCode> = self
+            .bindings
+            .borrow()
+            .keys()
+            .copied()
+            .chain(self.escape)
+            .collect();
+
+        trace!(name = ?self.name, "waiting for key chord");
+        // Any count prefix is passed on to the binding that follows the chord
+        state.bindings.pending_count = state.bindings.count;
+        // Mouse bindings are released until the chord finishes
+        x.grab(&codes, &[])?;
+
+        state.bindings.active_chord = Some(ActiveChord {
+            bindings: Rc::clone(&self.bindings),
+            exit: self.escape.into_iter().collect(),
+            deadline: self.timeout.map(|t| Instant::now() + t),
+            persistent: false,
+        });
+        state.publish(KeyChordHint {
+            name: self.name.clone(),
+            keys: self.keys.clone(),
+        });
+
+        if let Some(timeout) = self.timeout {
+            state.wake_after(timeout);
+        }
+
+        x.refresh(state)
+    }
+}
+
+/// A key binding that runs when its key is released rather than when it is pressed.
+///
+/// The wrapped handler runs once each time the key is released, regardless of how long the
+/// key was held for (auto-repeated key presses are ignored) or whether the modifiers of the
+/// binding are released first. This makes it possible to show something while a binding
+/// is held down and act on it once the key is let go.
+///
+/// Release bindings only apply to the top level key bindings: inside of a [KeyChord] or
+/// [BindingMode] the wrapped handler is run when its key is pressed.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::spawn, core::bindings::*, map, x11rb::RustConn};
+/// # use std::collections::HashMap;
+/// # fn example() -> penrose::Result<KeyBindings<RustConn>> {
+/// let raw_bindings: HashMap<&str, Box<dyn KeyEventHandler<RustConn>>> = map! {
+///     "M-Tab" => OnRelease::new(spawn("rofi -show window")).boxed(),
+/// };
+///
+/// parse_keybindings_with_xmodmap(raw_bindings)
+/// # }
+/// ```
+pub struct OnRelease<X: XConn> {
+    inner: Box<dyn KeyEventHandler<X>>,
+}
+
+impl<X: XConn> fmt::Debug for OnRelease<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnRelease").finish()
+    }
+}
+
+impl<X: XConn> OnRelease<X> {
+    /// Run the given handler when its key is released.
+    pub fn new(inner: Box<dyn KeyEventHandler<X>>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<X: XConn + 'static> OnRelease<X> {
+    /// Box this handler ready for adding to your key bindings.
+    pub fn boxed(self) -> Box<dyn KeyEventHandler<X>> {
+        Box::new(self)
+    }
+}
+
+impl<X: XConn> KeyEventHandler<X> for OnRelease<X> {
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        self.inner.call(state, x)
+    }
+
+    fn runs_on_release(&self) -> bool {
+        true
+    }
+
+    fn remap_keys(&mut self, keymap: &Keymap, previous: &Keymap) {
+        self.inner.remap_keys(keymap, previous);
+    }
+
+    fn description(&self) -> Option<String> {
+        self.inner.description()
+    }
+}
+
+/// How long to wait before repeating a [HoldToRepeat] binding by default.
+pub const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(300);
+
+/// How often to repeat a [HoldToRepeat] binding by default once it has started repeating.
+pub const DEFAULT_REPEAT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How a [HoldToRepeat] binding is repeated while its key is held down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatRate {
+    /// How long the key needs to be held before the binding starts repeating
+    pub delay: Duration,
+    /// How long to wait between each repeat once the binding has started repeating
+    pub interval: Duration,
+}
+
+impl Default for RepeatRate {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_REPEAT_DELAY,
+            interval: DEFAULT_REPEAT_INTERVAL,
+        }
+    }
+}
+
+impl RepeatRate {
+    /// Create a new [RepeatRate] with the given initial delay and repeat interval.
+    pub fn new(delay: Duration, interval: Duration) -> Self {
+        Self { delay, interval }
+    }
+}
+
+/// A key binding that is run repeatedly for as long as its key is held down.
+///
+/// The wrapped handler runs once when the key is pressed and then, once the key has been held
+/// for the initial delay of its [RepeatRate], again after each repeat interval until the key
+/// is released. Repeats are timed by penrose itself so any auto-repeated key presses sent by
+/// the X server are ignored. This makes for smooth and predictable repeating when nudging
+/// floating windows or adjusting layouts.
+///
+/// As with [OnRelease], repeating only applies to the top level key bindings: inside of a
+/// [KeyChord] or [BindingMode] the wrapped handler is run once each time its key is pressed.
+///
+/// ```no_run
+/// # use penrose::{
+/// #     builtin::actions::floating::reposition,
+/// #     core::bindings::*,
+/// #     map,
+/// #     x11rb::RustConn,
+/// # };
+/// # use std::{collections::HashMap, time::Duration};
+/// # fn example() -> penrose::Result<KeyBindings<RustConn>> {
+/// let fast = RepeatRate::new(Duration::from_millis(200), Duration::from_millis(20));
+/// let raw_bindings: HashMap<&str, Box<dyn KeyEventHandler<RustConn>>> = map! {
+///     "M-Left" => HoldToRepeat::new(reposition(-10, 0)).boxed(),
+///     "M-Right" => HoldToRepeat::new(reposition(10, 0)).with_rate(fast).boxed(),
+/// };
+///
+/// parse_keybindings_with_xmodmap(raw_bindings)
+/// # }
+/// ```
+pub struct HoldToRepeat<X: XConn> {
+    inner: Box<dyn KeyEventHandler<X>>,
+    rate: RepeatRate,
+}
+
+impl<X: XConn> fmt::Debug for HoldToRepeat<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HoldToRepeat")
+            .field("rate", &self.rate)
+            .finish()
+    }
+}
+
+impl<X: XConn> HoldToRepeat<X> {
+    /// Repeat the given handler while its key is held using the default [RepeatRate].
+    pub fn new(inner: Box<dyn KeyEventHandler<X>>) -> Self {
+        Self {
+            inner,
+            rate: RepeatRate::default(),
+        }
+    }
+
+    /// Repeat at the given [RepeatRate] rather than the default.
+    pub fn with_rate(mut self, rate: RepeatRate) -> Self {
+        self.rate = rate;
+        self
+    }
+}
+
+impl<X: XConn + 'static> HoldToRepeat<X> {
+    /// Box this handler ready for adding to your key bindings.
+    pub fn boxed(self) -> Box<dyn KeyEventHandler<X>> {
+        Box::new(self)
+    }
+}
+
+impl<X: XConn> KeyEventHandler<X> for HoldToRepeat<X> {
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        self.inner.call(state, x)
+    }
+
+    fn repeat_rate(&self) -> Option<RepeatRate> {
+        Some(self.rate)
+    }
+
+    fn remap_keys(&mut self, keymap: &Keymap, previous: &Keymap) {
+        self.inner.remap_keys(keymap, previous);
+    }
+
+    fn description(&self) -> Option<String> {
+        self.inner.description()
+    }
+}
+
+// The HoldToRepeat binding whose key is currently held down along with when it should next
+// be repeated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct HeldKey {
+    pub(crate) key: KeyCode,
+    pub(crate) next: Instant,
+    pub(crate) interval: Duration,
+}
+
+/// A key binding along with a short description of what it does.
+///
+/// Descriptions are listed alongside their key bindings in [State::binding_descriptions],
+/// allowing you to build a cheatsheet of your current key bindings that stays up to date
+/// with your config (see [dmenu_binding_cheatsheet][0] for an example). [KeyChord]s and
+/// [BindingMode]s are described by their name.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::modify_with, core::bindings::*, map, x11rb::RustConn};
+/// # use std::collections::HashMap;
+/// # fn example() -> penrose::Result<KeyBindings<RustConn>> {
+/// let raw_bindings: HashMap<&str, Box<dyn KeyEventHandler<RustConn>>> = map! {
+///     "M-j" => Described::new("focus down", modify_with(|cs| cs.focus_down())).boxed(),
+///     "M-k" => Described::new("focus up", modify_with(|cs| cs.focus_up())).boxed(),
+/// };
+///
+/// parse_keybindings_with_xmodmap(raw_bindings)
+/// # }
+/// ```
+///
+///   [0]: crate::extensions::actions::dmenu_binding_cheatsheet
+pub struct Described<X: XConn> {
+    description: String,
+    inner: Box<dyn KeyEventHandler<X>>,
+}
+
+impl<X: XConn> fmt::Debug for Described<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Described")
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+impl<X: XConn> Described<X> {
+    /// Attach the given description to a handler.
+    pub fn new(description: impl Into<String>, inner: Box<dyn KeyEventHandler<X>>) -> Self {
+        Self {
+            description: description.into(),
+            inner,
+        }
+    }
+}
+
+impl<X: XConn + 'static> Described<X> {
+    /// Box this handler ready for adding to your key bindings.
+    pub fn boxed(self) -> Box<dyn KeyEventHandler<X>> {
+        Box::new(self)
+    }
+}
+
+impl<X: XConn> KeyEventHandler<X> for Described<X> {
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        self.inner.call(state, x)
+    }
+
+    fn runs_on_release(&self) -> bool {
+        self.inner.runs_on_release()
+    }
+
+    fn repeat_rate(&self) -> Option<RepeatRate> {
+        self.inner.repeat_rate()
+    }
+
+    fn remap_keys(&mut self, keymap: &Keymap, previous: &Keymap) {
+        self.inner.remap_keys(keymap, previous);
+    }
+
+    fn description(&self) -> Option<String> {
+        Some(self.description.clone())
+    }
+}
+
+/// The follow-up keys of the [KeyChord] that is currently waiting for a key.
+///
+/// This is published on the [State] while a chord is active and can be read using
+/// [State::published].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChordHint {
+    /// The name of the chord, if it has one
+    pub name: Option<String>,
+    /// The key bindings that can follow the chord, in sorted order
+    pub keys: Vec<String>,
+}
+
+/// A named set of key bindings that replaces the normal key bindings until it is exited.
+///
+/// Binding modes work in a similar way to a [KeyChord], but remain active after one of their
+/// key bindings has been run: keys that are not part of the mode are ignored and the mode is
+/// only exited once one of its exit keys (`Escape` and `Return` by default) is pressed. This
+/// makes them a good fit for things like resizing windows with repeated key presses.
+///
+/// Mouse bindings are released while a mode is active. Running a [KeyChord] from inside of a
+/// mode replaces the mode, so the normal bindings are restored once the chord finishes.
+/// Running another [BindingMode] switches directly to that mode.
+///
+/// The name of the current mode can be found using [State::active_mode] and the
+/// [Config::mode_change_hook][0] is run each time a mode is entered or exited.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::send_layout_message, core::bindings::*, map, x11rb::RustConn};
+/// # use penrose::builtin::layout::messages::{ExpandMain, ShrinkMain};
+/// # use std::collections::HashMap;
+/// # fn example() -> penrose::Result<KeyBindings<RustConn>> {
+/// let resize_bindings: HashMap<&str, Box<dyn KeyEventHandler<RustConn>>> = map! {
+///     "h" => send_layout_message(|| ShrinkMain),
+///     "l" => send_layout_message(|| ExpandMain),
+/// };
+/// let resize: Box<dyn KeyEventHandler<RustConn>> =
+///     Box::new(parse_binding_mode_with_xmodmap("resize", resize_bindings)?);
+///
+/// let raw_bindings: HashMap<&str, Box<dyn KeyEventHandler<RustConn>>> = map! {
+///     "M-r" => resize,
+/// };
+///
+/// parse_keybindings_with_xmodmap(raw_bindings)
+/// # }
+/// ```
+///
+///   [0]: crate::core::Config::mode_change_hook
+pub struct BindingMode<X: XConn> {
+    name: String,
+    bindings: Rc<RefCell<KeyBindings<X>>>,
+    exit: Vec<KeyCode>,
+}
+
+impl<X: XConn> fmt::Debug for BindingMode<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BindingMode")
+            .field("name", &self.name)
+            .field("exit", &self.exit)
+            .finish()
+    }
+}
+
+impl<X: XConn> BindingMode<X> {
+    /// Create a new [BindingMode] from already parsed key bindings.
+    ///
+    /// Modes created this way have no exit keys, so at least one should be set using
+    /// [BindingMode::with_exit_keys]. Use [parse_binding_mode_with_xmodmap] to create a mode
+    /// from string format key bindings instead.
+    pub fn new(name: impl Into<String>, bindings: KeyBindings<X>) -> Self {
+        Self {
+            name: name.into(),
+            bindings: Rc::new(RefCell::new(bindings)),
+            exit: Vec::new(),
+        }
+    }
+
+    pub(crate) fn try_from_str_bindings<S>(
+        name: impl Into<String>,
+        str_bindings: HashMap<S, Box<dyn KeyEventHandler<X>>>,
+        known_codes: &HashMap<String, u8>,
+    ) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let bindings = str_bindings
+            .into_iter()
+            .map(|(s, v)| parse_binding(s.as_ref(), known_codes).map(|k| (k, v)))
+            .collect::<Result<_>>()?;
+
+        let mut mode = Self::new(name, bindings);
+        mode.exit = ["Escape", "Return"]
+            .iter()
+            .flat_map(|s| parse_binding(s, known_codes).ok())
+            .collect();
+
+        Ok(mode)
+    }
+
+    /// Set the keys used to exit this mode, replacing the current exit keys.
+    pub fn with_exit_keys(mut self, exit: Vec<KeyCode>) -> Self {
+        self.exit = exit;
+        self
+    }
+}
+
+impl<X: XConn> KeyEventHandler<X> for BindingMode<X> {
+    fn remap_keys(&mut self, keymap: &Keymap, previous: &Keymap) {
+        remap_keybindings(&mut self.bindings.borrow_mut(), keymap, previous);
+        for k in self.exit.iter_mut() {
+            *k = keymap.remap_or_keep(*k, previous);
+        }
+    }
+
+    fn description(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        let codes: Vec<KeyCode> = self
+            .bindings
+            .borrow()
+            .keys()
+            .chain(self.exit.iter())
+            .copied()
+            .collect();
+
+        trace!(name = self.name, "entering binding mode");
+        // Any count prefix is passed on to the first binding run in the mode
+        state.bindings.pending_count = state.bindings.count;
+        // Mouse bindings are released until the mode is exited
+        x.grab(&codes, &[])?;
+
+        state.bindings.active_chord = Some(ActiveChord {
+            bindings: Rc::clone(&self.bindings),
+            exit: self.exit.clone(),
+            deadline: None,
+            persistent: true,
+        });
+
+        if state.bindings.active_mode.as_ref() != Some(&self.name) {
+            state.bindings.active_mode = Some(self.name.clone());
+            run_mode_change_hook(state, x);
+        }
+
+        x.refresh(state)
+    }
+}
+
+// The state of key and mouse bindings that is tracked between events by the main event loop.
+#[derive(Debug)]
+pub(crate) struct BindingState<X: XConn> {
+    pub(crate) active_chord: Option<ActiveChord<X>>,
+    pub(crate) active_mode: Option<String>,
+    pub(crate) pending_changes: Vec<BindingChange<X>>,
+    pub(crate) modal_grab: ModalGrab,
+    pub(crate) keymap: Option<Keymap>,
+    pub(crate) descriptions: Vec<BindingDescription>,
+    pub(crate) pending_key_releases: HashMap<KeyCodeValue, KeyCode>,
+    pub(crate) pending_count: Option<usize>,
+    pub(crate) count: Option<usize>,
+    pub(crate) last_click: Option<(Xid, MouseState, Instant)>,
+    pub(crate) held_key: Option<HeldKey>,
+    pub(crate) macros: KeyMacros,
+}
+
+impl<X: XConn> Default for BindingState<X> {
+    fn default() -> Self {
+        Self {
+            active_chord: None,
+            active_mode: None,
+            pending_changes: Vec::new(),
+            modal_grab: ModalGrab::default(),
+            keymap: None,
+            descriptions: Vec::new(),
+            pending_key_releases: HashMap::new(),
+            pending_count: None,
+            count: None,
+            last_click: None,
+            held_key: None,
+            macros: KeyMacros::default(),
+        }
+    }
+}
+
+// The sub-map of a KeyChord that is waiting for a follow-up key or of the active BindingMode.
+// Persistent sub-maps remain active after running one of their bindings.
+#[derive(Debug)]
+pub(crate) struct ActiveChord<X: XConn> {
+    pub(crate) bindings: Rc<RefCell<KeyBindings<X>>>,
+    pub(crate) exit: Vec<KeyCode>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) persistent: bool,
+}
+
+// A modification to the active key or mouse bindings requested through [State][0] that is
+// applied (and the bindings regrabbed) once the current event has been handled.
+//
+//   [0]: crate::core::State
+#[derive(Debug)]
+pub(crate) enum BindingChange<X: XConn> {
+    AddKey(KeyCode, Box<dyn KeyEventHandler<X>>),
+    RemoveKey(KeyCode),
+    AddMouse((MouseEventKind, MouseState), Box<dyn MouseEventHandler<X>>),
+    RemoveMouse((MouseEventKind, MouseState)),
+}
+
+impl<X: XConn> BindingChange<X> {
+    // Apply this change to the given bindings, returning true if the set of bound keys or
+    // mouse states was modified and the bindings need to be regrabbed.
+    pub(crate) fn apply(
+        self,
+        key_bindings: &mut KeyBindings<X>,
+        mouse_bindings: &mut MouseBindings<X>,
+    ) -> bool {
+        match self {
+            Self::AddKey(code, handler) => key_bindings.insert(code, handler).is_none(),
+            Self::RemoveKey(code) => key_bindings.remove(&code).is_some(),
+            Self::AddMouse(key, handler) => mouse_bindings.insert(key, handler).is_none(),
+            Self::RemoveMouse(key) => mouse_bindings.remove(&key).is_some(),
+        }
+    }
+}
+
+// A key binding run while recording a key macro, identified by the key that ran it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MacroStep {
+    Press(KeyCode),
+    Release(KeyCode),
+}
+
+impl From<MacroStep> for XEvent {
+    fn from(step: MacroStep) -> Self {
+        match step {
+            MacroStep::Press(key) => XEvent::KeyPress(key),
+            MacroStep::Release(key) => XEvent::KeyRelease(key),
+        }
+    }
+}
+
+// The key macros recorded using State::start_recording_macro along with the steps of any
+// macro that is waiting to be replayed.
+#[derive(Debug, Default)]
+pub(crate) struct KeyMacros {
+    pub(crate) recording: Option<(String, Vec<MacroStep>)>,
+    pub(crate) saved: HashMap<String, Vec<MacroStep>>,
+    pub(crate) pending_replay: Vec<MacroStep>,
+    pub(crate) replaying: bool,
+}
+
+impl KeyMacros {
+    // Steps are only recorded if a recording was in progress both before and after running
+    // the binding, so that the bindings used to start and stop recording are not included.
+    // Steps run while replaying a macro are covered by the binding that replayed it.
+    pub(crate) fn record(&mut self, was_recording: bool, step: MacroStep) {
+        if !was_recording || self.replaying {
+            return;
+        }
+
+        if let Some((_, steps)) = self.recording.as_mut() {
+            steps.push(step);
+        }
+    }
+}
+
+// Key bindings that are layered on top of the normal key bindings while the focused workspace
+// matches a predicate. While active, the overlay bindings are moved into the normal key bindings
+// and any bindings they replace are held in `displaced` until the overlay is deactivated.
+struct KeyBindingOverlay<X: XConn> {
+    matches: Box<dyn Fn(&str) -> bool>,
+    bindings: KeyBindings<X>,
+    displaced: KeyBindings<X>,
+    keys: Vec<KeyCode>,
+    active: bool,
+}
+
+impl<X: XConn> KeyBindingOverlay<X> {
+    fn activate(&mut self, key_bindings: &mut KeyBindings<X>) {
+        self.keys = self.bindings.keys().copied().collect();
+        for (key, handler) in self.bindings.drain() {
+            if let Some(displaced) = key_bindings.insert(key, handler) {
+                self.displaced.insert(key, displaced);
+            }
+        }
+        self.active = true;
+    }
+
+    fn deactivate(&mut self, key_bindings: &mut KeyBindings<X>) {
+        for key in self.keys.drain(..) {
+            if let Some(handler) = key_bindings.remove(&key) {
+                self.bindings.insert(key, handler);
+            }
+        }
+        key_bindings.extend(self.displaced.drain());
+        self.active = false;
+    }
+}
+
+// The per-workspace key binding overlays registered with a WindowManager along with the tag of
+// the workspace that they were last updated for.
+pub(crate) struct KeyBindingOverlays<X: XConn> {
+    overlays: Vec<KeyBindingOverlay<X>>,
+    tag: Option<String>,
+}
+
+impl<X: XConn> Default for KeyBindingOverlays<X> {
+    fn default() -> Self {
+        Self {
+            overlays: Vec::new(),
+            tag: None,
+        }
+    }
+}
+
+impl<X: XConn> fmt::Debug for KeyBindingOverlays<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyBindingOverlays")
+            .field("n_overlays", &self.overlays.len())
+            .field("tag", &self.tag)
+            .finish()
+    }
+}
+
+impl<X: XConn> KeyBindingOverlays<X> {
+    pub(crate) fn push(&mut self, matches: Box<dyn Fn(&str) -> bool>, bindings: KeyBindings<X>) {
+        self.overlays.push(KeyBindingOverlay {
+            matches,
+            bindings,
+            displaced: HashMap::new(),
+            keys: Vec::new(),
+            active: false,
+        });
+        // Force the overlays to be re-applied the next time they are updated
+        self.tag = None;
+    }
+
+    // Swap in the overlays matching the given workspace tag, returning true if the set of
+    // active overlays changed.
+    pub(crate) fn update(&mut self, tag: &str, key_bindings: &mut KeyBindings<X>) -> bool {
+        if self.tag.as_deref() == Some(tag) {
+            return false;
+        }
+
+        let before: Vec<bool> = self.overlays.iter().map(|o| o.active).collect();
+        self.deactivate_all(key_bindings);
+        self.activate_matching(tag, key_bindings);
+        self.tag = Some(tag.to_string());
+
+        self.overlays.iter().map(|o| o.active).ne(before)
+    }
+
+    // Update the key codes of both the normal bindings and all overlays after a change to
+    // the keyboard mapping.
+    pub(crate) fn remap(
+        &mut self,
+        key_bindings: &mut KeyBindings<X>,
+        keymap: &Keymap,
+        previous: &Keymap,
+    ) {
+        self.deactivate_all(key_bindings);
+        remap_keybindings(key_bindings, keymap, previous);
+        for o in self.overlays.iter_mut() {
+            remap_keybindings(&mut o.bindings, keymap, previous);
+        }
+
+        if let Some(tag) = self.tag.clone() {
+            self.activate_matching(&tag, key_bindings);
+        }
+    }
+
+    // Overlays are deactivated in reverse order so that overlapping overlays restore the
+    // bindings that they displaced correctly.
+    fn deactivate_all(&mut self, key_bindings: &mut KeyBindings<X>) {
+        for o in self.overlays.iter_mut().rev().filter(|o| o.active) {
+            o.deactivate(key_bindings);
+        }
+    }
+
+    fn activate_matching(&mut self, tag: &str, key_bindings: &mut KeyBindings<X>) {
+        for o in self.overlays.iter_mut().filter(|o| (o.matches)(tag)) {
+            o.activate(key_bindings);
+        }
+    }
+}
+
 /// Abstraction layer for working with key presses
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KeyPress {
@@ -230,6 +1289,10 @@ pub enum MouseButton {
     ScrollUp,
     /// 5
     ScrollDown,
+    /// 6
+    ScrollLeft,
+    /// 7
+    ScrollRight,
 }
 
 impl From<MouseButton> for u8 {
@@ -240,6 +1303,8 @@ impl From<MouseButton> for u8 {
             MouseButton::Right => 3,
             MouseButton::ScrollUp => 4,
             MouseButton::ScrollDown => 5,
+            MouseButton::ScrollLeft => 6,
+            MouseButton::ScrollRight => 7,
         }
     }
 }
@@ -254,6 +1319,8 @@ impl TryFrom<u8> for MouseButton {
             3 => Ok(Self::Right),
             4 => Ok(Self::ScrollUp),
             5 => Ok(Self::ScrollDown),
+            6 => Ok(Self::ScrollLeft),
+            7 => Ok(Self::ScrollRight),
             _ => Err(Error::UnknownMouseButton { button: n }),
         }
     }
@@ -352,17 +1419,33 @@ pub enum MouseEventKind {
     Release,
     /// The mouse was moved while a button was held
     Motion,
+    /// A button was pressed for the second time in quick succession on the same window (see
+    /// [Config::double_click_interval][0]).
+    ///
+    /// If there is a binding for the double click then it is run in place of the binding for
+    /// the second press. Otherwise the second press is handled as a normal [Press][1].
+    ///
+    ///   [0]: crate::core::Config::double_click_interval
+    ///   [1]: MouseEventKind::Press
+    DoubleClick,
 }
 
 /// A mouse movement or button event
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MouseEvent {
-    /// The ID of the window that was contained the click
+    /// The ID of the window that contained the click: either the top level client window
+    /// under the pointer or the root window if there is no client under the pointer.
+    ///
+    /// Previous versions of penrose always reported the root window here for grabbed bindings,
+    /// so bindings that need the window under the pointer no longer need to look it up.
     pub id: Xid,
     /// Absolute coordinate of the event
     pub rpt: Point,
-    /// Coordinate of the event relative to top-left of the window itself
+    /// Coordinate of the event relative to the top-left of the window that received it.
+    ///
+    /// For grabbed bindings this is the root window, so this matches `rpt` rather than being
+    /// relative to the client window given by `id`.
     pub wpt: Point,
     /// The modifier and button code that was received
     pub state: MouseState,
@@ -1,19 +1,25 @@
 //! XEvent handlers for use in the main event loop;
 use crate::{
     core::{
-        bindings::{KeyBindings, KeyCode, MouseBindings, MouseEvent},
+        bindings::{
+            describe_keybindings, ActiveChord, HeldKey, KeyBindingOverlays, KeyBindings,
+            KeyChordHint, KeyCode, KeyEventHandler, MacroStep, MouseBindings, MouseEvent,
+            MouseEventKind, MouseState,
+        },
         State, Xid,
     },
-    pure::geometry::Point,
+    pure::geometry::{Point, Rect},
+    util::CommandOutput,
     x::{
         atom::Atom,
-        event::{ClientMessage, ClientMessageKind, ConfigureEvent, PointerChange},
-        property::{Prop, WmHints},
-        ClientConfig, XConn, XConnExt,
+        event::{ClientMessage, ClientMessageKind, ConfigureEvent, PointerChange, PropertyEvent},
+        property::{Prop, WmHints, WmHintsFlags},
+        update_motif_hints, ClientConfig, PointerWarp, XConn, XConnExt,
     },
     Result,
 };
-use tracing::{error, info, trace};
+use std::{collections::HashMap, fmt, io, sync::mpsc, time::Instant};
+use tracing::{debug, error, info, trace, warn};
 
 // Currently no client messages are handled by default (see the ewmh extension for some examples of messages
 // that are handled when that is enabled)
@@ -31,23 +37,143 @@ pub(crate) fn mapping_notify<X: XConn>(
 ) -> Result<()> {
     trace!("grabbing key and mouse bindings");
     let key_codes: Vec<_> = key_bindings.keys().copied().collect();
-    let mouse_states: Vec<_> = mouse_bindings
-        .keys()
-        .map(|(_, state)| state.clone())
-        .collect();
+    let mouse_states = grabbed_mouse_states(mouse_bindings);
 
     x.grab(&key_codes, &mouse_states)
 }
 
+// Fetch the current keyboard mapping and, if it has changed since we last saw it, move key
+// bindings to the keys that now produce their keysyms before regrabbing them.
+pub(crate) fn keyboard_mapping_changed<X: XConn>(
+    key_bindings: &mut KeyBindings<X>,
+    overlays: &mut KeyBindingOverlays<X>,
+    mouse_bindings: &MouseBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    match x.keymap() {
+        Ok(keymap) => {
+            if let Some(previous) = state.bindings.keymap.as_ref().filter(|&k| *k != keymap) {
+                debug!("keyboard mapping changed: updating key bindings");
+                overlays.remap(key_bindings, &keymap, previous);
+            }
+            state.bindings.keymap = Some(keymap);
+        }
+
+        Err(e) => warn!(%e, "unable to fetch the current keyboard mapping"),
+    }
+    refresh_binding_descriptions(key_bindings, state);
+
+    // The normal bindings are grabbed again once the active chord or modal grab finishes
+    if grab_suspended(state) {
+        return Ok(());
+    }
+
+    mapping_notify(key_bindings, mouse_bindings, x)
+}
+
+// Swap in the key binding overlays for the focused workspace if it has changed, regrabbing
+// key bindings if the active overlays have changed as a result.
+pub(crate) fn update_binding_overlays<X: XConn>(
+    key_bindings: &mut KeyBindings<X>,
+    overlays: &mut KeyBindingOverlays<X>,
+    mouse_bindings: &MouseBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let tag = state.client_set.current_tag().to_string();
+    if !overlays.update(&tag, key_bindings) {
+        return Ok(());
+    }
+
+    trace!(%tag, "active key binding overlays changed");
+    refresh_binding_descriptions(key_bindings, state);
+    if grab_suspended(state) {
+        return Ok(());
+    }
+
+    mapping_notify(key_bindings, mouse_bindings, x)
+}
+
+// Update the descriptions of the current key bindings returned by State::binding_descriptions
+pub(crate) fn refresh_binding_descriptions<X: XConn>(
+    key_bindings: &KeyBindings<X>,
+    state: &mut State<X>,
+) {
+    let keymap = state.bindings.keymap.clone().unwrap_or_default();
+    state.bindings.descriptions = describe_keybindings(key_bindings, &keymap);
+}
+
+// Mouse bindings without modifiers are not grabbed, as they would prevent clients from
+// receiving any events for that button. Instead they are only seen when the pointer is over
+// the root window itself.
+pub(crate) fn grabbed_mouse_states<X: XConn>(mouse_bindings: &MouseBindings<X>) -> Vec<MouseState> {
+    mouse_bindings
+        .keys()
+        .filter(|(_, state)| !state.modifiers.is_empty())
+        .map(|(_, state)| state.clone())
+        .collect()
+}
+
 pub(crate) fn keypress<X: XConn>(
     key: KeyCode,
     bindings: &mut KeyBindings<X>,
+    mouse_bindings: &MouseBindings<X>,
     state: &mut State<X>,
     x: &X,
 ) -> Result<()> {
+    let recording = state.bindings.macros.recording.is_some();
+    let handled = state.bindings.active_chord.is_some() || bindings.contains_key(&key);
+    let res = run_keypress(key, bindings, mouse_bindings, state, x);
+    if handled {
+        state
+            .bindings
+            .macros
+            .record(recording, MacroStep::Press(key));
+    }
+
+    res
+}
+
+fn run_keypress<X: XConn>(
+    key: KeyCode,
+    bindings: &mut KeyBindings<X>,
+    mouse_bindings: &MouseBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    if let Some(chord) = state.bindings.active_chord.take() {
+        return chord_keypress(key, chord, bindings, mouse_bindings, state, x);
+    }
+
     if let Some(action) = bindings.get_mut(&key) {
+        if action.runs_on_release() {
+            trace!(?key, "waiting for key release");
+            state.bindings.pending_key_releases.insert(key.code, key);
+            return Ok(());
+        }
+
+        if let Some(rate) = action.repeat_rate() {
+            if state
+                .bindings
+                .held_key
+                .is_some_and(|h| h.key.code == key.code)
+            {
+                trace!(?key, "ignoring auto-repeated key press");
+                return Ok(());
+            }
+
+            trace!(?key, ?rate, "repeating key binding while held");
+            state.bindings.held_key = Some(HeldKey {
+                key,
+                next: Instant::now() + rate.delay,
+                interval: rate.interval,
+            });
+            state.wake_after(rate.delay);
+        }
+
         trace!(?key, "running user keybinding");
-        if let Err(error) = action.call(state, x) {
+        if let Err(error) = call_with_count(action, state, x) {
             error!(%error, ?key, "error running user keybinding");
             return Err(error);
         }
@@ -56,12 +182,256 @@ pub(crate) fn keypress<X: XConn>(
     Ok(())
 }
 
+// Run a key binding, passing it any pending count prefix. The count is consumed unless the
+// binding sets a new pending count (by entering another digit or opening a chord).
+fn call_with_count<X: XConn>(
+    action: &mut Box<dyn KeyEventHandler<X>>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    state.bindings.count = state.bindings.pending_count.take();
+    let res = action.call(state, x);
+    state.bindings.count = None;
+
+    res
+}
+
+// Run the release binding that was pressed using the given key (if there is one). Bindings are
+// looked up using the modifiers held when the key was pressed as they may have already been
+// released.
+pub(crate) fn keyrelease<X: XConn>(
+    key: KeyCode,
+    bindings: &mut KeyBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let recording = state.bindings.macros.recording.is_some();
+    if state
+        .bindings
+        .held_key
+        .is_some_and(|h| h.key.code == key.code)
+    {
+        trace!(?key, "held key released: no longer repeating");
+        state.bindings.held_key = None;
+        state
+            .bindings
+            .macros
+            .record(recording, MacroStep::Release(key));
+        return Ok(());
+    }
+
+    let pressed = match state.bindings.pending_key_releases.remove(&key.code) {
+        Some(pressed) => pressed,
+        None => return Ok(()),
+    };
+
+    if let Some(action) = bindings.get_mut(&pressed) {
+        trace!(key = ?pressed, "running user key release binding");
+        if let Err(error) = call_with_count(action, state, x) {
+            error!(%error, key = ?pressed, "error running user key release binding");
+            return Err(error);
+        }
+    }
+    state
+        .bindings
+        .macros
+        .record(recording, MacroStep::Release(key));
+
+    Ok(())
+}
+
+// Repeat the binding for the key currently being held down if it is due, scheduling the
+// next repeat.
+pub(crate) fn pending_key_repeat<X: XConn>(
+    bindings: &mut KeyBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let held = match state.bindings.held_key {
+        Some(held) if Instant::now() >= held.next => held,
+        _ => return Ok(()),
+    };
+
+    // The binding may have been removed or replaced since the key was pressed
+    let action = match bindings.get_mut(&held.key) {
+        Some(action) if action.repeat_rate().is_some() => action,
+        _ => {
+            state.bindings.held_key = None;
+            return Ok(());
+        }
+    };
+
+    state.bindings.held_key = Some(HeldKey {
+        next: Instant::now() + held.interval,
+        ..held
+    });
+    state.wake_after(held.interval);
+
+    trace!(key = ?held.key, "repeating held key binding");
+    if let Err(error) = action.call(state, x) {
+        error!(%error, key = ?held.key, "error repeating held key binding");
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+// Run the binding for the given key from the sub-map of the active chord (if there is one)
+// and then restore the normal bindings, unless the binding opened another chord or the
+// chord is a binding mode that has not been exited.
+fn chord_keypress<X: XConn>(
+    key: KeyCode,
+    chord: ActiveChord<X>,
+    bindings: &KeyBindings<X>,
+    mouse_bindings: &MouseBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let exited = chord.exit.contains(&key);
+    let res = if exited {
+        trace!(?key, "key chord cancelled");
+        state.bindings.pending_count = None;
+        Ok(())
+    } else {
+        match chord.bindings.borrow_mut().get_mut(&key) {
+            Some(action) => {
+                trace!(?key, "running key chord binding");
+                call_with_count(action, state, x)
+            }
+            None => Ok(()),
+        }
+    };
+
+    if state.bindings.active_chord.is_none() {
+        if chord.persistent && !exited {
+            state.bindings.active_chord = Some(chord);
+        } else {
+            end_chord(bindings, mouse_bindings, state, x)?;
+        }
+    }
+
+    if let Err(error) = res {
+        error!(%error, ?key, "error running key chord binding");
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+// Whether or not a modal grab has been started using State::begin_modal_grab, replacing the
+// grab of the normal bindings until it is ended.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ModalGrab {
+    #[default]
+    Inactive,
+    Active,
+    // Ended while handling the current event: the normal bindings need to be grabbed again
+    Ending,
+}
+
+// Whether the normal bindings are currently replaced by the grab for a chord, binding mode or
+// modal grab, in which case they should not be regrabbed.
+fn grab_suspended<X: XConn>(state: &State<X>) -> bool {
+    state.bindings.active_chord.is_some() || state.bindings.modal_grab == ModalGrab::Active
+}
+
+// Apply any binding changes requested through State while handling the last event and
+// regrab bindings if needed. While a chord, binding mode or modal grab is active the grab is
+// left alone: the updated bindings are grabbed when it ends.
+pub(crate) fn pending_binding_changes<X: XConn>(
+    bindings: &mut KeyBindings<X>,
+    mouse_bindings: &mut MouseBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let modal_grab_ended = state.bindings.modal_grab == ModalGrab::Ending;
+    if state.bindings.pending_changes.is_empty() && !modal_grab_ended {
+        return Ok(());
+    }
+
+    if modal_grab_ended {
+        state.bindings.modal_grab = ModalGrab::Inactive;
+    }
+
+    let mut regrab = modal_grab_ended;
+    for change in std::mem::take(&mut state.bindings.pending_changes) {
+        regrab |= change.apply(bindings, mouse_bindings);
+    }
+    refresh_binding_descriptions(bindings, state);
+
+    if regrab && !grab_suspended(state) {
+        trace!("regrabbing modified bindings");
+        mapping_notify(bindings, mouse_bindings, x)?;
+    }
+
+    Ok(())
+}
+
+// Cancel the active key chord if it has been waiting longer than its timeout.
+pub(crate) fn pending_chord_timeout<X: XConn>(
+    bindings: &KeyBindings<X>,
+    mouse_bindings: &MouseBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    match &state.bindings.active_chord {
+        Some(ActiveChord {
+            deadline: Some(t), ..
+        }) if Instant::now() >= *t => (),
+        _ => return Ok(()),
+    }
+
+    debug!("key chord timed out");
+    state.bindings.active_chord = None;
+
+    end_chord(bindings, mouse_bindings, state, x)
+}
+
+fn end_chord<X: XConn>(
+    bindings: &KeyBindings<X>,
+    mouse_bindings: &MouseBindings<X>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    state.unpublish::<KeyChordHint>();
+    if state.bindings.modal_grab != ModalGrab::Active {
+        mapping_notify(bindings, mouse_bindings, x)?;
+    }
+
+    if state.bindings.active_mode.take().is_some() {
+        trace!("exiting binding mode");
+        run_mode_change_hook(state, x);
+    }
+
+    x.refresh(state)
+}
+
+// Run the user mode change hook after a binding mode has been entered or exited.
+pub(crate) fn run_mode_change_hook<X: XConn>(state: &mut State<X>, x: &X) {
+    let mut hook = state.config.mode_change_hook.take();
+    if let Some(ref mut h) = hook {
+        trace!(mode = ?state.bindings.active_mode, "running user mode change hook");
+        if let Err(e) = h.call(state, x) {
+            error!(%e, "error returned from user mode change hook");
+        }
+    }
+    state.config.mode_change_hook = hook;
+}
+
 pub(crate) fn mouse_event<X: XConn>(
-    e: MouseEvent,
+    mut e: MouseEvent,
     bindings: &mut MouseBindings<X>,
     state: &mut State<X>,
     x: &X,
 ) -> Result<()> {
+    if e.kind == MouseEventKind::Press && is_double_click(&e, state) {
+        let double_click = (MouseEventKind::DoubleClick, e.state.clone());
+        if bindings.contains_key(&double_click) {
+            trace!(?e, "mouse press is a double click");
+            e.kind = MouseEventKind::DoubleClick;
+        }
+    }
+
     if let Some(action) = bindings.get_mut(&(e.kind, e.state.clone())) {
         if let Err(error) = action.call(&e, state, x) {
             error!(%error, ?e, "error running user mouse binding");
@@ -72,6 +442,21 @@ pub(crate) fn mouse_event<X: XConn>(
     Ok(())
 }
 
+// Record the given button press, returning true if it completes a double click. The press that
+// completes a double click is not recorded so that a third press starts a new one.
+fn is_double_click<X: XConn>(e: &MouseEvent, state: &mut State<X>) -> bool {
+    let now = Instant::now();
+    let interval = state.config.double_click_interval;
+
+    match state.bindings.last_click.take() {
+        Some((id, ref ms, t)) if id == e.id && *ms == e.state && now - t <= interval => true,
+        _ => {
+            state.bindings.last_click = Some((e.id, e.state.clone(), now));
+            false
+        }
+    }
+}
+
 pub(crate) fn configure_request<X: XConn>(
     ConfigureEvent { id, r, .. }: &ConfigureEvent,
     state: &mut State<X>,
@@ -88,9 +473,47 @@ pub(crate) fn map_request<X: XConn>(client: Xid, state: &mut State<X>, x: &X) ->
     trace!(?client, "handling new map request");
     let attrs = x.get_window_attributes(client)?;
 
-    if !state.client_set.contains(&client) && !attrs.override_redirect {
-        trace!(?client, "managing client");
-        x.manage(client, state)?;
+    if state.client_set.contains(&client) || attrs.override_redirect {
+        return Ok(());
+    }
+
+    if is_splash(client, x) {
+        trace!(?client, "showing unmanaged splash window");
+        return show_splash(client, state, x);
+    }
+
+    trace!(?client, "managing client");
+    x.manage(client, state)
+}
+
+fn is_splash<X: XConn>(client: Xid, x: &X) -> bool {
+    match x.get_prop(client, Atom::NetWmWindowType.as_ref()) {
+        Ok(Some(Prop::Atom(atoms))) => atoms
+            .iter()
+            .any(|a| a == Atom::NetWindowTypeSplash.as_ref()),
+        _ => false,
+    }
+}
+
+// Splash windows are centered above everything else on the focused screen without being
+// managed so that they never disturb the layout of the current workspace.
+fn show_splash<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    // Splash windows that are too large to fit on the screen are left where they are
+    let r_initial = x.client_geometry(client)?;
+    let r = r_initial
+        .centered_in(&state.client_set.screens.focus.r)
+        .unwrap_or(r_initial);
+    x.set_client_config(client, &[ClientConfig::Position(r), ClientConfig::StackTop])?;
+    x.map(client)?;
+
+    let timeout = state.config.splash_timeout;
+    state
+        .client_timeouts
+        .splashes
+        .insert(client, timeout.map(|t| Instant::now() + t));
+
+    if let Some(timeout) = timeout {
+        state.wake_after(timeout);
     }
 
     Ok(())
@@ -101,12 +524,19 @@ pub(crate) fn destroy<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Res
     x.unmanage(client, state)?;
     state.mapped.remove(&client);
     state.pending_unmap.remove(&client);
+    state.client_timeouts.kills.remove(&client);
+    state.client_timeouts.splashes.remove(&client);
 
     Ok(())
 }
 
 // Expected unmap events are tracked in pending_unmap. We ignore expected unmaps.
 pub(crate) fn unmap_notify<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    if state.client_timeouts.splashes.remove(&client).is_some() {
+        trace!(?client, "splash window unmapped");
+        return Ok(());
+    }
+
     let expected = *state.pending_unmap.get(&client).unwrap_or(&0);
 
     if expected == 0 {
@@ -124,12 +554,9 @@ pub(crate) fn unmap_notify<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -
 }
 
 pub(crate) fn focus_in<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
-    let accepts_focus = match x.get_prop(client, Atom::WmHints.as_ref()) {
-        Ok(Some(Prop::WmHints(WmHints { accepts_input, .. }))) => accepts_input,
-        _ => true,
-    };
+    let model = x.input_model(client).unwrap_or_default();
 
-    if accepts_focus {
+    if model.accepts_input() {
         x.focus(client)?;
         x.set_prop(
             x.root(),
@@ -137,7 +564,7 @@ pub(crate) fn focus_in<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Re
             Prop::Window(vec![client]),
         )?;
         x.set_active_client(client, state)?;
-    } else {
+    } else if model.takes_focus() {
         let msg = ClientMessageKind::TakeFocus(client).as_message(x)?;
         x.send_client_message(msg)?;
     }
@@ -145,17 +572,235 @@ pub(crate) fn focus_in<X: XConn>(client: Xid, state: &mut State<X>, x: &X) -> Re
     Ok(())
 }
 
+// Track changes to the urgency state and decoration hints of managed clients
+pub(crate) fn property_notify<X: XConn>(
+    e: &PropertyEvent,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    if e.is_root || !state.client_set.contains(&e.id) {
+        return Ok(());
+    }
+
+    if e.atom == Atom::MotifWmHints.as_ref() {
+        trace!(id = %e.id, "client decoration hints changed");
+        update_motif_hints(e.id, state, x)?;
+        return x.refresh(state);
+    }
+
+    let urgent = match e.atom.as_ref() {
+        "WM_HINTS" => match x.get_prop(e.id, Atom::WmHints.as_ref()) {
+            Ok(Some(Prop::WmHints(WmHints { flags, .. }))) => {
+                flags.contains(WmHintsFlags::URGENCY_HINT)
+            }
+            _ => false,
+        },
+
+        "_NET_WM_STATE" => x
+            .has_net_wm_state(e.id, Atom::NetWmStateDemandsAttention)
+            .unwrap_or(false),
+
+        _ => return Ok(()),
+    };
+
+    trace!(id = %e.id, atom = e.atom, urgent, "checking client urgency state");
+    x.set_client_urgency(e.id, urgent, state)
+}
+
 pub(crate) fn enter<X: XConn>(p: PointerChange, state: &mut State<X>, x: &X) -> Result<()> {
-    if state.config.focus_follow_mouse {
-        x.modify_and_refresh(state, |cs| {
+    if !state.config.focus_follow_mouse {
+        return Ok(());
+    }
+
+    if state.config.focus_follow_mouse_requires_motion
+        && state.focus.pointer_at_refresh == Some(p.abs)
+    {
+        trace!(id = %p.id, "pointer has not moved since last refresh: not following mouse");
+        return Ok(());
+    }
+
+    match state.config.focus_follow_mouse_delay {
+        Some(delay) if !delay.is_zero() => {
+            trace!(id = %p.id, ?delay, "delaying focus change");
+            state.focus.pending = Some((p.id, Instant::now() + delay));
+            state.wake_after(delay);
+
+            Ok(())
+        }
+
+        _ => x.modify_and_refresh(state, |cs| {
             cs.focus_client(&p.id);
-        })
-    } else {
-        Ok(())
+        }),
     }
 }
 
+// Focus the client the pointer entered if it has remained inside of it for the configured
+// focus_follow_mouse_delay.
+pub(crate) fn pending_focus<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let id = match state.focus.pending {
+        Some((id, due)) if Instant::now() >= due => id,
+        _ => return Ok(()),
+    };
+    state.focus.pending = None;
+
+    if !state.config.focus_follow_mouse {
+        return Ok(());
+    }
+
+    trace!(%id, "applying delayed focus change");
+    // The pointer is already inside of the client so it should not be warped
+    let warp = std::mem::replace(&mut state.config.pointer_warp, PointerWarp::Never);
+    let res = x.modify_and_refresh(state, |cs| {
+        cs.focus_client(&id);
+    });
+    state.config.pointer_warp = warp;
+
+    res
+}
+
+// Clients that were asked to close and will be forcibly killed if they are still running at
+// the given deadline, along with splash windows and the deadline (if any) for hiding them.
+#[derive(Debug, Default)]
+pub(crate) struct ClientTimeouts {
+    pub(crate) kills: HashMap<Xid, Instant>,
+    pub(crate) splashes: HashMap<Xid, Option<Instant>>,
+}
+
+type OutputHandler<X> = Box<dyn FnOnce(CommandOutput, &mut State<X>, &X) -> Result<()>>;
+
+// A command started using State::spawn_and_capture that has not yet had its output handled.
+pub(crate) struct PendingOutput<X: XConn> {
+    cmd: String,
+    rx: mpsc::Receiver<io::Result<CommandOutput>>,
+    handler: OutputHandler<X>,
+}
+
+impl<X: XConn> fmt::Debug for PendingOutput<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingOutput")
+            .field("cmd", &self.cmd)
+            .finish()
+    }
+}
+
+impl<X: XConn> PendingOutput<X> {
+    pub(crate) fn new(
+        cmd: String,
+        rx: mpsc::Receiver<io::Result<CommandOutput>>,
+        handler: OutputHandler<X>,
+    ) -> Self {
+        Self { cmd, rx, handler }
+    }
+}
+
+// Run the handlers for any commands started using State::spawn_and_capture that have exited.
+pub(crate) fn pending_output<X: XConn>(state: &mut State<X>, x: &X) {
+    if state.deferred.output.is_empty() {
+        return;
+    }
+
+    for p in std::mem::take(&mut state.deferred.output) {
+        let PendingOutput { cmd, rx, handler } = p;
+        match rx.try_recv() {
+            Ok(Ok(output)) => {
+                trace!(%cmd, status = output.status, "running handler for command output");
+                if let Err(error) = handler(output, state, x) {
+                    error!(%error, %cmd, "error running command output handler");
+                }
+            }
+            Ok(Err(error)) => error!(%error, %cmd, "unable to run command"),
+            Err(mpsc::TryRecvError::Empty) => {
+                state
+                    .deferred
+                    .output
+                    .push(PendingOutput { cmd, rx, handler })
+            }
+            Err(mpsc::TryRecvError::Disconnected) => {
+                error!(%cmd, "command exited without returning its output")
+            }
+        }
+    }
+}
+
+// Forcibly kill any clients that are still alive after being asked to close once their
+// configured timeout has elapsed.
+pub(crate) fn pending_kills<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    if state.client_timeouts.kills.is_empty() {
+        return Ok(());
+    }
+
+    let now = Instant::now();
+    let due: Vec<Xid> = state
+        .client_timeouts
+        .kills
+        .iter()
+        .filter(|&(_, &t)| now >= t)
+        .map(|(&id, _)| id)
+        .collect();
+
+    for id in due {
+        state.client_timeouts.kills.remove(&id);
+        // Clients that have already exited will fail to return their geometry
+        if x.client_geometry(id).is_ok() {
+            warn!(%id, "client did not close in time: forcibly killing it");
+            x.force_kill(id)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Hide any splash windows that are still visible once the configured splash timeout has
+// elapsed. They remain tracked until the resulting unmap notification is received.
+pub(crate) fn pending_splash_timeouts<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let now = Instant::now();
+    let due: Vec<Xid> = state
+        .client_timeouts
+        .splashes
+        .iter()
+        .filter(|&(_, t)| matches!(t, Some(t) if now >= *t))
+        .map(|(&id, _)| id)
+        .collect();
+
+    for id in due {
+        state.client_timeouts.splashes.insert(id, None);
+        warn!(%id, "splash window did not close in time: hiding it");
+        x.unmap(id)?;
+    }
+
+    Ok(())
+}
+
+// Clear the urgency of any clients that have been urgent for longer than the configured
+// urgency timeout.
+pub(crate) fn pending_urgency_timeouts<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    if state.urgency.deadlines.is_empty() {
+        return Ok(());
+    }
+
+    let now = Instant::now();
+    let due: Vec<Xid> = state
+        .urgency
+        .deadlines
+        .iter()
+        .filter(|&(_, &t)| now >= t)
+        .map(|(&id, _)| id)
+        .collect();
+
+    for id in due {
+        debug!(%id, "urgency timeout elapsed: clearing urgency");
+        x.set_client_urgency(id, false, state)?;
+    }
+
+    Ok(())
+}
+
 pub(crate) fn leave<X: XConn>(p: PointerChange, state: &mut State<X>, x: &X) -> Result<()> {
+    if matches!(state.focus.pending, Some((id, _)) if id == p.id) {
+        trace!(id = %p.id, "pointer left client before delayed focus change");
+        state.focus.pending = None;
+    }
+
     if p.id == state.root() && !p.same_screen {
         x.focus(p.id)?;
         set_screen_from_point(p.abs, state, x)?;
@@ -164,16 +809,74 @@ pub(crate) fn leave<X: XConn>(p: PointerChange, state: &mut State<X>, x: &X) ->
     Ok(())
 }
 
+// RandR sends several notifications for a single change to the connected outputs so we only
+// update our state when the screen geometry we get back actually differs from what we have.
+// Screens that are entirely covered by another screen are mirroring it (such as a projector
+// cloning a laptop display) and are merged into the screen covering them so that there is only
+// a single logical screen for the shared area. Returns the indices of the remaining screens in
+// the order they were reported.
+pub(crate) fn distinct_screens(rects: &[Rect]) -> Vec<usize> {
+    let mirrors = |i: usize, r: &Rect| {
+        rects
+            .iter()
+            .enumerate()
+            .any(|(j, other)| j != i && other.contains(r) && (other != r || j < i))
+    };
+
+    (0..rects.len())
+        .filter(|&i| !mirrors(i, &rects[i]))
+        .collect()
+}
+
+// The screens reported by the X server with mirrored outputs merged (see distinct_screens).
+pub(crate) fn logical_screens<X: XConn>(x: &X) -> Result<Vec<Rect>> {
+    let rects = x.screen_details()?;
+    let distinct = distinct_screens(&rects);
+    if distinct.len() < rects.len() {
+        debug!(?rects, "merging mirrored screens");
+    }
+
+    Ok(distinct.into_iter().map(|i| rects[i]).collect())
+}
+
 pub(crate) fn detect_screens<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
     info!("re-detecting screens");
-    let rects = x.screen_details()?;
+    let rects = logical_screens(x)?;
+    let current: Vec<Rect> = state.client_set.screens().map(|s| s.r).collect();
+    if rects == current {
+        trace!("screen geometry unchanged");
+        // The primary output and rotation can be changed without changing the screen geometry
+        state.update_primary_screen(x);
+        if state.update_screen_rotations(x) {
+            x.refresh(state)?;
+        }
+        return Ok(());
+    }
+
     info!(?rects, "found screens");
+    state.client_set.update_screens(rects)?;
+    state.update_screen_scales(x);
+    state.update_primary_screen(x);
+    state.update_monitor_configs(x);
+    state.update_screen_rotations(x);
+    x.refresh(state)?;
+
+    let mut hook = state.config.screens_changed_hook.take();
+    let res = match hook {
+        Some(ref mut h) => {
+            trace!("running user screens changed hook");
+            h.call(state, x)
+        }
+        None => Ok(()),
+    };
+    state.config.screens_changed_hook = hook;
 
-    state.client_set.update_screens(rects)
+    res
 }
 
 pub(crate) fn screen_change<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
     trace!("screen changed");
+    detect_screens(state, x)?;
     set_screen_from_point(x.cursor_position()?, state, x)
 }
 
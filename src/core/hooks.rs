@@ -54,6 +54,15 @@
 //! >           triggering a refresh directly will do is run the refresh twice: once with
 //! >           the inital state of the client before your hook was applied and once after.
 //!
+//! ### Urgency Change Hooks
+//!
+//! [`UrgencyChangeHook`]s are run each time a client is marked as urgent or has its urgency
+//! cleared, along with the client in question and its new urgency status. Urgency is cleared
+//! when the client is focused or removed, and can optionally be cleared after a timeout or
+//! once the client's workspace is viewed (see [Config::urgency_timeout][3] and
+//! [Config::clear_urgency_on_view][4]). This allows you to keep track of urgent clients in a
+//! status bar or to notify yourself about them in some other way.
+//!
 //! ### Layout Hooks
 //!
 //! Finally we have [`LayoutHook`]s which operate a little differently, in that they have
@@ -86,6 +95,8 @@
 //!   [0]: crate::extensions::hooks::manage
 //!   [1]: crate::core::layout::Layout
 //!   [2]: crate::core::Config
+//!   [3]: crate::core::Config::urgency_timeout
+//!   [4]: crate::core::Config::clear_urgency_on_view
 
 use crate::{
     core::{layout::LayoutTransformer, State},
@@ -260,6 +271,86 @@ where
     }
 }
 
+/// Action to run when a client is marked as urgent or has its urgency cleared.
+///
+/// Urgency change hooks should _not_ trigger refreshes of state directly: they are called
+/// as part of handling the event or refresh that changed the client's urgency.
+pub trait UrgencyChangeHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook
+    fn call(&mut self, client: Xid, urgent: bool, state: &mut State<X>, x: &X) -> Result<()>;
+
+    /// Convert to a trait object
+    fn boxed(self) -> Box<dyn UrgencyChangeHook<X>>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+
+    /// Compose this hook with another [UrgencyChangeHook].
+    fn then<H>(self, next: H) -> ComposedUrgencyChangeHook<X>
+    where
+        H: UrgencyChangeHook<X> + 'static,
+        Self: Sized + 'static,
+    {
+        ComposedUrgencyChangeHook {
+            first: Box::new(self),
+            second: Box::new(next),
+        }
+    }
+
+    /// Compose this hook with a boxed [UrgencyChangeHook].
+    fn then_boxed(self, next: Box<dyn UrgencyChangeHook<X>>) -> Box<dyn UrgencyChangeHook<X>>
+    where
+        Self: Sized + 'static,
+        X: 'static,
+    {
+        Box::new(ComposedUrgencyChangeHook {
+            first: Box::new(self),
+            second: next,
+        })
+    }
+}
+
+impl<X: XConn> fmt::Debug for Box<dyn UrgencyChangeHook<X>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrgencyChangeHook").finish()
+    }
+}
+
+/// The result of composing two urgency change hooks using `then`
+#[derive(Debug)]
+pub struct ComposedUrgencyChangeHook<X>
+where
+    X: XConn,
+{
+    first: Box<dyn UrgencyChangeHook<X>>,
+    second: Box<dyn UrgencyChangeHook<X>>,
+}
+
+impl<X> UrgencyChangeHook<X> for ComposedUrgencyChangeHook<X>
+where
+    X: XConn,
+{
+    fn call(&mut self, client: Xid, urgent: bool, state: &mut State<X>, x: &X) -> Result<()> {
+        self.first.call(client, urgent, state, x)?;
+        self.second.call(client, urgent, state, x)
+    }
+}
+
+impl<F, X> UrgencyChangeHook<X> for F
+where
+    F: FnMut(Xid, bool, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, client: Xid, urgent: bool, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(client, urgent, state, x)
+    }
+}
+
 /// An arbitrary action that can be run and modify [State]
 pub trait StateHook<X>
 where
@@ -91,6 +91,23 @@ pub trait Layout {
         (None, vec![])
     }
 
+    /// The border width (in pixels) to use for clients positioned by this [Layout] in place
+    /// of the `border_width` set in [Config][crate::core::Config].
+    ///
+    /// The default implementation of this method returns `None`, leaving the border width
+    /// unchanged.
+    fn border_width(&self) -> Option<u32> {
+        None
+    }
+
+    /// The current values of any user adjustable parameters of this [Layout] as pairs of
+    /// parameter names and display values, for presenting the state of the layout to the user.
+    ///
+    /// The default implementation of this method returns no parameters.
+    fn parameters(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+
     /// Process a dynamic [Message].
     ///
     /// See the trait level docs for details on what is possible with messages.
@@ -204,6 +221,14 @@ impl Layout for LayoutStack {
         (None, self.run_and_replace(|l| l.layout_empty(r)))
     }
 
+    fn border_width(&self) -> Option<u32> {
+        self.focus.border_width()
+    }
+
+    fn parameters(&self) -> Vec<(String, String)> {
+        self.focus.parameters()
+    }
+
     fn handle_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
         let new_focus = self.focus.handle_message(m);
 
@@ -33,6 +33,22 @@ pub trait LayoutTransformer: Clone + Sized + 'static {
         self.swap_inner(Box::new(Monocle))
     }
 
+    /// The same as [Layout::border_width] but for [LayoutTransformer] itself.
+    ///
+    /// The default implementation of this method returns `None`: implementations will typically
+    /// want to defer to the border width of the inner [Layout].
+    fn transformed_border_width(&self) -> Option<u32> {
+        None
+    }
+
+    /// The same as [Layout::parameters] but for [LayoutTransformer] itself.
+    ///
+    /// The default implementation of this method returns no parameters: implementations will
+    /// typically want to defer to the parameters of the inner [Layout].
+    fn transformed_parameters(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+
     /// Modify the initial [Rect] that will be passed to the inner [Layout].
     ///
     /// The default implementation of this method leaves the initial Rect unchanged.
@@ -109,6 +125,14 @@ where
         self.run_transform(|r, inner| inner.layout_empty(r), r)
     }
 
+    fn border_width(&self) -> Option<u32> {
+        self.transformed_border_width()
+    }
+
+    fn parameters(&self) -> Vec<(String, String)> {
+        self.transformed_parameters()
+    }
+
     fn handle_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
         if let Some(&UnwrapTransformer) = m.downcast_ref() {
             return Some(self.unwrap());
@@ -163,6 +187,14 @@ macro_rules! simple_transformer {
                 &mut self.0
             }
 
+            fn transformed_border_width(&self) -> Option<u32> {
+                self.0.border_width()
+            }
+
+            fn transformed_parameters(&self) -> Vec<(String, String)> {
+                self.0.parameters()
+            }
+
             fn transform_positions(
                 &mut self,
                 r: $crate::pure::geometry::Rect,
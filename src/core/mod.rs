@@ -1,35 +1,84 @@
 //! Core data structures and user facing functionality for the window manager
+use crate::pure::{StackSet, Workspace};
+#[cfg(feature = "wm")]
 use crate::{
-    pure::{geometry::Rect, Diff, ScreenClients, Snapshot, StackSet, Workspace},
+    builtin::layout::messages::{ScreenRotation, SetGaps},
+    pure::{
+        geometry::{Point, Rect, Rotation},
+        Diff, Position, ScreenClients, Snapshot, Stack,
+    },
+    util::{spawn_for_output_with_status, CommandOutput},
     x::{
+        event::ClientMessageKind,
         manage_without_refresh,
-        property::{MapState, WmState},
-        Atom, Prop, WindowAttributes, XConn, XConnExt, XEvent,
+        property::{MapState, MotifDecorations, MotifWmHints, WmState},
+        Atom, FloatingPlacement, FocusPolicy, PointerWarp, Prop, WindowAttributes, XConn, XConnExt,
+        XEvent,
     },
     Color, Error, Result,
 };
+#[cfg(feature = "wm")]
 use anymap::{any::Any, AnyMap};
-use nix::sys::signal::{signal, SigHandler, Signal};
+#[cfg(feature = "wm")]
+use nix::sys::signal::{sigaction, signal, SaFlags, SigAction, SigHandler, SigSet, Signal};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+#[cfg(feature = "wm")]
 use std::{
     any::TypeId,
     cell::RefCell,
     collections::{HashMap, HashSet},
     fmt,
-    ops::Deref,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
+#[cfg(feature = "wm")]
 use tracing::{debug, error, info, span, trace, warn, Level};
 
+#[cfg(feature = "wm")]
 pub mod bindings;
+#[cfg(feature = "wm")]
 pub(crate) mod handle;
+#[cfg(feature = "wm")]
 pub mod hooks;
 pub mod layout;
-
-use bindings::{KeyBindings, MouseBindings};
-use hooks::{EventHook, LayoutHook, ManageHook, StateHook};
+#[cfg(feature = "wm")]
+mod monitor_config;
+#[cfg(feature = "wm")]
+mod restart;
+#[cfg(feature = "wm")]
+mod scaling;
+mod snapshot;
+#[cfg(feature = "wm")]
+mod timers;
+#[cfg(feature = "wm")]
+mod whitelist;
+
+#[cfg(feature = "wm")]
+use bindings::{
+    BindingChange, BindingDescription, BindingState, KeyBindingOverlays, KeyBindings, KeyCode,
+    KeyEventHandler, Keymap, MouseBindings, MouseEventHandler, MouseEventKind, MouseState,
+};
+#[cfg(feature = "wm")]
+use hooks::{EventHook, LayoutHook, ManageHook, StateHook, UrgencyChangeHook};
+#[cfg(feature = "wm")]
 use layout::{Layout, LayoutStack};
+#[cfg(feature = "wm")]
+pub use monitor_config::MonitorConfig;
+#[cfg(feature = "wm")]
+pub use restart::RESTART_STATE_ENV_VAR;
+#[cfg(feature = "wm")]
+pub use scaling::{scale_px, DpiScaling};
+pub use snapshot::StateSnapshot;
+#[cfg(feature = "wm")]
+pub(crate) use whitelist::{check_command, set_active_whitelist};
+#[cfg(feature = "wm")]
+pub use whitelist::{CommandWhitelist, WhitelistEntry};
 
 /// An X11 ID for a given resource
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -69,6 +118,7 @@ pub type ClientSet = StackSet<Xid>;
 pub type ClientSpace = Workspace<Xid>;
 
 /// Mutable internal state for the window manager
+#[cfg(feature = "wm")]
 #[derive(Debug)]
 pub struct State<X>
 where
@@ -79,39 +129,107 @@ where
     /// The pure window manager state
     pub client_set: ClientSet,
     pub(crate) extensions: AnyMap,
+    pub(crate) client_data: HashMap<Xid, AnyMap>,
+    pub(crate) published: AnyMap,
     pub(crate) root: Xid,
     pub(crate) mapped: HashSet<Xid>,
     pub(crate) pending_unmap: HashMap<Xid, usize>,
     pub(crate) current_event: Option<XEvent>,
     pub(crate) diff: Diff<Xid>,
+    pub(crate) urgency: Urgency,
+    pub(crate) focus: FocusState,
+    pub(crate) layout_overrides: LayoutOverrides,
+    pub(crate) client_timeouts: handle::ClientTimeouts,
+    pub(crate) screen_details: ScreenDetails,
+    pub(crate) bindings: BindingState<X>,
+    pub(crate) deferred: timers::Deferred<X>,
     // pub(crate) mouse_focused: bool,
-    // pub(crate) mouse_position: Option<(Point, Point)>,
 }
 
+// Clients that are currently flagged as urgent along with the changes that have not yet been
+// passed to the urgency change hook.
+#[cfg(feature = "wm")]
+#[derive(Debug, Default)]
+pub(crate) struct Urgency {
+    pub(crate) clients: Vec<Xid>,
+    pub(crate) deadlines: HashMap<Xid, Instant>,
+    pub(crate) changes: Vec<(Xid, bool)>,
+}
+
+// Previously focused clients along with any focus change that is waiting on the pointer
+// settling over a client.
+#[cfg(feature = "wm")]
+#[derive(Debug, Default)]
+pub(crate) struct FocusState {
+    pub(crate) history: Vec<Xid>,
+    pub(crate) pending: Option<(Xid, Instant)>,
+    pub(crate) pointer_at_refresh: Option<Point>,
+}
+
+// Per-client changes requested by layouts (keyed by workspace id for hidden clients) along
+// with the border widths last applied on the X server.
+#[cfg(feature = "wm")]
+#[derive(Debug, Default)]
+pub(crate) struct LayoutOverrides {
+    pub(crate) hidden: HashMap<usize, Vec<Xid>>,
+    pub(crate) borders: HashMap<Xid, u32>,
+    pub(crate) applied_borders: HashMap<Xid, u32>,
+}
+
+// Details of each screen that are fetched from the X server, indexed by screen.
+#[cfg(feature = "wm")]
+#[derive(Debug, Default)]
+pub(crate) struct ScreenDetails {
+    pub(crate) scales: Vec<f64>,
+    pub(crate) monitor_configs: Vec<Option<MonitorConfig>>,
+    pub(crate) rotations: Vec<Rotation>,
+}
+
+#[cfg(feature = "wm")]
 impl<X> State<X>
 where
     X: XConn,
 {
     pub(crate) fn try_new(config: Config<X>, x: &X) -> Result<Self> {
-        let mut client_set = StackSet::try_new(
-            config.default_layouts.clone(),
-            config.tags.iter(),
-            x.screen_details()?,
-        )?;
+        let screens = handle::logical_screens(x)?;
+        let mut client_set = if config.per_screen_workspaces {
+            StackSet::try_new_per_screen(
+                config.default_layouts.clone(),
+                config.tags.iter(),
+                screens,
+            )?
+        } else {
+            StackSet::try_new(config.default_layouts.clone(), config.tags.iter(), screens)?
+        };
 
         let ss = client_set.snapshot(vec![]);
         let diff = Diff::new(ss.clone(), ss);
 
-        Ok(Self {
+        let mut state = Self {
             config,
             client_set,
             extensions: AnyMap::new(),
+            client_data: HashMap::new(),
+            published: AnyMap::new(),
             root: x.root(),
             mapped: HashSet::new(),
             pending_unmap: HashMap::new(),
             current_event: None,
             diff,
-        })
+            urgency: Urgency::default(),
+            focus: FocusState::default(),
+            layout_overrides: LayoutOverrides::default(),
+            client_timeouts: handle::ClientTimeouts::default(),
+            screen_details: ScreenDetails::default(),
+            bindings: BindingState::default(),
+            deferred: timers::Deferred::default(),
+        };
+        state.update_screen_scales(x);
+        state.update_primary_screen(x);
+        state.update_monitor_configs(x);
+        state.update_screen_rotations(x);
+
+        Ok(state)
     }
 
     /// The Xid of the root window for the running [WindowManager].
@@ -124,6 +242,129 @@ where
         &self.mapped
     }
 
+    /// The set of clients that are currently flagged as urgent, ordered from oldest to newest.
+    ///
+    /// Clients are marked as urgent by setting the `XUrgencyHint` flag in their `WM_HINTS`
+    /// property or `_NET_WM_STATE_DEMANDS_ATTENTION` in their `_NET_WM_STATE` and are cleared
+    /// once they receive focus, or according to [Config::urgency_timeout] and
+    /// [Config::clear_urgency_on_view] if they are set.
+    pub fn urgent_clients(&self) -> &[Xid] {
+        &self.urgency.clients
+    }
+
+    // Add or remove the given client from the set of urgent clients, returning whether or
+    // not this resulted in a change. Changes are recorded for the urgency change hook.
+    pub(crate) fn update_urgency(&mut self, id: Xid, urgent: bool) -> bool {
+        if !urgent {
+            let n = self.urgency.clients.len();
+            self.urgency.clients.retain(|&c| c != id);
+            self.urgency.deadlines.remove(&id);
+
+            let changed = self.urgency.clients.len() != n;
+            if changed {
+                self.urgency.changes.push((id, false));
+            }
+
+            return changed;
+        }
+
+        let ignore = !self.client_set.contains(&id)
+            || self.client_set.current_client() == Some(&id)
+            || self.urgency.clients.contains(&id);
+
+        if !ignore {
+            self.urgency.clients.push(id);
+            self.urgency.changes.push((id, true));
+            if let Some(timeout) = self.config.urgency_timeout {
+                self.urgency.deadlines.insert(id, Instant::now() + timeout);
+            }
+        }
+
+        !ignore
+    }
+
+    /// Clients that have previously held focus, ordered from most to least recently focused.
+    ///
+    /// Clients are removed from the history once they are no longer managed.
+    pub fn focus_history(&self) -> &[Xid] {
+        &self.focus.history
+    }
+
+    /// The most recently focused client other than the one that currently has focus.
+    pub fn last_focused_client(&self) -> Option<Xid> {
+        let focused = self.client_set.current_client();
+
+        self.focus
+            .history
+            .iter()
+            .find(|&id| Some(id) != focused && self.client_set.contains(id))
+            .copied()
+    }
+
+    // Drop any clients that are no longer managed from the focus history and move the currently
+    // focused client (if there is one) to the front.
+    pub(crate) fn update_focus_history(&mut self) {
+        let client_set = &self.client_set;
+        let focused = client_set.current_client().copied();
+        self.focus
+            .history
+            .retain(|id| Some(*id) != focused && client_set.contains(id));
+
+        if let Some(id) = focused {
+            self.focus.history.insert(0, id);
+        }
+    }
+
+    /// Exclude the given client from the layout of the workspace it is on until
+    /// [State::show_all_clients] is called for that workspace.
+    ///
+    /// Unlike minimizing a client, the client remains in the workspace stack: it is simply not
+    /// given a position by the layout while the remaining clients make use of the available
+    /// space. The focused client of a workspace is never hidden, so hiding it only takes effect
+    /// once focus moves to another client.
+    pub fn hide_client(&mut self, id: Xid) {
+        let ws_id = match self.client_set.tag_for_client(&id) {
+            Some(tag) => self.client_set.workspace(tag).map(|w| w.id()),
+            None => None,
+        };
+
+        if let Some(ws_id) = ws_id {
+            let hidden = self.layout_overrides.hidden.entry(ws_id).or_default();
+            if !hidden.contains(&id) {
+                hidden.push(id);
+            }
+        }
+    }
+
+    /// Restore any clients on the given workspace that were hidden using [State::hide_client].
+    pub fn show_all_clients(&mut self, tag: &str) {
+        if let Some(ws_id) = self.client_set.workspace(tag).map(|w| w.id()) {
+            self.layout_overrides.hidden.remove(&ws_id);
+        }
+    }
+
+    /// The clients on the given workspace that are currently hidden from its layout.
+    pub fn hidden_clients(&self, tag: &str) -> &[Xid] {
+        self.client_set
+            .workspace(tag)
+            .and_then(|w| self.layout_overrides.hidden.get(&w.id()))
+            .map_or(&[], |ids| ids.as_slice())
+    }
+
+    // Drop a client that is no longer managed from the clients hidden from their layout.
+    pub(crate) fn forget_hidden_client(&mut self, id: Xid) {
+        self.layout_overrides.hidden.retain(|_, ids| {
+            ids.retain(|&c| c != id);
+            !ids.is_empty()
+        });
+    }
+
+    /// The on screen positions of all visible clients as computed by the last layout pass,
+    /// ordered from the bottom to the top of the stacking order.
+    pub fn client_positions(&self) -> &[(Xid, Rect)] {
+        &self.diff.after.positions
+    }
+
     /// The event currently being processed.
     pub fn current_event(&self) -> Option<&XEvent> {
         self.current_event.as_ref()
@@ -179,563 +420,3545 @@ where
         self.extensions.insert(Arc::new(RefCell::new(extension)));
     }
 
-    pub(crate) fn position_and_snapshot(&mut self, x: &X) -> Snapshot<Xid> {
-        let positions = self.visible_client_positions(x);
-        self.client_set.snapshot(positions)
+    /// Get the data of type `T` attached to the given client, if there is any.
+    ///
+    /// Data attached to clients using [State::set_client_data] is removed automatically once
+    /// the client is no longer managed by the window manager.
+    pub fn client_data<T: Any>(&self, id: &Xid) -> Option<&T> {
+        self.client_data.get(id)?.get()
     }
 
-    /// Run the per-workspace layouts to get a screen position for each visible client. Floating clients
-    /// are placed above stacked clients, clients per workspace are stacked in the order they are returned
-    /// from the layout.
-    pub(crate) fn visible_client_positions(&mut self, x: &X) -> Vec<(Xid, Rect)> {
-        let mut float_positions: Vec<(Xid, Rect)> = Vec::new();
-        let mut positions: Vec<(Xid, Rect)> = Vec::new();
+    /// Get mutable access to the data of type `T` attached to the given client, if there is any.
+    pub fn client_data_mut<T: Any>(&mut self, id: &Xid) -> Option<&mut T> {
+        self.client_data.get_mut(id)?.get_mut()
+    }
 
-        // pop the layout hook off of `state` so that we can pass state into it
-        let mut hook = self.config.layout_hook.take();
+    /// Attach data of type `T` to the given client, returning any previous data of the same
+    /// type.
+    ///
+    /// Each client can hold one value of each type: wrapping values in a new type allows
+    /// for attaching multiple values that share an underlying representation.
+    ///
+    /// ```no_run
+    /// # use penrose::{core::State, x11rb::RustConn, Xid};
+    /// struct ProjectTag(String);
+    ///
+    /// # fn example(state: &mut State<RustConn>, id: Xid) {
+    /// state.set_client_data(id, ProjectTag("penrose".to_string()));
+    ///
+    /// let tag = state.client_data::<ProjectTag>(&id).map(|t| t.0.as_str());
+    /// assert_eq!(tag, Some("penrose"));
+    /// # }
+    /// ```
+    pub fn set_client_data<T: Any>(&mut self, id: Xid, data: T) -> Option<T> {
+        self.client_data
+            .entry(id)
+            .or_insert_with(AnyMap::new)
+            .insert(data)
+    }
 
-        let scs: Vec<ScreenClients> = self
-            .client_set
-            .screens
+    /// Remove the data of type `T` attached to the given client, returning it if there was any.
+    pub fn remove_client_data<T: Any>(&mut self, id: &Xid) -> Option<T> {
+        let data = self.client_data.get_mut(id)?;
+        let removed = data.remove();
+        if data.is_empty() {
+            self.client_data.remove(id);
+        }
+
+        removed
+    }
+
+    /// All clients that have data of type `T` attached to them, along with that data.
+    pub fn clients_with_data<T: Any>(&self) -> impl Iterator<Item = (Xid, &T)> {
+        self.client_data
             .iter()
-            .map(|s| s.screen_clients(&self.client_set.floating))
-            .collect();
+            .flat_map(|(&id, data)| data.get().map(|d| (id, d)))
+    }
 
-        for (i, sc) in scs.into_iter().enumerate() {
-            let ScreenClients {
-                floating,
-                tiling,
-                tag,
-                r_s,
-            } = sc;
-
-            // Sort out the floating client positions first
-            for (c, r_c) in floating.iter() {
-                float_positions.push((*c, r_c.applied_to(&r_s)));
-            }
+    /// Publish a value for other extensions and status bar widgets to read, returning the
+    /// previously published value of the same type if there was one.
+    ///
+    /// Unlike state extensions, published values can only be replaced by publishing a new
+    /// value: readers see the most recent value using [State::published]. Wrapping values in
+    /// a new type allows for publishing multiple values that share an underlying
+    /// representation.
+    ///
+    /// ```no_run
+    /// # use penrose::{core::State, x11rb::RustConn};
+    /// #[derive(Debug, PartialEq)]
+    /// struct DoNotDisturb(bool);
+    ///
+    /// # fn example(state: &mut State<RustConn>) {
+    /// state.publish(DoNotDisturb(true));
+    ///
+    /// assert_eq!(state.published::<DoNotDisturb>(), Some(&DoNotDisturb(true)));
+    /// # }
+    /// ```
+    pub fn publish<T: Any>(&mut self, value: T) -> Option<T> {
+        self.published.insert(value)
+    }
 
-            // Next run layout functions for each workspace on a visible screen
-            let stack_positions = match hook {
-                Some(ref mut h) => {
-                    let r_s = h.transform_initial(r_s, self, x);
-                    let s = self.client_set.screens.iter_mut().nth(i).unwrap();
-                    let (_, initial) = s.workspace.layouts.layout_workspace(&tag, &tiling, r_s);
-                    h.transform_positions(r_s, initial, self, x)
-                }
-                None => {
-                    let s = self.client_set.screens.iter_mut().nth(i).unwrap();
-                    let (_, positions) = s.workspace.layouts.layout_workspace(&tag, &tiling, r_s);
-                    positions
-                }
-            };
+    /// The most recently published value of type `T`, if there is one.
+    pub fn published<T: Any>(&self) -> Option<&T> {
+        self.published.get()
+    }
 
-            positions.extend(stack_positions.into_iter().rev());
-        }
+    /// Remove the published value of type `T`, returning it if there was one.
+    pub fn unpublish<T: Any>(&mut self) -> Option<T> {
+        self.published.remove()
+    }
 
-        float_positions.reverse();
-        positions.extend(float_positions);
+    /// The name of the active [BindingMode][0], if there is one.
+    ///
+    ///   [0]: crate::core::bindings::BindingMode
+    pub fn active_mode(&self) -> Option<&str> {
+        self.bindings.active_mode.as_deref()
+    }
 
-        // Restore the layout hook
-        self.config.layout_hook = hook;
+    /// The numeric count prefix entered before the key binding that is currently running, if
+    /// there is one.
+    ///
+    /// Counts are entered using [count_digit][0] bindings and are passed to the next key
+    /// binding that is run. Key bindings are free to interpret the count however they like:
+    /// [modify_with_count][1] repeats its modification `count` times.
+    ///
+    ///   [0]: crate::builtin::actions::count_digit
+    ///   [1]: crate::builtin::actions::modify_with_count
+    pub fn count(&self) -> Option<usize> {
+        self.bindings.count
+    }
 
-        positions
+    /// The current keyboard mapping, if it has been fetched from the X server.
+    pub fn keymap(&self) -> Option<&Keymap> {
+        self.bindings.keymap.as_ref()
     }
-}
 
-/// The user specified config options for how the window manager should run
-pub struct Config<X>
-where
-    X: XConn,
-{
-    /// The RGBA color to use for normal (unfocused) window borders
-    pub normal_border: Color,
-    /// The RGBA color to use for the focused window border
-    pub focused_border: Color,
-    /// The width in pixels to use for drawing window borders
-    pub border_width: u32,
-    /// Whether or not the mouse entering a new window should set focus
-    pub focus_follow_mouse: bool,
-    /// The stack of layouts to use for each workspace
-    pub default_layouts: LayoutStack,
-    /// The ordered set of workspace tags to use on window manager startup
-    pub tags: Vec<String>,
-    /// Window classes that should always be assigned floating positions rather than tiled
-    pub floating_classes: Vec<String>,
-    /// Window classes that is not focusable e.g. because they are a dock/status bar
-    pub bar_classes: Vec<String>,
-    /// A [StateHook] to run before entering the main event loop
-    pub startup_hook: Option<Box<dyn StateHook<X>>>,
-    /// A [StateHook] to run before processing each [XEvent]
-    pub event_hook: Option<Box<dyn EventHook<X>>>,
-    /// A [ManageHook] to run after each new window becomes managed by the window manager
-    pub manage_hook: Option<Box<dyn ManageHook<X>>>,
-    /// A [StateHook] to run every time the on screen X state is refreshed
-    pub refresh_hook: Option<Box<dyn StateHook<X>>>,
-    /// A [LayoutHook] to run when positioning clients on the screen
-    pub layout_hook: Option<Box<dyn LayoutHook<X>>>,
-}
+    /// The current key bindings along with their descriptions, sorted by key.
+    ///
+    /// This reflects any bindings added or removed at runtime along with the key binding
+    /// overlays for the focused workspace (see [WindowManager::add_workspace_key_bindings]).
+    /// Descriptions are set by wrapping key bindings in [Described][0].
+    ///
+    ///   [0]: crate::core::bindings::Described
+    pub fn binding_descriptions(&self) -> &[BindingDescription] {
+        &self.bindings.descriptions
+    }
 
-impl<X> fmt::Debug for Config<X>
-where
-    X: XConn,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Config")
-            .field("normal_border", &self.normal_border)
-            .field("focused_border", &self.focused_border)
-            .field("border_width", &self.border_width)
-            .field("focus_follow_mouse", &self.focus_follow_mouse)
-            .field("default_layouts", &self.default_layouts)
-            .field("tags", &self.tags)
-            .field("floating_classes", &self.floating_classes)
-            .finish()
+    /// Start recording a key macro with the given name.
+    ///
+    /// Each key binding that is run from this point on is recorded so that the sequence of
+    /// actions can later be run again using [State::replay_macro]. Keys that are not bound are
+    /// not recorded. Starting a new recording discards any recording already in progress.
+    ///
+    /// Macros record the key bindings that were run rather than the handlers themselves, so
+    /// replaying a macro after rebinding one of its keys will run the new binding.
+    pub fn start_recording_macro(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        info!(%name, "recording key macro");
+        self.bindings.macros.recording = Some((name, Vec::new()));
     }
-}
 
-impl<X> Default for Config<X>
-where
-    X: XConn,
-{
-    fn default() -> Self {
-        let strings = |slice: &[&str]| slice.iter().map(|s| s.to_string()).collect();
+    /// Stop recording the current key macro, saving it under the name it was started with
+    /// and replacing any existing macro with that name.
+    ///
+    /// Returns the name of the macro that was saved, if a recording was in progress.
+    pub fn stop_recording_macro(&mut self) -> Option<String> {
+        let (name, steps) = self.bindings.macros.recording.take()?;
+        info!(%name, n_steps = steps.len(), "saving key macro");
+        self.bindings.macros.saved.insert(name.clone(), steps);
 
-        Config {
-            normal_border: "#3c3836ff".try_into().expect("valid hex code"),
-            focused_border: "#cc241dff".try_into().expect("valid hex code"),
-            border_width: 2,
-            focus_follow_mouse: true,
-            default_layouts: LayoutStack::default(),
-            tags: strings(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]),
-            floating_classes: strings(&["dmenu", "dunst"]),
-            bar_classes: vec![],
-            startup_hook: None,
-            event_hook: None,
-            manage_hook: None,
-            refresh_hook: None,
-            layout_hook: None,
+        Some(name)
+    }
+
+    /// The name of the key macro currently being recorded, if there is one.
+    pub fn recording_macro(&self) -> Option<&str> {
+        self.bindings
+            .macros
+            .recording
+            .as_ref()
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Replay the key macro with the given name `times` times.
+    ///
+    /// The recorded key bindings are run once the event currently being handled has been
+    /// processed. Replaying a macro from within a macro that is already being replayed is
+    /// not supported and will be ignored.
+    pub fn replay_macro(&mut self, name: &str, times: usize) -> Result<()> {
+        let steps = self
+            .bindings
+            .macros
+            .saved
+            .get(name)
+            .ok_or_else(|| Error::UnknownMacro {
+                name: name.to_string(),
+            })?;
+
+        if self.bindings.macros.replaying {
+            warn!(%name, "unable to replay a key macro while another is being replayed");
+            return Ok(());
         }
+
+        for _ in 0..times {
+            self.bindings
+                .macros
+                .pending_replay
+                .extend(steps.iter().copied());
+        }
+
+        Ok(())
     }
-}
 
-impl<X> Config<X>
-where
-    X: XConn,
-{
-    /// Set the startup_hook or compose it with what is already set.
+    /// Run an external command in the background, passing its output and exit status to
+    /// `handler` once it exits.
     ///
-    /// The new hook will run before what was there before.
-    pub fn compose_or_set_startup_hook<H>(&mut self, hook: H)
+    /// The command is run using [spawn_for_output_with_status] on a separate thread so
+    /// that the window manager continues to handle events while waiting for it (for example,
+    /// while the user is picking a value from `dmenu`). If `input` is provided then it is
+    /// written to the stdin of the command. The handler is run from the main event loop with
+    /// access to the window manager state.
+    pub fn spawn_and_capture<S, F>(&mut self, cmd: S, input: Option<String>, handler: F)
     where
-        H: StateHook<X> + 'static,
-        X: 'static,
+        S: Into<String>,
+        F: FnOnce(CommandOutput, &mut State<X>, &X) -> Result<()> + 'static,
     {
-        self.startup_hook = match self.startup_hook.take() {
-            Some(h) => Some(hook.then_boxed(h)),
-            None => Some(hook.boxed()),
-        };
+        let cmd = cmd.into();
+        let (tx, rx) = mpsc::channel();
+        let pending = handle::PendingOutput::new(cmd.clone(), rx, Box::new(handler));
+        self.deferred.output.push(pending);
+
+        thread::spawn(move || {
+            // The receiver is only dropped if the window manager is exiting
+            let _ = tx.send(spawn_for_output_with_status(cmd, input));
+            timers::wake_event_loop();
+        });
     }
 
-    /// Set the event_hook or compose it with what is already set.
+    /// Run `f` from the main event loop once `delay` has elapsed.
     ///
-    /// The new hook will run before what was there before.
-    pub fn compose_or_set_event_hook<H>(&mut self, hook: H)
+    /// The event loop waits for the next event from the X server with a timeout so that `f`
+    /// is run on time even if no events are received in the meantime. If the [XConn] in use
+    /// does not support waiting with a timeout then `f` is run once the next event has been
+    /// received after the delay has elapsed.
+    pub fn run_after<F>(&mut self, delay: Duration, f: F)
     where
-        H: EventHook<X> + 'static,
-        X: 'static,
+        F: FnOnce(&mut State<X>, &X) -> Result<()> + 'static,
     {
-        self.event_hook = match self.event_hook.take() {
-            Some(h) => Some(hook.then_boxed(h)),
-            None => Some(hook.boxed()),
-        };
+        self.deferred
+            .timers
+            .push(Instant::now() + delay, Some(Box::new(f)));
     }
 
-    /// Set the manage_hook or compose it with what is already set.
+    // Make sure that the event loop wakes up once the delay has elapsed so that pending work
+    // that is due at that point is handled without waiting for the next event.
+    pub(crate) fn wake_after(&mut self, delay: Duration) {
+        self.deferred.timers.push(Instant::now() + delay, None);
+    }
+
+    /// Bind the given [KeyCode] to a new handler, replacing any existing binding for it.
     ///
-    /// The new hook will run before what was there before.
-    pub fn compose_or_set_manage_hook<H>(&mut self, hook: H)
-    where
-        H: ManageHook<X> + 'static,
-        X: 'static,
-    {
-        self.manage_hook = match self.manage_hook.take() {
-            Some(h) => Some(hook.then_boxed(h)),
-            None => Some(hook.boxed()),
-        };
+    /// The change takes effect (and key bindings are regrabbed from the X server) once the
+    /// event currently being handled has been processed. To modify bindings before the
+    /// window manager is running, use [WindowManager::add_key_binding].
+    pub fn add_key_binding(&mut self, code: KeyCode, handler: Box<dyn KeyEventHandler<X>>) {
+        self.bindings
+            .pending_changes
+            .push(BindingChange::AddKey(code, handler));
     }
 
-    /// Set the refresh_hook or compose it with what is already set.
+    /// Remove the binding for the given [KeyCode] if there is one.
     ///
-    /// The new hook will run before what was there before.
-    pub fn compose_or_set_refresh_hook<H>(&mut self, hook: H)
-    where
-        H: StateHook<X> + 'static,
-        X: 'static,
-    {
-        self.refresh_hook = match self.refresh_hook.take() {
-            Some(h) => Some(hook.then_boxed(h)),
-            None => Some(hook.boxed()),
-        };
+    /// As with [State::add_key_binding], the change takes effect once the event currently
+    /// being handled has been processed.
+    pub fn remove_key_binding(&mut self, code: KeyCode) {
+        self.bindings
+            .pending_changes
+            .push(BindingChange::RemoveKey(code));
     }
 
-    /// Set the layout_hook or compose it with what is already set.
+    /// Bind the given mouse event to a new handler, replacing any existing binding for it.
     ///
-    /// The new hook will run before what was there before.
-    pub fn compose_or_set_layout_hook<H>(&mut self, hook: H)
-    where
-        H: LayoutHook<X> + 'static,
-        X: 'static,
-    {
-        self.layout_hook = match self.layout_hook.take() {
-            Some(h) => Some(hook.then_boxed(h)),
-            None => Some(hook.boxed()),
-        };
+    /// As with [State::add_key_binding], the change takes effect once the event currently
+    /// being handled has been processed.
+    pub fn add_mouse_binding(
+        &mut self,
+        kind: MouseEventKind,
+        mouse_state: MouseState,
+        handler: Box<dyn MouseEventHandler<X>>,
+    ) {
+        self.bindings
+            .pending_changes
+            .push(BindingChange::AddMouse((kind, mouse_state), handler));
     }
-}
 
-/// A top level struct holding all of the state required to run as an X11 window manager.
-///
-/// This allows for final configuration to be carried out before entering the main event
-/// loop.
-#[derive(Debug)]
-pub struct WindowManager<X>
-where
-    X: XConn,
-{
-    x: X,
-    /// The mutable [State] of the window manager
-    pub state: State<X>,
-    key_bindings: KeyBindings<X>,
-    mouse_bindings: MouseBindings<X>,
+    /// Remove the binding for the given mouse event if there is one.
+    ///
+    /// As with [State::add_key_binding], the change takes effect once the event currently
+    /// being handled has been processed.
+    pub fn remove_mouse_binding(&mut self, kind: MouseEventKind, mouse_state: MouseState) {
+        self.bindings
+            .pending_changes
+            .push(BindingChange::RemoveMouse((kind, mouse_state)));
+    }
+
+    /// Grab only the given keys and mouse states in place of the normal bindings, for modal
+    /// input handled by an event hook (such as the [layout editor][0]).
+    ///
+    /// The normal bindings are left ungrabbed, even if they are modified or the key binding
+    /// overlays for the focused workspace change, until [State::end_modal_grab] is called.
+    ///
+    ///   [0]: crate::extensions::hooks::layout_editor
+    pub fn begin_modal_grab(
+        &mut self,
+        keys: &[KeyCode],
+        mouse_states: &[MouseState],
+        x: &X,
+    ) -> Result<()> {
+        self.bindings.modal_grab = handle::ModalGrab::Active;
+        x.grab(keys, mouse_states)
+    }
+
+    /// End the modal grab started by [State::begin_modal_grab], if there is one.
+    ///
+    /// The normal bindings are grabbed again once the event currently being handled has been
+    /// processed.
+    pub fn end_modal_grab(&mut self) {
+        if self.bindings.modal_grab == handle::ModalGrab::Active {
+            self.bindings.modal_grab = handle::ModalGrab::Ending;
+        }
+    }
+
+    /// Whether or not a modal grab started by [State::begin_modal_grab] is active.
+    pub fn modal_grab_active(&self) -> bool {
+        self.bindings.modal_grab == handle::ModalGrab::Active
+    }
+
+    pub(crate) fn position_and_snapshot(&mut self, x: &X) -> Snapshot<Xid> {
+        let positions = self.visible_client_positions(x);
+        self.client_set.snapshot(positions)
+    }
+
+    pub(crate) fn position_screen_and_snapshot(
+        &mut self,
+        x: &X,
+        screen_index: Option<usize>,
+    ) -> Snapshot<Xid> {
+        let positions = self.single_screen_client_positions(x, screen_index);
+        self.client_set.snapshot(positions)
+    }
+
+    /// The factor by which pixel sizes should be scaled on the screen with the given index.
+    ///
+    /// This is always `1.0` unless [Config::dpi_scaling] is set.
+    pub fn screen_scale(&self, index: usize) -> f64 {
+        self.screen_details
+            .scales
+            .get(index)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    // Recompute the scale factor for each screen from its DPI, as reported by the X server or
+    // overridden in the user's config.
+    pub(crate) fn update_screen_scales(&mut self, x: &X) {
+        let scaling = match &self.config.dpi_scaling {
+            Some(scaling) => scaling,
+            None => {
+                self.screen_details.scales.clear();
+                return;
+            }
+        };
+
+        // Mirrored screens are merged so only the DPI of the screens that were kept is used
+        let detected = x
+            .screen_dpi()
+            .and_then(|dpi| {
+                let rects = x.screen_details()?;
+                let distinct = handle::distinct_screens(&rects);
+
+                Ok(distinct
+                    .into_iter()
+                    .map(|i| dpi.get(i).copied().flatten())
+                    .collect())
+            })
+            .unwrap_or_else(|e: Error| {
+                warn!(%e, "unable to detect screen DPI");
+                vec![]
+            });
+
+        self.screen_details.scales = (0..self.client_set.screens.len())
+            .map(|i| scaling.scale_for(i, detected.get(i).copied().flatten()))
+            .collect();
+        debug!(scales = ?self.screen_details.scales, "updated screen scale factors");
+    }
+
+    /// The overrides from [Config::monitor_overrides] for the output shown on the screen with
+    /// the given index, if there are any.
+    pub fn monitor_config(&self, index: usize) -> Option<&MonitorConfig> {
+        self.screen_details
+            .monitor_configs
+            .get(index)
+            .and_then(|c| c.as_ref())
+    }
+
+    /// The border width to use for clients on the screen with the given index before any
+    /// scaling is applied.
+    pub fn screen_border_width(&self, index: usize) -> u32 {
+        self.monitor_config(index)
+            .and_then(|c| c.border_width)
+            .unwrap_or(self.config.border_width)
+    }
+
+    /// Whether or not a status bar should be shown on the screen with the given index.
+    pub fn bar_enabled(&self, index: usize) -> bool {
+        self.monitor_config(index)
+            .and_then(|c| c.bar)
+            .unwrap_or(true)
+    }
+
+    // Match the outputs named in the user's config to the screens showing them, switching the
+    // workspace on each matched screen to its default layout (if one is set).
+    pub(crate) fn update_monitor_configs(&mut self, x: &X) {
+        if self.config.monitor_overrides.is_empty() {
+            self.screen_details.monitor_configs.clear();
+            return;
+        }
+
+        let outputs = x.outputs().unwrap_or_else(|e| {
+            warn!(%e, "unable to fetch output details");
+            vec![]
+        });
+
+        let overrides = &self.config.monitor_overrides;
+        self.screen_details.monitor_configs = self
+            .client_set
+            .screens
+            .iter()
+            .map(|s| {
+                outputs.iter().find_map(|o| {
+                    let r = o.r?;
+                    let conf = overrides.get(&o.name)?;
+
+                    s.r.contains_point(r.midpoint()).then(|| conf.clone())
+                })
+            })
+            .collect();
+        debug!(configs = ?self.screen_details.monitor_configs, "updated per-monitor config overrides");
+
+        let layouts = self
+            .client_set
+            .screens
+            .iter_mut()
+            .zip(&self.screen_details.monitor_configs);
+        for (s, conf) in layouts {
+            if let Some(name) = conf.as_ref().and_then(|c| c.default_layout.as_ref()) {
+                s.workspace.set_layout_by_name(name);
+            }
+        }
+    }
+
+    /// The RandR [Rotation] of the output shown on the screen with the given index.
+    ///
+    /// Screens whose output can not be determined are reported as [Rotation::Normal].
+    pub fn screen_rotation(&self, index: usize) -> Rotation {
+        self.screen_details
+            .rotations
+            .get(index)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // Match each screen to the output it is showing in order to track the rotation of each
+    // screen for the layouts running on it, returning whether or not any rotations changed.
+    pub(crate) fn update_screen_rotations(&mut self, x: &X) -> bool {
+        let outputs = x.outputs().unwrap_or_else(|e| {
+            warn!(%e, "unable to fetch output details");
+            vec![]
+        });
+
+        let rotations: Vec<Rotation> = self
+            .client_set
+            .screens
+            .iter()
+            .map(|s| {
+                outputs
+                    .iter()
+                    .find(|o| o.r.is_some_and(|r| s.r.contains_point(r.midpoint())))
+                    .map(|o| o.rotation)
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        if rotations == self.screen_details.rotations {
+            return false;
+        }
+
+        debug!(?rotations, "updated screen rotations");
+        self.screen_details.rotations = rotations;
+
+        true
+    }
+
+    // Locate the screen showing the primary output reported by RandR (if there is one)
+    pub(crate) fn update_primary_screen(&mut self, x: &X) {
+        let outputs = x.outputs().unwrap_or_else(|e| {
+            warn!(%e, "unable to fetch output details");
+            vec![]
+        });
+
+        let index = outputs
+            .iter()
+            .find(|o| o.primary)
+            .and_then(|o| o.r)
+            .and_then(|r| {
+                let p = r.midpoint();
+                self.client_set.screens().find(|s| s.r.contains_point(p))
+            })
+            .map(|s| s.index());
+
+        if index != self.client_set.primary_screen {
+            debug!(?index, "updated primary screen");
+            self.client_set.set_primary_screen(index);
+        }
+    }
+
+    /// The border width to use for the given client, taking into account any override
+    /// requested by the [Layout] that positioned it.
+    pub fn border_width_for(&self, id: Xid) -> u32 {
+        self.layout_overrides
+            .borders
+            .get(&id)
+            .copied()
+            .unwrap_or(self.config.border_width)
+    }
+
+    /// Run the per-workspace layouts to get a screen position for each visible client. Floating clients
+    /// are placed above stacked clients, clients per workspace are stacked in the order they are returned
+    /// from the layout.
+    ///
+    /// Any border width overrides requested by the layouts are recorded for the tiled clients they
+    /// position. All clients on workspaces with borders disabled, along with floating clients that
+    /// have asked not to be given a border using `_MOTIF_WM_HINTS`, are given a border width of 0.
+    pub(crate) fn visible_client_positions(&mut self, x: &X) -> Vec<(Xid, Rect)> {
+        let mut float_positions: Vec<(Xid, Rect)> = Vec::new();
+        let mut positions: Vec<(Xid, Rect)> = Vec::new();
+        self.layout_overrides.borders.clear();
+
+        // pop the layout hook off of `state` so that we can pass state into it
+        let mut hook = self.config.layout_hook.take();
+
+        for i in 0..self.client_set.screens.len() {
+            self.position_screen(i, &mut hook, x, &mut positions, &mut float_positions);
+        }
+
+        float_positions.reverse();
+        positions.extend(float_positions);
+        self.client_set.raise_transients(&mut positions);
+
+        // Restore the layout hook
+        self.config.layout_hook = hook;
+
+        positions
+    }
+
+    /// The clients on the workspace shown on the screen with the given index.
+    pub(crate) fn clients_on_screen(&self, index: usize) -> HashSet<Xid> {
+        match self.client_set.screens().find(|s| s.index == index) {
+            Some(s) => s.workspace.clients().copied().collect(),
+            None => HashSet::new(),
+        }
+    }
+
+    /// Run the layout for the workspace on the screen with the given index (if any), keeping the
+    /// positions of clients on all other visible screens as they were following the last refresh.
+    pub(crate) fn single_screen_client_positions(
+        &mut self,
+        x: &X,
+        screen_index: Option<usize>,
+    ) -> Vec<(Xid, Rect)> {
+        let i =
+            screen_index.and_then(|index| self.client_set.screens().position(|s| s.index == index));
+        let on_screen = screen_index
+            .map(|index| self.clients_on_screen(index))
+            .unwrap_or_default();
+
+        let cs = &self.client_set;
+        let mut positions: Vec<(Xid, Rect)> = self
+            .diff
+            .after
+            .positions
+            .iter()
+            .filter(|(id, _)| !on_screen.contains(id) && cs.screen_for_client(id).is_some())
+            .copied()
+            .collect();
+
+        if let Some(i) = i {
+            self.layout_overrides
+                .borders
+                .retain(|id, _| !on_screen.contains(id));
+            let mut float_positions = Vec::new();
+            let mut hook = self.config.layout_hook.take();
+            self.position_screen(i, &mut hook, x, &mut positions, &mut float_positions);
+            self.config.layout_hook = hook;
+
+            float_positions.reverse();
+            positions.extend(float_positions);
+        }
+
+        self.client_set.raise_transients(&mut positions);
+
+        positions
+    }
+
+    // Add the positions of the tiled (in stacking order) and floating clients on the screen at
+    // position `i` in the screen stack, recording any border width overrides.
+    fn position_screen(
+        &mut self,
+        i: usize,
+        hook: &mut Option<Box<dyn LayoutHook<X>>>,
+        x: &X,
+        positions: &mut Vec<(Xid, Rect)>,
+        float_positions: &mut Vec<(Xid, Rect)>,
+    ) {
+        let ScreenClients {
+            floating,
+            tiling,
+            tag,
+            r_s,
+        } = self
+            .client_set
+            .screens
+            .iter()
+            .nth(i)
+            .unwrap()
+            .screen_clients(&self.client_set.floating);
+
+        // Sort out the floating client positions first
+        for (c, r_c) in floating.iter() {
+            float_positions.push((*c, r_c.applied_to(&r_s)));
+        }
+
+        // Clients hidden from the layout are left out of the stack it is given
+        let ws_id = self
+            .client_set
+            .screens
+            .iter()
+            .nth(i)
+            .unwrap()
+            .workspace
+            .id();
+        let tiling = match self.layout_overrides.hidden.get(&ws_id) {
+            Some(hidden) => tiling.and_then(|s| {
+                let focus = s.focus;
+                s.filter(|id| *id == focus || !hidden.contains(id))
+            }),
+            None => tiling,
+        };
+
+        // Let the layout know the rotation of the screen it is about to be run on and whether
+        // or not gaps are enabled for the workspace
+        let rotation = self.screen_rotation(i);
+        let s = self.client_set.screens.iter_mut().nth(i).unwrap();
+        s.workspace.layouts.handle_message(ScreenRotation(rotation));
+        s.workspace
+            .layouts
+            .handle_message(SetGaps(s.workspace.gaps_enabled()));
+
+        // Next run the layout function for the workspace on this screen
+        let stack_positions = match hook {
+            Some(ref mut h) => {
+                let r_s = h.transform_initial(r_s, self, x);
+                let s = self.client_set.screens.iter_mut().nth(i).unwrap();
+                let (_, initial) = s.workspace.layouts.layout_workspace(&tag, &tiling, r_s);
+                let initial = self.with_maximized_clients(i, initial, r_s);
+                h.transform_positions(r_s, initial, self, x)
+            }
+            None => {
+                let s = self.client_set.screens.iter_mut().nth(i).unwrap();
+                let (_, positions) = s.workspace.layouts.layout_workspace(&tag, &tiling, r_s);
+                self.with_maximized_clients(i, positions, r_s)
+            }
+        };
+
+        let s = self.client_set.screens.iter().nth(i).unwrap();
+        let scale = self.screen_scale(i);
+        let border_width = self.screen_border_width(i);
+        if !s.workspace.borders_enabled() {
+            let ids = stack_positions.iter().map(|&(id, _)| id);
+            for id in ids.chain(floating.iter().map(|&(id, _)| id)) {
+                self.layout_overrides.borders.insert(id, 0);
+            }
+        } else {
+            if scale != 1.0 || border_width != self.config.border_width {
+                let bw = scale_px(border_width, scale);
+                let ids = stack_positions.iter().map(|&(id, _)| id);
+                for id in ids.chain(floating.iter().map(|&(id, _)| id)) {
+                    self.layout_overrides.borders.insert(id, bw);
+                }
+            }
+
+            if let Some(bw) = s.workspace.layouts.border_width() {
+                for &(id, _) in stack_positions.iter() {
+                    self.layout_overrides
+                        .borders
+                        .insert(id, scale_px(bw, scale));
+                }
+            }
+
+            // Floating clients drawing their own decorations are not given a border
+            for &(id, _) in floating.iter() {
+                let hints = self.client_data::<MotifWmHints>(&id);
+                if hints.is_some_and(|h| !h.requests_decoration(MotifDecorations::BORDER)) {
+                    self.layout_overrides.borders.insert(id, 0);
+                }
+            }
+        }
+
+        positions.extend(stack_positions.into_iter().rev());
+    }
+
+    // Maximized clients are given the position they would have if they were the only client
+    // being laid out on their workspace and are stacked above the other tiled clients.
+    fn with_maximized_clients(
+        &self,
+        screen_index: usize,
+        positions: Vec<(Xid, Rect)>,
+        r: Rect,
+    ) -> Vec<(Xid, Rect)> {
+        if self.client_set.maximized.is_empty() {
+            return positions;
+        }
+
+        let s = self.client_set.screens.iter().nth(screen_index).unwrap();
+        let (mut maximized, mut others): (Vec<_>, Vec<_>) = positions
+            .into_iter()
+            .partition(|(id, _)| self.client_set.maximized.contains(id));
+
+        for (id, r_c) in maximized.iter_mut() {
+            let mut layout = s.workspace.layouts.focus.boxed_clone();
+            let (_, ps) = layout.layout(&Stack::new([], *id, []), r);
+            if let Some(&(_, r_max)) = ps.iter().find(|(c, _)| c == id) {
+                *r_c = r_max;
+            }
+        }
+
+        maximized.append(&mut others);
+
+        maximized
+    }
 }
 
-impl<X> WindowManager<X>
+/// The user specified config options for how the window manager should run
+#[cfg(feature = "wm")]
+pub struct Config<X>
 where
     X: XConn,
 {
-    /// Construct a new [WindowManager] with the provided config and X connection.
+    /// The RGBA color to use for normal (unfocused) window borders
+    pub normal_border: Color,
+    /// The RGBA color to use for the focused window border
+    pub focused_border: Color,
+    /// The width in pixels to use for drawing window borders
+    pub border_width: u32,
+    /// Whether or not the mouse entering a new window should set focus.
     ///
-    /// If you need to set [State] extensions, call [WindowManager::add_extension] after
-    /// constructing your initial WindowManager.
-    pub fn new(
-        config: Config<X>,
-        key_bindings: KeyBindings<X>,
-        mouse_bindings: MouseBindings<X>,
-        x: X,
-    ) -> Result<Self> {
-        let state = State::try_new(config, &x)?;
+    /// This can be toggled at runtime using
+    /// [toggle_focus_follow_mouse][crate::builtin::actions::toggle_focus_follow_mouse].
+    pub focus_follow_mouse: bool,
+    /// How long the pointer needs to remain inside of a window before it is given focus when
+    /// `focus_follow_mouse` is enabled. If this is `None` then focus changes immediately.
+    ///
+    /// Pointer movements that only briefly pass over a window on their way elsewhere will
+    /// not change focus when a delay is set.
+    pub focus_follow_mouse_delay: Option<Duration>,
+    /// Whether or not focus should only follow the mouse when the pointer itself has moved.
+    ///
+    /// When set, the pointer entering a window because windows were mapped, unmapped or
+    /// repositioned underneath a stationary pointer will not change focus. This requires
+    /// querying the pointer position each time the X state is refreshed.
+    pub focus_follow_mouse_requires_motion: bool,
+    /// The maximum time between two presses of the same mouse button for them to be treated as
+    /// a double click (see [MouseEventKind::DoubleClick]).
+    pub double_click_interval: Duration,
+    /// The stack of layouts to use for each workspace
+    pub default_layouts: LayoutStack,
+    /// The ordered set of workspace tags to use on window manager startup
+    pub tags: Vec<String>,
+    /// Whether each screen should have its own independent copy of `tags` (dwm style)
+    /// rather than all screens sharing a single set of workspaces (xmonad style).
+    ///
+    /// See [StackSet::try_new_per_screen] for details.
+    pub per_screen_workspaces: bool,
+    /// Window classes that should always be assigned floating positions rather than tiled
+    pub floating_classes: Vec<String>,
+    /// Whether or not clients that can not be resized (those whose minimum and maximum size
+    /// hints are equal, such as splash screens and fixed size dialogs) should be floated in the
+    /// center of the screen rather than tiled.
+    ///
+    /// This can be overridden for individual clients from a [ManageHook] by sinking the client
+    /// back into the tiled layer (see [FixedSize][0] for identifying such clients).
+    ///
+    ///   [0]: crate::x::query::FixedSize
+    pub float_fixed_size_clients: bool,
+    /// Window classes that is not focusable e.g. because they are a dock/status bar
+    pub bar_classes: Vec<String>,
+    /// Where newly managed clients are inserted into the [Stack] of their workspace.
+    ///
+    /// This controls where new clients are placed relative to the previously focused client,
+    /// regardless of whether or not they are given focus (see `new_client_focus`). This can
+    /// be overridden for individual clients from a [ManageHook] using
+    /// [StackSet::move_client_to_position].
+    pub insert_position: Position,
+    /// How newly managed floating clients are positioned on the screen.
+    ///
+    /// This can be overridden for individual clients from a [ManageHook] using
+    /// [floating_client_position][crate::x::floating_client_position].
+    pub floating_placement: FloatingPlacement,
+    /// When the mouse pointer should be warped to follow changes in focus made by actions
+    /// (rather than by the pointer itself).
+    ///
+    /// By default the pointer is only warped if [Config::focus_follow_mouse] is enabled.
+    ///
+    /// This can be overridden for individual key bindings using
+    /// [with_pointer_warp][crate::builtin::actions::with_pointer_warp].
+    pub pointer_warp: PointerWarp,
+    /// Whether or not newly managed clients are given focus.
+    ///
+    /// Transient clients (such as dialogs) of the focused client are always given focus.
+    pub new_client_focus: FocusPolicy,
+    /// Whether or not clients requesting focus using `_NET_ACTIVE_WINDOW` are given focus.
+    ///
+    /// Requests from pagers and other tools acting on behalf of the user are always honoured.
+    /// These requests are only handled when the [ewmh hooks][0] have been added.
+    ///
+    ///   [0]: crate::extensions::hooks::add_ewmh_hooks
+    pub activation_focus: FocusPolicy,
+    /// A [StateHook] to run before entering the main event loop
+    pub startup_hook: Option<Box<dyn StateHook<X>>>,
+    /// A [StateHook] to run before processing each [XEvent]
+    pub event_hook: Option<Box<dyn EventHook<X>>>,
+    /// A [ManageHook] to run after each new window becomes managed by the window manager
+    pub manage_hook: Option<Box<dyn ManageHook<X>>>,
+    /// A [StateHook] to run every time the on screen X state is refreshed
+    pub refresh_hook: Option<Box<dyn StateHook<X>>>,
+    /// A [LayoutHook] to run when positioning clients on the screen
+    pub layout_hook: Option<Box<dyn LayoutHook<X>>>,
+    /// A [StateHook] to run every time the set of urgent clients changes
+    pub urgency_hook: Option<Box<dyn StateHook<X>>>,
+    /// An [UrgencyChangeHook] to run for each client that is marked as urgent or has its
+    /// urgency cleared. This is run before the [Config::urgency_hook].
+    pub urgency_change_hook: Option<Box<dyn UrgencyChangeHook<X>>>,
+    /// A [StateHook] to run each time a [BindingMode][0] is entered or exited. The name of the
+    /// new mode (if there is one) can be found using [State::active_mode].
+    ///
+    ///   [0]: crate::core::bindings::BindingMode
+    pub mode_change_hook: Option<Box<dyn StateHook<X>>>,
+    /// A [StateHook] to run after the connected screens have changed, such as when a monitor
+    /// is plugged in or unplugged or the resolution of a screen changes.
+    pub screens_changed_hook: Option<Box<dyn StateHook<X>>>,
+    /// A [StateHook] to run when the window manager shuts down after receiving SIGTERM or
+    /// SIGINT. This is the place to persist any state you want to restore on restart.
+    pub shutdown_hook: Option<Box<dyn StateHook<X>>>,
+    /// Whether or not to ask all clients supporting WM_DELETE_WINDOW to close when shutting
+    /// down. Clients that do not support the protocol are left running.
+    pub close_clients_on_shutdown: bool,
+    /// How long to wait for a client to close after being asked to using `WM_DELETE_WINDOW`
+    /// before forcibly disconnecting it from the X server using `XKillClient`.
+    ///
+    /// If this is `None` then clients that ignore the request are left running.
+    pub kill_timeout: Option<Duration>,
+    /// How long splash screens (windows with a `_NET_WM_WINDOW_TYPE_SPLASH` window type) are
+    /// allowed to remain on screen before they are forcibly hidden.
+    ///
+    /// Splash screens are never managed: they are centered on the focused screen above all
+    /// other windows and left to their client. If this is `None` then they are only removed
+    /// when their client unmaps or destroys them.
+    pub splash_timeout: Option<Duration>,
+    /// How long clients remain urgent before their urgency is automatically cleared.
+    ///
+    /// If this is `None` then clients remain urgent until they are focused, their urgency is
+    /// cleared by their client or they are removed.
+    pub urgency_timeout: Option<Duration>,
+    /// Whether or not urgent clients should have their urgency cleared once the workspace
+    /// they are on becomes visible, rather than waiting until they are focused.
+    pub clear_urgency_on_view: bool,
+    /// An optional whitelist of external commands that the window manager is permitted to run
+    /// using the [spawn][0] action or any of the spawning functions in [util][1]. All commands
+    /// are permitted when this is `None`.
+    ///
+    ///   [0]: crate::builtin::actions::spawn
+    ///   [1]: crate::util
+    pub command_whitelist: Option<CommandWhitelist>,
+    /// How to scale border widths and other pixel sizes on each screen according to its DPI.
+    /// Nothing is scaled when this is `None`.
+    pub dpi_scaling: Option<DpiScaling>,
+    /// Config overrides for specific monitors, keyed by output name (e.g. `eDP-1`).
+    ///
+    /// See [MonitorConfig] for details.
+    pub monitor_overrides: HashMap<String, MonitorConfig>,
+}
 
-        Ok(Self {
-            x,
-            state,
-            key_bindings,
-            mouse_bindings,
-        })
+#[cfg(feature = "wm")]
+impl<X> fmt::Debug for Config<X>
+where
+    X: XConn,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("normal_border", &self.normal_border)
+            .field("focused_border", &self.focused_border)
+            .field("border_width", &self.border_width)
+            .field("focus_follow_mouse", &self.focus_follow_mouse)
+            .field("focus_follow_mouse_delay", &self.focus_follow_mouse_delay)
+            .field(
+                "focus_follow_mouse_requires_motion",
+                &self.focus_follow_mouse_requires_motion,
+            )
+            .field("double_click_interval", &self.double_click_interval)
+            .field("default_layouts", &self.default_layouts)
+            .field("tags", &self.tags)
+            .field("per_screen_workspaces", &self.per_screen_workspaces)
+            .field("floating_classes", &self.floating_classes)
+            .field("float_fixed_size_clients", &self.float_fixed_size_clients)
+            .field("insert_position", &self.insert_position)
+            .field("floating_placement", &self.floating_placement)
+            .field("pointer_warp", &self.pointer_warp)
+            .field("new_client_focus", &self.new_client_focus)
+            .field("activation_focus", &self.activation_focus)
+            .field("close_clients_on_shutdown", &self.close_clients_on_shutdown)
+            .field("kill_timeout", &self.kill_timeout)
+            .field("splash_timeout", &self.splash_timeout)
+            .field("urgency_timeout", &self.urgency_timeout)
+            .field("clear_urgency_on_view", &self.clear_urgency_on_view)
+            .field("command_whitelist", &self.command_whitelist)
+            .field("dpi_scaling", &self.dpi_scaling)
+            .field("monitor_overrides", &self.monitor_overrides)
+            .finish()
     }
+}
 
-    /// Add a typed [State] extension to this WindowManager.
-    pub fn add_extension<E: Any>(&mut self, extension: E) {
-        self.state.add_extension(extension);
-    }
+#[cfg(feature = "wm")]
+impl<X> Default for Config<X>
+where
+    X: XConn,
+{
+    fn default() -> Self {
+        let strings = |slice: &[&str]| slice.iter().map(|s| s.to_string()).collect();
 
-    /// Start the WindowManager and run it until told to exit.
-    ///
-    /// Any provided startup hooks will be run after setting signal handlers and grabbing
-    /// key / mouse bindings from the X server. Any set up you need to do should be run
-    /// explicitly before calling this method or as part of a startup hook.
-    pub fn run(mut self) -> Result<()> {
-        info!("registering SIGCHILD signal handler");
-        // SAFETY: there is no previous signal handler so we are safe to set our own without needing
-        //         to worry about UB from the previous handler being invalid.
-        if let Err(e) = unsafe { signal(Signal::SIGCHLD, SigHandler::SigIgn) } {
-            panic!("unable to set signal handler: {}", e);
-        }
+        Config {
+            normal_border: "#3c3836ff".try_into().expect("valid hex code"),
+            focused_border: "#cc241dff".try_into().expect("valid hex code"),
+            border_width: 2,
+            focus_follow_mouse: true,
+            focus_follow_mouse_delay: None,
+            focus_follow_mouse_requires_motion: false,
+            double_click_interval: Duration::from_millis(400),
+            default_layouts: LayoutStack::default(),
+            tags: strings(&["1", "2", "3", "4", "5", "6", "7", "8", "9"]),
+            per_screen_workspaces: false,
+            floating_classes: strings(&["dmenu", "dunst"]),
+            float_fixed_size_clients: true,
+            bar_classes: vec![],
+            insert_position: Position::Focus,
+            floating_placement: FloatingPlacement::Center,
+            pointer_warp: PointerWarp::FocusFollowsMouse,
+            new_client_focus: FocusPolicy::Always,
+            activation_focus: FocusPolicy::Never,
+            startup_hook: None,
+            event_hook: None,
+            manage_hook: None,
+            refresh_hook: None,
+            layout_hook: None,
+            urgency_hook: None,
+            urgency_change_hook: None,
+            mode_change_hook: None,
+            screens_changed_hook: None,
+            shutdown_hook: None,
+            close_clients_on_shutdown: false,
+            kill_timeout: None,
+            splash_timeout: None,
+            urgency_timeout: None,
+            clear_urgency_on_view: false,
+            command_whitelist: None,
+            dpi_scaling: None,
+            monitor_overrides: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(feature = "wm")]
+impl<X> Config<X>
+where
+    X: XConn,
+{
+    /// Set the startup_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_startup_hook<H>(&mut self, hook: H)
+    where
+        H: StateHook<X> + 'static,
+        X: 'static,
+    {
+        self.startup_hook = match self.startup_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the event_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_event_hook<H>(&mut self, hook: H)
+    where
+        H: EventHook<X> + 'static,
+        X: 'static,
+    {
+        self.event_hook = match self.event_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the manage_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_manage_hook<H>(&mut self, hook: H)
+    where
+        H: ManageHook<X> + 'static,
+        X: 'static,
+    {
+        self.manage_hook = match self.manage_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the refresh_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_refresh_hook<H>(&mut self, hook: H)
+    where
+        H: StateHook<X> + 'static,
+        X: 'static,
+    {
+        self.refresh_hook = match self.refresh_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the layout_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_layout_hook<H>(&mut self, hook: H)
+    where
+        H: LayoutHook<X> + 'static,
+        X: 'static,
+    {
+        self.layout_hook = match self.layout_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the urgency_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_urgency_hook<H>(&mut self, hook: H)
+    where
+        H: StateHook<X> + 'static,
+        X: 'static,
+    {
+        self.urgency_hook = match self.urgency_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the urgency_change_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_urgency_change_hook<H>(&mut self, hook: H)
+    where
+        H: UrgencyChangeHook<X> + 'static,
+        X: 'static,
+    {
+        self.urgency_change_hook = match self.urgency_change_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the mode_change_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_mode_change_hook<H>(&mut self, hook: H)
+    where
+        H: StateHook<X> + 'static,
+        X: 'static,
+    {
+        self.mode_change_hook = match self.mode_change_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the screens_changed_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_screens_changed_hook<H>(&mut self, hook: H)
+    where
+        H: StateHook<X> + 'static,
+        X: 'static,
+    {
+        self.screens_changed_hook = match self.screens_changed_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+
+    /// Set the shutdown_hook or compose it with what is already set.
+    ///
+    /// The new hook will run before what was there before.
+    pub fn compose_or_set_shutdown_hook<H>(&mut self, hook: H)
+    where
+        H: StateHook<X> + 'static,
+        X: 'static,
+    {
+        self.shutdown_hook = match self.shutdown_hook.take() {
+            Some(h) => Some(hook.then_boxed(h)),
+            None => Some(hook.boxed()),
+        };
+    }
+}
+
+// Set by our SIGTERM / SIGINT handler and checked by the main event loop.
+#[cfg(feature = "wm")]
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Set by request_restart alongside SHUTDOWN_REQUESTED.
+#[cfg(feature = "wm")]
+static RESTART_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "wm")]
+extern "C" fn handle_shutdown_signal(_: nix::libc::c_int) {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+    timers::wake_event_loop();
+}
+
+/// Request that the running [WindowManager] shuts down cleanly.
+///
+/// This is equivalent to sending SIGTERM to the window manager process: the shutdown hook
+/// is run and (if configured) clients are asked to close before [WindowManager::run] returns.
+/// This takes effect once the current event has finished being processed.
+#[cfg(feature = "wm")]
+pub fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+    timers::wake_event_loop();
+}
+
+/// Request that the running [WindowManager] restarts in place.
+///
+/// The shutdown hook is run and the window manager binary is then re-executed with the same
+/// arguments, leaving all clients mapped. Once the new process has adopted the existing clients
+/// their workspaces, stack order, focus, layouts and floating positions are restored. Clients
+/// are never asked to close on restart, regardless of [Config::close_clients_on_shutdown].
+/// This takes effect once the current event has finished being processed.
+#[cfg(feature = "wm")]
+pub fn request_restart() {
+    RESTART_REQUESTED.store(true, Ordering::SeqCst);
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+    timers::wake_event_loop();
+}
+
+/// A top level struct holding all of the state required to run as an X11 window manager.
+///
+/// This allows for final configuration to be carried out before entering the main event
+/// loop.
+#[cfg(feature = "wm")]
+#[derive(Debug)]
+pub struct WindowManager<X>
+where
+    X: XConn,
+{
+    x: X,
+    /// The mutable [State] of the window manager
+    pub state: State<X>,
+    pub(crate) key_bindings: KeyBindings<X>,
+    pub(crate) mouse_bindings: MouseBindings<X>,
+    key_overlays: KeyBindingOverlays<X>,
+}
+
+#[cfg(feature = "wm")]
+impl<X> WindowManager<X>
+where
+    X: XConn,
+{
+    /// Construct a new [WindowManager] with the provided config and X connection.
+    ///
+    /// If you need to set [State] extensions, call [WindowManager::add_extension] after
+    /// constructing your initial WindowManager.
+    pub fn new(
+        config: Config<X>,
+        key_bindings: KeyBindings<X>,
+        mouse_bindings: MouseBindings<X>,
+        x: X,
+    ) -> Result<Self> {
+        let state = State::try_new(config, &x)?;
+
+        Ok(Self {
+            x,
+            state,
+            key_bindings,
+            mouse_bindings,
+            key_overlays: KeyBindingOverlays::default(),
+        })
+    }
+
+    /// Add a typed [State] extension to this WindowManager.
+    pub fn add_extension<E: Any>(&mut self, extension: E) {
+        self.state.add_extension(extension);
+    }
+
+    /// Bind the given [KeyCode] to a new handler, replacing any existing binding for it.
+    ///
+    /// If the window manager is already running then the updated bindings are grabbed
+    /// immediately. From inside of a binding or hook use [State::add_key_binding] instead.
+    pub fn add_key_binding(
+        &mut self,
+        code: KeyCode,
+        handler: Box<dyn KeyEventHandler<X>>,
+    ) -> Result<()> {
+        self.apply_binding_change(BindingChange::AddKey(code, handler))
+    }
+
+    /// Remove the binding for the given [KeyCode] if there is one.
+    pub fn remove_key_binding(&mut self, code: KeyCode) -> Result<()> {
+        self.apply_binding_change(BindingChange::RemoveKey(code))
+    }
+
+    /// Bind the given mouse event to a new handler, replacing any existing binding for it.
+    ///
+    /// If the window manager is already running then the updated bindings are grabbed
+    /// immediately. From inside of a binding or hook use [State::add_mouse_binding] instead.
+    pub fn add_mouse_binding(
+        &mut self,
+        kind: MouseEventKind,
+        mouse_state: MouseState,
+        handler: Box<dyn MouseEventHandler<X>>,
+    ) -> Result<()> {
+        self.apply_binding_change(BindingChange::AddMouse((kind, mouse_state), handler))
+    }
+
+    /// Remove the binding for the given mouse event if there is one.
+    pub fn remove_mouse_binding(
+        &mut self,
+        kind: MouseEventKind,
+        mouse_state: MouseState,
+    ) -> Result<()> {
+        self.apply_binding_change(BindingChange::RemoveMouse((kind, mouse_state)))
+    }
+
+    /// Add key bindings that are only active while the workspace with the given tag is focused.
+    ///
+    /// These are layered on top of the normal key bindings: any binding for a key that is
+    /// also bound in the normal key bindings takes precedence until a different workspace
+    /// is focused, at which point the original binding is restored. If multiple overlays
+    /// bind the same key then the one that was added last wins.
+    pub fn add_workspace_key_bindings(
+        &mut self,
+        tag: impl Into<String>,
+        key_bindings: KeyBindings<X>,
+    ) -> Result<()> {
+        let tag = tag.into();
+        self.add_workspace_key_bindings_matching(move |t| t == tag, key_bindings)
+    }
+
+    /// Add key bindings that are only active while a workspace whose tag matches the given
+    /// predicate is focused.
+    ///
+    /// See [WindowManager::add_workspace_key_bindings] for details.
+    pub fn add_workspace_key_bindings_matching<F>(
+        &mut self,
+        f: F,
+        key_bindings: KeyBindings<X>,
+    ) -> Result<()>
+    where
+        F: Fn(&str) -> bool + 'static,
+    {
+        self.key_overlays.push(Box::new(f), key_bindings);
+
+        handle::update_binding_overlays(
+            &mut self.key_bindings,
+            &mut self.key_overlays,
+            &self.mouse_bindings,
+            &mut self.state,
+            &self.x,
+        )
+    }
+
+    fn apply_binding_change(&mut self, change: BindingChange<X>) -> Result<()> {
+        self.state.bindings.pending_changes.push(change);
+
+        handle::pending_binding_changes(
+            &mut self.key_bindings,
+            &mut self.mouse_bindings,
+            &mut self.state,
+            &self.x,
+        )
+    }
+
+    /// Refresh the X server state after changes that only affect the screen with the given
+    /// index, leaving clients on all other screens where they are.
+    ///
+    /// See [XConnExt::refresh_screen] for details.
+    pub fn refresh_screen(&mut self, index: usize) -> Result<()> {
+        self.x.refresh_screen(&mut self.state, index)
+    }
+
+    /// Refresh the X server state after changes that only affect the workspace with the
+    /// given tag, leaving clients on all other screens where they are.
+    ///
+    /// See [XConnExt::refresh_workspace] for details.
+    pub fn refresh_workspace(&mut self, tag: &str) -> Result<()> {
+        self.x.refresh_workspace(&mut self.state, tag)
+    }
+
+    /// Start the WindowManager and run it until told to exit.
+    ///
+    /// Any provided startup hooks will be run after setting signal handlers and grabbing
+    /// key / mouse bindings from the X server. Any set up you need to do should be run
+    /// explicitly before calling this method or as part of a startup hook.
+    ///
+    /// On receiving SIGTERM or SIGINT (or a call to [request_shutdown]) the shutdown hook
+    /// is run, clients are asked to close if [Config::close_clients_on_shutdown] is set, the
+    /// `WM_S0` manager selection is released and this method returns. Following a call to
+    /// [request_restart] this method only returns if re-executing the window manager fails.
+    ///
+    /// If a [StateSnapshot] has been added as a state extension then it is used to restore
+    /// the arrangement of any existing clients on startup (see [crash_recovery][0]).
+    ///
+    ///   [0]: crate::extensions::hooks::crash_recovery
+    pub fn run(mut self) -> Result<()> {
+        let restored = restart::take_saved_snapshot();
+        set_active_whitelist(self.state.config.command_whitelist.clone());
+
+        info!("registering SIGCHILD signal handler");
+        // SAFETY: there is no previous signal handler so we are safe to set our own without needing
+        //         to worry about UB from the previous handler being invalid.
+        if let Err(e) = unsafe { signal(Signal::SIGCHLD, SigHandler::SigIgn) } {
+            panic!("unable to set signal handler: {}", e);
+        }
+
+        info!("registering SIGTERM and SIGINT signal handlers");
+        let action = SigAction::new(
+            SigHandler::Handler(handle_shutdown_signal),
+            SaFlags::empty(),
+            SigSet::empty(),
+        );
+        for sig in [Signal::SIGTERM, Signal::SIGINT] {
+            // SAFETY: our handler only stores to an atomic and writes to the wake pipe, both of
+            //         which are async-signal-safe.
+            if let Err(e) = unsafe { sigaction(sig, &action) } {
+                panic!("unable to set signal handler: {}", e);
+            }
+        }
+
+        acquire_wm_selection(&self.x);
+        handle::keyboard_mapping_changed(
+            &mut self.key_bindings,
+            &mut self.key_overlays,
+            &self.mouse_bindings,
+            &mut self.state,
+            &self.x,
+        )?;
+
+        startup_hook(&mut self.state, &self.x);
+
+        let restored = restored.or_else(|| self.state.remove_extension::<StateSnapshot>());
+        manage_existing_clients(restored, &mut self.state, &self.x)?;
+        self.update_binding_overlays()?;
+
+        let wake = timers::WakePipe::new()?;
+        let mut woken = false;
+
+        loop {
+            if RESTART_REQUESTED.load(Ordering::SeqCst) {
+                info!("restart requested");
+                return restart::restart(&mut self.state, &self.x);
+            }
+
+            if SHUTDOWN_REQUESTED.load(Ordering::SeqCst) {
+                info!("shutdown requested");
+                return shutdown(&mut self.state, &self.x);
+            }
+
+            self.run_timers(woken);
+
+            let deadline = self.state.deferred.timers.next_deadline();
+            woken = match self.x.next_event_until(deadline, wake.read_fd()) {
+                Ok(Some(event)) => {
+                    self.process_event(event);
+                    false
+                }
+                Ok(None) => {
+                    wake.drain();
+                    true
+                }
+                Err(e) => {
+                    self.handle_error(e);
+                    false
+                }
+            };
+        }
+    }
+
+    // Run the actions of any timers that have expired followed by any pending work that is now
+    // due. Pending work is also checked if the event loop was explicitly woken.
+    fn run_timers(&mut self, woken: bool) {
+        let actions = match self.state.deferred.timers.take_expired(Instant::now()) {
+            Some(actions) => actions,
+            None if woken => Vec::new(),
+            None => return,
+        };
+
+        for action in actions {
+            if let Err(e) = action(&mut self.state, &self.x) {
+                error!(%e, "error running timer");
+            }
+        }
+
+        if let Err(e) = self.run_pending() {
+            error!(%e, "error running pending actions");
+        }
+        self.x.flush();
+    }
+
+    // Handle any delayed work whose deadline has passed along with changes to the active
+    // bindings requested since this was last run.
+    fn run_pending(&mut self) -> Result<()> {
+        let WindowManager {
+            x,
+            state,
+            key_bindings,
+            mouse_bindings,
+            key_overlays,
+        } = self;
+
+        handle::pending_focus(state, x)?;
+        handle::pending_kills(state, x)?;
+        handle::pending_output(state, x);
+        handle::pending_splash_timeouts(state, x)?;
+        handle::pending_urgency_timeouts(state, x)?;
+        handle::pending_chord_timeout(key_bindings, mouse_bindings, state, x)?;
+        handle::pending_key_repeat(key_bindings, state, x)?;
+        handle::pending_binding_changes(key_bindings, mouse_bindings, state, x)?;
+        handle::update_binding_overlays(key_bindings, key_overlays, mouse_bindings, state, x)
+    }
+
+    fn process_event(&mut self, event: XEvent) {
+        let span = span!(target: "penrose", Level::INFO, "XEvent", %event);
+        let _enter = span.enter();
+        trace!(details = ?event, "event details");
+        self.state.current_event = Some(event.clone());
+
+        if let Err(e) = self.handle_xevent(event) {
+            error!(%e, "Error handling XEvent");
+        }
+        self.x.flush();
+
+        self.state.current_event = None;
+
+        if !self.state.bindings.macros.replaying {
+            self.replay_pending_macro();
+        }
+    }
+
+    fn replay_pending_macro(&mut self) {
+        let steps = std::mem::take(&mut self.state.bindings.macros.pending_replay);
+        if steps.is_empty() {
+            return;
+        }
+
+        self.state.bindings.macros.replaying = true;
+        for step in steps {
+            self.process_event(step.into());
+        }
+        self.state.bindings.macros.replaying = false;
+    }
+
+    fn handle_xevent(&mut self, event: XEvent) -> Result<()> {
+        use XEvent::*;
+
+        // Failures here are unrelated to the event itself so it still needs to be handled
+        if let Err(e) = self.run_pending() {
+            error!(%e, "error running pending actions");
+        }
+
+        let WindowManager {
+            x,
+            state,
+            key_bindings,
+            mouse_bindings,
+            key_overlays,
+        } = self;
+
+        let mut hook = state.config.event_hook.take();
+        let should_run = match hook {
+            Some(ref mut h) => {
+                trace!("running user event hook");
+                match h.call(&event, state, x) {
+                    Ok(should_run) => should_run,
+                    Err(e) => {
+                        error!(%e, "error returned from user event hook");
+                        true
+                    }
+                }
+            }
+
+            None => true,
+        };
+        state.config.event_hook = hook;
+
+        if !should_run {
+            trace!("User event hook returned false: skipping default handling");
+            handle::pending_binding_changes(key_bindings, mouse_bindings, state, x)?;
+            return handle::update_binding_overlays(
+                key_bindings,
+                key_overlays,
+                mouse_bindings,
+                state,
+                x,
+            );
+        }
+
+        match &event {
+            ClientMessage(m) => handle::client_message(m.clone(), state, x)?,
+            ConfigureNotify(e) if e.is_root => handle::detect_screens(state, x)?,
+            ConfigureNotify(_) => (), // Not currently handled
+            ConfigureRequest(e) => handle::configure_request(e, state, x)?,
+            Enter(p) => handle::enter(*p, state, x)?,
+            Expose(_) => (), // Not currently handled
+            FocusIn(id) => handle::focus_in(*id, state, x)?,
+            Destroy(xid) => handle::destroy(*xid, state, x)?,
+            KeyPress(code) => handle::keypress(*code, key_bindings, mouse_bindings, state, x)?,
+            KeyRelease(code) => handle::keyrelease(*code, key_bindings, state, x)?,
+            Leave(p) => handle::leave(*p, state, x)?,
+            MappingNotify => handle::keyboard_mapping_changed(
+                key_bindings,
+                key_overlays,
+                mouse_bindings,
+                state,
+                x,
+            )?,
+            MapRequest(xid) => handle::map_request(*xid, state, x)?,
+            MouseEvent(e) => handle::mouse_event(e.clone(), mouse_bindings, state, x)?,
+            PropertyNotify(e) => handle::property_notify(e, state, x)?,
+            RandrNotify => handle::detect_screens(state, x)?,
+            ScreenChange => handle::screen_change(state, x)?,
+            UnmapNotify(xid) => handle::unmap_notify(*xid, state, x)?,
+
+            _ => (), // XEvent is non-exhaustive
+        }
+
+        handle::pending_binding_changes(key_bindings, mouse_bindings, state, x)?;
+        handle::update_binding_overlays(key_bindings, key_overlays, mouse_bindings, state, x)
+    }
+
+    fn update_binding_overlays(&mut self) -> Result<()> {
+        handle::update_binding_overlays(
+            &mut self.key_bindings,
+            &mut self.key_overlays,
+            &self.mouse_bindings,
+            &mut self.state,
+            &self.x,
+        )
+    }
+
+    fn handle_error(&mut self, e: Error) {
+        match e {
+            // If we get an error from the XConn telling us that a client ID is unknown then
+            // we need to make sure that we remove any reference to it from our internal state
+            Error::UnknownClient(id) => {
+                debug!(%id, "XConn encountered an error due to an unknown client ID: removing client");
+                self.state.client_set.remove_client(&id);
+            }
+
+            _ => error!(%e, "Unhandled error pulling next x event"),
+        }
+    }
+}
+
+// Entry points used by crate::testing to drive the window manager without running the
+// main event loop.
+#[cfg(all(feature = "wm", any(test, feature = "testing")))]
+impl<X> WindowManager<X>
+where
+    X: XConn,
+{
+    // The startup portion of WindowManager::run
+    pub(crate) fn start_without_event_loop(&mut self) -> Result<()> {
+        acquire_wm_selection(&self.x);
+        handle::keyboard_mapping_changed(
+            &mut self.key_bindings,
+            &mut self.key_overlays,
+            &self.mouse_bindings,
+            &mut self.state,
+            &self.x,
+        )?;
+        startup_hook(&mut self.state, &self.x);
+        manage_existing_clients(None, &mut self.state, &self.x)?;
+        self.update_binding_overlays()
+    }
+
+    pub(crate) fn handle_event_without_event_loop(&mut self, event: XEvent) {
+        self.process_event(event);
+    }
+
+    pub(crate) fn run_timers_without_event_loop(&mut self) {
+        self.run_timers(true);
+    }
+
+    pub(crate) fn conn(&self) -> &X {
+        &self.x
+    }
+
+    pub(crate) fn state_and_conn(&mut self) -> (&mut State<X>, &X) {
+        (&mut self.state, &self.x)
+    }
+}
+
+// Run the user shutdown hook and then (if requested) politely ask all clients to close
+// before releasing the WM selection. Clients that do not support WM_DELETE_WINDOW are not
+// killed: they will be cleaned up by the X server when their own connection closes.
+#[cfg(feature = "wm")]
+fn shutdown<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    shutdown_hook(state, x);
+
+    if state.config.close_clients_on_shutdown {
+        let clients: Vec<Xid> = state.client_set.clients().copied().collect();
+        for id in clients {
+            if x.client_supports_protocol(id, Atom::WmDeleteWindow.as_ref())? {
+                debug!(%id, "asking client to close");
+                x.send_client_message(ClientMessageKind::DeleteWindow(id).as_message(x)?)?;
+            }
+        }
+    }
+
+    x.release_wm_selection()?;
+    x.flush();
+
+    Ok(())
+}
+
+// Failing to take the selection is not fatal: it is only used to let other clients know that
+// a window manager is running.
+#[cfg(feature = "wm")]
+fn acquire_wm_selection<X: XConn>(x: &X) {
+    info!("acquiring the WM selection");
+    if let Err(e) = x.acquire_wm_selection() {
+        warn!(%e, "unable to acquire the WM selection");
+    }
+}
+
+#[cfg(feature = "wm")]
+fn startup_hook<X: XConn>(state: &mut State<X>, x: &X) {
+    if let Some(mut h) = state.config.startup_hook.take() {
+        trace!("running user startup hook");
+        if let Err(e) = h.call(state, x) {
+            error!(%e, "error returned from user startup hook");
+        }
+    }
+}
+
+#[cfg(feature = "wm")]
+fn shutdown_hook<X: XConn>(state: &mut State<X>, x: &X) {
+    if let Some(mut h) = state.config.shutdown_hook.take() {
+        trace!("running user shutdown hook");
+        if let Err(e) = h.call(state, x) {
+            error!(%e, "error returned from user shutdown hook");
+        }
+    }
+}
+
+// A "best effort" attempt to manage existing clients on the workspaces they were present
+// on previously. This is not guaranteed to preserve the stack order or correctly handle
+// any clients that were on invisible workspaces / workspaces that no longer exist unless
+// we are being restarted in place, in which case the state saved by the previous process
+// is used to restore the full arrangement of clients.
+//
+// NOTE: the check for if each client is already in state is in case a startup hook has
+//       pre-managed clients for us. In that case we want to avoid stomping on
+//       anything that they have set up.
+#[cfg(feature = "wm")]
+#[tracing::instrument(level = "info", skip(restored, state, x))]
+fn manage_existing_clients<X: XConn>(
+    restored: Option<StateSnapshot>,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    info!("managing existing clients");
+
+    // We're not guaranteed that workspace indices are _always_ continuous from 0..n
+    // so we explicitly map tags to indices instead.
+    // We also exclude hidden workspaces as those can contain windows which are
+    // externally managed by a user written extension, which can lead to malformed
+    // internal state for those extensions when they restart.
+    let ws_map: HashMap<usize, String> = state
+        .client_set
+        .non_hidden_workspaces()
+        .map(|w| (w.id, w.tag.clone()))
+        .collect();
+
+    let first_tag = state.client_set.ordered_tags()[0].clone();
+
+    for id in x.existing_clients()? {
+        if !state.client_set.contains(&id) && client_should_be_manged(id, x) {
+            let workspace_id = match x.get_prop(id, Atom::NetWmDesktop.as_ref()) {
+                Ok(Some(Prop::Cardinal(ids))) => ids[0] as usize,
+                _ => 0, // we know that we always have at least one workspace
+            };
+
+            let tag = ws_map.get(&workspace_id).unwrap_or(&first_tag);
+            let title = x.window_title(id)?;
+            info!(%id, %title, %tag, "attempting to manage existing client");
+            manage_without_refresh(id, Some(tag), state, x)?;
+        }
+    }
+
+    if let Some(restored) = restored {
+        info!("restoring client positions from before restart");
+        restored.apply(&mut state.client_set);
+    }
+
+    info!("triggering refresh");
+    x.refresh(state)
+}
+
+/// For a given existing client being processed on startup, determine whether we need
+/// to bring it into our internal state and manage it.
+#[cfg(feature = "wm")]
+fn client_should_be_manged<X: XConn>(id: Xid, x: &X) -> bool {
+    let attrs = match x.get_window_attributes(id) {
+        Ok(attrs) => attrs,
+        _ => {
+            warn!(%id, "unable to pull window attributes for client: skipping.");
+            return false;
+        }
+    };
+
+    let wm_state = match x.get_wm_state(id) {
+        Ok(state) => state,
+        _ => {
+            warn!(%id, "unable to pull wm state for client: skipping.");
+            return false;
+        }
+    };
+
+    info!(%id, ?attrs, ?wm_state, "processing client");
+
+    let WindowAttributes {
+        override_redirect,
+        map_state,
+        ..
+    } = attrs;
+
+    let viewable = map_state == MapState::Viewable;
+    let iconic = wm_state == Some(WmState::Iconic);
+
+    // This condition for determining what windows we should manage is
+    // taken from the `scan` function found in both dwm and XMonad.
+    !override_redirect && (viewable || iconic)
+}
+
+#[cfg(all(test, feature = "wm"))]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::{
+            actions::{
+                count_digit, hide_focused_client, key_handler, modify_with, modify_with_count,
+                replay_macro, show_all_clients, spawn_and_capture, spawn_and_capture_with_input,
+                toggle_macro_recording,
+            },
+            layout::{
+                transformers::{Gaps, RotationAware},
+                Grid, MainAndStack, Monocle,
+            },
+        },
+        core::bindings::{
+            Described, HoldToRepeat, KeyChordHint, KeyEventHandler, ModifierKey, MouseButton,
+            MouseEvent, MouseEventHandler, MouseEventKind, MouseState, OnRelease, RepeatRate,
+        },
+        map,
+        pure::{test_xid_stack_set, Position},
+        stack,
+        testing::{
+            self, headless_keycodes, parse_binding_mode, parse_key_chord, HeadlessConn, Scenario,
+        },
+        x::{MockXConn, Output},
+    };
+    use simple_test_case::test_case;
+    use std::rc::Rc;
+
+    // The names of the bindings that have been run, in the order that they ran
+    type Calls = Rc<RefCell<Vec<&'static str>>>;
+
+    // A key binding that records its name in `calls` each time it is run
+    fn record(calls: &Calls, name: &'static str) -> Box<dyn KeyEventHandler<HeadlessConn>> {
+        let calls = Rc::clone(calls);
+        Box::new(move |_: &mut State<HeadlessConn>, _: &HeadlessConn| {
+            calls.borrow_mut().push(name);
+            Ok(())
+        })
+    }
+
+    // A mouse binding that records its name in `calls` each time it is run
+    fn record_mouse(calls: &Calls, name: &'static str) -> Box<dyn MouseEventHandler<HeadlessConn>> {
+        let calls = Rc::clone(calls);
+        Box::new(
+            move |_: &MouseEvent, _: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                calls.borrow_mut().push(name);
+                Ok(())
+            },
+        )
+    }
+
+    fn stack_order(cs: &ClientSet) -> Vec<u32> {
+        let positions = cs.visible_client_positions();
+        positions.iter().map(|&(id, _)| *id).collect()
+    }
+
+    #[test]
+    fn floating_client_positions_are_respected() {
+        let mut s = test_xid_stack_set(5, 2);
+
+        for n in 0..4 {
+            s.insert(Xid(n));
+        }
+
+        let r = Rect::new(50, 50, 50, 50);
+        s.float_unchecked(Xid(1), r);
+
+        let positions = s.visible_client_positions();
+
+        assert!(positions.contains(&(Xid(1), r)), "{positions:?}")
+    }
+
+    #[test]
+    fn floating_clients_stay_on_their_assigned_screen() {
+        let mut s = test_xid_stack_set(5, 2);
+
+        for n in 0..4 {
+            s.insert(Xid(n));
+        }
+
+        let r = Rect::new(50, 50, 50, 50);
+        s.float_unchecked(Xid(1), r);
+
+        let positions = s.visible_client_positions();
+
+        assert!(positions.contains(&(Xid(1), r)), "{positions:?}");
+
+        // If we move the client to tag 2 on the second screen then it should
+        // change position and be relative to that screen instead
+        s.move_client_to_tag(&Xid(1), "2");
+        let positions = s.visible_client_positions();
+
+        assert!(!positions.contains(&(Xid(1), r)), "{positions:?}");
+        assert!(
+            positions.contains(&(Xid(1), Rect::new(1050, 2050, 50, 50))),
+            "{positions:?}"
+        );
+    }
+
+    #[test]
+    fn floating_windows_are_returned_last() {
+        let mut s = test_xid_stack_set(5, 2);
+
+        for n in 1..6 {
+            s.insert(Xid(n));
+        }
+
+        s.float_unchecked(Xid(2), Rect::new(0, 0, 42, 42));
+        s.float_unchecked(Xid(3), Rect::new(0, 0, 69, 69));
+
+        assert_eq!(stack_order(&s), vec![1, 4, 5, 2, 3]);
+    }
+
+    #[test]
+    fn newly_added_windows_are_below_floating() {
+        let mut s = test_xid_stack_set(5, 2);
+
+        for n in 1..6 {
+            s.insert(Xid(n));
+        }
+
+        s.float_unchecked(Xid(2), Rect::new(0, 0, 42, 42));
+        s.float_unchecked(Xid(3), Rect::new(0, 0, 69, 69));
+
+        s.insert(Xid(6));
+
+        assert_eq!(stack_order(&s), vec![1, 4, 5, 6, 2, 3]);
+    }
+
+    #[test]
+    fn maximized_clients_fill_the_screen_above_other_tiled_clients() {
+        let mut s = test_xid_stack_set(5, 1);
+
+        for n in 1..5 {
+            s.insert(Xid(n));
+        }
+
+        s.float_unchecked(Xid(2), Rect::new(0, 0, 42, 42));
+        s.toggle_maximized(&Xid(1));
+        let r_s = s.current_screen().r;
+
+        assert_eq!(stack_order(&s), vec![3, 4, 1, 2]);
+        assert!(s.visible_client_positions().contains(&(Xid(1), r_s)));
+
+        s.toggle_maximized(&Xid(1));
+
+        assert_eq!(stack_order(&s), vec![1, 3, 4, 2]);
+    }
+
+    #[test]
+    fn floating_clients_dont_break_insert_focus() {
+        let mut s = test_xid_stack_set(1, 1);
+
+        s.insert_at(Position::Focus, Xid(0));
+        s.float_unchecked(Xid(0), Rect::new(0, 0, 42, 42));
+
+        assert_eq!(s.current_client(), Some(&Xid(0)));
+
+        // Each time we add a client it should be the focus
+        // and the floating window should be stacked above
+        // all others.
+        let mut expected = vec![0];
+        for n in 1..=5 {
+            s.insert_at(Position::Focus, Xid(n));
+            assert_eq!(s.current_client(), Some(&Xid(n)));
+
+            // Tiled position ordering is reversed in visible_client_positions
+            // in order to ensure that when we restack, the order returned
+            // is from bottom -> top of the stack to make `restack` simpler to
+            // implement.
+            expected.insert(expected.len() - 1, n);
+            assert_eq!(stack_order(&s), expected, "{:?}", s.current_stack());
+        }
+    }
+
+    #[derive(Default)]
+    struct ShutdownXConn {
+        closed: RefCell<Vec<Xid>>,
+    }
+
+    impl MockXConn for ShutdownXConn {
+        fn mock_screen_details(&self) -> Result<Vec<Rect>> {
+            Ok(vec![Rect::new(0, 0, 1920, 1080)])
+        }
+
+        // Only clients 2 and 4 support WM_DELETE_WINDOW
+        fn mock_get_prop(&self, id: Xid, prop_name: &str) -> Result<Option<Prop>> {
+            if prop_name == Atom::WmProtocols.as_ref() && [Xid(2), Xid(4)].contains(&id) {
+                let proto = Atom::WmDeleteWindow.as_ref().to_string();
+                Ok(Some(Prop::Atom(vec![proto])))
+            } else {
+                Ok(None)
+            }
+        }
+
+        fn mock_intern_atom(&self, _: &str) -> Result<Xid> {
+            Ok(Xid(0))
+        }
+
+        fn mock_send_client_message(&self, msg: crate::x::event::ClientMessage) -> Result<()> {
+            self.closed.borrow_mut().push(msg.id);
+            Ok(())
+        }
+    }
+
+    #[test_case(false, &[]; "clients left running")]
+    #[test_case(true, &[2, 4]; "supporting clients closed")]
+    #[test]
+    fn shutdown_runs_hook_and_closes_clients(close_clients: bool, expected: &[u32]) {
+        let conn = ShutdownXConn::default();
+        let mut config: Config<ShutdownXConn> = Config {
+            close_clients_on_shutdown: close_clients,
+            ..Default::default()
+        };
+        config.compose_or_set_shutdown_hook(|state: &mut State<ShutdownXConn>, _: &_| {
+            state.add_extension(String::from("persisted"));
+            Ok(())
+        });
+        let mut state = State::try_new(config, &conn).expect("test state");
+        for n in 1..=4 {
+            state.client_set.insert(Xid(n));
+        }
+
+        shutdown(&mut state, &conn).expect("shutdown to succeed");
+
+        let mut closed: Vec<u32> = conn.closed.borrow().iter().map(|id| **id).collect();
+        closed.sort();
+        assert_eq!(closed, expected);
+        assert!(state.extension::<String>().is_ok(), "shutdown hook was run");
+    }
+
+    #[test_case(true; "enabled")]
+    #[test_case(false; "disabled")]
+    #[test]
+    fn borders_can_be_disabled_per_workspace(enabled: bool) {
+        let conn = ShutdownXConn::default();
+        let config: Config<ShutdownXConn> = Config {
+            border_width: 3,
+            ..Default::default()
+        };
+        let mut state = State::try_new(config, &conn).expect("test state");
+        for n in 1..=3 {
+            state.client_set.insert(Xid(n));
+        }
+        state
+            .client_set
+            .float_unchecked(Xid(2), Rect::new(0, 0, 42, 42));
+        state
+            .client_set
+            .current_workspace_mut()
+            .set_borders_enabled(enabled);
+
+        state.visible_client_positions(&conn);
+
+        let expected = if enabled { 3 } else { 0 };
+        for n in 1..=3 {
+            assert_eq!(state.border_width_for(Xid(n)), expected, "client {n}");
+        }
+    }
+
+    #[test_case(None, 3; "unscaled")]
+    #[test_case(Some(192.0), 6; "scaled")]
+    #[test]
+    fn border_widths_are_scaled_by_screen_dpi(dpi: Option<f64>, expected: u32) {
+        let conn = ShutdownXConn::default();
+        let config: Config<ShutdownXConn> = Config {
+            border_width: 3,
+            dpi_scaling: dpi.map(|dpi| DpiScaling::default().with_screen_dpi(0, dpi)),
+            ..Default::default()
+        };
+        let mut state = State::try_new(config, &conn).expect("test state");
+        for n in 1..=3 {
+            state.client_set.insert(Xid(n));
+        }
+        state
+            .client_set
+            .float_unchecked(Xid(2), Rect::new(0, 0, 42, 42));
+
+        state.visible_client_positions(&conn);
+
+        for n in 1..=3 {
+            assert_eq!(state.border_width_for(Xid(n)), expected, "client {n}");
+        }
+    }
+
+    fn pointer_change(id: u32, x: u32, y: u32) -> crate::x::event::PointerChange {
+        crate::x::event::PointerChange {
+            id: Xid(id),
+            abs: Point::new(x, y),
+            relative: Point::new(0, 0),
+            same_screen: true,
+        }
+    }
+
+    #[test]
+    fn delayed_focus_is_cancelled_when_the_pointer_leaves() {
+        let conn = ShutdownXConn::default();
+        let config: Config<ShutdownXConn> = Config {
+            focus_follow_mouse_delay: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut state = State::try_new(config, &conn).expect("test state");
+        for n in 1..=2 {
+            state.client_set.insert(Xid(n));
+        }
+
+        handle::enter(pointer_change(1, 10, 10), &mut state, &conn).unwrap();
+        assert_eq!(state.focus.pending.map(|(id, _)| id), Some(Xid(1)));
+        assert_eq!(state.client_set.current_client(), Some(&Xid(2)));
+
+        handle::leave(pointer_change(2, 10, 10), &mut state, &conn).unwrap();
+        assert!(state.focus.pending.is_some(), "leaving another client");
+
+        handle::leave(pointer_change(1, 10, 10), &mut state, &conn).unwrap();
+        assert!(state.focus.pending.is_none());
+    }
+
+    #[test_case(true, Point::new(10, 10), false; "stationary pointer")]
+    #[test_case(true, Point::new(20, 20), true; "pointer moved")]
+    #[test_case(false, Point::new(10, 10), true; "motion not required")]
+    #[test]
+    fn focus_only_follows_pointer_motion_when_required(
+        requires_motion: bool,
+        at_refresh: Point,
+        follows: bool,
+    ) {
+        let conn = ShutdownXConn::default();
+        let config: Config<ShutdownXConn> = Config {
+            focus_follow_mouse_delay: Some(Duration::from_secs(60)),
+            focus_follow_mouse_requires_motion: requires_motion,
+            ..Default::default()
+        };
+        let mut state = State::try_new(config, &conn).expect("test state");
+        state.client_set.insert(Xid(1));
+        state.focus.pointer_at_refresh = Some(at_refresh);
+
+        handle::enter(pointer_change(1, 10, 10), &mut state, &conn).unwrap();
+
+        assert_eq!(state.focus.pending.is_some(), follows);
+    }
+
+    #[test]
+    fn published_values_replace_previous_values() {
+        #[derive(Debug, PartialEq)]
+        struct Mode(&'static str);
+
+        let conn = ShutdownXConn::default();
+        let mut state = State::try_new(Config::default(), &conn).expect("test state");
+
+        assert_eq!(state.published::<Mode>(), None);
+        assert_eq!(state.publish(Mode("normal")), None);
+        assert_eq!(state.publish(Mode("resize")), Some(Mode("normal")));
+        state.publish(3usize);
+
+        assert_eq!(state.published::<Mode>(), Some(&Mode("resize")));
+        assert_eq!(state.published::<usize>(), Some(&3));
+        assert_eq!(state.unpublish::<Mode>(), Some(Mode("resize")));
+        assert_eq!(state.published::<Mode>(), None);
+    }
+
+    #[test]
+    fn client_data_can_be_attached_and_queried() {
+        #[derive(Debug, PartialEq)]
+        struct Project(&'static str);
+
+        let conn = ShutdownXConn::default();
+        let mut state = State::try_new(Config::default(), &conn).expect("test state");
+
+        assert_eq!(state.set_client_data(Xid(1), Project("penrose")), None);
+        state.set_client_data(Xid(1), 42u32);
+        state.set_client_data(Xid(2), Project("other"));
+
+        if let Some(n) = state.client_data_mut::<u32>(&Xid(1)) {
+            *n += 1;
+        }
+        assert_eq!(state.client_data::<u32>(&Xid(1)), Some(&43));
+        assert_eq!(state.client_data::<u32>(&Xid(2)), None);
+
+        let mut projects: Vec<_> = state.clients_with_data::<Project>().collect();
+        projects.sort_by_key(|&(id, _)| id);
+        assert_eq!(
+            projects,
+            vec![(Xid(1), &Project("penrose")), (Xid(2), &Project("other"))]
+        );
+
+        assert_eq!(
+            state.remove_client_data::<Project>(&Xid(2)),
+            Some(Project("other"))
+        );
+        assert!(
+            !state.client_data.contains_key(&Xid(2)),
+            "empty maps are removed"
+        );
+    }
+
+    #[derive(Default)]
+    struct KillXConn {
+        killed: RefCell<Vec<Xid>>,
+    }
+
+    impl MockXConn for KillXConn {
+        fn mock_screen_details(&self) -> Result<Vec<Rect>> {
+            Ok(vec![Rect::new(0, 0, 1920, 1080)])
+        }
+
+        // Client 2 has already exited
+        fn mock_client_geometry(&self, id: Xid) -> Result<Rect> {
+            if id == Xid(2) {
+                Err(Error::UnknownClient(id))
+            } else {
+                Ok(Rect::new(0, 0, 100, 100))
+            }
+        }
+
+        fn mock_kill(&self, id: Xid) -> Result<()> {
+            self.killed.borrow_mut().push(id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn clients_still_alive_after_the_kill_timeout_are_forcibly_killed() {
+        let conn = KillXConn::default();
+        let mut state = State::try_new(Config::default(), &conn).expect("test state");
+        let now = Instant::now();
+        state.client_timeouts.kills.insert(Xid(1), now);
+        state.client_timeouts.kills.insert(Xid(2), now);
+        state
+            .client_timeouts
+            .kills
+            .insert(Xid(3), now + Duration::from_secs(60));
+
+        handle::pending_kills(&mut state, &conn).unwrap();
+
+        assert_eq!(*conn.killed.borrow(), vec![Xid(1)]);
+        assert_eq!(
+            state.client_timeouts.kills.keys().collect::<Vec<_>>(),
+            vec![&Xid(3)]
+        );
+    }
+
+    fn splash_scenario(splash_timeout: Option<Duration>) -> (Scenario, Xid) {
+        let conn = HeadlessConn::default();
+        let splash = conn.create_window("splash");
+        conn.set_prop(
+            splash,
+            Atom::NetWmWindowType.as_ref(),
+            Prop::Atom(vec![Atom::NetWindowTypeSplash.as_ref().to_string()]),
+        )
+        .unwrap();
+
+        let config = Config {
+            splash_timeout,
+            ..Config::default()
+        };
+        let wm = WindowManager::new(config, HashMap::new(), HashMap::new(), conn).unwrap();
+        let s = Scenario::new(wm)
+            .unwrap()
+            .given_windows(["a"])
+            .send(XEvent::MapRequest(splash));
+
+        (s, splash)
+    }
+
+    #[test]
+    fn splash_windows_are_centered_without_being_managed() {
+        let (s, splash) = splash_scenario(None);
+        let w = s.conn().window(splash).unwrap();
+
+        assert!(w.mapped);
+        assert_eq!(w.geometry, Rect::new(640, 300, 640, 480));
+        assert!(!s.state().client_set.contains(&splash));
+        assert!(s.state().client_timeouts.splashes.contains_key(&splash));
+
+        let s = s
+            .expect_focus("a")
+            .expect_geometry("a", Rect::new(0, 0, 1916, 1076));
+        s.conn().destroy_window(splash);
+        let s = s.act(|_, _| Ok(()));
+
+        assert!(s.state().client_timeouts.splashes.is_empty());
+        s.expect_visible(["a"]);
+    }
+
+    #[test]
+    fn splash_windows_are_hidden_once_the_timeout_elapses() {
+        let (s, splash) = splash_scenario(Some(Duration::ZERO));
+
+        assert!(s.conn().window(splash).unwrap().mapped);
+
+        let s = s.wait(Duration::ZERO);
+
+        assert!(!s.conn().window(splash).unwrap().mapped);
+        assert!(s.state().client_timeouts.splashes.is_empty());
+        s.expect_visible(["a"]);
+    }
+
+    fn hidden_client_scenario() -> Scenario {
+        let bindings: HashMap<&str, Box<dyn KeyEventHandler<HeadlessConn>>> = map! {
+            "M-h" => hide_focused_client(),
+            "M-s" => show_all_clients(),
+        };
+
+        Scenario::with_bindings(Config::default(), bindings)
+            .unwrap()
+            .given_windows(["a", "b", "c"])
+    }
+
+    #[test]
+    fn hidden_clients_are_excluded_from_the_layout() {
+        let s = hidden_client_scenario()
+            .press("M-h")
+            .expect_visible(["a", "b"])
+            .expect_stack(["c", "b", "a"]);
+
+        assert_eq!(s.state().hidden_clients("1"), &[s.id("c")]);
+
+        s.press("M-s").expect_visible(["a", "b", "c"]);
+    }
+
+    #[test]
+    fn the_focused_client_is_never_hidden() {
+        let s = hidden_client_scenario().press("M-h").press("M-h");
+        let focused = s.state().client_set.current_client().copied();
+        let s = s.press("M-h");
+
+        assert_eq!(s.state().client_set.current_client().copied(), focused);
+        assert_eq!(s.state().hidden_clients("1").len(), 3);
+        assert_eq!(s.state().client_positions().len(), 1);
+    }
+
+    #[test]
+    fn hidden_clients_are_forgotten_when_unmanaged() {
+        let s = hidden_client_scenario().press("M-h").close("c");
+
+        assert!(s.state().hidden_clients("1").is_empty());
+        assert!(s.state().layout_overrides.hidden.is_empty());
+    }
+
+    fn chord_scenario(timeout: Option<Duration>) -> (Scenario, Calls) {
+        let calls = Calls::default();
+
+        let inner = parse_key_chord(map! { "x" => record(&calls, "w-t-x"), }).unwrap();
+        let chord = parse_key_chord(map! {
+            "j" => record(&calls, "w-j"),
+            "S-k" => record(&calls, "w-S-k"),
+            "t" => Box::new(inner) as Box<dyn KeyEventHandler<HeadlessConn>>,
+        })
+        .unwrap()
+        .with_name("window")
+        .with_timeout(timeout);
+
+        let bindings = map! {
+            "M-w" => Box::new(chord) as Box<dyn KeyEventHandler<HeadlessConn>>,
+            "M-j" => record(&calls, "M-j"),
+        };
+        let s = Scenario::with_bindings(Config::default(), bindings).unwrap();
+
+        (s, calls)
+    }
+
+    fn chord_hint(s: &Scenario) -> Option<KeyChordHint> {
+        s.state().published::<KeyChordHint>().cloned()
+    }
+
+    #[test_case(&["M-j"], &["M-j"]; "normal binding")]
+    #[test_case(&["M-w", "j"], &["w-j"]; "chord binding")]
+    #[test_case(&["M-w", "S-k", "M-j"], &["w-S-k", "M-j"]; "normal bindings are restored")]
+    #[test_case(&["j", "M-w", "j", "j"], &["w-j"]; "chord keys are only bound in the chord")]
+    #[test_case(&["M-w", "M-j", "j"], &[]; "other keys end the chord")]
+    #[test_case(&["M-w", "Escape", "j"], &[]; "escape")]
+    #[test_case(&["M-w", "t", "x"], &["w-t-x"]; "nested chord")]
+    #[test]
+    fn key_chords(keys: &[&str], expected: &[&str]) {
+        let (s, calls) = chord_scenario(None);
+        let s = keys.iter().fold(s, |s, k| s.press(k));
+
+        assert_eq!(*calls.borrow(), expected);
+        assert!(s.state().bindings.active_chord.is_none());
+        assert!(chord_hint(&s).is_none());
+    }
+
+    #[test]
+    fn key_chord_hints_are_published_while_waiting() {
+        let (s, _) = chord_scenario(None);
+        let s = s.press("M-w");
+
+        let hint = KeyChordHint {
+            name: Some("window".to_string()),
+            keys: vec!["S-k".to_string(), "j".to_string(), "t".to_string()],
+        };
+        assert_eq!(chord_hint(&s), Some(hint));
+    }
+
+    #[test]
+    fn key_chords_time_out() {
+        let (s, calls) = chord_scenario(Some(Duration::ZERO));
+        let s = s.press("M-w").wait(Duration::ZERO);
+
+        assert!(s.state().bindings.active_chord.is_none());
+        assert!(chord_hint(&s).is_none());
+
+        s.press("j");
+        assert!(calls.borrow().is_empty());
+    }
+
+    fn scroll_scenario() -> Scenario {
+        let next_ws = |_: &MouseEvent, state: &mut State<HeadlessConn>, x: &HeadlessConn| {
+            x.modify_and_refresh(state, |cs| cs.focus_tag("2"))
+        };
+        let mouse_bindings: MouseBindings<HeadlessConn> = map! {
+            (MouseEventKind::Press, MouseState::new(MouseButton::ScrollRight, vec![])) =>
+                Box::new(next_ws) as Box<dyn MouseEventHandler<HeadlessConn>>,
+        };
+        let wm = WindowManager::new(
+            Config::default(),
+            HashMap::new(),
+            mouse_bindings,
+            HeadlessConn::default(),
+        )
+        .unwrap();
+
+        Scenario::new(wm).unwrap()
+    }
+
+    #[test_case(MouseButton::ScrollRight, "2"; "bound scroll button")]
+    #[test_case(MouseButton::ScrollLeft, "1"; "unbound scroll button")]
+    #[test]
+    fn scroll_bindings_run_for_scroll_events(button: MouseButton, expected_tag: &str) {
+        let s = scroll_scenario();
+        let root = s.state().root();
+        let state = MouseState::new(button, vec![]);
+        let event = MouseEvent::new(root, 10, 10, 10, 10, state, MouseEventKind::Press);
+
+        s.send(XEvent::MouseEvent(event))
+            .expect_workspace(expected_tag);
+    }
+
+    fn click_scenario(double_click: bool) -> (Scenario, Calls) {
+        let calls = Calls::default();
+
+        let state = MouseState::new(MouseButton::Left, vec![ModifierKey::Meta]);
+        let mut mouse_bindings = map! {
+            (MouseEventKind::Press, state.clone()) => record_mouse(&calls, "press"),
+        };
+        if double_click {
+            mouse_bindings.insert(
+                (MouseEventKind::DoubleClick, state),
+                record_mouse(&calls, "double"),
+            );
+        }
+
+        let wm = WindowManager::new(
+            Config::default(),
+            HashMap::new(),
+            mouse_bindings,
+            HeadlessConn::default(),
+        )
+        .unwrap();
+
+        (Scenario::new(wm).unwrap(), calls)
+    }
+
+    #[test_case(true, &[0], &["press"]; "single click")]
+    #[test_case(true, &[0, 0], &["press", "double"]; "double click")]
+    #[test_case(true, &[0, 0, 0], &["press", "double", "press"]; "triple click")]
+    #[test_case(true, &[0, 1], &["press", "press"]; "different windows")]
+    #[test_case(false, &[0, 0], &["press", "press"]; "no double click binding")]
+    #[test]
+    fn double_click_bindings(double_click: bool, windows: &[u32], expected: &[&str]) {
+        let (s, calls) = click_scenario(double_click);
+        let state = MouseState::new(MouseButton::Left, vec![ModifierKey::Meta]);
+
+        windows.iter().fold(s, |s, &id| {
+            let e = MouseEvent::new(
+                Xid(id),
+                10,
+                10,
+                10,
+                10,
+                state.clone(),
+                MouseEventKind::Press,
+            );
+            s.send(XEvent::MouseEvent(e))
+        });
+
+        assert_eq!(*calls.borrow(), expected);
+    }
+
+    #[test]
+    fn mouse_bindings_without_modifiers_are_not_grabbed() {
+        let noop = |_: &MouseEvent, _: &mut State<HeadlessConn>, _: &HeadlessConn| Ok(());
+        let with_mod = MouseState::new(MouseButton::ScrollUp, vec![ModifierKey::Meta]);
+        let without_mod = MouseState::new(MouseButton::ScrollUp, vec![]);
+        let mouse_bindings: MouseBindings<HeadlessConn> = map! {
+            (MouseEventKind::Press, with_mod.clone()) =>
+                Box::new(noop) as Box<dyn MouseEventHandler<HeadlessConn>>,
+            (MouseEventKind::Press, without_mod) =>
+                Box::new(noop) as Box<dyn MouseEventHandler<HeadlessConn>>,
+        };
+
+        assert_eq!(
+            handle::grabbed_mouse_states(&mouse_bindings),
+            vec![with_mod]
+        );
+    }
+
+    #[test_case(6, MouseButton::ScrollLeft; "scroll left")]
+    #[test_case(7, MouseButton::ScrollRight; "scroll right")]
+    #[test]
+    fn horizontal_scroll_buttons_round_trip(n: u8, button: MouseButton) {
+        assert_eq!(MouseButton::try_from(n).unwrap(), button);
+        assert_eq!(u8::from(button), n);
+    }
+
+    type ModeChanges = Rc<RefCell<Vec<Option<String>>>>;
+
+    fn mode_scenario() -> (Scenario, Calls, ModeChanges) {
+        let calls = Calls::default();
+
+        let mv = parse_binding_mode("move", map! { "x" => record(&calls, "m-x"), }).unwrap();
+        let chord = parse_key_chord(map! { "y" => record(&calls, "r-c-y"), }).unwrap();
+        let resize = parse_binding_mode(
+            "resize",
+            map! {
+                "h" => record(&calls, "r-h"),
+                "l" => record(&calls, "r-l"),
+                "m" => Box::new(mv) as Box<dyn KeyEventHandler<HeadlessConn>>,
+                "c" => Box::new(chord) as Box<dyn KeyEventHandler<HeadlessConn>>,
+            },
+        )
+        .unwrap();
+
+        let modes = Rc::new(RefCell::new(Vec::new()));
+        let mut config = Config::default();
+        let changes = Rc::clone(&modes);
+        config.compose_or_set_mode_change_hook(
+            move |state: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                changes
+                    .borrow_mut()
+                    .push(state.active_mode().map(String::from));
+                Ok(())
+            },
+        );
+
+        let bindings = map! {
+            "M-r" => Box::new(resize) as Box<dyn KeyEventHandler<HeadlessConn>>,
+            "M-j" => record(&calls, "M-j"),
+        };
+        let s = Scenario::with_bindings(config, bindings).unwrap();
+
+        (s, calls, modes)
+    }
+
+    #[test_case(&["M-r", "h", "h", "Escape", "h", "M-j"], &["r-h", "r-h", "M-j"], &[Some("resize"), None], None; "escape exits")]
+    #[test_case(&["M-r", "M-j", "l", "Return", "M-j"], &["r-l", "M-j"], &[Some("resize"), None], None; "other keys are ignored")]
+    #[test_case(&["M-r", "l", "M-r", "h"], &["r-l", "r-h"], &[Some("resize")], Some("resize"); "mode stays active")]
+    #[test_case(&["M-r", "m", "x", "h", "Escape"], &["m-x"], &[Some("resize"), Some("move"), None], None; "switching modes")]
+    #[test_case(&["M-r", "c", "y", "h", "M-j"], &["r-c-y", "M-j"], &[Some("resize"), None], None; "chords replace the mode")]
+    #[test]
+    fn binding_modes(
+        keys: &[&str],
+        expected: &[&str],
+        expected_modes: &[Option<&str>],
+        active_mode: Option<&str>,
+    ) {
+        let (s, calls, modes) = mode_scenario();
+        let s = keys.iter().fold(s, |s, k| s.press(k));
+        let expected_modes: Vec<Option<String>> =
+            expected_modes.iter().map(|m| m.map(String::from)).collect();
+
+        assert_eq!(*calls.borrow(), expected);
+        assert_eq!(*modes.borrow(), expected_modes);
+        assert_eq!(s.state().active_mode(), active_mode);
+        assert_eq!(
+            s.state().bindings.active_chord.is_some(),
+            active_mode.is_some()
+        );
+    }
+
+    #[test_case(&[("M-Tab", true)], &[]; "press only")]
+    #[test_case(&[("M-Tab", true), ("M-Tab", false)], &["M-Tab"]; "press and release")]
+    #[test_case(&[("M-Tab", true), ("M-Tab", true), ("M-Tab", true), ("M-Tab", false)], &["M-Tab"]; "repeats are ignored")]
+    #[test_case(&[("M-Tab", true), ("Tab", false)], &["M-Tab"]; "modifier released first")]
+    #[test_case(&[("M-Tab", false)], &[]; "release without press")]
+    #[test_case(&[("M-Tab", true), ("M-Tab", false), ("M-Tab", false)], &["M-Tab"]; "duplicate release")]
+    #[test_case(&[("M-j", true), ("M-j", false)], &["M-j"]; "press bindings ignore release")]
+    #[test]
+    fn release_bindings(keys: &[(&str, bool)], expected: &[&str]) {
+        let calls = Calls::default();
+
+        let bindings = map! {
+            "M-Tab" => OnRelease::new(record(&calls, "M-Tab")).boxed(),
+            "M-j" => record(&calls, "M-j"),
+        };
+        let s = Scenario::with_bindings(Config::default(), bindings).unwrap();
+        keys.iter().fold(
+            s,
+            |s, &(k, pressed)| {
+                if pressed {
+                    s.press(k)
+                } else {
+                    s.release(k)
+                }
+            },
+        );
+
+        assert_eq!(*calls.borrow(), expected);
+    }
+
+    fn repeat_scenario(delay: Duration) -> (Scenario, Calls) {
+        let calls = Calls::default();
+        let rate = RepeatRate::new(delay, Duration::ZERO);
+
+        let bindings = map! {
+            "M-l" => HoldToRepeat::new(record(&calls, "M-l")).with_rate(rate).boxed(),
+            "M-j" => record(&calls, "M-j"),
+        };
+        let s = Scenario::with_bindings(Config::default(), bindings).unwrap();
+
+        (s, calls)
+    }
+
+    // Due repeats are run before handling the next event as well as when the event loop wakes
+    // up: releasing an unbound key stands in for the wake up here. With no delay or interval a repeat is due on
+    // every event, including the one releasing the held key.
+    #[test_case(&[("M-l", true)], &["M-l"], true; "press")]
+    #[test_case(&[("M-l", true), ("x", false), ("x", false)], &["M-l", "M-l", "M-l"], true; "held")]
+    #[test_case(&[("M-l", true), ("M-l", true), ("M-l", true)], &["M-l", "M-l", "M-l"], true; "auto-repeated presses are ignored")]
+    #[test_case(&[("M-l", true), ("l", false), ("x", false)], &["M-l", "M-l"], false; "release stops repeating")]
+    #[test_case(&[("M-l", true), ("l", false), ("M-l", true)], &["M-l", "M-l", "M-l"], true; "pressing again")]
+    #[test_case(&[("M-l", true), ("M-j", true), ("x", false)], &["M-l", "M-l", "M-j", "M-l"], true; "other bindings while held")]
+    #[test_case(&[("M-j", true), ("x", false), ("x", false)], &["M-j"], false; "other bindings do not repeat")]
+    #[test]
+    fn hold_to_repeat_bindings(keys: &[(&str, bool)], expected: &[&str], held: bool) {
+        let (s, calls) = repeat_scenario(Duration::ZERO);
+        let s = keys.iter().fold(
+            s,
+            |s, &(k, pressed)| {
+                if pressed {
+                    s.press(k)
+                } else {
+                    s.release(k)
+                }
+            },
+        );
+
+        assert_eq!(*calls.borrow(), expected);
+        assert_eq!(s.state().bindings.held_key.is_some(), held);
+    }
+
+    #[test]
+    fn hold_to_repeat_waits_for_the_initial_delay() {
+        let (s, calls) = repeat_scenario(Duration::from_secs(3600));
+        s.press("M-l").release("x").press("M-l").release("x");
+
+        assert_eq!(*calls.borrow(), vec!["M-l"]);
+    }
+
+    fn count_scenario() -> Scenario {
+        let chord = parse_key_chord(map! {
+            "j" => modify_with_count(|cs| cs.focus_down()),
+        })
+        .unwrap();
+
+        let bindings = map! {
+            "M-A-1" => count_digit(1),
+            "M-A-2" => count_digit(2),
+            "M-j" => modify_with_count(|cs| cs.focus_down()),
+            "M-k" => modify_with(|cs| cs.focus_up()),
+            "M-c" => Box::new(chord) as Box<dyn KeyEventHandler<HeadlessConn>>,
+        };
+
+        Scenario::with_bindings(Config::default(), bindings)
+            .unwrap()
+            .given_windows([
+                "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n",
+            ])
+    }
+
+    #[test_case(&["M-j"], "m"; "no count")]
+    #[test_case(&["M-A-2", "M-j"], "l"; "single digit")]
+    #[test_case(&["M-A-1", "M-A-2", "M-j"], "b"; "multiple digits")]
+    #[test_case(&["M-A-2", "M-j", "M-j"], "k"; "count is consumed")]
+    #[test_case(&["M-A-2", "M-k", "M-j"], "n"; "ignored counts are consumed")]
+    #[test_case(&["M-A-2", "M-c", "j"], "l"; "count is passed through chords")]
+    #[test_case(&["M-A-2", "M-c", "Escape", "M-j"], "m"; "cancelled chords clear the count")]
+    #[test]
+    fn count_prefix(keys: &[&str], expected: &str) {
+        let s = keys.iter().fold(count_scenario(), |s, k| s.press(k));
+
+        s.expect_focus(expected);
+    }
+
+    #[test_case(|cs| cs.focus_down(), 999; "counts are capped")]
+    #[test_case(|_| (), 1; "repeating stops once nothing changes")]
+    #[test]
+    fn counted_modifications(f: fn(&mut ClientSet), expected: usize) {
+        let calls = Calls::default();
+        let c = Rc::clone(&calls);
+        let bindings = map! {
+            "M-A-9" => count_digit(9),
+            "M-j" => modify_with_count(move |cs| {
+                c.borrow_mut().push("M-j");
+                f(cs);
+            }),
+        };
+
+        let s = Scenario::with_bindings(Config::default(), bindings)
+            .unwrap()
+            .given_windows(["a", "b"]);
+        let _ = ["M-A-9"; 30].iter().fold(s, |s, k| s.press(k)).press("M-j");
+
+        assert_eq!(calls.borrow().len(), expected);
+    }
+
+    fn macro_scenario() -> Scenario {
+        let chord = parse_key_chord(map! {
+            "j" => modify_with(|cs| cs.focus_down()),
+        })
+        .unwrap();
+
+        let bindings = map! {
+            "M-A-2" => count_digit(2),
+            "M-q" => toggle_macro_recording("q"),
+            "M-w" => toggle_macro_recording("w"),
+            "M-2" => replay_macro("q"),
+            "M-3" => replay_macro("w"),
+            "M-j" => modify_with_count(|cs| cs.focus_down()),
+            "M-k" => modify_with(|cs| cs.focus_up()),
+            "M-c" => Box::new(chord) as Box<dyn KeyEventHandler<HeadlessConn>>,
+        };
+
+        Scenario::with_bindings(Config::default(), bindings)
+            .unwrap()
+            .given_windows([
+                "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n",
+            ])
+    }
+
+    #[test_case(&["M-q", "M-j", "M-j", "M-q"], "l"; "recording runs bindings")]
+    #[test_case(&["M-q", "M-j", "M-j", "M-q", "M-2"], "j"; "replay")]
+    #[test_case(&["M-q", "M-j", "M-q", "M-2", "M-2", "M-2"], "j"; "repeated replay")]
+    #[test_case(&["M-q", "M-j", "M-q", "M-A-2", "M-2"], "k"; "count repeats the replay")]
+    #[test_case(&["M-q", "M-j", "x", "M-k", "M-j", "M-q", "M-2"], "l"; "unbound keys are ignored")]
+    #[test_case(&["M-q", "M-c", "j", "M-q", "M-2"], "l"; "chords")]
+    #[test_case(&["M-q", "M-A-2", "M-j", "M-q", "M-2"], "j"; "counts are recorded")]
+    #[test_case(&["M-q", "M-j", "M-q", "M-w", "M-2", "M-2", "M-w", "M-3"], "k"; "nested replays are ignored")]
+    #[test_case(&["M-q", "M-j", "M-q", "M-q", "M-k", "M-q", "M-2"], "a"; "recording again replaces the macro")]
+    #[test_case(&["M-2", "M-j"], "m"; "unknown macros are ignored")]
+    #[test]
+    fn key_macros(keys: &[&str], expected: &str) {
+        let s = keys.iter().fold(macro_scenario(), |s, k| s.press(k));
+
+        s.expect_focus(expected);
+    }
+
+    #[test]
+    fn toggle_keys_are_not_recorded() {
+        let s = ["M-q", "M-j", "M-q"]
+            .iter()
+            .fold(macro_scenario(), |s, k| s.press(k));
+
+        assert_eq!(s.state().recording_macro(), None);
+        assert_eq!(s.state().bindings.macros.saved["q"].len(), 1);
+    }
+
+    #[test]
+    fn replaying_an_unknown_macro_is_an_error() {
+        let mut res = Ok(());
+        macro_scenario().act(|state, _| {
+            res = state.replay_macro("missing", 1);
+            Ok(())
+        });
+
+        assert!(matches!(res, Err(Error::UnknownMacro { name }) if name == "missing"));
+    }
+
+    // Command output is handled once the event loop is woken after the command exits
+    fn await_output<T>(mut s: Scenario, results: &Rc<RefCell<Vec<T>>>) -> Scenario {
+        for _ in 0..500 {
+            if !results.borrow().is_empty() {
+                break;
+            }
+            s = s.wait(Duration::from_millis(10));
+        }
+
+        s
+    }
+
+    #[test_case("echo hello world", None, "hello world\n", 0; "stdout")]
+    #[test_case("false", None, "", 1; "exit status")]
+    #[test_case("cat", Some("a\nb"), "a\nb", 0; "input")]
+    #[test_case("sort -r", Some("a\nb\n"), "b\na\n", 0; "input with args")]
+    #[test_case("not-a-real-command-for-penrose", None, "", 127; "missing command")]
+    #[test]
+    fn spawn_and_capture_passes_output_to_handler(
+        cmd: &str,
+        input: Option<&str>,
+        stdout: &str,
+        status: i32,
+    ) {
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let r = Rc::clone(&results);
+        let handler = move |out: CommandOutput, _: &mut State<HeadlessConn>, _: &HeadlessConn| {
+            r.borrow_mut().push(out);
+            Ok(())
+        };
+
+        let action = match input {
+            Some(input) => spawn_and_capture_with_input(cmd, input, handler),
+            None => spawn_and_capture(cmd, handler),
+        };
+        let s = Scenario::with_bindings(Config::default(), map! { "M-o" => action, }).unwrap();
+        let s = await_output(s.press("M-o"), &results);
+
+        let expected = CommandOutput {
+            stdout: stdout.to_string(),
+            status,
+        };
+        assert_eq!(*results.borrow(), vec![expected]);
+        assert!(s.state().deferred.output.is_empty());
+    }
+
+    #[test]
+    fn spawn_and_capture_handlers_can_modify_state() {
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let r = Rc::clone(&results);
+        let action = spawn_and_capture_with_input(
+            "head -n1",
+            "3\n2\n1",
+            move |out, s: &mut State<HeadlessConn>, x: &HeadlessConn| {
+                r.borrow_mut().push(());
+                x.modify_and_refresh(s, |cs| cs.focus_tag(out.stdout.trim()))
+            },
+        );
+        let s = Scenario::with_bindings(Config::default(), map! { "M-o" => action, }).unwrap();
+
+        await_output(s.press("M-o"), &results).expect_workspace("3");
+    }
+
+    #[test]
+    fn timers_run_once_their_delay_has_elapsed() {
+        let s = Scenario::with_bindings(Config::default(), HashMap::<&str, _>::new())
+            .unwrap()
+            .act(|state, _| {
+                state.run_after(Duration::from_millis(50), |s, x| {
+                    x.modify_and_refresh(s, |cs| cs.focus_tag("3"))
+                });
+                Ok(())
+            });
+
+        s.expect_workspace("1")
+            .wait(Duration::ZERO)
+            .expect_workspace("1")
+            .wait(Duration::from_millis(60))
+            .expect_workspace("3");
+    }
+
+    const XMODMAP_OUTPUT: &str = "
+keycode  24 = q Q q Q Cyrillic_shorti Cyrillic_SHORTI
+keycode  38 = a A a A
+keycode  48 = apostrophe quotedbl dead_acute
+keycode 121 = XF86AudioMute NoSymbol XF86AudioMute
+keycode 200 = NoSymbol
+keycode 201 = A
+";
+
+    #[test_case("a", Some(38); "unshifted")]
+    #[test_case("A", Some(201); "unshifted on another key is preferred")]
+    #[test_case("Cyrillic_shorti", Some(24); "non latin group")]
+    #[test_case("dead_acute", Some(48); "dead key")]
+    #[test_case("XF86AudioMute", Some(121); "media key")]
+    #[test_case("NoSymbol", None; "no symbol")]
+    #[test_case("b", None; "unknown")]
+    #[test]
+    fn keymap_code_for(name: &str, expected: Option<u8>) {
+        let keymap = Keymap::parse_xmodmap(XMODMAP_OUTPUT).unwrap();
+
+        assert_eq!(keymap.code_for(name), expected);
+        assert_eq!(keymap.keycodes().get(name).copied(), expected);
+    }
+
+    #[test]
+    fn keymap_parse_xmodmap_rejects_invalid_output() {
+        assert!(Keymap::parse_xmodmap("keycode 38 a A").is_err());
+    }
+
+    fn swapped_ab_keymap() -> Keymap {
+        let codes = headless_keycodes();
+        Keymap::new(codes.into_iter().map(|(name, code)| {
+            let name = match name.as_str() {
+                "a" => "b".to_string(),
+                "b" => "a".to_string(),
+                _ => name,
+            };
+            (code, vec![name])
+        }))
+    }
+
+    #[test]
+    fn keymap_remap_keeps_modifiers() {
+        let codes = headless_keycodes();
+        let previous = Keymap::new(codes.iter().map(|(name, &code)| (code, vec![name.clone()])));
+        let keymap = swapped_ab_keymap();
+
+        assert_eq!(
+            keymap.remap(key_code("M-S-a"), &previous),
+            Some(key_code("M-S-b"))
+        );
+        assert_eq!(
+            keymap.remap(key_code("M-j"), &previous),
+            Some(key_code("M-j"))
+        );
+    }
+
+    #[test_case("j"; "no modifiers")]
+    #[test_case("M-S-j"; "modifiers")]
+    #[test_case("M-C-A-S-Return"; "all modifiers")]
+    #[test]
+    fn keymap_describe_round_trips(keys: &str) {
+        let codes = headless_keycodes();
+        let keymap = Keymap::new(codes.iter().map(|(name, &code)| (code, vec![name.clone()])));
+
+        assert_eq!(keymap.describe(key_code(keys)), keys);
+    }
+
+    #[test]
+    fn keymap_describe_falls_back_to_the_key_code() {
+        let key = KeyCode {
+            mask: ModifierKey::Meta.into(),
+            code: 42,
+        };
+
+        assert_eq!(Keymap::default().describe(key), "M-42");
+    }
+
+    #[test]
+    fn binding_descriptions_follow_runtime_changes() {
+        let noop = |_: &mut State<HeadlessConn>, _: &HeadlessConn| Ok(());
+        let add = move |state: &mut State<HeadlessConn>, _: &HeadlessConn| {
+            let handler = Described::new("added", Box::new(noop)).boxed();
+            state.add_key_binding(key_code("M-b"), handler);
+            Ok(())
+        };
+        let chord = parse_key_chord(map! { "j" => Box::new(noop) as Box<dyn KeyEventHandler<_>>, })
+            .unwrap()
+            .with_name("chord");
+
+        let bindings = map! {
+            "M-a" => Box::new(add) as Box<dyn KeyEventHandler<HeadlessConn>>,
+            "M-c" => Box::new(chord),
+            "M-j" => Described::new("focus down", modify_with(|cs| cs.focus_down())).boxed(),
+        };
+        let s = Scenario::with_bindings(Config::default(), bindings).unwrap();
+        let describe = |keys: &str, description: Option<&str>| BindingDescription {
+            keys: keys.to_string(),
+            description: description.map(String::from),
+        };
 
-        handle::mapping_notify(&self.key_bindings, &self.mouse_bindings, &self.x)?;
+        assert_eq!(
+            s.state().binding_descriptions(),
+            &[
+                describe("M-a", None),
+                describe("M-c", Some("chord")),
+                describe("M-j", Some("focus down")),
+            ]
+        );
 
-        if let Some(mut h) = self.state.config.startup_hook.take() {
-            trace!("running user startup hook");
-            if let Err(e) = h.call(&mut self.state, &self.x) {
-                error!(%e, "error returned from user startup hook");
-            }
-        }
+        let s = s.press("M-a");
 
-        manage_existing_clients(&mut self.state, &self.x)?;
+        assert_eq!(
+            s.state().binding_descriptions(),
+            &[
+                describe("M-a", None),
+                describe("M-b", Some("added")),
+                describe("M-c", Some("chord")),
+                describe("M-j", Some("focus down")),
+            ]
+        );
+    }
 
-        loop {
-            match self.x.next_event() {
-                Ok(event) => {
-                    let span = span!(target: "penrose", Level::INFO, "XEvent", %event);
-                    let _enter = span.enter();
-                    trace!(details = ?event, "event details");
-                    self.state.current_event = Some(event.clone());
-
-                    if let Err(e) = self.handle_xevent(event) {
-                        error!(%e, "Error handling XEvent");
-                    }
-                    self.x.flush();
+    #[test_case(&["M-a"], &[]; "old key is no longer bound")]
+    #[test_case(&["M-b"], &["M-a"]; "binding follows its keysym")]
+    #[test_case(&["M-c", "a"], &["c-b"]; "chord bindings follow their keysyms")]
+    #[test]
+    fn key_bindings_follow_keyboard_mapping_changes(keys: &[&str], expected: &[&str]) {
+        let calls = Calls::default();
 
-                    self.state.current_event = None;
-                }
+        let chord = parse_key_chord(map! { "b" => record(&calls, "c-b"), }).unwrap();
+        let bindings = map! {
+            "M-a" => record(&calls, "M-a"),
+            "M-c" => Box::new(chord) as Box<dyn KeyEventHandler<HeadlessConn>>,
+        };
+        let s = Scenario::with_bindings(Config::default(), bindings).unwrap();
+        // The MappingNotify queued here is handled before the first key press. Key names are
+        // resolved using the original headless keycodes, so "b" is now the key for "a".
+        s.conn().set_keymap(swapped_ab_keymap());
+        keys.iter().fold(s, |s, k| s.press(k));
 
-                Err(e) => self.handle_error(e),
-            }
-        }
+        assert_eq!(*calls.borrow(), expected);
     }
 
-    fn handle_xevent(&mut self, event: XEvent) -> Result<()> {
-        use XEvent::*;
+    fn key_code(keys: &str) -> KeyCode {
+        bindings::parse_binding(keys, &headless_keycodes()).unwrap()
+    }
 
-        let WindowManager {
-            x,
-            state,
-            key_bindings,
-            mouse_bindings,
-        } = self;
+    fn runtime_bindings_scenario() -> (Scenario, Calls) {
+        let calls = Calls::default();
 
-        let mut hook = state.config.event_hook.take();
-        let should_run = match hook {
-            Some(ref mut h) => {
-                trace!("running user event hook");
-                match h.call(&event, state, x) {
-                    Ok(should_run) => should_run,
-                    Err(e) => {
-                        error!(%e, "error returned from user event hook");
-                        true
-                    }
-                }
+        let installed = RefCell::new(Some(record(&calls, "M-b")));
+        let install = move |state: &mut State<HeadlessConn>, _: &HeadlessConn| {
+            if let Some(handler) = installed.borrow_mut().take() {
+                state.add_key_binding(key_code("M-b"), handler);
             }
+            Ok(())
+        };
+        let uninstall = |state: &mut State<HeadlessConn>, _: &HeadlessConn| {
+            state.remove_key_binding(key_code("M-b"));
+            Ok(())
+        };
 
-            None => true,
+        let bindings = map! {
+            "M-a" => Box::new(install) as Box<dyn KeyEventHandler<HeadlessConn>>,
+            "M-r" => Box::new(uninstall) as Box<dyn KeyEventHandler<HeadlessConn>>,
+            "M-j" => record(&calls, "M-j"),
         };
-        state.config.event_hook = hook;
+        let s = Scenario::with_bindings(Config::default(), bindings).unwrap();
 
-        if !should_run {
-            trace!("User event hook returned false: skipping default handling");
-            return Ok(());
-        }
+        (s, calls)
+    }
 
-        match &event {
-            ClientMessage(m) => handle::client_message(m.clone(), state, x)?,
-            ConfigureNotify(e) if e.is_root => handle::detect_screens(state, x)?,
-            ConfigureNotify(_) => (), // Not currently handled
-            ConfigureRequest(e) => handle::configure_request(e, state, x)?,
-            Enter(p) => handle::enter(*p, state, x)?,
-            Expose(_) => (), // Not currently handled
-            FocusIn(id) => handle::focus_in(*id, state, x)?,
-            Destroy(xid) => handle::destroy(*xid, state, x)?,
-            KeyPress(code) => handle::keypress(*code, key_bindings, state, x)?,
-            Leave(p) => handle::leave(*p, state, x)?,
-            MappingNotify => handle::mapping_notify(key_bindings, mouse_bindings, x)?,
-            MapRequest(xid) => handle::map_request(*xid, state, x)?,
-            MouseEvent(e) => handle::mouse_event(e.clone(), mouse_bindings, state, x)?,
-            PropertyNotify(_) => (), // Not currently handled
-            RandrNotify => handle::detect_screens(state, x)?,
-            ScreenChange => handle::screen_change(state, x)?,
-            UnmapNotify(xid) => handle::unmap_notify(*xid, state, x)?,
+    #[test_case(&["M-b", "M-j"], &["M-j"]; "not bound initially")]
+    #[test_case(&["M-a", "M-b", "M-b"], &["M-b", "M-b"]; "added from a binding")]
+    #[test_case(&["M-a", "M-b", "M-r", "M-b", "M-j"], &["M-b", "M-j"]; "removed from a binding")]
+    #[test]
+    fn key_bindings_can_be_modified_from_state(keys: &[&str], expected: &[&str]) {
+        let (s, calls) = runtime_bindings_scenario();
+        keys.iter().fold(s, |s, k| s.press(k));
 
-            _ => (), // XEvent is non-exhaustive
-        }
+        assert_eq!(*calls.borrow(), expected);
+    }
 
-        Ok(())
+    #[test]
+    fn key_bindings_can_be_modified_on_the_window_manager() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&calls);
+        let noop_key = |_: &mut State<HeadlessConn>, _: &HeadlessConn| Ok(());
+        let noop = |_: &MouseEvent, _: &mut State<HeadlessConn>, _: &HeadlessConn| Ok(());
+        let mouse_state = MouseState::new(MouseButton::Left, vec![ModifierKey::Meta]);
+        let mut wm = WindowManager::new(
+            Config::default(),
+            HashMap::new(),
+            HashMap::new(),
+            HeadlessConn::default(),
+        )
+        .unwrap();
+
+        wm.add_key_binding(
+            key_code("M-j"),
+            Box::new(move |_: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                seen.borrow_mut().push("M-j");
+                Ok(())
+            }),
+        )
+        .unwrap();
+        wm.add_key_binding(key_code("M-k"), Box::new(noop_key))
+            .unwrap();
+        wm.remove_key_binding(key_code("M-k")).unwrap();
+        wm.add_mouse_binding(MouseEventKind::Press, mouse_state.clone(), Box::new(noop))
+            .unwrap();
+        assert_eq!(wm.mouse_bindings.len(), 1);
+
+        wm.remove_mouse_binding(MouseEventKind::Press, mouse_state)
+            .unwrap();
+        assert_eq!(wm.key_bindings.len(), 1);
+        assert!(wm.mouse_bindings.is_empty());
+
+        Scenario::new(wm).unwrap().press("M-j");
+        assert_eq!(*calls.borrow(), vec!["M-j"]);
     }
 
-    fn handle_error(&mut self, e: Error) {
-        match e {
-            // If we get an error from the XConn telling us that a client ID is unknown then
-            // we need to make sure that we remove any reference to it from our internal state
-            Error::UnknownClient(id) => {
-                debug!(%id, "XConn encountered an error due to an unknown client ID: removing client");
-                self.state.client_set.remove_client(&id);
-            }
+    #[test_case(&["M-a", "M-b", "M-c"], &["M-a", "M-b"]; "global bindings only on other workspaces")]
+    #[test_case(&["M-2", "M-a", "M-c"], &["2:M-a", "2:M-c"]; "overlay shadows global bindings")]
+    #[test_case(&["M-2", "M-b"], &["M-b"]; "unbound keys fall through to global bindings")]
+    #[test_case(&["M-2", "M-1", "M-a", "M-c"], &["M-a"]; "overlay removed on leaving workspace")]
+    #[test_case(&["M-3", "M-a", "M-b"], &["M-a", "3+:M-b"]; "predicate overlay")]
+    #[test_case(&["M-3", "M-2", "M-3", "M-b"], &["3+:M-b"]; "predicate overlay restored")]
+    #[test]
+    fn workspace_key_binding_overlays(keys: &[&str], expected: &[&str]) {
+        let calls = Calls::default();
+
+        let bindings = testing::parse_keybindings(map! {
+            "M-a" => record(&calls, "M-a"),
+            "M-b" => record(&calls, "M-b"),
+            "M-1" => modify_with(|cs| cs.focus_tag("1")),
+            "M-2" => modify_with(|cs| cs.focus_tag("2")),
+            "M-3" => modify_with(|cs| cs.focus_tag("3")),
+        })
+        .unwrap();
+        let mut wm = WindowManager::new(
+            Config::default(),
+            bindings,
+            HashMap::new(),
+            HeadlessConn::default(),
+        )
+        .unwrap();
+
+        let ws2 = testing::parse_keybindings(map! {
+            "M-a" => record(&calls, "2:M-a"),
+            "M-c" => record(&calls, "2:M-c"),
+        })
+        .unwrap();
+        wm.add_workspace_key_bindings("2", ws2).unwrap();
 
-            _ => error!(%e, "Unhandled error pulling next x event"),
-        }
-    }
-}
+        let high = testing::parse_keybindings(map! { "M-b" => record(&calls, "3+:M-b"), }).unwrap();
+        wm.add_workspace_key_bindings_matching(|tag| tag.parse().is_ok_and(|n: u8| n >= 3), high)
+            .unwrap();
 
-// A "best effort" attempt to manage existing clients on the workspaces they were present
-// on previously. This is not guaranteed to preserve the stack order or correctly handle
-// any clients that were on invisible workspaces / workspaces that no longer exist.
-//
-// NOTE: the check for if each client is already in state is in case a startup hook has
-//       pre-managed clients for us. In that case we want to avoid stomping on
-//       anything that they have set up.
-#[tracing::instrument(level = "info", skip(state, x))]
-fn manage_existing_clients<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
-    info!("managing existing clients");
+        keys.iter()
+            .fold(Scenario::new(wm).unwrap(), |s, k| s.press(k));
 
-    // We're not guaranteed that workspace indices are _always_ continuous from 0..n
-    // so we explicitly map tags to indices instead.
-    // We also exclude hidden workspaces as those can contain windows which are
-    // externally managed by a user written extension, which can lead to malformed
-    // internal state for those extensions when they restart.
-    let ws_map: HashMap<usize, String> = state
-        .client_set
-        .non_hidden_workspaces()
-        .map(|w| (w.id, w.tag.clone()))
-        .collect();
+        assert_eq!(*calls.borrow(), expected);
+    }
 
-    let first_tag = state.client_set.ordered_tags()[0].clone();
+    #[test]
+    fn workspace_gaps_are_applied_when_the_layout_is_run() {
+        let gaps = || stack!(Gaps::wrap(Monocle::boxed(), 10, 0));
+        let config = Config {
+            default_layouts: gaps(),
+            ..Config::default()
+        };
+        let wm = WindowManager::new(
+            config,
+            HashMap::new(),
+            HashMap::new(),
+            HeadlessConn::default(),
+        )
+        .unwrap();
+        let toggle = |s: &mut State<HeadlessConn>, _: &HeadlessConn| {
+            s.client_set.current_workspace_mut().toggle_gaps();
+            Ok(())
+        };
+        let (with_gaps, without_gaps) =
+            (Rect::new(10, 10, 1900, 1060), Rect::new(0, 0, 1920, 1080));
+
+        let s = Scenario::new(wm)
+            .unwrap()
+            .open_window("a", "a")
+            .expect_geometry("a", with_gaps)
+            .act(toggle)
+            .expect_geometry("a", without_gaps);
+
+        // New layouts pick up the gap setting of the workspace
+        s.act(|s, _| {
+            s.client_set
+                .current_workspace_mut()
+                .set_available_layouts(gaps());
+            Ok(())
+        })
+        .expect_geometry("a", without_gaps)
+        .act(toggle)
+        .expect_geometry("a", with_gaps);
+    }
 
-    for id in x.existing_clients()? {
-        if !state.client_set.contains(&id) && client_should_be_manged(id, x) {
-            let workspace_id = match x.get_prop(id, Atom::NetWmDesktop.as_ref()) {
-                Ok(Some(Prop::Cardinal(ids))) => ids[0] as usize,
-                _ => 0, // we know that we always have at least one workspace
-            };
+    #[test]
+    fn modal_grabs_are_kept_until_they_end() {
+        let modal = vec![key_code("x")];
+        let grab = modal.clone();
+        let bindings = testing::parse_keybindings(map! {
+            "M-m" => key_handler(move |s: &mut State<HeadlessConn>, x: &HeadlessConn| {
+                s.begin_modal_grab(&grab, &[], x)
+            }),
+            "M-e" => key_handler(|s: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                s.end_modal_grab();
+                Ok(())
+            }),
+            "M-b" => key_handler(|s: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                s.add_key_binding(key_code("M-c"), modify_with(|_| ()));
+                Ok(())
+            }),
+            "M-2" => modify_with(|cs| cs.focus_tag("2")),
+        })
+        .unwrap();
+        let mut wm = WindowManager::new(
+            Config::default(),
+            bindings,
+            HashMap::new(),
+            HeadlessConn::default(),
+        )
+        .unwrap();
+        let ws2 = testing::parse_keybindings(map! { "M-d" => modify_with(|_| ()), }).unwrap();
+        wm.add_workspace_key_bindings("2", ws2).unwrap();
+
+        let s = Scenario::new(wm).unwrap().press("M-m");
+        assert!(s.state().modal_grab_active());
+
+        // Neither modified bindings nor a change of key binding overlays regrab the normal keys
+        let s = s.press("M-b").press("M-2");
+        assert_eq!(s.conn().grabbed_keys(), modal);
+
+        let s = s.press("M-e");
+        let grabbed: HashSet<_> = s.conn().grabbed_keys().into_iter().collect();
+        let expected: HashSet<_> = ["M-m", "M-e", "M-b", "M-2", "M-c", "M-d"]
+            .into_iter()
+            .map(key_code)
+            .collect();
 
-            let tag = ws_map.get(&workspace_id).unwrap_or(&first_tag);
-            let title = x.window_title(id)?;
-            info!(%id, %title, %tag, "attempting to manage existing client");
-            manage_without_refresh(id, Some(tag), state, x)?;
-        }
+        assert!(!s.state().modal_grab_active());
+        assert_eq!(grabbed, expected);
     }
 
-    info!("triggering refresh");
-    x.refresh(state)
-}
+    type UrgencyChanges = Rc<RefCell<Vec<(Xid, bool)>>>;
 
-/// For a given existing client being processed on startup, determine whether we need
-/// to bring it into our internal state and manage it.
-fn client_should_be_manged<X: XConn>(id: Xid, x: &X) -> bool {
-    let attrs = match x.get_window_attributes(id) {
-        Ok(attrs) => attrs,
-        _ => {
-            warn!(%id, "unable to pull window attributes for client: skipping.");
-            return false;
-        }
-    };
+    fn urgency_scenario(
+        screens: Vec<Rect>,
+        urgency_timeout: Option<Duration>,
+        clear_urgency_on_view: bool,
+    ) -> (Scenario, UrgencyChanges) {
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&changes);
 
-    let wm_state = match x.get_wm_state(id) {
-        Ok(state) => state,
-        _ => {
-            warn!(%id, "unable to pull wm state for client: skipping.");
-            return false;
-        }
-    };
+        let mut config = Config {
+            urgency_timeout,
+            clear_urgency_on_view,
+            ..Config::default()
+        };
+        config.compose_or_set_urgency_change_hook(
+            move |id: Xid, urgent: bool, _: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                seen.borrow_mut().push((id, urgent));
+                Ok(())
+            },
+        );
 
-    info!(%id, ?attrs, ?wm_state, "processing client");
+        let conn = HeadlessConn::new(screens);
+        let wm = WindowManager::new(config, HashMap::new(), HashMap::new(), conn).unwrap();
+        let s = Scenario::new(wm)
+            .unwrap()
+            .given_windows(["a", "b"])
+            .act(|state, _| {
+                let b = state.client_set.current_client().copied().unwrap();
+                state.client_set.move_client_to_tag(&b, "3");
+                Ok(())
+            });
+
+        (s, changes)
+    }
 
-    let WindowAttributes {
-        override_redirect,
-        map_state,
-        ..
-    } = attrs;
+    fn mark_urgent(s: Scenario, name: &str) -> Scenario {
+        let id = s.id(name);
+        s.act(move |state, x| x.set_client_urgency(id, true, state))
+    }
 
-    let viewable = map_state == MapState::Viewable;
-    let iconic = wm_state == Some(WmState::Iconic);
+    #[test]
+    fn urgency_change_hooks_see_urgency_being_set_and_cleared() {
+        let (s, changes) = urgency_scenario(vec![LAPTOP], None, false);
+        let b = s.id("b");
+        let s = mark_urgent(s, "b");
 
-    // This condition for determining what windows we should manage is
-    // taken from the `scan` function found in both dwm and XMonad.
-    !override_redirect && (viewable || iconic)
-}
+        assert_eq!(s.state().urgent_clients(), &[b]);
+        assert_eq!(*changes.borrow(), vec![(b, true)]);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::pure::{test_xid_stack_set, Position};
+        let s = s.act(move |state, _| {
+            state.client_set.focus_client(&b);
+            Ok(())
+        });
 
-    fn stack_order(cs: &ClientSet) -> Vec<u32> {
-        let positions = cs.visible_client_positions();
-        positions.iter().map(|&(id, _)| *id).collect()
+        assert!(s.state().urgent_clients().is_empty());
+        assert_eq!(*changes.borrow(), vec![(b, true), (b, false)]);
     }
 
+    #[test_case(false; "left urgent")]
+    #[test_case(true; "cleared on view")]
     #[test]
-    fn floating_client_positions_are_respected() {
-        let mut s = test_xid_stack_set(5, 2);
+    fn urgency_can_be_cleared_when_the_workspace_is_viewed(clear_urgency_on_view: bool) {
+        let (s, _) = urgency_scenario(vec![LAPTOP, EXTERNAL], None, clear_urgency_on_view);
+        let b = s.id("b");
+        let s = mark_urgent(s, "b");
+
+        // Show the workspace holding the urgent client on the second screen without focusing it
+        let s = s.act(|state, _| {
+            state.client_set.focus_screen(1);
+            state.client_set.focus_tag("3");
+            state.client_set.focus_screen(0);
+            Ok(())
+        });
+
+        assert_eq!(s.state().client_set.tag_for_screen(1), Some("3"));
+        assert_ne!(s.state().client_set.current_client(), Some(&b));
+        assert_eq!(s.state().urgent_clients().is_empty(), clear_urgency_on_view);
+    }
 
-        for n in 0..4 {
-            s.insert(Xid(n));
-        }
+    #[test]
+    fn urgency_is_cleared_once_the_timeout_elapses() {
+        let (s, changes) = urgency_scenario(vec![LAPTOP], Some(Duration::ZERO), false);
+        let b = s.id("b");
+        let s = mark_urgent(s, "b");
 
-        let r = Rect::new(50, 50, 50, 50);
-        s.float_unchecked(Xid(1), r);
+        assert_eq!(s.state().urgent_clients(), &[b]);
 
-        let positions = s.visible_client_positions();
+        let s = s.wait(Duration::ZERO);
 
-        assert!(positions.contains(&(Xid(1), r)), "{positions:?}")
+        assert!(s.state().urgent_clients().is_empty());
+        assert!(s.state().urgency.deadlines.is_empty());
+        assert_eq!(*changes.borrow(), vec![(b, true), (b, false)]);
     }
 
+    const LAPTOP: Rect = Rect::new(0, 0, 1920, 1080);
+    const EXTERNAL: Rect = Rect::new(1920, 0, 1920, 1080);
+    const PROJECTOR: Rect = Rect::new(0, 0, 1024, 768);
+
+    #[test_case(vec![LAPTOP, EXTERNAL], vec![0, 1]; "side by side")]
+    #[test_case(vec![LAPTOP, LAPTOP], vec![0]; "identical")]
+    #[test_case(vec![PROJECTOR, LAPTOP], vec![1]; "contained in a later screen")]
+    #[test_case(vec![LAPTOP, PROJECTOR, EXTERNAL], vec![0, 2]; "contained in an earlier screen")]
+    #[test_case(vec![LAPTOP, Rect::new(960, 0, 1920, 1080)], vec![0, 1]; "partial overlap")]
     #[test]
-    fn floating_clients_stay_on_their_assigned_screen() {
-        let mut s = test_xid_stack_set(5, 2);
+    fn distinct_screens(rects: Vec<Rect>, expected: Vec<usize>) {
+        assert_eq!(handle::distinct_screens(&rects), expected);
+    }
 
-        for n in 0..4 {
-            s.insert(Xid(n));
-        }
+    #[test]
+    fn mirrored_screens_are_merged_and_split_again() {
+        let conn = HeadlessConn::new(vec![LAPTOP, PROJECTOR]);
+        let wm =
+            WindowManager::new(Config::default(), HashMap::new(), HashMap::new(), conn).unwrap();
+        let s = Scenario::new(wm).unwrap().given_windows(["a"]);
+        let screens = |s: &Scenario| {
+            s.state()
+                .client_set
+                .screens()
+                .map(|s| s.r)
+                .collect::<Vec<_>>()
+        };
 
-        let r = Rect::new(50, 50, 50, 50);
-        s.float_unchecked(Xid(1), r);
+        assert_eq!(screens(&s), vec![LAPTOP]);
 
-        let positions = s.visible_client_positions();
+        s.conn().set_screens(vec![LAPTOP, EXTERNAL]);
+        let s = s.act(|_, _| Ok(()));
 
-        assert!(positions.contains(&(Xid(1), r)), "{positions:?}");
+        assert_eq!(screens(&s), vec![LAPTOP, EXTERNAL]);
 
-        // If we move the client to tag 2 on the second screen then it should
-        // change position and be relative to that screen instead
-        s.move_client_to_tag(&Xid(1), "2");
-        let positions = s.visible_client_positions();
+        s.conn().set_screens(vec![LAPTOP, LAPTOP]);
+        let s = s.act(|_, _| Ok(()));
 
-        assert!(!positions.contains(&(Xid(1), r)), "{positions:?}");
-        assert!(
-            positions.contains(&(Xid(1), Rect::new(1050, 2050, 50, 50))),
-            "{positions:?}"
-        );
+        assert_eq!(screens(&s), vec![LAPTOP]);
+        s.expect_visible(["a"]);
     }
 
-    #[test]
-    fn floating_windows_are_returned_last() {
-        let mut s = test_xid_stack_set(5, 2);
+    fn rotation_scenario() -> Scenario {
+        let portrait = Rect::new(1920, 0, 1080, 1920);
+        let conn = HeadlessConn::new(vec![LAPTOP, portrait]);
+        conn.set_outputs(vec![
+            Output {
+                name: "eDP-1".to_string(),
+                connected: true,
+                r: Some(LAPTOP),
+                primary: false,
+                rotation: Rotation::Normal,
+            },
+            Output {
+                name: "DP-1".to_string(),
+                connected: true,
+                r: Some(portrait),
+                primary: false,
+                rotation: Rotation::Left,
+            },
+        ]);
+
+        let config = Config {
+            default_layouts: stack!(RotationAware::wrap(MainAndStack::boxed_default())),
+            ..Config::default()
+        };
+        let wm = WindowManager::new(config, HashMap::new(), HashMap::new(), conn).unwrap();
 
-        for n in 1..6 {
-            s.insert(Xid(n));
-        }
+        Scenario::new(wm).unwrap()
+    }
 
-        s.float_unchecked(Xid(2), Rect::new(0, 0, 42, 42));
-        s.float_unchecked(Xid(3), Rect::new(0, 0, 69, 69));
+    fn screen_layout(s: &Scenario, index: usize) -> String {
+        let screen = s.state().client_set.screens().nth(index).unwrap();
 
-        assert_eq!(stack_order(&s), vec![1, 4, 5, 2, 3]);
+        screen.workspace.layout_name()
     }
 
     #[test]
-    fn newly_added_windows_are_below_floating() {
-        let mut s = test_xid_stack_set(5, 2);
+    fn layouts_see_the_rotation_of_their_screen() {
+        let s = rotation_scenario();
 
-        for n in 1..6 {
-            s.insert(Xid(n));
-        }
+        assert_eq!(s.state().screen_rotation(0), Rotation::Normal);
+        assert_eq!(s.state().screen_rotation(1), Rotation::Left);
+        assert_eq!(screen_layout(&s, 0), "Side");
+        assert_eq!(screen_layout(&s, 1), "Bottom");
+    }
 
-        s.float_unchecked(Xid(2), Rect::new(0, 0, 42, 42));
-        s.float_unchecked(Xid(3), Rect::new(0, 0, 69, 69));
+    #[test]
+    fn rotation_changes_without_a_geometry_change_are_tracked() {
+        let s = rotation_scenario();
+        let mut outputs = s.conn().outputs().unwrap();
+        outputs[0].rotation = Rotation::Right;
+        outputs[1].rotation = Rotation::Normal;
+        s.conn().set_outputs(outputs);
+
+        let s = s.send(XEvent::RandrNotify);
+
+        assert_eq!(s.state().screen_rotation(0), Rotation::Right);
+        assert_eq!(screen_layout(&s, 0), "Bottom");
+        assert_eq!(screen_layout(&s, 1), "Side");
+    }
 
-        s.insert(Xid(6));
+    fn monitor_overrides_scenario() -> Scenario {
+        let output = |name: &str, r: Option<Rect>| Output {
+            name: name.to_string(),
+            connected: r.is_some(),
+            r,
+            primary: false,
+            rotation: Rotation::Normal,
+        };
 
-        assert_eq!(stack_order(&s), vec![1, 4, 5, 6, 2, 3]);
+        let conn = HeadlessConn::new(vec![LAPTOP, EXTERNAL]);
+        conn.set_outputs(vec![
+            output("eDP-1", Some(LAPTOP)),
+            output("HDMI-1", Some(EXTERNAL)),
+        ]);
+
+        let mut config = Config {
+            default_layouts: stack!(MainAndStack::boxed_default(), Grid::boxed()),
+            ..Config::default()
+        };
+        config.monitor_overrides.insert(
+            "HDMI-1".to_string(),
+            MonitorConfig::new()
+                .with_border_width(5)
+                .with_default_layout("Grid")
+                .with_bar(false),
+        );
+
+        let wm = WindowManager::new(config, HashMap::new(), HashMap::new(), conn).unwrap();
+
+        Scenario::new(wm).unwrap()
     }
 
     #[test]
-    fn floating_clients_dont_break_insert_focus() {
-        let mut s = test_xid_stack_set(1, 1);
+    fn monitor_overrides_are_applied_to_the_screen_showing_the_output() {
+        let s = monitor_overrides_scenario()
+            .given_windows(["a"])
+            .act(|state, _| {
+                state.client_set.focus_screen(1);
+                Ok(())
+            })
+            .given_windows(["b"]);
+
+        let state = s.state();
+        assert!(state.monitor_config(0).is_none());
+        assert!(state.bar_enabled(0));
+        assert!(!state.bar_enabled(1));
+        assert_eq!(state.screen_border_width(0), 2);
+        assert_eq!(state.screen_border_width(1), 5);
+        assert_eq!(state.border_width_for(s.id("a")), 2);
+        assert_eq!(state.border_width_for(s.id("b")), 5);
+
+        let layouts: Vec<String> = state
+            .client_set
+            .screens()
+            .map(|s| s.workspace.layout_name())
+            .collect();
+        assert_eq!(layouts, vec!["Side", "Grid"]);
+    }
 
-        s.insert_at(Position::Focus, Xid(0));
-        s.float_unchecked(Xid(0), Rect::new(0, 0, 42, 42));
+    #[test]
+    fn monitor_overrides_are_reapplied_on_hotplug() {
+        let s = monitor_overrides_scenario();
+
+        s.conn().set_screens(vec![LAPTOP]);
+        s.conn().set_outputs(vec![Output {
+            name: "eDP-1".to_string(),
+            connected: true,
+            r: Some(LAPTOP),
+            primary: false,
+            rotation: Rotation::Normal,
+        }]);
+        let s = s.act(|_, _| Ok(()));
+
+        assert!(s.state().monitor_config(0).is_none());
+        assert!(s.state().monitor_config(1).is_none());
+
+        s.conn().set_screens(vec![EXTERNAL, LAPTOP]);
+        s.conn().set_outputs(vec![
+            Output {
+                name: "HDMI-1".to_string(),
+                connected: true,
+                r: Some(EXTERNAL),
+                primary: false,
+                rotation: Rotation::Normal,
+            },
+            Output {
+                name: "eDP-1".to_string(),
+                connected: true,
+                r: Some(LAPTOP),
+                primary: false,
+                rotation: Rotation::Normal,
+            },
+        ]);
+        let s = s.act(|_, _| Ok(()));
+
+        assert_eq!(s.state().screen_border_width(0), 5);
+        assert_eq!(s.state().screen_border_width(1), 2);
+        assert_eq!(
+            s.state()
+                .client_set
+                .screens()
+                .next()
+                .unwrap()
+                .workspace
+                .layout_name(),
+            "Grid"
+        );
+    }
 
-        assert_eq!(s.current_client(), Some(&Xid(0)));
+    fn open_window(wm: &mut WindowManager<HeadlessConn>) -> Xid {
+        let id = wm.x.create_window("test");
+        wm.x.push_event(XEvent::MapRequest(id));
+        while let Some(event) = wm.x.pop_event() {
+            wm.handle_event_without_event_loop(event);
+        }
 
-        // Each time we add a client it should be the focus
-        // and the floating window should be stacked above
-        // all others.
-        let mut expected = vec![0];
-        for n in 1..=5 {
-            s.insert_at(Position::Focus, Xid(n));
-            assert_eq!(s.current_client(), Some(&Xid(n)));
+        id
+    }
 
-            // Tiled position ordering is reversed in visible_client_positions
-            // in order to ensure that when we restack, the order returned
-            // is from bottom -> top of the stack to make `restack` simpler to
-            // implement.
-            expected.insert(expected.len() - 1, n);
-            assert_eq!(stack_order(&s), expected, "{:?}", s.current_stack());
+    #[test_case(Some(1), false, true; "screen")]
+    #[test_case(None, true, false; "visible workspace")]
+    #[test]
+    fn partial_refreshes_only_reposition_clients_on_the_target_screen(
+        screen: Option<usize>,
+        a_repositioned: bool,
+        b_repositioned: bool,
+    ) {
+        let moved = Rect::new(10, 10, 50, 50);
+        let conn = HeadlessConn::new(vec![LAPTOP, EXTERNAL]);
+        let mut wm =
+            WindowManager::new(Config::default(), HashMap::new(), HashMap::new(), conn).unwrap();
+        wm.start_without_event_loop().unwrap();
+
+        let a = open_window(&mut wm);
+        wm.state.client_set.focus_screen(1);
+        let b = open_window(&mut wm);
+
+        for id in [a, b] {
+            wm.x.position_client(id, moved).unwrap();
         }
+
+        match screen {
+            Some(index) => wm.refresh_screen(index).unwrap(),
+            None => wm.refresh_workspace("1").unwrap(),
+        }
+
+        let geometry = |id| wm.x.window(id).unwrap().geometry;
+        assert_eq!(geometry(a) != moved, a_repositioned);
+        assert_eq!(geometry(b) != moved, b_repositioned);
+        assert_eq!(geometry(b) == EXTERNAL.shrink_in(2), b_repositioned);
+    }
+
+    #[test]
+    fn refreshing_a_hidden_workspace_repositions_nothing() {
+        let moved = Rect::new(10, 10, 50, 50);
+        let conn = HeadlessConn::new(vec![LAPTOP, EXTERNAL]);
+        let mut wm =
+            WindowManager::new(Config::default(), HashMap::new(), HashMap::new(), conn).unwrap();
+        wm.start_without_event_loop().unwrap();
+
+        let a = open_window(&mut wm);
+        wm.x.position_client(a, moved).unwrap();
+        wm.state.client_set.move_client_to_tag(&a, "3");
+        wm.refresh_workspace("3").unwrap();
+
+        assert_eq!(wm.x.window(a).unwrap().geometry, moved);
+        assert!(!wm.state.mapped.contains(&a));
+    }
+    #[test]
+    fn the_wm_selection_is_held_until_shutdown() {
+        let conn = HeadlessConn::new(vec![LAPTOP]);
+        let mut wm =
+            WindowManager::new(Config::default(), HashMap::new(), HashMap::new(), conn).unwrap();
+        wm.start_without_event_loop().unwrap();
+        assert!(wm.x.owns_wm_selection());
+
+        shutdown(&mut wm.state, &wm.x).unwrap();
+        assert!(!wm.x.owns_wm_selection());
     }
 }
@@ -0,0 +1,87 @@
+//! Overriding config options for specific monitors.
+//!
+//! [Config::monitor_overrides][0] maps output names (as reported by RandR, e.g. `eDP-1`) to a
+//! [MonitorConfig] holding the options that should differ from the rest of the [Config][1]
+//! when that output is connected. Overrides are matched to screens whenever screens are
+//! detected, so they follow an output if it is moved, unplugged and reconnected. Anything
+//! left unset falls back to the value used for every other screen.
+//!
+//! Border widths are applied automatically and gaps are applied by the [SpacingHook][2].
+//! Other components (such as the `penrose_ui` status bar) can make use of
+//! [State::monitor_config][3] to look up the overrides for a given screen.
+//!
+//!   [0]: crate::core::Config::monitor_overrides
+//!   [1]: crate::core::Config
+//!   [2]: crate::builtin::hooks::SpacingHook
+//!   [3]: crate::core::State::monitor_config
+
+/// Config options to use in place of the defaults on a specific monitor.
+///
+/// ```
+/// # use penrose::core::{Config, MonitorConfig};
+/// # use penrose::x11rb::RustConn;
+/// let mut config: Config<RustConn> = Config::default();
+///
+/// // A projector with thick borders, no gaps and no status bar that switches to the
+/// // monocle layout whenever it is connected
+/// config.monitor_overrides.insert(
+///     "HDMI-1".to_string(),
+///     MonitorConfig::new()
+///         .with_gaps(0, 0)
+///         .with_border_width(4)
+///         .with_default_layout("Mono")
+///         .with_bar(false),
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MonitorConfig {
+    /// The outer gap size in pixels to use in place of [SpacingHook::outer_px][0]
+    ///
+    ///   [0]: crate::builtin::hooks::SpacingHook::outer_px
+    pub outer_gap: Option<u32>,
+    /// The inner gap size in pixels to use in place of [SpacingHook::inner_px][0]
+    ///
+    ///   [0]: crate::builtin::hooks::SpacingHook::inner_px
+    pub inner_gap: Option<u32>,
+    /// The border width in pixels to use in place of [Config::border_width][0]
+    ///
+    ///   [0]: crate::core::Config::border_width
+    pub border_width: Option<u32>,
+    /// The name of the layout to switch to for the workspace shown on this monitor when it
+    /// is detected
+    pub default_layout: Option<String>,
+    /// Whether or not a status bar should be shown on this monitor
+    pub bar: Option<bool>,
+}
+
+impl MonitorConfig {
+    /// Create a new set of overrides that leaves everything set to its default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given outer and inner gap sizes on this monitor.
+    pub fn with_gaps(mut self, outer_px: u32, inner_px: u32) -> Self {
+        self.outer_gap = Some(outer_px);
+        self.inner_gap = Some(inner_px);
+        self
+    }
+
+    /// Use the given border width on this monitor.
+    pub fn with_border_width(mut self, px: u32) -> Self {
+        self.border_width = Some(px);
+        self
+    }
+
+    /// Switch to the layout with the given name when this monitor is detected.
+    pub fn with_default_layout(mut self, name: impl Into<String>) -> Self {
+        self.default_layout = Some(name.into());
+        self
+    }
+
+    /// Show or hide the status bar on this monitor.
+    pub fn with_bar(mut self, enabled: bool) -> Self {
+        self.bar = Some(enabled);
+        self
+    }
+}
@@ -0,0 +1,67 @@
+//! Restarting the window manager in place without losing the arrangement of clients.
+//!
+//! Before re-executing the window manager binary, a [StateSnapshot] is written to a file whose
+//! path is passed to the new process in the [RESTART_STATE_ENV_VAR] environment variable. Once
+//! the new process has managed the existing clients it reads the file back and re-applies the
+//! saved state.
+use crate::{
+    core::{shutdown_hook, State, StateSnapshot},
+    x::XConn,
+    Result,
+};
+use std::{env, ffi::OsString, fs, os::unix::process::CommandExt, path::PathBuf, process};
+use tracing::{info, warn};
+
+/// The environment variable used to pass the path of the saved state to the restarted process.
+pub const RESTART_STATE_ENV_VAR: &str = "PENROSE_RESTART_STATE";
+
+// Run the user shutdown hook, save the current state of the client set and then replace the
+// current process with a new instance of the window manager. This only returns if we are
+// unable to restart.
+pub(crate) fn restart<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    shutdown_hook(state, x);
+
+    let path = env::temp_dir().join(format!("penrose-restart-{}", process::id()));
+    StateSnapshot::from_client_set(&state.client_set).save(&path)?;
+    x.flush();
+
+    // Prefer argv[0] over the current executable so that we pick up a rebuilt binary rather
+    // than the (potentially deleted) one we are currently running.
+    let mut args = env::args_os();
+    let program = match args.next() {
+        Some(p) => p,
+        None => OsString::from(env::current_exe()?),
+    };
+
+    info!(?program, "restarting window manager");
+    // Re-executing ourselves is exempt from the CommandWhitelist in the same way as the other
+    // helper programs run by penrose.
+    let err = process::Command::new(program)
+        .args(args)
+        .env(RESTART_STATE_ENV_VAR, &path)
+        .exec();
+
+    // exec only returns if it failed
+    let _ = fs::remove_file(&path);
+
+    Err(err.into())
+}
+
+// Load and remove the snapshot saved by a previous process if we were started via a restart.
+pub(crate) fn take_saved_snapshot() -> Option<StateSnapshot> {
+    let path = PathBuf::from(env::var_os(RESTART_STATE_ENV_VAR)?);
+    env::remove_var(RESTART_STATE_ENV_VAR);
+
+    let res = StateSnapshot::load(&path);
+    if let Err(e) = fs::remove_file(&path) {
+        warn!(%e, ?path, "unable to remove saved restart state");
+    }
+
+    match res {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            warn!(%e, ?path, "unable to load saved restart state");
+            None
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Scaling pixel sizes per screen according to their DPI.
+//!
+//! When [Config::dpi_scaling][0] is set, the window manager computes a scale factor for each
+//! screen from its DPI relative to [DpiScaling::base_dpi]. The DPI of each screen is taken from
+//! [DpiScaling::overrides] if present, falling back to the value calculated from the physical
+//! size of the screen reported by the X server. Border widths are scaled automatically and the
+//! [SpacingHook][1] scales its gaps and reserved space: other components (such as the
+//! `penrose_ui` status bar) can make use of [State::screen_scale][2] to size themselves.
+//!
+//!   [0]: crate::core::Config::dpi_scaling
+//!   [1]: crate::builtin::hooks::SpacingHook
+//!   [2]: crate::core::State::screen_scale
+use std::collections::HashMap;
+
+// The DPI that X assumes when a screen's physical size is unknown.
+const DEFAULT_BASE_DPI: f64 = 96.0;
+
+/// How pixel sizes should be scaled on each screen.
+///
+/// ```
+/// # use penrose::core::DpiScaling;
+/// // Treat screen 1 as a 192 DPI display regardless of what the X server reports
+/// let scaling = DpiScaling::default().with_screen_dpi(1, 192.0);
+///
+/// assert_eq!(scaling.scale_for(1, None), 2.0);
+/// assert_eq!(scaling.scale_for(0, Some(144.0)), 1.5);
+/// assert_eq!(scaling.scale_for(0, None), 1.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DpiScaling {
+    /// The DPI at which pixel sizes are used without being scaled
+    pub base_dpi: f64,
+    /// The DPI to use for specific screens (by index) in place of the detected value
+    pub overrides: HashMap<usize, f64>,
+}
+
+impl Default for DpiScaling {
+    fn default() -> Self {
+        Self {
+            base_dpi: DEFAULT_BASE_DPI,
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+impl DpiScaling {
+    /// Use the given DPI for the screen with the given index rather than the detected value.
+    pub fn with_screen_dpi(mut self, index: usize, dpi: f64) -> Self {
+        self.overrides.insert(index, dpi);
+        self
+    }
+
+    /// The scale factor to use for the screen with the given index and detected DPI.
+    ///
+    /// Scale factors are rounded to the nearest quarter so that small differences in reported
+    /// physical sizes do not result in different sizes between similar screens. Screens with
+    /// no known DPI are not scaled.
+    pub fn scale_for(&self, index: usize, detected: Option<f64>) -> f64 {
+        let dpi = match self.overrides.get(&index).copied().or(detected) {
+            Some(dpi) if dpi > 0.0 && self.base_dpi > 0.0 => dpi,
+            _ => return 1.0,
+        };
+
+        ((dpi / self.base_dpi) * 4.0).round().max(1.0) / 4.0
+    }
+}
+
+/// Scale a pixel size by the given factor, rounding to the nearest pixel.
+pub fn scale_px(px: u32, scale: f64) -> u32 {
+    (px as f64 * scale).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case(None, 1.0; "unknown")]
+    #[test_case(Some(96.0), 1.0; "base dpi")]
+    #[test_case(Some(92.0), 1.0; "rounded down")]
+    #[test_case(Some(81.0), 0.75; "low dpi")]
+    #[test_case(Some(1.0), 0.25; "minimum")]
+    #[test_case(Some(282.0), 3.0; "4k laptop panel")]
+    #[test_case(Some(0.0), 1.0; "invalid")]
+    #[test]
+    fn scale_for(detected: Option<f64>, expected: f64) {
+        assert_eq!(DpiScaling::default().scale_for(0, detected), expected);
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let scaling = DpiScaling::default().with_screen_dpi(0, 144.0);
+
+        assert_eq!(scaling.scale_for(0, Some(288.0)), 1.5);
+        assert_eq!(scaling.scale_for(1, Some(288.0)), 3.0);
+    }
+
+    #[test_case(2, 1.0, 2; "unscaled")]
+    #[test_case(2, 1.5, 3; "scaled")]
+    #[test_case(3, 1.25, 4; "rounded")]
+    #[test_case(0, 2.0, 0; "zero")]
+    #[test]
+    fn scale_px_works(px: u32, scale: f64, expected: u32) {
+        assert_eq!(scale_px(px, scale), expected);
+    }
+}
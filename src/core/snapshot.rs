@@ -0,0 +1,282 @@
+//! Snapshots of the arrangement of clients for restoring after a restart or crash.
+use crate::{
+    core::ClientSet,
+    pure::{
+        geometry::{Rect, RelativeTo},
+        Stack,
+    },
+    Error, Result, Xid,
+};
+use std::{fs, path::Path};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SavedWorkspace {
+    tag: String,
+    layout: String,
+    focus: Option<Xid>,
+    clients: Vec<Xid>,
+}
+
+/// A snapshot of the parts of a [ClientSet] that can not be recovered from the X server alone.
+///
+/// This covers which workspace is shown on each screen, the stack order, focus and active
+/// layout of each workspace and the position of floating clients.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateSnapshot {
+    focused_tag: String,
+    screens: Vec<String>,
+    workspaces: Vec<SavedWorkspace>,
+    floating: Vec<(Xid, Rect)>,
+}
+
+impl StateSnapshot {
+    /// Take a snapshot of the current state of the given [ClientSet].
+    pub fn from_client_set(cs: &ClientSet) -> Self {
+        let is_client = |id: &Xid| !cs.bars.contains_key(id);
+
+        let workspaces = cs
+            .workspaces()
+            .map(|w| SavedWorkspace {
+                tag: w.tag.clone(),
+                layout: w.layout_name(),
+                focus: w.focus().copied().filter(is_client),
+                clients: w.clients().copied().filter(is_client).collect(),
+            })
+            .collect();
+
+        let focused_r = cs.screens.focus.r;
+        let mut floating: Vec<(Xid, Rect)> = cs
+            .floating
+            .iter()
+            .filter(|(id, _)| is_client(id))
+            .map(|(&id, rr)| {
+                let r = cs.screen_for_client(&id).map(|s| s.r).unwrap_or(focused_r);
+                (id, rr.applied_to(&r))
+            })
+            .collect();
+        floating.sort_by_key(|&(id, _)| id);
+
+        Self {
+            focused_tag: cs.current_tag().to_string(),
+            screens: cs.screens().map(|s| s.workspace.tag.clone()).collect(),
+            workspaces,
+            floating,
+        }
+    }
+
+    /// Re-apply this snapshot to the clients that are present in the given [ClientSet].
+    ///
+    /// Clients that are not in the snapshot are left where they are, and any clients or tags
+    /// from the snapshot that are no longer present are ignored.
+    pub fn apply(&self, cs: &mut ClientSet) {
+        for (ix, tag) in self.screens.iter().enumerate() {
+            cs.focus_screen(ix);
+            cs.pull_tag_to_screen(tag);
+        }
+
+        for saved in self.workspaces.iter() {
+            if !cs.contains_tag(&saved.tag) {
+                continue;
+            }
+
+            let clients: Vec<Xid> = saved
+                .clients
+                .iter()
+                .filter(|id| cs.contains(id))
+                .copied()
+                .collect();
+            for id in clients.iter() {
+                cs.move_client_to_tag(id, &saved.tag);
+            }
+
+            let w = cs.workspace_mut(&saved.tag).expect("tag to exist");
+            w.set_layout_by_name(&saved.layout);
+
+            let others: Vec<Xid> = w
+                .clients()
+                .filter(|id| !clients.contains(id))
+                .copied()
+                .collect();
+            w.stack = Stack::try_from_iter(clients.into_iter().chain(others));
+            if let (Some(s), Some(id)) = (w.stack.as_mut(), saved.focus) {
+                s.focus_element(&id);
+            }
+        }
+
+        cs.focus_tag(&self.focused_tag);
+
+        // Floating clients on hidden workspaces were saved relative to the focused screen
+        let focused_r = cs.screens.focus.r;
+        for (id, r) in self.floating.iter() {
+            if cs.contains(id) {
+                let screen_r = cs.screen_for_client(id).map(|s| s.r).unwrap_or(focused_r);
+                cs.floating.insert(*id, r.relative_to(&screen_r));
+            }
+        }
+    }
+
+    /// Load a snapshot previously written using [StateSnapshot::save].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Write this snapshot to the given path, replacing any existing file.
+    ///
+    /// The snapshot is written to a temporary file alongside `path` and then moved into place
+    /// so that a partially written snapshot is never left behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, self.serialize())?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        s.lines()
+            .enumerate()
+            .filter(|(_, l)| !l.is_empty())
+            .try_fold(Self::default(), |mut state, (i, line)| {
+                match state.parse_line(line) {
+                    Some(()) => Ok(state),
+                    None => Err(Error::InvalidSnapshot { line: i + 1 }),
+                }
+            })
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<()> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            ["focus", tag] => self.focused_tag = tag.to_string(),
+            ["screen", tag] => self.screens.push(tag.to_string()),
+            ["workspace", tag, layout, focus, clients] => self.workspaces.push(SavedWorkspace {
+                tag: tag.to_string(),
+                layout: layout.to_string(),
+                focus: match *focus {
+                    "-" => None,
+                    id => Some(Xid(id.parse().ok()?)),
+                },
+                clients: clients
+                    .split_whitespace()
+                    .map(|id| id.parse().ok().map(Xid))
+                    .collect::<Option<Vec<_>>>()?,
+            }),
+            ["float", id, x, y, w, h] => self.floating.push((
+                Xid(id.parse().ok()?),
+                Rect::new(
+                    x.parse().ok()?,
+                    y.parse().ok()?,
+                    w.parse().ok()?,
+                    h.parse().ok()?,
+                ),
+            )),
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    fn serialize(&self) -> String {
+        let mut lines = vec![format!("focus\t{}", self.focused_tag)];
+        lines.extend(self.screens.iter().map(|tag| format!("screen\t{tag}")));
+
+        for w in self.workspaces.iter() {
+            let focus = w.focus.map(|id| id.to_string()).unwrap_or("-".to_string());
+            let clients: Vec<String> = w.clients.iter().map(|id| id.to_string()).collect();
+            lines.push(format!(
+                "workspace\t{}\t{}\t{focus}\t{}",
+                w.tag,
+                w.layout,
+                clients.join(" ")
+            ));
+        }
+
+        for (id, Rect { x, y, w, h }) in self.floating.iter() {
+            lines.push(format!("float\t{id}\t{x}\t{y}\t{w}\t{h}"));
+        }
+
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pure::test_xid_stack_set;
+    use simple_test_case::test_case;
+
+    fn client_set() -> ClientSet {
+        let mut cs = test_xid_stack_set(4, 2);
+        for n in 1..=3 {
+            cs.insert(Xid(n));
+        }
+        cs.focus_tag("3");
+        for n in 4..=5 {
+            cs.insert(Xid(n));
+        }
+        cs.float(Xid(5), Rect::new(10, 20, 300, 400)).unwrap();
+        cs.focus_client(&Xid(4));
+        cs.focus_tag("4");
+        cs.focus_client(&Xid(2));
+
+        cs
+    }
+
+    #[test]
+    fn serialized_state_round_trips() {
+        let state = StateSnapshot::from_client_set(&client_set());
+
+        assert_eq!(StateSnapshot::parse(&state.serialize()).unwrap(), state);
+    }
+
+    #[test]
+    fn apply_restores_positions_of_readopted_clients() {
+        let cs = client_set();
+        let state = StateSnapshot::from_client_set(&cs);
+
+        // As if all clients had been managed on the first tag following a restart or crash
+        let mut restarted = test_xid_stack_set(4, 2);
+        for n in [5, 3, 1, 4, 2, 6] {
+            restarted.insert(Xid(n));
+        }
+        state.apply(&mut restarted);
+
+        assert_eq!(
+            StateSnapshot::from_client_set(&restarted).floating,
+            state.floating
+        );
+        assert_eq!(restarted.current_tag(), cs.current_tag());
+        assert_eq!(restarted.current_client(), cs.current_client());
+        assert_eq!(
+            restarted
+                .screens()
+                .map(|s| s.workspace.tag.as_str())
+                .collect::<Vec<_>>(),
+            cs.screens()
+                .map(|s| s.workspace.tag.as_str())
+                .collect::<Vec<_>>(),
+        );
+        for tag in ["1", "2", "3", "4"] {
+            let expected: Vec<_> = cs.workspace(tag).unwrap().clients().collect();
+            let mut got: Vec<_> = restarted.workspace(tag).unwrap().clients().collect();
+            if tag == "1" {
+                assert_eq!(got.pop(), Some(&Xid(6)), "unknown clients are kept");
+            }
+            assert_eq!(got, expected, "tag {tag}");
+        }
+    }
+
+    #[test_case("focus\t1\nbogus\n", 2; "unknown line")]
+    #[test_case("focus\t1\n\nworkspace\t1\tSide\t-\t1 two\n", 3; "invalid client id")]
+    #[test_case("float\t1\t2\t3\n", 1; "missing fields")]
+    #[test]
+    fn parse_invalid(s: &str, expected: usize) {
+        match StateSnapshot::parse(s) {
+            Err(Error::InvalidSnapshot { line }) => assert_eq!(line, expected),
+            res => panic!("expected InvalidSnapshot error, got {res:?}"),
+        }
+    }
+}
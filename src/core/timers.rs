@@ -0,0 +1,180 @@
+//! Deadlines and wake ups for the main event loop.
+//!
+//! Rather than blocking on the X server indefinitely, the event loop waits for the next
+//! event until the earliest deadline held in [Timers] has passed or until another thread
+//! wakes it using [wake_event_loop].
+use crate::{
+    core::{handle::PendingOutput, State},
+    x::XConn,
+    Result,
+};
+use std::{
+    fmt,
+    io::{self, Read},
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::net::UnixStream,
+    },
+    sync::atomic::{AtomicI32, Ordering},
+    time::Instant,
+};
+
+pub(crate) type TimerAction<X> = Box<dyn FnOnce(&mut State<X>, &X) -> Result<()>>;
+
+// The write end of the pipe held by the running event loop, or -1 if it is not running.
+static WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Wake the main event loop so that any pending work is handled without waiting for the
+/// next event from the X server.
+///
+/// This is safe to call from other threads and from signal handlers. It does nothing if the
+/// event loop is not currently running.
+pub(crate) fn wake_event_loop() {
+    let fd = WAKE_FD.load(Ordering::SeqCst);
+    if fd >= 0 {
+        // A full pipe already has a pending wake up so the result can be ignored
+        let _ = nix::unistd::write(fd, &[0]);
+    }
+}
+
+// A self-pipe that is polled alongside the X connection by the running event loop.
+#[derive(Debug)]
+pub(crate) struct WakePipe {
+    rx: UnixStream,
+    _tx: UnixStream,
+}
+
+impl WakePipe {
+    pub(crate) fn new() -> io::Result<Self> {
+        let (rx, tx) = UnixStream::pair()?;
+        rx.set_nonblocking(true)?;
+        tx.set_nonblocking(true)?;
+        WAKE_FD.store(tx.as_raw_fd(), Ordering::SeqCst);
+
+        Ok(Self { rx, _tx: tx })
+    }
+
+    pub(crate) fn read_fd(&self) -> RawFd {
+        self.rx.as_raw_fd()
+    }
+
+    // Clear any pending wake ups so that the next poll blocks again.
+    pub(crate) fn drain(&self) {
+        let mut buf = [0; 64];
+        while matches!((&self.rx).read(&mut buf), Ok(n) if n > 0) {}
+    }
+}
+
+impl Drop for WakePipe {
+    fn drop(&mut self) {
+        WAKE_FD.store(-1, Ordering::SeqCst);
+    }
+}
+
+// Work that the main event loop needs to come back to once it is due: timers set using
+// State::run_after and the output of commands run using State::spawn_and_capture.
+#[derive(Debug)]
+pub(crate) struct Deferred<X: XConn> {
+    pub(crate) timers: Timers<X>,
+    pub(crate) output: Vec<PendingOutput<X>>,
+}
+
+impl<X: XConn> Default for Deferred<X> {
+    fn default() -> Self {
+        Self {
+            timers: Timers::default(),
+            output: Vec::new(),
+        }
+    }
+}
+
+struct Timer<X: XConn> {
+    deadline: Instant,
+    action: Option<TimerAction<X>>,
+}
+
+/// The deadlines that the main event loop needs to wake up for, along with any actions
+/// that should be run once they have passed.
+pub(crate) struct Timers<X: XConn> {
+    timers: Vec<Timer<X>>,
+}
+
+impl<X: XConn> Default for Timers<X> {
+    fn default() -> Self {
+        Self { timers: Vec::new() }
+    }
+}
+
+impl<X: XConn> fmt::Debug for Timers<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timers")
+            .field(
+                "deadlines",
+                &self.timers.iter().map(|t| t.deadline).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<X: XConn> Timers<X> {
+    // Wake the event loop at the given deadline, running the action if there is one.
+    pub(crate) fn push(&mut self, deadline: Instant, action: Option<TimerAction<X>>) {
+        self.timers.push(Timer { deadline, action });
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.timers.iter().map(|t| t.deadline).min()
+    }
+
+    // Remove all timers whose deadline has passed, returning whether there were any along with
+    // their actions in the order that they are due.
+    pub(crate) fn take_expired(&mut self, now: Instant) -> Option<Vec<TimerAction<X>>> {
+        if !self.timers.iter().any(|t| t.deadline <= now) {
+            return None;
+        }
+
+        let (mut expired, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.timers)
+            .into_iter()
+            .partition(|t| t.deadline <= now);
+        self.timers = pending;
+        expired.sort_by_key(|t| t.deadline);
+
+        Some(expired.into_iter().filter_map(|t| t.action).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::Config, x::StubXConn};
+    use std::time::Duration;
+
+    fn focus_tag(tag: &'static str) -> Option<TimerAction<StubXConn>> {
+        Some(Box::new(move |s: &mut State<StubXConn>, _: &StubXConn| {
+            s.client_set.focus_tag(tag);
+            Ok(())
+        }))
+    }
+
+    #[test]
+    fn expired_timers_are_taken_in_deadline_order() {
+        let now = Instant::now();
+        let secs = Duration::from_secs;
+        let mut timers = Timers::default();
+        timers.push(now + secs(2), focus_tag("2"));
+        timers.push(now + secs(3), focus_tag("3"));
+        timers.push(now + secs(1), focus_tag("1"));
+        timers.push(now, None);
+
+        assert!(timers.take_expired(now - secs(1)).is_none());
+
+        let mut state = State::try_new(Config::default(), &StubXConn).unwrap();
+        state.client_set.focus_tag("4");
+        for action in timers.take_expired(now + secs(2)).unwrap() {
+            action(&mut state, &StubXConn).unwrap();
+        }
+
+        assert_eq!(state.client_set.current_tag(), "2");
+        assert_eq!(timers.next_deadline(), Some(now + secs(3)));
+    }
+}
@@ -0,0 +1,266 @@
+//! An opt-in policy restricting which external commands the window manager will run.
+//!
+//! When a [CommandWhitelist] is set as [Config::command_whitelist][0], the running window
+//! manager refuses to run any command that does not match an entry in the whitelist. This is
+//! enforced by the spawning functions in [util][1] so it applies to the [spawn][2] action,
+//! [State::spawn_and_capture][3], [DMenu][4] and any extensions that run external programs.
+//! Violations are logged and (optionally) shown to the user via `notify-send`. This is intended
+//! for setups where commands may be triggered by semi-trusted tooling, such as a user defined
+//! IPC handler: any such handler that runs commands without using [util][1] should call
+//! [CommandWhitelist::check] first.
+//!
+//! The helper programs that penrose runs internally are never chosen by the user and so are
+//! exempt from the whitelist:
+//! - `xmodmap`, for reading the current keyboard mapping.
+//! - `notify-send`, for reporting blocked commands and layout editor status.
+//! - `gdbus`, for reading the system colour scheme in the [theme][5] hook.
+//! - `date`, for finding the local start of the day in the [journal][6] hook.
+//! - The window manager binary itself, when it is re-executed on restart.
+//!
+//!   [0]: crate::core::Config::command_whitelist
+//!   [1]: crate::util
+//!   [2]: crate::builtin::actions::spawn
+//!   [3]: crate::core::State::spawn_and_capture
+//!   [4]: crate::extensions::util::dmenu::DMenu
+//!   [5]: crate::extensions::hooks::theme
+//!   [6]: crate::extensions::hooks::journal
+use crate::{util::notify, Error, Result};
+use std::{
+    path::{Component, Path},
+    sync::{PoisonError, RwLock},
+};
+use tracing::warn;
+
+// The whitelist of the running window manager, checked before running any external command.
+static ACTIVE_WHITELIST: RwLock<Option<CommandWhitelist>> = RwLock::new(None);
+
+/// Set the whitelist that is enforced for all commands run by the window manager.
+pub(crate) fn set_active_whitelist(whitelist: Option<CommandWhitelist>) {
+    *ACTIVE_WHITELIST
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = whitelist;
+}
+
+/// Check a command and any additional arguments against the whitelist of the running window
+/// manager, if there is one.
+pub(crate) fn check_command(cmd: &str, args: &[&str]) -> Result<()> {
+    let guard = ACTIVE_WHITELIST
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    check_against(guard.as_ref(), cmd, args)
+}
+
+fn check_against(whitelist: Option<&CommandWhitelist>, cmd: &str, args: &[&str]) -> Result<()> {
+    match whitelist {
+        Some(wl) if args.is_empty() => wl.check(cmd),
+        Some(wl) => wl.check(&format!("{cmd} {}", args.join(" "))),
+        None => Ok(()),
+    }
+}
+
+/// A single entry in a [CommandWhitelist].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WhitelistEntry {
+    /// Allow commands whose program exactly matches the given path or name, with any arguments
+    Exact(String),
+    /// Allow commands whose full command line starts with the given prefix, ending on a word or
+    /// path boundary
+    Prefix(String),
+}
+
+impl WhitelistEntry {
+    fn matches(&self, program: &str, cmd: &str) -> bool {
+        match self {
+            Self::Exact(s) => s == program,
+            Self::Prefix(s) => match cmd.strip_prefix(s.as_str()) {
+                // `foo` allows `foo`, `foo bar` and `foo/bar` but not `foobar`
+                Some(rest) => {
+                    s.ends_with(|c: char| c == '/' || c.is_whitespace())
+                        || rest.is_empty()
+                        || rest.starts_with(|c: char| c == '/' || c.is_whitespace())
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+/// The set of external commands that the window manager is permitted to run.
+///
+/// Commands are split on whitespace in the same way as [util::spawn][0], with the first word
+/// being the program that will be run. A command is allowed if its program exactly matches an
+/// [Exact][WhitelistEntry::Exact] entry or if the full command line starts with a
+/// [Prefix][WhitelistEntry::Prefix] entry followed by a space, a `/` or the end of the command
+/// so that `foo` does not also allow `foobar`. Commands whose program contains a `..` path
+/// component are always rejected so that prefixes such as `/usr/bin/` can not be escaped.
+///
+/// ```
+/// # use penrose::core::CommandWhitelist;
+/// let whitelist = CommandWhitelist::new()
+///     .allow("alacritty")
+///     .allow_prefix("/usr/local/bin/")
+///     .allow_prefix("notify-send ");
+///
+/// assert!(whitelist.is_allowed("alacritty"));
+/// assert!(whitelist.is_allowed("/usr/local/bin/screenshot --full"));
+/// assert!(!whitelist.is_allowed("sh -c alacritty"));
+/// assert!(!whitelist.is_allowed("/usr/local/bin/../../bin/sh"));
+/// ```
+///
+///   [0]: crate::util::spawn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandWhitelist {
+    entries: Vec<WhitelistEntry>,
+    notify: bool,
+}
+
+impl Default for CommandWhitelist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandWhitelist {
+    /// Create a new, empty, whitelist that rejects all commands.
+    ///
+    /// Rejected commands are reported using `notify-send` by default.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            notify: true,
+        }
+    }
+
+    /// Allow commands running the given program.
+    pub fn allow(mut self, program: impl Into<String>) -> Self {
+        self.entries.push(WhitelistEntry::Exact(program.into()));
+        self
+    }
+
+    /// Allow commands starting with the given prefix.
+    pub fn allow_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.entries.push(WhitelistEntry::Prefix(prefix.into()));
+        self
+    }
+
+    /// Set whether or not rejected commands are reported using `notify-send`.
+    pub fn notify_on_violation(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// The entries in this whitelist.
+    pub fn entries(&self) -> &[WhitelistEntry] {
+        &self.entries
+    }
+
+    /// Check whether or not the given command is permitted by this whitelist.
+    pub fn is_allowed(&self, cmd: &str) -> bool {
+        let cmd = cmd.trim();
+        let program = match cmd.split_whitespace().next() {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if Path::new(program)
+            .components()
+            .any(|c| c == Component::ParentDir)
+        {
+            return false;
+        }
+
+        self.entries.iter().any(|e| e.matches(program, cmd))
+    }
+
+    /// Check the given command against this whitelist, logging and reporting it to the user
+    /// if it is not permitted.
+    pub fn check(&self, cmd: &str) -> Result<()> {
+        if self.is_allowed(cmd) {
+            return Ok(());
+        }
+
+        warn!(%cmd, "refusing to run command that is not whitelisted");
+        if self.notify {
+            if let Err(e) = notify(&format!("penrose: blocked command '{cmd}'")) {
+                warn!(%e, "unable to notify user of blocked command");
+            }
+        }
+
+        Err(Error::CommandNotWhitelisted {
+            cmd: cmd.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    fn whitelist() -> CommandWhitelist {
+        CommandWhitelist::new()
+            .allow("alacritty")
+            .allow("/usr/bin/firefox")
+            .allow_prefix("/usr/local/bin/")
+            .allow_prefix("notify-send ")
+            .allow_prefix("/opt/tools")
+            .allow_prefix("xrandr --output")
+            .notify_on_violation(false)
+    }
+
+    #[test_case("alacritty", true; "exact program")]
+    #[test_case("alacritty -e htop", true; "exact program with args")]
+    #[test_case("  /usr/bin/firefox  ", true; "surrounding whitespace")]
+    #[test_case("firefox", false; "exact match requires same path")]
+    #[test_case("/usr/local/bin/screenshot --full", true; "prefix")]
+    #[test_case("notify-send hello", true; "prefix with args")]
+    #[test_case("notify-send", false; "prefix requires full match")]
+    #[test_case("/opt/tools", true; "prefix matching whole command")]
+    #[test_case("/opt/tools/screenshot", true; "prefix ending on a path boundary")]
+    #[test_case("/opt/toolshed/sh", false; "prefix ending mid path component")]
+    #[test_case("xrandr --output HDMI-1 --off", true; "prefix ending on a word boundary")]
+    #[test_case("xrandr --outputs", false; "prefix ending mid word")]
+    #[test_case("/usr/local/bin/../../bin/sh", false; "parent dir in prefix")]
+    #[test_case("../alacritty", false; "parent dir in exact")]
+    #[test_case("sh -c alacritty", false; "unknown program")]
+    #[test_case("", false; "empty")]
+    #[test]
+    fn is_allowed(cmd: &str, expected: bool) {
+        assert_eq!(whitelist().is_allowed(cmd), expected);
+    }
+
+    #[test]
+    fn check_returns_an_error_for_rejected_commands() {
+        let wl = whitelist();
+
+        assert!(wl.check("alacritty").is_ok());
+        assert!(matches!(
+            wl.check("rm -rf /"),
+            Err(Error::CommandNotWhitelisted { cmd }) if cmd == "rm -rf /"
+        ));
+    }
+
+    #[test_case("alacritty", &[], true; "exact program")]
+    #[test_case("alacritty", &["-e", "htop"], true; "exact program with args")]
+    #[test_case("notify-send", &["hello"], true; "prefix spanning args")]
+    #[test_case("notify-send", &[], false; "prefix requires args")]
+    #[test_case("sh", &["-c", "alacritty"], false; "unknown program")]
+    #[test]
+    fn check_against_joins_program_and_args(program: &str, args: &[&str], expected: bool) {
+        assert_eq!(
+            check_against(Some(&whitelist()), program, args).is_ok(),
+            expected
+        );
+    }
+
+    #[test]
+    fn check_against_allows_everything_without_a_whitelist() {
+        assert!(check_against(None, "rm", &["-rf", "/"]).is_ok());
+    }
+
+    #[test]
+    fn empty_whitelist_rejects_everything() {
+        assert!(!CommandWhitelist::new().is_allowed("alacritty"));
+    }
+}
@@ -0,0 +1,228 @@
+//! Rate limiting for key bindings.
+use crate::{
+    core::{bindings::KeyEventHandler, Config, State},
+    x::{XConn, XEvent},
+    Result,
+};
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error};
+
+struct CooldownState<X: XConn> {
+    handler: Box<dyn KeyEventHandler<X>>,
+    interval: Duration,
+    last_run: Option<Instant>,
+    queued: bool,
+}
+
+impl<X: XConn> CooldownState<X> {
+    fn ready(&self, now: Instant) -> bool {
+        match self.last_run {
+            Some(t) => now.duration_since(t) >= self.interval,
+            None => true,
+        }
+    }
+
+    fn run(&mut self, now: Instant, state: &mut State<X>, x: &X) -> Result<()> {
+        self.last_run = Some(now);
+        self.queued = false;
+        self.handler.call(state, x)
+    }
+}
+
+/// Wrap a [KeyEventHandler] so that it can be run at most once within a given interval.
+///
+/// By default, invocations made while the handler is cooling down are dropped. Calling
+/// [Cooldown::queue_last] changes this so that the last invocation made during the cooldown
+/// is run once the interval has elapsed. Queued invocations are run from an [EventHook][0]
+/// so they will run on the first X event after the cooldown expires: this requires the hook
+/// to be registered using [add_cooldown_hook].
+///
+/// ```no_run
+/// # use penrose::{
+/// #     builtin::actions::spawn,
+/// #     extensions::actions::Cooldown,
+/// #     x11rb::RustConn,
+/// # };
+/// # use std::time::Duration;
+/// let screenshot = Cooldown::<RustConn>::new(Duration::from_secs(2), spawn("flameshot gui"))
+///     .boxed();
+/// ```
+///
+///   [0]: crate::core::hooks::EventHook
+pub struct Cooldown<X: XConn> {
+    inner: Rc<RefCell<CooldownState<X>>>,
+    queue_last: bool,
+}
+
+impl<X: XConn> fmt::Debug for Cooldown<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("Cooldown")
+            .field("interval", &inner.interval)
+            .field("last_run", &inner.last_run)
+            .field("queued", &inner.queued)
+            .field("queue_last", &self.queue_last)
+            .finish()
+    }
+}
+
+impl<X: XConn + 'static> Cooldown<X> {
+    /// Wrap the given handler so that it is run at most once per `interval`.
+    pub fn new(interval: Duration, handler: Box<dyn KeyEventHandler<X>>) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(CooldownState {
+                handler,
+                interval,
+                last_run: None,
+                queued: false,
+            })),
+            queue_last: false,
+        }
+    }
+
+    /// Run the last invocation made during the cooldown once it has expired rather than
+    /// dropping it.
+    pub fn queue_last(mut self) -> Self {
+        self.queue_last = true;
+        self
+    }
+
+    /// Box this handler ready for adding to your key bindings.
+    pub fn boxed(self) -> Box<dyn KeyEventHandler<X>> {
+        Box::new(self)
+    }
+
+    fn call_at(&mut self, now: Instant, state: &mut State<X>, x: &X) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.ready(now) {
+            return inner.run(now, state, x);
+        }
+
+        if self.queue_last && !inner.queued {
+            debug!("action is cooling down: queuing");
+            inner.queued = true;
+            state
+                .extension_or_default::<QueuedCooldowns<X>>()
+                .borrow_mut()
+                .0
+                .push(Rc::clone(&self.inner));
+        }
+
+        Ok(())
+    }
+}
+
+impl<X: XConn + 'static> KeyEventHandler<X> for Cooldown<X> {
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        self.call_at(Instant::now(), state, x)
+    }
+}
+
+// Handlers with an invocation queued for when their cooldown expires.
+struct QueuedCooldowns<X: XConn>(Vec<Rc<RefCell<CooldownState<X>>>>);
+
+impl<X: XConn> Default for QueuedCooldowns<X> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+/// Add the [EventHook][0] required for running invocations queued by [Cooldown::queue_last].
+///
+///   [0]: crate::core::hooks::EventHook
+pub fn add_cooldown_hook<X>(mut config: Config<X>) -> Config<X>
+where
+    X: XConn + 'static,
+{
+    config.compose_or_set_event_hook(cooldown_event_hook);
+
+    config
+}
+
+/// Run any queued [Cooldown] invocations whose cooldown has expired.
+pub fn cooldown_event_hook<X>(_: &XEvent, state: &mut State<X>, x: &X) -> Result<bool>
+where
+    X: XConn + 'static,
+{
+    run_expired(Instant::now(), state, x);
+
+    Ok(true)
+}
+
+fn run_expired<X: XConn + 'static>(now: Instant, state: &mut State<X>, x: &X) {
+    let queued = state.extension_or_default::<QueuedCooldowns<X>>();
+    let (ready, waiting): (Vec<_>, Vec<_>) = queued
+        .borrow_mut()
+        .0
+        .drain(..)
+        .partition(|c| c.borrow().ready(now));
+    queued.borrow_mut().0 = waiting;
+
+    for c in ready {
+        debug!("running queued action");
+        if let Err(e) = c.borrow_mut().run(now, state, x) {
+            error!(%e, "error running queued action");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builtin::actions::key_handler, x::StubXConn};
+
+    fn counting_cooldown(queue_last: bool) -> (Cooldown<StubXConn>, Rc<RefCell<usize>>) {
+        let count = Rc::new(RefCell::new(0));
+        let c = Rc::clone(&count);
+        let handler = key_handler(move |_: &mut State<StubXConn>, _: &StubXConn| {
+            *c.borrow_mut() += 1;
+            Ok(())
+        });
+        let mut cd = Cooldown::new(Duration::from_secs(1), handler);
+        cd.queue_last = queue_last;
+
+        (cd, count)
+    }
+
+    #[test]
+    fn calls_during_cooldown_are_dropped() {
+        let mut state = State::try_new(Config::default(), &StubXConn).unwrap();
+        let (mut cd, count) = counting_cooldown(false);
+        let t = Instant::now();
+
+        cd.call_at(t, &mut state, &StubXConn).unwrap();
+        cd.call_at(t + Duration::from_millis(500), &mut state, &StubXConn)
+            .unwrap();
+        run_expired(t + Duration::from_secs(2), &mut state, &StubXConn);
+        assert_eq!(*count.borrow(), 1);
+
+        cd.call_at(t + Duration::from_secs(1), &mut state, &StubXConn)
+            .unwrap();
+        assert_eq!(*count.borrow(), 2);
+    }
+
+    #[test]
+    fn queued_calls_run_once_the_cooldown_expires() {
+        let mut state = State::try_new(Config::default(), &StubXConn).unwrap();
+        let (mut cd, count) = counting_cooldown(true);
+        let t = Instant::now();
+
+        cd.call_at(t, &mut state, &StubXConn).unwrap();
+        for ms in [200, 400, 600] {
+            cd.call_at(t + Duration::from_millis(ms), &mut state, &StubXConn)
+                .unwrap();
+        }
+
+        run_expired(t + Duration::from_millis(800), &mut state, &StubXConn);
+        assert_eq!(*count.borrow(), 1, "cooldown not yet expired");
+
+        run_expired(t + Duration::from_secs(1), &mut state, &StubXConn);
+        run_expired(t + Duration::from_secs(3), &mut state, &StubXConn);
+        assert_eq!(*count.borrow(), 2, "queued calls run once");
+    }
+}
@@ -1,16 +1,68 @@
 //! Dynamic selection based actions using Dmenu.
 use crate::{
     builtin::actions::key_handler,
-    core::{bindings::KeyEventHandler, State},
+    core::{
+        bindings::{BindingDescription, KeyEventHandler},
+        State,
+    },
     custom_error,
-    extensions::util::dmenu::{DMenu, DMenuConfig, MenuMatch},
+    extensions::{
+        hooks::minimize::{restore_minimized, MinimizedClients},
+        util::{
+            dmenu::{DMenu, DMenuConfig, MenuMatch},
+            title::client_title,
+        },
+    },
     x::{XConn, XConnExt},
-    Xid,
+    Result, Xid,
 };
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
+use tracing::error;
+
+#[cfg(feature = "freedesktop")]
+use crate::{
+    extensions::util::{dmenu::DMenuKind, freedesktop::AppIndex},
+    x::{atom::Atom, property::Prop},
+};
+
+#[cfg(feature = "freedesktop")]
+const ICON_SIZE: u32 = 48;
+
+#[cfg(feature = "freedesktop")]
+fn app_index(config: &DMenuConfig) -> AppIndex {
+    AppIndex::load(config.icon_theme.as_deref().unwrap_or("hicolor"), ICON_SIZE)
+}
+
+// Icons for the given clients based on their WM_CLASS, if icons can be displayed.
+#[cfg(feature = "freedesktop")]
+fn client_icons<X: XConn>(config: &DMenuConfig, ids: &[Xid], x: &X) -> HashMap<Xid, PathBuf> {
+    if config.kind != DMenuKind::Rofi {
+        return HashMap::new();
+    }
+
+    let apps = app_index(config);
+
+    ids.iter()
+        .filter_map(|&id| match x.get_prop(id, Atom::WmClass.as_ref()) {
+            Ok(Some(Prop::UTF8String(strs))) => strs
+                .iter()
+                .rev()
+                .find_map(|class| apps.icon_for_class(class))
+                .map(|icon| (id, icon)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "freedesktop"))]
+fn client_icons<X: XConn>(_: &DMenuConfig, _: &[Xid], _: &X) -> HashMap<Xid, PathBuf> {
+    HashMap::new()
+}
 
 /// Use [DMenu] to dynamically select and focus a client window.
 ///
+/// Clients that have `_NET_WM_STATE_SKIP_TASKBAR` set are not offered as choices.
+///
 /// # Arguments
 /// * `config` users custom DMenuConfig, the dmenu instance that is launched will
 ///    obey colorscheme, postion, custom font, custom prompt etc...
@@ -21,8 +73,8 @@ pub fn dmenu_focus_client<X: XConn>(mut config: DMenuConfig) -> Box<dyn KeyEvent
             .workspaces()
             .filter(|w| !state.client_set.invisible_tags.iter().any(|t| t == w.tag()))
             .flat_map(|w| {
-                w.clients().map(|&id| {
-                    let title = x.window_title(id).unwrap_or_else(|_| (*id).to_string());
+                w.clients().filter(|&&id| !x.skips_taskbar(id)).map(|&id| {
+                    let title = client_title(id, state, x);
 
                     (format!("{}: {}", w.tag(), title), id)
                 })
@@ -35,7 +87,14 @@ pub fn dmenu_focus_client<X: XConn>(mut config: DMenuConfig) -> Box<dyn KeyEvent
         }
         let dmenu = DMenu::new(&config, screen);
 
-        if let MenuMatch::Line(_, s) = dmenu.build_menu(choices.keys().collect())? {
+        let ids: Vec<Xid> = choices.values().copied().collect();
+        let icons = client_icons(&config, &ids, x);
+        let menu_choices = choices
+            .iter()
+            .map(|(s, id)| (s.as_str(), icons.get(id).cloned()))
+            .collect();
+
+        if let MenuMatch::Line(_, s) = dmenu.build_menu_with_icons(menu_choices)? {
             let id = choices
                 .get(&s)
                 .ok_or_else(|| custom_error!("unexpected dmenu output: {}", s))?;
@@ -47,6 +106,95 @@ pub fn dmenu_focus_client<X: XConn>(mut config: DMenuConfig) -> Box<dyn KeyEvent
     })
 }
 
+/// Use [DMenu] to select a client window from another workspace and bring it to the current
+/// workspace, placing it in the focused position.
+///
+/// # Arguments
+/// * `config` users custom DMenuConfig, the dmenu instance that is launched will
+///   obey colorscheme, position, custom font, custom prompt etc...
+/// * `float_centered` whether the selected client should be floated in the center of the
+///   current screen rather than being tiled
+pub fn dmenu_fetch_client<X: XConn>(
+    mut config: DMenuConfig,
+    float_centered: bool,
+) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let current_tag = state.client_set.current_tag();
+        let choices: HashMap<String, Xid> = state
+            .client_set
+            .workspaces()
+            .filter(|w| w.tag() != current_tag)
+            .filter(|w| !state.client_set.invisible_tags.iter().any(|t| t == w.tag()))
+            .flat_map(|w| {
+                w.clients().map(|&id| {
+                    let title = client_title(id, state, x);
+
+                    (format!("{}: {}", w.tag(), title), id)
+                })
+            })
+            .collect();
+
+        if choices.is_empty() {
+            return Ok(());
+        }
+
+        let screen = state.client_set.current_screen().index();
+        if config.custom_prompt.is_none() {
+            config.custom_prompt = Some("Fetch: ".to_owned());
+        }
+        let dmenu = DMenu::new(&config, screen);
+
+        let ids: Vec<Xid> = choices.values().copied().collect();
+        let icons = client_icons(&config, &ids, x);
+        let menu_choices = choices
+            .iter()
+            .map(|(s, id)| (s.as_str(), icons.get(id).cloned()))
+            .collect();
+
+        if let MenuMatch::Line(_, s) = dmenu.build_menu_with_icons(menu_choices)? {
+            let id = choices
+                .get(&s)
+                .ok_or_else(|| custom_error!("unexpected dmenu output: {}", s))?;
+
+            fetch_client(*id, float_centered, state, x)?;
+        }
+
+        Ok(())
+    })
+}
+
+// Move a client to the focused position of the current workspace, either tiling it or
+// floating it in the center of the current screen.
+fn fetch_client<X: XConn>(
+    id: Xid,
+    float_centered: bool,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let r_screen = state.client_set.screens.focus.r;
+    let r_float = if float_centered {
+        let r = x.client_geometry(id)?;
+        Some(r.centered_in(&r_screen).unwrap_or(r_screen))
+    } else {
+        None
+    };
+
+    x.modify_and_refresh(state, |cs| {
+        cs.move_client_to_current_tag(&id);
+
+        match r_float {
+            Some(r) => {
+                if let Err(err) = cs.float(id, r) {
+                    error!(%err, %id, "unable to float fetched client window");
+                }
+            }
+            None => {
+                cs.sink(&id);
+            }
+        }
+    })
+}
+
 /// Use [DMenu] to dynamically select and focus a client window.
 ///
 /// # Arguments
@@ -71,6 +219,90 @@ pub fn dmenu_focus_tag<X: XConn>(mut config: DMenuConfig) -> Box<dyn KeyEventHan
     })
 }
 
+/// Use [DMenu] to select a minimized client to restore.
+///
+/// See [crate::extensions::hooks::minimize] for details of setting up minimize support.
+///
+/// # Arguments
+/// * `config` users custom DMenuConfig, the dmenu instance that is launched will
+///   obey colorscheme, position, custom font, custom prompt etc...
+pub fn dmenu_restore_minimized<X: XConn>(mut config: DMenuConfig) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let minimized: Vec<Xid> = state
+            .extension_or_default::<MinimizedClients>()
+            .borrow()
+            .clients()
+            .collect();
+
+        if minimized.is_empty() {
+            return Ok(());
+        }
+
+        let choices: HashMap<String, Xid> = minimized
+            .into_iter()
+            .map(|id| {
+                let title = client_title(id, state, x);
+
+                (format!("{}: {}", *id, title), id)
+            })
+            .collect();
+
+        let screen = state.client_set.current_screen().index();
+        if config.custom_prompt.is_none() {
+            config.custom_prompt = Some("Restore: ".to_owned());
+        }
+        let dmenu = DMenu::new(&config, screen);
+
+        if let MenuMatch::Line(_, s) = dmenu.build_menu(choices.keys().collect())? {
+            let id = choices
+                .get(&s)
+                .ok_or_else(|| custom_error!("unexpected dmenu output: {}", s))?;
+
+            restore_minimized(*id, state, x)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Use [DMenu] to show a cheatsheet of the current key bindings and their descriptions.
+///
+/// Descriptions are taken from [State::binding_descriptions], so only bindings that have been
+/// wrapped in [Described][0] (along with any chords and binding modes) are described. Selecting
+/// an entry closes the menu without running anything.
+///
+/// # Arguments
+/// * `config` users custom DMenuConfig, the dmenu instance that is launched will
+///   obey colorscheme, position, custom font, custom prompt etc...
+///
+///   [0]: crate::core::bindings::Described
+pub fn dmenu_binding_cheatsheet<X: XConn>(mut config: DMenuConfig) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, _: &X| {
+        let choices = cheatsheet_lines(state.binding_descriptions());
+        let screen = state.client_set.current_screen().index();
+        if config.custom_prompt.is_none() {
+            config.custom_prompt = Some("Bindings: ".to_owned());
+        }
+        let dmenu = DMenu::new(&config, screen);
+        dmenu.build_menu(choices)?;
+
+        Ok(())
+    })
+}
+
+// One line per key binding, with descriptions aligned in a single column.
+fn cheatsheet_lines(descriptions: &[BindingDescription]) -> Vec<String> {
+    let width = descriptions.iter().map(|d| d.keys.len()).max().unwrap_or(0);
+
+    descriptions
+        .iter()
+        .map(|d| match &d.description {
+            Some(desc) => format!("{:width$}  {desc}", d.keys),
+            None => d.keys.clone(),
+        })
+        .collect()
+}
+
 /// Launch [DMenu] for its most basic purposes, launching other programs.
 ///
 /// # Arguments
@@ -86,3 +318,122 @@ pub fn launch_dmenu<X: XConn>(mut config: DMenuConfig) -> Box<dyn KeyEventHandle
         dmenu.run()
     })
 }
+
+/// Use [DMenu] to select and launch an application from the desktop entries installed on the
+/// system.
+///
+/// Applications that need to be run in a terminal are launched using `terminal`. Icons are
+/// shown alongside each application when using [DMenuKind::Rofi].
+///
+/// This requires the `freedesktop` feature to be enabled.
+///
+/// # Arguments
+/// * `config` users custom DMenuConfig, the dmenu instance that is launched will
+///   obey colorscheme, position, custom font, custom prompt etc...
+/// * `terminal` the terminal emulator to use for applications with `Terminal=true`
+#[cfg(feature = "freedesktop")]
+pub fn dmenu_launch_application<X: XConn>(
+    mut config: DMenuConfig,
+    terminal: impl Into<String>,
+) -> Box<dyn KeyEventHandler<X>> {
+    let terminal = terminal.into();
+
+    key_handler(move |state: &mut State<X>, _: &X| {
+        let apps = app_index(&config);
+        let entries: Vec<_> = apps.launchable().collect();
+        let show_icons = config.kind == DMenuKind::Rofi;
+        let choices = entries
+            .iter()
+            .map(|e| {
+                let icon = show_icons.then(|| apps.icon_for_entry(e)).flatten();
+                (e.name.as_str(), icon)
+            })
+            .collect();
+
+        let screen = state.client_set.current_screen().index();
+        if config.custom_prompt.is_none() {
+            config.custom_prompt = Some("Run: ".to_owned());
+        }
+        let dmenu = DMenu::new(&config, screen);
+
+        match dmenu.build_menu_with_icons(choices)? {
+            MenuMatch::Line(i, _) => entries[i].launch(&terminal),
+            _ => Ok(()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::Config, testing::Scenario};
+    use simple_test_case::test_case;
+
+    #[test_case(false; "tiled")]
+    #[test_case(true; "floating centered")]
+    #[test]
+    fn fetch_client_brings_the_client_to_the_current_workspace(float_centered: bool) {
+        let s = Scenario::with_bindings(Config::default(), HashMap::<&str, _>::new())
+            .unwrap()
+            .given_windows(["a", "b"])
+            .act(|state, _| {
+                state.client_set.focus_tag("2");
+                Ok(())
+            })
+            .given_windows(["c"]);
+
+        let id = s.id("a");
+        let s = s
+            .act(|state, x| fetch_client(id, float_centered, state, x))
+            .expect_workspace("2")
+            .expect_on_workspace("a", "2")
+            .expect_on_workspace("b", "1")
+            .expect_focus("a");
+
+        if float_centered {
+            let r_screen = s.state().client_set.screens.focus.r;
+            let r = s.state().client_set.floating[&id].applied_to(&r_screen);
+
+            s.expect_floating("a");
+            assert_eq!(r.centered_in(&r_screen), Some(r));
+        } else {
+            s.expect_tiled("a").expect_stack(["a", "c"]);
+        }
+    }
+
+    #[test]
+    fn cheatsheet_lines_align_descriptions() {
+        let describe = |keys: &str, description: Option<&str>| BindingDescription {
+            keys: keys.to_string(),
+            description: description.map(String::from),
+        };
+        let descriptions = vec![
+            describe("M-S-q", Some("quit")),
+            describe("M-j", Some("focus down")),
+            describe("M-x", None),
+        ];
+
+        let lines = cheatsheet_lines(&descriptions);
+
+        assert_eq!(lines, vec!["M-S-q  quit", "M-j    focus down", "M-x"]);
+    }
+
+    #[test]
+    fn fetching_a_floating_client_tiles_it() {
+        let s = Scenario::with_bindings(Config::default(), HashMap::<&str, _>::new())
+            .unwrap()
+            .given_windows(["a"])
+            .act(|state, x| {
+                let id = *state.client_set.current_client().unwrap();
+                let r = x.client_geometry(id)?;
+                state.client_set.float(id, r)?;
+                state.client_set.focus_tag("2");
+                Ok(())
+            });
+
+        let id = s.id("a");
+        s.act(|state, x| fetch_client(id, false, state, x))
+            .expect_on_workspace("a", "2")
+            .expect_tiled("a");
+    }
+}
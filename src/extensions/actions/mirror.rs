@@ -0,0 +1,55 @@
+//! Mirroring a client onto another screen for presentations.
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State},
+    x::XConn,
+    x11rb::mirror::Mirror,
+};
+use tracing::warn;
+
+// The currently running mirror, if there is one.
+#[derive(Debug, Default)]
+struct ActiveMirror(Option<Mirror>);
+
+/// Toggle mirroring the focused client onto the screen with the given index.
+///
+/// The contents of the client are scaled to fill the target screen while preserving their
+/// aspect ratio, allowing you to (for example) share a single application window with an
+/// audience on a projector while keeping your notes visible on your laptop screen. Running
+/// this action again stops the mirror: only one client can be mirrored at a time.
+///
+/// This requires the X server to support the Composite, Damage and Render extensions.
+pub fn toggle_mirror_focused<X: XConn>(screen_index: usize) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, _: &X| {
+        let active = state.extension_or_default::<ActiveMirror>();
+        let mut active = active.borrow_mut();
+
+        if let Some(m) = active.0.take() {
+            if m.is_running() {
+                m.stop();
+                return Ok(());
+            }
+        }
+
+        let id = match state.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        let target = match state
+            .client_set
+            .screens()
+            .find(|s| s.index() == screen_index)
+        {
+            Some(s) => s.geometry(),
+            None => {
+                warn!(%screen_index, "unable to mirror client: unknown screen");
+                return Ok(());
+            }
+        };
+
+        active.0 = Some(Mirror::start(id, target)?);
+
+        Ok(())
+    })
+}
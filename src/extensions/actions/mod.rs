@@ -2,16 +2,37 @@
 use crate::{
     builtin::actions::{key_handler, modify_with},
     core::{bindings::KeyEventHandler, layout::LayoutStack, State},
+    pure::geometry::RelativeRect,
     util::spawn,
     x::{atom::Atom, property::Prop, XConn, XConnExt},
     Error, Result, Xid,
 };
+use std::collections::{HashMap, HashSet};
 use tracing::error;
 
+mod cooldown;
 mod dynamic_select;
+#[cfg(feature = "mirror")]
+mod mirror;
+mod mouse_gestures;
+#[cfg(feature = "x11rb")]
+mod transition;
+mod workspace_grid;
 
+#[doc(inline)]
+pub use cooldown::*;
 #[doc(inline)]
 pub use dynamic_select::*;
+#[cfg(feature = "mirror")]
+#[doc(inline)]
+pub use mirror::*;
+#[doc(inline)]
+pub use mouse_gestures::*;
+#[cfg(feature = "x11rb")]
+#[doc(inline)]
+pub use transition::*;
+#[doc(inline)]
+pub use workspace_grid::*;
 
 /// The possible valid actions to use when manipulating full screen state
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -24,7 +45,105 @@ pub enum FullScreenAction {
     Toggle,
 }
 
+// The state of a client before it was made fullscreen so that it can be restored.
+#[derive(Debug, Clone)]
+struct PreFullscreenState {
+    tag: String,
+    floating: Option<RelativeRect>,
+}
+
+/// Clients that have been made fullscreen using [set_fullscreen_state], stored as a
+/// [State] extension.
+#[derive(Debug, Default, Clone)]
+pub struct FullscreenClients {
+    clients: HashMap<Xid, PreFullscreenState>,
+}
+
+impl FullscreenClients {
+    /// Whether or not the given client is currently fullscreen.
+    pub fn contains(&self, id: &Xid) -> bool {
+        self.clients.contains_key(id)
+    }
+
+    /// The clients that are currently fullscreen.
+    pub fn clients(&self) -> impl Iterator<Item = &Xid> {
+        self.clients.keys()
+    }
+}
+
+/// The fullscreen state of the focused client.
+///
+/// This is published on the [State] whenever it changes so that it can be shown in a status
+/// bar, and can be read using [State::published]. Fullscreen state is updated by
+/// [set_fullscreen_state] and by the [ewmh refresh hook][0] when focus moves between clients.
+///
+///   [0]: crate::extensions::hooks::ewmh::refresh_hook
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FullscreenStatus {
+    /// Whether the focused client is fullscreen
+    pub fullscreen: bool,
+    /// Whether the focused client is in fake fullscreen mode
+    pub fake: bool,
+}
+
+/// Publish the [FullscreenStatus] of the focused client if it has changed since it was last
+/// published.
+pub fn publish_fullscreen_status<X: XConn>(state: &mut State<X>) {
+    let status = match state.client_set.current_client() {
+        Some(&id) => FullscreenStatus {
+            fullscreen: is_fullscreen(id, state),
+            fake: is_fake_fullscreen(id, state),
+        },
+        None => FullscreenStatus::default(),
+    };
+
+    if state.published::<FullscreenStatus>() != Some(&status) {
+        state.publish(status);
+    }
+}
+
+/// Clients that have been placed in fake fullscreen mode using [toggle_fake_fullscreen],
+/// stored as a [State] extension.
+#[derive(Debug, Default, Clone)]
+pub struct FakeFullscreenClients {
+    clients: HashSet<Xid>,
+}
+
+impl FakeFullscreenClients {
+    /// Whether or not the given client is in fake fullscreen mode.
+    pub fn contains(&self, id: &Xid) -> bool {
+        self.clients.contains(id)
+    }
+
+    /// The clients that are currently in fake fullscreen mode.
+    pub fn clients(&self) -> impl Iterator<Item = &Xid> {
+        self.clients.iter()
+    }
+}
+
+/// Check whether or not the given client is currently fullscreen.
+///
+/// This does not include clients in fake fullscreen mode (see [is_fake_fullscreen]).
+pub fn is_fullscreen<X: XConn>(id: Xid, state: &State<X>) -> bool {
+    state
+        .extension::<FullscreenClients>()
+        .map(|fs| fs.borrow().contains(&id))
+        .unwrap_or(false)
+}
+
+/// Check whether or not the given client is in fake fullscreen mode.
+pub fn is_fake_fullscreen<X: XConn>(id: Xid, state: &State<X>) -> bool {
+    state
+        .extension::<FakeFullscreenClients>()
+        .map(|fs| fs.borrow().contains(&id))
+        .unwrap_or(false)
+}
+
 /// Set the fullscreen state of a particular client
+///
+/// When a client leaves fullscreen it is restored to its previous tiled or floating position.
+/// Clients in fake fullscreen mode (see [toggle_fake_fullscreen]) only have their
+/// `_NET_WM_STATE` updated: they remain in their current position.
 pub fn set_fullscreen_state<X: XConn>(
     id: Xid,
     action: FullScreenAction,
@@ -33,33 +152,143 @@ pub fn set_fullscreen_state<X: XConn>(
 ) -> Result<()> {
     use FullScreenAction::*;
 
-    let net_wm_state = Atom::NetWmState.as_ref();
-    let full_screen = x.intern_atom(Atom::NetWmStateFullscreen.as_ref())?;
-
-    let mut wstate = match x.get_prop(id, net_wm_state) {
-        Ok(Some(Prop::Cardinal(vals))) => vals,
-        _ => vec![],
-    };
+    let currently_fullscreen = x
+        .has_net_wm_state(id, Atom::NetWmStateFullscreen)
+        .unwrap_or(false);
+    let fs = state.extension_or_default::<FullscreenClients>();
+    let adding = action == Add || (action == Toggle && !currently_fullscreen);
 
-    let currently_fullscreen = wstate.contains(&full_screen);
-
-    if action == Add || (action == Toggle && !currently_fullscreen) {
+    if is_fake_fullscreen(id, state) {
+        // Only the reported state changes: the client remains where it is
+    } else if adding {
         let r = state
             .client_set
             .screen_for_client(&id)
             .ok_or_else(|| Error::UnknownClient(id))?
             .r;
+
+        if !fs.borrow().contains(&id) {
+            let prev = PreFullscreenState {
+                tag: state
+                    .client_set
+                    .tag_for_client(&id)
+                    .unwrap_or_default()
+                    .to_string(),
+                floating: state.client_set.floating.get(&id).copied(),
+            };
+            fs.borrow_mut().clients.insert(id, prev);
+        }
+
         state.client_set.float(id, r)?;
-        wstate.push(*full_screen);
-    } else if action == Remove || (action == Toggle && currently_fullscreen) {
-        state.client_set.sink(&id);
-        wstate.retain(|&val| val != *full_screen);
+    } else {
+        let prev = fs.borrow_mut().clients.remove(&id);
+        restore_pre_fullscreen_state(id, prev, state);
     }
 
-    x.set_prop(id, net_wm_state, Prop::Cardinal(wstate))?;
+    x.set_net_wm_state(id, Atom::NetWmStateFullscreen, adding)?;
+    publish_fullscreen_status(state);
     x.refresh(state)
 }
 
+fn restore_pre_fullscreen_state<X: XConn>(
+    id: Xid,
+    prev: Option<PreFullscreenState>,
+    state: &mut State<X>,
+) {
+    match prev.and_then(|p| p.floating) {
+        Some(r) => {
+            state.client_set.floating.insert(id, r);
+        }
+        None => {
+            state.client_set.sink(&id);
+        }
+    }
+}
+
+/// Toggle fake fullscreen mode for the currently focused window.
+///
+/// Clients in fake fullscreen mode are told that they are fullscreen (via `_NET_WM_STATE`)
+/// while remaining confined to their current tiled or floating position. This allows, for
+/// example, a video player to show its fullscreen UI without taking over the whole screen.
+/// Any requests from the client to enter or leave fullscreen while in this mode only update
+/// its `_NET_WM_STATE`.
+///
+/// If the client is already fullscreen when it enters fake fullscreen mode then it is restored
+/// to its previous position. Leaving fake fullscreen mode tells the client that it is no
+/// longer fullscreen.
+///
+/// **NOTE**: You will need to make use of [add_ewmh_hooks][0] for fullscreen requests from
+///           clients to be handled.
+///
+///   [0]: crate::extensions::hooks::add_ewmh_hooks
+pub fn toggle_fake_fullscreen<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, x: &X| {
+        let id = match state.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        let fake = state.extension_or_default::<FakeFullscreenClients>();
+        let entering = !fake.borrow().contains(&id);
+
+        if entering {
+            let prev = state
+                .extension::<FullscreenClients>()
+                .ok()
+                .and_then(|fs| fs.borrow_mut().clients.remove(&id));
+            if prev.is_some() {
+                restore_pre_fullscreen_state(id, prev, state);
+            }
+            fake.borrow_mut().clients.insert(id);
+            set_fullscreen_state(id, FullScreenAction::Add, state, x)
+        } else {
+            set_fullscreen_state(id, FullScreenAction::Remove, state, x)?;
+            fake.borrow_mut().clients.remove(&id);
+
+            Ok(())
+        }
+    })
+}
+
+/// Remove the fullscreen state from any clients that have moved to a different workspace
+/// since they were made fullscreen, restoring their previous position.
+///
+/// This is run automatically as part of the [ewmh refresh hook][0].
+///
+///   [0]: crate::extensions::hooks::ewmh::refresh_hook
+pub fn exit_fullscreen_for_moved_clients<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    if let Ok(fake) = state.extension::<FakeFullscreenClients>() {
+        fake.borrow_mut()
+            .clients
+            .retain(|id| state.client_set.contains(id));
+    }
+
+    let fs = match state.extension::<FullscreenClients>() {
+        Ok(fs) => fs,
+        Err(_) => return Ok(()),
+    };
+
+    fs.borrow_mut()
+        .clients
+        .retain(|id, _| state.client_set.contains(id));
+
+    let moved: Vec<Xid> = fs
+        .borrow()
+        .clients
+        .iter()
+        .filter(|(id, prev)| state.client_set.tag_for_client(id) != Some(prev.tag.as_str()))
+        .map(|(&id, _)| id)
+        .collect();
+
+    for id in moved {
+        set_fullscreen_state(id, FullScreenAction::Remove, state, x)?;
+    }
+
+    publish_fullscreen_status(state);
+
+    Ok(())
+}
+
 /// Toggle the fullscreen state of the currently focused window.
 ///
 /// **NOTE**: You will need to make use of [add_ewmh_hooks][0] for this action to
@@ -77,6 +306,29 @@ pub fn toggle_fullscreen<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
     })
 }
 
+/// Toggle the maximized state of the currently focused window.
+///
+/// Maximized windows take up the full area available to the layout of their workspace,
+/// stacked above the other tiled windows, without becoming fullscreen. Toggling the state
+/// again returns the window to its previous position in the layout. The current state is
+/// reported to external clients using `_NET_WM_STATE_MAXIMIZED_VERT` and
+/// `_NET_WM_STATE_MAXIMIZED_HORZ`.
+pub fn toggle_maximized<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state, x: &X| {
+        let id = match state.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        let maximized = state.client_set.toggle_maximized(&id);
+
+        for atom in [Atom::NetWmStateMaximizedVert, Atom::NetWmStateMaximizedHorz] {
+            x.set_net_wm_state(id, atom, maximized)?;
+        }
+        x.refresh(state)
+    })
+}
+
 /// Jump to, or create a [Workspace][0].
 ///
 /// Call 'get_name' to obtain a Workspace name and check to see if there is currently a Workspace
@@ -130,7 +382,10 @@ where
 /// if no such client exists.
 ///
 /// This is useful for key bindings that are based on the program you want to work with rather than
-/// having to remember where things are running.
+/// having to remember where things are running. As with [spawn][crate::builtin::actions::spawn],
+/// the command is checked against any configured [CommandWhitelist][0] before it is run.
+///
+///   [0]: crate::core::CommandWhitelist
 pub fn focus_or_spawn<X>(class: &'static str, command: &'static str) -> Box<dyn KeyEventHandler<X>>
 where
     X: XConn,
@@ -156,3 +411,36 @@ where
         })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::Config,
+        testing::{HeadlessConn, Scenario},
+    };
+
+    #[test]
+    fn toggling_maximized_preserves_other_net_wm_states() {
+        let bindings: HashMap<&str, Box<dyn KeyEventHandler<HeadlessConn>>> =
+            HashMap::from([("M-m", toggle_maximized())]);
+        let s = Scenario::with_bindings(Config::default(), bindings)
+            .unwrap()
+            .open_window("a", "a");
+        let id = s.id("a");
+        s.conn()
+            .set_net_wm_state(id, Atom::NetWmStateSkipTaskbar, true)
+            .unwrap();
+
+        let s = s.press("M-m");
+        let has_state = |atom| s.conn().has_net_wm_state(id, atom).unwrap();
+        assert!(has_state(Atom::NetWmStateMaximizedVert));
+        assert!(has_state(Atom::NetWmStateMaximizedHorz));
+        assert!(has_state(Atom::NetWmStateSkipTaskbar));
+
+        let s = s.press("M-m");
+        let has_state = |atom| s.conn().has_net_wm_state(id, atom).unwrap();
+        assert!(!has_state(Atom::NetWmStateMaximizedVert));
+        assert!(has_state(Atom::NetWmStateSkipTaskbar));
+    }
+}
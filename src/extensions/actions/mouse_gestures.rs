@@ -0,0 +1,222 @@
+//! Simple mouse gestures made by dragging across the root window.
+use crate::{
+    core::{
+        bindings::{
+            KeyEventHandler, ModifierKey, MouseBindings, MouseButton, MouseEvent,
+            MouseEventHandler, MouseEventKind, MouseState,
+        },
+        State,
+    },
+    pure::geometry::{Direction, Point},
+    x::XConn,
+    Result,
+};
+use std::{collections::HashMap, fmt};
+use tracing::trace;
+
+/// The distance in pixels the pointer needs to move before a drag is treated as a gesture if
+/// no other threshold is given.
+pub const DEFAULT_GESTURE_THRESHOLD: u32 = 50;
+
+// State extension tracking where the gesture currently in progress (if any) started so that the
+// handlers bound to press and release events are able to coordinate.
+#[derive(Debug, Default)]
+struct ActiveGesture(Option<Point>);
+
+/// A [MouseEventHandler] that runs actions in response to straight line strokes made by
+/// dragging the mouse across the root window.
+///
+/// A gesture starts when the bound mouse button is pressed over the root window (i.e. not over
+/// a client) and is completed when the button is released. The direction of the gesture is
+/// taken from the overall distance moved between the two: whichever of the horizontal or
+/// vertical distance is larger determines the [Direction] of the stroke. Drags shorter than
+/// the gesture threshold are ignored so that plain clicks do not trigger an action.
+///
+/// This handler needs to be bound to both the press and release events for the mouse state
+/// being used: see [MouseGestures::into_bindings].
+///
+/// ```no_run
+/// # use penrose::{
+/// #     builtin::actions::modify_with,
+/// #     core::bindings::{ModifierKey, MouseButton},
+/// #     extensions::actions::MouseGestures,
+/// #     pure::geometry::Direction,
+/// #     x11rb::RustConn,
+/// # };
+/// let mouse_bindings = MouseGestures::<RustConn>::new()
+///     .on(Direction::Left, modify_with(|cs| cs.previous_tag()))
+///     .on(Direction::Right, modify_with(|cs| cs.next_tag()))
+///     .into_bindings(MouseButton::Left, vec![ModifierKey::Meta, ModifierKey::Shift]);
+/// ```
+pub struct MouseGestures<X: XConn> {
+    threshold: u32,
+    actions: HashMap<Direction, Box<dyn KeyEventHandler<X>>>,
+}
+
+impl<X: XConn> fmt::Debug for MouseGestures<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MouseGestures")
+            .field("threshold", &self.threshold)
+            .field("directions", &self.actions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<X: XConn> Default for MouseGestures<X> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<X: XConn> MouseGestures<X> {
+    /// Create a new set of gestures with no actions, using [DEFAULT_GESTURE_THRESHOLD].
+    pub fn new() -> Self {
+        Self {
+            threshold: DEFAULT_GESTURE_THRESHOLD,
+            actions: HashMap::new(),
+        }
+    }
+
+    /// Set the distance in pixels the pointer needs to move for a drag to count as a gesture.
+    pub fn with_threshold(mut self, px: u32) -> Self {
+        self.threshold = px;
+        self
+    }
+
+    /// Run the given action when a stroke is made in the given [Direction], replacing any
+    /// existing action for that direction.
+    pub fn on(mut self, direction: Direction, action: Box<dyn KeyEventHandler<X>>) -> Self {
+        self.actions.insert(direction, action);
+        self
+    }
+
+    /// Mouse bindings for making these gestures by dragging with the given button while
+    /// holding `modifiers`.
+    pub fn into_bindings(self, button: MouseButton, modifiers: Vec<ModifierKey>) -> MouseBindings<X>
+    where
+        X: 'static,
+    {
+        let ms = MouseState::new(button, modifiers);
+        let mut bindings: MouseBindings<X> = HashMap::new();
+        bindings.insert((MouseEventKind::Press, ms.clone()), Box::new(start_gesture));
+        bindings.insert((MouseEventKind::Release, ms), Box::new(self));
+
+        bindings
+    }
+}
+
+impl<X: XConn> MouseEventHandler<X> for MouseGestures<X> {
+    fn call(&mut self, evt: &MouseEvent, state: &mut State<X>, x: &X) -> Result<()> {
+        match evt.kind {
+            MouseEventKind::Press | MouseEventKind::DoubleClick => start_gesture(evt, state, x),
+            MouseEventKind::Motion => Ok(()),
+            MouseEventKind::Release => {
+                let start = state.extension_or_default::<ActiveGesture>();
+                let start = match start.borrow_mut().0.take() {
+                    Some(p) => p,
+                    None => return Ok(()),
+                };
+
+                let direction = match stroke_direction(start, evt.rpt, self.threshold) {
+                    Some(d) => d,
+                    None => return Ok(()),
+                };
+
+                trace!(?direction, "mouse gesture completed");
+                match self.actions.get_mut(&direction) {
+                    Some(action) => action.call(state, x),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+fn start_gesture<X: XConn>(evt: &MouseEvent, state: &mut State<X>, _: &X) -> Result<()> {
+    let start = (evt.id == state.root()).then_some(evt.rpt);
+    state.extension_or_default::<ActiveGesture>().borrow_mut().0 = start;
+
+    Ok(())
+}
+
+// The direction of a stroke from `from` to `to`, or None if it is shorter than `threshold`.
+fn stroke_direction(from: Point, to: Point, threshold: u32) -> Option<Direction> {
+    let dx = to.x as i64 - from.x as i64;
+    let dy = to.y as i64 - from.y as i64;
+
+    if dx.abs().max(dy.abs()) < threshold as i64 {
+        return None;
+    }
+
+    let d = match (dx.abs() >= dy.abs(), dx > 0, dy > 0) {
+        (true, true, _) => Direction::Right,
+        (true, false, _) => Direction::Left,
+        (false, _, true) => Direction::Down,
+        (false, _, false) => Direction::Up,
+    };
+
+    Some(d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::modify_with,
+        core::{Config, WindowManager},
+        testing::{HeadlessConn, Scenario},
+        x::XEvent,
+    };
+    use simple_test_case::test_case;
+    use Direction::*;
+
+    #[test_case((100, 100), (200, 110), Some(Right); "right")]
+    #[test_case((100, 100), (20, 80), Some(Left); "left")]
+    #[test_case((100, 100), (90, 20), Some(Up); "up")]
+    #[test_case((100, 100), (130, 300), Some(Down); "down")]
+    #[test_case((100, 100), (149, 100), None; "below threshold")]
+    #[test_case((100, 100), (150, 100), Some(Right); "at threshold")]
+    #[test_case((100, 100), (160, 160), Some(Right); "diagonal prefers horizontal")]
+    #[test]
+    fn stroke_direction_works(from: (u32, u32), to: (u32, u32), expected: Option<Direction>) {
+        let from = Point::new(from.0, from.1);
+        let to = Point::new(to.0, to.1);
+
+        assert_eq!(stroke_direction(from, to, 50), expected);
+    }
+
+    fn gesture_scenario() -> Scenario {
+        let mouse_bindings = MouseGestures::new()
+            .on(Left, modify_with(|cs| cs.previous_tag()))
+            .on(Right, modify_with(|cs| cs.next_tag()))
+            .into_bindings(MouseButton::Left, vec![ModifierKey::Meta]);
+        let conn = HeadlessConn::default();
+        let wm = WindowManager::new(Config::default(), HashMap::new(), mouse_bindings, conn)
+            .expect("valid window manager");
+
+        Scenario::new(wm).unwrap()
+    }
+
+    fn drag(s: Scenario, id: Option<&str>, from: i16, to: i16) -> Scenario {
+        let id = id.map_or(s.state().root(), |name| s.id(name));
+        let ms = MouseState::new(MouseButton::Left, vec![ModifierKey::Meta]);
+        let evt =
+            |x, kind| XEvent::MouseEvent(MouseEvent::new(id, x, 100, x, 100, ms.clone(), kind));
+
+        let press = evt(from, MouseEventKind::Press);
+        let release = evt(to, MouseEventKind::Release);
+
+        s.send(press).send(release)
+    }
+
+    #[test_case(None, 100, 300, "2"; "right stroke")]
+    #[test_case(None, 300, 100, "9"; "left stroke")]
+    #[test_case(None, 100, 120, "1"; "short drags are ignored")]
+    #[test_case(Some("a"), 100, 300, "1"; "drags over clients are ignored")]
+    #[test]
+    fn gestures_run_actions(id: Option<&str>, from: i16, to: i16, expected: &str) {
+        let s = gesture_scenario().given_windows(["a"]);
+
+        drag(s, id, from, to).expect_workspace(expected);
+    }
+}
@@ -0,0 +1,207 @@
+//! Animated transitions when switching workspaces.
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State, WindowManager},
+    extensions::{hooks::power_profiles::PowerSourceHook, util::power::PowerSource},
+    x::XConn,
+    x11rb::transition::{Transition, TransitionEffect},
+};
+use std::time::Duration;
+use tracing::warn;
+
+/// The animation used when switching workspaces.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TransitionStyle {
+    /// Slide the old arrangement off the screen in the direction of the switch: moving to a
+    /// workspace with a higher index slides to the left and moving to a lower index slides to
+    /// the right.
+    #[default]
+    Slide,
+    /// Crossfade from the old arrangement to the new one.
+    ///
+    /// This requires a running compositing manager (see [TransitionEffect::Fade]).
+    Crossfade,
+}
+
+/// The state extension used to configure workspace transitions and track the currently
+/// running transition.
+#[derive(Debug)]
+pub struct WorkspaceTransitions {
+    style: TransitionStyle,
+    duration: Duration,
+    enabled: bool,
+    active: Option<Transition>,
+}
+
+impl WorkspaceTransitions {
+    /// Whether or not transitions are currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Add the [WorkspaceTransitions] state extension so that actions wrapped with
+/// [with_workspace_transition] are animated.
+///
+/// Transitions with a zero `duration` are skipped entirely.
+pub fn add_workspace_transitions<X: XConn>(
+    mut wm: WindowManager<X>,
+    style: TransitionStyle,
+    duration: Duration,
+) -> WindowManager<X> {
+    wm.state.add_extension(WorkspaceTransitions {
+        style,
+        duration,
+        enabled: true,
+        active: None,
+    });
+
+    wm
+}
+
+/// Run the given action, animating the change if it results in a different workspace being
+/// shown on the focused screen.
+///
+/// A snapshot of the focused screen is taken before running the action and shown while the
+/// new arrangement of clients is put in place underneath it. The snapshot is then animated
+/// away using the [TransitionStyle] passed to [add_workspace_transitions]. Starting a new
+/// transition skips the remainder of any transition that is still running.
+///
+/// If [add_workspace_transitions] has not been called or transitions have been disabled using
+/// [toggle_workspace_transitions] then the action is run as normal.
+///
+/// ```no_run
+/// # use penrose::{builtin::actions::modify_with, x11rb::RustConn};
+/// use penrose::extensions::actions::with_workspace_transition;
+///
+/// let action = with_workspace_transition::<RustConn>(modify_with(|cs| cs.focus_tag("2")));
+/// ```
+pub fn with_workspace_transition<X: XConn + 'static>(
+    mut action: Box<dyn KeyEventHandler<X>>,
+) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |s: &mut State<X>, x: &X| {
+        let wt = match s.extension::<WorkspaceTransitions>() {
+            Ok(wt) => wt,
+            Err(_) => return action.call(s, x),
+        };
+
+        let (style, duration) = {
+            let mut wt = wt.borrow_mut();
+            // The overlay of a running transition must not end up in the new snapshot
+            if let Some(t) = wt.active.take() {
+                t.stop();
+            }
+
+            if !wt.enabled || wt.duration.is_zero() {
+                return action.call(s, x);
+            }
+
+            (wt.style, wt.duration)
+        };
+
+        let screen = s.client_set.current_screen();
+        let (index, before) = (screen.index(), screen.workspace.id());
+        let mut transition = match Transition::capture(screen.geometry()) {
+            Ok(t) => t,
+            Err(e) => {
+                warn!(%e, "unable to capture screen for workspace transition");
+                return action.call(s, x);
+            }
+        };
+
+        let res = action.call(s, x);
+
+        let after = s
+            .client_set
+            .screens()
+            .find(|s| s.index() == index)
+            .map(|s| s.workspace.id());
+
+        // If the workspace on the screen is unchanged then dropping the transition removes
+        // the overlay without animating it.
+        if let Some(effect) = after.and_then(|after| transition_effect(style, before, after)) {
+            transition.run(effect, duration);
+            wt.borrow_mut().active = Some(transition);
+        }
+
+        res
+    })
+}
+
+/// A [PowerSourceHook] that disables workspace transitions while running on battery and
+/// re-enables them once mains power is restored.
+///
+/// ```no_run
+/// # use penrose::{core::WindowManager, x11rb::RustConn};
+/// # use std::time::Duration;
+/// use penrose::extensions::{
+///     actions::{add_workspace_transitions, transitions_power_hook, TransitionStyle},
+///     hooks::{add_power_profiles, on_power_source_change},
+/// };
+///
+/// # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+/// let wm = add_workspace_transitions(wm, TransitionStyle::Slide, Duration::from_millis(200));
+/// let wm = add_power_profiles(wm, "BAT0", Duration::from_secs(10));
+/// on_power_source_change(wm, transitions_power_hook())
+/// # }
+/// ```
+pub fn transitions_power_hook<X: XConn>() -> impl PowerSourceHook<X> {
+    |source: PowerSource, s: &mut State<X>, _: &X| {
+        if let Ok(wt) = s.extension::<WorkspaceTransitions>() {
+            wt.borrow_mut().enabled = source == PowerSource::Ac;
+        }
+
+        Ok(())
+    }
+}
+
+/// Toggle whether or not actions wrapped with [with_workspace_transition] are animated.
+pub fn toggle_workspace_transitions<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|s: &mut State<X>, _: &X| {
+        match s.extension::<WorkspaceTransitions>() {
+            Ok(wt) => {
+                let mut wt = wt.borrow_mut();
+                wt.enabled = !wt.enabled;
+            }
+            Err(_) => warn!("add_workspace_transitions has not been called"),
+        }
+
+        Ok(())
+    })
+}
+
+// The effect to use when the workspace shown on a screen changes from the workspace with
+// id `before` to the workspace with id `after`, or None if it is unchanged.
+fn transition_effect(
+    style: TransitionStyle,
+    before: usize,
+    after: usize,
+) -> Option<TransitionEffect> {
+    match style {
+        _ if before == after => None,
+        TransitionStyle::Crossfade => Some(TransitionEffect::Fade),
+        TransitionStyle::Slide if after > before => Some(TransitionEffect::SlideLeft),
+        TransitionStyle::Slide => Some(TransitionEffect::SlideRight),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case(TransitionStyle::Slide, 1, 1, None; "unchanged")]
+    #[test_case(TransitionStyle::Slide, 1, 2, Some(TransitionEffect::SlideLeft); "slide forward")]
+    #[test_case(TransitionStyle::Slide, 2, 1, Some(TransitionEffect::SlideRight); "slide back")]
+    #[test_case(TransitionStyle::Crossfade, 2, 1, Some(TransitionEffect::Fade); "crossfade")]
+    #[test_case(TransitionStyle::Crossfade, 2, 2, None; "crossfade unchanged")]
+    #[test]
+    fn transition_effect_works(
+        style: TransitionStyle,
+        before: usize,
+        after: usize,
+        expected: Option<TransitionEffect>,
+    ) {
+        assert_eq!(transition_effect(style, before, after), expected);
+    }
+}
@@ -0,0 +1,175 @@
+//! Arranging workspaces in a two dimensional grid.
+use crate::{
+    builtin::actions::modify_with,
+    core::{bindings::KeyEventHandler, ClientSet},
+    pure::geometry::Direction,
+    x::XConn,
+};
+
+/// Workspaces arranged in a grid of `rows` x `cols` (the classic "desktop wall").
+///
+/// Workspaces are placed into the grid in the order given by [ClientSet::ordered_tags],
+/// filling each row from left to right before moving on to the next row. Any workspaces
+/// beyond the size of the grid are not reachable using grid navigation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkspaceGrid {
+    rows: usize,
+    cols: usize,
+    wrap: bool,
+}
+
+impl WorkspaceGrid {
+    /// Create a new grid with the given number of rows and columns.
+    ///
+    /// # Panics
+    /// Panics if `rows` or `cols` is zero.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        if rows == 0 || cols == 0 {
+            panic!("a workspace grid must have at least one row and column: got {rows}x{cols}");
+        }
+
+        Self {
+            rows,
+            cols,
+            wrap: false,
+        }
+    }
+
+    /// Wrap around to the opposite edge of the grid when moving off of it.
+    pub fn wrapping(mut self) -> Self {
+        self.wrap = true;
+        self
+    }
+
+    /// The number of rows in this grid.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of columns in this grid.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The (row, column) position of the given tag within the grid.
+    pub fn position_of(&self, tags: &[String], tag: &str) -> Option<(usize, usize)> {
+        let ix = tags.iter().position(|t| t == tag)?;
+        if ix >= self.rows * self.cols {
+            return None;
+        }
+
+        Some((ix / self.cols, ix % self.cols))
+    }
+
+    /// The tag at the given (row, column) position within the grid.
+    pub fn tag_at<'a>(&self, tags: &'a [String], row: usize, col: usize) -> Option<&'a str> {
+        if row >= self.rows || col >= self.cols {
+            return None;
+        }
+
+        tags.get(row * self.cols + col).map(|t| t.as_str())
+    }
+
+    /// The tag of the workspace next to `tag` in the given [Direction], if there is one.
+    pub fn neighbour<'a>(&self, tags: &'a [String], tag: &str, d: Direction) -> Option<&'a str> {
+        let (row, col) = self.position_of(tags, tag)?;
+        let (row, col) = (row as isize, col as isize);
+        let (dr, dc) = match d {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        };
+
+        let (rows, cols) = (self.rows as isize, self.cols as isize);
+        let (mut r, mut c) = (row + dr, col + dc);
+        if self.wrap {
+            r = r.rem_euclid(rows);
+            c = c.rem_euclid(cols);
+        } else if !((0..rows).contains(&r) && (0..cols).contains(&c)) {
+            return None;
+        }
+
+        self.tag_at(tags, r as usize, c as usize)
+    }
+
+    fn neighbour_of_current(&self, cs: &ClientSet, d: Direction) -> Option<String> {
+        let tags = cs.ordered_tags();
+
+        self.neighbour(&tags, cs.current_tag(), d)
+            .map(|t| t.to_string())
+    }
+}
+
+/// Focus the workspace next to the current one in the given [Direction] within the grid.
+///
+/// This is a no-op if the current workspace is not in the grid or there is no workspace
+/// in the requested direction.
+pub fn grid_focus<X: XConn>(grid: WorkspaceGrid, d: Direction) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| {
+        if let Some(tag) = grid.neighbour_of_current(cs, d) {
+            cs.focus_tag(&tag);
+        }
+    })
+}
+
+/// Move the focused client to the workspace next to the current one in the given [Direction]
+/// within the grid and follow it there.
+///
+/// This is a no-op if the current workspace is not in the grid or there is no workspace
+/// in the requested direction.
+pub fn grid_move_client<X: XConn>(
+    grid: WorkspaceGrid,
+    d: Direction,
+) -> Box<dyn KeyEventHandler<X>> {
+    modify_with(move |cs| {
+        let tag = match grid.neighbour_of_current(cs, d) {
+            Some(tag) => tag,
+            None => return,
+        };
+
+        if let Some(&id) = cs.current_client() {
+            cs.move_focused_to_tag(&tag);
+            cs.focus_client(&id);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+    use Direction::*;
+
+    fn tags(n: usize) -> Vec<String> {
+        (1..=n).map(|n| n.to_string()).collect()
+    }
+
+    #[test_case("5", Up, false, Some("2"); "up")]
+    #[test_case("5", Down, false, Some("8"); "down")]
+    #[test_case("5", Left, false, Some("4"); "left")]
+    #[test_case("5", Right, false, Some("6"); "right")]
+    #[test_case("1", Up, false, None; "off the top edge")]
+    #[test_case("3", Right, false, None; "off the right edge")]
+    #[test_case("1", Up, true, Some("7"); "wrapping top edge")]
+    #[test_case("3", Right, true, Some("1"); "wrapping right edge")]
+    #[test_case("7", Left, true, Some("9"); "wrapping left edge")]
+    #[test_case("10", Left, false, None; "tag outside of grid")]
+    #[test]
+    fn neighbour(tag: &str, d: Direction, wrap: bool, expected: Option<&str>) {
+        let mut grid = WorkspaceGrid::new(3, 3);
+        grid.wrap = wrap;
+
+        assert_eq!(grid.neighbour(&tags(10), tag, d), expected);
+    }
+
+    #[test]
+    fn missing_cells_have_no_tag() {
+        let grid = WorkspaceGrid::new(2, 3);
+        let tags = tags(4);
+
+        assert_eq!(grid.neighbour(&tags, "2", Down), None);
+        assert_eq!(grid.neighbour(&tags, "1", Down), Some("4"));
+        assert_eq!(grid.position_of(&tags, "4"), Some((1, 0)));
+    }
+}
@@ -0,0 +1,306 @@
+//! Moving clients that have not been focused for a while to an archive workspace.
+//!
+//! [add_auto_archive] adds a regular workspace tagged with [ARCHIVE_TAG] along with an
+//! [AutoArchive] state extension that tracks when each client was last focused. Clients that
+//! have been left untouched for longer than the configured maximum idle time can be moved to
+//! the archive workspace on demand using [archive_stale_clients], or automatically by
+//! providing a check interval. A notification summarising the archived clients is sent via
+//! `notify-send` each time clients are archived.
+//!
+//! Clients on the archive workspace are not considered stale: moving a client back out of the
+//! archive resets its idle time. Clients on invisible workspaces (such as minimized clients
+//! or named scratchpads) and the currently focused client are never archived.
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, ClientSet, State, WindowManager},
+    extensions::util::notify_send,
+    x::{XConn, XConnExt, XEvent},
+    Result, Xid,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// The tag used for the workspace holding archived clients.
+pub const ARCHIVE_TAG: &str = "archive";
+
+/// The time at which each client was last focused, used to determine which clients should be
+/// moved to the archive workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoArchive {
+    max_idle: Duration,
+    check_interval: Option<Duration>,
+    last_check: Option<Instant>,
+    last_focus: HashMap<Xid, Instant>,
+}
+
+impl AutoArchive {
+    /// Archive clients that have not been focused for `max_idle`, checking for stale clients
+    /// every `check_interval` if one is provided.
+    pub fn new(max_idle: Duration, check_interval: Option<Duration>) -> Self {
+        Self {
+            max_idle,
+            check_interval,
+            last_check: None,
+            last_focus: HashMap::new(),
+        }
+    }
+
+    /// The time at which the given client was last focused (or first seen), if it is known.
+    pub fn last_focused(&self, id: &Xid) -> Option<Instant> {
+        self.last_focus.get(id).copied()
+    }
+
+    // Update the tracked focus times from the current state of the client set, dropping any
+    // clients that are no longer managed.
+    fn update(&mut self, cs: &ClientSet, now: Instant) {
+        self.last_focus.retain(|id, _| cs.contains(id));
+
+        for w in cs.ordered_workspaces() {
+            for &id in w.clients() {
+                if w.tag == ARCHIVE_TAG {
+                    self.last_focus.insert(id, now);
+                } else {
+                    self.last_focus.entry(id).or_insert(now);
+                }
+            }
+        }
+
+        if let Some(&id) = cs.current_client() {
+            self.last_focus.insert(id, now);
+        }
+    }
+
+    // The clients outside of the archive workspace that have not been focused for longer
+    // than the maximum idle time.
+    fn stale_clients(&self, cs: &ClientSet, now: Instant) -> Vec<Xid> {
+        let focused = cs.current_client();
+        let mut stale: Vec<Xid> = cs
+            .ordered_workspaces()
+            .filter(|w| w.tag != ARCHIVE_TAG)
+            .flat_map(|w| w.clients())
+            .filter(|&id| Some(id) != focused)
+            .filter(|id| match self.last_focus.get(id) {
+                Some(&t) => now.saturating_duration_since(t) > self.max_idle,
+                None => false,
+            })
+            .copied()
+            .collect();
+
+        stale.sort_by_key(|id| self.last_focus.get(id).copied());
+
+        stale
+    }
+
+    // Whether or not a scheduled check for stale clients is due.
+    fn check_due(&mut self, now: Instant) -> bool {
+        let interval = match self.check_interval {
+            Some(interval) => interval,
+            None => return false,
+        };
+
+        match self.last_check {
+            Some(t) if now.saturating_duration_since(t) < interval => false,
+            _ => {
+                self.last_check = Some(now);
+                true
+            }
+        }
+    }
+
+    // Move all stale clients to the archive workspace, returning the clients that were moved.
+    fn archive(&mut self, cs: &mut ClientSet, now: Instant) -> Vec<Xid> {
+        let stale = self.stale_clients(cs, now);
+        for id in stale.iter() {
+            cs.move_client_to_tag(id, ARCHIVE_TAG);
+        }
+
+        stale
+    }
+}
+
+/// Add the workspace used for archived clients, the [AutoArchive] state extension and the
+/// hooks required for tracking focus and running scheduled checks for stale clients.
+///
+/// # Panics
+/// This function will panic if [ARCHIVE_TAG] is already in use as a workspace tag.
+pub fn add_auto_archive<X>(
+    mut wm: WindowManager<X>,
+    max_idle: Duration,
+    check_interval: Option<Duration>,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    let layouts = wm.state.config.default_layouts.clone();
+    wm.state
+        .client_set
+        .add_workspace(ARCHIVE_TAG, layouts)
+        .expect("archive tag to be unique");
+    wm.state
+        .add_extension(AutoArchive::new(max_idle, check_interval));
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+    wm.state.config.compose_or_set_event_hook(event_hook);
+
+    wm
+}
+
+/// Record the focused client and track any newly managed clients.
+pub fn refresh_hook<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+    if let Ok(aa) = state.extension::<AutoArchive>() {
+        aa.borrow_mut().update(&state.client_set, Instant::now());
+    }
+
+    Ok(())
+}
+
+/// Archive stale clients if a scheduled check is due.
+///
+/// As penrose does not run a timer of its own, checks are made as X events are received
+/// rather than at exact intervals.
+pub fn event_hook<X: XConn>(_: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+    let due = match state.extension::<AutoArchive>() {
+        Ok(aa) => aa.borrow_mut().check_due(Instant::now()),
+        Err(_) => return Ok(true),
+    };
+
+    if due {
+        archive_and_notify(state, x)?;
+    }
+
+    Ok(true)
+}
+
+/// Move all clients that have not been focused for longer than the configured maximum idle
+/// time to the archive workspace.
+pub fn archive_stale_clients<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        if !state.client_set.contains_tag(ARCHIVE_TAG) {
+            warn!("unable to archive clients: add_auto_archive has not been called");
+            return Ok(());
+        }
+
+        archive_and_notify(state, x)
+    })
+}
+
+fn archive_and_notify<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let aa = state.extension::<AutoArchive>()?;
+    let archived = aa
+        .borrow_mut()
+        .archive(&mut state.client_set, Instant::now());
+
+    if archived.is_empty() {
+        return Ok(());
+    }
+
+    debug!(?archived, "archiving stale clients");
+    x.refresh(state)?;
+
+    let titles: Vec<String> = archived
+        .iter()
+        .map(|&id| x.window_title(id).unwrap_or_else(|_| id.to_string()))
+        .collect();
+    let summary = match archived.len() {
+        1 => "Archived 1 window".to_string(),
+        n => format!("Archived {n} windows"),
+    };
+
+    if let Err(e) = notify_send(summary, titles.join("\n")) {
+        warn!(%e, "unable to send archive notification");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pure::test_xid_stack_set;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn client_set() -> ClientSet {
+        let mut cs = test_xid_stack_set(3, 1);
+        cs.add_workspace(ARCHIVE_TAG, Default::default()).unwrap();
+        for n in 1..=3 {
+            cs.insert(Xid(n));
+        }
+
+        cs
+    }
+
+    #[test]
+    fn only_unfocused_clients_past_max_idle_are_stale() {
+        let mut cs = client_set();
+        let mut aa = AutoArchive::new(HOUR, None);
+        let t = Instant::now();
+        aa.update(&cs, t);
+
+        cs.focus_client(&Xid(2));
+        aa.update(&cs, t + HOUR / 2);
+        cs.focus_client(&Xid(1));
+        aa.update(&cs, t + HOUR / 2);
+
+        assert_eq!(aa.stale_clients(&cs, t + HOUR), vec![]);
+        assert_eq!(aa.stale_clients(&cs, t + 2 * HOUR), vec![Xid(3), Xid(2)]);
+    }
+
+    #[test]
+    fn archive_moves_stale_clients() {
+        let mut cs = client_set();
+        let mut aa = AutoArchive::new(HOUR, None);
+        let t = Instant::now();
+        aa.update(&cs, t);
+
+        let archived = aa.archive(&mut cs, t + 2 * HOUR);
+
+        assert_eq!(archived, vec![Xid(2), Xid(1)]);
+        for id in archived {
+            assert_eq!(cs.tag_for_client(&id), Some(ARCHIVE_TAG));
+        }
+        assert_eq!(cs.tag_for_client(&Xid(3)), Some("1"));
+    }
+
+    #[test]
+    fn moving_a_client_out_of_the_archive_resets_its_idle_time() {
+        let mut cs = client_set();
+        let mut aa = AutoArchive::new(HOUR, None);
+        let t = Instant::now();
+        aa.update(&cs, t);
+        aa.archive(&mut cs, t + 2 * HOUR);
+        aa.update(&cs, t + 2 * HOUR);
+
+        cs.move_client_to_tag(&Xid(1), "2");
+        aa.update(&cs, t + 2 * HOUR);
+
+        assert_eq!(aa.last_focused(&Xid(1)), Some(t + 2 * HOUR));
+        assert_eq!(aa.stale_clients(&cs, t + 2 * HOUR), vec![]);
+    }
+
+    #[test]
+    fn unmanaged_clients_are_dropped() {
+        let mut cs = client_set();
+        let mut aa = AutoArchive::new(HOUR, None);
+        let t = Instant::now();
+        aa.update(&cs, t);
+
+        cs.remove_client(&Xid(2));
+        aa.update(&cs, t);
+
+        assert_eq!(aa.last_focused(&Xid(2)), None);
+    }
+
+    #[test]
+    fn checks_are_only_due_once_per_interval() {
+        let mut aa = AutoArchive::new(HOUR, Some(Duration::from_secs(60)));
+        let t = Instant::now();
+
+        assert!(aa.check_due(t));
+        assert!(!aa.check_due(t + Duration::from_secs(30)));
+        assert!(aa.check_due(t + Duration::from_secs(60)));
+        assert!(!AutoArchive::new(HOUR, None).check_due(t), "no interval");
+    }
+}
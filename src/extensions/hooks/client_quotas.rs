@@ -0,0 +1,300 @@
+//! Soft limits on the number of clients on each workspace.
+//!
+//! [ClientQuotas] sets a soft limit on the number of clients that each workspace should hold.
+//! Limits are never enforced: new clients are always managed as normal. Instead, whenever a
+//! workspace goes over its limit each registered [QuotaHook] is run and the set of workspaces
+//! that are over their limit is published on the [State] as [CrowdedWorkspaces] so that it can
+//! be shown in a status bar (the `penrose_ui` Workspaces widget highlights crowded tags).
+//!
+//! This can serve as a nudge when using layouts such as monocle where it is easy to lose track
+//! of how many clients have accumulated on a workspace.
+//!
+//! Refresh hooks run in the order they were added so [add_client_quotas] should be called
+//! before adding a status bar in order for the bar to see up to date quota information.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! use penrose::extensions::hooks::client_quotas::{add_client_quotas, on_quota_exceeded, ClientQuotas};
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! let quotas = ClientQuotas::new(Some(6)).with_limit("web", 3);
+//! let wm = add_client_quotas(wm, quotas);
+//!
+//! on_quota_exceeded(wm, |tag: &str, n: usize, _: &mut _, _: &_| {
+//!     tracing::warn!(%tag, n, "workspace is getting crowded");
+//!     Ok(())
+//! })
+//! # }
+//! ```
+use crate::{
+    core::{State, WindowManager},
+    x::XConn,
+    Result,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, mem,
+};
+use tracing::{debug, error};
+
+/// Logic to run when a workspace goes over its client quota.
+pub trait QuotaHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook for the given tag and the number of clients now on that workspace.
+    fn call(&mut self, tag: &str, n_clients: usize, state: &mut State<X>, x: &X) -> Result<()>;
+}
+
+impl<F, X> QuotaHook<X> for F
+where
+    F: FnMut(&str, usize, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, tag: &str, n_clients: usize, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(tag, n_clients, state, x)
+    }
+}
+
+/// The tags of workspaces that currently hold more clients than their quota allows, in
+/// workspace order.
+///
+/// This is published on the [State] by [ClientQuotas] and can be read using
+/// [State::published].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CrowdedWorkspaces(pub Vec<String>);
+
+impl CrowdedWorkspaces {
+    /// Whether or not the workspace with the given tag is over its quota.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.0.iter().any(|t| t == tag)
+    }
+}
+
+/// Soft limits on the number of clients on each workspace along with the hooks to run when
+/// they are exceeded.
+pub struct ClientQuotas<X: XConn> {
+    default: Option<usize>,
+    limits: HashMap<String, usize>,
+    crowded: HashSet<String>,
+    hooks: Vec<Box<dyn QuotaHook<X>>>,
+}
+
+impl<X: XConn> Default for ClientQuotas<X> {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<X: XConn> fmt::Debug for ClientQuotas<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientQuotas")
+            .field("default", &self.default)
+            .field("limits", &self.limits)
+            .field("crowded", &self.crowded)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+impl<X: XConn> ClientQuotas<X> {
+    /// Create a new set of quotas using the given limit for all workspaces. If `default` is
+    /// `None` then only workspaces with a limit set using [ClientQuotas::with_limit] have a
+    /// quota.
+    pub fn new(default: Option<usize>) -> Self {
+        Self {
+            default,
+            limits: HashMap::new(),
+            crowded: HashSet::new(),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Set the limit for the workspace with the given tag, overriding the default.
+    pub fn with_limit(mut self, tag: impl Into<String>, limit: usize) -> Self {
+        self.limits.insert(tag.into(), limit);
+        self
+    }
+
+    /// The maximum number of clients that the workspace with the given tag should hold, if it
+    /// has a limit.
+    pub fn limit_for(&self, tag: &str) -> Option<usize> {
+        self.limits.get(tag).copied().or(self.default)
+    }
+
+    /// Register a hook to be run each time a workspace goes over its quota.
+    pub fn add_hook<H>(&mut self, hook: H)
+    where
+        H: QuotaHook<X> + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+    }
+
+    // Update the set of crowded workspaces from the current client counts, returning the
+    // crowded workspaces and those that have newly gone over their quota.
+    fn update<'a>(
+        &mut self,
+        counts: impl Iterator<Item = (&'a str, usize)>,
+    ) -> (CrowdedWorkspaces, Vec<(String, usize)>) {
+        let mut crowded = Vec::new();
+        let mut newly_crowded = Vec::new();
+
+        for (tag, n) in counts {
+            match self.limit_for(tag) {
+                Some(limit) if n > limit => (),
+                _ => continue,
+            }
+            if !self.crowded.contains(tag) {
+                newly_crowded.push((tag.to_string(), n));
+            }
+            crowded.push(tag.to_string());
+        }
+
+        self.crowded = crowded.iter().cloned().collect();
+
+        (CrowdedWorkspaces(crowded), newly_crowded)
+    }
+}
+
+/// Add the given [ClientQuotas] as a state extension along with a refresh hook that checks
+/// the number of clients on each workspace.
+pub fn add_client_quotas<X>(mut wm: WindowManager<X>, quotas: ClientQuotas<X>) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(quotas);
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+
+    wm
+}
+
+/// Register a hook to be run each time a workspace goes over its client quota.
+///
+/// Hooks only run once [add_client_quotas] has been called.
+pub fn on_quota_exceeded<X, H>(mut wm: WindowManager<X>, hook: H) -> WindowManager<X>
+where
+    X: XConn + 'static,
+    H: QuotaHook<X> + 'static,
+{
+    wm.state
+        .extension_or_default::<ClientQuotas<X>>()
+        .borrow_mut()
+        .add_hook(hook);
+
+    wm
+}
+
+/// Publish the current [CrowdedWorkspaces] and run any registered [QuotaHook]s for
+/// workspaces that have gone over their quota since the last refresh.
+pub fn refresh_hook<X>(state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    let quotas = state.extension::<ClientQuotas<X>>()?;
+    let counts = state
+        .client_set
+        .ordered_workspaces()
+        .map(|w| (w.tag(), w.clients().count()));
+    let (crowded, newly_crowded) = quotas.borrow_mut().update(counts);
+
+    if state.published::<CrowdedWorkspaces>() != Some(&crowded) {
+        state.publish(crowded);
+    }
+
+    if newly_crowded.is_empty() {
+        return Ok(());
+    }
+
+    let mut hooks = mem::take(&mut quotas.borrow_mut().hooks);
+    for (tag, n) in newly_crowded {
+        debug!(%tag, n, "workspace is over its client quota");
+        for h in hooks.iter_mut() {
+            if let Err(e) = h.call(&tag, n, state, x) {
+                error!(%e, "error returned from client quota hook");
+            }
+        }
+    }
+
+    // Keep any hooks that were registered while running the existing ones
+    let mut quotas = quotas.borrow_mut();
+    hooks.append(&mut quotas.hooks);
+    quotas.hooks = hooks;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::Config,
+        testing::{HeadlessConn, Scenario},
+    };
+    use simple_test_case::test_case;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test_case(None, Some(2), Some(2); "explicit limit")]
+    #[test_case(Some(5), Some(2), Some(2); "explicit limit overrides default")]
+    #[test_case(Some(5), None, Some(5); "default")]
+    #[test_case(None, None, None; "no limit")]
+    #[test]
+    fn limit_for(default: Option<usize>, limit: Option<usize>, expected: Option<usize>) {
+        let mut quotas: ClientQuotas<HeadlessConn> = ClientQuotas::new(default);
+        if let Some(limit) = limit {
+            quotas = quotas.with_limit("1", limit);
+        }
+
+        assert_eq!(quotas.limit_for("1"), expected);
+    }
+
+    #[test]
+    fn workspaces_are_only_newly_crowded_once() {
+        let mut quotas: ClientQuotas<HeadlessConn> = ClientQuotas::new(Some(2)).with_limit("2", 1);
+
+        let (crowded, newly) = quotas.update([("1", 3), ("2", 1)].into_iter());
+        assert_eq!(crowded, CrowdedWorkspaces(vec!["1".to_string()]));
+        assert_eq!(newly, vec![("1".to_string(), 3)]);
+
+        let (crowded, newly) = quotas.update([("1", 4), ("2", 2)].into_iter());
+        assert_eq!(crowded.0, vec!["1", "2"]);
+        assert_eq!(newly, vec![("2".to_string(), 2)]);
+
+        let (crowded, newly) = quotas.update([("1", 2), ("2", 2)].into_iter());
+        assert_eq!(crowded.0, vec!["2"]);
+        assert!(newly.is_empty());
+    }
+
+    #[test]
+    fn hooks_run_and_crowded_workspaces_are_published() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&calls);
+
+        let wm = WindowManager::new(
+            Config::default(),
+            HashMap::new(),
+            HashMap::new(),
+            HeadlessConn::default(),
+        )
+        .unwrap();
+        let wm = add_client_quotas(wm, ClientQuotas::new(Some(2)));
+        let wm = on_quota_exceeded(wm, move |tag: &str, n, _: &mut _, _: &_| {
+            seen.borrow_mut().push((tag.to_string(), n));
+            Ok(())
+        });
+
+        let s = Scenario::new(wm).unwrap().given_windows(["a", "b"]);
+        let crowded = |s: &Scenario| s.state().published::<CrowdedWorkspaces>().cloned();
+
+        assert_eq!(crowded(&s), Some(CrowdedWorkspaces::default()));
+        assert!(calls.borrow().is_empty());
+
+        let s = s.given_windows(["c", "d"]);
+
+        assert!(crowded(&s).unwrap().contains("1"));
+        assert_eq!(*calls.borrow(), vec![("1".to_string(), 3)]);
+
+        let s = s.close("c").close("d");
+
+        assert_eq!(crowded(&s), Some(CrowdedWorkspaces::default()));
+    }
+}
@@ -0,0 +1,257 @@
+//! Recovering the arrangement of clients after the window manager crashes.
+//!
+//! [add_crash_recovery] periodically writes a [StateSnapshot] of the workspaces, stack order,
+//! focus, layouts and floating positions of all clients to disk, along with writing the most
+//! recent snapshot if penrose panics. When the window manager is next started, any existing
+//! clients that are found are re-adopted using the saved snapshot rather than all being placed
+//! on the first workspace.
+//!
+//! On a clean shutdown the snapshot is written one final time if clients are being left running
+//! (see [Config::close_clients_on_shutdown][0]) so that they can be re-adopted by the next window
+//! manager to start. Otherwise it is removed.
+//!
+//! Snapshots are taken as part of the refresh hook, so a change to the state of the window
+//! manager is written to disk on the first refresh after `interval` has elapsed since the
+//! previous write.
+//!
+//!   [0]: crate::core::Config::close_clients_on_shutdown
+use crate::{
+    core::{State, StateSnapshot, WindowManager},
+    x::XConn,
+    Error, Result,
+};
+use std::{
+    fs, io, panic,
+    path::PathBuf,
+    sync::{Arc, Mutex, TryLockError},
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+// The latest snapshot along with when it was last written to disk.
+#[derive(Debug)]
+struct CrashRecovery {
+    path: PathBuf,
+    interval: Duration,
+    last_write: Option<Instant>,
+    written: Option<StateSnapshot>,
+    latest: Arc<Mutex<Option<StateSnapshot>>>,
+}
+
+impl CrashRecovery {
+    // Record the latest snapshot, returning whether or not it should be written to disk.
+    fn update(&mut self, snapshot: StateSnapshot, now: Instant) -> bool {
+        let due = match self.last_write {
+            Some(t) => now.duration_since(t) >= self.interval,
+            None => true,
+        };
+        let changed = self.written.as_ref() != Some(&snapshot);
+
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some(snapshot);
+        }
+
+        due && changed
+    }
+
+    fn write_latest(&mut self, now: Instant) {
+        let latest = match self.latest.lock() {
+            Ok(latest) => latest.clone(),
+            Err(_) => return,
+        };
+
+        if let Some(snapshot) = latest {
+            match snapshot.save(&self.path) {
+                Ok(()) => debug!(path = ?self.path, "saved state snapshot"),
+                Err(e) => warn!(%e, path = ?self.path, "unable to save state snapshot"),
+            }
+            self.last_write = Some(now);
+            self.written = Some(snapshot);
+        }
+    }
+}
+
+/// Periodically save a snapshot of the window manager state to the given path (and save it
+/// on panic) so that existing clients can be re-adopted following a crash.
+///
+/// If a snapshot already exists at `path` it is loaded and used to restore the arrangement
+/// of existing clients when [WindowManager::run] is called.
+pub fn add_crash_recovery<X>(
+    mut wm: WindowManager<X>,
+    path: impl Into<PathBuf>,
+    interval: Duration,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    let path = path.into();
+
+    match StateSnapshot::load(&path) {
+        Ok(snapshot) => {
+            info!(?path, "found state snapshot from previous session");
+            wm.state.add_extension(snapshot);
+        }
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => warn!(%e, ?path, "unable to load state snapshot"),
+    }
+
+    let latest = Arc::new(Mutex::new(None::<StateSnapshot>));
+    let (panic_latest, panic_path) = (Arc::clone(&latest), path.clone());
+    let prev = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // Blocking on the lock would deadlock if the panic happened while it was held
+        match panic_latest.try_lock() {
+            Ok(guard) => {
+                if let Some(snapshot) = guard.as_ref() {
+                    let _ = snapshot.save(&panic_path);
+                }
+            }
+            Err(TryLockError::WouldBlock) => {
+                warn!("state snapshot is locked: unable to save it before panicking")
+            }
+            Err(TryLockError::Poisoned(_)) => {
+                warn!("state snapshot lock is poisoned: unable to save it before panicking")
+            }
+        }
+        prev(info)
+    }));
+
+    wm.state.add_extension(CrashRecovery {
+        path,
+        interval,
+        last_write: None,
+        written: None,
+        latest,
+    });
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+    wm.state.config.compose_or_set_shutdown_hook(shutdown_hook);
+
+    wm
+}
+
+/// Record a snapshot of the current state, writing it to disk if it has changed and the
+/// snapshot interval has elapsed.
+pub fn refresh_hook<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+    let cr = match state.extension::<CrashRecovery>() {
+        Ok(cr) => cr,
+        Err(_) => return Ok(()),
+    };
+    let mut cr = cr.borrow_mut();
+
+    let now = Instant::now();
+    if cr.update(StateSnapshot::from_client_set(&state.client_set), now) {
+        cr.write_latest(now);
+    }
+
+    Ok(())
+}
+
+/// Save the current state if clients are being left running so that they can be re-adopted
+/// the next time the window manager starts, otherwise remove the saved snapshot so that it
+/// is not used.
+pub fn shutdown_hook<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+    let cr = match state.extension::<CrashRecovery>() {
+        Ok(cr) => cr,
+        Err(_) => return Ok(()),
+    };
+    let mut cr = cr.borrow_mut();
+
+    if !state.config.close_clients_on_shutdown {
+        let now = Instant::now();
+        cr.update(StateSnapshot::from_client_set(&state.client_set), now);
+        cr.write_latest(now);
+
+        return Ok(());
+    }
+
+    match fs::remove_file(&cr.path) {
+        Ok(()) => debug!(path = ?cr.path, "removed state snapshot"),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => warn!(%e, path = ?cr.path, "unable to remove state snapshot"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::Config, pure::test_xid_stack_set, x::StubXConn, Xid};
+    use simple_test_case::test_case;
+
+    #[test]
+    fn snapshots_are_written_when_changed_and_due() {
+        let path =
+            std::env::temp_dir().join(format!("penrose-crash-recovery-{}", std::process::id()));
+        let mut cr = CrashRecovery {
+            path: path.clone(),
+            interval: Duration::from_secs(5),
+            last_write: None,
+            written: None,
+            latest: Default::default(),
+        };
+        let mut cs = test_xid_stack_set(3, 1);
+        let t = Instant::now();
+
+        assert!(cr.update(StateSnapshot::from_client_set(&cs), t), "first");
+        cr.write_latest(t);
+        assert!(!cr.update(
+            StateSnapshot::from_client_set(&cs),
+            t + Duration::from_secs(6)
+        ));
+
+        cs.insert(Xid(1));
+        let snapshot = StateSnapshot::from_client_set(&cs);
+        assert!(
+            !cr.update(snapshot.clone(), t + Duration::from_secs(1)),
+            "not due"
+        );
+        assert_eq!(cr.latest.lock().unwrap().as_ref(), Some(&snapshot));
+        assert!(
+            cr.update(snapshot.clone(), t + Duration::from_secs(5)),
+            "due"
+        );
+        cr.write_latest(t + Duration::from_secs(5));
+
+        let saved = StateSnapshot::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved, snapshot);
+    }
+
+    #[test_case(false, true; "clients left running")]
+    #[test_case(true, false; "clients closed")]
+    #[test]
+    fn snapshots_are_kept_on_shutdown_if_clients_are_left_running(close: bool, kept: bool) {
+        let path = std::env::temp_dir().join(format!(
+            "penrose-crash-recovery-shutdown-{close}-{}",
+            std::process::id()
+        ));
+        let config = Config {
+            close_clients_on_shutdown: close,
+            ..Config::default()
+        };
+        let mut state = State::try_new(config, &StubXConn).unwrap();
+        state.client_set.insert(Xid(1));
+        state.add_extension(CrashRecovery {
+            path: path.clone(),
+            interval: Duration::from_secs(5),
+            last_write: None,
+            written: None,
+            latest: Default::default(),
+        });
+        StateSnapshot::from_client_set(&test_xid_stack_set(3, 1))
+            .save(&path)
+            .unwrap();
+
+        shutdown_hook(&mut state, &StubXConn).unwrap();
+        let saved = StateSnapshot::load(&path).ok();
+        let _ = fs::remove_file(&path);
+
+        let expected = StateSnapshot::from_client_set(&state.client_set);
+        assert_eq!(saved.is_some(), kept);
+        if kept {
+            assert_eq!(saved, Some(expected));
+        }
+    }
+}
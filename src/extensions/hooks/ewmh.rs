@@ -3,20 +3,53 @@
 //! The primary use of this extension is to provide support for external
 //! status bars / panels and fullscreen windows.
 //!
+//! Clients with `_NET_WM_STATE_SKIP_TASKBAR` set are left out of `_NET_CLIENT_LIST` and those
+//! with `_NET_WM_STATE_SKIP_PAGER` set are left out of `_NET_CLIENT_LIST_STACKING`, so that
+//! utility windows are not shown by external taskbars and pagers.
+//!
+//! Each desktop reports the origin of the screen it is shown on as its `_NET_DESKTOP_VIEWPORT`
+//! and the area of that screen left clear by the struts of any dock windows as its
+//! `_NET_WORKAREA`. Hidden desktops use the screen they belong to when running with per-screen
+//! workspaces and the focused screen otherwise. This allows bars such as polybar to show only
+//! the desktops for the monitor they are running on.
+//!
+//! Clients asking to be moved or resized using `_NET_WM_MOVERESIZE` (such as when dragging the
+//! header bar of a GTK application) are floated and then follow the pointer in the same way as
+//! when using a [MouseDragHandler][2] or [MouseResizeHandler][3], until the mouse button is
+//! released.
+//!
+//! Struts set by visible dock windows are tracked in the [Struts] state extension and the space
+//! they reserve is kept clear of tiled clients by the [StrutsHook] layout hook. Struts are
+//! re-read whenever a dock updates its `_NET_WM_STRUT` or `_NET_WM_STRUT_PARTIAL` properties
+//! so panels that start after penrose, change size or exit are handled automatically: there is
+//! no need to also reserve space for them using a [SpacingHook][0] or [ReservedSpace][1].
+//!
 //! See details of the spec here:
 //!   <https://specifications.freedesktop.org/wm-spec/wm-spec-latest.html>
+//!
+//!   [0]: crate::builtin::hooks::SpacingHook
+//!   [1]: crate::extensions::hooks::reserved_space::ReservedSpace
+//!   [2]: crate::builtin::actions::floating::MouseDragHandler
+//!   [3]: crate::builtin::actions::floating::MouseResizeHandler
 use crate::{
-    core::{ClientSet, Config, State},
-    extensions::actions::{set_fullscreen_state, FullScreenAction},
+    builtin::actions::floating::{
+        cancel_client_drag, handle_client_drag, start_client_drag, DragKind, Edges,
+    },
+    core::{hooks::LayoutHook, ClientSet, Config, State},
+    extensions::actions::{
+        exit_fullscreen_for_moved_clients, set_fullscreen_state, FullScreenAction,
+    },
+    pure::geometry::{Direction, Point, Rect},
     x::{
         atom::Atom,
-        event::{ClientMessage, ClientMessageData},
+        event::{ClientMessage, ClientMessageData, PropertyEvent},
         property::Prop,
-        XConn, XConnExt, XEvent,
+        FocusPolicy, XConn, XConnExt, XEvent,
     },
     Result, Xid,
 };
-use tracing::warn;
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, warn};
 
 /// The set of Atoms this extension adds support for.
 ///
@@ -25,18 +58,26 @@ pub const EWMH_SUPPORTED_ATOMS: &[Atom] = &[
     Atom::NetWmStateHidden,
     Atom::NetWmStateFullscreen,
     Atom::NetWmStateDemandsAttention,
+    Atom::NetWmStateMaximizedHorz,
+    Atom::NetWmStateMaximizedVert,
+    Atom::NetWmStateModal,
+    Atom::NetWmStateSkipPager,
+    Atom::NetWmStateSkipTaskbar,
     Atom::NetNumberOfDesktops,
     Atom::NetClientList,
     Atom::NetClientListStacking,
     Atom::NetCurrentDesktop,
     Atom::NetDesktopNames,
+    Atom::NetDesktopViewport,
+    Atom::NetWorkarea,
     Atom::NetActiveWindow,
+    Atom::NetCloseWindow,
     Atom::NetWmDesktop,
+    Atom::NetWmMoveresize,
     Atom::NetWmStrut,
+    Atom::NetWmStrutPartial,
     Atom::NetWmState,
     Atom::NetWmName,
-    // TODO: read up on how this works and implement
-    // Atom::NetDesktopViewport,
 ];
 
 /// The WM_NAME that will be set for the X server
@@ -53,6 +94,9 @@ where
     config.compose_or_set_startup_hook(startup_hook);
     config.compose_or_set_refresh_hook(refresh_hook);
     config.compose_or_set_event_hook(event_hook);
+    config.compose_or_set_manage_hook(manage_hook);
+    config.compose_or_set_layout_hook(StrutsHook);
+    config.compose_or_set_shutdown_hook(shutdown_hook);
 
     config
 }
@@ -79,19 +123,55 @@ pub fn startup_hook<X: XConn>(_state: &mut State<X>, x: &X) -> Result<()> {
     )
 }
 
+/// The properties set on the root window by this extension that are removed by [shutdown_hook].
+const EWMH_ROOT_PROPS: &[Atom] = &[
+    Atom::WmName,
+    Atom::NetSupported,
+    Atom::NetNumberOfDesktops,
+    Atom::NetDesktopNames,
+    Atom::NetCurrentDesktop,
+    Atom::NetDesktopViewport,
+    Atom::NetWorkarea,
+    Atom::NetClientList,
+    Atom::NetClientListStacking,
+    Atom::NetActiveWindow,
+];
+
+/// Remove the properties set on the root window by this extension so that they are not left
+/// behind once the window manager has exited.
+pub fn shutdown_hook<X: XConn>(_state: &mut State<X>, x: &X) -> Result<()> {
+    let root = x.root();
+
+    for atom in EWMH_ROOT_PROPS.iter() {
+        x.delete_prop(root, atom.as_ref())?;
+    }
+
+    Ok(())
+}
+
 /// Intercept messages from external applications and handle them.
 ///
+/// Changes to the struts of managed clients are also tracked, refreshing the X state if the
+/// space they reserve has changed, and mouse events are consumed while a client is being moved
+/// or resized at its own request.
+///
 /// Currently supports the following:
 ///   - _NET_CURRENT_DESKTOP :: switching between workspaces
 ///   - _NET_WM_DESKTOP      :: moving clients between workspaces
 ///   - _NET_ACTIVE_WINDOW   :: focus a new client and handle workspace switching
 ///   - _NET_CLOSE_WINDOW    :: closing a client window
-///   - _NET_WM_STATE        :: support for fullscreen windows
+///   - _NET_WM_MOVERESIZE   :: moving and resizing clients with the mouse
+///   - _NET_WM_STATE        :: support for fullscreen and urgent windows
 pub fn event_hook<X: XConn>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
     let ClientMessage {
         id, dtype, data, ..
     } = match event {
         XEvent::ClientMessage(m) => m,
+        XEvent::PropertyNotify(e) => {
+            handle_strut_change(e, state, x)?;
+            return Ok(true);
+        }
+        XEvent::MouseEvent(e) => return Ok(!handle_client_drag(e, state, x)?),
         _ => return Ok(true),
     };
 
@@ -104,30 +184,32 @@ pub fn event_hook<X: XConn>(event: &XEvent, state: &mut State<X>, x: &X) -> Resu
             }
         }
 
-        // Move the client receiving the message to its desired workspace
+        // Move the client receiving the message to its desired workspace. Requests to show a
+        // client on all desktops (0xFFFFFFFF) are not supported.
         "_NET_WM_DESKTOP" => {
             let tag = state.client_set.tag_for_workspace_id(data.as_usize()[0]);
             if let Some(tag) = tag {
-                x.modify_and_refresh(state, |cs| cs.move_client_to_tag(id, &tag))?;
+                if state.client_set.contains(id) {
+                    x.modify_and_refresh(state, |cs| cs.move_client_to_tag(id, &tag))?;
+                }
             }
         }
 
-        // If the request came from a pager, the first data element should be 2.
-        // For pager requests, set the active client (see docs linked at the top of
-        // this file for more details on the semantics of this message)
-        "_NET_ACTIVE_WINDOW" => {
-            if data.as_u32()[0] == 2 {
-                x.set_active_client(*id, state)?;
-            }
-        }
+        // Set the active client following the configured activation focus policy (see docs
+        // linked at the top of this file for more details on the semantics of this message)
+        "_NET_ACTIVE_WINDOW" => handle_active_window_message(*id, data, state, x)?,
 
-        // Attempt to remove the requested client
-        "_NET_CLOSE_WINDOW" => x.modify_and_refresh(state, |cs| {
-            cs.remove_client(id);
-        })?,
+        // Ask the requested client to close: it is removed once it has exited
+        "_NET_CLOSE_WINDOW" if state.client_set.contains(id) => x.kill_client(*id, state)?,
 
-        // Handle clients that want fullscreen behaviour
-        "_NET_WM_STATE" => handle_fullscreen_message(*id, data, state, x)?,
+        // Float the requested client and move or resize it as the pointer moves
+        "_NET_WM_MOVERESIZE" => handle_moveresize_message(*id, data, state, x)?,
+
+        // Handle clients that want fullscreen behaviour or are demanding attention
+        "_NET_WM_STATE" => {
+            handle_fullscreen_message(*id, data, state, x)?;
+            handle_demands_attention_message(*id, data, state, x)?;
+        }
 
         // Leave other client messages for the default event handling
         _ => (),
@@ -136,6 +218,75 @@ pub fn event_hook<X: XConn>(event: &XEvent, state: &mut State<X>, x: &X) -> Resu
     Ok(true)
 }
 
+// If the request came from a pager then the first data element should be 2: these requests
+// are made on behalf of the user so they are always honoured. Requests from applications
+// are subject to Config::activation_focus.
+fn handle_active_window_message<X: XConn>(
+    id: Xid,
+    data: &ClientMessageData,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let from_pager = data.as_u32().first() == Some(&2);
+    let policy = state.config.activation_focus;
+    let cs = &state.client_set;
+    let on_current_workspace = cs.tag_for_client(&id) == Some(cs.current_tag());
+
+    if from_pager || policy.allows_focus(on_current_workspace) {
+        x.set_active_client(id, state)
+    } else if policy == FocusPolicy::MarkUrgent {
+        x.set_client_urgency(id, true, state)
+    } else {
+        debug!(%id, ?policy, "ignoring request to activate client");
+        Ok(())
+    }
+}
+
+// The data for the message is [x_root, y_root, direction, button, source] where direction is
+// the edge or corner of the client being dragged (0-7, clockwise from the top left), a move (8),
+// a move or resize using the keyboard (9, 10) or a request to cancel the current drag (11).
+fn handle_moveresize_message<X: XConn>(
+    id: Xid,
+    data: &ClientMessageData,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let data32 = data.as_u32();
+    let (start, direction) = (Point::new(data32[0], data32[1]), data32[2]);
+
+    let resize = |left, top, right, bottom| {
+        DragKind::Resize(Edges {
+            left,
+            top,
+            right,
+            bottom,
+        })
+    };
+
+    let kind = match direction {
+        0 => resize(true, true, false, false),
+        1 => resize(false, true, false, false),
+        2 => resize(false, true, true, false),
+        3 => resize(false, false, true, false),
+        4 => resize(false, false, true, true),
+        5 => resize(false, false, false, true),
+        6 => resize(true, false, false, true),
+        7 => resize(true, false, false, false),
+        8 => DragKind::Move,
+        9 | 10 => {
+            debug!(%id, "ignoring request for keyboard driven move/resize");
+            return Ok(());
+        }
+        11 => return cancel_client_drag(state, x),
+        _ => {
+            warn!(%direction, "invalid direction in _NET_WM_MOVERESIZE message");
+            return Ok(());
+        }
+    };
+
+    start_client_drag(id, kind, start, state, x)
+}
+
 fn handle_fullscreen_message<X: XConn>(
     id: Xid,
     data: &ClientMessageData,
@@ -169,15 +320,51 @@ fn handle_fullscreen_message<X: XConn>(
     set_fullscreen_state(id, action, state, x)
 }
 
-/// Notify external clients of the current status of workspaces and clients
+fn handle_demands_attention_message<X: XConn>(
+    id: Xid,
+    data: &ClientMessageData,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let mut data32 = data.as_u32();
+    if data32.is_empty() {
+        return Ok(()); // already warned about in handle_fullscreen_message
+    }
+
+    let demands_attention = x.intern_atom(Atom::NetWmStateDemandsAttention.as_ref())?;
+    let raw_action = data32.remove(0);
+
+    if !data32.contains(&demands_attention) {
+        return Ok(());
+    }
+
+    let urgent = match raw_action {
+        0 => false,
+        1 => true,
+        2 => !state.urgent_clients().contains(&id),
+        action => {
+            warn!(%action, "invalid demands attention action: expected 0, 1 or 2");
+            return Ok(());
+        }
+    };
+
+    x.set_client_urgency(id, urgent, state)
+}
+
+/// Notify external clients of the current status of workspaces and clients.
+///
+/// Fullscreen clients that have been moved to a different workspace are also returned
+/// to their previous (non-fullscreen) position.
 pub fn refresh_hook<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
     set_known_desktops(&state.client_set, x)?;
-    set_known_clients(&state.client_set, x)?;
+    set_known_clients(state, x)?;
     set_current_desktop(&state.client_set, x)?;
+    set_desktop_viewports(&state.client_set, x)?;
+    forget_removed_struts(state);
+    set_workareas(state, x)?;
     set_client_desktops(&state.client_set, x)?;
     set_active_client(&state.client_set, x)?;
-
-    // TODO: set desktop viewport
+    exit_fullscreen_for_moved_clients(state, x)?;
 
     Ok(())
 }
@@ -201,26 +388,273 @@ where
     )
 }
 
-fn set_known_clients<X>(cs: &ClientSet, x: &X) -> Result<()>
+fn set_known_clients<X>(state: &State<X>, x: &X) -> Result<()>
 where
     X: XConn,
 {
-    // FIXME: this currently isn't in stacking order
-    let ordered_clients: Vec<Xid> = cs.clients().copied().collect();
+    let all: Vec<Xid> = state.client_set.clients().copied().collect();
+    let listed = |ids: Vec<Xid>, skip: fn(&X, Xid) -> bool| -> Vec<Xid> {
+        ids.into_iter().filter(|&id| !skip(x, id)).collect()
+    };
+
+    let stacking = listed(
+        stacking_order(&all, &state.diff.after.positions),
+        X::skips_pager,
+    );
+    let clients = listed(all, X::skips_taskbar);
 
     x.set_prop(
         x.root(),
         Atom::NetClientList.as_ref(),
-        Prop::Window(ordered_clients.clone()),
+        Prop::Window(clients),
     )?;
 
     x.set_prop(
         x.root(),
         Atom::NetClientListStacking.as_ref(),
-        Prop::Window(ordered_clients),
+        Prop::Window(stacking),
+    )
+}
+
+// Clients ordered from bottom to top: hidden clients are below all visible clients, which are
+// stacked in the order they were positioned during the last refresh.
+fn stacking_order(clients: &[Xid], positions: &[(Xid, Rect)]) -> Vec<Xid> {
+    let known: HashSet<Xid> = clients.iter().copied().collect();
+    let visible: Vec<Xid> = positions
+        .iter()
+        .map(|&(id, _)| id)
+        .filter(|id| known.contains(id))
+        .collect();
+
+    clients
+        .iter()
+        .filter(|id| !visible.contains(id))
+        .copied()
+        .chain(visible.iter().copied())
+        .collect()
+}
+
+// The screen each desktop is shown on, falling back to the screen that owns it for hidden
+// per-screen workspaces and the focused screen for other hidden workspaces.
+fn desktop_screens(cs: &ClientSet) -> Vec<Rect> {
+    let screen_rect = |index: usize| cs.screens().find(|s| s.index() == index).map(|s| s.r);
+
+    cs.ordered_tags()
+        .iter()
+        .map(|tag| {
+            cs.screens()
+                .find(|s| &s.workspace.tag == tag)
+                .map(|s| s.r)
+                .or_else(|| cs.screen_for_tag(tag).and_then(screen_rect))
+                .unwrap_or(cs.screens.focus.r)
+        })
+        .collect()
+}
+
+fn set_desktop_viewports<X>(cs: &ClientSet, x: &X) -> Result<()>
+where
+    X: XConn,
+{
+    let viewports = desktop_screens(cs)
+        .into_iter()
+        .flat_map(|r| [r.x, r.y])
+        .collect();
+
+    x.set_prop(
+        x.root(),
+        Atom::NetDesktopViewport.as_ref(),
+        Prop::Cardinal(viewports),
     )
 }
 
+fn set_workareas<X>(state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn,
+{
+    let struts = state.extension_or_default::<Struts>();
+    let struts = struts.borrow();
+    let cs = &state.client_set;
+
+    let workareas = desktop_screens(cs)
+        .into_iter()
+        .flat_map(|r| {
+            let a = struts.available(cs, r);
+            [a.x, a.y, a.w, a.h]
+        })
+        .collect();
+
+    x.set_prop(
+        x.root(),
+        Atom::NetWorkarea.as_ref(),
+        Prop::Cardinal(workareas),
+    )
+}
+
+/// The struts set by managed dock windows, reserving space along the edges of the screen.
+///
+/// This is maintained as a [State] extension by the hooks added in [add_ewmh_hooks]. Only the
+/// struts of docks that are currently visible (either floating or configured as bars) reserve
+/// any space.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Struts {
+    struts: HashMap<Xid, Vec<u32>>,
+}
+
+impl Struts {
+    /// The raw `_NET_WM_STRUT_PARTIAL` (or `_NET_WM_STRUT`) values set by the given client.
+    pub fn strut(&self, id: Xid) -> Option<&[u32]> {
+        self.struts.get(&id).map(|vals| vals.as_slice())
+    }
+
+    /// The regions of the root window currently reserved by visible docks, along with the
+    /// edge of the screen each is attached to.
+    pub fn reserved(&self, cs: &ClientSet) -> Vec<(Direction, Rect)> {
+        let root = cs.screens().fold(Rect::default(), |root, s| {
+            let (w, h) = (s.r.x + s.r.w, s.r.y + s.r.h);
+            Rect::new(0, 0, root.w.max(w), root.h.max(h))
+        });
+
+        self.struts
+            .iter()
+            .filter(|(id, _)| cs.floating.contains_key(id) || cs.bars.contains_key(id))
+            .filter(|(id, _)| cs.screen_for_client(id).is_some())
+            .flat_map(|(_, strut)| strut_regions(strut, root))
+            .collect()
+    }
+
+    /// The part of `r` that is not reserved by the struts of any visible docks.
+    pub fn available(&self, cs: &ClientSet, r: Rect) -> Rect {
+        workarea(r, &self.reserved(cs))
+    }
+
+    // Re-read the strut of the given client, returning whether or not it has changed.
+    fn update<X: XConn>(&mut self, id: Xid, x: &X) -> Result<bool> {
+        match read_strut(id, x)? {
+            Some(strut) => Ok(self.struts.insert(id, strut.clone()).as_ref() != Some(&strut)),
+            None => Ok(self.struts.remove(&id).is_some()),
+        }
+    }
+}
+
+/// A [LayoutHook] that keeps tiled clients out of the space reserved by the [Struts] of
+/// visible dock windows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StrutsHook;
+
+impl<X: XConn> LayoutHook<X> for StrutsHook {
+    fn transform_initial(&mut self, r: Rect, state: &State<X>, _: &X) -> Rect {
+        match state.extension::<Struts>() {
+            Ok(struts) => struts.borrow().available(&state.client_set, r),
+            Err(_) => r,
+        }
+    }
+}
+
+/// Record the strut of newly managed clients so that the space it reserves is left clear.
+pub fn manage_hook<X: XConn>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    let struts = state.extension_or_default::<Struts>();
+    struts.borrow_mut().update(id, x)?;
+
+    Ok(())
+}
+
+// Docks that have exited are no longer in the client set but will not have removed their struts
+fn forget_removed_struts<X: XConn>(state: &mut State<X>) {
+    let struts = state.extension_or_default::<Struts>();
+    let cs = &state.client_set;
+    struts.borrow_mut().struts.retain(|id, _| cs.contains(id));
+}
+
+fn handle_strut_change<X: XConn>(e: &PropertyEvent, state: &mut State<X>, x: &X) -> Result<()> {
+    let is_strut = [Atom::NetWmStrut, Atom::NetWmStrutPartial]
+        .iter()
+        .any(|a| a.as_ref() == e.atom);
+
+    if e.is_root || !is_strut || !state.client_set.contains(&e.id) {
+        return Ok(());
+    }
+
+    let struts = state.extension_or_default::<Struts>();
+    let changed = struts.borrow_mut().update(e.id, x)?;
+    if changed {
+        debug!(id = %e.id, "strut changed: refreshing");
+        x.refresh(state)?;
+    }
+
+    Ok(())
+}
+
+// _NET_WM_STRUT_PARTIAL takes precedence over _NET_WM_STRUT when both are set.
+fn read_strut<X: XConn>(id: Xid, x: &X) -> Result<Option<Vec<u32>>> {
+    for atom in [Atom::NetWmStrutPartial, Atom::NetWmStrut] {
+        if let Some(Prop::Cardinal(vals)) = x.get_prop(id, atom.as_ref())? {
+            return Ok(Some(vals));
+        }
+    }
+
+    Ok(None)
+}
+
+// The regions of the root window reserved by a strut, along with the edge each is attached to.
+// Plain _NET_WM_STRUT values reserve the full length of their edge.
+fn strut_regions(strut: &[u32], root: Rect) -> Vec<(Direction, Rect)> {
+    if strut.len() < 4 {
+        warn!(?strut, "malformed strut");
+        return vec![];
+    }
+
+    let (w, h) = (root.w, root.h);
+    let range = |i: usize, max: u32| match strut.get(i..i + 2) {
+        Some(&[start, end]) if strut.len() >= 12 && end >= start => (start, end - start + 1),
+        _ => (0, max),
+    };
+
+    let (ly, lh) = range(4, h);
+    let (ry, rh) = range(6, h);
+    let (tx, tw) = range(8, w);
+    let (bx, bw) = range(10, w);
+
+    let regions = [
+        (Direction::Left, Rect::new(0, ly, strut[0], lh)),
+        (
+            Direction::Right,
+            Rect::new(w.saturating_sub(strut[1]), ry, strut[1], rh),
+        ),
+        (Direction::Up, Rect::new(tx, 0, tw, strut[2])),
+        (
+            Direction::Down,
+            Rect::new(bx, h.saturating_sub(strut[3]), bw, strut[3]),
+        ),
+    ];
+
+    regions
+        .into_iter()
+        .filter(|(_, r)| r.w > 0 && r.h > 0)
+        .collect()
+}
+
+// The area of the screen `r` that is not covered by any of the reserved regions along its
+// edges.
+fn workarea(r: Rect, reserved: &[(Direction, Rect)]) -> Rect {
+    let (mut x0, mut y0, mut x1, mut y1) = (r.x, r.y, r.x + r.w, r.y + r.h);
+
+    for &(edge, s) in reserved {
+        let overlaps = s.x < r.x + r.w && r.x < s.x + s.w && s.y < r.y + r.h && r.y < s.y + s.h;
+        if !overlaps {
+            continue;
+        }
+
+        match edge {
+            Direction::Left => x0 = x0.max(s.x + s.w),
+            Direction::Right => x1 = x1.min(s.x),
+            Direction::Up => y0 = y0.max(s.y + s.h),
+            Direction::Down => y1 = y1.min(s.y),
+        }
+    }
+
+    Rect::new(x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0))
+}
+
 fn set_current_desktop<X>(cs: &ClientSet, x: &X) -> Result<()>
 where
     X: XConn,
@@ -269,3 +703,353 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{
+            bindings::{MouseEvent, MouseEventKind, MouseState},
+            WindowManager,
+        },
+        extensions::actions::FullscreenStatus,
+        testing::{HeadlessConn, Scenario},
+        x::event::ClientEventMask,
+    };
+    use simple_test_case::test_case;
+    use std::collections::HashMap;
+    use Direction::*;
+
+    const ROOT: Rect = Rect::new(0, 0, 1920, 1080);
+
+    #[test_case(&[0, 0, 30, 0], vec![(Up, Rect::new(0, 0, 1920, 30))]; "top strut")]
+    #[test_case(&[20, 10, 0, 0], vec![(Left, Rect::new(0, 0, 20, 1080)), (Right, Rect::new(1910, 0, 10, 1080))]; "left and right")]
+    #[test_case(&[0, 0, 0, 25], vec![(Down, Rect::new(0, 1055, 1920, 25))]; "bottom strut")]
+    #[test_case(&[0, 0, 30, 0, 0, 0, 0, 0, 100, 599, 0, 0], vec![(Up, Rect::new(100, 0, 500, 30))]; "partial top strut")]
+    #[test_case(&[0, 0, 0, 0], vec![]; "empty strut")]
+    #[test_case(&[30, 0], vec![]; "malformed strut")]
+    #[test]
+    fn strut_regions_works(strut: &[u32], expected: Vec<(Direction, Rect)>) {
+        assert_eq!(strut_regions(strut, ROOT), expected);
+    }
+
+    #[test_case(vec![], ROOT; "nothing reserved")]
+    #[test_case(vec![(Up, Rect::new(0, 0, 1920, 30))], Rect::new(0, 30, 1920, 1050); "top")]
+    #[test_case(
+        vec![(Left, Rect::new(0, 0, 20, 1080)), (Down, Rect::new(0, 1055, 1920, 25))],
+        Rect::new(20, 0, 1900, 1055);
+        "left and bottom"
+    )]
+    #[test_case(vec![(Up, Rect::new(2000, 0, 500, 30))], ROOT; "other screen")]
+    #[test]
+    fn workarea_works(reserved: Vec<(Direction, Rect)>, expected: Rect) {
+        assert_eq!(workarea(ROOT, &reserved), expected);
+    }
+
+    #[test]
+    fn stacking_order_puts_hidden_clients_first() {
+        let clients = [Xid(1), Xid(2), Xid(3), Xid(4)];
+        let positions = [(Xid(4), ROOT), (Xid(99), ROOT), (Xid(2), ROOT)];
+
+        let order = stacking_order(&clients, &positions);
+
+        assert_eq!(order, vec![Xid(1), Xid(3), Xid(4), Xid(2)]);
+    }
+
+    fn ewmh_scenario(screens: Vec<Rect>) -> Scenario {
+        let mut config = add_ewmh_hooks(Config::default());
+        config.floating_classes.push("polybar".to_string());
+        let conn = HeadlessConn::new(screens);
+        let wm = WindowManager::new(config, HashMap::new(), HashMap::new(), conn)
+            .expect("valid window manager");
+
+        Scenario::new(wm).unwrap()
+    }
+
+    fn root_prop(s: &Scenario, atom: Atom) -> Option<Prop> {
+        s.conn().get_prop(s.state().root(), atom.as_ref()).unwrap()
+    }
+
+    fn open_dock(s: Scenario, strut: Atom, vals: Vec<u32>) -> (Scenario, Xid) {
+        let id = s.conn().create_window("polybar");
+        let dock = Prop::Atom(vec![Atom::NetWindowTypeDock.as_ref().to_string()]);
+        s.conn()
+            .set_prop(id, Atom::NetWmWindowType.as_ref(), dock)
+            .unwrap();
+        s.conn()
+            .set_prop(id, strut.as_ref(), Prop::Cardinal(vals))
+            .unwrap();
+
+        (s.send(XEvent::MapRequest(id)), id)
+    }
+
+    fn set_strut(s: Scenario, id: Xid, vals: Vec<u32>) -> Scenario {
+        let atom = Atom::NetWmStrutPartial.as_ref();
+        s.conn().set_prop(id, atom, Prop::Cardinal(vals)).unwrap();
+
+        s.send(XEvent::PropertyNotify(PropertyEvent {
+            id,
+            atom: atom.to_string(),
+            is_root: false,
+        }))
+    }
+
+    fn geometry(s: &Scenario, name: &str) -> Rect {
+        s.conn().window(s.id(name)).unwrap().geometry
+    }
+
+    fn client_message(id: Xid, dtype: &str, data: [u32; 5]) -> XEvent {
+        XEvent::ClientMessage(ClientMessage::new(
+            id,
+            ClientEventMask::NoEventMask,
+            dtype,
+            data.into(),
+        ))
+    }
+
+    #[test]
+    fn floating_clients_are_stacked_above_tiled_clients() {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["dmenu", "a", "b"]);
+        let (dmenu, a, b) = (s.id("dmenu"), s.id("a"), s.id("b"));
+
+        let stacking = root_prop(&s, Atom::NetClientListStacking);
+
+        match stacking {
+            Some(Prop::Window(ids)) => {
+                assert_eq!(ids.len(), 3);
+                assert!(ids.contains(&a) && ids.contains(&b));
+                assert_eq!(ids.last(), Some(&dmenu));
+            }
+            p => panic!("unexpected _NET_CLIENT_LIST_STACKING: {p:?}"),
+        }
+    }
+
+    #[test]
+    fn desktop_viewports_follow_screens() {
+        let screens = vec![ROOT, Rect::new(1920, 0, 1280, 1024)];
+        let s = ewmh_scenario(screens);
+
+        let mut expected = vec![0, 0, 1920, 0];
+        expected.extend([0, 0].repeat(7));
+
+        assert_eq!(
+            root_prop(&s, Atom::NetDesktopViewport),
+            Some(Prop::Cardinal(expected))
+        );
+    }
+
+    #[test_case(Atom::NetWmStrut, vec![0, 0, 30, 0], Rect::new(1920, 30, 1280, 994); "full width strut")]
+    #[test_case(Atom::NetWmStrutPartial, vec![0, 0, 30, 0, 0, 0, 0, 0, 0, 1919, 0, 0], Rect::new(1920, 0, 1280, 1024); "partial strut")]
+    #[test]
+    fn workareas_account_for_dock_struts(strut: Atom, vals: Vec<u32>, second: Rect) {
+        let screens = vec![ROOT, Rect::new(1920, 0, 1280, 1024)];
+        let (s, _) = open_dock(ewmh_scenario(screens), strut, vals);
+
+        let first = Rect::new(0, 30, 1920, 1050);
+        let mut expected = vec![first.x, first.y, first.w, first.h];
+        expected.extend([second.x, second.y, second.w, second.h]);
+        expected.extend([first.x, first.y, first.w, first.h].repeat(7));
+
+        assert_eq!(
+            root_prop(&s, Atom::NetWorkarea),
+            Some(Prop::Cardinal(expected))
+        );
+    }
+
+    #[test]
+    fn close_window_messages_kill_the_client() {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a", "b"]);
+        let a = s.id("a");
+
+        s.send(client_message(a, "_NET_CLOSE_WINDOW", [0; 5]))
+            .expect_closed("a")
+            .expect_focus("b");
+    }
+
+    fn moveresize(s: Scenario, name: &str, direction: u32) -> (Scenario, Rect) {
+        let id = s.id(name);
+        let r = geometry(&s, name);
+        let data = [r.x + 10, r.y + 200, direction, 1, 1];
+
+        (s.send(client_message(id, "_NET_WM_MOVERESIZE", data)), r)
+    }
+
+    // Events are built in the same way as they are converted from X events: motion events have
+    // no button of their own and none of the events have any modifiers held.
+    fn pointer(s: Scenario, kind: MouseEventKind, p: Point) -> Scenario {
+        let detail = match kind {
+            MouseEventKind::Motion => 5,
+            _ => 1,
+        };
+        let ms = MouseState::from_detail_and_state(detail, 0).expect("valid mouse state");
+        let root = s.state().root();
+        let (x, y) = (p.x as i16, p.y as i16);
+
+        s.send(XEvent::MouseEvent(MouseEvent::new(
+            root, x, y, x, y, ms, kind,
+        )))
+    }
+
+    fn floating(s: &Scenario, name: &str) -> Option<Rect> {
+        let r_s = s.state().client_set.screens.focus.r;
+        let floating = &s.state().client_set.floating;
+
+        floating.get(&s.id(name)).map(|rr| rr.applied_to(&r_s))
+    }
+
+    // Pointer motion is 100px in each direction: upwards when moving the bottom edge to keep
+    // the client on the screen.
+    #[test_case(8, 1, (100, 100, 0, 0); "move client")]
+    #[test_case(0, 1, (100, 100, -100, -100); "top left")]
+    #[test_case(1, 1, (0, 100, 0, -100); "top")]
+    #[test_case(4, -1, (0, 0, 100, -100); "bottom right")]
+    #[test_case(6, -1, (100, 0, -100, -100); "bottom left")]
+    #[test_case(7, 1, (100, 0, -100, 0); "left")]
+    #[test]
+    fn moveresize_messages_drag_the_client(
+        direction: u32,
+        sign: i32,
+        deltas: (i32, i32, i32, i32),
+    ) {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a", "b"]);
+        let (s, r) = moveresize(s, "a", direction);
+
+        assert!(s.conn().pointer_grabbed());
+        assert_eq!(floating(&s, "a"), Some(r));
+
+        let (dx, dy, dw, dh) = deltas;
+        let mut expected = r;
+        expected.reposition(dx, dy);
+        expected.resize(dw, dh);
+
+        let p = Point::new(r.x + 110, (r.y as i32 + 200 + sign * 100) as u32);
+        let s = pointer(s, MouseEventKind::Motion, p);
+        assert_eq!(floating(&s, "a"), Some(expected));
+
+        let s = pointer(s, MouseEventKind::Release, p);
+        assert!(!s.conn().pointer_grabbed());
+
+        let s = pointer(s, MouseEventKind::Motion, Point::new(r.x + 300, r.y + 400));
+        assert_eq!(floating(&s, "a"), Some(expected));
+    }
+
+    #[test]
+    fn moveresize_drags_do_not_warp_the_pointer() {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a", "b"]);
+        let before = s.conn().cursor_position().unwrap();
+        let (s, r) = moveresize(s, "a", 8);
+
+        let s = pointer(s, MouseEventKind::Motion, Point::new(r.x + 110, r.y + 300));
+        let s = pointer(s, MouseEventKind::Release, Point::new(r.x + 110, r.y + 300));
+
+        assert_ne!(floating(&s, "a"), Some(r));
+        assert_eq!(s.conn().cursor_position().unwrap(), before);
+    }
+
+    #[test]
+    fn moveresize_cancel_messages_end_the_drag() {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a", "b"]);
+        let (s, r) = moveresize(s, "a", 8);
+        let (s, _) = moveresize(s, "a", 11);
+
+        assert!(!s.conn().pointer_grabbed());
+
+        let s = pointer(s, MouseEventKind::Motion, Point::new(r.x + 110, r.y + 300));
+        assert_eq!(floating(&s, "a"), Some(r));
+    }
+
+    #[test_case(9; "keyboard size")]
+    #[test_case(10; "keyboard move")]
+    #[test_case(12; "invalid direction")]
+    #[test]
+    fn unsupported_moveresize_messages_are_ignored(direction: u32) {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a", "b"]);
+        let (s, _) = moveresize(s, "a", direction);
+
+        assert!(!s.conn().pointer_grabbed());
+        assert_eq!(floating(&s, "a"), None);
+    }
+
+    #[test]
+    fn fullscreen_status_of_the_focused_client_is_published() {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a", "b"]);
+        let (a, b) = (s.id("a"), s.id("b"));
+        let fs = *s
+            .conn()
+            .intern_atom(Atom::NetWmStateFullscreen.as_ref())
+            .unwrap();
+        let status = |s: &Scenario| s.state().published::<FullscreenStatus>().copied();
+
+        let s = s.send(client_message(b, "_NET_WM_STATE", [1, fs, 0, 0, 0]));
+        assert_eq!(status(&s).map(|st| st.fullscreen), Some(true));
+
+        let s = s.act(|state, _| {
+            state.client_set.focus_client(&a);
+            Ok(())
+        });
+        assert_eq!(status(&s).map(|st| st.fullscreen), Some(false));
+    }
+
+    #[test]
+    fn wm_desktop_messages_move_the_client() {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a", "b"]);
+        let a = s.id("a");
+
+        let s = s
+            .send(client_message(a, "_NET_WM_DESKTOP", [2, 0, 0, 0, 0]))
+            .expect_on_workspace("a", "3")
+            .expect_workspace("1");
+
+        assert_eq!(
+            s.conn().get_prop(a, Atom::NetWmDesktop.as_ref()).unwrap(),
+            Some(Prop::Cardinal(vec![2]))
+        );
+    }
+
+    #[test]
+    fn tiled_clients_follow_dock_struts() {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a"]);
+        let full = geometry(&s, "a");
+        let shrink = |px: u32| Rect::new(full.x, full.y + px, full.w, full.h - px);
+        let top = |px| vec![0, 0, px, 0, 0, 0, 0, 0, 0, 1919, 0, 0];
+        let strut = |s: &Scenario, id| {
+            let struts = s.state().extension::<Struts>().unwrap();
+            let strut = struts.borrow().strut(id).map(|vals| vals.to_vec());
+            strut
+        };
+
+        // A panel that sets its strut after being mapped
+        let s = s.open_window("panel", "polybar");
+        let panel = s.id("panel");
+        assert_eq!(geometry(&s, "a"), full);
+
+        let s = set_strut(s, panel, top(30));
+        assert_eq!(geometry(&s, "a"), shrink(30));
+
+        // Resizing the panel
+        let s = set_strut(s, panel, top(50));
+        assert_eq!(geometry(&s, "a"), shrink(50));
+        assert_eq!(strut(&s, panel), Some(top(50)));
+
+        // The panel exiting
+        let s = s.close("panel");
+        assert_eq!(geometry(&s, "a"), full);
+        assert_eq!(strut(&s, panel), None);
+    }
+
+    #[test]
+    fn struts_of_hidden_docks_are_ignored() {
+        let s = ewmh_scenario(vec![ROOT]).given_windows(["a"]);
+        let full = geometry(&s, "a");
+
+        let (s, dock) = open_dock(s, Atom::NetWmStrut, vec![0, 0, 30, 0]);
+        assert_eq!(geometry(&s, "a").y, full.y + 30);
+
+        let s = s.act(|state, _| {
+            state.client_set.move_client_to_tag(&dock, "2");
+            Ok(())
+        });
+
+        assert_eq!(geometry(&s, "a"), full);
+    }
+}
@@ -0,0 +1,401 @@
+//! A journal of window activity for lightweight time tracking.
+//!
+//! [add_activity_journal] appends an entry to a plain text file each time focus moves to a
+//! different client and each time the focused workspace changes. Entries are written as part
+//! of the refresh hook, one per line with tab separated fields, starting with the unix
+//! timestamp (in seconds) of the change (shown here with the tabs expanded):
+//!
+//! ```text
+//! 1700000000  workspace  2
+//! 1700000000  focus      firefox    Penrose - Mozilla Firefox
+//! 1700000754  focus      Alacritty  ~/repos/penrose
+//! 1700001020  focus
+//! ```
+//!
+//! A `focus` entry without a class and title marks a point where no client was focused,
+//! including when the window manager exits. The journal is never truncated so it is up to
+//! you to rotate or remove it as you see fit.
+//!
+//! [show_focus_time_today] is a key binding action that uses `notify-send` to display how long
+//! each application (identified by the class from its `WM_CLASS` property) has held focus
+//! since midnight.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! use penrose::extensions::hooks::journal::add_activity_journal;
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! add_activity_journal(wm, "/home/me/.local/share/penrose/journal.tsv")
+//! # }
+//! ```
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State, WindowManager},
+    extensions::util::notify_send,
+    util::helper_output_with_args,
+    x::{atom::Atom, property::Prop, XConn, XConnExt},
+    Result, Xid,
+};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, warn};
+
+/// A single change recorded in the activity journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEntry {
+    /// Focus moved to a client with the given class and title, or to no client at all if
+    /// `class` is empty.
+    Focus {
+        /// When focus changed (seconds since the unix epoch)
+        at: u64,
+        /// The class of the newly focused client
+        class: String,
+        /// The title of the newly focused client
+        title: String,
+    },
+    /// The workspace with the given tag was focused.
+    Workspace {
+        /// When the workspace was focused (seconds since the unix epoch)
+        at: u64,
+        /// The tag of the newly focused workspace
+        tag: String,
+    },
+}
+
+impl JournalEntry {
+    /// When this change happened (seconds since the unix epoch).
+    pub fn at(&self) -> u64 {
+        match self {
+            Self::Focus { at, .. } | Self::Workspace { at, .. } => *at,
+        }
+    }
+
+    /// Parse a single line of the journal, returning `None` if it is malformed.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let at = fields.next()?.parse().ok()?;
+
+        match fields.next()? {
+            "focus" => Some(Self::Focus {
+                at,
+                class: fields.next().unwrap_or_default().to_string(),
+                title: fields.next().unwrap_or_default().to_string(),
+            }),
+            "workspace" => Some(Self::Workspace {
+                at,
+                tag: fields.next()?.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Render this entry as a single line of the journal (without a trailing newline).
+    pub fn to_line(&self) -> String {
+        match self {
+            Self::Focus { at, class, .. } if class.is_empty() => format!("{at}\tfocus"),
+            Self::Focus { at, class, title } => {
+                format!("{at}\tfocus\t{}\t{}", sanitize(class), sanitize(title))
+            }
+            Self::Workspace { at, tag } => format!("{at}\tworkspace\t{}", sanitize(tag)),
+        }
+    }
+}
+
+// Tabs and newlines would break the line based format of the journal
+fn sanitize(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+/// State extension tracking the file used for the activity journal along with the last
+/// recorded client and workspace so that only changes are written.
+#[derive(Debug)]
+pub struct ActivityJournal {
+    path: PathBuf,
+    last_client: Option<Xid>,
+    last_tag: Option<String>,
+}
+
+impl ActivityJournal {
+    /// The path of the journal file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read and parse all of the entries in the journal, skipping any malformed lines.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>> {
+        let raw = fs::read_to_string(&self.path)?;
+
+        Ok(raw.lines().filter_map(JournalEntry::parse).collect())
+    }
+
+    fn append(&self, entries: &[JournalEntry]) {
+        let res = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| {
+                let lines: String = entries.iter().map(|e| e.to_line() + "\n").collect();
+                f.write_all(lines.as_bytes())
+            });
+
+        match res {
+            Ok(()) => debug!(n = entries.len(), "appended activity journal entries"),
+            Err(e) => warn!(%e, path = ?self.path, "unable to write activity journal"),
+        }
+    }
+}
+
+/// Record focus changes and workspace switches to a journal file at `path`, creating it if
+/// it does not already exist.
+///
+/// See the [module level docs][self] for details of the format of the journal.
+pub fn add_activity_journal<X>(
+    mut wm: WindowManager<X>,
+    path: impl Into<PathBuf>,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(ActivityJournal {
+        path: path.into(),
+        last_client: None,
+        last_tag: None,
+    });
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+    wm.state.config.compose_or_set_shutdown_hook(shutdown_hook);
+
+    wm
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Append entries to the journal if focus has moved to a different client or workspace since
+/// the last refresh.
+pub fn refresh_hook<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let journal = match state.extension::<ActivityJournal>() {
+        Ok(journal) => journal,
+        Err(_) => return Ok(()),
+    };
+    let mut journal = journal.borrow_mut();
+
+    let (at, mut entries) = (now(), Vec::new());
+    let tag = state.client_set.current_tag();
+    if journal.last_tag.as_deref() != Some(tag) {
+        journal.last_tag = Some(tag.to_string());
+        entries.push(JournalEntry::Workspace {
+            at,
+            tag: tag.to_string(),
+        });
+    }
+
+    let client = state.client_set.current_client().copied();
+    if journal.last_client != client {
+        journal.last_client = client;
+        let (class, title) = match client {
+            Some(id) => (client_class(id, x), x.window_title(id).unwrap_or_default()),
+            None => Default::default(),
+        };
+        entries.push(JournalEntry::Focus { at, class, title });
+    }
+
+    if !entries.is_empty() {
+        journal.append(&entries);
+    }
+
+    Ok(())
+}
+
+/// Mark the end of the current session in the journal so that time spent with the window
+/// manager not running is not attributed to the last focused client.
+pub fn shutdown_hook<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+    if let Ok(journal) = state.extension::<ActivityJournal>() {
+        let mut journal = journal.borrow_mut();
+        if journal.last_client.take().is_some() {
+            journal.append(&[JournalEntry::Focus {
+                at: now(),
+                class: String::new(),
+                title: String::new(),
+            }]);
+        }
+    }
+
+    Ok(())
+}
+
+fn client_class<X: XConn>(id: Xid, x: &X) -> String {
+    match x.get_prop(id, Atom::WmClass.as_ref()) {
+        Ok(Some(Prop::UTF8String(strs))) if strs.len() > 1 => strs[1].clone(),
+        _ => String::new(),
+    }
+}
+
+/// The total time each application held focus between `since` and `until` (both in seconds
+/// since the unix epoch), ordered from most to least time.
+///
+/// Each focus entry is treated as lasting until the next focus entry in the journal, or until
+/// `until` if it is the last one. Time spent with no client focused is not included.
+pub fn focus_times(entries: &[JournalEntry], since: u64, until: u64) -> Vec<(String, Duration)> {
+    let focus: Vec<(u64, &str)> = entries
+        .iter()
+        .filter_map(|e| match e {
+            JournalEntry::Focus { at, class, .. } => Some((*at, class.as_str())),
+            JournalEntry::Workspace { .. } => None,
+        })
+        .collect();
+
+    let mut totals: HashMap<&str, u64> = HashMap::new();
+    for (i, &(start, class)) in focus.iter().enumerate() {
+        let end = focus.get(i + 1).map_or(until, |&(t, _)| t).min(until);
+        let start = start.max(since);
+        if !class.is_empty() && end > start {
+            *totals.entry(class).or_default() += end - start;
+        }
+    }
+
+    let mut times: Vec<(String, Duration)> = totals
+        .into_iter()
+        .map(|(class, secs)| (class.to_string(), Duration::from_secs(secs)))
+        .collect();
+    times.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    times
+}
+
+// Render a duration as hours and minutes, e.g. "1h 05m" or "42m"
+fn format_duration(d: Duration) -> String {
+    let mins = d.as_secs() / 60;
+
+    match (mins / 60, mins % 60) {
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h {m:02}m"),
+    }
+}
+
+// The unix timestamp of the most recent local midnight, falling back to UTC if the local
+// time zone can not be determined using `date`.
+fn start_of_today(now: u64) -> u64 {
+    helper_output_with_args("date", &["-d", "today 00:00", "+%s"])
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(now - now % 86_400)
+}
+
+/// Display how long each application has held focus today using `notify-send`.
+///
+/// This requires the [ActivityJournal] to have been added using [add_activity_journal].
+pub fn show_focus_time_today<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, _: &X| {
+        let entries = state.extension::<ActivityJournal>()?.borrow().entries()?;
+        let now = now();
+        let times = focus_times(&entries, start_of_today(now), now);
+
+        let body = if times.is_empty() {
+            "No activity recorded today".to_string()
+        } else {
+            times
+                .iter()
+                .map(|(class, d)| format!("{}  {class}", format_duration(*d)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        notify_send("Focus time today", body)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    fn focus(at: u64, class: &str) -> JournalEntry {
+        JournalEntry::Focus {
+            at,
+            class: class.to_string(),
+            title: if class.is_empty() {
+                String::new()
+            } else {
+                format!("{class} title")
+            },
+        }
+    }
+
+    fn workspace(at: u64, tag: &str) -> JournalEntry {
+        JournalEntry::Workspace {
+            at,
+            tag: tag.to_string(),
+        }
+    }
+
+    #[test_case(focus(10, "firefox"); "focused client")]
+    #[test_case(focus(10, ""); "no focus")]
+    #[test_case(workspace(10, "3"); "workspace switch")]
+    #[test]
+    fn entries_round_trip(entry: JournalEntry) {
+        assert_eq!(JournalEntry::parse(&entry.to_line()), Some(entry));
+    }
+
+    #[test]
+    fn tabs_and_newlines_are_removed_from_titles() {
+        let entry = JournalEntry::Focus {
+            at: 1,
+            class: "Alacritty".to_string(),
+            title: "vim\tfoo.rs\n".to_string(),
+        };
+
+        assert_eq!(entry.to_line(), "1\tfocus\tAlacritty\tvim foo.rs ");
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("focus\tfirefox"; "missing timestamp")]
+    #[test_case("10\tclose\tfirefox"; "unknown kind")]
+    #[test_case("10\tworkspace"; "missing tag")]
+    #[test]
+    fn malformed_lines_are_skipped(line: &str) {
+        assert_eq!(JournalEntry::parse(line), None);
+    }
+
+    #[test_case(0, 100, vec![("a", 60), ("b", 30)]; "whole journal")]
+    #[test_case(25, 100, vec![("a", 35), ("b", 30)]; "since part way through an entry")]
+    #[test_case(0, 50, vec![("a", 30), ("b", 20)]; "until part way through an entry")]
+    #[test_case(0, 30, vec![("a", 30)]; "until the end of an entry")]
+    #[test_case(100, 200, vec![("a", 100)]; "last entry runs until now")]
+    #[test_case(200, 100, vec![]; "empty range")]
+    #[test]
+    fn focus_times_works(since: u64, until: u64, expected: Vec<(&str, u64)>) {
+        let entries = vec![
+            workspace(0, "1"),
+            focus(0, "a"),
+            focus(30, "b"),
+            workspace(40, "2"),
+            focus(60, ""),
+            focus(70, "a"),
+        ];
+
+        let expected: Vec<(String, Duration)> = expected
+            .into_iter()
+            .map(|(c, s)| (c.to_string(), Duration::from_secs(s)))
+            .collect();
+
+        assert_eq!(focus_times(&entries, since, until), expected);
+    }
+
+    #[test_case(0, "0m"; "zero")]
+    #[test_case(59, "0m"; "under a minute")]
+    #[test_case(42 * 60, "42m"; "minutes")]
+    #[test_case(65 * 60, "1h 05m"; "hours")]
+    #[test]
+    fn format_duration_works(secs: u64, expected: &str) {
+        assert_eq!(format_duration(Duration::from_secs(secs)), expected);
+    }
+}
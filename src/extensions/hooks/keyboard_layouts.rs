@@ -0,0 +1,428 @@
+//! Remembering the keyboard layout used in each client window.
+//!
+//! [add_keyboard_layout_memory] tracks the active keyboard layout (XKB group) for each client
+//! and switches back to it whenever that client is focused, so that you can type in one
+//! language in your chat client and another in your editor without switching layouts by
+//! hand each time. Clients that have not been focused before keep whatever layout is active
+//! when they are first focused, unless a default layout has been set using
+//! [KeyboardLayouts::with_default_layout].
+//!
+//! Layouts can be switched using [cycle_keyboard_layout] or any XKB shortcut configured with
+//! `setxkbmap` (such as `grp:alt_shift_toggle`). Hooks registered with
+//! [on_keyboard_layout_change] are run each time the active layout changes, allowing a status
+//! bar to display the current layout:
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! use penrose::extensions::hooks::keyboard_layouts::{
+//!     add_keyboard_layout_memory, on_keyboard_layout_change, KeyboardLayout, KeyboardLayouts,
+//! };
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! let wm = add_keyboard_layout_memory(wm, KeyboardLayouts::new());
+//! on_keyboard_layout_change(wm, |layout: &KeyboardLayout, _: &mut _, _: &_| {
+//!     tracing::info!(name = %layout.name, "keyboard layout changed");
+//!     Ok(())
+//! })
+//! # }
+//! ```
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State, WindowManager},
+    x::{XConn, XEvent},
+    Result, Xid,
+};
+use std::{collections::HashMap, fmt};
+use tracing::{debug, error};
+
+/// A keyboard layout (XKB group) known to the X server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    /// The index of the XKB group for this layout
+    pub group: u8,
+    /// The name of the layout as reported by the X server (such as `"English (US)"`)
+    pub name: String,
+}
+
+/// Logic to run when the active keyboard layout changes.
+pub trait KeyboardLayoutHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook for the new keyboard layout.
+    fn call(&mut self, layout: &KeyboardLayout, state: &mut State<X>, x: &X) -> Result<()>;
+}
+
+impl<F, X> KeyboardLayoutHook<X> for F
+where
+    F: FnMut(&KeyboardLayout, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, layout: &KeyboardLayout, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(layout, state, x)
+    }
+}
+
+/// The state extension tracking the keyboard layout last used in each client.
+pub struct KeyboardLayouts<X: XConn> {
+    names: Vec<String>,
+    current: u8,
+    default_group: Option<u8>,
+    clients: HashMap<Xid, u8>,
+    focused: Option<Xid>,
+    hooks: Vec<Box<dyn KeyboardLayoutHook<X>>>,
+}
+
+impl<X: XConn> Default for KeyboardLayouts<X> {
+    fn default() -> Self {
+        Self {
+            names: Vec::new(),
+            current: 0,
+            default_group: None,
+            clients: HashMap::new(),
+            focused: None,
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl<X: XConn> fmt::Debug for KeyboardLayouts<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyboardLayouts")
+            .field("names", &self.names)
+            .field("current", &self.current)
+            .field("default_group", &self.default_group)
+            .field("clients", &self.clients)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+impl<X: XConn> KeyboardLayouts<X> {
+    /// Create a new, empty, set of per-client keyboard layouts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Switch to the layout with the given group index when a client is focused for the first
+    /// time, rather than keeping the layout that is currently active.
+    pub fn with_default_layout(mut self, group: u8) -> Self {
+        self.default_group = Some(group);
+        self
+    }
+
+    /// The currently active keyboard layout.
+    pub fn current(&self) -> KeyboardLayout {
+        self.layout(self.current)
+    }
+
+    /// The names of the configured keyboard layouts, in group order.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The group index of the layout last used in the given client, if it has been focused.
+    pub fn layout_for(&self, id: Xid) -> Option<u8> {
+        self.clients.get(&id).copied()
+    }
+
+    /// Register a hook to be run each time the active keyboard layout changes.
+    pub fn add_hook<H>(&mut self, hook: H)
+    where
+        H: KeyboardLayoutHook<X> + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+    }
+
+    fn layout(&self, group: u8) -> KeyboardLayout {
+        let name = match self.names.get(group as usize) {
+            Some(name) => name.clone(),
+            None => group.to_string(),
+        };
+
+        KeyboardLayout { group, name }
+    }
+}
+
+/// Add the given [KeyboardLayouts] as a state extension along with the hooks required to
+/// switch to the layout last used in each client when it gains focus.
+pub fn add_keyboard_layout_memory<X>(
+    mut wm: WindowManager<X>,
+    mut layouts: KeyboardLayouts<X>,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    // Keep any hooks that were registered before the extension was added
+    if let Ok(existing) = wm.state.extension::<KeyboardLayouts<X>>() {
+        layouts.hooks.append(&mut existing.borrow_mut().hooks);
+    }
+    wm.state.add_extension(layouts);
+    wm.state.config.compose_or_set_startup_hook(startup_hook);
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+    wm.state.config.compose_or_set_event_hook(event_hook);
+
+    wm
+}
+
+/// Register a hook to be run each time the active keyboard layout changes.
+///
+/// Hooks only run once [add_keyboard_layout_memory] has been called, but may be registered
+/// before or after it.
+pub fn on_keyboard_layout_change<X, H>(mut wm: WindowManager<X>, hook: H) -> WindowManager<X>
+where
+    X: XConn + 'static,
+    H: KeyboardLayoutHook<X> + 'static,
+{
+    wm.state
+        .extension_or_default::<KeyboardLayouts<X>>()
+        .borrow_mut()
+        .add_hook(hook);
+
+    wm
+}
+
+/// Switch to the next keyboard layout, wrapping back around to the first.
+///
+/// The new layout is remembered for the focused client.
+pub fn cycle_keyboard_layout<X>() -> Box<dyn KeyEventHandler<X>>
+where
+    X: XConn + 'static,
+{
+    key_handler(|state: &mut State<X>, x: &X| {
+        let next = {
+            let kl = state.extension::<KeyboardLayouts<X>>()?;
+            let kl = kl.borrow();
+            if kl.names.len() < 2 {
+                return Ok(());
+            }
+
+            ((kl.current as usize + 1) % kl.names.len()) as u8
+        };
+
+        switch_layout(next, state, x)
+    })
+}
+
+/// Fetch the configured keyboard layouts from the X server and run any registered hooks with
+/// the initial layout.
+pub fn startup_hook<X>(state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    refresh_layout_names(state, x)?;
+    let current = x.keyboard_layout()?;
+    state
+        .extension::<KeyboardLayouts<X>>()?
+        .borrow_mut()
+        .current = current;
+
+    run_hooks(state, x)
+}
+
+/// Switch to the layout last used in the focused client if focus has changed.
+pub fn refresh_hook<X>(state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    let focused = state.client_set.current_client().copied();
+    let group = {
+        let kl = state.extension::<KeyboardLayouts<X>>()?;
+        let mut kl = kl.borrow_mut();
+        kl.clients.retain(|id, _| state.client_set.contains(id));
+        if kl.focused == focused {
+            return Ok(());
+        }
+        kl.focused = focused;
+
+        match focused {
+            Some(id) => {
+                let group = kl.default_group.unwrap_or(kl.current);
+                *kl.clients.entry(id).or_insert(group)
+            }
+            None => return Ok(()),
+        }
+    };
+
+    switch_layout(group, state, x)
+}
+
+/// Track layout changes made outside of the window manager (such as XKB shortcuts) and
+/// changes to the configured layouts.
+pub fn event_hook<X>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool>
+where
+    X: XConn + 'static,
+{
+    match event {
+        XEvent::KeyboardLayoutChange(group) => {
+            let changed = state.extension::<KeyboardLayouts<X>>()?.borrow().current != *group;
+            if changed {
+                layout_changed(*group, state, x)?;
+            }
+        }
+
+        XEvent::MappingNotify => refresh_layout_names(state, x)?,
+
+        _ => (),
+    }
+
+    Ok(true)
+}
+
+fn refresh_layout_names<X>(state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    let names = x.keyboard_layouts()?;
+    debug!(?names, "fetched keyboard layouts");
+    state
+        .extension_or_default::<KeyboardLayouts<X>>()
+        .borrow_mut()
+        .names = names;
+
+    Ok(())
+}
+
+fn switch_layout<X>(group: u8, state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    if state.extension::<KeyboardLayouts<X>>()?.borrow().current == group {
+        return Ok(());
+    }
+
+    x.set_keyboard_layout(group)?;
+    layout_changed(group, state, x)
+}
+
+// Record the new layout for the focused client and run the registered hooks.
+fn layout_changed<X>(group: u8, state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    {
+        let kl = state.extension::<KeyboardLayouts<X>>()?;
+        let mut kl = kl.borrow_mut();
+        kl.current = group;
+        if let Some(id) = kl.focused {
+            kl.clients.insert(id, group);
+        }
+    }
+
+    run_hooks(state, x)
+}
+
+fn run_hooks<X>(state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    let kl = state.extension::<KeyboardLayouts<X>>()?;
+    let (layout, mut hooks) = {
+        let mut kl = kl.borrow_mut();
+        (kl.current(), std::mem::take(&mut kl.hooks))
+    };
+
+    debug!(group = layout.group, name = %layout.name, "keyboard layout changed");
+    for h in hooks.iter_mut() {
+        if let Err(e) = h.call(&layout, state, x) {
+            error!(%e, "error returned from keyboard layout hook");
+        }
+    }
+
+    // Keep any hooks that were registered while running the existing ones
+    let mut kl = kl.borrow_mut();
+    hooks.append(&mut kl.hooks);
+    kl.hooks = hooks;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::actions::modify_with,
+        core::Config,
+        map,
+        testing::{parse_keybindings, HeadlessConn, Scenario},
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    fn scenario(layouts: KeyboardLayouts<HeadlessConn>) -> (Scenario, Rc<RefCell<Vec<String>>>) {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let names = Rc::clone(&seen);
+
+        let bindings = parse_keybindings(map! {
+            "M-j" => modify_with(|cs| cs.focus_down()),
+            "M-space" => cycle_keyboard_layout(),
+        })
+        .unwrap();
+        let conn = HeadlessConn::default();
+        conn.set_keyboard_layouts(vec!["us".to_string(), "de".to_string(), "ru".to_string()]);
+
+        let wm = WindowManager::new(Config::default(), bindings, HashMap::new(), conn).unwrap();
+        let wm = add_keyboard_layout_memory(wm, layouts);
+        let wm = on_keyboard_layout_change(wm, move |l: &KeyboardLayout, _: &mut _, _: &_| {
+            names.borrow_mut().push(l.name.clone());
+            Ok(())
+        });
+
+        (Scenario::new(wm).unwrap(), seen)
+    }
+
+    fn expect_layout(s: Scenario, group: u8) -> Scenario {
+        assert_eq!(s.conn().keyboard_layout().unwrap(), group);
+        let current = s
+            .state()
+            .extension::<KeyboardLayouts<HeadlessConn>>()
+            .unwrap()
+            .borrow()
+            .current();
+        assert_eq!(current.group, group);
+
+        s
+    }
+
+    #[test]
+    fn layouts_are_restored_on_focus() {
+        let (s, seen) = scenario(KeyboardLayouts::new());
+        let s = s.given_windows(["a", "b"]).expect_focus("b");
+
+        let s = expect_layout(s.press("M-space"), 1);
+        let s = expect_layout(s.press("M-j").expect_focus("a"), 0);
+        let s = expect_layout(s.press("M-j").expect_focus("b"), 1);
+        expect_layout(s.open_window("c", "c"), 1);
+
+        assert_eq!(*seen.borrow(), vec!["us", "de", "us", "de"]);
+    }
+
+    #[test]
+    fn external_layout_changes_are_remembered() {
+        let (s, _) = scenario(KeyboardLayouts::new());
+        let s = s.given_windows(["a", "b"]);
+
+        s.conn().switch_keyboard_layout(2);
+        let s = s.act(|_, _| Ok(()));
+
+        let s = expect_layout(s.press("M-j").expect_focus("a"), 0);
+        expect_layout(s.press("M-j").expect_focus("b"), 2);
+    }
+
+    #[test]
+    fn new_clients_use_the_default_layout_if_set() {
+        let (s, _) = scenario(KeyboardLayouts::new().with_default_layout(0));
+        let s = expect_layout(s.given_windows(["a"]).press("M-space"), 1);
+
+        expect_layout(s.open_window("b", "b"), 0);
+    }
+
+    #[test]
+    fn cycling_layouts_wraps_around() {
+        let (s, seen) = scenario(KeyboardLayouts::new());
+        let s = s.given_windows(["a"]);
+
+        expect_layout(s.press("M-space").press("M-space").press("M-space"), 0);
+
+        assert_eq!(*seen.borrow(), vec!["us", "de", "ru", "us"]);
+    }
+}
@@ -0,0 +1,441 @@
+//! An interactive mode for adjusting the parameters of the active layout.
+//!
+//! Once [add_layout_editor] has been called, running [enter_layout_edit_mode] switches to a
+//! modal editing mode for the layout of the current workspace. While editing, your normal key
+//! bindings are released (see [State::begin_modal_grab]) and the following keys are grabbed
+//! instead:
+//!
+//!   - `Up` / `Down`: select the previous / next adjustable parameter
+//!   - `Left` / `Right` (or the scroll wheel): decrease / increase the selected parameter
+//!   - `Return`: keep the changes and leave edit mode
+//!   - `Escape`: restore the layout as it was when edit mode was entered and leave edit mode
+//!
+//! The parameters that can be edited are those reported by the active layout through
+//! [Layout::parameters] that have a corresponding [LayoutParam]. The current values are shown
+//! using `notify-send` (updating the notification in place for notification daemons that
+//! support it) and can also be rendered by a status bar using [LayoutEditor::status].
+use crate::{
+    builtin::{
+        actions::key_handler,
+        layout::messages::{ExpandMain, IncInnerGap, IncMain, IncOuterGap, ShrinkMain},
+    },
+    core::{
+        bindings::{
+            keycodes_from_xmodmap, KeyCode, KeyEventHandler, MouseButton, MouseEventKind,
+            MouseState,
+        },
+        layout::{Layout, LayoutStack},
+        ClientSet, State, WindowManager,
+    },
+    util::spawn_helper_with_args,
+    x::{XConn, XConnExt, XEvent},
+    Error, Result,
+};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+const NOTIFICATION_TAG: &str = "string:x-canonical-private-synchronous:penrose-layout-editor";
+const GAP_STEP: i32 = 2;
+
+/// A layout parameter that can be adjusted in layout edit mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LayoutParam {
+    /// The ratio between the main and secondary areas, adjusted using [ExpandMain] and
+    /// [ShrinkMain].
+    Ratio,
+    /// The number of clients in the main area, adjusted using [IncMain].
+    MaxMain,
+    /// The outer gap of a [Gaps][0] transformer, adjusted using [IncOuterGap].
+    ///
+    ///   [0]: crate::builtin::layout::transformers::Gaps
+    OuterGap,
+    /// The inner gap of a [Gaps][0] transformer, adjusted using [IncInnerGap].
+    ///
+    ///   [0]: crate::builtin::layout::transformers::Gaps
+    InnerGap,
+}
+
+impl LayoutParam {
+    /// The name of this parameter as reported by [Layout::parameters].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ratio => "ratio",
+            Self::MaxMain => "max_main",
+            Self::OuterGap => "outer_gap",
+            Self::InnerGap => "inner_gap",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Ratio, Self::MaxMain, Self::OuterGap, Self::InnerGap]
+            .into_iter()
+            .find(|p| p.name() == name)
+    }
+
+    fn adjust(&self, layouts: &mut LayoutStack, increase: bool) {
+        let sign = if increase { 1 } else { -1 };
+
+        match self {
+            Self::Ratio if increase => layouts.handle_message(ExpandMain),
+            Self::Ratio => layouts.handle_message(ShrinkMain),
+            Self::MaxMain => layouts.handle_message(IncMain(sign as i8)),
+            Self::OuterGap => layouts.handle_message(IncOuterGap(sign * GAP_STEP)),
+            Self::InnerGap => layouts.handle_message(IncInnerGap(sign * GAP_STEP)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum EditAction {
+    Previous,
+    Next,
+    Decrease,
+    Increase,
+    Commit,
+    Cancel,
+}
+
+// The layout being edited along with its state before editing began.
+#[derive(Debug, Clone)]
+struct EditSession {
+    tag: String,
+    original: Box<dyn Layout>,
+    selected: usize,
+}
+
+/// State for the layout editor, including the layout currently being edited if edit mode is
+/// active.
+#[derive(Debug)]
+pub struct LayoutEditor {
+    keys: HashMap<KeyCode, EditAction>,
+    session: Option<EditSession>,
+}
+
+impl LayoutEditor {
+    /// Whether or not layout edit mode is currently active.
+    pub fn is_active(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// A summary of the parameters of the layout being edited with the selected parameter
+    /// highlighted, or `None` if edit mode is not active.
+    pub fn status(&self, cs: &ClientSet) -> Option<String> {
+        let s = self.session.as_ref()?;
+        let layout = &cs.workspace(&s.tag)?.layouts.focus;
+
+        Some(status_text(
+            &layout.name(),
+            &layout.parameters(),
+            s.selected,
+        ))
+    }
+
+    // The parameters of the given layout that can be adjusted.
+    fn editable(layout: &dyn Layout) -> Vec<LayoutParam> {
+        layout
+            .parameters()
+            .iter()
+            .flat_map(|(name, _)| LayoutParam::from_name(name))
+            .collect()
+    }
+
+    // Apply an edit action to the client set, returning true if edit mode should be exited.
+    fn apply(&mut self, action: EditAction, cs: &mut ClientSet) -> bool {
+        let s = match self.session.as_mut() {
+            Some(s) => s,
+            None => return true,
+        };
+        let ws = match cs.workspace_mut(&s.tag) {
+            Some(ws) => ws,
+            None => {
+                self.session = None;
+                return true;
+            }
+        };
+
+        let editable = Self::editable(ws.layouts.focus.as_ref());
+        let n = editable.len().max(1);
+
+        match action {
+            EditAction::Previous => s.selected = (s.selected + n - 1) % n,
+            EditAction::Next => s.selected = (s.selected + 1) % n,
+            EditAction::Decrease | EditAction::Increase => {
+                if let Some(p) = editable.get(s.selected) {
+                    p.adjust(&mut ws.layouts, action == EditAction::Increase);
+                }
+            }
+            EditAction::Commit => {
+                self.session = None;
+                return true;
+            }
+            EditAction::Cancel => {
+                ws.layouts.focus = s.original.clone();
+                self.session = None;
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+// Render the parameters of a layout as a single line with the selected editable parameter
+// wrapped in brackets.
+fn status_text(name: &str, params: &[(String, String)], selected: usize) -> String {
+    let mut editable_ix = 0;
+    let rendered: Vec<String> = params
+        .iter()
+        .map(|(k, v)| {
+            if LayoutParam::from_name(k).is_none() {
+                return format!("{k} {v}");
+            }
+
+            let s = if editable_ix == selected {
+                format!("[{k} {v}]")
+            } else {
+                format!("{k} {v}")
+            };
+            editable_ix += 1;
+
+            s
+        })
+        .collect();
+
+    format!("{name}: {}", rendered.join("  "))
+}
+
+/// Add the [LayoutEditor] state extension and the event hook used to handle input while in
+/// layout edit mode.
+///
+/// The key codes used in edit mode are looked up using [keycodes_from_xmodmap].
+pub fn add_layout_editor<X>(mut wm: WindowManager<X>) -> Result<WindowManager<X>>
+where
+    X: XConn + 'static,
+{
+    let codes = keycodes_from_xmodmap()?;
+    let mut keys = HashMap::new();
+
+    for (name, action) in [
+        ("Up", EditAction::Previous),
+        ("Down", EditAction::Next),
+        ("Left", EditAction::Decrease),
+        ("Right", EditAction::Increase),
+        ("Return", EditAction::Commit),
+        ("Escape", EditAction::Cancel),
+    ] {
+        let code = *codes.get(name).ok_or_else(|| Error::UnknownKeyName {
+            name: name.to_owned(),
+        })?;
+        keys.insert(KeyCode { mask: 0, code }, action);
+    }
+
+    wm.state.add_extension(LayoutEditor {
+        keys,
+        session: None,
+    });
+    wm.state.config.compose_or_set_event_hook(event_hook);
+
+    Ok(wm)
+}
+
+/// Enter layout edit mode for the layout of the current workspace.
+pub fn enter_layout_edit_mode<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        let editor = match state.extension::<LayoutEditor>() {
+            Ok(editor) => editor,
+            Err(_) => {
+                warn!("unable to edit layout: add_layout_editor has not been called");
+                return Ok(());
+            }
+        };
+        let mut editor = editor.borrow_mut();
+
+        if editor.is_active() {
+            return Ok(());
+        }
+
+        let ws = state.client_set.current_workspace();
+        if LayoutEditor::editable(ws.layouts.focus.as_ref()).is_empty() {
+            debug!(layout = %ws.layout_name(), "layout has no adjustable parameters");
+            return Ok(());
+        }
+
+        editor.session = Some(EditSession {
+            tag: ws.tag.clone(),
+            original: ws.layouts.focus.clone(),
+            selected: 0,
+        });
+
+        debug!("entering layout edit mode");
+        let key_codes: Vec<_> = editor.keys.keys().copied().collect();
+        let scroll =
+            [MouseButton::ScrollUp, MouseButton::ScrollDown].map(|b| MouseState::new(b, vec![]));
+        state.begin_modal_grab(&key_codes, &scroll, x)?;
+
+        show_status(&editor, &state.client_set);
+
+        Ok(())
+    })
+}
+
+/// Intercept key presses and scroll events while layout edit mode is active.
+pub fn event_hook<X: XConn>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+    let editor = match state.extension::<LayoutEditor>() {
+        Ok(editor) if editor.borrow().is_active() => editor,
+        _ => return Ok(true),
+    };
+    let mut editor = editor.borrow_mut();
+
+    let action = match event {
+        XEvent::KeyPress(code) => match editor.keys.get(code) {
+            Some(&action) => action,
+            None => return Ok(false),
+        },
+        XEvent::MouseEvent(e) if e.kind == MouseEventKind::Press => match e.state.button {
+            MouseButton::ScrollUp => EditAction::Increase,
+            MouseButton::ScrollDown => EditAction::Decrease,
+            _ => return Ok(false),
+        },
+        XEvent::MouseEvent(_) => return Ok(false),
+        _ => return Ok(true),
+    };
+
+    let done = editor.apply(action, &mut state.client_set);
+    if matches!(action, EditAction::Decrease | EditAction::Increase) {
+        x.refresh(state)?;
+    }
+
+    if done {
+        debug!(?action, "leaving layout edit mode");
+        state.end_modal_grab();
+        notify(
+            if action == EditAction::Commit {
+                "Layout changes saved"
+            } else {
+                "Layout changes reverted"
+            },
+            2000,
+        );
+    } else {
+        show_status(&editor, &state.client_set);
+    }
+
+    Ok(false)
+}
+
+fn show_status(editor: &LayoutEditor, cs: &ClientSet) {
+    if let Some(status) = editor.status(cs) {
+        notify(&status, 10000);
+    }
+}
+
+fn notify(body: &str, duration: usize) {
+    let duration = duration.to_string();
+    let args = [
+        "-h",
+        NOTIFICATION_TAG,
+        "-t",
+        &duration,
+        "Layout editor",
+        body,
+    ];
+
+    if let Err(e) = spawn_helper_with_args("notify-send", &args) {
+        warn!(%e, "unable to show layout editor status");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::layout::{transformers::Gaps, MainAndStack, Monocle},
+        pure::test_xid_stack_set,
+        stack,
+    };
+    use simple_test_case::test_case;
+
+    fn editor_with_session(cs: &ClientSet) -> LayoutEditor {
+        LayoutEditor {
+            keys: HashMap::new(),
+            session: Some(EditSession {
+                tag: cs.current_tag().to_string(),
+                original: cs.current_workspace().layouts.focus.clone(),
+                selected: 0,
+            }),
+        }
+    }
+
+    fn client_set() -> ClientSet {
+        let mut cs = test_xid_stack_set(1, 1);
+        cs.current_workspace_mut()
+            .set_available_layouts(stack!(Gaps::wrap(MainAndStack::boxed_default(), 4, 4)));
+
+        cs
+    }
+
+    fn current_status(editor: &LayoutEditor, cs: &ClientSet) -> String {
+        editor.status(cs).unwrap()
+    }
+
+    #[test_case(0, "Side: [ratio 0.60]  max_main 1  outer_gap 4  inner_gap 4"; "first")]
+    #[test_case(2, "Side: ratio 0.60  max_main 1  [outer_gap 4]  inner_gap 4"; "third")]
+    #[test]
+    fn status_text_highlights_selected(selected: usize, expected: &str) {
+        let l = Gaps::wrap(MainAndStack::boxed_default(), 4, 4);
+
+        assert_eq!(status_text(&l.name(), &l.parameters(), selected), expected);
+    }
+
+    #[test]
+    fn monocle_has_nothing_to_edit() {
+        assert!(LayoutEditor::editable(Monocle::boxed().as_ref()).is_empty());
+    }
+
+    #[test]
+    fn adjusting_updates_the_selected_parameter() {
+        let mut cs = client_set();
+        let mut editor = editor_with_session(&cs);
+
+        assert!(!editor.apply(EditAction::Increase, &mut cs));
+        assert!(!editor.apply(EditAction::Previous, &mut cs));
+        assert!(!editor.apply(EditAction::Increase, &mut cs));
+
+        assert_eq!(
+            current_status(&editor, &cs),
+            "Side: ratio 0.70  max_main 1  outer_gap 4  [inner_gap 6]"
+        );
+    }
+
+    #[test]
+    fn commit_keeps_changes() {
+        let mut cs = client_set();
+        let mut editor = editor_with_session(&cs);
+
+        editor.apply(EditAction::Decrease, &mut cs);
+        assert!(editor.apply(EditAction::Commit, &mut cs));
+
+        assert!(!editor.is_active());
+        assert_eq!(
+            cs.current_workspace().layouts.focus.parameters()[0],
+            ("ratio".to_owned(), "0.50".to_owned())
+        );
+    }
+
+    #[test]
+    fn cancel_restores_the_original_layout() {
+        let mut cs = client_set();
+        let mut editor = editor_with_session(&cs);
+
+        editor.apply(EditAction::Decrease, &mut cs);
+        editor.apply(EditAction::Next, &mut cs);
+        editor.apply(EditAction::Increase, &mut cs);
+        assert!(editor.apply(EditAction::Cancel, &mut cs));
+
+        assert!(!editor.is_active());
+        assert_eq!(
+            cs.current_workspace().layouts.focus.parameters(),
+            Gaps::wrap(MainAndStack::boxed_default(), 4, 4).parameters()
+        );
+    }
+}
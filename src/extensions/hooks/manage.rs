@@ -4,8 +4,11 @@
 //! itself when the manage hook is called.
 use crate::{
     core::{hooks::ManageHook, State},
-    pure::geometry::{Rect, RelativeRect},
-    x::{Query, XConn},
+    pure::{
+        geometry::{Rect, RelativeRect},
+        Position,
+    },
+    x::{atom::Atom, floating_client_position, FloatingPlacement, Query, XConn, XConnExt},
     Result, Xid,
 };
 
@@ -123,3 +126,53 @@ impl<X: XConn> ManageHook<X> for SetWorkspace {
         Ok(())
     }
 }
+
+/// Insert the specified client at the given [Position] in the stack of its workspace,
+/// overriding [Config::insert_position][0].
+///
+/// The position is relative to the client that was focused before the new client was
+/// managed. If this hook is run after [SetWorkspace] then it is relative to the focused
+/// client of the new workspace instead.
+///
+///   [0]: crate::core::Config::insert_position
+#[derive(Debug)]
+pub struct InsertPosition(pub Position);
+impl<X: XConn> ManageHook<X> for InsertPosition {
+    fn call(&mut self, client: Xid, state: &mut State<X>, _: &X) -> Result<()> {
+        state.client_set.move_client_to_position(&client, self.0);
+        Ok(())
+    }
+}
+
+/// Leave the specified client out of taskbars, window switchers and window title displays by
+/// setting `_NET_WM_STATE_SKIP_TASKBAR`, as if the client had requested it itself.
+#[derive(Debug)]
+pub struct SkipTaskbar;
+impl<X: XConn> ManageHook<X> for SkipTaskbar {
+    fn call(&mut self, client: Xid, _: &mut State<X>, x: &X) -> Result<()> {
+        x.set_net_wm_state(client, Atom::NetWmStateSkipTaskbar, true)
+    }
+}
+
+/// Leave the specified client out of pagers by setting `_NET_WM_STATE_SKIP_PAGER`, as if the
+/// client had requested it itself.
+#[derive(Debug)]
+pub struct SkipPager;
+impl<X: XConn> ManageHook<X> for SkipPager {
+    fn call(&mut self, client: Xid, _: &mut State<X>, x: &X) -> Result<()> {
+        x.set_net_wm_state(client, Atom::NetWmStateSkipPager, true)
+    }
+}
+
+/// Float the specified client, positioning it using the given [FloatingPlacement] rather
+/// than [Config::floating_placement][0].
+///
+///   [0]: crate::core::Config::floating_placement
+#[derive(Debug)]
+pub struct FloatingPlaced(pub FloatingPlacement);
+impl<X: XConn> ManageHook<X> for FloatingPlaced {
+    fn call(&mut self, client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+        let r = floating_client_position(client, self.0, state, x)?;
+        float(client, r, state, x)
+    }
+}
@@ -0,0 +1,153 @@
+//! Vim style marks for client windows.
+//!
+//! [mark_focused] assigns a single character mark to the focused client which can then be
+//! jumped back to from any workspace using [jump_to_mark]. A client may hold multiple marks
+//! but each mark refers to at most one client: re-using a mark moves it to the newly focused
+//! client.
+//!
+//! The current marks are stored in a [Marks] state extension so that they are available to
+//! other hooks (such as a status bar) via [State::extension]. Calling [add_marks] registers
+//! a refresh hook to remove marks from clients that are no longer being managed.
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, ClientSet, State, WindowManager},
+    x::{XConn, XConnExt},
+    Result, Xid,
+};
+use std::collections::BTreeMap;
+use tracing::debug;
+
+/// The currently assigned client marks.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Marks(BTreeMap<char, Xid>);
+
+impl Marks {
+    /// The client currently holding the given mark, if there is one.
+    pub fn get(&self, mark: char) -> Option<Xid> {
+        self.0.get(&mark).copied()
+    }
+
+    /// All marks currently held by the given client in sorted order.
+    pub fn marks_for(&self, id: Xid) -> Vec<char> {
+        self.iter()
+            .filter(|&(_, c)| c == id)
+            .map(|(m, _)| m)
+            .collect()
+    }
+
+    /// Iterate over all marks and the clients holding them in sorted order.
+    pub fn iter(&self) -> impl Iterator<Item = (char, Xid)> + '_ {
+        self.0.iter().map(|(&m, &id)| (m, id))
+    }
+
+    /// Whether or not any marks are currently set.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn set(&mut self, mark: char, id: Xid) {
+        self.0.insert(mark, id);
+    }
+
+    // Remove any marks held by clients that are no longer in the client set, returning
+    // whether or not any marks were removed.
+    fn prune(&mut self, cs: &ClientSet) -> bool {
+        let n = self.0.len();
+        self.0.retain(|_, id| cs.contains(id));
+
+        self.0.len() != n
+    }
+}
+
+/// Add the [Marks] state extension and a refresh hook for removing marks from clients
+/// that are no longer managed.
+pub fn add_marks<X>(mut wm: WindowManager<X>) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(Marks::default());
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+
+    wm
+}
+
+/// Remove marks from clients that are no longer managed.
+pub fn refresh_hook<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+    let marks = state.extension_or_default::<Marks>();
+    if marks.borrow_mut().prune(&state.client_set) {
+        debug!("removed marks for clients that are no longer managed");
+    }
+
+    Ok(())
+}
+
+/// Assign the given mark to the currently focused client, removing it from any other client.
+pub fn mark_focused<X: XConn>(mark: char) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, _: &X| {
+        if let Some(&id) = state.client_set.current_client() {
+            debug!(%mark, %id, "marking focused client");
+            state
+                .extension_or_default::<Marks>()
+                .borrow_mut()
+                .set(mark, id);
+        }
+
+        Ok(())
+    })
+}
+
+/// Focus the client holding the given mark, switching to its workspace if needed.
+///
+/// This is a no-op if no client currently holds the mark.
+pub fn jump_to_mark<X: XConn>(mark: char) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let marks = state.extension_or_default::<Marks>();
+        marks.borrow_mut().prune(&state.client_set);
+        let id = match marks.borrow().get(mark) {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+
+        x.modify_and_refresh(state, |cs| cs.focus_client(&id))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pure::test_xid_stack_set;
+
+    #[test]
+    fn marks_for_returns_sorted_marks() {
+        let mut marks = Marks::default();
+        marks.set('b', Xid(1));
+        marks.set('a', Xid(1));
+        marks.set('c', Xid(2));
+
+        assert_eq!(marks.marks_for(Xid(1)), vec!['a', 'b']);
+        assert_eq!(marks.marks_for(Xid(3)), vec![]);
+    }
+
+    #[test]
+    fn reusing_a_mark_moves_it() {
+        let mut marks = Marks::default();
+        marks.set('a', Xid(1));
+        marks.set('a', Xid(2));
+
+        assert_eq!(marks.get('a'), Some(Xid(2)));
+        assert!(marks.marks_for(Xid(1)).is_empty());
+    }
+
+    #[test]
+    fn prune_removes_unknown_clients() {
+        let mut cs = test_xid_stack_set(1, 1);
+        cs.insert(Xid(1));
+        let mut marks = Marks::default();
+        marks.set('a', Xid(1));
+        marks.set('b', Xid(2));
+
+        assert!(marks.prune(&cs));
+        assert!(!marks.prune(&cs));
+        assert_eq!(marks.iter().collect::<Vec<_>>(), vec![('a', Xid(1))]);
+    }
+}
@@ -0,0 +1,260 @@
+//! Minimizing and restoring client windows.
+//!
+//! Minimized clients are moved to an invisible workspace so that they are removed from their
+//! layout while remaining managed by the window manager. They are flagged with
+//! `_NET_WM_STATE_HIDDEN` so that external pagers and task bars can show them as minimized.
+//!
+//! The currently minimized clients are tracked in a [MinimizedClients] state extension which
+//! can be read by other hooks (such as a status bar) via [State::extension]. Call
+//! [add_minimize] to set up the required workspace, state and hooks before binding
+//! [minimize_focused] and [restore_last_minimized] (or [dmenu_restore_minimized][0]).
+//!
+//!   [0]: crate::extensions::actions::dmenu_restore_minimized
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, ClientSet, State, WindowManager},
+    x::{atom::Atom, XConn, XConnExt},
+    Result, Xid,
+};
+use tracing::{debug, warn};
+
+/// The tag used for the invisible workspace holding minimized clients.
+pub const MINIMIZED_TAG: &str = "MIN";
+
+/// The clients that are currently minimized along with the workspace each was minimized from.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MinimizedClients(Vec<(Xid, String)>);
+
+impl MinimizedClients {
+    /// Whether or not the given client is currently minimized.
+    pub fn contains(&self, id: &Xid) -> bool {
+        self.0.iter().any(|(c, _)| c == id)
+    }
+
+    /// The minimized clients, most recently minimized first.
+    pub fn clients(&self) -> impl Iterator<Item = Xid> + '_ {
+        self.0.iter().rev().map(|&(id, _)| id)
+    }
+
+    /// The most recently minimized client, if there is one.
+    pub fn last(&self) -> Option<Xid> {
+        self.0.last().map(|&(id, _)| id)
+    }
+
+    /// The number of minimized clients.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether or not there are any minimized clients.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Move the given client to the minimized workspace, returning false if it is not present
+    // in the client set or is already minimized.
+    fn minimize(&mut self, id: Xid, cs: &mut ClientSet) -> bool {
+        let tag = match cs.tag_for_client(&id) {
+            Some(tag) if tag != MINIMIZED_TAG => tag.to_string(),
+            _ => return false,
+        };
+
+        cs.move_client_to_tag(&id, MINIMIZED_TAG);
+        self.0.retain(|(c, _)| *c != id);
+        self.0.push((id, tag));
+
+        true
+    }
+
+    // Move the given client back to the workspace it was minimized from (or the current
+    // workspace if that no longer exists) and focus it.
+    fn restore(&mut self, id: Xid, cs: &mut ClientSet) -> bool {
+        let tag = match self.0.iter().position(|(c, _)| *c == id) {
+            Some(ix) => self.0.remove(ix).1,
+            None => return false,
+        };
+
+        let tag = if cs.contains_tag(&tag) {
+            tag
+        } else {
+            cs.current_tag().to_string()
+        };
+
+        cs.move_client_to_tag(&id, &tag);
+        cs.focus_client(&id);
+
+        true
+    }
+
+    // Drop any clients that are no longer managed or have been moved off of the minimized
+    // workspace by something else, returning whether or not any were removed.
+    fn prune(&mut self, cs: &ClientSet) -> bool {
+        let n = self.0.len();
+        self.0
+            .retain(|(id, _)| cs.tag_for_client(id) == Some(MINIMIZED_TAG));
+
+        self.0.len() != n
+    }
+}
+
+/// Add the invisible workspace used for minimized clients, the [MinimizedClients] state
+/// extension and a refresh hook for tracking clients that are no longer minimized.
+///
+/// # Panics
+/// This function will panic if [MINIMIZED_TAG] is already in use as a workspace tag.
+pub fn add_minimize<X>(mut wm: WindowManager<X>) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(MinimizedClients::default());
+    wm.state
+        .client_set
+        .add_invisible_workspace(MINIMIZED_TAG)
+        .expect("minimized tag to be unique");
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+
+    wm
+}
+
+/// Stop tracking clients that have been removed or moved off of the minimized workspace.
+pub fn refresh_hook<X: XConn>(state: &mut State<X>, _: &X) -> Result<()> {
+    let min = state.extension_or_default::<MinimizedClients>();
+    if min.borrow_mut().prune(&state.client_set) {
+        debug!("removed clients that are no longer minimized");
+    }
+
+    Ok(())
+}
+
+/// Minimize the currently focused client.
+pub fn minimize_focused<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        let id = match state.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        if !state.client_set.contains_tag(MINIMIZED_TAG) {
+            warn!("unable to minimize client: add_minimize has not been called");
+            return Ok(());
+        }
+
+        let min = state.extension_or_default::<MinimizedClients>();
+        if min.borrow_mut().minimize(id, &mut state.client_set) {
+            debug!(%id, "minimizing client");
+            set_hidden_state(id, true, x)?;
+        }
+
+        x.refresh(state)
+    })
+}
+
+/// Restore the most recently minimized client to the workspace it was minimized from
+/// and focus it.
+pub fn restore_last_minimized<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        let last = state
+            .extension_or_default::<MinimizedClients>()
+            .borrow()
+            .last();
+        match last {
+            Some(id) => restore_minimized(id, state, x),
+            None => Ok(()),
+        }
+    })
+}
+
+/// Restore the given minimized client to the workspace it was minimized from and focus it.
+///
+/// This is a no-op if the client is not currently minimized.
+pub fn restore_minimized<X: XConn>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    let min = state.extension_or_default::<MinimizedClients>();
+    if !min.borrow_mut().restore(id, &mut state.client_set) {
+        return Ok(());
+    }
+
+    debug!(%id, "restoring minimized client");
+    set_hidden_state(id, false, x)?;
+
+    x.refresh(state)
+}
+
+fn set_hidden_state<X: XConn>(id: Xid, hidden: bool, x: &X) -> Result<()> {
+    x.set_net_wm_state(id, Atom::NetWmStateHidden, hidden)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pure::test_xid_stack_set;
+
+    fn client_set() -> ClientSet {
+        let mut cs = test_xid_stack_set(3, 1);
+        cs.add_invisible_workspace(MINIMIZED_TAG).unwrap();
+        for n in 1..=3 {
+            cs.insert(Xid(n));
+        }
+
+        cs
+    }
+
+    #[test]
+    fn minimize_removes_client_from_its_workspace() {
+        let mut cs = client_set();
+        let mut min = MinimizedClients::default();
+
+        assert!(min.minimize(Xid(2), &mut cs));
+        assert!(!min.minimize(Xid(2), &mut cs), "already minimized");
+        assert!(!min.minimize(Xid(9), &mut cs), "unknown client");
+
+        assert_eq!(cs.tag_for_client(&Xid(2)), Some(MINIMIZED_TAG));
+        assert!(!cs.current_workspace().contains(&Xid(2)));
+        assert_eq!(min.clients().collect::<Vec<_>>(), vec![Xid(2)]);
+    }
+
+    #[test]
+    fn clients_are_most_recent_first() {
+        let mut cs = client_set();
+        let mut min = MinimizedClients::default();
+
+        for n in 1..=3 {
+            min.minimize(Xid(n), &mut cs);
+        }
+
+        assert_eq!(min.last(), Some(Xid(3)));
+        assert_eq!(
+            min.clients().collect::<Vec<_>>(),
+            vec![Xid(3), Xid(2), Xid(1)]
+        );
+    }
+
+    #[test]
+    fn restore_returns_client_to_original_workspace() {
+        let mut cs = client_set();
+        let mut min = MinimizedClients::default();
+        min.minimize(Xid(2), &mut cs);
+        cs.focus_tag("2");
+
+        assert!(min.restore(Xid(2), &mut cs));
+        assert!(!min.restore(Xid(2), &mut cs), "no longer minimized");
+
+        assert_eq!(cs.current_tag(), "1");
+        assert_eq!(cs.current_client(), Some(&Xid(2)));
+        assert!(min.is_empty());
+    }
+
+    #[test]
+    fn prune_drops_clients_no_longer_minimized() {
+        let mut cs = client_set();
+        let mut min = MinimizedClients::default();
+        for n in 1..=3 {
+            min.minimize(Xid(n), &mut cs);
+        }
+
+        cs.remove_client(&Xid(1));
+        cs.move_client_to_tag(&Xid(2), "3");
+
+        assert!(min.prune(&cs));
+        assert_eq!(min.clients().collect::<Vec<_>>(), vec![Xid(3)]);
+    }
+}
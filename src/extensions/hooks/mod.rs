@@ -1,11 +1,56 @@
 //! Hook implementations and helpers for adding to your Penrose window manager
+pub mod auto_archive;
+pub mod client_quotas;
+pub mod crash_recovery;
 pub mod ewmh;
+pub mod journal;
+pub mod keyboard_layouts;
+pub mod layout_editor;
 pub mod manage;
+pub mod marks;
+pub mod minimize;
+pub mod monitor_profiles;
 pub mod named_scratchpads;
+pub mod opacity;
+pub mod polled;
+pub mod position_history;
+pub mod power_profiles;
+pub mod reserved_space;
+pub mod root_properties;
+pub mod rules;
 pub mod startup;
+pub mod theme;
 pub mod window_swallowing;
+pub mod workspace_pinning;
 
+pub use auto_archive::{add_auto_archive, AutoArchive};
+pub use client_quotas::{add_client_quotas, on_quota_exceeded, ClientQuotas, CrowdedWorkspaces};
+pub use crash_recovery::add_crash_recovery;
 pub use ewmh::add_ewmh_hooks;
-pub use named_scratchpads::{add_named_scratchpads, NamedScratchPad, ToggleNamedScratchPad};
+pub use journal::{add_activity_journal, ActivityJournal};
+pub use keyboard_layouts::{
+    add_keyboard_layout_memory, cycle_keyboard_layout, on_keyboard_layout_change, KeyboardLayouts,
+};
+pub use layout_editor::{add_layout_editor, LayoutEditor};
+pub use marks::{add_marks, Marks};
+pub use minimize::{add_minimize, MinimizedClients};
+pub use monitor_profiles::{
+    add_monitor_profiles, apply_monitor_profile, MonitorProfile, MonitorProfiles,
+};
+pub use named_scratchpads::{
+    add_named_scratchpads, DropDown, NamedScratchPad, ToggleNamedScratchPad,
+};
+pub use opacity::{ActiveOpacity, SetOpacity};
+pub use polled::add_polled_source;
+pub use position_history::{add_position_history, PositionHistory, RestorePreviousPosition};
+pub use power_profiles::{add_power_profiles, on_power_source_change, PowerProfiles};
+pub use reserved_space::{add_reserved_space, Region, ReservedSpace};
+pub use root_properties::{
+    add_root_properties, delete_root_property, root_property, set_root_property,
+    watch_root_property, RootProperties,
+};
+pub use rules::RulesFile;
 pub use startup::SpawnOnStartup;
+pub use theme::{add_theme_switching, ActiveTheme, Theme};
 pub use window_swallowing::WindowSwallowing;
+pub use workspace_pinning::{add_workspace_pinning, PinnedWorkspaces, PreferredOutput};
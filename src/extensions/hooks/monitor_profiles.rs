@@ -0,0 +1,461 @@
+//! Named monitor profiles that are applied automatically when outputs are connected.
+//!
+//! A [MonitorProfile] describes the position and resolution of each output that should be
+//! enabled when a particular set of outputs is connected (for example, a laptop panel on its
+//! own or a laptop docked with an external monitor) along with the outputs that specific
+//! workspaces should be shown on. Calling [add_monitor_profiles] registers hooks that apply
+//! the matching profile via RandR on startup and whenever the set of connected outputs
+//! changes, moving workspaces to their preferred outputs once the new screen layout has been
+//! picked up by the window manager.
+//!
+//! Profiles can also be applied by hand using [apply_monitor_profile], so long as the outputs
+//! they use are currently connected.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, pure::geometry::Rect, x11rb::RustConn};
+//! use penrose::extensions::hooks::monitor_profiles::{
+//!     add_monitor_profiles, MonitorProfile, MonitorProfiles,
+//! };
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! let laptop = MonitorProfile::new("laptop")
+//!     .primary_output("eDP-1", Rect::new(0, 0, 1920, 1080));
+//!
+//! let docked = MonitorProfile::new("docked")
+//!     .output("eDP-1", Rect::new(0, 0, 1920, 1080))
+//!     .primary_output("DP-1", Rect::new(1920, 0, 2560, 1440))
+//!     .workspace("1", "DP-1")
+//!     .workspace("9", "eDP-1");
+//!
+//! add_monitor_profiles(wm, MonitorProfiles::new(vec![laptop, docked]))
+//! # }
+//! ```
+use crate::{
+    builtin::actions::key_handler,
+    core::{bindings::KeyEventHandler, State, WindowManager},
+    pure::geometry::Rect,
+    x::{Output, OutputConfig, XConn, XConnExt, XEvent},
+    Result,
+};
+use tracing::{debug, error, info, warn};
+
+/// The desired configuration of outputs for a given set of connected outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorProfile {
+    name: String,
+    outputs: Vec<OutputConfig>,
+    workspaces: Vec<(String, String)>,
+}
+
+impl MonitorProfile {
+    /// Create a new, empty, profile with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outputs: Vec::new(),
+            workspaces: Vec::new(),
+        }
+    }
+
+    /// Enable the named output with the given position and resolution.
+    pub fn output(mut self, name: impl Into<String>, r: Rect) -> Self {
+        let name = name.into();
+        self.outputs.retain(|o| o.name != name);
+        self.outputs.push(OutputConfig {
+            name,
+            r,
+            primary: false,
+        });
+
+        self
+    }
+
+    /// Enable the named output with the given position and resolution as the primary output.
+    pub fn primary_output(mut self, name: impl Into<String>, r: Rect) -> Self {
+        for o in self.outputs.iter_mut() {
+            o.primary = false;
+        }
+        self = self.output(name, r);
+        if let Some(o) = self.outputs.last_mut() {
+            o.primary = true;
+        }
+
+        self
+    }
+
+    /// Show the workspace with the given tag on the named output when this profile is applied.
+    pub fn workspace(mut self, tag: impl Into<String>, output: impl Into<String>) -> Self {
+        self.workspaces.push((tag.into(), output.into()));
+        self
+    }
+
+    /// The name of this profile.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The outputs enabled by this profile.
+    pub fn outputs(&self) -> &[OutputConfig] {
+        &self.outputs
+    }
+
+    /// Whether or not this profile is for exactly the set of currently connected outputs.
+    pub fn matches(&self, outputs: &[Output]) -> bool {
+        let mut wanted: Vec<&str> = self.outputs.iter().map(|o| o.name.as_str()).collect();
+        wanted.sort();
+
+        !wanted.is_empty() && wanted == connected_names(outputs)
+    }
+
+    /// Whether or not the given outputs are already configured as specified by this profile.
+    pub fn is_applied(&self, outputs: &[Output]) -> bool {
+        let has_primary = self.outputs.iter().any(|o| o.primary);
+
+        outputs
+            .iter()
+            .all(|o| match self.outputs.iter().find(|c| c.name == o.name) {
+                Some(c) => o.r == Some(c.r) && (!has_primary || o.primary == c.primary),
+                None => o.r.is_none(),
+            })
+    }
+}
+
+fn connected_names(outputs: &[Output]) -> Vec<&str> {
+    let mut names: Vec<&str> = outputs
+        .iter()
+        .filter(|o| o.connected)
+        .map(|o| o.name.as_str())
+        .collect();
+    names.sort();
+
+    names
+}
+
+/// The known monitor profiles along with the one that is currently active.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MonitorProfiles {
+    profiles: Vec<MonitorProfile>,
+    active: Option<String>,
+    connected: Option<Vec<String>>,
+}
+
+impl MonitorProfiles {
+    /// Create a new set of profiles.
+    ///
+    /// If more than one profile matches the connected outputs then the first is used.
+    pub fn new(profiles: Vec<MonitorProfile>) -> Self {
+        Self {
+            profiles,
+            ..Self::default()
+        }
+    }
+
+    /// The name of the most recently applied profile, if there is one.
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// The profile with the given name, if there is one.
+    pub fn profile(&self, name: &str) -> Option<&MonitorProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// The first profile matching the given outputs, if there is one.
+    pub fn matching(&self, outputs: &[Output]) -> Option<&MonitorProfile> {
+        self.profiles.iter().find(|p| p.matches(outputs))
+    }
+
+    // Record the set of connected outputs, returning whether or not it has changed.
+    fn update_connected(&mut self, outputs: &[Output]) -> bool {
+        let connected: Vec<String> = connected_names(outputs)
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let changed = self.connected.as_ref() != Some(&connected);
+        self.connected = Some(connected);
+
+        changed
+    }
+}
+
+/// Add the given [MonitorProfiles] as a state extension along with the hooks required to
+/// apply the matching profile whenever the set of connected outputs changes.
+pub fn add_monitor_profiles<X>(
+    mut wm: WindowManager<X>,
+    profiles: MonitorProfiles,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(profiles);
+    wm.state.config.compose_or_set_startup_hook(startup_hook);
+    wm.state.config.compose_or_set_event_hook(event_hook);
+    wm.state
+        .config
+        .compose_or_set_screens_changed_hook(screens_changed_hook);
+
+    wm
+}
+
+/// Apply the profile matching the outputs that are connected on startup.
+pub fn startup_hook<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    apply_on_connection_change(state, x)
+}
+
+/// Apply the matching profile when RandR reports that the set of connected outputs has
+/// changed.
+pub fn event_hook<X: XConn>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool> {
+    // Failing to apply a profile should not prevent the new screens from being detected
+    if let XEvent::RandrNotify = event {
+        if let Err(e) = apply_on_connection_change(state, x) {
+            error!(%e, "unable to apply monitor profile");
+        }
+    }
+
+    Ok(true)
+}
+
+/// Move workspaces to their preferred outputs once the active profile has been applied.
+pub fn screens_changed_hook<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let profile = {
+        let profiles = state.extension::<MonitorProfiles>()?;
+        let profiles = profiles.borrow();
+        match profiles.active().and_then(|name| profiles.profile(name)) {
+            Some(p) => p.clone(),
+            None => return Ok(()),
+        }
+    };
+
+    let outputs = x.outputs()?;
+    if profile.is_applied(&outputs) {
+        place_workspaces(&profile, &outputs, state, x)?;
+    }
+
+    Ok(())
+}
+
+/// Apply the named profile if the outputs it uses are currently connected.
+pub fn apply_monitor_profile<X: XConn>(name: impl Into<String>) -> Box<dyn KeyEventHandler<X>> {
+    let name = name.into();
+
+    key_handler(move |state: &mut State<X>, x: &X| {
+        let outputs = x.outputs()?;
+        let profile = match state
+            .extension::<MonitorProfiles>()?
+            .borrow()
+            .profile(&name)
+        {
+            Some(p) => p.clone(),
+            None => {
+                warn!(%name, "unknown monitor profile");
+                return Ok(());
+            }
+        };
+
+        if !profile.matches(&outputs) {
+            warn!(%name, "connected outputs do not match monitor profile");
+            return Ok(());
+        }
+
+        apply(&profile, &outputs, state, x)
+    })
+}
+
+// Profiles are only applied automatically when outputs are connected or disconnected so that
+// manual changes made using tools like xrandr are left alone.
+fn apply_on_connection_change<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let outputs = x.outputs()?;
+    let profiles = state.extension::<MonitorProfiles>()?;
+    if !profiles.borrow_mut().update_connected(&outputs) {
+        return Ok(());
+    }
+
+    let profile = profiles.borrow().matching(&outputs).cloned();
+    match profile {
+        Some(p) => apply(&p, &outputs, state, x),
+        None => {
+            debug!("no monitor profile matches the connected outputs");
+            profiles.borrow_mut().active = None;
+            Ok(())
+        }
+    }
+}
+
+fn apply<X: XConn>(
+    profile: &MonitorProfile,
+    outputs: &[Output],
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    state.extension::<MonitorProfiles>()?.borrow_mut().active = Some(profile.name.clone());
+
+    if profile.is_applied(outputs) {
+        return place_workspaces(profile, outputs, state, x);
+    }
+
+    // Workspaces are placed by the screens changed hook once the new screen layout is known
+    info!(name = %profile.name, "applying monitor profile");
+    x.configure_outputs(&profile.outputs)
+}
+
+fn place_workspaces<X: XConn>(
+    profile: &MonitorProfile,
+    outputs: &[Output],
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    let cs = &mut state.client_set;
+    if cs.is_per_screen() {
+        return Ok(()); // workspaces are already tied to their own screen
+    }
+
+    let focused = cs.current_screen().index();
+    let mut moved = false;
+
+    for (tag, name) in profile.workspaces.iter() {
+        let r = match outputs.iter().find(|o| &o.name == name).and_then(|o| o.r) {
+            Some(r) => r,
+            None => continue,
+        };
+
+        let index = match cs.screens().find(|s| s.r.contains_point(r.midpoint())) {
+            Some(s) => s.index(),
+            None => continue,
+        };
+
+        if !cs.contains_tag(tag) || cs.tag_for_screen(index) == Some(tag) {
+            continue;
+        }
+
+        debug!(%tag, output = %name, "moving workspace to preferred output");
+        cs.focus_screen(index);
+        cs.pull_tag_to_screen(tag);
+        moved = true;
+    }
+
+    if !moved {
+        return Ok(());
+    }
+
+    cs.focus_screen(focused);
+    x.refresh(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pure::geometry::Rotation,
+        testing::{HeadlessConn, Scenario},
+    };
+    use simple_test_case::test_case;
+    use std::collections::HashMap;
+
+    const LAPTOP: Rect = Rect::new(0, 0, 1920, 1080);
+    const EXTERNAL: Rect = Rect::new(1920, 0, 2560, 1440);
+
+    fn output(name: &str, connected: bool, r: Option<Rect>, primary: bool) -> Output {
+        Output {
+            name: name.to_string(),
+            connected,
+            r,
+            primary,
+            rotation: Rotation::Normal,
+        }
+    }
+
+    fn docked() -> MonitorProfile {
+        MonitorProfile::new("docked")
+            .output("eDP-1", LAPTOP)
+            .primary_output("HDMI-1", EXTERNAL)
+            .workspace("9", "HDMI-1")
+    }
+
+    fn profiles() -> MonitorProfiles {
+        let laptop = MonitorProfile::new("laptop").primary_output("eDP-1", LAPTOP);
+
+        MonitorProfiles::new(vec![laptop, docked()])
+    }
+
+    #[test_case(vec![output("eDP-1", true, None, false)], false; "missing output")]
+    #[test_case(vec![output("HDMI-1", true, None, false), output("eDP-1", true, None, false)], true; "exact match")]
+    #[test_case(vec![output("eDP-1", true, None, false), output("HDMI-1", false, None, false)], false; "disconnected")]
+    #[test_case(vec![output("eDP-1", true, None, false), output("HDMI-1", true, None, false), output("DP-1", true, None, false)], false; "extra output")]
+    #[test]
+    fn matches(outputs: Vec<Output>, expected: bool) {
+        assert_eq!(docked().matches(&outputs), expected);
+    }
+
+    #[test_case(Some(LAPTOP), Some(EXTERNAL), None, true; "applied")]
+    #[test_case(Some(LAPTOP), None, None, false; "output disabled")]
+    #[test_case(Some(LAPTOP), Some(LAPTOP), None, false; "wrong position")]
+    #[test_case(Some(LAPTOP), Some(EXTERNAL), Some(LAPTOP), false; "extra output enabled")]
+    #[test]
+    fn is_applied(
+        laptop: Option<Rect>,
+        external: Option<Rect>,
+        other: Option<Rect>,
+        expected: bool,
+    ) {
+        let outputs = vec![
+            output("eDP-1", true, laptop, false),
+            output("HDMI-1", true, external, true),
+            output("DP-1", false, other, false),
+        ];
+
+        assert_eq!(docked().is_applied(&outputs), expected);
+    }
+
+    #[test]
+    fn only_one_output_is_primary() {
+        let p = docked().primary_output("eDP-1", LAPTOP);
+        let primary: Vec<&str> = p
+            .outputs()
+            .iter()
+            .filter(|o| o.primary)
+            .map(|o| o.name.as_str())
+            .collect();
+
+        assert_eq!(primary, vec!["eDP-1"]);
+    }
+
+    #[test]
+    fn profiles_are_applied_when_outputs_change() {
+        let conn = HeadlessConn::new(vec![LAPTOP]);
+        conn.set_outputs(vec![
+            output("eDP-1", true, Some(LAPTOP), true),
+            output("HDMI-1", false, None, false),
+        ]);
+        let wm =
+            WindowManager::new(Default::default(), HashMap::new(), HashMap::new(), conn).unwrap();
+        let s = Scenario::new(add_monitor_profiles(wm, profiles())).unwrap();
+        let active = |s: &Scenario| {
+            let profiles = s.state().extension::<MonitorProfiles>().unwrap();
+            let active = profiles.borrow().active().map(String::from);
+            active
+        };
+
+        assert_eq!(active(&s).as_deref(), Some("laptop"));
+
+        s.conn().set_outputs(vec![
+            output("eDP-1", true, Some(LAPTOP), true),
+            output("HDMI-1", true, None, false),
+        ]);
+        let s = s.act(|_, _| Ok(()));
+        let outputs = s.conn().outputs().unwrap();
+
+        assert_eq!(active(&s).as_deref(), Some("docked"));
+        assert!(docked().is_applied(&outputs));
+        assert_eq!(s.state().client_set.screens().count(), 2);
+        assert_eq!(s.state().client_set.tag_for_screen(1), Some("9"));
+
+        s.conn().set_outputs(vec![
+            output("eDP-1", true, Some(LAPTOP), false),
+            output("HDMI-1", false, Some(EXTERNAL), true),
+        ]);
+        let s = s.act(|_, _| Ok(()));
+
+        assert_eq!(active(&s).as_deref(), Some("laptop"));
+        assert_eq!(s.state().client_set.screens().count(), 1);
+    }
+}
@@ -1,12 +1,18 @@
 //! Support for managing multiple floating scratchpad programs that can be
 //! toggled on or off on the active workspace.
+//!
+//! Scratchpads can optionally be displayed as a [DropDown] ("quake" style) window that
+//! occupies the top of the focused screen, sliding in and out when toggled. By default
+//! scratchpads are shown on the focused screen but they can be tied to a specific screen
+//! (such as the screen showing the primary output) using [NamedScratchPad::on_screen].
 use crate::{
     core::{bindings::KeyEventHandler, hooks::ManageHook, State, WindowManager},
+    pure::{geometry::Rect, ScreenTarget},
     util::spawn,
-    x::{Query, XConn, XConnExt, XEvent},
+    x::{ClientConfig, Query, XConn, XConnExt, XEvent},
     Result, Xid,
 };
-use std::{borrow::Cow, collections::HashMap, fmt};
+use std::{borrow::Cow, collections::HashMap, fmt, time::Duration, vec};
 use tracing::{debug, error, warn};
 
 /// The tag used for a placeholder Workspace that holds scratchpad windows when
@@ -23,6 +29,11 @@ where
     client: Option<Xid>,
     query: Box<dyn Query<X>>,
     hook: Box<dyn ManageHook<X>>,
+    dropdown: Option<DropDown>,
+    claim_existing: bool,
+    screen: Option<ScreenTarget>,
+    animation: usize,
+    hiding: bool,
 }
 
 impl<X: XConn> fmt::Debug for NamedScratchPad<X> {
@@ -31,6 +42,9 @@ impl<X: XConn> fmt::Debug for NamedScratchPad<X> {
             .field("name", &self.name)
             .field("prog", &self.prog)
             .field("client", &self.client)
+            .field("dropdown", &self.dropdown)
+            .field("claim_existing", &self.claim_existing)
+            .field("screen", &self.screen)
             .finish()
     }
 }
@@ -58,6 +72,11 @@ where
             client: None,
             query: Box::new(query),
             hook: Box::new(manage_hook),
+            dropdown: None,
+            claim_existing: false,
+            screen: None,
+            animation: 0,
+            hiding: false,
         };
 
         (
@@ -68,6 +87,171 @@ where
             },
         )
     }
+
+    /// Create a new named scratchpad that is displayed as a [DropDown] window at the top
+    /// of the focused screen.
+    pub fn new_dropdown<Q>(
+        name: impl Into<Cow<'static, str>>,
+        prog: impl Into<Cow<'static, str>>,
+        query: Q,
+        dropdown: DropDown,
+    ) -> (Self, ToggleNamedScratchPad)
+    where
+        Q: Query<X> + 'static,
+    {
+        let (mut nsp, toggle) = Self::new(name, prog, query, dropdown, false);
+        nsp.dropdown = Some(dropdown);
+
+        (nsp, toggle)
+    }
+
+    /// Allow this scratchpad to claim an already running client window matching its query
+    /// when it is toggled without an active client, rather than always spawning a new one.
+    ///
+    /// The first matching client that is not already in use by another scratchpad is claimed
+    /// and its [ManageHook] is run before it is moved to the focused workspace.
+    pub fn claim_existing(mut self) -> Self {
+        self.claim_existing = true;
+
+        self
+    }
+
+    /// Always show this scratchpad on the given screen rather than the focused screen, moving
+    /// focus to that screen when the scratchpad is shown.
+    ///
+    /// If the target screen is not connected then the focused screen is used.
+    pub fn on_screen(mut self, screen: impl Into<ScreenTarget>) -> Self {
+        self.screen = Some(screen.into());
+
+        self
+    }
+}
+
+/// Settings for displaying a [NamedScratchPad] as a drop-down window that occupies the
+/// full width of the top of the focused screen.
+///
+/// Visible drop-down windows are kept at the top of the stacking order so that they are
+/// displayed above other floating (and fullscreen) windows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DropDown {
+    /// The fraction of the screen height that the window should occupy.
+    pub height: f64,
+    /// The number of frames to use when sliding the window in and out of view.
+    /// Setting this to 0 disables the animation.
+    pub animation_frames: u32,
+    /// The delay between each frame of the slide animation.
+    pub frame_delay: Duration,
+    /// Whether or not the window should be hidden automatically when it loses focus.
+    pub hide_on_focus_loss: bool,
+}
+
+impl Default for DropDown {
+    fn default() -> Self {
+        Self {
+            height: 0.4,
+            animation_frames: 0,
+            frame_delay: Duration::from_millis(10),
+            hide_on_focus_loss: false,
+        }
+    }
+}
+
+impl DropDown {
+    /// The position of the drop-down window on a screen with the given dimensions.
+    pub fn rect(&self, r_screen: &Rect) -> Rect {
+        let h = (r_screen.h as f64 * self.height.clamp(0.0, 1.0)) as u32;
+
+        Rect::new(r_screen.x, r_screen.y, r_screen.w, h.max(1))
+    }
+
+    // The position of the top left corner of the window for each frame of the slide animation
+    // when showing it, sliding down from above the top of the screen and ending with the final
+    // position of the window.
+    fn frames(&self, r: Rect) -> Vec<(i32, i32)> {
+        let n = self.animation_frames.max(1) as i32;
+        let (x, y, h) = (r.x as i32, r.y as i32, r.h as i32);
+
+        (1..=n).map(|i| (x, y - h + h * i / n)).collect()
+    }
+}
+
+// A slide animation of a drop-down scratchpad that is in progress. Each frame is shown from
+// the main event loop once the frame delay has elapsed, stopping early if the scratchpad is
+// toggled again before the animation completes.
+struct Slide {
+    name: Cow<'static, str>,
+    id: Xid,
+    animation: usize,
+    frames: vec::IntoIter<(i32, i32)>,
+    frame_delay: Duration,
+    hide: bool,
+}
+
+impl Slide {
+    // Begin a new animation for the named scratchpad, cancelling any that are in progress.
+    fn start<X: XConn + 'static>(
+        name: Cow<'static, str>,
+        id: Xid,
+        dropdown: DropDown,
+        frames: Vec<(i32, i32)>,
+        hide: bool,
+        state: &mut State<X>,
+        x: &X,
+    ) -> Result<()> {
+        let s = state.extension::<NamedScratchPadState<X>>()?;
+        let animation = match s.borrow_mut().0.get_mut(&name) {
+            Some(nsp) => {
+                nsp.animation += 1;
+                nsp.hiding = hide;
+                nsp.animation
+            }
+            None => return Ok(()),
+        };
+
+        let slide = Self {
+            name,
+            id,
+            animation,
+            frames: frames.into_iter(),
+            frame_delay: dropdown.frame_delay,
+            hide,
+        };
+
+        slide.next_frame(state, x)
+    }
+
+    fn next_frame<X: XConn + 'static>(mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        let s = state.extension::<NamedScratchPadState<X>>()?;
+        if s.borrow().0.get(&self.name).map(|nsp| nsp.animation) != Some(self.animation) {
+            return Ok(());
+        }
+
+        if let Some((fx, fy)) = self.frames.next() {
+            x.set_client_config(self.id, &[ClientConfig::Move(fx, fy)])?;
+            let delay = self.frame_delay;
+            state.run_after(delay, move |state, x| self.next_frame(state, x));
+
+            return Ok(());
+        }
+
+        if !self.hide {
+            return Ok(());
+        }
+
+        if let Some(nsp) = s.borrow_mut().0.get_mut(&self.name) {
+            nsp.hiding = false;
+        }
+
+        x.modify_and_refresh(state, |cs| cs.move_client_to_tag(&self.id, NSP_TAG))
+    }
+}
+
+impl<X: XConn> ManageHook<X> for DropDown {
+    fn call(&mut self, client: Xid, state: &mut State<X>, _: &X) -> Result<()> {
+        let r = self.rect(&state.client_set.screens.focus.r);
+
+        state.client_set.float(client, r)
+    }
 }
 
 // Private wrapper type to ensure that only this module can access this state extension
@@ -89,6 +273,8 @@ where
         .map(|nsp| (nsp.name.clone(), nsp))
         .collect();
 
+    let has_dropdowns = state.values().any(|nsp| nsp.dropdown.is_some());
+
     wm.state.add_extension(NamedScratchPadState(state));
     wm.state
         .client_set
@@ -97,6 +283,10 @@ where
     wm.state.config.compose_or_set_manage_hook(manage_hook);
     wm.state.config.compose_or_set_event_hook(event_hook);
 
+    if has_dropdowns {
+        wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+    }
+
     wm
 }
 
@@ -135,6 +325,46 @@ pub fn event_hook<X: XConn + 'static>(event: &XEvent, state: &mut State<X>, _: &
     Ok(true)
 }
 
+/// Keep visible [DropDown] scratchpads above other windows and hide those that have lost
+/// focus if they are configured to do so.
+///
+/// Hiding a scratchpad requires a refresh so it is run from the main event loop once the
+/// current refresh has completed.
+pub fn refresh_hook<X: XConn + 'static>(state: &mut State<X>, x: &X) -> Result<()> {
+    let s = state.extension::<NamedScratchPadState<X>>()?;
+    let focused = state.client_set.current_client().copied();
+    let mut to_hide = Vec::new();
+
+    for sp in s.borrow().0.values() {
+        let (id, dropdown) = match (sp.client, sp.dropdown) {
+            (Some(id), Some(dropdown)) => (id, dropdown),
+            _ => continue,
+        };
+
+        if !state
+            .client_set
+            .screens()
+            .any(|s| s.workspace.contains(&id))
+        {
+            continue;
+        }
+
+        x.set_client_config(id, &[ClientConfig::StackTop])?;
+        if dropdown.hide_on_focus_loss && !sp.hiding && focused != Some(id) {
+            debug!(%sp.name, %id, "drop-down scratchpad lost focus: hiding");
+            to_hide.push((sp.name.clone(), id, dropdown));
+        }
+    }
+
+    for (name, id, dropdown) in to_hide {
+        state.run_after(Duration::ZERO, move |state, x| {
+            hide_dropdown(name, id, dropdown, state, x)
+        });
+    }
+
+    Ok(())
+}
+
 /// Toggle the visibility of a NamedScratchPad.
 ///
 /// This will spawn the requested client program if it isn't currently running or
@@ -152,24 +382,48 @@ impl<X: XConn + 'static> KeyEventHandler<X> for ToggleNamedScratchPad {
         let _s = state.extension::<NamedScratchPadState<X>>()?;
         let mut s = _s.borrow_mut();
         let name = self.name.as_ref();
+        let in_use: Vec<Xid> = s.0.values().flat_map(|sp| sp.client).collect();
+        let target =
+            s.0.get(&self.name)
+                .and_then(|nsp| nsp.screen)
+                .and_then(|screen| state.client_set.screen_index_for(screen));
+        // Scratchpads that are sliding out of view are shown again rather than hidden
+        let hiding = s.0.get(&self.name).is_some_and(|nsp| nsp.hiding);
 
-        let (id, hook) = match s.0.get_mut(&self.name) {
+        let (id, hook, dropdown) = match s.0.get_mut(&self.name) {
             // Active client somewhere in the StackSet
             Some(NamedScratchPad {
                 client: Some(id),
                 hook,
+                dropdown,
                 ..
             }) if state.client_set.contains(id) => {
                 debug!(%id, %name, "NamedScratchPad client exists in state");
-                (*id, hook)
+                (*id, hook, *dropdown)
             }
 
             // No active client or client is no longer in state
-            Some(nsp) => {
-                debug!(%nsp.prog, %name, ?nsp.client, "spawning NamedScratchPad program");
-                nsp.client = None;
-                return spawn(nsp.prog.as_ref());
-            }
+            Some(nsp) => match claimable_client(nsp, &in_use, state, x)? {
+                Some(id) => {
+                    debug!(%id, %name, "claiming existing client for NamedScratchPad");
+                    nsp.client = Some(id);
+                    nsp.hook.call(id, state, x)?;
+                    // Hide the client so that it is always shown by the toggle below
+                    state.client_set.move_client_to_tag(&id, NSP_TAG);
+
+                    (id, &mut nsp.hook, nsp.dropdown)
+                }
+
+                None => {
+                    debug!(%nsp.prog, %name, ?nsp.client, "spawning NamedScratchPad program");
+                    nsp.client = None;
+                    // New clients are placed on the focused screen once they are mapped
+                    if let Some(index) = target {
+                        state.client_set.focus_screen(index);
+                    }
+                    return spawn(nsp.prog.as_ref());
+                }
+            },
 
             // The user created a ToggleNamedScratchPad but didn't register the scratchpad
             None => {
@@ -186,7 +440,24 @@ impl<X: XConn + 'static> KeyEventHandler<X> for ToggleNamedScratchPad {
             "Toggling nsp client"
         );
 
-        if state.client_set.current_workspace().contains(&id) {
+        let visible = !hiding && is_visible_on_target(id, target, state);
+        if !visible {
+            if let Some(index) = target {
+                state.client_set.focus_screen(index);
+            }
+        }
+
+        if let Some(dropdown) = dropdown {
+            // Release the state extension so that it is available to the refresh hook
+            drop(s);
+            if visible {
+                return hide_dropdown(self.name.clone(), id, dropdown, state, x);
+            }
+
+            return show_dropdown(self.name.clone(), id, dropdown, state, x);
+        }
+
+        if visible {
             // Toggle off: hiding the client on our invisible workspace
             debug!(%id, "current workspace contains target client: moving to NSP tag");
             state.client_set.move_client_to_tag(&id, NSP_TAG);
@@ -206,3 +477,196 @@ impl<X: XConn + 'static> KeyEventHandler<X> for ToggleNamedScratchPad {
         x.refresh(state)
     }
 }
+
+// The first managed client matching the query for the given scratchpad that is not already
+// in use by a scratchpad, if the scratchpad is permitted to claim existing clients.
+fn claimable_client<X: XConn>(
+    nsp: &NamedScratchPad<X>,
+    in_use: &[Xid],
+    state: &State<X>,
+    x: &X,
+) -> Result<Option<Xid>> {
+    if !nsp.claim_existing {
+        return Ok(None);
+    }
+
+    for &id in state.client_set.clients() {
+        if !in_use.contains(&id) && nsp.query.run(id, x)? {
+            return Ok(Some(id));
+        }
+    }
+
+    Ok(None)
+}
+
+// Whether or not the client is visible on the workspace of the target screen (or the focused
+// workspace if there is no target).
+fn is_visible_on_target<X: XConn>(id: Xid, target: Option<usize>, state: &State<X>) -> bool {
+    let cs = &state.client_set;
+
+    match target.and_then(|index| cs.tag_for_screen(index)) {
+        Some(tag) => cs.workspace(tag).is_some_and(|w| w.contains(&id)),
+        None => cs.current_workspace().contains(&id),
+    }
+}
+
+// Show the window in its final position before sliding it down into view from above the
+// top of the screen.
+fn show_dropdown<X: XConn + 'static>(
+    name: Cow<'static, str>,
+    id: Xid,
+    dropdown: DropDown,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    debug!(%id, "showing drop-down scratchpad");
+    let r = dropdown.rect(&state.client_set.screens.focus.r);
+
+    x.modify_and_refresh(state, |cs| {
+        cs.move_client_to_current_tag(&id);
+        cs.float_unchecked(id, r);
+    })?;
+    x.set_client_config(id, &[ClientConfig::StackTop])?;
+
+    Slide::start(name, id, dropdown, dropdown.frames(r), false, state, x)
+}
+
+// Slide the window up out of view before moving it to the scratchpad workspace.
+fn hide_dropdown<X: XConn + 'static>(
+    name: Cow<'static, str>,
+    id: Xid,
+    dropdown: DropDown,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()> {
+    debug!(%id, "hiding drop-down scratchpad");
+    let r = x.client_geometry(id)?;
+    let frames = dropdown.frames(r).into_iter().rev().skip(1).collect();
+
+    Slide::start(name, id, dropdown, frames, true, state, x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::Config,
+        extensions::hooks::manage::DefaultTiled,
+        map,
+        testing::{parse_keybindings, HeadlessConn, Scenario},
+        x::{query::ClassName, StubXConn},
+    };
+    use simple_test_case::test_case;
+
+    struct IsOneOf(&'static [u32]);
+
+    impl Query<StubXConn> for IsOneOf {
+        fn run(&self, id: Xid, _: &StubXConn) -> Result<bool> {
+            Ok(self.0.contains(&id))
+        }
+    }
+
+    #[test_case(false, &[], None; "claiming disabled")]
+    #[test_case(true, &[3], Some(Xid(2)); "matching client")]
+    #[test_case(true, &[2], Some(Xid(3)); "skips clients in use")]
+    #[test_case(true, &[2, 3], None; "all matching clients in use")]
+    #[test]
+    fn claimable_client_respects_settings(claim: bool, in_use: &[u32], expected: Option<Xid>) {
+        let x = StubXConn;
+        let mut state = State::try_new(Default::default(), &x).expect("test state");
+        for n in 1..=4 {
+            state.client_set.insert(Xid(n));
+        }
+
+        let (mut nsp, _) =
+            NamedScratchPad::new("test", "test", IsOneOf(&[2, 3]), DefaultTiled, false);
+        if claim {
+            nsp = nsp.claim_existing();
+        }
+        let in_use: Vec<Xid> = in_use.iter().map(|&n| Xid(n)).collect();
+
+        let res = claimable_client(&nsp, &in_use, &state, &x).unwrap();
+
+        assert_eq!(res, expected);
+    }
+
+    #[test_case(0.4, Rect::new(10, 20, 1000, 200); "fraction of screen")]
+    #[test_case(1.0, Rect::new(10, 20, 1000, 500); "full screen")]
+    #[test_case(1.5, Rect::new(10, 20, 1000, 500); "clamped to screen")]
+    #[test_case(0.0, Rect::new(10, 20, 1000, 1); "minimum height")]
+    #[test]
+    fn dropdown_rect(height: f64, expected: Rect) {
+        let dropdown = DropDown {
+            height,
+            ..Default::default()
+        };
+
+        assert_eq!(dropdown.rect(&Rect::new(10, 20, 1000, 500)), expected);
+    }
+
+    #[test_case(0, &[20]; "no animation")]
+    #[test_case(1, &[20]; "single frame")]
+    #[test_case(4, &[-55, -30, -5, 20]; "multiple frames")]
+    #[test]
+    fn dropdown_frames_slide_to_final_position(animation_frames: u32, ys: &[i32]) {
+        let dropdown = DropDown {
+            animation_frames,
+            ..Default::default()
+        };
+        let r = Rect::new(10, 20, 200, 100);
+
+        let frames = dropdown.frames(r);
+        let frame_ys: Vec<i32> = frames.iter().map(|&(_, y)| y).collect();
+
+        assert_eq!(frame_ys, ys);
+        assert!(frames.iter().all(|&(x, _)| x == 10));
+    }
+
+    fn dropdown_scenario(dropdown: DropDown) -> Scenario {
+        let (nsp, toggle) =
+            NamedScratchPad::new_dropdown("term", "term", ClassName("term"), dropdown);
+        let bindings = map! {
+            "M-t" => Box::new(toggle) as Box<dyn KeyEventHandler<HeadlessConn>>,
+        };
+        let key_bindings = parse_keybindings(bindings).unwrap();
+        let conn = HeadlessConn::default();
+        let wm = WindowManager::new(Config::default(), key_bindings, HashMap::new(), conn).unwrap();
+
+        Scenario::new(add_named_scratchpads(wm, vec![nsp]))
+            .unwrap()
+            .open_window("term", "term")
+            .expect_floating("term")
+    }
+
+    #[test]
+    fn dropdowns_losing_focus_are_hidden_after_the_refresh() {
+        let s = dropdown_scenario(DropDown {
+            hide_on_focus_loss: true,
+            ..Default::default()
+        })
+        .open_window("a", "a")
+        .expect_on_workspace("term", "1");
+
+        s.wait(Duration::ZERO)
+            .expect_on_workspace("term", NSP_TAG)
+            .expect_visible(["a"]);
+    }
+
+    #[test]
+    fn dropdowns_are_hidden_once_they_have_slid_out_of_view() {
+        let s = dropdown_scenario(DropDown {
+            animation_frames: 4,
+            frame_delay: Duration::ZERO,
+            ..Default::default()
+        })
+        .press("M-t")
+        .expect_on_workspace("term", "1");
+
+        (0..3)
+            .fold(s, |s, _| s.wait(Duration::ZERO))
+            .expect_on_workspace("term", NSP_TAG)
+            .press("M-t")
+            .expect_on_workspace("term", "1")
+            .expect_geometry("term", Rect::new(0, 0, 1916, 428));
+    }
+}
@@ -0,0 +1,215 @@
+//! Per-client opacity for use with a compositor.
+//!
+//! Opacity is set using the `_NET_WM_WINDOW_OPACITY` property which is read by compositors
+//! such as picom: it has no effect if a compositor is not running.
+//!
+//! Opacity can be set for individual clients using the [SetOpacity] [ManageHook] (or the
+//! `opacity` action in a [RulesFile][0]) and the [set_focused_opacity] and
+//! [reset_focused_opacity] actions. The [ActiveOpacity] refresh hook can be used to
+//! automatically set an opacity for the focused and unfocused clients on focus change. Clients
+//! that have had their opacity set explicitly are not modified by [ActiveOpacity].
+//!
+//!   [0]: crate::extensions::hooks::RulesFile
+use crate::{
+    builtin::actions::key_handler,
+    core::{
+        bindings::KeyEventHandler,
+        hooks::{ManageHook, StateHook},
+        State,
+    },
+    x::{atom::Atom, property::Prop, XConn, XConnExt},
+    Result, Xid,
+};
+use std::collections::HashMap;
+use tracing::warn;
+
+// Clients that have had their opacity set explicitly rather than by ActiveOpacity.
+#[derive(Debug, Default)]
+struct OpacityOverrides(HashMap<Xid, f64>);
+
+/// Set the opacity of the given client, where 0.0 is fully transparent and 1.0 is fully
+/// opaque. Values outside of this range are clamped.
+pub fn set_opacity<X: XConn>(id: Xid, opacity: f64, x: &X) -> Result<()> {
+    x.set_prop(
+        id,
+        Atom::NetWmWindowOpacity.as_ref(),
+        Prop::Cardinal(vec![opacity_to_cardinal(opacity)]),
+    )
+}
+
+fn opacity_to_cardinal(opacity: f64) -> u32 {
+    (opacity.clamp(0.0, 1.0) * u32::MAX as f64).round() as u32
+}
+
+fn set_override<X: XConn>(id: Xid, opacity: f64, state: &mut State<X>, x: &X) -> Result<()> {
+    state
+        .extension_or_default::<OpacityOverrides>()
+        .borrow_mut()
+        .0
+        .insert(id, opacity);
+
+    set_opacity(id, opacity, x)
+}
+
+/// Set the opacity of the currently focused client.
+///
+/// The client will keep this opacity until [reset_focused_opacity] is called.
+pub fn set_focused_opacity<X: XConn>(opacity: f64) -> Box<dyn KeyEventHandler<X>> {
+    key_handler(
+        move |state: &mut State<X>, x: &X| match state.client_set.current_client() {
+            Some(&id) => set_override(id, opacity, state, x),
+            None => Ok(()),
+        },
+    )
+}
+
+/// Clear any opacity set for the currently focused client, returning it to full opacity
+/// or the opacity set by [ActiveOpacity] if it is in use.
+pub fn reset_focused_opacity<X: XConn>() -> Box<dyn KeyEventHandler<X>> {
+    key_handler(|state: &mut State<X>, x: &X| {
+        let id = match state.client_set.current_client() {
+            Some(&id) => id,
+            None => return Ok(()),
+        };
+
+        state
+            .extension_or_default::<OpacityOverrides>()
+            .borrow_mut()
+            .0
+            .remove(&id);
+        x.delete_prop(id, Atom::NetWmWindowOpacity.as_ref())?;
+
+        x.refresh(state)
+    })
+}
+
+/// Set the opacity of matching clients when they are first managed.
+///
+/// # Example
+/// ```no_run
+/// # use penrose::{extensions::hooks::opacity::SetOpacity, x::query::ClassName};
+/// // Make a scratchpad terminal slightly translucent
+/// let hook = (ClassName("scratch-term"), SetOpacity(0.85));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SetOpacity(pub f64);
+
+impl<X: XConn> ManageHook<X> for SetOpacity {
+    fn call(&mut self, client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+        set_override(client, self.0, state, x)
+    }
+}
+
+/// A refresh hook that sets the opacity of clients when focus changes: the focused client
+/// is set to `active` and all other clients are set to `inactive`.
+///
+/// Clients that have had their opacity set explicitly using [SetOpacity] or
+/// [set_focused_opacity] are left unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActiveOpacity {
+    active: f64,
+    inactive: f64,
+}
+
+impl ActiveOpacity {
+    /// Create a new [ActiveOpacity] hook with the given opacity values.
+    ///
+    /// # Panics
+    /// Panics if `active` or `inactive` are not in the range `0.0..=1.0`.
+    pub fn new(active: f64, inactive: f64) -> Self {
+        if !((0.0..=1.0).contains(&active) && (0.0..=1.0).contains(&inactive)) {
+            panic!("opacity must be between 0.0 and 1.0: got active={active}, inactive={inactive}")
+        }
+
+        Self { active, inactive }
+    }
+
+    // The clients whose opacity needs updating following the last refresh along with
+    // the opacity they should be set to.
+    fn updates<X: XConn>(&self, state: &mut State<X>) -> Vec<(Xid, f64)> {
+        let overrides = state.extension_or_default::<OpacityOverrides>();
+        let overrides = overrides.borrow();
+        let focused = state.client_set.current_client().copied();
+        let previous = state.diff.before.focused_client;
+
+        let mut ids: Vec<Xid> = state.diff.new_clients().copied().collect();
+        if previous != focused {
+            ids.extend(previous);
+        }
+        ids.extend(focused);
+        ids.sort();
+        ids.dedup();
+
+        ids.into_iter()
+            .filter(|id| state.client_set.contains(id) && !overrides.0.contains_key(id))
+            .filter(|id| !state.client_set.bars.contains_key(id))
+            .map(|id| {
+                let opacity = if Some(id) == focused {
+                    self.active
+                } else {
+                    self.inactive
+                };
+
+                (id, opacity)
+            })
+            .collect()
+    }
+}
+
+impl<X: XConn> StateHook<X> for ActiveOpacity {
+    fn call(&mut self, state: &mut State<X>, x: &X) -> Result<()> {
+        for (id, opacity) in self.updates(state) {
+            if let Err(e) = set_opacity(id, opacity, x) {
+                warn!(%id, %e, "unable to set client opacity");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::Config, x::StubXConn};
+    use simple_test_case::test_case;
+
+    #[test_case(0.0, 0; "transparent")]
+    #[test_case(1.0, u32::MAX; "opaque")]
+    #[test_case(0.5, u32::MAX / 2 + 1; "half")]
+    #[test_case(-1.0, 0; "clamped below")]
+    #[test_case(2.0, u32::MAX; "clamped above")]
+    #[test]
+    fn opacity_to_cardinal_works(opacity: f64, expected: u32) {
+        assert_eq!(opacity_to_cardinal(opacity), expected);
+    }
+
+    #[test_case(false, &[(2, 0.8), (4, 1.0)]; "no overrides")]
+    #[test_case(true, &[(4, 1.0)]; "previous focus overridden")]
+    #[test]
+    fn updates_only_include_clients_that_changed_focus(overridden: bool, expected: &[(u32, f64)]) {
+        let mut state = State::try_new(Config::default(), &StubXConn).unwrap();
+        for n in 1..=3 {
+            state.client_set.insert(Xid(n));
+        }
+        state.client_set.focus_client(&Xid(2));
+        let ss = state.position_and_snapshot(&StubXConn);
+        state.diff.update(ss);
+
+        if overridden {
+            state
+                .extension_or_default::<OpacityOverrides>()
+                .borrow_mut()
+                .0
+                .insert(Xid(2), 0.5);
+        }
+
+        state.client_set.insert(Xid(4));
+        let ss = state.position_and_snapshot(&StubXConn);
+        state.diff.update(ss);
+
+        let expected: Vec<_> = expected.iter().map(|&(id, o)| (Xid(id), o)).collect();
+
+        assert_eq!(ActiveOpacity::new(1.0, 0.8).updates(&mut state), expected);
+    }
+}
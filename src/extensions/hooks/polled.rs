@@ -0,0 +1,197 @@
+//! Tracking external state that has to be polled rather than being reported through X events.
+//!
+//! [add_polled_source] samples a value (such as the current power source or the system colour
+//! scheme) from a background thread so that slow reads never block the window manager, even
+//! when it is starting up. The most recently sampled value is handed to the main event loop on
+//! the same interval using [State::run_after][0].
+//!
+//!   [0]: crate::core::State::run_after
+use crate::{
+    core::{State, WindowManager},
+    x::XConn,
+    Result,
+};
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+use tracing::debug;
+
+// How often the main event loop checks for the first sample before falling back to checking
+// on the poll interval.
+const FIRST_SAMPLE_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+// The most recent value sampled by the polling thread.
+#[derive(Debug)]
+struct Latest<T> {
+    sampled: bool,
+    value: Option<T>,
+}
+
+/// Sample a value using `poll` every `poll_interval`, passing the most recent result to `apply`
+/// from the main event loop on the same interval.
+///
+/// Polling starts on a background thread when the window manager starts, with the first sample
+/// being applied as soon as it is available. Samples of `None` are ignored, keeping the previous
+/// value. `apply` is run every `poll_interval` even if the value has not changed so it should be
+/// cheap to call with the value that was last applied.
+pub fn add_polled_source<X, T, P, A>(
+    mut wm: WindowManager<X>,
+    poll_interval: Duration,
+    poll: P,
+    apply: A,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+    T: Copy + PartialEq + fmt::Debug + Send + 'static,
+    P: Fn() -> Option<T> + Clone + Send + 'static,
+    A: Fn(T, &mut State<X>, &X) -> Result<()> + Clone + 'static,
+{
+    wm.state
+        .config
+        .compose_or_set_startup_hook(move |state: &mut State<X>, x: &X| {
+            let latest = Arc::new(Mutex::new(Latest {
+                sampled: false,
+                value: None,
+            }));
+            let (polled, poll) = (Arc::clone(&latest), poll.clone());
+
+            thread::spawn(move || loop {
+                let sample = poll();
+                let mut current = polled.lock().unwrap_or_else(PoisonError::into_inner);
+                current.sampled = true;
+                if let Some(value) = sample {
+                    if current.value.replace(value) != Some(value) {
+                        debug!(?value, "polled value changed");
+                    }
+                }
+                drop(current);
+
+                thread::sleep(poll_interval);
+            });
+
+            apply_latest(latest, poll_interval, apply.clone(), state, x)
+        });
+
+    wm
+}
+
+// Apply the most recently polled value, checking it again from the main event loop once the
+// poll interval has elapsed (or sooner if the first sample is still being taken).
+fn apply_latest<X, T, A>(
+    latest: Arc<Mutex<Latest<T>>>,
+    poll_interval: Duration,
+    apply: A,
+    state: &mut State<X>,
+    x: &X,
+) -> Result<()>
+where
+    X: XConn,
+    T: Copy + 'static,
+    A: Fn(T, &mut State<X>, &X) -> Result<()> + Clone + 'static,
+{
+    let (sampled, current) = {
+        let guard = latest.lock().unwrap_or_else(PoisonError::into_inner);
+        (guard.sampled, guard.value)
+    };
+    let delay = if sampled {
+        poll_interval
+    } else {
+        poll_interval.min(FIRST_SAMPLE_CHECK_INTERVAL)
+    };
+
+    let next = apply.clone();
+    state.run_after(delay, move |state, x| {
+        apply_latest(latest, poll_interval, next, state, x)
+    });
+
+    match current {
+        Some(value) => apply(value, state, x),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::Config,
+        testing::{HeadlessConn, Scenario},
+    };
+    use std::{collections::HashMap, time::Instant};
+
+    #[test]
+    fn the_latest_polled_value_is_applied_on_each_interval() {
+        let value = Arc::new(Mutex::new(None));
+        let v = Arc::clone(&value);
+        let wm = WindowManager::new(
+            Config::default(),
+            HashMap::new(),
+            HashMap::new(),
+            HeadlessConn::default(),
+        )
+        .unwrap();
+        let wm = add_polled_source(
+            wm,
+            Duration::from_millis(5),
+            move || *v.lock().unwrap(),
+            |n: u32, state: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                state
+                    .extension_or_default::<Vec<u32>>()
+                    .borrow_mut()
+                    .push(n);
+                Ok(())
+            },
+        );
+
+        let s = Scenario::new(wm).unwrap();
+        assert!(
+            s.state().extension::<Vec<u32>>().is_err(),
+            "nothing polled yet"
+        );
+
+        *value.lock().unwrap() = Some(1);
+        let s = s.wait(Duration::from_millis(50));
+        *value.lock().unwrap() = None;
+        let s = s.wait(Duration::from_millis(50));
+
+        let applied = s.state().extension::<Vec<u32>>().unwrap();
+        assert_eq!(*applied.borrow(), vec![1, 1]);
+    }
+
+    #[test]
+    fn slow_polls_do_not_block_startup() {
+        let wm = WindowManager::new(
+            Config::default(),
+            HashMap::new(),
+            HashMap::new(),
+            HeadlessConn::default(),
+        )
+        .unwrap();
+        let wm = add_polled_source(
+            wm,
+            Duration::from_secs(3600),
+            || {
+                thread::sleep(Duration::from_millis(200));
+                Some(1)
+            },
+            |n: u32, state: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                state.add_extension(n);
+                Ok(())
+            },
+        );
+
+        let start = Instant::now();
+        let s = Scenario::new(wm).unwrap();
+        assert!(
+            start.elapsed() < Duration::from_millis(200),
+            "startup blocked"
+        );
+        assert!(s.state().extension::<u32>().is_err(), "nothing polled yet");
+
+        let s = s.wait(Duration::from_millis(300));
+        assert_eq!(*s.state().extension::<u32>().unwrap().borrow(), 1);
+    }
+}
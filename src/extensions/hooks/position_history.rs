@@ -0,0 +1,670 @@
+//! Remembering where clients were last placed so that they can be put back there.
+//!
+//! [PositionHistory] is a state extension that tracks the workspace (and floating position if
+//! the client was floating) that each client was last seen on, keyed by its class name and a
+//! prefix of its title. Call [add_position_history] to register the extension along with the
+//! refresh hook that keeps it up to date, and then use the [RestorePreviousPosition]
+//! [ManageHook] (or the `restore_previous_position` action in a [RulesFile][0]) for the clients
+//! you want returned to where they were last time they were open.
+//!
+//! Using [PositionHistory::restore_floating] the history can also be used to remember where
+//! floating clients are placed: whenever a tracked client starts floating (whether that is from
+//! a [ManageHook], a rule in a [RulesFile][0] or toggling it to floating by hand) it is moved to
+//! the position and size it last had while floating, or that the last floating client of the
+//! same class had. Positions are recorded relative to the screen the client was on, so a client
+//! restored on a different screen is placed at the same position on that screen.
+//!
+//! By default the history is only held in memory, but using [PositionHistory::persisted] it is
+//! written to disk whenever it changes so that positions are remembered across restarts of your
+//! window manager.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! use penrose::extensions::hooks::position_history::{add_position_history, PositionHistory};
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> penrose::Result<WindowManager<RustConn>> {
+//! let history = PositionHistory::persisted("/home/me/.cache/penrose/positions")?
+//!     .restore_floating(true);
+//!
+//! Ok(add_position_history(wm, history))
+//! # }
+//! ```
+//!
+//!   [0]: crate::extensions::hooks::RulesFile
+use crate::{
+    core::{hooks::ManageHook, ClientSet, State, WindowManager},
+    pure::geometry::{MonitorRect, Rect},
+    x::{atom::Atom, property::Prop, XConn, XConnExt},
+    Result, Xid,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{debug, warn};
+
+/// The default number of characters of a client's title used to identify it.
+pub const DEFAULT_TITLE_PREFIX: usize = 8;
+
+/// The default maximum number of entries held in a [PositionHistory].
+pub const DEFAULT_MAX_ENTRIES: usize = 256;
+
+/// Where a client was last seen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviousPosition {
+    /// The class name of the client (the second string of WM_CLASS)
+    pub class: String,
+    /// The start of the client's title
+    pub title_prefix: String,
+    /// The tag of the workspace the client was on
+    pub tag: String,
+    /// The position of the client relative to its screen if it was floating
+    pub floating: Option<MonitorRect>,
+    /// The most recent position of the client relative to its screen while it was floating,
+    /// which is kept once it stops floating
+    pub last_floating: Option<MonitorRect>,
+}
+
+impl PreviousPosition {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let (class, title_prefix, tag, floating, last_floating) = (
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+            parse_geometry(fields.next()?)?,
+            parse_geometry(fields.next()?)?,
+        );
+        if fields.next().is_some() || class.is_empty() || tag.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            class: class.to_string(),
+            title_prefix: title_prefix.to_string(),
+            tag: tag.to_string(),
+            floating,
+            last_floating,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.class,
+            self.title_prefix,
+            self.tag,
+            geometry_field(self.floating),
+            geometry_field(self.last_floating),
+        )
+    }
+}
+
+// Geometry fields are either "-" for no geometry or "x y w h".
+fn parse_geometry(s: &str) -> Option<Option<MonitorRect>> {
+    if s == "-" {
+        return Some(None);
+    }
+
+    let vals = s
+        .split(' ')
+        .map(|v| v.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match vals.as_slice() {
+        &[x, y, w, h] => Some(Some(MonitorRect::new(x, y, w, h))),
+        _ => None,
+    }
+}
+
+fn geometry_field(r: Option<MonitorRect>) -> String {
+    match r {
+        Some(MonitorRect { x, y, w, h }) => format!("{x} {y} {w} {h}"),
+        None => "-".to_string(),
+    }
+}
+
+/// The last known positions of clients, identified by their class name and title prefix.
+///
+/// Entries are ordered from least to most recently updated, with the oldest entries being
+/// dropped once the maximum number of entries is reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionHistory {
+    entries: Vec<PreviousPosition>,
+    clients: HashMap<Xid, (String, String)>,
+    floating: HashSet<Xid>,
+    title_prefix: usize,
+    max_entries: usize,
+    restore_floating: bool,
+    path: Option<PathBuf>,
+}
+
+impl Default for PositionHistory {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            clients: HashMap::new(),
+            floating: HashSet::new(),
+            title_prefix: DEFAULT_TITLE_PREFIX,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            restore_floating: false,
+            path: None,
+        }
+    }
+}
+
+impl PositionHistory {
+    /// Create a new, empty, in memory history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a history that is stored in the file at the given path.
+    ///
+    /// Any existing history in the file is loaded and the file is re-written each time the
+    /// history changes. It is not an error for the file to not exist yet.
+    pub fn persisted(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read_to_string(&path) {
+            Ok(s) => parse_entries(&s),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            entries,
+            path: Some(path),
+            ..Self::default()
+        })
+    }
+
+    /// Set the number of characters from the start of a client's title used (along with its
+    /// class name) to identify it.
+    ///
+    /// Setting this to 0 identifies clients by their class name alone.
+    pub fn title_prefix(mut self, n: usize) -> Self {
+        self.title_prefix = n;
+        self
+    }
+
+    /// Set the maximum number of entries to keep.
+    pub fn max_entries(mut self, n: usize) -> Self {
+        self.max_entries = n;
+        self.truncate();
+        self
+    }
+
+    /// Set whether or not tracked clients are moved to their previous floating position each
+    /// time they start floating. This is disabled by default.
+    pub fn restore_floating(mut self, restore: bool) -> Self {
+        self.restore_floating = restore;
+        self
+    }
+
+    /// The recorded positions, least recently updated first.
+    pub fn entries(&self) -> &[PreviousPosition] {
+        &self.entries
+    }
+
+    /// The previous position of a client with the given class and title.
+    ///
+    /// If there is no entry matching both the class and title prefix then the most recently
+    /// updated entry for the class is returned.
+    pub fn lookup(&self, class: &str, title: &str) -> Option<&PreviousPosition> {
+        self.get(class, &self.prefix_of(title))
+            .or_else(|| self.entries.iter().rev().find(|e| e.class == class))
+    }
+
+    /// The most recent floating position of a client with the given class and title.
+    ///
+    /// If there is no floating position for an entry matching both the class and title prefix
+    /// then the most recent floating position for the class is returned.
+    pub fn floating_position_for(&self, class: &str, title: &str) -> Option<MonitorRect> {
+        self.get(class, &self.prefix_of(title))
+            .and_then(|e| e.last_floating)
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .rev()
+                    .filter(|e| e.class == class)
+                    .find_map(|e| e.last_floating)
+            })
+    }
+
+    fn get(&self, class: &str, title_prefix: &str) -> Option<&PreviousPosition> {
+        self.entries
+            .iter()
+            .find(|e| e.class == class && e.title_prefix == title_prefix)
+    }
+
+    fn prefix_of(&self, title: &str) -> String {
+        title
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(self.title_prefix)
+            .collect()
+    }
+
+    fn track(&mut self, id: Xid, class: &str, title: &str) {
+        let class = class.chars().filter(|c| !c.is_control()).collect();
+        let title_prefix = self.prefix_of(title);
+        self.clients.insert(id, (class, title_prefix));
+    }
+
+    // Update the entry for the given key, moving it to the end of the history if it has
+    // changed. Returns whether or not the history was modified.
+    fn record(
+        &mut self,
+        class: &str,
+        title_prefix: &str,
+        tag: &str,
+        floating: Option<MonitorRect>,
+    ) -> bool {
+        let ix = self
+            .entries
+            .iter()
+            .position(|e| e.class == class && e.title_prefix == title_prefix);
+
+        let mut last_floating = floating;
+        if let Some(ix) = ix {
+            let e = &self.entries[ix];
+            if e.tag == tag && e.floating == floating {
+                return false;
+            }
+            last_floating = floating.or(e.last_floating);
+            self.entries.remove(ix);
+        }
+
+        self.entries.push(PreviousPosition {
+            class: class.to_string(),
+            title_prefix: title_prefix.to_string(),
+            tag: tag.to_string(),
+            floating,
+            last_floating,
+        });
+        self.truncate();
+
+        true
+    }
+
+    fn truncate(&mut self) {
+        if self.entries.len() > self.max_entries {
+            let n = self.entries.len() - self.max_entries;
+            self.entries.drain(..n);
+        }
+    }
+
+    // Find the tracked clients that have started floating since the last call that need
+    // moving to their previous floating position.
+    fn newly_floating(&mut self, cs: &ClientSet) -> Vec<(Xid, Rect)> {
+        self.clients.retain(|id, _| cs.contains(id));
+        let floating: HashSet<Xid> = self
+            .clients
+            .keys()
+            .filter(|id| cs.floating.contains_key(id))
+            .copied()
+            .collect();
+
+        let mut to_restore: Vec<_> = floating
+            .difference(&self.floating)
+            .filter_map(|id| {
+                let (class, title_prefix) = &self.clients[id];
+                let r_screen = cs.screen_for_client(id)?.r;
+                let r = self
+                    .floating_position_for(class, title_prefix)?
+                    .on_screen(&r_screen);
+                let current = cs.floating[id].applied_to(&r_screen);
+
+                (current != r).then_some((*id, r))
+            })
+            .collect();
+        to_restore.sort_by_key(|(id, _)| *id);
+
+        self.floating = floating;
+
+        to_restore
+    }
+
+    // Record the current position of all tracked clients, dropping any that are no
+    // longer managed. Clients on invisible workspaces keep their previous position.
+    fn update_from(&mut self, cs: &ClientSet) -> bool {
+        self.clients.retain(|id, _| cs.contains(id));
+
+        let mut positions: Vec<_> = self
+            .clients
+            .iter()
+            .filter_map(|(id, (class, title_prefix))| {
+                let tag = cs.tag_for_client(id)?;
+                if cs.invisible_tags.iter().any(|t| t == tag) {
+                    return None;
+                }
+
+                let floating = match (cs.floating.get(id), cs.screen_for_client(id)) {
+                    (Some(rr), Some(s)) => Some(rr.applied_to(&s.r).to_monitor(&s.r)),
+                    (Some(_), None) => self.get(class, title_prefix).and_then(|e| e.floating),
+                    (None, _) => None,
+                };
+
+                Some((
+                    *id,
+                    class.clone(),
+                    title_prefix.clone(),
+                    tag.to_string(),
+                    floating,
+                ))
+            })
+            .collect();
+
+        // Keep the focused client as the most recent entry for its class
+        positions.sort_by_key(|(id, ..)| (cs.current_client() == Some(id), *id));
+
+        let mut changed = false;
+        for (_, class, title_prefix, tag, floating) in positions {
+            changed |= self.record(&class, &title_prefix, &tag, floating);
+        }
+
+        changed
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut s: String = self.entries.iter().map(|e| e.to_line() + "\n").collect();
+        if s.is_empty() {
+            s.push('\n');
+        }
+
+        fs::write(path, s)
+    }
+}
+
+fn parse_entries(s: &str) -> Vec<PreviousPosition> {
+    s.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let e = PreviousPosition::parse(line);
+            if e.is_none() {
+                warn!(%line, "skipping invalid position history entry");
+            }
+            e
+        })
+        .collect()
+}
+
+fn class_and_title<X: XConn>(id: Xid, x: &X) -> Result<Option<(String, String)>> {
+    let class = match x.get_prop(id, Atom::WmClass.as_ref())? {
+        Some(Prop::UTF8String(mut strs)) if strs.len() > 1 => strs.swap_remove(1),
+        _ => return Ok(None),
+    };
+
+    Ok(Some((class, x.window_title(id)?)))
+}
+
+/// Add the given [PositionHistory] as a state extension along with a refresh hook that
+/// records the position of each client as it changes.
+pub fn add_position_history<X>(
+    mut wm: WindowManager<X>,
+    history: PositionHistory,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(history);
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+
+    wm
+}
+
+/// Start tracking newly managed clients and record the current position of all tracked clients.
+///
+/// If [PositionHistory::restore_floating] is enabled then clients that have started floating
+/// are also moved to their previous floating position, with a follow up refresh being run from
+/// the main event loop once this refresh has completed.
+pub fn refresh_hook<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let history = state.extension_or_default::<PositionHistory>();
+    let mut history = history.borrow_mut();
+
+    for &id in state.diff.new_clients() {
+        match class_and_title(id, x) {
+            Ok(Some((class, title))) => history.track(id, &class, &title),
+            Ok(None) => (),
+            Err(e) => warn!(%id, %e, "unable to identify client for position history"),
+        }
+    }
+
+    let to_restore = history.newly_floating(&state.client_set);
+    if history.restore_floating && !to_restore.is_empty() {
+        for (id, r) in to_restore {
+            debug!(%id, ?r, "restoring previous floating position");
+            state.client_set.float(id, r)?;
+        }
+        state.run_after(Duration::ZERO, |state, x| x.refresh(state));
+    }
+
+    if history.update_from(&state.client_set) {
+        debug!("position history updated");
+        if let Err(e) = history.save() {
+            warn!(%e, path = ?history.path, "unable to save position history");
+        }
+    }
+
+    Ok(())
+}
+
+/// Move a client to the workspace it was last seen on, floating it at its previous position
+/// if it was floating.
+///
+/// This has no effect if there is no [PositionHistory] entry for the client or if its previous
+/// workspace no longer exists. See the [module level docs][self] for how to set up the history.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RestorePreviousPosition;
+
+impl<X: XConn> ManageHook<X> for RestorePreviousPosition {
+    fn call(&mut self, client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+        let history = match state.extension::<PositionHistory>() {
+            Ok(history) => history,
+            Err(_) => {
+                warn!("unable to restore position: add_position_history has not been called");
+                return Ok(());
+            }
+        };
+
+        let (class, title) = match class_and_title(client, x)? {
+            Some(ids) => ids,
+            None => return Ok(()),
+        };
+
+        let prev = match history.borrow().lookup(&class, &title) {
+            Some(prev) if state.client_set.contains_tag(&prev.tag) => prev.clone(),
+            _ => return Ok(()),
+        };
+
+        debug!(%client, tag = %prev.tag, "restoring previous client position");
+        state.client_set.move_client_to_tag(&client, &prev.tag);
+        if let Some(r) = prev.floating {
+            state.client_set.float(client, r)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pure::{geometry::Rect, test_xid_stack_set};
+    use simple_test_case::test_case;
+
+    fn entry(class: &str, title_prefix: &str, tag: &str) -> PreviousPosition {
+        PreviousPosition {
+            class: class.to_string(),
+            title_prefix: title_prefix.to_string(),
+            tag: tag.to_string(),
+            floating: None,
+            last_floating: None,
+        }
+    }
+
+    fn floating_entry(
+        class: &str,
+        tag: &str,
+        r: Option<MonitorRect>,
+        last: MonitorRect,
+    ) -> PreviousPosition {
+        PreviousPosition {
+            floating: r,
+            last_floating: Some(last),
+            ..entry(class, "", tag)
+        }
+    }
+
+    #[test_case("firefox\tGitHub\t2\t-\t-", Some(entry("firefox", "GitHub", "2")); "tiled")]
+    #[test_case("mpv\t\t3\t1 2 3 4\t1 2 3 4", Some(floating_entry("mpv", "3", Some(MonitorRect::new(1, 2, 3, 4)), MonitorRect::new(1, 2, 3, 4))); "floating")]
+    #[test_case("mpv\t\t3\t-\t1 2 3 4", Some(floating_entry("mpv", "3", None, MonitorRect::new(1, 2, 3, 4))); "previously floating")]
+    #[test_case("mpv\t\t3\t-", None; "missing geometry")]
+    #[test_case("mpv\t\t3\t10 20 300\t-", None; "incomplete geometry")]
+    #[test_case("\tGitHub\t2\t-\t-", None; "missing class")]
+    #[test]
+    fn parse_entry(line: &str, expected: Option<PreviousPosition>) {
+        let parsed = PreviousPosition::parse(line);
+
+        assert_eq!(parsed, expected);
+        if let Some(e) = parsed {
+            assert_eq!(e.to_line(), line);
+        }
+    }
+
+    #[test_case("firefox", "GitHub - Mozilla", Some("2"); "exact match")]
+    #[test_case("firefox", "Inbox - Mozilla", Some("3"); "most recent for class")]
+    #[test_case("chromium", "GitHub - Chromium", None; "unknown class")]
+    #[test]
+    fn lookup(class: &str, title: &str, expected: Option<&str>) {
+        let mut h = PositionHistory::new().title_prefix(6);
+        h.record("firefox", "GitHub", "2", None);
+        h.record("firefox", "Docs -", "3", None);
+
+        assert_eq!(h.lookup(class, title).map(|e| e.tag.as_str()), expected);
+    }
+
+    #[test]
+    fn record_moves_entries_to_the_end_and_drops_the_oldest() {
+        let mut h = PositionHistory::new().max_entries(2);
+
+        assert!(h.record("a", "", "1", None));
+        assert!(h.record("b", "", "1", None));
+        assert!(!h.record("b", "", "1", None), "unchanged");
+        assert!(h.record("a", "", "2", None));
+        assert!(h.record("c", "", "1", None));
+
+        assert_eq!(h.entries(), &[entry("a", "", "2"), entry("c", "", "1")]);
+    }
+
+    #[test]
+    fn update_from_records_tracked_clients() {
+        let mut cs = test_xid_stack_set(3, 1);
+        for n in 1..=3 {
+            cs.insert(Xid(n));
+        }
+        cs.add_invisible_workspace("MIN").unwrap();
+        cs.move_client_to_tag(&Xid(2), "3");
+        cs.float(Xid(1), Rect::new(10, 10, 100, 100)).unwrap();
+
+        let mut h = PositionHistory::new();
+        h.track(Xid(1), "mpv", "video.mkv");
+        h.track(Xid(2), "discord", "Discord");
+        h.track(Xid(4), "gone", "");
+
+        assert!(h.update_from(&cs));
+        assert!(!h.update_from(&cs), "nothing changed");
+
+        cs.move_client_to_tag(&Xid(2), "MIN");
+        assert!(!h.update_from(&cs), "invisible workspaces are ignored");
+
+        let r = MonitorRect::new(10, 10, 100, 100);
+        assert_eq!(
+            h.entries(),
+            &[
+                PreviousPosition {
+                    floating: Some(r),
+                    last_floating: Some(r),
+                    ..entry("mpv", "video.mk", "1")
+                },
+                entry("discord", "Discord", "3"),
+            ]
+        );
+        assert!(!h.clients.contains_key(&Xid(4)));
+    }
+
+    #[test]
+    fn floating_positions_are_kept_after_sinking_and_restored_when_floating() {
+        let mut cs = test_xid_stack_set(3, 1);
+        for n in 1..=3 {
+            cs.insert(Xid(n));
+        }
+        let r = Rect::new(10, 20, 300, 400);
+
+        let mut h = PositionHistory::new();
+        h.track(Xid(1), "pavucontrol", "");
+        h.track(Xid(2), "pavucontrol", "");
+
+        cs.float(Xid(1), r).unwrap();
+        assert!(h.newly_floating(&cs).is_empty(), "no known position");
+        assert!(h.update_from(&cs));
+
+        cs.sink(&Xid(1));
+        h.update_from(&cs);
+        assert_eq!(
+            h.floating_position_for("pavucontrol", ""),
+            Some(MonitorRect::new(10, 20, 300, 400))
+        );
+
+        cs.float(Xid(2), Rect::new(0, 0, 50, 50)).unwrap();
+        assert_eq!(h.newly_floating(&cs), vec![(Xid(2), r)]);
+        assert!(h.newly_floating(&cs).is_empty(), "only restored once");
+    }
+
+    #[test]
+    fn floating_positions_are_restored_relative_to_the_current_screen() {
+        let mut cs = test_xid_stack_set(3, 2);
+        cs.insert(Xid(1));
+        cs.focus_screen(1);
+        cs.insert(Xid(2));
+
+        let mut h = PositionHistory::new();
+        h.track(Xid(1), "pavucontrol", "");
+        h.track(Xid(2), "pavucontrol", "");
+
+        cs.float(Xid(1), Rect::new(10, 20, 300, 400)).unwrap();
+        h.newly_floating(&cs);
+        h.update_from(&cs);
+        cs.sink(&Xid(1));
+        cs.float(Xid(2), Rect::new(1000, 2000, 50, 50)).unwrap();
+
+        assert_eq!(
+            h.newly_floating(&cs),
+            vec![(Xid(2), Rect::new(1010, 2020, 300, 400))]
+        );
+    }
+
+    #[test]
+    fn persisted_history_round_trips() {
+        let path =
+            std::env::temp_dir().join(format!("penrose-position-history-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut h = PositionHistory::persisted(&path).unwrap();
+        h.record("firefox", "GitHub", "2", None);
+        h.record("mpv", "", "3", Some(MonitorRect::new(1, 2, 3, 4)));
+        h.save().unwrap();
+
+        let loaded = PositionHistory::persisted(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.entries(), h.entries());
+    }
+}
@@ -0,0 +1,200 @@
+//! Switching behaviour profiles when moving between mains and battery power.
+//!
+//! [add_power_profiles] polls the status of a battery (using the same [data source][0] as the
+//! `penrose_ui` battery widget) using [add_polled_source][1]. Whenever the system switches
+//! between mains and battery power each registered [PowerSourceHook] is run with the new
+//! [PowerSource], allowing for expensive behaviour such as animations to be disabled while on
+//! battery and re-enabled once mains power is restored. Hooks are also run once on startup with
+//! the initial power source.
+//!
+//! Other extensions can attach their own hooks using [on_power_source_change]:
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! # use std::time::Duration;
+//! use penrose::extensions::{
+//!     hooks::power_profiles::{add_power_profiles, on_power_source_change},
+//!     util::power::PowerSource,
+//! };
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! let wm = add_power_profiles(wm, "BAT0", Duration::from_secs(10));
+//! on_power_source_change(wm, |source, _: &mut _, _: &_| {
+//!     tracing::info!(?source, "power source changed");
+//!     Ok(())
+//! })
+//! # }
+//! ```
+//!
+//!   [0]: crate::extensions::util::power
+//!   [1]: crate::extensions::hooks::add_polled_source
+use crate::{
+    core::{State, WindowManager},
+    extensions::{
+        hooks::add_polled_source,
+        util::power::{BatteryStatus, PowerSource},
+    },
+    x::XConn,
+    Result,
+};
+use std::{fmt, time::Duration};
+use tracing::{error, info};
+
+/// Logic to run when the system switches between mains and battery power.
+pub trait PowerSourceHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook for the new power source.
+    fn call(&mut self, source: PowerSource, state: &mut State<X>, x: &X) -> Result<()>;
+}
+
+impl<F, X> PowerSourceHook<X> for F
+where
+    F: FnMut(PowerSource, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, source: PowerSource, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(source, state, x)
+    }
+}
+
+/// The state extension tracking the current power source and the hooks to run when it
+/// changes.
+pub struct PowerProfiles<X: XConn> {
+    current: Option<PowerSource>,
+    hooks: Vec<Box<dyn PowerSourceHook<X>>>,
+}
+
+impl<X: XConn> Default for PowerProfiles<X> {
+    fn default() -> Self {
+        Self {
+            current: None,
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl<X: XConn> fmt::Debug for PowerProfiles<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PowerProfiles")
+            .field("current", &self.current)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+impl<X: XConn> PowerProfiles<X> {
+    /// The current power source, if it is known.
+    pub fn current(&self) -> Option<PowerSource> {
+        self.current
+    }
+
+    /// Register a hook to be run each time the power source changes.
+    pub fn add_hook<H>(&mut self, hook: H)
+    where
+        H: PowerSourceHook<X> + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+    }
+}
+
+/// Poll the named battery (e.g. `BAT0`) every `poll_interval` and run any registered
+/// [PowerSourceHook]s when the system switches between mains and battery power.
+///
+/// If the battery can not be found then the system is assumed to be running on mains power.
+pub fn add_power_profiles<X>(
+    mut wm: WindowManager<X>,
+    battery: &'static str,
+    poll_interval: Duration,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.extension_or_default::<PowerProfiles<X>>();
+
+    add_polled_source(
+        wm,
+        poll_interval,
+        move || Some(read_power_source(battery)),
+        run_power_source_hooks,
+    )
+}
+
+/// Register a hook to be run each time the system switches between mains and battery power.
+///
+/// Hooks only run once [add_power_profiles] has been called.
+pub fn on_power_source_change<X, H>(mut wm: WindowManager<X>, hook: H) -> WindowManager<X>
+where
+    X: XConn + 'static,
+    H: PowerSourceHook<X> + 'static,
+{
+    wm.state
+        .extension_or_default::<PowerProfiles<X>>()
+        .borrow_mut()
+        .add_hook(hook);
+
+    wm
+}
+
+// Run the registered hooks if the power source differs from the last one they were run for.
+fn run_power_source_hooks<X>(source: PowerSource, state: &mut State<X>, x: &X) -> Result<()>
+where
+    X: XConn + 'static,
+{
+    let pp = state.extension::<PowerProfiles<X>>()?;
+    let mut hooks = {
+        let mut pp = pp.borrow_mut();
+        if pp.current == Some(source) {
+            return Ok(());
+        }
+        pp.current = Some(source);
+
+        std::mem::take(&mut pp.hooks)
+    };
+
+    info!(?source, "switching power profile");
+    for h in hooks.iter_mut() {
+        if let Err(e) = h.call(source, state, x) {
+            error!(%e, "error returned from power source hook");
+        }
+    }
+
+    // Keep any hooks that were registered while running the existing ones
+    let mut pp = pp.borrow_mut();
+    hooks.append(&mut pp.hooks);
+    pp.hooks = hooks;
+
+    Ok(())
+}
+
+fn read_power_source(battery: &str) -> PowerSource {
+    BatteryStatus::read(battery).map_or(PowerSource::Ac, |b| b.power_source())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::Config, x::StubXConn};
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn hooks_only_run_when_the_source_changes() {
+        let mut state = State::try_new(Config::default(), &StubXConn).unwrap();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let s = Rc::clone(&seen);
+        state
+            .extension_or_default::<PowerProfiles<StubXConn>>()
+            .borrow_mut()
+            .add_hook(move |source, _: &mut State<_>, _: &_| {
+                s.borrow_mut().push(source);
+                Ok(())
+            });
+
+        for source in [PowerSource::Ac, PowerSource::Ac, PowerSource::Battery] {
+            run_power_source_hooks(source, &mut state, &StubXConn).unwrap();
+        }
+
+        assert_eq!(*seen.borrow(), vec![PowerSource::Ac, PowerSource::Battery]);
+    }
+}
@@ -0,0 +1,289 @@
+//! Reserving space on specific screens so that it is not used by tiled clients.
+//!
+//! [ReservedSpace] records regions of individual screens that should be left clear of tiled
+//! clients, independently of any EWMH struts set by dock windows. This is useful for programs
+//! that do not set struts (such as a conky sidebar) or for keeping part of a single screen free
+//! without needing to add a [SpacingHook][0] that applies to every screen.
+//!
+//! Screens are identified either by index or as the screen showing the primary output using a
+//! [ScreenTarget]. Regions are identified by name so that they can be updated or released at
+//! runtime through the [State] extension. Floating clients are not affected.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! use penrose::{
+//!     extensions::hooks::reserved_space::{add_reserved_space, Region, ReservedSpace},
+//!     pure::ScreenTarget,
+//! };
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! // Keep the right hand 300 pixels of the second screen clear for conky and leave space
+//! // for a panel at the top of the primary screen
+//! let reserved = ReservedSpace::new()
+//!     .with_region(1, "conky", Region::Right(300))
+//!     .with_region(ScreenTarget::Primary, "panel", Region::Top(24));
+//!
+//! add_reserved_space(wm, reserved)
+//! # }
+//! ```
+//!
+//!   [0]: crate::builtin::hooks::SpacingHook
+use crate::{
+    core::{hooks::LayoutHook, ClientSet, State, WindowManager},
+    pure::{
+        geometry::{MonitorRect, Rect},
+        ScreenTarget,
+    },
+    x::XConn,
+};
+use std::collections::HashMap;
+
+/// A region of a screen to reserve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    /// The given number of pixels along the top edge of the screen
+    Top(u32),
+    /// The given number of pixels along the bottom edge of the screen
+    Bottom(u32),
+    /// The given number of pixels along the left edge of the screen
+    Left(u32),
+    /// The given number of pixels along the right edge of the screen
+    Right(u32),
+    /// An arbitrary area, positioned relative to the top left corner of the screen
+    Area(MonitorRect),
+}
+
+impl Region {
+    /// The absolute position of this region on a screen with the given [Rect].
+    pub fn on_screen(&self, r: &Rect) -> Rect {
+        match *self {
+            Self::Top(px) => Rect::new(r.x, r.y, r.w, px.min(r.h)),
+            Self::Bottom(px) => Rect::new(r.x, r.y + r.h.saturating_sub(px), r.w, px.min(r.h)),
+            Self::Left(px) => Rect::new(r.x, r.y, px.min(r.w), r.h),
+            Self::Right(px) => Rect::new(r.x + r.w.saturating_sub(px), r.y, px.min(r.w), r.h),
+            Self::Area(a) => a.on_screen(r),
+        }
+    }
+}
+
+/// Named regions of each screen that should not be used by tiled clients.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReservedSpace {
+    regions: HashMap<ScreenTarget, Vec<(String, Region)>>,
+}
+
+impl ReservedSpace {
+    /// Create a new, empty, set of reserved regions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserve the given region of the target screen.
+    pub fn with_region(
+        mut self,
+        screen: impl Into<ScreenTarget>,
+        name: impl Into<String>,
+        region: Region,
+    ) -> Self {
+        self.reserve(screen, name, region);
+        self
+    }
+
+    /// Reserve the given region of the target screen, replacing any existing region with the
+    /// same name.
+    ///
+    /// The X state needs to be refreshed for this to take effect.
+    pub fn reserve(
+        &mut self,
+        screen: impl Into<ScreenTarget>,
+        name: impl Into<String>,
+        region: Region,
+    ) {
+        let name = name.into();
+        self.release(&name);
+        self.regions
+            .entry(screen.into())
+            .or_default()
+            .push((name, region));
+    }
+
+    /// Release the region with the given name, returning whether or not it was reserved.
+    ///
+    /// The X state needs to be refreshed for this to take effect.
+    pub fn release(&mut self, name: &str) -> bool {
+        let mut released = false;
+        for regions in self.regions.values_mut() {
+            let n = regions.len();
+            regions.retain(|(n, _)| n != name);
+            released |= regions.len() != n;
+        }
+        self.regions.retain(|_, regions| !regions.is_empty());
+
+        released
+    }
+
+    /// The regions reserved for the given target screen.
+    pub fn regions(&self, screen: impl Into<ScreenTarget>) -> impl Iterator<Item = &Region> {
+        self.regions
+            .get(&screen.into())
+            .into_iter()
+            .flat_map(|regions| regions.iter().map(|(_, r)| r))
+    }
+
+    /// The part of `r` that is left available for tiled clients on the screen with the given
+    /// index, including any regions reserved on the primary screen if it is the primary screen.
+    pub fn available(&self, cs: &ClientSet, screen: usize, r: Rect) -> Rect {
+        let r_screen = match cs.screens().find(|s| s.index() == screen) {
+            Some(s) => s.r,
+            None => return r,
+        };
+
+        let primary = (cs.primary_screen() == screen).then(|| self.regions(ScreenTarget::Primary));
+
+        self.regions(screen)
+            .chain(primary.into_iter().flatten())
+            .fold(r, |r, region| r.without(&region.on_screen(&r_screen)))
+    }
+}
+
+/// A [LayoutHook] that removes the regions recorded in the [ReservedSpace] state extension
+/// from the space available to layouts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReservedSpaceHook;
+
+impl<X: XConn> LayoutHook<X> for ReservedSpaceHook {
+    fn transform_initial(&mut self, r: Rect, state: &State<X>, _: &X) -> Rect {
+        let reserved = match state.extension::<ReservedSpace>() {
+            Ok(reserved) => reserved,
+            Err(_) => return r,
+        };
+
+        let p = r.midpoint();
+        let screen = state.client_set.screens().find(|s| s.r.contains_point(p));
+
+        match screen {
+            Some(s) => reserved.borrow().available(&state.client_set, s.index(), r),
+            None => r,
+        }
+    }
+}
+
+/// Add the given [ReservedSpace] as a state extension along with the [ReservedSpaceHook]
+/// required to keep tiled clients out of the reserved regions.
+pub fn add_reserved_space<X>(mut wm: WindowManager<X>, reserved: ReservedSpace) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(reserved);
+    wm.state
+        .config
+        .compose_or_set_layout_hook(ReservedSpaceHook);
+
+    wm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::layout::LayoutStack,
+        testing::{HeadlessConn, Scenario},
+        Xid,
+    };
+    use simple_test_case::test_case;
+
+    const SCREEN: Rect = Rect::new(100, 0, 1000, 800);
+
+    fn client_set(screens: Vec<Rect>) -> ClientSet {
+        ClientSet::try_new(LayoutStack::default(), ["1", "2", "3"], screens).unwrap()
+    }
+
+    #[test_case(Region::Top(20), Rect::new(100, 0, 1000, 20); "top")]
+    #[test_case(Region::Bottom(20), Rect::new(100, 780, 1000, 20); "bottom")]
+    #[test_case(Region::Left(300), Rect::new(100, 0, 300, 800); "left")]
+    #[test_case(Region::Right(300), Rect::new(800, 0, 300, 800); "right")]
+    #[test_case(Region::Right(2000), Rect::new(100, 0, 1000, 800); "larger than screen")]
+    #[test_case(Region::Area(MonitorRect::new(10, 20, 30, 40)), Rect::new(110, 20, 30, 40); "area")]
+    #[test_case(Region::Area(MonitorRect::new(990, 0, 30, 40)), Rect::new(1070, 0, 30, 40); "area past the edge")]
+    #[test]
+    fn on_screen(region: Region, expected: Rect) {
+        assert_eq!(region.on_screen(&SCREEN), expected);
+    }
+
+    #[test]
+    fn reserving_a_name_replaces_the_existing_region() {
+        let mut reserved = ReservedSpace::new()
+            .with_region(0, "conky", Region::Right(300))
+            .with_region(0, "panel", Region::Top(20));
+
+        reserved.reserve(1, "conky", Region::Left(100));
+
+        assert_eq!(
+            reserved.regions(0).collect::<Vec<_>>(),
+            vec![&Region::Top(20)]
+        );
+        assert_eq!(
+            reserved.regions(1).collect::<Vec<_>>(),
+            vec![&Region::Left(100)]
+        );
+        assert!(reserved.release("conky"));
+        assert!(!reserved.release("conky"));
+        assert_eq!(reserved.regions(1).count(), 0);
+    }
+
+    #[test]
+    fn available_removes_all_regions() {
+        let reserved = ReservedSpace::new()
+            .with_region(0, "conky", Region::Right(300))
+            .with_region(0, "panel", Region::Top(20))
+            .with_region(1, "other", Region::Left(100));
+        let cs = client_set(vec![SCREEN]);
+
+        assert_eq!(
+            reserved.available(&cs, 0, SCREEN),
+            Rect::new(100, 20, 700, 780)
+        );
+    }
+
+    #[test_case(None, Rect::new(0, 0, 1000, 780); "defaults to first screen")]
+    #[test_case(Some(1), Rect::new(0, 0, 1000, 800); "other screen is primary")]
+    #[test]
+    fn primary_regions_follow_the_primary_screen(primary: Option<usize>, expected: Rect) {
+        let screens = vec![Rect::new(0, 0, 1000, 800), Rect::new(1000, 0, 1000, 800)];
+        let reserved =
+            ReservedSpace::new().with_region(ScreenTarget::Primary, "panel", Region::Bottom(20));
+        let mut cs = client_set(screens.clone());
+        cs.set_primary_screen(primary);
+
+        assert_eq!(reserved.available(&cs, 0, screens[0]), expected);
+    }
+
+    #[test]
+    fn only_the_reserved_screen_is_affected() {
+        let screens = vec![Rect::new(0, 0, 1000, 800), Rect::new(1000, 0, 1000, 800)];
+        let conn = HeadlessConn::new(screens.clone());
+        let wm =
+            WindowManager::new(Default::default(), HashMap::new(), HashMap::new(), conn).unwrap();
+        let reserved = ReservedSpace::new().with_region(1, "conky", Region::Right(300));
+
+        let s = Scenario::new(add_reserved_space(wm, reserved))
+            .unwrap()
+            .given_windows(["a"])
+            .act(|state, _| {
+                state.client_set.focus_screen(1);
+                Ok(())
+            })
+            .given_windows(["b"]);
+        let (a, b) = (s.id("a"), s.id("b"));
+
+        let mut positions = Vec::new();
+        s.act(|state, x| {
+            positions = state.visible_client_positions(x);
+            Ok(())
+        });
+        let r = |id: Xid| positions.iter().find(|(c, _)| *c == id).unwrap().1;
+
+        assert_eq!(r(a), screens[0]);
+        assert_eq!(r(b), Rect::new(1000, 0, 700, 800));
+    }
+}
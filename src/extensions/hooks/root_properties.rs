@@ -0,0 +1,273 @@
+//! Publishing and watching custom properties on the root window.
+//!
+//! Properties set on the root window are visible to anything connected to the X server,
+//! making them a lightweight way for shell scripts to signal the window manager and read
+//! state back from it without needing a full IPC socket. Each [RootPropertyHook] registered
+//! with [watch_root_property] is run with the new value of its property whenever that
+//! property is changed or removed, and [set_root_property] can be used to publish values for
+//! scripts to read.
+//!
+//! ```no_run
+//! # use penrose::{core::{State, WindowManager}, x11rb::RustConn};
+//! use penrose::extensions::hooks::root_properties::{
+//!     add_root_properties, set_root_property, watch_root_property,
+//! };
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! let wm = add_root_properties(wm);
+//!
+//! // $ xprop -root -f PENROSE_WORKSPACE 8s -set PENROSE_WORKSPACE 3
+//! // $ xprop -root PENROSE_CURRENT_TAG
+//! let focus_tag = |tag: Option<&str>, state: &mut State<_>, x: &_| {
+//!     if let Some(tag) = tag {
+//!         state.client_set.focus_tag(tag);
+//!         set_root_property(x, "PENROSE_CURRENT_TAG", tag)?;
+//!     }
+//!
+//!     Ok(())
+//! };
+//!
+//! watch_root_property(wm, "PENROSE_WORKSPACE", focus_tag)
+//! # }
+//! ```
+//!
+//! Hooks see every change to their property, including those made by the window manager
+//! itself, so a hook should not set the property it is watching.
+use crate::{
+    core::{State, WindowManager},
+    x::{event::PropertyEvent, Prop, XConn, XEvent},
+    Result,
+};
+use std::{collections::HashMap, fmt};
+use tracing::{debug, error};
+
+/// Logic to run when a watched root window property changes.
+pub trait RootPropertyHook<X>
+where
+    X: XConn,
+{
+    /// Run this hook with the new value of the property, or `None` if it has been removed
+    /// (or holds a value that can not be represented as a string).
+    fn call(&mut self, value: Option<&str>, state: &mut State<X>, x: &X) -> Result<()>;
+}
+
+impl<F, X> RootPropertyHook<X> for F
+where
+    F: FnMut(Option<&str>, &mut State<X>, &X) -> Result<()>,
+    X: XConn,
+{
+    fn call(&mut self, value: Option<&str>, state: &mut State<X>, x: &X) -> Result<()> {
+        (self)(value, state, x)
+    }
+}
+
+/// The state extension holding the hooks to run for each watched root window property.
+pub struct RootProperties<X: XConn> {
+    watchers: HashMap<String, Vec<Box<dyn RootPropertyHook<X>>>>,
+}
+
+impl<X: XConn> Default for RootProperties<X> {
+    fn default() -> Self {
+        Self {
+            watchers: HashMap::new(),
+        }
+    }
+}
+
+impl<X: XConn> fmt::Debug for RootProperties<X> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RootProperties")
+            .field("watched", &self.watchers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<X: XConn> RootProperties<X> {
+    /// Register a hook to be run each time the named root window property changes.
+    pub fn watch<H>(&mut self, name: impl Into<String>, hook: H)
+    where
+        H: RootPropertyHook<X> + 'static,
+    {
+        self.watchers
+            .entry(name.into())
+            .or_default()
+            .push(Box::new(hook));
+    }
+
+    /// Whether or not any hooks are registered for the named root window property.
+    pub fn is_watched(&self, name: &str) -> bool {
+        self.watchers.contains_key(name)
+    }
+}
+
+/// Add the [RootProperties] state extension along with the event hook required to run
+/// watchers when root window properties change.
+pub fn add_root_properties<X>(mut wm: WindowManager<X>) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.extension_or_default::<RootProperties<X>>();
+    wm.state.config.compose_or_set_event_hook(event_hook);
+
+    wm
+}
+
+/// Register a hook to be run each time the named root window property changes.
+///
+/// Hooks only run once [add_root_properties] has been called.
+pub fn watch_root_property<X, H>(
+    mut wm: WindowManager<X>,
+    name: impl Into<String>,
+    hook: H,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+    H: RootPropertyHook<X> + 'static,
+{
+    wm.state
+        .extension_or_default::<RootProperties<X>>()
+        .borrow_mut()
+        .watch(name, hook);
+
+    wm
+}
+
+/// Set the named property on the root window to the given string so that it can be read by
+/// external programs (e.g. `xprop -root NAME`).
+pub fn set_root_property<X: XConn>(x: &X, name: &str, value: &str) -> Result<()> {
+    x.set_prop(x.root(), name, Prop::UTF8String(vec![value.to_string()]))
+}
+
+/// Remove the named property from the root window.
+pub fn delete_root_property<X: XConn>(x: &X, name: &str) -> Result<()> {
+    x.delete_prop(x.root(), name)
+}
+
+/// The current value of the named root window property as a string.
+///
+/// Strings, atoms and cardinals are supported, with multiple values being separated by
+/// commas. `None` is returned for properties that are not set or hold any other type of
+/// value.
+pub fn root_property<X: XConn>(x: &X, name: &str) -> Result<Option<String>> {
+    let value = match x.get_prop(x.root(), name)? {
+        Some(Prop::UTF8String(strs)) | Some(Prop::Atom(strs)) => Some(strs.join(",")),
+        Some(Prop::Cardinal(vals)) => Some(
+            vals.iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        _ => None,
+    };
+
+    Ok(value)
+}
+
+/// Run the registered [RootPropertyHook]s for any watched root window property that has
+/// changed.
+pub fn event_hook<X>(event: &XEvent, state: &mut State<X>, x: &X) -> Result<bool>
+where
+    X: XConn + 'static,
+{
+    let name = match event {
+        XEvent::PropertyNotify(PropertyEvent {
+            atom,
+            is_root: true,
+            ..
+        }) => atom,
+        _ => return Ok(true),
+    };
+
+    let props = state.extension_or_default::<RootProperties<X>>();
+    if !props.borrow().is_watched(name) {
+        return Ok(true);
+    }
+
+    let value = root_property(x, name)?;
+    debug!(%name, ?value, "watched root window property changed");
+
+    let mut hooks = props.borrow_mut().watchers.remove(name).unwrap_or_default();
+    for h in hooks.iter_mut() {
+        if let Err(e) = h.call(value.as_deref(), state, x) {
+            error!(%e, %name, "error returned from root property hook");
+        }
+    }
+
+    // Keep any hooks that were registered while running the existing ones
+    let mut props = props.borrow_mut();
+    let registered = props.watchers.entry(name.clone()).or_default();
+    hooks.append(registered);
+    *registered = hooks;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::Config,
+        testing::{HeadlessConn, Scenario},
+    };
+    use simple_test_case::test_case;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test_case(None, None; "unset")]
+    #[test_case(Some(Prop::UTF8String(vec!["a".into(), "b".into()])), Some("a,b"); "strings")]
+    #[test_case(Some(Prop::Atom(vec!["ATOM".into()])), Some("ATOM"); "atoms")]
+    #[test_case(Some(Prop::Cardinal(vec![1, 2])), Some("1,2"); "cardinals")]
+    #[test_case(Some(Prop::Bytes(vec![1, 2])), None; "unsupported")]
+    #[test]
+    fn root_property_values(prop: Option<Prop>, expected: Option<&str>) {
+        let conn = HeadlessConn::default();
+        if let Some(prop) = prop {
+            conn.set_prop(conn.root(), "TEST", prop).unwrap();
+        }
+
+        assert_eq!(root_property(&conn, "TEST").unwrap().as_deref(), expected);
+    }
+
+    #[test]
+    fn watchers_run_when_their_property_changes() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let calls = Rc::clone(&seen);
+
+        let wm = WindowManager::new(
+            Config::default(),
+            HashMap::new(),
+            HashMap::new(),
+            HeadlessConn::default(),
+        )
+        .unwrap();
+        let wm = add_root_properties(wm);
+        let wm = watch_root_property(wm, "WATCHED", move |v: Option<&str>, _: &mut _, _: &_| {
+            calls.borrow_mut().push(v.map(|s| s.to_string()));
+            Ok(())
+        });
+
+        let s = Scenario::new(wm).unwrap();
+        let notify = |atom: &str, is_root: bool| {
+            XEvent::PropertyNotify(PropertyEvent {
+                id: s.conn().root(),
+                atom: atom.to_string(),
+                is_root,
+            })
+        };
+        let (watched, unwatched, not_root) = (
+            notify("WATCHED", true),
+            notify("UNWATCHED", true),
+            notify("WATCHED", false),
+        );
+
+        set_root_property(s.conn(), "WATCHED", "signal").unwrap();
+        set_root_property(s.conn(), "UNWATCHED", "ignored").unwrap();
+        let s = s.send(watched.clone()).send(unwatched).send(not_root);
+
+        assert_eq!(*seen.borrow(), vec![Some("signal".to_string())]);
+
+        delete_root_property(s.conn(), "WATCHED").unwrap();
+        s.send(watched);
+
+        assert_eq!(*seen.borrow(), vec![Some("signal".to_string()), None]);
+    }
+}
@@ -0,0 +1,460 @@
+//! Client placement rules loaded from a plain text file.
+//!
+//! [RulesFile] is a [ManageHook] that reads a list of `pattern -> action` rules from disk so
+//! that simple placement rules can be maintained without recompiling your window manager.
+//! Each non-empty line of the file that is not a comment is a single rule:
+//!
+//! ```text
+//! # Lines starting with '#' are ignored
+//! class = discord                  -> workspace 9
+//! app_name = pavucontrol           -> float_centered 0.5 0.6
+//! title = "Picture-in-Picture"     -> float
+//! class = Steam                    -> tile
+//! class = Gimp                     -> float_relative 0.1 0.1 0.8 0.8
+//! class = Alacritty                -> insert below
+//! class = scratch-term             -> opacity 0.85
+//! class = Pinentry                 -> place pointer
+//! class = Thunderbird              -> restore_previous_position
+//! class = Pavucontrol              -> skip_taskbar
+//! class = conky                    -> boring
+//! decorated = false                -> float
+//! ```
+//!
+//! The supported patterns are `class`, `app_name` and `title`, which match exactly against the
+//! corresponding properties of the client window (in the same way as the queries in
+//! [crate::x::query]), and `decorated`, which matches clients that have (`false`) or have not
+//! (`true`) asked not to be decorated using `_MOTIF_WM_HINTS`. Values may optionally be wrapped
+//! in double quotes. The supported actions are:
+//!
+//!   - `float`: float the client at its requested position
+//!   - `tile`: tile the client, even if it would otherwise be floated (such as fixed size
+//!     clients when [Config::float_fixed_size_clients][1] is set)
+//!   - `float_centered <w> <h>`: float the client in the center of the screen
+//!   - `float_relative <x> <y> <w> <h>`: float the client at a position relative to the screen
+//!   - `workspace <tag>`: move the client to the named workspace
+//!   - `insert <position>`: insert the client `above` or `below` the focused client, or at the
+//!     `head` or `tail` of the stack (see [InsertPosition])
+//!   - `opacity <value>`: set the opacity of the client between 0.0 and 1.0 (see [SetOpacity])
+//!   - `place <placement>`: float the client, placing it at its `requested` position, in the
+//!     `center` of the screen, over its `parent`, under the `pointer` or in the least occupied
+//!     area of the screen using `smart` (see [FloatingPlaced])
+//!   - `restore_previous_position`: move the client back to the workspace (and floating
+//!     position) it was last seen on (see [RestorePreviousPosition])
+//!   - `skip_taskbar`: leave the client out of taskbars, window switchers and window title
+//!     displays (see [SkipTaskbar])
+//!   - `skip_pager`: leave the client out of pagers (see [SkipPager])
+//!   - `boring`: skip the client when cycling focus (see [StackSet::toggle_boring][0])
+//!
+//! All rules matching a given client are applied in the order they appear in the file. The file
+//! is parsed when the [RulesFile] is created and then re-read whenever it is modified on disk.
+//!
+//!   [0]: crate::pure::StackSet::toggle_boring
+//!   [1]: crate::core::Config::float_fixed_size_clients
+use crate::{
+    core::{hooks::ManageHook, State},
+    extensions::hooks::{
+        manage::{
+            FloatingCentered, FloatingPlaced, FloatingRelative, InsertPosition, SkipPager,
+            SkipTaskbar,
+        },
+        opacity::SetOpacity,
+        position_history::RestorePreviousPosition,
+    },
+    pure::Position,
+    x::{
+        atom::Atom,
+        query::{str_prop, title, Query, Undecorated},
+        FloatingPlacement, XConn,
+    },
+    Error, Result, Xid,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tracing::{error, info};
+
+/// The client property that a [Rule] matches against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleMatch {
+    /// Match the application name of the client (the first string of WM_CLASS)
+    AppName(String),
+    /// Match the class name of the client (the second string of WM_CLASS)
+    ClassName(String),
+    /// Match the title of the client
+    Title(String),
+    /// Match whether or not the client wants to be decorated (see [Undecorated])
+    Decorated(bool),
+}
+
+impl RuleMatch {
+    fn matches<X: XConn>(&self, id: Xid, x: &X) -> Result<bool> {
+        let (strs, target) = match self {
+            Self::Decorated(decorated) => return Ok(Undecorated.run(id, x)? != *decorated),
+            Self::AppName(s) => (str_prop(Atom::WmClass, id, x)?, s),
+            Self::ClassName(s) => {
+                let strs = str_prop(Atom::WmClass, id, x)?.map(|v| v.into_iter().skip(1).collect());
+                (strs, s)
+            }
+            Self::Title(s) => (title(id, x), s),
+        };
+
+        Ok(matches!(strs, Some(strs) if strs.first() == Some(target)))
+    }
+}
+
+/// The action to take for a client matched by a [Rule].
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleAction {
+    /// Float the client at its current position
+    Float,
+    /// Tile the client, removing any floating position it was given when it was managed
+    Tile,
+    /// Float the client in the center of the screen with the given width and height ratios
+    FloatCentered(f64, f64),
+    /// Float the client at the given x, y, width and height ratios relative to the screen
+    FloatRelative(f64, f64, f64, f64),
+    /// Move the client to the named workspace
+    Workspace(String),
+    /// Insert the client at the given position in the stack of its workspace
+    Insert(Position),
+    /// Set the opacity of the client
+    Opacity(f64),
+    /// Float the client using the given placement policy
+    Place(FloatingPlacement),
+    /// Move the client to where it was last seen (requires a [PositionHistory][0])
+    ///
+    ///   [0]: crate::extensions::hooks::PositionHistory
+    RestorePreviousPosition,
+    /// Set `_NET_WM_STATE_SKIP_TASKBAR` for the client
+    SkipTaskbar,
+    /// Set `_NET_WM_STATE_SKIP_PAGER` for the client
+    SkipPager,
+    /// Skip the client when cycling focus
+    Boring,
+}
+
+impl<X: XConn> ManageHook<X> for RuleAction {
+    fn call(&mut self, client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+        match self {
+            Self::Float => {
+                let r = x.client_geometry(client)?;
+                state.client_set.float(client, r)
+            }
+            Self::Tile => {
+                state.client_set.sink(&client);
+                Ok(())
+            }
+            Self::FloatCentered(w, h) => FloatingCentered::new(*w, *h).call(client, state, x),
+            Self::FloatRelative(rx, ry, w, h) => {
+                FloatingRelative::new(*rx, *ry, *w, *h).call(client, state, x)
+            }
+            Self::Workspace(tag) => {
+                state.client_set.move_client_to_tag(&client, tag);
+                Ok(())
+            }
+            Self::Insert(pos) => InsertPosition(*pos).call(client, state, x),
+            Self::Opacity(o) => SetOpacity(*o).call(client, state, x),
+            Self::Place(p) => FloatingPlaced(*p).call(client, state, x),
+            Self::RestorePreviousPosition => RestorePreviousPosition.call(client, state, x),
+            Self::SkipTaskbar => SkipTaskbar.call(client, state, x),
+            Self::SkipPager => SkipPager.call(client, state, x),
+            Self::Boring => {
+                state.client_set.mark_boring(&client);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A single `pattern -> action` rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// The clients that this rule applies to
+    pub query: RuleMatch,
+    /// What to do with matching clients
+    pub action: RuleAction,
+}
+
+/// Parse the contents of a rules file into a list of [Rule]s.
+///
+/// See the [module level docs][self] for details of the expected format.
+pub fn parse_rules(s: &str) -> Result<Vec<Rule>> {
+    s.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !(line.is_empty() || line.starts_with('#')))
+        .map(|(n, line)| parse_rule(line).map_err(|reason| Error::InvalidRule { line: n, reason }))
+        .collect()
+}
+
+fn parse_rule(line: &str) -> std::result::Result<Rule, String> {
+    let (pattern, action) = line
+        .split_once("->")
+        .ok_or_else(|| "expected 'pattern -> action'".to_string())?;
+    let (key, val) = pattern
+        .split_once('=')
+        .ok_or_else(|| "expected 'key = value' pattern".to_string())?;
+
+    let val = val.trim();
+    let val = val
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(val)
+        .to_string();
+
+    let query = match key.trim() {
+        "app_name" => RuleMatch::AppName(val),
+        "class" => RuleMatch::ClassName(val),
+        "title" => RuleMatch::Title(val),
+        "decorated" => match val.parse() {
+            Ok(decorated) => RuleMatch::Decorated(decorated),
+            Err(_) => return Err(format!("expected 'true' or 'false': got '{val}'")),
+        },
+        k => return Err(format!("unknown pattern '{k}'")),
+    };
+
+    let mut words = action.split_whitespace();
+    let name = words.next().ok_or_else(|| "missing action".to_string())?;
+    let args: Vec<&str> = words.collect();
+
+    let action = match (name, args.as_slice()) {
+        ("float", []) => RuleAction::Float,
+        ("tile", []) => RuleAction::Tile,
+        ("float_centered", [w, h]) => {
+            let (w, h) = (ratio(w)?, ratio(h)?);
+            RuleAction::FloatCentered(w, h)
+        }
+        ("float_relative", [x, y, w, h]) => {
+            RuleAction::FloatRelative(ratio(x)?, ratio(y)?, ratio(w)?, ratio(h)?)
+        }
+        ("workspace", [tag]) => RuleAction::Workspace(tag.to_string()),
+        ("insert", [pos]) => RuleAction::Insert(position(pos)?),
+        ("opacity", [o]) => RuleAction::Opacity(ratio(o)?),
+        ("place", [p]) => RuleAction::Place(placement(p)?),
+        ("restore_previous_position", []) => RuleAction::RestorePreviousPosition,
+        ("skip_taskbar", []) => RuleAction::SkipTaskbar,
+        ("skip_pager", []) => RuleAction::SkipPager,
+        ("boring", []) => RuleAction::Boring,
+        (
+            "float"
+            | "tile"
+            | "float_centered"
+            | "float_relative"
+            | "workspace"
+            | "insert"
+            | "opacity"
+            | "place"
+            | "restore_previous_position"
+            | "skip_taskbar"
+            | "skip_pager"
+            | "boring",
+            _,
+        ) => return Err(format!("wrong number of arguments for '{name}'")),
+        (a, _) => return Err(format!("unknown action '{a}'")),
+    };
+
+    Ok(Rule { query, action })
+}
+
+fn ratio(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => Ok(f),
+        _ => Err(format!("'{s}' is not a number between 0.0 and 1.0")),
+    }
+}
+
+fn position(s: &str) -> std::result::Result<Position, String> {
+    match s {
+        "focus" => Ok(Position::Focus),
+        "above" => Ok(Position::Before),
+        "below" => Ok(Position::After),
+        "head" => Ok(Position::Head),
+        "tail" => Ok(Position::Tail),
+        _ => Err(format!(
+            "'{s}' is not a valid position: expected one of focus, above, below, head or tail"
+        )),
+    }
+}
+
+fn placement(s: &str) -> std::result::Result<FloatingPlacement, String> {
+    match s {
+        "requested" => Ok(FloatingPlacement::AsRequested),
+        "center" => Ok(FloatingPlacement::Center),
+        "parent" => Ok(FloatingPlacement::CenterOverParent),
+        "pointer" => Ok(FloatingPlacement::UnderPointer),
+        "smart" => Ok(FloatingPlacement::Smart),
+        _ => Err(format!(
+            "'{s}' is not a valid placement: expected one of requested, center, parent, pointer or smart"
+        )),
+    }
+}
+
+/// A [ManageHook] that applies the [Rule]s found in a plain text rules file.
+///
+/// The file is read when the [RulesFile] is created, allowing for any errors to be reported on
+/// startup, and then re-read the next time a client is managed after the file has been modified.
+/// If the modified file contains errors they are logged and the previous rules are kept.
+#[derive(Debug, Clone)]
+pub struct RulesFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    rules: Vec<Rule>,
+}
+
+impl RulesFile {
+    /// Load the rules contained in the file at the given path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut rf = Self {
+            path: path.as_ref().to_path_buf(),
+            modified: None,
+            rules: Vec::new(),
+        };
+        rf.reload()?;
+
+        Ok(rf)
+    }
+
+    /// Create a new boxed [RulesFile] ready for adding to your Config.
+    pub fn boxed<X: XConn>(path: impl AsRef<Path>) -> Result<Box<dyn ManageHook<X>>> {
+        Ok(Box::new(Self::load(path)?))
+    }
+
+    /// The rules that are currently loaded.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Re-read the rules file from disk, replacing the currently loaded rules.
+    ///
+    /// If the file can not be read or contains errors then the current rules are left unchanged.
+    pub fn reload(&mut self) -> Result<()> {
+        let modified = fs::metadata(&self.path)?.modified().ok();
+        let rules = parse_rules(&fs::read_to_string(&self.path)?)?;
+
+        self.modified = modified;
+        self.rules = rules;
+
+        Ok(())
+    }
+
+    fn reload_if_modified(&mut self) {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+
+        match self.reload() {
+            Ok(()) => info!(path = ?self.path, n_rules = self.rules.len(), "reloaded rules file"),
+            Err(e) => error!(%e, path = ?self.path, "unable to reload rules file"),
+        }
+    }
+}
+
+impl<X: XConn> ManageHook<X> for RulesFile {
+    fn call(&mut self, client: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+        self.reload_if_modified();
+
+        for rule in self.rules.iter_mut() {
+            if rule.query.matches(client, x)? {
+                rule.action.call(client, state, x)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::HeadlessConn;
+    use simple_test_case::test_case;
+
+    #[test_case("class = discord -> workspace 9", RuleMatch::ClassName("discord".into()), RuleAction::Workspace("9".into()); "workspace")]
+    #[test_case("app_name=pavucontrol->float", RuleMatch::AppName("pavucontrol".into()), RuleAction::Float; "no whitespace")]
+    #[test_case("title = \"Picture in Picture\" -> float", RuleMatch::Title("Picture in Picture".into()), RuleAction::Float; "quoted value")]
+    #[test_case("class = mpv -> float_centered 0.5 0.6", RuleMatch::ClassName("mpv".into()), RuleAction::FloatCentered(0.5, 0.6); "float centered")]
+    #[test_case("class = Gimp -> float_relative 0.1 0.2 0.7 0.6", RuleMatch::ClassName("Gimp".into()), RuleAction::FloatRelative(0.1, 0.2, 0.7, 0.6); "float relative")]
+    #[test_case("class = Alacritty -> insert below", RuleMatch::ClassName("Alacritty".into()), RuleAction::Insert(Position::After); "insert")]
+    #[test_case("class = scratch-term -> opacity 0.85", RuleMatch::ClassName("scratch-term".into()), RuleAction::Opacity(0.85); "opacity")]
+    #[test_case("class = Pinentry -> place pointer", RuleMatch::ClassName("Pinentry".into()), RuleAction::Place(FloatingPlacement::UnderPointer); "place")]
+    #[test_case("class = Thunderbird -> restore_previous_position", RuleMatch::ClassName("Thunderbird".into()), RuleAction::RestorePreviousPosition; "restore previous position")]
+    #[test_case("class = Pavucontrol -> skip_taskbar", RuleMatch::ClassName("Pavucontrol".into()), RuleAction::SkipTaskbar; "skip taskbar")]
+    #[test_case("class = Pavucontrol -> skip_pager", RuleMatch::ClassName("Pavucontrol".into()), RuleAction::SkipPager; "skip pager")]
+    #[test_case("class = conky -> boring", RuleMatch::ClassName("conky".into()), RuleAction::Boring; "boring")]
+    #[test_case("class = Steam -> tile", RuleMatch::ClassName("Steam".into()), RuleAction::Tile; "tile")]
+    #[test_case("decorated = false -> float", RuleMatch::Decorated(false), RuleAction::Float; "decorated")]
+    #[test]
+    fn parse_valid_rule(line: &str, query: RuleMatch, action: RuleAction) {
+        assert_eq!(parse_rule(line), Ok(Rule { query, action }));
+    }
+
+    #[test_case("class = discord"; "missing action")]
+    #[test_case("discord -> float"; "missing key")]
+    #[test_case("role = browser -> float"; "unknown pattern")]
+    #[test_case("decorated = maybe -> float"; "decorated not a bool")]
+    #[test_case("class = discord -> fullscreen"; "unknown action")]
+    #[test_case("class = discord -> workspace"; "missing argument")]
+    #[test_case("class = discord -> float_centered 0.5 1.5"; "ratio out of range")]
+    #[test_case("class = discord -> float_centered 0.5 big"; "ratio not a number")]
+    #[test_case("class = discord -> insert sideways"; "unknown position")]
+    #[test]
+    fn parse_invalid_rule(line: &str) {
+        assert!(parse_rule(line).is_err());
+    }
+
+    #[test]
+    fn parse_rules_skips_comments_and_blank_lines() {
+        let s = "# placement rules\n\nclass = discord -> workspace 9\n  # indented\ntitle = mpv -> float\n";
+        let rules = parse_rules(s).unwrap();
+
+        assert_eq!(
+            rules,
+            vec![
+                Rule {
+                    query: RuleMatch::ClassName("discord".into()),
+                    action: RuleAction::Workspace("9".into()),
+                },
+                Rule {
+                    query: RuleMatch::Title("mpv".into()),
+                    action: RuleAction::Float,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rules_reports_line_number() {
+        let s = "# placement rules\nclass = discord -> workspace 9\n\nclass = mpv -> sink\n";
+
+        match parse_rules(s) {
+            Err(Error::InvalidRule { line, .. }) => assert_eq!(line, 4),
+            res => panic!("expected InvalidRule error, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_reload_keeps_existing_rules() {
+        let path = std::env::temp_dir().join(format!("penrose-rules-{}", std::process::id()));
+        fs::write(&path, "class = discord -> workspace 9\n").unwrap();
+        let mut rf = RulesFile::load(&path).unwrap();
+
+        fs::write(&path, "class = discord -> nonsense\n").unwrap();
+        let res = rf.reload();
+        fs::remove_file(&path).unwrap();
+
+        assert!(res.is_err());
+        assert_eq!(rf.rules().len(), 1);
+    }
+
+    #[test]
+    fn title_falls_back_to_net_wm_name() {
+        let conn = HeadlessConn::default();
+        let id = conn.create_window("mpv");
+        conn.delete_prop(id, Atom::WmName.as_ref()).unwrap();
+
+        assert!(RuleMatch::Title("mpv".into()).matches(id, &conn).unwrap());
+    }
+}
@@ -0,0 +1,310 @@
+//! Switching between light and dark themes on a schedule or by following the system setting.
+//!
+//! [add_theme_switching] uses [add_polled_source][1] to periodically check which [ThemeMode]
+//! should be active according to a [ThemeSource], switching theme whenever the result changes.
+//! Switching theme updates the border colours in the [Config][0] and re-applies them to all
+//! existing clients before triggering a refresh. The active [Theme] is stored in the
+//! [ActiveTheme] state extension so that other components (such as the `penrose_ui` status
+//! bar) can restyle themselves when it changes.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! # use std::time::Duration;
+//! use penrose::extensions::hooks::theme::{add_theme_switching, Theme, ThemeSource};
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> penrose::Result<WindowManager<RustConn>> {
+//! let light = Theme {
+//!     fg: "#3c3836ff".try_into()?,
+//!     bg: "#fbf1c7ff".try_into()?,
+//!     highlight: "#458588ff".try_into()?,
+//!     dim: "#a89984ff".try_into()?,
+//!     normal_border: "#d5c4a1ff".try_into()?,
+//!     focused_border: "#458588ff".try_into()?,
+//! };
+//! let dark = Theme {
+//!     fg: "#ebdbb2ff".try_into()?,
+//!     bg: "#282828ff".try_into()?,
+//!     highlight: "#cc241dff".try_into()?,
+//!     dim: "#665c54ff".try_into()?,
+//!     normal_border: "#3c3836ff".try_into()?,
+//!     focused_border: "#cc241dff".try_into()?,
+//! };
+//!
+//! // Use the light theme between 07:30 and 19:00
+//! let source = ThemeSource::Schedule {
+//!     light_from: (7, 30),
+//!     dark_from: (19, 0),
+//! };
+//!
+//! Ok(add_theme_switching(wm, light, dark, source, Duration::from_secs(60)))
+//! # }
+//! ```
+//!
+//!   [0]: crate::core::Config
+//!   [1]: crate::extensions::hooks::add_polled_source
+use crate::{
+    core::{State, WindowManager},
+    extensions::hooks::add_polled_source,
+    util::helper_output_with_args,
+    x::{XConn, XConnExt},
+    Color, Result,
+};
+use nix::libc;
+use std::{
+    mem::MaybeUninit,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// A set of colours used for client borders and the status bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// The foreground colour for text
+    pub fg: Color,
+    /// The background colour
+    pub bg: Color,
+    /// The colour used to highlight focused elements
+    pub highlight: Color,
+    /// The foreground colour for inactive or empty elements
+    pub dim: Color,
+    /// The border colour for unfocused clients
+    pub normal_border: Color,
+    /// The border colour for the focused client
+    pub focused_border: Color,
+}
+
+/// Whether the light or dark [Theme] is active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThemeMode {
+    /// The light theme
+    Light,
+    /// The dark theme
+    Dark,
+}
+
+/// How to determine which [ThemeMode] should be active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeSource {
+    /// Switch between themes at fixed local times, given as `(hour, minute)` pairs.
+    Schedule {
+        /// The time at which to switch to the light theme
+        light_from: (u32, u32),
+        /// The time at which to switch to the dark theme
+        dark_from: (u32, u32),
+    },
+    /// Follow the `color-scheme` setting of the freedesktop settings portal over D-Bus (read
+    /// using `gdbus`). If the system has no preference then the current theme is kept.
+    SystemColorScheme,
+}
+
+impl ThemeSource {
+    // The mode that should be active at the given number of minutes past midnight.
+    fn mode_at(light_from: (u32, u32), dark_from: (u32, u32), now: u32) -> ThemeMode {
+        let (light, dark) = (minutes(light_from), minutes(dark_from));
+        let is_light = if light <= dark {
+            (light..dark).contains(&now)
+        } else {
+            !(dark..light).contains(&now)
+        };
+
+        if is_light {
+            ThemeMode::Light
+        } else {
+            ThemeMode::Dark
+        }
+    }
+
+    fn current_mode(&self) -> Option<ThemeMode> {
+        match *self {
+            Self::Schedule {
+                light_from,
+                dark_from,
+            } => Some(Self::mode_at(light_from, dark_from, local_minutes()?)),
+
+            Self::SystemColorScheme => {
+                let raw = helper_output_with_args(
+                    "gdbus",
+                    &[
+                        "call",
+                        "--session",
+                        "--dest",
+                        "org.freedesktop.portal.Desktop",
+                        "--object-path",
+                        "/org/freedesktop/portal/desktop",
+                        "--method",
+                        "org.freedesktop.portal.Settings.Read",
+                        "org.freedesktop.appearance",
+                        "color-scheme",
+                    ],
+                )
+                .ok()?;
+
+                parse_color_scheme(&raw)
+            }
+        }
+    }
+}
+
+fn minutes((h, m): (u32, u32)) -> u32 {
+    (h % 24) * 60 + m % 60
+}
+
+// The current local time as a number of minutes past midnight.
+fn local_minutes() -> Option<u32> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let now = libc::time_t::try_from(secs).ok()?;
+    let mut tm = MaybeUninit::<libc::tm>::uninit();
+
+    // SAFETY: localtime_r only writes to the tm struct we provide, which is only read if the
+    //         call succeeds and it has been initialised.
+    let tm = unsafe {
+        if libc::localtime_r(&now, tm.as_mut_ptr()).is_null() {
+            return None;
+        }
+        tm.assume_init()
+    };
+
+    Some(minutes((
+        tm.tm_hour.try_into().ok()?,
+        tm.tm_min.try_into().ok()?,
+    )))
+}
+
+// Output from gdbus is of the form '(<<uint32 1>>,)' where 1 is a preference for a dark
+// theme, 2 is a preference for a light theme and 0 is no preference.
+fn parse_color_scheme(raw: &str) -> Option<ThemeMode> {
+    let (_, rest) = raw.split_once("uint32 ")?;
+    let n: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+
+    match n.parse::<u32>().ok()? {
+        1 => Some(ThemeMode::Dark),
+        2 => Some(ThemeMode::Light),
+        _ => None,
+    }
+}
+
+/// The state extension holding the available themes and tracking which one is active.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveTheme {
+    light: Theme,
+    dark: Theme,
+    mode: Option<ThemeMode>,
+}
+
+impl ActiveTheme {
+    /// The currently active mode, or `None` if no theme has been applied yet.
+    pub fn mode(&self) -> Option<ThemeMode> {
+        self.mode
+    }
+
+    /// The currently active theme, or `None` if no theme has been applied yet.
+    pub fn theme(&self) -> Option<&Theme> {
+        self.mode.map(|m| self.theme_for(m))
+    }
+
+    /// The theme used for the given mode.
+    pub fn theme_for(&self, mode: ThemeMode) -> &Theme {
+        match mode {
+            ThemeMode::Light => &self.light,
+            ThemeMode::Dark => &self.dark,
+        }
+    }
+}
+
+/// Add the [ActiveTheme] state extension and the hooks required for switching between the
+/// `light` and `dark` themes, checking the [ThemeSource] every `poll_interval`.
+pub fn add_theme_switching<X>(
+    mut wm: WindowManager<X>,
+    light: Theme,
+    dark: Theme,
+    source: ThemeSource,
+    poll_interval: Duration,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(ActiveTheme {
+        light,
+        dark,
+        mode: None,
+    });
+
+    add_polled_source(
+        wm,
+        poll_interval,
+        move || source.current_mode(),
+        apply_theme,
+    )
+}
+
+/// Switch to the theme for the given mode, updating the borders of all clients and
+/// refreshing the X state.
+///
+/// This is a no-op if the mode is already active or [add_theme_switching] has not been called.
+pub fn apply_theme<X: XConn>(mode: ThemeMode, state: &mut State<X>, x: &X) -> Result<()> {
+    let at = match state.extension::<ActiveTheme>() {
+        Ok(at) => at,
+        Err(_) => return Ok(()),
+    };
+
+    let theme = {
+        let mut at = at.borrow_mut();
+        if at.mode == Some(mode) {
+            return Ok(());
+        }
+        at.mode = Some(mode);
+
+        *at.theme_for(mode)
+    };
+
+    info!(?mode, "switching theme");
+    state.config.normal_border = theme.normal_border;
+    state.config.focused_border = theme.focused_border;
+
+    let focused = state.client_set.current_client().copied();
+    for &id in state.client_set.clients() {
+        if state.client_set.bars.contains_key(&id) {
+            continue;
+        }
+
+        let color = if Some(id) == focused {
+            theme.focused_border
+        } else {
+            theme.normal_border
+        };
+        x.set_client_border_color(id, color)?;
+    }
+
+    x.refresh(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case((7, 0), (19, 0), (12, 0), ThemeMode::Light; "day")]
+    #[test_case((7, 0), (19, 0), (7, 0), ThemeMode::Light; "at light start")]
+    #[test_case((7, 0), (19, 0), (19, 0), ThemeMode::Dark; "at dark start")]
+    #[test_case((7, 0), (19, 0), (2, 30), ThemeMode::Dark; "night")]
+    #[test_case((22, 0), (6, 0), (23, 0), ThemeMode::Light; "wrapping light")]
+    #[test_case((22, 0), (6, 0), (12, 0), ThemeMode::Dark; "wrapping dark")]
+    #[test]
+    fn mode_at(light: (u32, u32), dark: (u32, u32), now: (u32, u32), expected: ThemeMode) {
+        assert_eq!(ThemeSource::mode_at(light, dark, minutes(now)), expected);
+    }
+
+    #[test_case("(<<uint32 1>>,)\n", Some(ThemeMode::Dark); "dark")]
+    #[test_case("(<<uint32 2>>,)\n", Some(ThemeMode::Light); "light")]
+    #[test_case("(<<uint32 0>>,)\n", None; "no preference")]
+    #[test_case("Error: no such interface", None; "error")]
+    #[test]
+    fn parse_color_scheme_works(raw: &str, expected: Option<ThemeMode>) {
+        assert_eq!(parse_color_scheme(raw), expected);
+    }
+
+    #[test]
+    fn local_minutes_is_within_a_day() {
+        assert!(local_minutes().unwrap() < 24 * 60);
+    }
+}
@@ -0,0 +1,337 @@
+//! Pinning workspaces to a preferred output.
+//!
+//! [PinnedWorkspaces] records the output (either by name or by screen index) that each pinned
+//! workspace should be shown on. Whenever the preferred output is connected, a pinned workspace
+//! that becomes visible on any other screen is moved over to its preferred output, with focus
+//! following it if it was the focused workspace. When the output is disconnected the workspace
+//! is free to be shown on the remaining screens as normal, moving back to its preferred output
+//! once it is reconnected.
+//!
+//! Pins have no effect when running with per-screen workspaces, as workspaces are already tied
+//! to a specific screen.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! use penrose::extensions::hooks::workspace_pinning::{
+//!     add_workspace_pinning, PinnedWorkspaces, PreferredOutput,
+//! };
+//!
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! let pins = PinnedWorkspaces::new()
+//!     .pin("1", "DP-1")
+//!     .pin("2", PreferredOutput::Primary)
+//!     .pin("9", 1);
+//!
+//! add_workspace_pinning(wm, pins)
+//! # }
+//! ```
+use crate::{
+    core::{ClientSet, State, WindowManager},
+    x::{Output, XConn, XConnExt, XEvent},
+    Result,
+};
+use std::collections::HashMap;
+use tracing::debug;
+
+/// The output that a pinned workspace should be shown on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PreferredOutput {
+    /// The output with the given name as reported by the X server (e.g. `eDP-1`)
+    Name(String),
+    /// The screen with the given index
+    Index(usize),
+    /// The screen showing the primary output (see [primary_screen][0])
+    ///
+    ///   [0]: crate::pure::StackSet::primary_screen
+    Primary,
+}
+
+impl From<&str> for PreferredOutput {
+    fn from(name: &str) -> Self {
+        Self::Name(name.to_string())
+    }
+}
+
+impl From<String> for PreferredOutput {
+    fn from(name: String) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<usize> for PreferredOutput {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
+/// The preferred output of each pinned workspace.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PinnedWorkspaces {
+    pins: Vec<(String, PreferredOutput)>,
+    // Screen indices for named outputs: cleared whenever RandR reports a change
+    resolved: Option<HashMap<String, usize>>,
+}
+
+impl PinnedWorkspaces {
+    /// Create a new, empty, set of pins.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin the workspace with the given tag to the given output, replacing any existing pin.
+    pub fn pin(mut self, tag: impl Into<String>, output: impl Into<PreferredOutput>) -> Self {
+        let tag = tag.into();
+        self.pins.retain(|(t, _)| *t != tag);
+        self.pins.push((tag, output.into()));
+        self.resolved = None;
+
+        self
+    }
+
+    /// The preferred output of the workspace with the given tag, if it is pinned.
+    pub fn preferred_output(&self, tag: &str) -> Option<&PreferredOutput> {
+        self.pins.iter().find(|(t, _)| t == tag).map(|(_, o)| o)
+    }
+
+    fn resolve(&mut self, cs: &ClientSet, outputs: &[Output]) {
+        let resolved = outputs
+            .iter()
+            .flat_map(|o| {
+                let r = o.r?;
+                let s = cs.screens().find(|s| s.r.contains_point(r.midpoint()))?;
+
+                Some((o.name.clone(), s.index()))
+            })
+            .collect();
+
+        self.resolved = Some(resolved);
+    }
+
+    // The pinned tags along with the index of their preferred screen if it is connected.
+    fn screen_pins(&self, cs: &ClientSet) -> Vec<(String, usize)> {
+        let resolved = self.resolved.as_ref();
+
+        self.pins
+            .iter()
+            .flat_map(|(tag, output)| {
+                let index = match output {
+                    PreferredOutput::Name(name) => *resolved?.get(name)?,
+                    PreferredOutput::Index(index) => *index,
+                    PreferredOutput::Primary => cs.primary_screen(),
+                };
+
+                cs.screens()
+                    .any(|s| s.index() == index)
+                    .then(|| (tag.clone(), index))
+            })
+            .collect()
+    }
+}
+
+// Move pinned workspaces that are visible on the wrong screen to their preferred screen,
+// returning whether or not anything was moved.
+//
+// If the workspace already on the preferred screen is also pinned there then it is only
+// displaced by the focused workspace, so that two workspaces pinned to the same screen do
+// not repeatedly swap places with one another.
+fn move_to_preferred_screens(cs: &mut ClientSet, pins: &[(String, usize)]) -> bool {
+    if cs.is_per_screen() {
+        return false;
+    }
+
+    let focused_tag = cs.current_tag().to_string();
+    let mut moved = false;
+
+    for (tag, index) in pins.iter() {
+        let current = match cs.screens().find(|s| &s.workspace.tag == tag) {
+            Some(s) if s.index() != *index => s.index(),
+            _ => continue,
+        };
+
+        let occupant = cs.tag_for_screen(*index).unwrap_or_default();
+        let occupant_pinned = pins.iter().any(|(t, i)| t == occupant && i == index);
+        if occupant_pinned && *tag != focused_tag {
+            continue;
+        }
+
+        debug!(%tag, from = current, to = index, "moving pinned workspace to preferred screen");
+        cs.focus_screen(*index);
+        cs.pull_tag_to_screen(tag);
+        moved = true;
+    }
+
+    // Focus stays with the workspace that was focused before anything was moved
+    let focused = cs
+        .screens()
+        .find(|s| s.workspace.tag == focused_tag)
+        .map(|s| s.index());
+    if let Some(index) = focused {
+        cs.focus_screen(index);
+    }
+
+    moved
+}
+
+/// Add the given [PinnedWorkspaces] as a state extension along with the hooks required to
+/// move pinned workspaces to their preferred outputs.
+pub fn add_workspace_pinning<X>(
+    mut wm: WindowManager<X>,
+    pins: PinnedWorkspaces,
+) -> WindowManager<X>
+where
+    X: XConn + 'static,
+{
+    wm.state.add_extension(pins);
+    wm.state.config.compose_or_set_event_hook(event_hook);
+    wm.state.config.compose_or_set_refresh_hook(refresh_hook);
+
+    wm
+}
+
+/// Forget the screens that named outputs were found on when RandR reports a change so that
+/// they are looked up again on the next refresh.
+pub fn event_hook<X: XConn>(event: &XEvent, state: &mut State<X>, _: &X) -> Result<bool> {
+    if let XEvent::RandrNotify = event {
+        state
+            .extension_or_default::<PinnedWorkspaces>()
+            .borrow_mut()
+            .resolved = None;
+    }
+
+    Ok(true)
+}
+
+/// Move any pinned workspaces that are visible on a screen other than their preferred one.
+pub fn refresh_hook<X: XConn>(state: &mut State<X>, x: &X) -> Result<()> {
+    let pinned = state.extension_or_default::<PinnedWorkspaces>();
+    if pinned.borrow().resolved.is_none() {
+        let outputs = x.outputs()?;
+        pinned.borrow_mut().resolve(&state.client_set, &outputs);
+    }
+
+    let pins = pinned.borrow().screen_pins(&state.client_set);
+    if move_to_preferred_screens(&mut state.client_set, &pins) {
+        x.refresh(state)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pure::{
+            geometry::{Rect, Rotation},
+            test_xid_stack_set,
+        },
+        testing::{HeadlessConn, Scenario},
+    };
+    use simple_test_case::test_case;
+
+    fn pins(pins: &[(&str, usize)]) -> Vec<(String, usize)> {
+        pins.iter().map(|&(t, i)| (t.to_string(), i)).collect()
+    }
+
+    fn visible_tags(cs: &ClientSet) -> Vec<&str> {
+        let mut screens: Vec<_> = cs.screens().collect();
+        screens.sort_by_key(|s| s.index());
+
+        screens.iter().map(|s| s.workspace.tag.as_str()).collect()
+    }
+
+    #[test_case("3", &[("3", 1)], vec!["2", "3"], "3"; "focused pinned workspace moves with focus")]
+    #[test_case("3", &[("2", 0)], vec!["2", "3"], "3"; "unfocused pinned workspace moves without focus")]
+    #[test_case("3", &[("3", 0)], vec!["3", "2"], "3"; "already on preferred screen")]
+    #[test_case("3", &[("2", 1), ("3", 1)], vec!["2", "3"], "3"; "focused workspace wins")]
+    #[test_case("3", &[("3", 1), ("2", 1)], vec!["2", "3"], "3"; "pin order does not matter")]
+    #[test]
+    fn move_to_preferred_screens_works(
+        focus: &str,
+        pins_: &[(&str, usize)],
+        expected: Vec<&str>,
+        expected_focus: &str,
+    ) {
+        let mut cs = test_xid_stack_set(5, 2);
+        cs.focus_tag(focus);
+        let pins = pins(pins_);
+
+        move_to_preferred_screens(&mut cs, &pins);
+
+        assert_eq!(visible_tags(&cs), expected);
+        assert_eq!(cs.current_tag(), expected_focus);
+        assert!(
+            !move_to_preferred_screens(&mut cs, &pins),
+            "pins should be stable"
+        );
+    }
+
+    #[test]
+    fn unconnected_screens_are_ignored() {
+        let cs = test_xid_stack_set(5, 2);
+        let pinned = PinnedWorkspaces::new()
+            .pin("1", 1)
+            .pin("2", 2)
+            .pin("3", "HDMI-1");
+
+        assert_eq!(pinned.screen_pins(&cs), pins(&[("1", 1)]));
+    }
+
+    #[test]
+    fn primary_pins_follow_the_primary_screen() {
+        let mut cs = test_xid_stack_set(5, 2);
+        let pinned = PinnedWorkspaces::new().pin("3", PreferredOutput::Primary);
+
+        assert_eq!(pinned.screen_pins(&cs), pins(&[("3", 0)]));
+
+        cs.set_primary_screen(Some(1));
+        assert_eq!(pinned.screen_pins(&cs), pins(&[("3", 1)]));
+    }
+
+    #[test]
+    fn named_workspaces_return_to_their_output_on_reconnect() {
+        let laptop = Rect::new(0, 0, 1920, 1080);
+        let external = Rect::new(1920, 0, 1920, 1080);
+        let output = |name: &str, r: Option<Rect>| Output {
+            name: name.to_string(),
+            connected: r.is_some(),
+            r,
+            primary: false,
+            rotation: Rotation::Normal,
+        };
+
+        let conn = HeadlessConn::new(vec![laptop, external]);
+        conn.set_outputs(vec![
+            output("eDP-1", Some(laptop)),
+            output("HDMI-1", Some(external)),
+        ]);
+        let wm =
+            WindowManager::new(Default::default(), HashMap::new(), HashMap::new(), conn).unwrap();
+        let pins = PinnedWorkspaces::new().pin("3", "HDMI-1");
+        let s = Scenario::new(add_workspace_pinning(wm, pins))
+            .unwrap()
+            .act(|state, _| {
+                state.client_set.focus_tag("3");
+                Ok(())
+            });
+
+        assert_eq!(visible_tags(&s.state().client_set), vec!["2", "3"]);
+
+        s.conn()
+            .set_outputs(vec![output("eDP-1", Some(laptop)), output("HDMI-1", None)]);
+        let s = s.act(|_, _| Ok(())).act(|state, _| {
+            state.client_set.focus_tag("3");
+            Ok(())
+        });
+
+        assert_eq!(visible_tags(&s.state().client_set), vec!["3"]);
+
+        s.conn().set_outputs(vec![
+            output("eDP-1", Some(laptop)),
+            output("HDMI-1", Some(external)),
+        ]);
+        let s = s.act(|_, _| Ok(()));
+
+        assert_eq!(s.state().client_set.tag_for_screen(1), Some("3"));
+    }
+}
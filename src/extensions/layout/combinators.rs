@@ -87,6 +87,22 @@ impl Layout for Conditional {
         }
     }
 
+    fn border_width(&self) -> Option<u32> {
+        if self.left_is_active {
+            self.left.border_width()
+        } else {
+            self.right.border_width()
+        }
+    }
+
+    fn parameters(&self) -> Vec<(String, String)> {
+        if self.left_is_active {
+            self.left.parameters()
+        } else {
+            self.right.parameters()
+        }
+    }
+
     fn handle_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
         if self.left_is_active {
             self.left.handle_message(m)
@@ -1,6 +1,6 @@
 //! Layout behaviour that is more specialised or complex than the builtin layouts.
 use crate::{
-    builtin::layout::messages::{ExpandMain, ShrinkMain},
+    builtin::layout::messages::{Equalize, ExpandMain, ShrinkMain},
     core::layout::{Layout, Message},
     pure::{geometry::Rect, Stack},
     Xid,
@@ -19,7 +19,7 @@ pub mod quickcheck_tests;
 ///   https://dwm.suckless.org/patches/fibonacci/
 ///
 /// The ratio between the main and secondary regions can be adjusted by sending [ShrinkMain]
-/// and [ExpandMain] messages to this layout.
+/// and [ExpandMain] messages to this layout and reset with an [Equalize] message.
 ///
 /// ```text
 /// ....................................
@@ -40,6 +40,7 @@ pub mod quickcheck_tests;
 pub struct Fibonacci {
     cutoff: u32,
     ratio: f32,
+    initial_ratio: f32,
     ratio_step: f32,
 }
 
@@ -48,6 +49,7 @@ impl Default for Fibonacci {
         Self {
             cutoff: 40,
             ratio: 0.5,
+            initial_ratio: 0.5,
             ratio_step: 0.1,
         }
     }
@@ -60,6 +62,7 @@ impl Fibonacci {
         Fibonacci {
             cutoff,
             ratio,
+            initial_ratio: ratio,
             ratio_step,
         }
     }
@@ -123,6 +126,10 @@ impl Layout for Fibonacci {
         (None, positions)
     }
 
+    fn parameters(&self) -> Vec<(String, String)> {
+        vec![("ratio".to_owned(), format!("{:.2}", self.ratio))]
+    }
+
     fn handle_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
         if let Some(&ExpandMain) = m.downcast_ref() {
             self.ratio += self.ratio_step;
@@ -134,6 +141,8 @@ impl Layout for Fibonacci {
             if self.ratio < 0.0 {
                 self.ratio = 0.0;
             }
+        } else if let Some(&Equalize) = m.downcast_ref() {
+            self.ratio = self.initial_ratio;
         };
 
         None
@@ -180,18 +189,23 @@ impl Layout for Fibonacci {
 #[derive(Debug, Copy, Clone)]
 pub struct Tatami {
     ratio: f32,
+    initial_ratio: f32,
     ratio_step: f32,
 }
 
 impl Tatami {
     /// Create a new [Tatami] layout with the specified ratio for the main window.
     pub fn new(ratio: f32, ratio_step: f32) -> Self {
-        Self { ratio, ratio_step }
+        Self {
+            ratio,
+            initial_ratio: ratio,
+            ratio_step,
+        }
     }
 
     /// Create a new [Tatami] layout returned as a trait object ready to be added to your [LayoutStack].
     pub fn boxed(ratio: f32, ratio_step: f32) -> Box<dyn Layout> {
-        Box::new(Tatami { ratio, ratio_step })
+        Box::new(Tatami::new(ratio, ratio_step))
     }
 }
 
@@ -199,6 +213,7 @@ impl Default for Tatami {
     fn default() -> Self {
         Self {
             ratio: 0.6,
+            initial_ratio: 0.6,
             ratio_step: 0.1,
         }
     }
@@ -275,6 +290,10 @@ impl Layout for Tatami {
         (None, positions)
     }
 
+    fn parameters(&self) -> Vec<(String, String)> {
+        vec![("ratio".to_owned(), format!("{:.2}", self.ratio))]
+    }
+
     fn handle_message(&mut self, m: &Message) -> Option<Box<dyn Layout>> {
         if let Some(&ExpandMain) = m.downcast_ref() {
             self.ratio += self.ratio_step;
@@ -286,6 +305,8 @@ impl Layout for Tatami {
             if self.ratio < 0.0 {
                 self.ratio = 0.0;
             }
+        } else if let Some(&Equalize) = m.downcast_ref() {
+            self.ratio = self.initial_ratio;
         };
 
         None
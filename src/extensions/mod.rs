@@ -1,6 +1,9 @@
 //! Extensions to the base behaviour of Penrose
 
+#[cfg(feature = "wm")]
 pub mod actions;
+#[cfg(feature = "wm")]
 pub mod hooks;
 pub mod layout;
+#[cfg(feature = "wm")]
 pub mod util;
@@ -0,0 +1,357 @@
+//! Validation of a window manager configuration without connecting to the X server.
+//!
+//! This is intended to be wired up to a `--check-config` flag in your `main.rs` so that a
+//! newly built window manager can be checked for obvious mistakes before it replaces the
+//! one that is currently running:
+//!
+//! ```no_run
+//! # use penrose::{core::Config, extensions::util::check::{check_requested, ConfigCheck}, x11rb::RustConn};
+//! # fn config() -> Config<RustConn> { Config::default() }
+//! if check_requested() {
+//!     ConfigCheck::new(&config())
+//!         .key_bindings(["M-j", "M-k", "M-S-q"])
+//!         .report()
+//!         .exit();
+//! }
+//! ```
+//!
+//! The following checks are run:
+//!   - workspace tags must be non-empty and unique
+//!   - window classes should not be marked as both floating and bars
+//!   - monitor overrides must only reference layouts that are present in the default layouts
+//!   - key bindings must use known modifiers and must not bind the same key combination twice
+//!   - layout ratios must be strictly between 0.0 and 1.0
+//!   - [Rule]s must only reference workspace tags that exist and must use ratios between 0.0
+//!     and 1.0
+use crate::{
+    core::{bindings::ModifierKey, Config},
+    extensions::hooks::rules::{Rule, RuleAction},
+    x::XConn,
+};
+use std::{collections::HashMap, fmt, process};
+
+/// The command line flag used to request a configuration check: see [check_requested].
+pub const CHECK_CONFIG_FLAG: &str = "--check-config";
+
+/// Whether or not [CHECK_CONFIG_FLAG] was passed as a command line argument.
+pub fn check_requested() -> bool {
+    std::env::args().any(|arg| arg == CHECK_CONFIG_FLAG)
+}
+
+/// Checks to run against a user [Config] and associated bindings and rules.
+#[derive(Debug)]
+pub struct ConfigCheck {
+    report: ConfigReport,
+    tags: Vec<String>,
+}
+
+impl ConfigCheck {
+    /// Start a new check, validating the given [Config].
+    pub fn new<X: XConn>(config: &Config<X>) -> Self {
+        let mut report = ConfigReport::default();
+
+        if config.tags.is_empty() {
+            report.error("no workspace tags have been configured");
+        }
+
+        let mut seen = Vec::new();
+        for tag in config.tags.iter() {
+            if tag.is_empty() {
+                report.error("workspace tags must not be empty");
+            } else if seen.contains(&tag) {
+                report.error(format!("workspace tag '{tag}' is used more than once"));
+            }
+            seen.push(tag);
+        }
+
+        let layouts: Vec<String> = config.default_layouts.iter().map(|l| l.name()).collect();
+        for (output, overrides) in config.monitor_overrides.iter() {
+            if let Some(name) = &overrides.default_layout {
+                if !layouts.contains(name) {
+                    report.error(format!(
+                        "monitor override for '{output}' uses unknown layout '{name}'"
+                    ));
+                }
+            }
+        }
+
+        for class in config.floating_classes.iter() {
+            if config.bar_classes.contains(class) {
+                report.warning(format!(
+                    "window class '{class}' is marked as floating and as a bar"
+                ));
+            }
+        }
+
+        Self {
+            report,
+            tags: config.tags.clone(),
+        }
+    }
+
+    /// Check the string representation of key bindings as passed to
+    /// [parse_keybindings_with_xmodmap][0].
+    ///
+    /// Key names are not validated as this requires `xmodmap` to be available.
+    ///
+    ///   [0]: crate::core::bindings::parse_keybindings_with_xmodmap
+    pub fn key_bindings<I, S>(mut self, bindings: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut seen: HashMap<(Vec<ModifierKey>, String), String> = HashMap::new();
+
+        for binding in bindings {
+            let binding = binding.as_ref();
+            let mut parts: Vec<&str> = binding.split('-').collect();
+            let name = parts.remove(parts.len() - 1);
+
+            if name.is_empty() {
+                self.report
+                    .error(format!("key binding '{binding}' does not specify a key"));
+                continue;
+            }
+
+            let mut mods = Vec::with_capacity(parts.len());
+            for part in parts {
+                match ModifierKey::try_from(part) {
+                    Ok(m) => mods.push(m),
+                    Err(e) => self.report.error(format!("key binding '{binding}': {e}")),
+                }
+            }
+            mods.sort();
+            mods.dedup();
+
+            if let Some(existing) = seen.insert((mods, name.to_string()), binding.to_string()) {
+                self.report.error(format!(
+                    "key bindings '{existing}' and '{binding}' bind the same keys"
+                ));
+            }
+        }
+
+        self
+    }
+
+    /// Check the main area ratios passed to layouts such as [MainAndStack][0], which must be
+    /// strictly between 0.0 and 1.0.
+    ///
+    ///   [0]: crate::builtin::layout::MainAndStack
+    pub fn layout_ratios<I>(mut self, ratios: I) -> Self
+    where
+        I: IntoIterator<Item = f32>,
+    {
+        for ratio in ratios {
+            if !(ratio > 0.0 && ratio < 1.0) {
+                self.report
+                    .error(format!("layout ratio {ratio} is not between 0.0 and 1.0"));
+            }
+        }
+
+        self
+    }
+
+    /// Check that the given [Rule]s only reference known workspace tags and use valid ratios.
+    pub fn rules(mut self, rules: &[Rule]) -> Self {
+        for rule in rules.iter() {
+            let ratios = match &rule.action {
+                RuleAction::Workspace(tag) => {
+                    if !self.tags.contains(tag) {
+                        self.report.error(format!(
+                            "rule for {:?} references unknown workspace '{tag}'",
+                            rule.query
+                        ));
+                    }
+                    continue;
+                }
+                RuleAction::FloatCentered(w, h) => vec![*w, *h],
+                RuleAction::FloatRelative(x, y, w, h) => vec![*x, *y, *w, *h],
+                _ => continue,
+            };
+
+            if ratios.iter().any(|r| !(0.0..=1.0).contains(r)) {
+                self.report.error(format!(
+                    "rule for {:?} uses a ratio that is not between 0.0 and 1.0",
+                    rule.query
+                ));
+            }
+        }
+
+        self
+    }
+
+    /// Finish running checks and return the resulting [ConfigReport].
+    pub fn report(self) -> ConfigReport {
+        self.report
+    }
+}
+
+/// The outcome of running a [ConfigCheck].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConfigReport {
+    /// Problems that will prevent the window manager from running correctly
+    pub errors: Vec<String>,
+    /// Potential problems that will not prevent the window manager from running
+    pub warnings: Vec<String>,
+}
+
+impl ConfigReport {
+    /// Whether or not the check found no errors.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Print this report and exit the process: with a status of 0 if there were no errors
+    /// and 1 otherwise.
+    pub fn exit(self) -> ! {
+        if self.is_ok() {
+            println!("{self}");
+            process::exit(0);
+        }
+
+        eprintln!("{self}");
+        process::exit(1);
+    }
+
+    fn error(&mut self, msg: impl Into<String>) {
+        self.errors.push(msg.into());
+    }
+
+    fn warning(&mut self, msg: impl Into<String>) {
+        self.warnings.push(msg.into());
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for e in self.errors.iter() {
+            writeln!(f, "error: {e}")?;
+        }
+        for w in self.warnings.iter() {
+            writeln!(f, "warning: {w}")?;
+        }
+
+        write!(
+            f,
+            "config check {}: {} error(s), {} warning(s)",
+            if self.is_ok() { "passed" } else { "failed" },
+            self.errors.len(),
+            self.warnings.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::MonitorConfig, extensions::hooks::rules::RuleMatch, x::StubXConn};
+    use simple_test_case::test_case;
+
+    fn config_with_tags(tags: &[&str]) -> Config<StubXConn> {
+        Config {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test_case(&["1", "2", "3"], 0; "valid")]
+    #[test_case(&[], 1; "no tags")]
+    #[test_case(&["1", ""], 1; "empty tag")]
+    #[test_case(&["1", "2", "1"], 1; "duplicate tag")]
+    #[test]
+    fn tags_are_checked(tags: &[&str], n_errors: usize) {
+        let report = ConfigCheck::new(&config_with_tags(tags)).report();
+
+        assert_eq!(report.errors.len(), n_errors, "{report}");
+    }
+
+    #[test]
+    fn overlapping_classes_are_a_warning() {
+        let config: Config<StubXConn> = Config {
+            floating_classes: vec!["dmenu".into()],
+            bar_classes: vec!["dmenu".into(), "polybar".into()],
+            ..Default::default()
+        };
+
+        let report = ConfigCheck::new(&config).report();
+
+        assert!(report.is_ok());
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test_case("Side", true; "known layout")]
+    #[test_case("Mono", false; "unknown layout")]
+    #[test]
+    fn monitor_override_layouts_are_checked(layout: &str, ok: bool) {
+        let mut config = config_with_tags(&["1"]);
+        config.monitor_overrides.insert(
+            "HDMI-1".to_string(),
+            MonitorConfig::new().with_default_layout(layout),
+        );
+
+        let report = ConfigCheck::new(&config).report();
+
+        assert_eq!(report.is_ok(), ok, "{report}");
+    }
+
+    #[test_case(&[0.6, 0.5], 0; "valid")]
+    #[test_case(&[0.0], 1; "zero")]
+    #[test_case(&[1.0], 1; "one")]
+    #[test_case(&[1.5, -0.2], 2; "out of range")]
+    #[test_case(&[f32::NAN], 1; "not a number")]
+    #[test]
+    fn layout_ratios_are_checked(ratios: &[f32], n_errors: usize) {
+        let report = ConfigCheck::new(&config_with_tags(&["1"]))
+            .layout_ratios(ratios.iter().copied())
+            .report();
+
+        assert_eq!(report.errors.len(), n_errors, "{report}");
+    }
+
+    #[test_case(RuleAction::FloatCentered(0.5, 0.6), true; "valid centered")]
+    #[test_case(RuleAction::FloatCentered(0.5, 1.6), false; "invalid centered")]
+    #[test_case(RuleAction::FloatRelative(0.1, 0.1, 0.8, 0.8), true; "valid relative")]
+    #[test_case(RuleAction::FloatRelative(-0.1, 0.1, 0.8, 0.8), false; "invalid relative")]
+    #[test]
+    fn rule_ratios_are_checked(action: RuleAction, ok: bool) {
+        let rules = vec![Rule {
+            query: RuleMatch::ClassName("mpv".into()),
+            action,
+        }];
+
+        let report = ConfigCheck::new(&config_with_tags(&["1"]))
+            .rules(&rules)
+            .report();
+
+        assert_eq!(report.is_ok(), ok);
+    }
+
+    #[test_case(&["M-j", "M-k", "M-S-j"], 0; "valid")]
+    #[test_case(&["M-S-j", "S-M-j"], 1; "same keys in different order")]
+    #[test_case(&["M-X-j"], 1; "unknown modifier")]
+    #[test_case(&["M-"], 1; "missing key")]
+    #[test]
+    fn key_bindings_are_checked(bindings: &[&str], n_errors: usize) {
+        let report = ConfigCheck::new(&config_with_tags(&["1"]))
+            .key_bindings(bindings)
+            .report();
+
+        assert_eq!(report.errors.len(), n_errors, "{report}");
+    }
+
+    #[test_case("1", true; "known tag")]
+    #[test_case("9", false; "unknown tag")]
+    #[test]
+    fn rule_tags_are_checked(tag: &str, ok: bool) {
+        let rules = vec![Rule {
+            query: RuleMatch::ClassName("discord".into()),
+            action: RuleAction::Workspace(tag.into()),
+        }];
+
+        let report = ConfigCheck::new(&config_with_tags(&["1", "2"]))
+            .rules(&rules)
+            .report();
+
+        assert_eq!(report.is_ok(), ok);
+    }
+}
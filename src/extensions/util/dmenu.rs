@@ -2,9 +2,10 @@
 //! providing quick text based menus.
 //!
 //! See [`DMenuKind`] for dmenu type support options.
-use crate::{Color, Error, Result};
+use crate::{util::spawn_command, Color, Error, Result};
 use std::{
     io::{Read, Write},
+    path::PathBuf,
     process::{Command, Stdio},
 };
 
@@ -19,7 +20,7 @@ pub enum MenuMatch {
     NoMatch,
 }
 
-/// Two different derivatives of dmenu
+/// The different derivatives of dmenu that are supported
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DMenuKind {
     /// Suckless's version of dmenu
@@ -30,6 +31,12 @@ pub enum DMenuKind {
     ///
     /// <https://github.com/Shizcow/dmenu-rs>
     Rust,
+    /// Rofi running in its dmenu compatible mode
+    ///
+    /// <https://github.com/davatorium/rofi>
+    ///
+    /// This is the only kind that is able to display icons alongside menu entries.
+    Rofi,
 }
 
 /// Custom configuration options for [`DMenu`].
@@ -115,6 +122,14 @@ pub struct DMenuConfig {
     ///
     /// Default: None
     pub custom_prompt: Option<String>,
+
+    /// The icon theme to use for menus that display application icons.
+    ///
+    /// Icons are only shown when using [DMenuKind::Rofi] with the `freedesktop` feature
+    /// enabled. If this is not set then the `hicolor` fallback theme is used.
+    ///
+    /// Default: None
+    pub icon_theme: Option<String>,
 }
 
 impl Default for DMenuConfig {
@@ -131,6 +146,7 @@ impl Default for DMenuConfig {
             custom_font: None,
             kind: DMenuKind::Suckless,
             custom_prompt: None,
+            icon_theme: None,
         }
     }
 }
@@ -164,6 +180,7 @@ impl DMenuConfig {
         let prefix = match kind {
             DMenuKind::Suckless => "-",
             DMenuKind::Rust => "--",
+            DMenuKind::Rofi => return self.rofi_flags(screen_index),
         };
 
         let mut flags = vec!["-m".to_owned(), screen_index.to_string()];
@@ -206,6 +223,49 @@ impl DMenuConfig {
 
         flags
     }
+
+    /// Build the equivalent flags for rofi
+    fn rofi_flags(&self, screen_index: usize) -> Vec<String> {
+        let mut flags = vec!["-m".to_owned(), screen_index.to_string()];
+
+        flags.push("-theme-str".to_owned());
+        flags.push(format!(
+            "* {{ background-color: {}; text-color: {}; }} element selected {{ background-color: {}; }}",
+            self.bg_color.as_rgb_hex_string(),
+            self.fg_color.as_rgb_hex_string(),
+            self.selected_color.as_rgb_hex_string(),
+        ));
+
+        if self.n_lines > 0 {
+            flags.push("-l".to_owned());
+            flags.push(self.n_lines.to_string());
+        }
+
+        if self.show_on_bottom {
+            flags.push("-location".to_owned());
+            flags.push("6".to_owned());
+        }
+
+        if self.password_input {
+            flags.push("-password".to_owned());
+        }
+
+        if self.ignore_case {
+            flags.push("-i".to_owned());
+        }
+
+        if let Some(font) = &self.custom_font {
+            flags.push("-font".to_owned());
+            flags.push(font.to_owned());
+        }
+
+        if let Some(prompt) = &self.custom_prompt {
+            flags.push("-p".to_owned());
+            flags.push(prompt.to_owned());
+        }
+
+        flags
+    }
 }
 
 /// A wrapper around the suckless [dmenu](http://tools.suckless.org/dmenu/) program
@@ -230,16 +290,22 @@ impl DMenu {
     }
 
     /// Used for launching regular old [`DMenu`] with no menu matching
-    /// via the `dmenu_run` wrapper script.
+    /// via the `dmenu_run` wrapper script (or `rofi -show run` for [DMenuKind::Rofi]).
     pub fn run(&self) -> Result<()> {
         let args = self.config.flags(self.screen_index);
-        let spawned_process = Command::new("dmenu_run").args(args).spawn();
+        let mut cmd = match self.config.kind {
+            DMenuKind::Rofi => {
+                let mut cmd = Command::new("rofi");
+                cmd.args(["-show", "run"]);
+                cmd
+            }
+            _ => Command::new("dmenu_run"),
+        };
+        cmd.args(args);
+        let mut process = spawn_command(&mut cmd)?;
 
-        match spawned_process {
-            Ok(mut process) => match process.wait() {
-                Ok(_) => Ok(()),
-                Err(e) => Err(e.into()),
-            },
+        match process.wait() {
+            Ok(_) => Ok(()),
             Err(e) => Err(e.into()),
         }
     }
@@ -266,7 +332,33 @@ impl DMenu {
             .into_iter()
             .map(std::convert::Into::into)
             .collect();
-        let raw = self.raw_user_choice_from_dmenu(&choices)?;
+
+        self.match_user_choice(choices, &[])
+    }
+
+    /// Run this [`DMenu`] command and return the selected choice, displaying the given
+    /// icon alongside each choice.
+    ///
+    /// Icons are only displayed when using [DMenuKind::Rofi]: for other kinds this is
+    /// equivalent to calling [DMenu::build_menu].
+    pub fn build_menu_with_icons(
+        &self,
+        param_choices: Vec<(impl Into<String>, Option<PathBuf>)>,
+    ) -> Result<MenuMatch> {
+        let (choices, icons): (Vec<String>, Vec<Option<PathBuf>>) = param_choices
+            .into_iter()
+            .map(|(s, icon)| (s.into(), icon))
+            .unzip();
+
+        self.match_user_choice(choices, &icons)
+    }
+
+    fn match_user_choice(
+        &self,
+        choices: Vec<String>,
+        icons: &[Option<PathBuf>],
+    ) -> Result<MenuMatch> {
+        let raw = self.raw_user_choice_from_dmenu(&choices, icons)?;
         let choice = raw.trim();
 
         if choice.is_empty() {
@@ -297,29 +389,49 @@ impl DMenu {
     }
 
     /// Get a vector of choices as bytes
-    fn choices_as_input_bytes(&self, choices: &[String]) -> Vec<u8> {
-        if self.config.show_line_numbers {
-            choices
-                .iter()
-                .enumerate()
-                .map(|(i, s)| format!("{i:<3} {s}"))
-                .collect::<Vec<String>>()
-                .join("\n")
-                .as_bytes()
-                .to_vec()
-        } else {
-            choices.join("\n").as_bytes().to_vec()
-        }
+    fn choices_as_input_bytes(&self, choices: &[String], icons: &[Option<PathBuf>]) -> Vec<u8> {
+        let show_icons = self.config.kind == DMenuKind::Rofi;
+
+        choices
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let mut line = if self.config.show_line_numbers {
+                    format!("{i:<3} {s}")
+                } else {
+                    s.clone()
+                };
+
+                // Rofi reads per-entry options following a null byte
+                if let (true, Some(Some(icon))) = (show_icons, icons.get(i)) {
+                    line.push_str(&format!("\0icon\x1f{}", icon.display()));
+                }
+
+                line
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+            .as_bytes()
+            .to_vec()
     }
 
     /// Launch a shell process with all arguments to dmenu
-    fn raw_user_choice_from_dmenu(&self, choices: &[String]) -> Result<String> {
+    fn raw_user_choice_from_dmenu(
+        &self,
+        choices: &[String],
+        icons: &[Option<PathBuf>],
+    ) -> Result<String> {
         let args = self.config.flags(self.screen_index);
-        let mut proc = Command::new("dmenu")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .args(args)
-            .spawn()?;
+        let mut cmd = match self.config.kind {
+            DMenuKind::Rofi => {
+                let mut cmd = Command::new("rofi");
+                cmd.args(["-dmenu", "-show-icons"]);
+                cmd
+            }
+            _ => Command::new("dmenu"),
+        };
+        cmd.args(args).stdin(Stdio::piped()).stdout(Stdio::piped());
+        let mut proc = spawn_command(&mut cmd)?;
 
         {
             // Taking stdin here and dropping it when the block scope ends to close it and
@@ -329,7 +441,7 @@ impl DMenu {
                 .take()
                 .ok_or_else(|| Error::Custom("unable to open stdin".to_owned()))?;
 
-            stdin.write_all(&self.choices_as_input_bytes(choices))?;
+            stdin.write_all(&self.choices_as_input_bytes(choices, icons))?;
         }
 
         let mut raw = String::new();
@@ -0,0 +1,635 @@
+//! Support for freedesktop.org [desktop entries][1] and [icon themes][2].
+//!
+//! Desktop entries are the `.desktop` files installed by applications to describe how they
+//! should be shown in application launchers: their name, icon and the command used to run them.
+//! The [AppIndex] loads all of the desktop entries available on the system and provides lookup
+//! of entries and icons by window class for use in launchers and window switchers.
+//!
+//! This module requires the `freedesktop` feature to be enabled.
+//!
+//!   [1]: https://specifications.freedesktop.org/desktop-entry-spec/latest/
+//!   [2]: https://specifications.freedesktop.org/icon-theme-spec/latest/
+use crate::{util::spawn_with_args, Error, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+const ICON_EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+const FALLBACK_THEME: &str = "hicolor";
+
+/// The base directories to search for XDG data files in priority order.
+///
+/// This is `$XDG_DATA_HOME` (defaulting to `~/.local/share`) followed by the entries in
+/// `$XDG_DATA_DIRS` (defaulting to `/usr/local/share:/usr/share`).
+pub fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    match env::var("XDG_DATA_HOME") {
+        Ok(s) if !s.is_empty() => dirs.push(PathBuf::from(s)),
+        _ => dirs.extend(env::var("HOME").map(|h| Path::new(&h).join(".local/share"))),
+    }
+
+    let data_dirs = match env::var("XDG_DATA_DIRS") {
+        Ok(s) if !s.is_empty() => s,
+        _ => "/usr/local/share:/usr/share".to_string(),
+    };
+    dirs.extend(
+        data_dirs
+            .split(':')
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from),
+    );
+
+    dirs
+}
+
+// Parse the key value pairs of the named group from a freedesktop style ini file, ignoring
+// localised keys.
+fn parse_group(s: &str, group: &str) -> HashMap<String, String> {
+    let header = format!("[{group}]");
+    let mut in_group = false;
+    let mut pairs = HashMap::new();
+
+    for line in s.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else if line.starts_with('[') {
+            in_group = line == header;
+        } else if in_group {
+            if let Some((k, v)) = line.split_once('=') {
+                let k = k.trim();
+                if !k.contains('[') {
+                    pairs.insert(k.to_string(), unescape(v.trim()));
+                }
+            }
+        }
+    }
+
+    pairs
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+
+    out
+}
+
+/// A parsed `Type=Application` desktop entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesktopEntry {
+    /// The desktop file ID of this entry, e.g. `org.gnome.Nautilus.desktop`
+    pub id: String,
+    /// The path to the file this entry was loaded from
+    pub path: PathBuf,
+    /// The display name of the application
+    pub name: String,
+    /// The command line used to launch the application, including field codes
+    pub exec: String,
+    /// The icon name or absolute icon path for the application
+    pub icon: Option<String>,
+    /// Whether or not the application needs to be run inside of a terminal
+    pub terminal: bool,
+    /// The WM_CLASS the application is expected to set on its windows
+    pub startup_wm_class: Option<String>,
+    /// Whether or not this entry should be hidden from application launchers
+    pub no_display: bool,
+}
+
+impl DesktopEntry {
+    /// Parse the contents of a desktop file.
+    ///
+    /// Returns `None` if the file is not a valid, non-hidden, application entry.
+    pub fn parse(id: impl Into<String>, path: impl Into<PathBuf>, s: &str) -> Option<Self> {
+        let mut pairs = parse_group(s, "Desktop Entry");
+        let is_true = |v: Option<&String>| v.map(|s| s == "true").unwrap_or(false);
+
+        if pairs.get("Type").map(|s| s.as_str()) != Some("Application")
+            || is_true(pairs.get("Hidden"))
+        {
+            return None;
+        }
+
+        Some(Self {
+            id: id.into(),
+            path: path.into(),
+            terminal: is_true(pairs.get("Terminal")),
+            no_display: is_true(pairs.get("NoDisplay")),
+            name: pairs.remove("Name")?,
+            exec: pairs.remove("Exec")?,
+            icon: pairs.remove("Icon").filter(|s| !s.is_empty()),
+            startup_wm_class: pairs.remove("StartupWMClass"),
+        })
+    }
+
+    /// Load the desktop entry at the given path.
+    pub fn load(id: impl Into<String>, path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path)?;
+
+        Ok(Self::parse(id, path, &s))
+    }
+
+    /// Whether or not this entry is for windows with the given WM_CLASS.
+    ///
+    /// This checks `StartupWMClass` if it is set, otherwise it compares against the name of
+    /// the desktop file (ignoring case).
+    pub fn matches_class(&self, class: &str) -> bool {
+        if let Some(c) = &self.startup_wm_class {
+            return c == class;
+        }
+
+        let stem = self.id.strip_suffix(".desktop").unwrap_or(&self.id);
+
+        stem.eq_ignore_ascii_case(class)
+            || stem
+                .rsplit('.')
+                .next()
+                .map(|s| s.eq_ignore_ascii_case(class))
+                .unwrap_or(false)
+    }
+
+    /// The command line arguments for launching this entry with field codes expanded.
+    ///
+    /// File and URL field codes are removed as penrose does not pass any files to the launched
+    /// application. If this entry needs to run in a terminal then `terminal` is used to run it
+    /// (using the `-e` flag supported by the majority of terminal emulators).
+    pub fn command(&self, terminal: &str) -> Vec<String> {
+        let mut args = Vec::new();
+
+        for arg in split_exec(&self.exec) {
+            match arg.as_str() {
+                "%f" | "%F" | "%u" | "%U" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => (),
+                "%i" => {
+                    if let Some(icon) = &self.icon {
+                        args.extend(["--icon".to_string(), icon.clone()]);
+                    }
+                }
+                _ => args.push(self.expand_field_codes(&arg)),
+            }
+        }
+
+        if self.terminal {
+            args.splice(0..0, [terminal.to_string(), "-e".to_string()]);
+        }
+
+        args
+    }
+
+    fn expand_field_codes(&self, arg: &str) -> String {
+        let mut out = String::with_capacity(arg.len());
+        let mut chars = arg.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('%') => out.push('%'),
+                Some('c') => out.push_str(&self.name),
+                Some('k') => out.push_str(&self.path.to_string_lossy()),
+                _ => (), // unknown or unsupported field codes are dropped
+            }
+        }
+
+        out
+    }
+
+    /// Launch this application using `terminal` to run it if required.
+    pub fn launch(&self, terminal: &str) -> Result<()> {
+        let args = self.command(terminal);
+        let (cmd, args) = args
+            .split_first()
+            .ok_or_else(|| Error::Custom(format!("empty Exec line in {}", self.id)))?;
+        debug!(id = %self.id, ?cmd, ?args, "launching desktop entry");
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+        spawn_with_args(cmd.as_str(), &args)
+    }
+}
+
+// Split an Exec line into arguments following the quoting rules from the desktop entry spec.
+fn split_exec(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut in_quotes = false;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_arg = true;
+            }
+            '\\' if in_quotes => current.extend(chars.next()),
+            c if c.is_whitespace() && !in_quotes => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+/// Load all application desktop entries found in the `applications` directory of each of
+/// the [data_dirs].
+///
+/// When multiple files share the same desktop file ID, the one from the highest priority
+/// directory is used. Entries that fail to parse are skipped.
+pub fn desktop_entries() -> Vec<DesktopEntry> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+
+    for dir in data_dirs() {
+        let root = dir.join("applications");
+        for path in desktop_files(&root) {
+            let id = match path.strip_prefix(&root) {
+                Ok(rel) => rel.to_string_lossy().replace('/', "-"),
+                Err(_) => continue,
+            };
+
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+
+            match DesktopEntry::load(id, &path) {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => (),
+                Err(e) => debug!(?path, %e, "unable to read desktop entry"),
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    entries
+}
+
+fn desktop_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let read_dir = match fs::read_dir(dir) {
+        Ok(rd) => rd,
+        Err(_) => return files,
+    };
+
+    for path in read_dir.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            files.extend(desktop_files(&path));
+        } else if path.extension().map(|e| e == "desktop").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+
+    files
+}
+
+// A sub-directory of an icon theme and the icon sizes it contains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IconDir {
+    path: String,
+    size: u32,
+    min: u32,
+    max: u32,
+}
+
+impl IconDir {
+    fn parse(path: &str, s: &str) -> Option<Self> {
+        let pairs = parse_group(s, path);
+        let num = |k: &str| pairs.get(k).and_then(|v| v.parse::<u32>().ok());
+        let size = num("Size")?;
+        let threshold = num("Threshold").unwrap_or(2);
+
+        let (min, max) = match pairs.get("Type").map(|s| s.as_str()) {
+            Some("Fixed") => (size, size),
+            Some("Scalable") => (
+                num("MinSize").unwrap_or(size),
+                num("MaxSize").unwrap_or(size),
+            ),
+            _ => (size.saturating_sub(threshold), size + threshold),
+        };
+
+        Some(Self {
+            path: path.to_string(),
+            size,
+            min,
+            max,
+        })
+    }
+
+    // How far this directory is from providing icons of the requested size.
+    fn distance(&self, size: u32) -> u32 {
+        self.min.saturating_sub(size) + size.saturating_sub(self.max)
+    }
+}
+
+// The parsed index.theme file for an icon theme.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct IconThemeIndex {
+    dirs: Vec<IconDir>,
+    inherits: Vec<String>,
+}
+
+impl IconThemeIndex {
+    fn parse(s: &str) -> Self {
+        let pairs = parse_group(s, "Icon Theme");
+        let list = |k: &str| -> Vec<String> {
+            pairs
+                .get(k)
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Self {
+            dirs: list("Directories")
+                .iter()
+                .filter_map(|d| IconDir::parse(d, s))
+                .collect(),
+            inherits: list("Inherits"),
+        }
+    }
+
+    // Theme sub-directories ordered by how closely they match the requested size.
+    fn dirs_for_size(&self, size: u32) -> Vec<&str> {
+        let mut dirs: Vec<&IconDir> = self.dirs.iter().collect();
+        dirs.sort_by_key(|d| (d.distance(size), d.size.abs_diff(size)));
+
+        dirs.into_iter().map(|d| d.path.as_str()).collect()
+    }
+}
+
+/// Lookup of icon files by name using an icon theme.
+///
+/// Icons are searched for in the requested theme, then the themes it inherits from and finally
+/// the `hicolor` fallback theme before checking `/usr/share/pixmaps`.
+#[derive(Debug, Clone)]
+pub struct IconLookup {
+    size: u32,
+    base_dirs: Vec<PathBuf>,
+    themes: Vec<IconThemeIndex>,
+    theme_names: Vec<String>,
+}
+
+impl IconLookup {
+    /// Create a new [IconLookup] for the named theme, preferring icons of the given size.
+    pub fn new(theme: &str, size: u32) -> Self {
+        let mut base_dirs: Vec<PathBuf> = env::var("HOME")
+            .map(|h| Path::new(&h).join(".icons"))
+            .into_iter()
+            .collect();
+        base_dirs.extend(data_dirs().into_iter().map(|d| d.join("icons")));
+
+        let mut lookup = Self {
+            size,
+            base_dirs,
+            themes: Vec::new(),
+            theme_names: Vec::new(),
+        };
+        lookup.load_theme(theme);
+        lookup.load_theme(FALLBACK_THEME);
+
+        lookup
+    }
+
+    fn load_theme(&mut self, name: &str) {
+        if self.theme_names.iter().any(|t| t == name) {
+            return;
+        }
+
+        let index = self
+            .base_dirs
+            .iter()
+            .find_map(|d| fs::read_to_string(d.join(name).join("index.theme")).ok())
+            .map(|s| IconThemeIndex::parse(&s));
+
+        if let Some(index) = index {
+            let inherits = index.inherits.clone();
+            self.theme_names.push(name.to_string());
+            self.themes.push(index);
+
+            for parent in inherits {
+                self.load_theme(&parent);
+            }
+        }
+    }
+
+    /// Find the path to the named icon.
+    ///
+    /// Absolute paths are returned as is if they exist.
+    pub fn find(&self, icon: &str) -> Option<PathBuf> {
+        let p = Path::new(icon);
+        if p.is_absolute() {
+            return p.exists().then(|| p.to_path_buf());
+        }
+
+        for (name, index) in self.theme_names.iter().zip(&self.themes) {
+            for dir in index.dirs_for_size(self.size) {
+                for base in self.base_dirs.iter() {
+                    let dir = base.join(name).join(dir);
+                    if let Some(path) = find_with_extension(&dir, icon) {
+                        return Some(path);
+                    }
+                }
+            }
+        }
+
+        find_with_extension(Path::new("/usr/share/pixmaps"), icon)
+    }
+}
+
+fn find_with_extension(dir: &Path, icon: &str) -> Option<PathBuf> {
+    ICON_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{icon}.{ext}")))
+        .find(|p| p.exists())
+}
+
+/// The desktop entries available on the system along with icon lookup for them.
+#[derive(Debug, Clone)]
+pub struct AppIndex {
+    entries: Vec<DesktopEntry>,
+    icons: IconLookup,
+}
+
+impl AppIndex {
+    /// Load all available [DesktopEntry]s, using the named icon theme to locate their icons.
+    pub fn load(icon_theme: &str, icon_size: u32) -> Self {
+        Self {
+            entries: desktop_entries(),
+            icons: IconLookup::new(icon_theme, icon_size),
+        }
+    }
+
+    /// The entries that should be shown in an application launcher, sorted by name.
+    pub fn launchable(&self) -> impl Iterator<Item = &DesktopEntry> {
+        self.entries.iter().filter(|e| !e.no_display)
+    }
+
+    /// The entry for windows with the given WM_CLASS if there is one.
+    pub fn entry_for_class(&self, class: &str) -> Option<&DesktopEntry> {
+        self.entries.iter().find(|e| e.matches_class(class))
+    }
+
+    /// The path to the icon of the given entry if it can be found.
+    pub fn icon_for_entry(&self, entry: &DesktopEntry) -> Option<PathBuf> {
+        entry.icon.as_deref().and_then(|i| self.icons.find(i))
+    }
+
+    /// The path to the icon for windows with the given WM_CLASS if it can be found.
+    pub fn icon_for_class(&self, class: &str) -> Option<PathBuf> {
+        self.entry_for_class(class)
+            .and_then(|e| self.icon_for_entry(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    const FIREFOX: &str = "\
+[Desktop Entry]
+Type=Application
+Name=Firefox
+Name[de]=Firefox Webbrowser
+Exec=firefox --name \"%c\" %u
+Icon=firefox
+StartupWMClass=firefox
+
+[Desktop Action new-window]
+Name=New Window
+Exec=firefox --new-window %u
+";
+
+    fn entry(exec: &str, terminal: bool) -> DesktopEntry {
+        DesktopEntry {
+            id: "org.example.App.desktop".to_string(),
+            path: PathBuf::from("/usr/share/applications/org.example.App.desktop"),
+            name: "Example App".to_string(),
+            exec: exec.to_string(),
+            icon: Some("example".to_string()),
+            terminal,
+            startup_wm_class: None,
+            no_display: false,
+        }
+    }
+
+    #[test]
+    fn parse_only_reads_the_desktop_entry_group() {
+        let e = DesktopEntry::parse("firefox.desktop", "/tmp/firefox.desktop", FIREFOX).unwrap();
+
+        assert_eq!(e.name, "Firefox");
+        assert_eq!(e.exec, "firefox --name \"%c\" %u");
+        assert_eq!(e.icon.as_deref(), Some("firefox"));
+        assert_eq!(e.startup_wm_class.as_deref(), Some("firefox"));
+        assert!(!e.terminal);
+    }
+
+    #[test_case("Type=Link\nName=x\nExec=x"; "not an application")]
+    #[test_case("Type=Application\nName=x\nExec=x\nHidden=true"; "hidden")]
+    #[test_case("Type=Application\nName=x"; "missing exec")]
+    #[test]
+    fn parse_rejects_invalid_entries(body: &str) {
+        let s = format!("[Desktop Entry]\n{body}");
+
+        assert_eq!(DesktopEntry::parse("x.desktop", "/tmp/x.desktop", &s), None);
+    }
+
+    #[test_case("app %U", false, &["app"]; "file codes removed")]
+    #[test_case("app --title=%c", false, &["app", "--title=Example App"]; "name expanded")]
+    #[test_case("app %i", false, &["app", "--icon", "example"]; "icon expanded")]
+    #[test_case("app \"two words\" 100%%", false, &["app", "two words", "100%"]; "quoting")]
+    #[test_case("sh -c \"echo \\\"hi\\\"\"", false, &["sh", "-c", "echo \"hi\""]; "escaped quotes")]
+    #[test_case("htop", true, &["st", "-e", "htop"]; "terminal")]
+    #[test]
+    fn command(exec: &str, terminal: bool, expected: &[&str]) {
+        assert_eq!(entry(exec, terminal).command("st"), expected);
+    }
+
+    #[test_case(Some("Example"), "Example", true; "startup wm class")]
+    #[test_case(Some("Example"), "example", false; "startup wm class is case sensitive")]
+    #[test_case(None, "org.example.app", true; "file name")]
+    #[test_case(None, "App", true; "last component of file name")]
+    #[test_case(None, "other", false; "no match")]
+    #[test]
+    fn matches_class(startup_wm_class: Option<&str>, class: &str, expected: bool) {
+        let mut e = entry("app", false);
+        e.startup_wm_class = startup_wm_class.map(String::from);
+
+        assert_eq!(e.matches_class(class), expected);
+    }
+
+    #[test]
+    fn icon_theme_dirs_are_ordered_by_size() {
+        let index = IconThemeIndex::parse(
+            "\
+[Icon Theme]
+Name=Test
+Inherits=Adwaita, hicolor
+Directories=16x16/apps,48x48/apps,scalable/apps
+
+[16x16/apps]
+Size=16
+Type=Fixed
+
+[48x48/apps]
+Size=48
+Type=Fixed
+
+[scalable/apps]
+Size=64
+MinSize=32
+MaxSize=256
+Type=Scalable
+",
+        );
+
+        assert_eq!(index.inherits, vec!["Adwaita", "hicolor"]);
+        assert_eq!(
+            index.dirs_for_size(48),
+            vec!["48x48/apps", "scalable/apps", "16x16/apps"]
+        );
+        assert_eq!(
+            index.dirs_for_size(128),
+            vec!["scalable/apps", "48x48/apps", "16x16/apps"]
+        );
+    }
+}
@@ -5,8 +5,13 @@ use crate::{
     Error, Result,
 };
 
+pub mod check;
 pub mod debug;
 pub mod dmenu;
+#[cfg(feature = "freedesktop")]
+pub mod freedesktop;
+pub mod power;
+pub mod title;
 
 /// Detect the current monitor set up and arrange the monitors if needed using [xrandr][1].
 ///
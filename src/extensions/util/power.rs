@@ -0,0 +1,101 @@
+//! Reading the charge level and status of system batteries.
+//!
+//! Batteries are identified by their name under `/sys/class/power_supply` (for example
+//! `BAT0`). This is the data source used by the [power profile hooks][0] and the battery
+//! widget provided by `penrose_ui`.
+//!
+//!   [0]: crate::extensions::hooks::power_profiles
+use std::fs;
+
+/// Where the system is currently drawing its power from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PowerSource {
+    /// Running from mains power
+    Ac,
+    /// Running from battery
+    Battery,
+}
+
+/// A snapshot of the state of a battery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatteryStatus {
+    /// The status reported by the kernel (`Charging`, `Discharging`, `Full` etc)
+    pub status: String,
+    /// The current charge level as a percentage
+    pub charge: u32,
+}
+
+impl BatteryStatus {
+    /// Read the current status of the named battery, returning `None` if the battery is not
+    /// found on this system.
+    pub fn read(bat: &str) -> Option<Self> {
+        let status = read_sys_file(bat, "status")?;
+        let energy_now = read_sys_file(bat, "energy_now")?.parse().ok()?;
+        let energy_full = read_sys_file(bat, "energy_full")?.parse().ok()?;
+
+        Self::from_parts(status, energy_now, energy_full)
+    }
+
+    fn from_parts(status: String, energy_now: u64, energy_full: u64) -> Option<Self> {
+        if energy_full == 0 {
+            return None;
+        }
+
+        let charge = (energy_now * 100 / energy_full).min(100) as u32;
+
+        Some(Self { status, charge })
+    }
+
+    /// Whether or not the battery is currently charging.
+    pub fn is_charging(&self) -> bool {
+        self.status == "Charging"
+    }
+
+    /// Where the system is drawing its power from based on the status of this battery.
+    ///
+    /// Only a discharging battery is taken to mean that the system is running on battery: a
+    /// battery that is full or not charging is assumed to be connected to mains power.
+    pub fn power_source(&self) -> PowerSource {
+        if self.status == "Discharging" {
+            PowerSource::Battery
+        } else {
+            PowerSource::Ac
+        }
+    }
+}
+
+fn read_sys_file(bat: &str, fname: &str) -> Option<String> {
+    fs::read_to_string(format!("/sys/class/power_supply/{bat}/{fname}"))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case("Discharging", 50, 100, Some(50); "half charged")]
+    #[test_case("Full", 101, 100, Some(100); "overfull")]
+    #[test_case("Unknown", 10, 0, None; "zero capacity")]
+    #[test]
+    fn from_parts(status: &str, now: u64, full: u64, expected: Option<u32>) {
+        let charge = BatteryStatus::from_parts(status.to_string(), now, full).map(|b| b.charge);
+
+        assert_eq!(charge, expected);
+    }
+
+    #[test_case("Discharging", PowerSource::Battery; "discharging")]
+    #[test_case("Charging", PowerSource::Ac; "charging")]
+    #[test_case("Full", PowerSource::Ac; "full")]
+    #[test_case("Not charging", PowerSource::Ac; "not charging")]
+    #[test]
+    fn power_source(status: &str, expected: PowerSource) {
+        let b = BatteryStatus {
+            status: status.to_string(),
+            charge: 80,
+        };
+
+        assert_eq!(b.power_source(), expected);
+    }
+}
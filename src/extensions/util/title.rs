@@ -0,0 +1,388 @@
+//! Formatting client window titles for display.
+//!
+//! A [TitleFormatter] is a pipeline of rewrites that are applied to the title of a client
+//! (along with its window class) to produce the string shown to the user. Once registered
+//! using [add_title_formatter], the formatter is used by [client_title] which is in turn used
+//! by the [dmenu_focus_client][0], [dmenu_fetch_client][2] and [dmenu_restore_minimized][1]
+//! window switchers and the `ActiveWindowName` widget provided by penrose_ui. If no formatter has been registered then
+//! [client_title] returns the raw title of the client.
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! use penrose::extensions::util::title::{add_title_formatter, TitleFormatter};
+//!
+//! let formatter = TitleFormatter::new()
+//!     .strip_suffix("firefox", " - Mozilla Firefox")
+//!     .with(|_: &str, title: String| title.replace("\u{2014}", "-"))
+//!     .max_length(50);
+//!
+//! add_title_formatter(wm, formatter)
+//! # }
+//! ```
+//!
+//! # Terminal titles
+//!
+//! Most terminal emulators set their window title from escape sequences sent by the shell,
+//! which typically results in long titles such as `user@host: ~/src/penrose` or
+//! `nvim /home/user/src/penrose/src/lib.rs`. The [TerminalTitle] stage can be used to
+//! shorten these to the last segment of the working directory or the running command with
+//! the paths in its arguments shortened, e.g. `penrose` and `nvim lib.rs`:
+//!
+//! ```no_run
+//! # use penrose::{core::WindowManager, x11rb::RustConn};
+//! # fn example(wm: WindowManager<RustConn>) -> WindowManager<RustConn> {
+//! use penrose::extensions::util::title::{add_title_formatter, TerminalTitle, TitleFormatter};
+//!
+//! let formatter = TitleFormatter::new()
+//!     .with(TerminalTitle::new(["Alacritty", "kitty"]))
+//!     .max_length(30);
+//!
+//! add_title_formatter(wm, formatter)
+//! # }
+//! ```
+//!
+//!   [0]: crate::extensions::actions::dmenu_focus_client
+//!   [1]: crate::extensions::actions::dmenu_restore_minimized
+//!   [2]: crate::extensions::actions::dmenu_fetch_client
+use crate::{
+    core::{State, WindowManager},
+    x::{Atom, Prop, XConn, XConnExt},
+    Xid,
+};
+use std::fmt;
+
+/// A single stage in a [TitleFormatter] pipeline.
+///
+/// Stages are given the window class of the client (or an empty string if it is not set)
+/// along with the title as formatted by the previous stages.
+pub trait TitleFormat {
+    /// Format the given title.
+    fn format(&self, class: &str, title: String) -> String;
+}
+
+impl<F> TitleFormat for F
+where
+    F: Fn(&str, String) -> String,
+{
+    fn format(&self, class: &str, title: String) -> String {
+        (self)(class, title)
+    }
+}
+
+/// A pipeline of [TitleFormat] stages for converting client titles into display strings.
+#[derive(Default)]
+pub struct TitleFormatter {
+    stages: Vec<Box<dyn TitleFormat>>,
+    max_length: Option<usize>,
+}
+
+impl fmt::Debug for TitleFormatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TitleFormatter")
+            .field("stages", &self.stages.len())
+            .field("max_length", &self.max_length)
+            .finish()
+    }
+}
+
+impl TitleFormatter {
+    /// Create a new [TitleFormatter] that leaves titles unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a custom stage to the end of this pipeline.
+    pub fn with<F>(mut self, stage: F) -> Self
+    where
+        F: TitleFormat + 'static,
+    {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Remove the given suffix from the titles of clients with the given window class.
+    pub fn strip_suffix(self, class: impl Into<String>, suffix: impl Into<String>) -> Self {
+        let (class, suffix) = (class.into(), suffix.into());
+
+        self.with(
+            move |c: &str, title: String| match title.strip_suffix(&suffix) {
+                Some(s) if c == class => s.to_string(),
+                _ => title,
+            },
+        )
+    }
+
+    /// Remove the given prefix from the titles of clients with the given window class.
+    pub fn strip_prefix(self, class: impl Into<String>, prefix: impl Into<String>) -> Self {
+        let (class, prefix) = (class.into(), prefix.into());
+
+        self.with(
+            move |c: &str, title: String| match title.strip_prefix(&prefix) {
+                Some(s) if c == class => s.to_string(),
+                _ => title,
+            },
+        )
+    }
+
+    /// Replace the title of all clients with the given window class.
+    pub fn rename_class(self, class: impl Into<String>, title: impl Into<String>) -> Self {
+        let (class, new_title) = (class.into(), title.into());
+
+        self.with(
+            move |c: &str, title: String| {
+                if c == class {
+                    new_title.clone()
+                } else {
+                    title
+                }
+            },
+        )
+    }
+
+    /// Shorten the titles of clients with one of the window classes in [TERMINAL_CLASSES] using
+    /// a default [TerminalTitle] stage.
+    pub fn shorten_terminal_titles(self) -> Self {
+        self.with(TerminalTitle::default())
+    }
+
+    /// Truncate formatted titles longer than `max_length` characters, ending them with "..."
+    /// to show that they have been shortened.
+    ///
+    /// This is applied after all other stages, regardless of the order in which it is set.
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length.max(3));
+        self
+    }
+
+    /// Run this pipeline for the given class and title.
+    pub fn format(&self, class: &str, title: &str) -> String {
+        let title = self
+            .stages
+            .iter()
+            .fold(title.to_string(), |t, stage| stage.format(class, t));
+
+        match self.max_length {
+            Some(n) if title.chars().count() > n => {
+                let s: String = title.chars().take(n - 3).collect();
+                format!("{s}...")
+            }
+            _ => title,
+        }
+    }
+}
+
+/// The window classes of common terminal emulators used by [TerminalTitle::default].
+pub const TERMINAL_CLASSES: &[&str] = &[
+    "Alacritty",
+    "foot",
+    "Gnome-terminal",
+    "kitty",
+    "st-256color",
+    "URxvt",
+    "org.wezfurlong.wezterm",
+    "XTerm",
+];
+
+/// A [TitleFormat] stage for shortening the titles set by shells running in terminal emulators.
+///
+/// Titles of clients with one of the configured window classes are rewritten as follows:
+///   - A leading `user@host:` prompt prefix is removed.
+///   - Titles that are a path (such as the current working directory) are replaced by the
+///     last segment of that path.
+///   - Otherwise the title is treated as a running command: the program is replaced by its
+///     name and any arguments that look like paths are replaced by their last segment. The
+///     arguments can be dropped entirely using [TerminalTitle::without_args].
+///
+/// Truncating the resulting titles is left to [TitleFormatter::max_length].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalTitle {
+    classes: Vec<String>,
+    show_args: bool,
+}
+
+impl Default for TerminalTitle {
+    fn default() -> Self {
+        Self::new(TERMINAL_CLASSES.iter().copied())
+    }
+}
+
+impl TerminalTitle {
+    /// Create a new [TerminalTitle] stage for clients with the given window classes.
+    pub fn new<S>(classes: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            classes: classes.into_iter().map(Into::into).collect(),
+            show_args: true,
+        }
+    }
+
+    /// Only show the name of running commands, dropping their arguments.
+    pub fn without_args(mut self) -> Self {
+        self.show_args = false;
+        self
+    }
+
+    /// Shorten the given terminal title, regardless of the window class it belongs to.
+    pub fn shorten(&self, title: &str) -> String {
+        let title = title.trim();
+        let title = match title.split_once(':') {
+            Some((prompt, rest)) if is_prompt(prompt) => rest.trim(),
+            _ => title,
+        };
+
+        if title.is_empty() || is_path(title) {
+            return last_segment(title).to_string();
+        }
+
+        let mut words = title.split_whitespace();
+        let cmd = words.next().map(last_segment).unwrap_or_default();
+        if !self.show_args {
+            return cmd.to_string();
+        }
+
+        let mut shortened = vec![cmd];
+        shortened.extend(words.map(|w| {
+            if !w.starts_with('-') && w.contains('/') {
+                last_segment(w)
+            } else {
+                w
+            }
+        }));
+
+        shortened.join(" ")
+    }
+}
+
+impl TitleFormat for TerminalTitle {
+    fn format(&self, class: &str, title: String) -> String {
+        if self.classes.iter().any(|c| c == class) {
+            self.shorten(&title)
+        } else {
+            title
+        }
+    }
+}
+
+// A "user@host" style prompt prefix
+fn is_prompt(s: &str) -> bool {
+    s.contains('@') && !s.contains(char::is_whitespace)
+}
+
+fn is_path(s: &str) -> bool {
+    s.starts_with('/') || s == "~" || s.starts_with("~/")
+}
+
+fn last_segment(path: &str) -> &str {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() && !path.is_empty() {
+        return "/";
+    }
+
+    trimmed.rsplit('/').next().unwrap_or(trimmed)
+}
+
+/// Register a [TitleFormatter] as a state extension for use by [client_title].
+pub fn add_title_formatter<X>(
+    mut wm: WindowManager<X>,
+    formatter: TitleFormatter,
+) -> WindowManager<X>
+where
+    X: XConn,
+{
+    wm.state.add_extension(formatter);
+
+    wm
+}
+
+/// The display title for the given client, formatted using the registered [TitleFormatter]
+/// if there is one.
+///
+/// If the title of the client can not be fetched its ID is used in its place.
+pub fn client_title<X: XConn>(id: Xid, state: &State<X>, x: &X) -> String {
+    let title = x.window_title(id).unwrap_or_else(|_| (*id).to_string());
+    let formatter = match state.extension::<TitleFormatter>() {
+        Ok(formatter) => formatter,
+        Err(_) => return title,
+    };
+
+    let class = match x.get_prop(id, Atom::WmClass.as_ref()) {
+        Ok(Some(Prop::UTF8String(strs))) if strs.len() > 1 => strs[1].clone(),
+        _ => String::new(),
+    };
+
+    let formatter = formatter.borrow();
+    formatter.format(&class, &title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    fn formatter() -> TitleFormatter {
+        TitleFormatter::new()
+            .strip_suffix("firefox", " - Mozilla Firefox")
+            .strip_prefix("Alacritty", "~/")
+            .rename_class("Spotify", "Music")
+            .with(|_: &str, title: String| title.to_uppercase())
+            .max_length(12)
+    }
+
+    #[test_case("firefox", "Penrose - Mozilla Firefox", "PENROSE"; "suffix stripped")]
+    #[test_case("other", "Penrose - Mozilla Firefox", "PENROSE -..."; "suffix for other class")]
+    #[test_case("Alacritty", "~/src", "SRC"; "prefix stripped")]
+    #[test_case("Spotify", "Some Song", "MUSIC"; "class renamed")]
+    #[test_case("", "exactly 12 c", "EXACTLY 12 C"; "at max length")]
+    #[test]
+    fn format(class: &str, title: &str, expected: &str) {
+        assert_eq!(formatter().format(class, title), expected);
+    }
+
+    #[test_case("~/src/penrose", "penrose"; "home relative cwd")]
+    #[test_case("/etc/", "etc"; "absolute cwd with trailing slash")]
+    #[test_case("~", "~"; "home")]
+    #[test_case("/", "/"; "root")]
+    #[test_case("user@host: ~/src/penrose", "penrose"; "prompt prefix")]
+    #[test_case("user@host:/tmp", "tmp"; "prompt prefix without space")]
+    #[test_case("nvim /home/user/src/lib.rs", "nvim lib.rs"; "command with path arg")]
+    #[test_case("/usr/bin/htop", "htop"; "command by path")]
+    #[test_case("git log --format=a/b", "git log --format=a/b"; "flags left alone")]
+    #[test_case("  top  ", "top"; "whitespace trimmed")]
+    #[test_case("", ""; "empty")]
+    #[test]
+    fn terminal_title_shorten(title: &str, expected: &str) {
+        assert_eq!(TerminalTitle::default().shorten(title), expected);
+    }
+
+    #[test_case("Alacritty", "vim ~/notes/todo.md", "vim"; "terminal class")]
+    #[test_case("firefox", "vim ~/notes/todo.md", "vim ~/notes/todo.md"; "other class")]
+    #[test]
+    fn terminal_title_without_args(class: &str, title: &str, expected: &str) {
+        let formatter =
+            TitleFormatter::new().with(TerminalTitle::new(["Alacritty"]).without_args());
+
+        assert_eq!(formatter.format(class, title), expected);
+    }
+
+    #[test]
+    fn terminal_titles_are_truncated_after_shortening() {
+        let formatter = TitleFormatter::new()
+            .shorten_terminal_titles()
+            .max_length(10);
+
+        assert_eq!(
+            formatter.format("kitty", "user@host: ~/src/a-long-project-name"),
+            "a-long-..."
+        );
+    }
+
+    #[test]
+    fn empty_formatter_leaves_titles_unchanged() {
+        let s = "a title that is quite long";
+
+        assert_eq!(TitleFormatter::new().format("class", s), s);
+    }
+}
@@ -0,0 +1,404 @@
+//! A C compatible API for embedding the pure tiling engine in other programs.
+//!
+//! This exposes a minimal subset of the [pure][0] data structures and layout algorithms so that
+//! other window managers and toolkits can make use of penrose's tiling logic without needing
+//! to be written in Rust. A matching C header can be found in `include/penrose.h` in the
+//! penrose repository. Clients are identified by a non-zero `u32` ID of the caller's
+//! choosing and each workspace uses the default [LayoutStack].
+//!
+//! An engine is created using [penrose_engine_new] and must be freed using
+//! [penrose_engine_free] once it is no longer needed. Functions returning an `int32_t`
+//! return `0` on success and `-1` if the engine pointer is null or the requested client or
+//! workspace is unknown. Panics are caught at the FFI boundary and reported in the same way
+//! as other failures rather than unwinding into the calling code.
+//!
+//! Only the Rust library is built by default. A static or shared library for linking against
+//! from C can be built using `cargo rustc`:
+//!
+//! ```sh
+//! cargo rustc --lib --release --features ffi --crate-type staticlib
+//! cargo rustc --lib --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! ```c
+//! PenroseRect screen = { 0, 0, 1920, 1080 };
+//! PenroseEngine *engine = penrose_engine_new(9, &screen, 1);
+//!
+//! penrose_engine_add_client(engine, 1);
+//! penrose_engine_add_client(engine, 2);
+//!
+//! PenrosePosition positions[16];
+//! size_t n = penrose_engine_layout(engine, screen, positions, 16);
+//!
+//! penrose_engine_free(engine);
+//! ```
+//!
+//!   [0]: crate::pure
+use crate::{
+    core::layout::{Layout, LayoutStack},
+    pure::{geometry::Rect, StackSet},
+    Xid,
+};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// A rectangular region on the screen.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PenroseRect {
+    /// The x-coordinate of the top left corner
+    pub x: u32,
+    /// The y-coordinate of the top left corner
+    pub y: u32,
+    /// The width of the region
+    pub w: u32,
+    /// The height of the region
+    pub h: u32,
+}
+
+impl From<PenroseRect> for Rect {
+    fn from(r: PenroseRect) -> Self {
+        Rect::new(r.x, r.y, r.w, r.h)
+    }
+}
+
+impl From<Rect> for PenroseRect {
+    fn from(r: Rect) -> Self {
+        Self {
+            x: r.x,
+            y: r.y,
+            w: r.w,
+            h: r.h,
+        }
+    }
+}
+
+/// The position assigned to a client by a layout.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PenrosePosition {
+    /// The ID of the client
+    pub id: u32,
+    /// The region of the screen assigned to the client
+    pub rect: PenroseRect,
+}
+
+/// An opaque handle on the state of the tiling engine.
+#[derive(Debug)]
+pub struct PenroseEngine {
+    cs: StackSet<Xid>,
+}
+
+/// Create a new engine with `n_workspaces` workspaces (tagged "1", "2", ...) and the given
+/// screens.
+///
+/// Returns a null pointer if `screens` is null, `n_screens` is zero or there are fewer
+/// workspaces than screens.
+///
+/// # Safety
+/// `screens` must point to `n_screens` valid [PenroseRect]s.
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_new(
+    n_workspaces: usize,
+    screens: *const PenroseRect,
+    n_screens: usize,
+) -> *mut PenroseEngine {
+    if screens.is_null() || n_screens == 0 {
+        return ptr::null_mut();
+    }
+
+    // SAFETY: the caller guarantees that screens points to n_screens valid rects
+    let screens = unsafe { slice::from_raw_parts(screens, n_screens) };
+    let rects = screens.iter().map(|&r| Rect::from(r));
+    let tags = (1..=n_workspaces).map(|n| n.to_string());
+
+    catch_panic(ptr::null_mut(), || {
+        match StackSet::try_new(LayoutStack::default(), tags, rects) {
+            Ok(cs) => Box::into_raw(Box::new(PenroseEngine { cs })),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Free an engine created by [penrose_engine_new]. Passing a null pointer is a no-op.
+///
+/// # Safety
+/// `engine` must be null or a pointer returned by [penrose_engine_new] that has not already
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_free(engine: *mut PenroseEngine) {
+    if !engine.is_null() {
+        // SAFETY: the caller guarantees that engine was allocated by penrose_engine_new
+        let engine = unsafe { Box::from_raw(engine) };
+        catch_panic((), || drop(engine));
+    }
+}
+
+// Run `f`, returning `default` rather than unwinding across the FFI boundary if it panics.
+fn catch_panic<T, F>(default: T, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(default)
+}
+
+// Run `f` against the engine behind the given pointer, returning `default` if it is null or
+// if `f` panics.
+//
+// SAFETY: callers must ensure that engine is null or valid for the duration of the call.
+unsafe fn with_engine<T, F>(engine: *mut PenroseEngine, default: T, f: F) -> T
+where
+    F: FnOnce(&mut PenroseEngine) -> T,
+{
+    // SAFETY: upheld by the caller
+    match unsafe { engine.as_mut() } {
+        Some(e) => catch_panic(default, || f(e)),
+        None => default,
+    }
+}
+
+/// Add a new client to the current workspace, giving it focus.
+///
+/// Returns `-1` if the ID is zero or the client is already known.
+///
+/// # Safety
+/// `engine` must be null or a valid pointer returned by [penrose_engine_new].
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_add_client(engine: *mut PenroseEngine, id: u32) -> i32 {
+    // SAFETY: upheld by the caller
+    unsafe {
+        with_engine(engine, -1, |e| {
+            if id == 0 || e.cs.contains(&Xid(id)) {
+                return -1;
+            }
+
+            e.cs.insert(Xid(id));
+            0
+        })
+    }
+}
+
+/// Remove a client from the engine.
+///
+/// # Safety
+/// `engine` must be null or a valid pointer returned by [penrose_engine_new].
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_remove_client(engine: *mut PenroseEngine, id: u32) -> i32 {
+    // SAFETY: upheld by the caller
+    unsafe {
+        with_engine(engine, -1, |e| match e.cs.remove_client(&Xid(id)) {
+            Some(_) => 0,
+            None => -1,
+        })
+    }
+}
+
+/// Focus the given client, focusing its workspace if it is not already focused.
+///
+/// # Safety
+/// `engine` must be null or a valid pointer returned by [penrose_engine_new].
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_focus_client(engine: *mut PenroseEngine, id: u32) -> i32 {
+    // SAFETY: upheld by the caller
+    unsafe {
+        with_engine(engine, -1, |e| {
+            if !e.cs.contains(&Xid(id)) {
+                return -1;
+            }
+
+            e.cs.focus_client(&Xid(id));
+            0
+        })
+    }
+}
+
+/// Focus the workspace with the given index (starting from zero).
+///
+/// # Safety
+/// `engine` must be null or a valid pointer returned by [penrose_engine_new].
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_focus_workspace(
+    engine: *mut PenroseEngine,
+    index: usize,
+) -> i32 {
+    // SAFETY: upheld by the caller
+    unsafe {
+        with_engine(engine, -1, |e| {
+            let tag = (index + 1).to_string();
+            if !e.cs.contains_tag(&tag) {
+                return -1;
+            }
+
+            e.cs.focus_tag(&tag);
+            0
+        })
+    }
+}
+
+/// The ID of the focused client, or `0` if there is no focused client.
+///
+/// # Safety
+/// `engine` must be null or a valid pointer returned by [penrose_engine_new].
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_focused_client(engine: *mut PenroseEngine) -> u32 {
+    // SAFETY: upheld by the caller
+    unsafe { with_engine(engine, 0, |e| e.cs.current_client().map_or(0, |id| id.0)) }
+}
+
+/// Switch the current workspace to its next available layout.
+///
+/// # Safety
+/// `engine` must be null or a valid pointer returned by [penrose_engine_new].
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_next_layout(engine: *mut PenroseEngine) -> i32 {
+    // SAFETY: upheld by the caller
+    unsafe {
+        with_engine(engine, -1, |e| {
+            e.cs.current_workspace_mut().next_layout();
+            0
+        })
+    }
+}
+
+/// Run the layout of the current workspace for the given region of the screen.
+///
+/// Up to `capacity` positions are written to `out` and the total number of positions is
+/// returned: if this is larger than `capacity` then the call should be repeated with a
+/// larger buffer. `out` may be null if `capacity` is zero.
+///
+/// # Safety
+/// `engine` must be null or a valid pointer returned by [penrose_engine_new] and `out` must
+/// be valid for writing `capacity` [PenrosePosition]s.
+#[no_mangle]
+pub unsafe extern "C" fn penrose_engine_layout(
+    engine: *mut PenroseEngine,
+    r: PenroseRect,
+    out: *mut PenrosePosition,
+    capacity: usize,
+) -> usize {
+    // SAFETY: upheld by the caller
+    let positions = unsafe { with_engine(engine, Vec::new(), |e| e.layout(r.into())) };
+
+    if !out.is_null() {
+        for (i, p) in positions.iter().take(capacity).enumerate() {
+            // SAFETY: the caller guarantees that out is valid for capacity writes
+            unsafe { out.add(i).write(*p) };
+        }
+    }
+
+    positions.len()
+}
+
+impl PenroseEngine {
+    fn layout(&mut self, r: Rect) -> Vec<PenrosePosition> {
+        let w = self.cs.current_workspace_mut();
+        let (_, positions) = w.layouts.layout_workspace(&w.tag, &w.stack, r);
+
+        positions
+            .into_iter()
+            .map(|(id, r)| PenrosePosition {
+                id: *id,
+                rect: r.into(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: PenroseRect = PenroseRect {
+        x: 0,
+        y: 0,
+        w: 1000,
+        h: 800,
+    };
+
+    fn engine_with_clients(ids: &[u32]) -> *mut PenroseEngine {
+        // SAFETY: SCREEN is a valid rect
+        let engine = unsafe { penrose_engine_new(3, &SCREEN, 1) };
+        assert!(!engine.is_null());
+        for &id in ids {
+            // SAFETY: engine is valid
+            assert_eq!(unsafe { penrose_engine_add_client(engine, id) }, 0);
+        }
+
+        engine
+    }
+
+    #[test]
+    fn invalid_arguments_return_null() {
+        // SAFETY: null pointers are checked
+        unsafe {
+            assert!(penrose_engine_new(3, ptr::null(), 1).is_null());
+            assert!(penrose_engine_new(0, &SCREEN, 1).is_null(), "no workspaces");
+            assert_eq!(penrose_engine_add_client(ptr::null_mut(), 1), -1);
+            assert_eq!(penrose_engine_focused_client(ptr::null_mut()), 0);
+            penrose_engine_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn clients_can_be_added_focused_and_removed() {
+        let engine = engine_with_clients(&[1, 2, 3]);
+
+        // SAFETY: engine is valid until it is freed
+        unsafe {
+            assert_eq!(penrose_engine_focused_client(engine), 3);
+            assert_eq!(penrose_engine_add_client(engine, 2), -1, "duplicate");
+            assert_eq!(penrose_engine_add_client(engine, 0), -1, "zero id");
+
+            assert_eq!(penrose_engine_focus_client(engine, 1), 0);
+            assert_eq!(penrose_engine_focused_client(engine), 1);
+            assert_eq!(penrose_engine_focus_client(engine, 42), -1);
+
+            assert_eq!(penrose_engine_remove_client(engine, 1), 0);
+            assert_eq!(penrose_engine_remove_client(engine, 1), -1);
+
+            assert_eq!(penrose_engine_focus_workspace(engine, 1), 0);
+            assert_eq!(penrose_engine_focused_client(engine), 0);
+            assert_eq!(penrose_engine_focus_workspace(engine, 3), -1);
+
+            penrose_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn layout_writes_up_to_capacity() {
+        let engine = engine_with_clients(&[1, 2, 3]);
+        let mut out = [PenrosePosition::default(); 2];
+
+        // SAFETY: engine is valid until it is freed and out has space for 2 positions
+        let (n, expected) = unsafe {
+            let n = penrose_engine_layout(engine, SCREEN, out.as_mut_ptr(), out.len());
+            let expected = (*engine).layout(SCREEN.into());
+            penrose_engine_free(engine);
+
+            (n, expected)
+        };
+
+        assert_eq!(n, 3);
+        assert_eq!(&out[..], &expected[..2]);
+        assert!(out
+            .iter()
+            .all(|p| p.id != 0 && p.rect.w > 0 && p.rect.h > 0));
+    }
+
+    #[test]
+    fn panics_do_not_unwind_across_the_ffi_boundary() {
+        let engine = engine_with_clients(&[1]);
+
+        // SAFETY: engine is valid until it is freed
+        let res = unsafe {
+            let res = with_engine(engine, -1, |_| panic!("boom"));
+            penrose_engine_free(engine);
+
+            res
+        };
+
+        assert_eq!(res, -1);
+    }
+}
@@ -39,6 +39,16 @@
 //! traits. To add functionality and flexability to your window manager, there are the [builtin][10]
 //! and [extensions][11] modules which offer capabilities built on top of the rest of penrose.
 //!
+//!
+//! ## Using the pure data structures on their own
+//!
+//! Everything that talks to the X server or the host operating system lives behind the `wm`
+//! feature (which is enabled by the default `x11rb` feature). Depending on penrose with
+//! `default-features = false` leaves just the [pure][7] data structures and the layout
+//! algorithms, which can be built for targets such as `wasm32-unknown-unknown` in order to
+//! prototype layouts or visualise window manager state in other tools. Note that this still
+//! requires `std`: penrose is not a `no_std` crate.
+//!
 //! [1]: https://dwm.suckless.org/
 //! [2]: https://xmonad.org/
 //! [3]: http://www.qtile.org/
@@ -78,9 +88,14 @@ use std::any::TypeId;
 pub mod builtin;
 pub mod core;
 pub mod extensions;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod macros;
 pub mod pure;
+#[cfg(all(feature = "wm", any(test, feature = "testing")))]
+pub mod testing;
 pub mod util;
+#[cfg(feature = "wm")]
 pub mod x;
 #[cfg(feature = "x11rb")]
 pub mod x11rb;
@@ -125,6 +140,36 @@ pub enum Error {
         reason: String,
     },
 
+    /// A command was rejected by the configured [CommandWhitelist][crate::core::CommandWhitelist]
+    #[error("Command is not whitelisted: {cmd}")]
+    CommandNotWhitelisted {
+        /// The command that was rejected
+        cmd: String,
+    },
+
+    /// A [Rect][crate::pure::geometry::Rect] with a width or height of zero was requested
+    #[error("Rects must have a non-zero width and height: {r:?}")]
+    DegenerateRect {
+        /// The rect that was requested
+        r: crate::pure::geometry::Rect,
+    },
+
+    /// A line in a rules file was unable to be parsed
+    #[error("Invalid rule on line {line}: {reason}")]
+    InvalidRule {
+        /// The (1-indexed) line number of the rule
+        line: usize,
+        /// Why parsing failed
+        reason: String,
+    },
+
+    /// A line in a saved state snapshot was unable to be parsed
+    #[error("Invalid state snapshot on line {line}")]
+    InvalidSnapshot {
+        /// The (1-indexed) line number that was invalid
+        line: usize,
+    },
+
     /// IO error
     #[error(transparent)]
     Io(#[from] std::io::Error),
@@ -144,6 +189,13 @@ pub enum Error {
         prop: String,
     },
 
+    /// A workspace that still contains clients was requested to be removed
+    #[error("Workspace '{tag}' still contains clients")]
+    NonEmptyWorkspace {
+        /// The tag of the workspace
+        tag: String,
+    },
+
     /// Duplicate tags were provided for one or more workspaces
     #[error("The following tags have been used multiple times for different workspaces: {tags:?}")]
     NonUniqueTags {
@@ -159,8 +211,8 @@ pub enum Error {
     #[error(transparent)]
     ParseInt(#[from] std::num::ParseIntError),
 
-    /// There was a problem initialising randr
-    #[error("Error initialising randr: {0}")]
+    /// There was a problem initialising randr or configuring outputs
+    #[error("Error using randr: {0}")]
     Randr(String),
 
     /// An operation was requested on a client window that is unknown
@@ -174,6 +226,13 @@ pub enum Error {
         name: String,
     },
 
+    /// A key macro was requested that has not been recorded
+    #[error("'{name}' is not a known key macro")]
+    UnknownMacro {
+        /// The name of the macro that was requested
+        name: String,
+    },
+
     /// An unknown character has been used to specify a modifier key
     #[error("'{name}' is not a known modifier key")]
     UnknownModifier {
@@ -195,6 +254,13 @@ pub enum Error {
         type_id: TypeId,
     },
 
+    /// An operation was requested on a workspace that is unknown
+    #[error("'{tag}' is not a known workspace tag")]
+    UnknownWorkspace {
+        /// The tag that was requested
+        tag: String,
+    },
+
     // TODO: These backend specific errors should be abstracted out to a
     //       set of common error variants that they can be mapped to without
     //       needing to extend the enum conditionally when flags are enabled
@@ -1,4 +1,17 @@
 //! Geometry primitives
+//!
+//! Positions are expressed in one of several coordinate spaces, each with its own type so that
+//! a position recorded against one screen is never accidentally applied against another:
+//!
+//! - [Rect]: absolute pixel coordinates relative to the root window, spanning all screens.
+//! - [MonitorRect]: pixel coordinates relative to the top left corner of a single screen.
+//! - [WorkspaceRect]: pixel coordinates relative to the top left corner of the region of a
+//!   screen available to the layout of a workspace (once bars, gaps and struts are removed).
+//! - [RelativeRect]: proportions of the size of a single screen.
+//!
+//! Converting between them is always explicit and requires the [Rect] of the screen (or
+//! workspace region) that the position is relative to.
+use crate::{Error, Result};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
@@ -135,6 +148,127 @@ impl RelativeTo for Rect {
     }
 }
 
+/// A position in pixels relative to the top left corner of a screen rather than the root
+/// window.
+///
+/// Use [Rect::to_monitor] and [MonitorRect::on_screen] to convert to and from absolute
+/// coordinates.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct MonitorRect {
+    /// The x-coordinate of the top left corner relative to the left edge of the screen
+    pub x: u32,
+    /// The y-coordinate of the top left corner relative to the top edge of the screen
+    pub y: u32,
+    /// The width of this rect
+    pub w: u32,
+    /// The height of this rect
+    pub h: u32,
+}
+
+impl MonitorRect {
+    /// Create a new MonitorRect.
+    pub const fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    /// The absolute position of this MonitorRect on the screen with the given [Rect].
+    ///
+    /// If the result would extend past the edge of the screen (such as when moving to a smaller
+    /// screen) then it is moved, and if needed shrunk, to fit.
+    pub fn on_screen(&self, screen: &Rect) -> Rect {
+        fit_within(self.x, self.y, self.w, self.h, screen)
+    }
+}
+
+impl RelativeTo for MonitorRect {
+    fn relative_to(&self, r: &Rect) -> RelativeRect {
+        self.on_screen(r).relative_to(r)
+    }
+}
+
+/// A position in pixels relative to the top left corner of the region of a screen that is
+/// available to the layout of a workspace.
+///
+/// Use [Rect::to_workspace] and [WorkspaceRect::in_region] to convert to and from absolute
+/// coordinates.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct WorkspaceRect {
+    /// The x-coordinate of the top left corner relative to the left edge of the region
+    pub x: u32,
+    /// The y-coordinate of the top left corner relative to the top edge of the region
+    pub y: u32,
+    /// The width of this rect
+    pub w: u32,
+    /// The height of this rect
+    pub h: u32,
+}
+
+impl WorkspaceRect {
+    /// Create a new WorkspaceRect.
+    pub const fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    /// The absolute position of this WorkspaceRect within the given region.
+    ///
+    /// If the result would extend past the edge of the region then it is moved, and if needed
+    /// shrunk, to fit.
+    pub fn in_region(&self, region: &Rect) -> Rect {
+        fit_within(self.x, self.y, self.w, self.h, region)
+    }
+}
+
+// Offset a position from the top left corner of `r`, keeping it inside of `r`.
+fn fit_within(x: u32, y: u32, w: u32, h: u32, r: &Rect) -> Rect {
+    let (w, h) = (w.min(r.w), h.min(r.h));
+
+    Rect {
+        x: r.x + x.min(r.w - w),
+        y: r.y + y.min(r.h - h),
+        w,
+        h,
+    }
+}
+
+/// A direction on screen relative to a given [Rect].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Direction {
+    /// Towards the top of the screen
+    Up,
+    /// Towards the bottom of the screen
+    Down,
+    /// Towards the left of the screen
+    Left,
+    /// Towards the right of the screen
+    Right,
+}
+
+/// The rotation of an [Output][crate::x::Output], as set using `xrandr --rotate`.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    /// Not rotated
+    #[default]
+    Normal,
+    /// Rotated 90 degrees counter clockwise
+    Left,
+    /// Rotated 180 degrees
+    Inverted,
+    /// Rotated 90 degrees clockwise
+    Right,
+}
+
+impl Rotation {
+    /// Whether or not this rotation results in a portrait orientation for a monitor that is
+    /// landscape when not rotated.
+    pub fn is_portrait(&self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+}
+
 /// An X window / screen position: top left corner + extent
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy, Hash)]
@@ -155,6 +289,53 @@ impl Rect {
         Rect { x, y, w, h }
     }
 
+    /// Create a new Rect, returning an error if it would have a width or height of zero.
+    /// ```
+    /// # use penrose::pure::geometry::Rect;
+    /// assert_eq!(Rect::try_new(0, 0, 10, 20).unwrap(), Rect::new(0, 0, 10, 20));
+    /// assert!(Rect::try_new(0, 0, 10, 0).is_err());
+    /// ```
+    pub fn try_new(x: u32, y: u32, w: u32, h: u32) -> Result<Rect> {
+        let r = Rect { x, y, w, h };
+        if r.is_degenerate() {
+            return Err(Error::DegenerateRect { r });
+        }
+
+        Ok(r)
+    }
+
+    /// Whether or not this Rect has a width or height of zero.
+    ///
+    /// Degenerate rects cover no pixels and should never be used to position a window.
+    pub fn is_degenerate(&self) -> bool {
+        self.w == 0 || self.h == 0
+    }
+
+    /// This Rect moved and shrunk as needed so that it lies entirely within `bounds`, with a
+    /// width and height of at least one pixel.
+    /// ```
+    /// # use penrose::pure::geometry::Rect;
+    /// let bounds = Rect::new(0, 0, 100, 100);
+    ///
+    /// assert_eq!(Rect::new(10, 10, 20, 0).clamped_to(&bounds), Rect::new(10, 10, 20, 1));
+    /// assert_eq!(Rect::new(90, 0, 20, 20).clamped_to(&bounds), Rect::new(80, 0, 20, 20));
+    /// assert_eq!(Rect::new(200, 200, 300, 50).clamped_to(&bounds), Rect::new(0, 50, 100, 50));
+    /// ```
+    pub fn clamped_to(&self, bounds: &Rect) -> Rect {
+        let w = self.w.clamp(1, bounds.w.max(1));
+        let h = self.h.clamp(1, bounds.h.max(1));
+        let x = self.x.clamp(
+            bounds.x,
+            (bounds.x + bounds.w).saturating_sub(w).max(bounds.x),
+        );
+        let y = self.y.clamp(
+            bounds.y,
+            (bounds.y + bounds.h).saturating_sub(h).max(bounds.y),
+        );
+
+        Rect { x, y, w, h }
+    }
+
     /// The four corners of this [Rect] in [Point] form returned in clockwise
     /// order from the top left corner.
     /// ```
@@ -200,6 +381,38 @@ impl Rect {
         }
     }
 
+    /// The distance from the midpoint of this rectangle to the midpoint of `other` if it lies
+    /// in the given [Direction], or `None` if it does not.
+    ///
+    /// Any offset perpendicular to the requested direction is weighted more heavily than the
+    /// distance along it so that rectangles in line with this one are preferred.
+    /// ```
+    /// # use penrose::pure::geometry::{Direction, Rect};
+    /// let r = Rect::new(0, 0, 100, 100);
+    /// let other = Rect::new(100, 0, 100, 100);
+    ///
+    /// assert_eq!(r.distance_in_direction(&other, Direction::Right), Some(100));
+    /// assert_eq!(r.distance_in_direction(&other, Direction::Left), None);
+    /// ```
+    pub fn distance_in_direction(&self, other: &Rect, d: Direction) -> Option<u32> {
+        let (p1, p2) = (self.midpoint(), other.midpoint());
+        let dx = p2.x as i64 - p1.x as i64;
+        let dy = p2.y as i64 - p1.y as i64;
+
+        let (along, across) = match d {
+            Direction::Up => (-dy, dx),
+            Direction::Down => (dy, dx),
+            Direction::Left => (-dx, dy),
+            Direction::Right => (dx, dy),
+        };
+
+        if along <= 0 {
+            return None;
+        }
+
+        Some((along + 2 * across.abs()) as u32)
+    }
+
     /// Shrink width and height by the given pixel border, maintaining the current x and y
     /// coordinates. The resulting `Rect` will always have a minimum width and height of 1.
     /// ```
@@ -225,6 +438,38 @@ impl Rect {
         Self { w, h, ..*self }
     }
 
+    /// The top, bottom, left and right edges of this [Rect] as `width` pixel thick rectangles
+    /// that together form an outline of it.
+    ///
+    /// Edges are at least one pixel thick. For rects that are too small to have an interior,
+    /// the left and right edges will have a height of zero.
+    /// ```
+    /// # use penrose::pure::geometry::Rect;
+    /// let r = Rect::new(10, 10, 100, 50);
+    ///
+    /// assert_eq!(
+    ///     r.outline(2),
+    ///     [
+    ///         Rect::new(10, 10, 100, 2),
+    ///         Rect::new(10, 58, 100, 2),
+    ///         Rect::new(10, 12, 2, 46),
+    ///         Rect::new(108, 12, 2, 46),
+    ///     ]
+    /// );
+    /// ```
+    pub fn outline(&self, width: u32) -> [Self; 4] {
+        let t = width.min(self.w / 2).min(self.h / 2).max(1);
+        let (w, h) = (self.w.max(1), self.h.max(1));
+        let inner_h = h.saturating_sub(2 * t);
+
+        [
+            Self::new(self.x, self.y, w, t),
+            Self::new(self.x, self.y + h.saturating_sub(t), w, t),
+            Self::new(self.x, self.y + t, t, inner_h),
+            Self::new(self.x + w.saturating_sub(t), self.y + t, t, inner_h),
+        ]
+    }
+
     /// Create a new [Rect] with width equal to `factor` x `self.w`
     /// ```
     /// # use penrose::pure::geometry::Rect;
@@ -340,6 +585,126 @@ impl Rect {
         })
     }
 
+    /// Center this Rect on the given [Point], keeping it within `enclosing`.
+    ///
+    /// Returns `None` if this Rect can not fit inside enclosing
+    pub fn centered_on_point_in(&self, p: Point, enclosing: &Rect) -> Option<Self> {
+        if self.w > enclosing.w || self.h > enclosing.h {
+            return None;
+        }
+
+        let x = p.x.saturating_sub(self.w / 2);
+        let y = p.y.saturating_sub(self.h / 2);
+
+        Some(Self {
+            x: x.clamp(enclosing.x, enclosing.x + enclosing.w - self.w),
+            y: y.clamp(enclosing.y, enclosing.y + enclosing.h - self.h),
+            ..*self
+        })
+    }
+
+    /// The area of the region where this Rect and `other` overlap.
+    pub fn overlap(&self, other: &Rect) -> u32 {
+        let x1 = self.x.max(other.x);
+        let y1 = self.y.max(other.y);
+        let x2 = (self.x + self.w).min(other.x + other.w);
+        let y2 = (self.y + self.h).min(other.y + other.h);
+
+        x2.saturating_sub(x1) * y2.saturating_sub(y1)
+    }
+
+    /// The position of this Rect relative to the top left corner of the screen with the given
+    /// [Rect].
+    ///
+    /// Any part of this Rect above or to the left of the screen is clipped.
+    pub fn to_monitor(&self, screen: &Rect) -> MonitorRect {
+        let (x, y) = (self.x.max(screen.x), self.y.max(screen.y));
+        let w = self.w.saturating_sub(x - self.x);
+        let h = self.h.saturating_sub(y - self.y);
+
+        MonitorRect::new(x - screen.x, y - screen.y, w, h)
+    }
+
+    /// The position of this Rect relative to the top left corner of the given region of a
+    /// screen available to a workspace.
+    ///
+    /// Any part of this Rect above or to the left of the region is clipped.
+    pub fn to_workspace(&self, region: &Rect) -> WorkspaceRect {
+        let MonitorRect { x, y, w, h } = self.to_monitor(region);
+
+        WorkspaceRect::new(x, y, w, h)
+    }
+
+    /// The largest region of this Rect that does not overlap with `other`.
+    ///
+    /// The region is found by cutting away the part of this Rect to the left, right, above or
+    /// below `other`, keeping whichever of the four remaining regions is largest. Ties are
+    /// broken in that order. If the two do not overlap then this Rect is returned unchanged.
+    pub fn without(&self, other: &Rect) -> Self {
+        if self.overlap(other) == 0 {
+            return *self;
+        }
+
+        let (x2, y2) = (self.x + self.w, self.y + self.h);
+        let candidates = [
+            Rect::new(self.x, self.y, other.x.saturating_sub(self.x), self.h),
+            Rect {
+                x: (other.x + other.w).min(x2),
+                w: x2.saturating_sub(other.x + other.w),
+                ..*self
+            },
+            Rect::new(self.x, self.y, self.w, other.y.saturating_sub(self.y)),
+            Rect {
+                y: (other.y + other.h).min(y2),
+                h: y2.saturating_sub(other.y + other.h),
+                ..*self
+            },
+        ];
+
+        candidates
+            .into_iter()
+            .rev()
+            .max_by_key(|r| r.w * r.h)
+            .expect("there are four candidates")
+    }
+
+    /// Position this Rect within `enclosing` so that it overlaps with `others` as little as
+    /// possible.
+    ///
+    /// Candidate positions are the corners of `enclosing` and the positions directly beside
+    /// each of `others`. Ties are broken by preferring positions closer to the top left of
+    /// `enclosing`. Returns `None` if this Rect can not fit inside enclosing.
+    pub fn least_overlapping_in(&self, enclosing: &Rect, others: &[Rect]) -> Option<Self> {
+        if self.w > enclosing.w || self.h > enclosing.h {
+            return None;
+        }
+
+        let (min_x, max_x) = (enclosing.x, enclosing.x + enclosing.w - self.w);
+        let (min_y, max_y) = (enclosing.y, enclosing.y + enclosing.h - self.h);
+
+        let mut xs = vec![min_x, max_x];
+        let mut ys = vec![min_y, max_y];
+        for o in others {
+            xs.extend([o.x + o.w, o.x.saturating_sub(self.w)]);
+            ys.extend([o.y + o.h, o.y.saturating_sub(self.h)]);
+        }
+
+        let clamp = |v: &mut Vec<u32>, lo: u32, hi: u32| {
+            v.iter_mut().for_each(|n| *n = (*n).clamp(lo, hi));
+            v.sort_unstable();
+            v.dedup();
+        };
+        clamp(&mut xs, min_x, max_x);
+        clamp(&mut ys, min_y, max_y);
+
+        ys.iter()
+            .flat_map(|&y| xs.iter().map(move |&x| Self { x, y, ..*self }))
+            .min_by_key(|r| {
+                let overlap: u32 = others.iter().map(|o| r.overlap(o)).sum();
+                (overlap, r.y, r.x)
+            })
+    }
+
     /// Split this `Rect` into evenly sized rows.
     pub fn as_rows(&self, n_rows: u32) -> Vec<Rect> {
         if n_rows <= 1 {
@@ -488,6 +853,19 @@ mod tests {
         Rect::new(x, y, w, h)
     }
 
+    #[test_case(r(200, 0, 100, 100), Direction::Right, Some(200); "in line")]
+    #[test_case(r(200, 50, 100, 100), Direction::Right, Some(300); "offset")]
+    #[test_case(r(200, 0, 100, 100), Direction::Left, None; "wrong direction")]
+    #[test_case(r(0, 0, 100, 100), Direction::Down, None; "same position")]
+    #[test_case(r(0, 100, 100, 100), Direction::Down, Some(100); "below")]
+    #[test_case(r(50, 100, 100, 100), Direction::Down, Some(200); "below offset")]
+    #[test]
+    fn distance_in_direction(other: Rect, d: Direction, expected: Option<u32>) {
+        let from = r(0, 0, 100, 100);
+
+        assert_eq!(from.distance_in_direction(&other, d), expected);
+    }
+
     fn rr(x: f64, y: f64, w: f64, h: f64) -> RelativeRect {
         RelativeRect::new(x, y, w, h)
     }
@@ -539,6 +917,23 @@ mod tests {
         )
     }
 
+    #[test_case(r(0, 0, 10, 20), 1, 1; "thin")]
+    #[test_case(r(0, 0, 10, 20), 100, 5; "clamped to half the size")]
+    #[test_case(r(0, 0, 1, 1), 3, 1; "tiny rect")]
+    #[test_case(r(0, 0, 10, 20), 0, 1; "zero width")]
+    #[test]
+    fn outline_edges_are_within_the_rect(r: Rect, width: u32, thickness: u32) {
+        let [top, bottom, left, right] = r.outline(width);
+
+        assert_eq!(
+            (top.h, bottom.h, left.w, right.w),
+            (thickness, thickness, thickness, thickness)
+        );
+        for edge in [top, bottom, left, right] {
+            assert!(r.contains(&edge), "{edge:?} not in {r:?}");
+        }
+    }
+
     #[test_case(1.5, r(10, 20, 45, 40); "scale up")]
     #[test_case(0.5, r(10, 20, 15, 40); "scale down")]
     #[test_case(1.0, r(10, 20, 30, 40); "unchanged")]
@@ -620,6 +1015,99 @@ mod tests {
         assert_eq!(res, expected);
     }
 
+    #[test_case(p(10, 10), Some(r(5, 5, 10, 10)); "centered on point")]
+    #[test_case(p(0, 0), Some(r(0, 0, 10, 10)); "clamped top left")]
+    #[test_case(p(20, 20), Some(r(10, 10, 10, 10)); "clamped bottom right")]
+    #[test]
+    fn centered_on_point_in(point: Point, expected: Option<Rect>) {
+        let outer = Rect::new(0, 0, 20, 20);
+
+        assert_eq!(
+            r(0, 0, 10, 10).centered_on_point_in(point, &outer),
+            expected
+        );
+    }
+
+    #[test_case(r(0, 0, 10, 10), 100; "identical")]
+    #[test_case(r(5, 5, 10, 10), 25; "partial")]
+    #[test_case(r(10, 0, 10, 10), 0; "adjacent")]
+    #[test_case(r(50, 50, 10, 10), 0; "disjoint")]
+    #[test]
+    fn overlap(other: Rect, expected: u32) {
+        assert_eq!(r(0, 0, 10, 10).overlap(&other), expected);
+        assert_eq!(other.overlap(&r(0, 0, 10, 10)), expected);
+    }
+
+    #[test_case(r(80, 0, 20, 100), r(0, 0, 80, 100); "right sidebar")]
+    #[test_case(r(0, 0, 100, 10), r(0, 10, 100, 90); "top bar")]
+    #[test_case(r(40, 90, 20, 10), r(0, 0, 100, 90); "partial bottom edge")]
+    #[test_case(r(90, 20, 50, 20), r(0, 0, 90, 100); "overhanging right edge")]
+    #[test_case(r(10, 40, 80, 20), r(0, 0, 100, 40); "middle band")]
+    #[test_case(r(0, 0, 100, 100), r(0, 0, 0, 100); "everything")]
+    #[test_case(r(200, 200, 10, 10), r(0, 0, 100, 100); "disjoint")]
+    #[test]
+    fn without(other: Rect, expected: Rect) {
+        assert_eq!(r(0, 0, 100, 100).without(&other), expected);
+    }
+
+    #[test_case(r(1100, 50, 200, 100), MonitorRect::new(100, 50, 200, 100); "inside")]
+    #[test_case(r(900, 50, 200, 100), MonitorRect::new(0, 50, 100, 100); "clipped left")]
+    #[test_case(r(0, 0, 50, 50), MonitorRect::new(0, 0, 0, 50); "other screen")]
+    #[test]
+    fn to_monitor(rect: Rect, expected: MonitorRect) {
+        assert_eq!(rect.to_monitor(&r(1000, 0, 1000, 800)), expected);
+    }
+
+    #[test_case(r(1000, 0, 1000, 800), r(1100, 50, 200, 100); "same screen")]
+    #[test_case(r(0, 0, 1000, 800), r(100, 50, 200, 100); "other screen")]
+    #[test_case(r(0, 0, 250, 800), r(50, 50, 200, 100); "moved to fit")]
+    #[test_case(r(0, 0, 150, 80), r(0, 0, 150, 80); "shrunk to fit")]
+    #[test]
+    fn monitor_rect_round_trips_between_screens(screen: Rect, expected: Rect) {
+        let m = r(1100, 50, 200, 100).to_monitor(&r(1000, 0, 1000, 800));
+
+        assert_eq!(m.on_screen(&screen), expected);
+    }
+
+    #[test]
+    fn workspace_rect_is_relative_to_the_region() {
+        let region = r(1000, 20, 1000, 780);
+        let w = r(1100, 70, 200, 100).to_workspace(&region);
+
+        assert_eq!(w, WorkspaceRect::new(100, 50, 200, 100));
+        assert_eq!(w.in_region(&region), r(1100, 70, 200, 100));
+    }
+
+    #[test_case(&[], Some(r(0, 0, 40, 40)); "empty screen")]
+    #[test_case(&[r(0, 0, 50, 50)], Some(r(50, 0, 40, 40)); "beside existing")]
+    #[test_case(&[r(0, 0, 100, 50)], Some(r(0, 50, 40, 40)); "below existing")]
+    #[test_case(&[r(0, 0, 100, 70)], Some(r(0, 60, 40, 40)); "minimal overlap")]
+    #[test]
+    fn least_overlapping_in(others: &[Rect], expected: Option<Rect>) {
+        let screen = r(0, 0, 100, 100);
+
+        assert_eq!(
+            r(0, 0, 40, 40).least_overlapping_in(&screen, others),
+            expected
+        );
+    }
+
+    #[test_case(r(60, 60, 20, 20), r(60, 60, 20, 20); "already inside")]
+    #[test_case(r(60, 60, 0, 0), r(60, 60, 1, 1); "zero sized")]
+    #[test_case(r(0, 0, 200, 50), r(50, 50, 100, 50); "too wide")]
+    #[test_case(r(140, 140, 20, 20), r(130, 130, 20, 20); "past bottom right")]
+    #[test_case(r(0, 0, 20, 20), r(50, 50, 20, 20); "before top left")]
+    #[test_case(r(500, 500, 20, 20), r(130, 130, 20, 20); "entirely outside")]
+    #[test]
+    fn clamped_to(rect: Rect, expected: Rect) {
+        let bounds = Rect::new(50, 50, 100, 100);
+        let clamped = rect.clamped_to(&bounds);
+
+        assert_eq!(clamped, expected);
+        assert!(bounds.contains(&clamped));
+        assert!(!clamped.is_degenerate());
+    }
+
     #[test_case(r(0, 0, 100, 100), 1; "simple single")]
     #[test_case(r(0, 0, 100, 100), 4; "simple even")]
     #[test_case(r(0, 0, 100, 100), 7; "simple odd")]
@@ -1,4 +1,16 @@
 //! Side effect free management of internal window manager state
+//!
+//! The data structures in this module (along with the layouts found in [core::layout][0],
+//! [builtin::layout][1] and [extensions::layout][2]) have no dependency on X11 or the host
+//! operating system. Building penrose with `default-features = false` provides just these
+//! modules, allowing them to be compiled to targets such as `wasm32-unknown-unknown` and
+//! reused outside of the window manager itself.
+//!
+//!   [0]: crate::core::layout
+//!   [1]: crate::builtin::layout
+//!   [2]: crate::extensions::layout
+// Some internals are only used by the window manager itself
+#![cfg_attr(not(feature = "wm"), allow(dead_code, unused_imports))]
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
@@ -24,6 +36,22 @@ pub(crate) use screen::ScreenClients;
 #[cfg(test)]
 pub(crate) use stack_set::tests::test_xid_stack_set;
 
+/// A screen identified either by its index or by the role of the output it is showing.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ScreenTarget {
+    /// The screen showing the primary output (see [StackSet::primary_screen])
+    Primary,
+    /// The screen with the given index
+    Index(usize),
+}
+
+impl From<usize> for ScreenTarget {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
 /// A relative position along the horizontal and vertical axes
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
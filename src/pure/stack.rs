@@ -494,6 +494,20 @@ impl<T> Stack<T> {
             None => self.reverse().rev_down(),
         }
     }
+
+    /// Rotate all elements of the stack forward, wrapping from top to bottom.
+    /// Unlike [Stack::rotate_up], the focused position in the stack is maintained
+    /// by this operation rather than the focused element.
+    pub fn rotate_contents_up(&mut self) -> &mut Self {
+        self.rotate_up().focus_down()
+    }
+
+    /// Rotate all elements of the stack back, wrapping from bottom to top.
+    /// Unlike [Stack::rotate_down], the focused position in the stack is maintained
+    /// by this operation rather than the focused element.
+    pub fn rotate_contents_down(&mut self) -> &mut Self {
+        self.rotate_down().focus_up()
+    }
 }
 
 impl<T: Clone> Stack<T> {
@@ -897,6 +911,28 @@ mod tests {
         assert_eq!(s, expected);
     }
 
+    #[test_case(stack!([1, 2], 3, [4, 5]), stack!([2, 3], 4, [5, 1]); "items up and down")]
+    #[test_case(stack!(1, [2, 3]), stack!(2, [3, 1]); "items down only")]
+    #[test_case(stack!([1, 2], 3), stack!([2, 3], 1); "items up only")]
+    #[test_case(stack!(1), stack!(1); "only focused")]
+    #[test]
+    fn rotate_contents_up(mut s: Stack<usize>, expected: Stack<usize>) {
+        s.rotate_contents_up();
+
+        assert_eq!(s, expected);
+    }
+
+    #[test_case(stack!([1, 2], 3, [4, 5]), stack!([5, 1], 2, [3, 4]); "items up and down")]
+    #[test_case(stack!(1, [2, 3]), stack!(3, [1, 2]); "items down only")]
+    #[test_case(stack!([1, 2], 3), stack!([3, 1], 2); "items up only")]
+    #[test_case(stack!(1), stack!(1); "only focused")]
+    #[test]
+    fn rotate_contents_down(mut s: Stack<usize>, expected: Stack<usize>) {
+        s.rotate_contents_down();
+
+        assert_eq!(s, expected);
+    }
+
     #[test_case(Position::Focus, stack!([1,2], 6, [3,4,5]); "focus")]
     #[test_case(Position::Before, stack!([1,2,6], 3, [4,5]); "before")]
     #[test_case(Position::After, stack!([1,2], 3, [6,4,5]); "after")]
@@ -3,15 +3,15 @@ use crate::{
     pop_where,
     pure::{
         diff::{ScreenState, Snapshot},
-        geometry::{Rect, RelativeRect, RelativeTo},
+        geometry::{Direction, Rect, RelativeRect, RelativeTo},
         workspace::check_workspace_invariants,
-        Position, Screen, Stack, Workspace,
+        Position, Screen, ScreenTarget, Stack, Workspace,
     },
     stack, Error, Result, Xid,
 };
 use std::{
     cmp::Ordering,
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     hash::Hash,
     mem::{swap, take},
 };
@@ -26,9 +26,17 @@ where
     pub(crate) hidden: VecDeque<Workspace<C>>, // Workspaces not currently on any screen
     pub(crate) floating: HashMap<C, RelativeRect>, // Floating windows
     pub(crate) bars: HashMap<C, RelativeRect>, // Bars
+    pub(crate) maximized: HashSet<C>,     // Tiled windows taking the full workspace area
+    pub(crate) boring: HashSet<C>,        // Windows skipped when cycling focus
+    pub(crate) transients: HashMap<C, C>, // Transient windows and the parent they belong to
+    pub(crate) modal: HashSet<C>,         // Transient windows blocking input to their parent
     pub(crate) previous_tag: String,      // The last tag to be focused before the current one
     pub(crate) invisible_tags: Vec<String>, // Tags that should never be focused
     pub(crate) killed_clients: Vec<C>, // clients that have been removed and need processing on the X side
+    pub(crate) screen_local_tags: Vec<String>, // Per-screen tags to generate for each screen (per-screen mode only)
+    pub(crate) tag_screens: HashMap<String, usize>, // The screen owning each tag (per-screen mode only)
+    pub(crate) detached_tags: Vec<String>, // Tags that were on screens which have since been disconnected
+    pub(crate) primary_screen: Option<usize>, // The screen showing the primary output if known
 }
 
 impl<C> StackSet<C>
@@ -93,12 +101,115 @@ where
             hidden,
             floating,
             bars: HashMap::new(),
+            maximized: HashSet::new(),
+            boring: HashSet::new(),
+            transients: HashMap::new(),
+            modal: HashSet::new(),
             previous_tag,
             invisible_tags: vec![],
             killed_clients: vec![],
+            screen_local_tags: vec![],
+            tag_screens: HashMap::new(),
+            detached_tags: vec![],
+            primary_screen: None,
         })
     }
 
+    /// Create a new [StackSet] where each screen has its own independent set of
+    /// workspaces using the given tags (dwm style) rather than sharing a single
+    /// pool of workspaces between all screens (xmonad style).
+    ///
+    /// The workspaces for each screen are tagged as `"{screen_index}:{tag}"` and
+    /// will only ever be displayed on the screen that owns them.
+    ///
+    /// # Errors
+    /// This method will error if no tags are provided, if the given tags are not
+    /// unique or if no screens are attached.
+    pub fn try_new_per_screen<I, J, T>(
+        layouts: LayoutStack,
+        ws_tags: I,
+        screen_details: J,
+    ) -> Result<Self>
+    where
+        T: Into<String>,
+        I: IntoIterator<Item = T>,
+        J: IntoIterator<Item = Rect>,
+    {
+        let local_tags: Vec<String> = ws_tags.into_iter().map(|t| t.into()).collect();
+        let screen_details: Vec<Rect> = screen_details.into_iter().collect();
+        let n_tags = local_tags.len();
+
+        if n_tags == 0 {
+            return Err(Error::InsufficientWorkspaces {
+                n_ws: 0,
+                n_screens: screen_details.len(),
+            });
+        }
+
+        let mut tag_screens = HashMap::new();
+        let mut workspaces = Vec::with_capacity(n_tags * screen_details.len());
+        for index in 0..screen_details.len() {
+            for (i, local) in local_tags.iter().enumerate() {
+                let tag = per_screen_tag(index, local);
+                tag_screens.insert(tag.clone(), index);
+                workspaces.push(Workspace::new(
+                    index * n_tags + i,
+                    tag,
+                    layouts.clone(),
+                    None,
+                ));
+            }
+        }
+
+        // The first workspace for each screen needs to be at the front so that
+        // it ends up on the correct screen when we construct the StackSet.
+        workspaces.sort_by_key(|w| (w.id % n_tags, w.id));
+
+        let mut ss = Self::try_new_concrete(workspaces, screen_details, HashMap::new())?;
+        ss.screen_local_tags = local_tags;
+        ss.tag_screens = tag_screens;
+
+        Ok(ss)
+    }
+
+    /// Whether or not each screen has its own independent set of workspaces.
+    ///
+    /// See [StackSet::try_new_per_screen] for details.
+    pub fn is_per_screen(&self) -> bool {
+        !self.screen_local_tags.is_empty()
+    }
+
+    /// The index of the [Screen] that owns the given tag when running with per-screen
+    /// workspaces.
+    ///
+    /// Returns `None` when workspaces are shared between screens, if the tag is unknown or
+    /// if the owning screen is no longer connected.
+    pub fn screen_for_tag(&self, tag: &str) -> Option<usize> {
+        let index = *self.tag_screens.get(tag)?;
+
+        self.screens
+            .iter()
+            .any(|s| s.index == index)
+            .then_some(index)
+    }
+
+    /// The tag for the `n`th (0-indexed) workspace available to the current [Screen].
+    ///
+    /// When running with per-screen workspaces this only considers workspaces owned by
+    /// the focused screen, otherwise it indexes into [StackSet::ordered_tags].
+    pub fn tag_for_current_screen(&self, n: usize) -> Option<String> {
+        if !self.is_per_screen() {
+            return self.ordered_tags().into_iter().nth(n);
+        }
+
+        let index = self.screens.focus.index;
+
+        self.ordered_workspaces()
+            .filter(|w| self.tag_screens.get(&w.tag) == Some(&index))
+            .map(|w| w.tag.clone())
+            .nth(n)
+    }
+
     /// Set focus to the [Screen] with the specified index.
     ///
     /// If there is no matching screen then the [StackSet] is unmodified.
@@ -139,8 +250,17 @@ where
             return; // already focused
         }
 
-        // If the tag is visible on another screen, focus moves to that screen
-        if !self.try_cycle_screen_to_tag(tag) {
+        if let Some(index) = self.screen_for_tag(tag) {
+            // With per-screen workspaces, tags are only ever shown on their own screen
+            if self.screens.focus.index != index {
+                self.update_previous_tag(self.screens.focus.workspace.tag.clone());
+                self.focus_screen(index);
+            }
+            if self.screens.focus.workspace.tag != tag {
+                self.try_swap_on_screen_workspace_with_hidden(tag);
+            }
+        } else if !self.try_cycle_screen_to_tag(tag) {
+            // If the tag is visible on another screen, focus moves to that screen
             // If the tag is hidden then it gets moved to the current screen
             self.try_swap_on_screen_workspace_with_hidden(tag);
         }
@@ -211,6 +331,9 @@ where
 
     /// Focus the requested tag on the current screen, swapping the current
     /// tag with it.
+    ///
+    /// When running with per-screen workspaces, tags owned by another screen are
+    /// focused on that screen instead (see [StackSet::focus_tag]).
     pub fn pull_tag_to_screen(&mut self, tag: impl AsRef<str>) {
         let tag = tag.as_ref();
 
@@ -218,6 +341,10 @@ where
             return;
         }
 
+        if self.screen_for_tag(tag).is_some() {
+            return self.focus_tag(tag);
+        }
+
         if !self.try_swap_focused_workspace_with_tag(tag) {
             self.try_swap_on_screen_workspace_with_hidden(tag);
         }
@@ -244,21 +371,85 @@ where
 
         self.focus_tag(&tag);
 
-        while self.current_client() != Some(client) {
-            self.focus_up()
+        // Focus is moved directly rather than stepping with focus_up, which skips boring
+        // clients and would never reach the target if it is one.
+        if let Some(ref mut stack) = self.screens.focus.workspace.stack {
+            stack.focus_element(client);
         }
-        // go to first non bar
-        loop {
-            if let Some(cl) = self.current_client() {
-                if self.bars.contains_key(cl) {
-                    self.focus_up();
-                } else {
-                    break;
-                }
+
+        // bars can't hold focus so move on to the next client that can
+        if self
+            .current_client()
+            .is_some_and(|c| self.bars.contains_key(c))
+        {
+            self.focus_up();
+        }
+    }
+
+    /// Swap the focused client with the client in the main (head) position of the current
+    /// [Stack], leaving focus on the client that is now in the main position.
+    ///
+    /// If the focused client is already in the main position then it is swapped with the
+    /// client below it instead. This is a no-op if the current stack is empty.
+    pub fn swap_focused_with_main(&mut self) {
+        if let Some(ref mut stack) = self.screens.focus.workspace.stack {
+            if stack.up.is_empty() {
+                stack.swap_down().focus_head();
             } else {
-                break;
+                stack.swap_focus_and_head();
+            }
+        }
+    }
+
+    /// Promote the focused client to the main (head) position of the current [Stack], leaving
+    /// focus on the client that is now in the main position.
+    ///
+    /// If the focused client is already in the main position then `previous` (typically the
+    /// client displaced by the last promotion) is promoted instead, falling back to the client
+    /// below the focused client if `previous` is not in the current stack. This mirrors the
+    /// `zoom` behaviour of dwm.
+    ///
+    /// Returns the client that was moved out of the main position, if any.
+    pub fn swap_with_main(&mut self, previous: Option<&C>) -> Option<C> {
+        let stack = self.current_stack().filter(|s| s.len() > 1)?;
+        let head = stack.head().clone();
+        let previous = previous
+            .filter(|&p| stack.up.is_empty() && p != &head && stack.contains(p))
+            .cloned();
+
+        if let Some(p) = previous {
+            self.focus_client(&p);
+        }
+        self.swap_focused_with_main();
+
+        Some(head)
+    }
+
+    /// Swap the positions of two clients within their respective [Stack]s.
+    ///
+    /// If either client was focused then focus follows it to its new position. This is a
+    /// no-op if either client is unknown.
+    pub fn swap_clients(&mut self, a: &C, b: &C) {
+        if a == b || !self.contains(a) || !self.contains(b) {
+            return;
+        }
+
+        let focused = self.current_client().cloned();
+
+        for s in self.workspaces_mut().flat_map(|w| w.stack.as_mut()) {
+            for c in s.iter_mut() {
+                if c == a {
+                    *c = b.clone();
+                } else if c == b {
+                    *c = a.clone();
+                }
             }
         }
+
+        match focused {
+            Some(c) if &c == a || &c == b => self.focus_client(&c),
+            _ => (),
+        }
     }
 
     /// Insert the given client to the current [Stack] in a default [Position].
@@ -298,9 +489,76 @@ where
     /// Clear the floating status of a client, returning its previous preferred
     /// screen position if the client was known, otherwise `None`.
     pub fn sink(&mut self, client: &C) -> Option<Rect> {
+        let r_screen = match self.screen_for_client(client) {
+            Some(s) => s.r,
+            None => self.screens.focus.r,
+        };
+
         self.floating
             .remove(client)
-            .map(|rr| rr.applied_to(&self.screens.focus.r))
+            .map(|rr| rr.applied_to(&r_screen))
+    }
+
+    /// Toggle whether or not the given tiled client should be maximized, taking up the
+    /// full area available to the layout of its workspace above the other tiled clients.
+    ///
+    /// Returns whether or not the client is now maximized.
+    pub fn toggle_maximized(&mut self, client: &C) -> bool {
+        if self.maximized.remove(client) || !self.contains(client) {
+            return false;
+        }
+
+        self.maximized.insert(client.clone());
+
+        true
+    }
+
+    /// Check whether or not the given client is currently maximized.
+    pub fn is_maximized(&self, client: &C) -> bool {
+        self.maximized.contains(client)
+    }
+
+    /// Toggle whether or not the given client is "boring": boring clients remain in their
+    /// current position but are skipped when moving focus using [StackSet::focus_up] and
+    /// [StackSet::focus_down].
+    ///
+    /// Returns whether or not the client is now boring.
+    pub fn toggle_boring(&mut self, client: &C) -> bool {
+        if self.boring.remove(client) || !self.contains(client) {
+            return false;
+        }
+
+        self.boring.insert(client.clone());
+
+        true
+    }
+
+    /// Mark the given client as boring (see [StackSet::toggle_boring]).
+    ///
+    /// This is a no-op if the client is unknown.
+    pub fn mark_boring(&mut self, client: &C) {
+        if self.contains(client) {
+            self.boring.insert(client.clone());
+        }
+    }
+
+    /// Check whether or not the given client is currently boring.
+    pub fn is_boring(&self, client: &C) -> bool {
+        self.boring.contains(client)
+    }
+
+    /// Clear the boring status of all clients, returning the clients that were boring.
+    ///
+    /// This allows for focus to be moved to boring clients by temporarily clearing the set
+    /// of boring clients before restoring it using [StackSet::restore_boring].
+    pub fn take_boring(&mut self) -> HashSet<C> {
+        std::mem::take(&mut self.boring)
+    }
+
+    /// Restore a set of boring clients returned by [StackSet::take_boring], ignoring any
+    /// clients that are no longer known.
+    pub fn restore_boring(&mut self, boring: HashSet<C>) {
+        self.boring = boring.into_iter().filter(|c| self.contains(c)).collect();
     }
 
     /// Check whether a given tag currently has any floating windows present.
@@ -315,6 +573,8 @@ where
     /// Delete a client from this [StackSet].
     pub fn remove_client(&mut self, client: &C) -> Option<C> {
         self.sink(client); // Clear any floating information we might have
+        self.maximized.remove(client);
+        self.boring.remove(client);
 
         self.workspaces_mut()
             .map(|w| w.remove(client))
@@ -340,6 +600,19 @@ where
         }
     }
 
+    /// Delete all clients on the current [Workspace] from this stack.
+    ///
+    /// As with [StackSet::kill_focused], the following diff will send a kill client
+    /// message to each of these clients on refresh.
+    pub fn kill_all_on_current_workspace(&mut self) {
+        let clients: Vec<C> = self.current_workspace().clients().cloned().collect();
+        for c in clients {
+            if self.remove_client(&c).is_some() {
+                self.killed_clients.push(c);
+            }
+        }
+    }
+
     /// Move the focused client of the current [Workspace] to the focused position
     /// of the workspace matching the provided `tag`.
     pub fn move_focused_to_tag(&mut self, tag: impl AsRef<str>) {
@@ -393,17 +666,169 @@ where
     /// NOTE: This will silently fail if the tag is not in the StackSet which
     ///       is why the method is not in the public API
     pub(crate) fn insert_as_focus_for(&mut self, tag: &str, c: C) {
+        self.insert_at_for(tag, Position::Focus, c)
+    }
+
+    /// Insert a client at the requested [Position] for the given tag.
+    ///
+    /// NOTE: This will silently fail if the tag is not in the StackSet which
+    ///       is why the method is not in the public API
+    pub(crate) fn insert_at_for(&mut self, tag: &str, pos: Position, c: C) {
         self.modify_workspace(tag, |w| {
             w.stack = Some(match take(&mut w.stack) {
                 None => stack!(c),
                 Some(mut s) => {
-                    s.insert_at(Position::Focus, c);
+                    s.insert_at(pos, c);
                     s
                 }
             });
         });
     }
 
+    /// Move the given client to the requested [Position] within the [Stack] of the
+    /// [Workspace] it is currently on.
+    ///
+    /// The position is relative to the focus of the stack once the client has been
+    /// removed from it, so moving the focused client will shift focus in the same way
+    /// as removing it. The client will only hold focus afterwards if `pos` is
+    /// [Position::Focus].
+    pub fn move_client_to_position(&mut self, client: &C, pos: Position) {
+        let tag = match self.tag_for_client(client) {
+            Some(tag) => tag.to_string(),
+            None => return,
+        };
+
+        if let Some(c) = self.workspace_mut(&tag).and_then(|w| w.remove(client)) {
+            self.insert_at_for(&tag, pos, c);
+        }
+    }
+
+    /// Record `child` as being transient for `parent` (typically a dialog window belonging
+    /// to an application window).
+    ///
+    /// Transient clients are kept on the same [Workspace] as their parent, are stacked
+    /// above it and are killed when their parent is removed. If `modal` is true then
+    /// focusing the parent will focus the child instead while it is open. Requests that
+    /// would create a cycle of transient clients are ignored.
+    pub fn set_transient_for(&mut self, child: C, parent: C, modal: bool) {
+        let mut ancestor = Some(&parent);
+        while let Some(a) = ancestor {
+            if *a == child {
+                return;
+            }
+            ancestor = self.transients.get(a);
+        }
+
+        if modal {
+            self.modal.insert(child.clone());
+        }
+        self.transients.insert(child, parent);
+    }
+
+    /// The client that the given client is transient for, if there is one.
+    pub fn transient_parent(&self, client: &C) -> Option<&C> {
+        self.transients.get(client)
+    }
+
+    /// The clients that are transient for the given client.
+    pub fn transient_children<'a>(&'a self, parent: &'a C) -> impl Iterator<Item = &'a C> {
+        self.transients
+            .iter()
+            .filter(move |&(_, p)| p == parent)
+            .map(|(c, _)| c)
+    }
+
+    /// Keep transient clients with their parents following a modification of this
+    /// [StackSet]: transients of removed clients are killed, transients on a different
+    /// workspace to their parent are moved to join it and focus is passed from a parent
+    /// to its modal transient.
+    pub(crate) fn sync_transients(&mut self) {
+        if self.transients.is_empty() {
+            return;
+        }
+
+        let mut orphaned = Vec::new();
+        let mut moves = Vec::new();
+        for (child, parent) in self.transients.iter() {
+            if !self.contains(child) {
+                orphaned.push(child.clone());
+            } else if !self.contains(parent) {
+                orphaned.push(child.clone());
+                self.killed_clients.push(child.clone());
+            } else {
+                match (self.tag_for_client(child), self.tag_for_client(parent)) {
+                    (Some(t1), Some(t2)) if t1 != t2 => moves.push((child.clone(), t2.to_string())),
+                    _ => (),
+                }
+            }
+        }
+
+        for c in orphaned {
+            self.remove_client(&c);
+            self.transients.remove(&c);
+            self.modal.remove(&c);
+        }
+
+        for (c, tag) in moves {
+            self.move_client_to_tag(&c, tag);
+        }
+
+        let focused = match self.current_client() {
+            Some(c) => c.clone(),
+            None => return,
+        };
+
+        let modal = self
+            .transient_children(&focused)
+            .find(|c| self.modal.contains(c))
+            .cloned();
+
+        if let Some(c) = modal {
+            self.focus_client(&c);
+        }
+    }
+
+    // Reorder the given positions so that transient clients are always stacked above
+    // their parent.
+    pub(crate) fn raise_transients(&self, positions: &mut Vec<(C, Rect)>) {
+        if self.transients.is_empty() {
+            return;
+        }
+
+        let mut i = 0;
+        while i < positions.len() {
+            let parent_ix = self
+                .transient_parent(&positions[i].0)
+                .and_then(|p| positions.iter().position(|(c, _)| c == p));
+
+            match parent_ix {
+                Some(ix) if ix > i => {
+                    // Keep siblings in their existing stacking order
+                    let child = positions.remove(i);
+                    let parent = self.transient_parent(&child.0);
+                    let n_siblings = positions[ix..]
+                        .iter()
+                        .take_while(|(c, _)| self.transient_parent(c) == parent)
+                        .count();
+                    positions.insert(ix + n_siblings, child);
+                }
+                _ => i += 1,
+            }
+        }
+    }
+
+    /// Focus the given client within the [Stack] of the [Workspace] it is on without
+    /// changing the focused workspace.
+    pub(crate) fn focus_client_on_its_workspace(&mut self, client: &C) {
+        if let Some(tag) = self.tag_for_client(client).map(|t| t.to_string()) {
+            self.modify_workspace(&tag, |w| {
+                if let Some(s) = w.stack.as_mut() {
+                    s.focus_element(client);
+                }
+            });
+        }
+    }
+
     /// Is the given tag present in the [StackSet]?
     pub fn contains_tag(&self, tag: &str) -> bool {
         self.workspaces().any(|w| w.tag == tag)
@@ -532,6 +957,12 @@ where
             .max()
             .expect("at least one workspace")
             + 1;
+
+        if self.is_per_screen() {
+            self.tag_screens
+                .insert(tag.clone(), self.screens.focus.index);
+        }
+
         let ws = Workspace::new(id, tag, layouts, None);
         self.hidden.push_front(ws);
 
@@ -557,6 +988,109 @@ where
         Ok(())
     }
 
+    /// Remove an empty [Workspace] from this [StackSet], returning it.
+    ///
+    /// If the workspace is currently visible on a screen then it will be replaced
+    /// by the first available hidden workspace (ordered by id).
+    ///
+    /// # Errors
+    /// This function will error with `UnknownWorkspace` if the tag is not present,
+    /// `NonEmptyWorkspace` if the workspace still contains clients and
+    /// `InsufficientWorkspaces` if removing it would leave a screen without a workspace.
+    pub fn remove_workspace(&mut self, tag: &str) -> Result<Workspace<C>> {
+        let ws = self.workspace(tag).ok_or_else(|| Error::UnknownWorkspace {
+            tag: tag.to_owned(),
+        })?;
+
+        if !ws.is_empty() {
+            return Err(Error::NonEmptyWorkspace {
+                tag: tag.to_owned(),
+            });
+        }
+
+        let removed = match pop_where!(self, hidden, |w: &Workspace<C>| w.tag == tag) {
+            Some(w) => w,
+            None => self.replace_on_screen_workspace(tag)?,
+        };
+
+        self.invisible_tags.retain(|t| t != tag);
+        self.tag_screens.remove(tag);
+        if self.previous_tag == tag {
+            self.previous_tag = self.current_tag().to_owned();
+        }
+
+        Ok(removed)
+    }
+
+    // Swap the visible workspace with the given tag for the lowest id hidden workspace
+    // that is not invisible (and owned by the same screen when using per-screen
+    // workspaces), returning the workspace that was on screen.
+    fn replace_on_screen_workspace(&mut self, tag: &str) -> Result<Workspace<C>> {
+        let owner = self.tag_screens.get(tag);
+        let replacement_id = self
+            .hidden
+            .iter()
+            .filter(|w| !self.invisible_tags.contains(&w.tag))
+            .filter(|w| owner.is_none() || self.tag_screens.get(&w.tag) == owner)
+            .map(|w| w.id)
+            .min();
+
+        let mut replacement = match replacement_id {
+            Some(id) => pop_where!(self, hidden, |w: &Workspace<C>| w.id == id)
+                .expect("replacement workspace to be hidden"),
+            None => {
+                return Err(Error::InsufficientWorkspaces {
+                    n_ws: self.non_hidden_workspaces().count() - 1,
+                    n_screens: self.screens.len(),
+                })
+            }
+        };
+
+        let screen = self
+            .screens
+            .iter_mut()
+            .find(|s| s.workspace.tag == tag)
+            .expect("workspace to be on a screen");
+        swap(&mut screen.workspace, &mut replacement);
+
+        Ok(replacement)
+    }
+
+    /// Rename the [Workspace] with tag `old` to have tag `new`.
+    ///
+    /// # Errors
+    /// This function will error with `UnknownWorkspace` if `old` is not present and
+    /// `NonUniqueTags` if `new` is already in use by another workspace.
+    pub fn rename_workspace(&mut self, old: &str, new: impl Into<String>) -> Result<()> {
+        let new = new.into();
+        if old == new {
+            return Ok(());
+        }
+
+        if self.contains_tag(&new) {
+            return Err(Error::NonUniqueTags { tags: vec![new] });
+        }
+
+        let ws = self
+            .workspace_mut(old)
+            .ok_or_else(|| Error::UnknownWorkspace {
+                tag: old.to_owned(),
+            })?;
+        ws.tag = new.clone();
+
+        for t in self.invisible_tags.iter_mut().filter(|t| *t == old) {
+            *t = new.clone();
+        }
+        if let Some(index) = self.tag_screens.remove(old) {
+            self.tag_screens.insert(new.clone(), index);
+        }
+        if self.previous_tag == old {
+            self.previous_tag = new;
+        }
+
+        Ok(())
+    }
+
     /// A reference to the [Workspace] with a tag of `tag` if there is one
     pub fn workspace(&self, tag: &str) -> Option<&Workspace<C>> {
         self.workspaces().find(|w| w.tag == tag)
@@ -597,6 +1131,65 @@ where
         self.screens.focus_up();
     }
 
+    /// The index of the nearest [Screen] in the given [Direction] from the focused screen,
+    /// based on the position of each screen rather than its index.
+    ///
+    /// Returns `None` if there is no screen in the requested direction.
+    pub fn screen_in_direction(&self, d: Direction) -> Option<usize> {
+        let r = self.screens.focus.r;
+
+        self.screens
+            .iter()
+            .filter(|s| s.index != self.screens.focus.index)
+            .flat_map(|s| r.distance_in_direction(&s.r, d).map(|dist| (dist, s.index)))
+            .min_by_key(|&(dist, _)| dist)
+            .map(|(_, index)| index)
+    }
+
+    /// Move focus to the nearest [Screen] in the given [Direction] (see
+    /// [StackSet::screen_in_direction]).
+    ///
+    /// If there is no screen in the requested direction then the [StackSet] is unmodified.
+    pub fn focus_screen_in_direction(&mut self, d: Direction) {
+        if let Some(index) = self.screen_in_direction(d) {
+            self.update_previous_tag(self.screens.focus.workspace.tag.clone());
+            self.focus_screen(index);
+        }
+    }
+
+    /// The index of the [Screen] showing the primary output as reported by the X server.
+    ///
+    /// If the primary output is not known (or is not currently connected) then this is the
+    /// screen with index 0.
+    pub fn primary_screen(&self) -> usize {
+        self.primary_screen.unwrap_or(0)
+    }
+
+    /// Set the index of the [Screen] showing the primary output.
+    ///
+    /// Passing `None` or the index of a screen that does not exist clears the primary screen.
+    pub fn set_primary_screen(&mut self, index: Option<usize>) {
+        self.primary_screen = index.filter(|&i| i < self.screens.len());
+    }
+
+    /// Move focus to the primary [Screen] (see [StackSet::primary_screen]).
+    pub fn focus_primary_screen(&mut self) {
+        let index = self.primary_screen();
+        if index != self.screens.focus.index {
+            self.update_previous_tag(self.screens.focus.workspace.tag.clone());
+            self.focus_screen(index);
+        }
+    }
+
+    /// The index of the [Screen] identified by the given [ScreenTarget], if it exists.
+    pub fn screen_index_for(&self, target: ScreenTarget) -> Option<usize> {
+        match target {
+            ScreenTarget::Primary => Some(self.primary_screen()),
+            ScreenTarget::Index(index) if index < self.screens.len() => Some(index),
+            ScreenTarget::Index(_) => None,
+        }
+    }
+
     ///Move focus to next tag
     pub fn next_tag(&mut self) {
         let tags = self.ordered_tags();
@@ -630,8 +1223,10 @@ where
     }
 
     /// Drag the focused workspace onto the next [Screen], holding focus
+    ///
+    /// This is a no-op when running with per-screen workspaces.
     pub fn drag_workspace_forward(&mut self) {
-        if self.screens.len() == 1 {
+        if self.screens.len() == 1 || self.is_per_screen() {
             return;
         }
 
@@ -644,8 +1239,10 @@ where
     }
 
     /// Drag the focused workspace onto the previous [Screen], holding focus
+    ///
+    /// This is a no-op when running with per-screen workspaces.
     pub fn drag_workspace_backward(&mut self) {
-        if self.screens.len() == 1 {
+        if self.screens.len() == 1 || self.is_per_screen() {
             return;
         }
 
@@ -657,22 +1254,96 @@ where
         self.previous_tag = true_previous_tag;
     }
 
-    /// If the current [Stack] is [None], return `default` otherwise
-    /// apply the function to it to generate a value
-    pub fn with<T, F>(&self, default: T, f: F) -> T
-    where
-        F: Fn(&Stack<C>) -> T,
-    {
-        self.current_stack().map(f).unwrap_or_else(|| default)
+    /// Swap the [Workspace]s shown on the [Screen]s with the given indices, with focus following
+    /// the focused workspace.
+    ///
+    /// This is a no-op when running with per-screen workspaces or if either screen does not
+    /// exist.
+    pub fn swap_screen_workspaces(&mut self, a: usize, b: usize) {
+        let n = self.screens.len();
+        if a == b || a >= n || b >= n || self.is_per_screen() {
+            return;
+        }
+
+        self.with_focus_following_workspace(|screens| {
+            let (i, j) = (a.min(b), a.max(b));
+            let (left, right) = screens.split_at_mut(j);
+            swap(&mut left[i].workspace, &mut right[0].workspace);
+        });
     }
 
-    /// Apply a function to modify the current [Stack] if there is one
-    /// or compute and inject a default value if it is currently [None]
-    pub fn modify<F>(&mut self, f: F)
-    where
-        F: FnOnce(Option<Stack<C>>) -> Option<Stack<C>>,
-    {
-        self.screens.focus.workspace.stack = f(take(&mut self.screens.focus.workspace.stack));
+    /// Move the [Workspace] shown on each [Screen] to the next screen, with the workspace on
+    /// the last screen moving to the first. Focus follows the focused workspace.
+    ///
+    /// This is a no-op when running with per-screen workspaces.
+    pub fn rotate_screen_workspaces_forward(&mut self) {
+        if self.screens.len() == 1 || self.is_per_screen() {
+            return;
+        }
+
+        self.with_focus_following_workspace(|screens| {
+            for k in 1..screens.len() {
+                let (left, right) = screens.split_at_mut(k);
+                swap(&mut left[0].workspace, &mut right[0].workspace);
+            }
+        });
+    }
+
+    /// Move the [Workspace] shown on each [Screen] to the previous screen, with the workspace
+    /// on the first screen moving to the last. Focus follows the focused workspace.
+    ///
+    /// This is a no-op when running with per-screen workspaces.
+    pub fn rotate_screen_workspaces_backward(&mut self) {
+        if self.screens.len() == 1 || self.is_per_screen() {
+            return;
+        }
+
+        self.with_focus_following_workspace(|screens| {
+            for k in (1..screens.len()).rev() {
+                let (left, right) = screens.split_at_mut(k);
+                swap(&mut left[0].workspace, &mut right[0].workspace);
+            }
+        });
+    }
+
+    // Rearrange the workspaces shown on each screen (passed in index order) and then move
+    // focus to wherever the focused workspace ended up.
+    fn with_focus_following_workspace<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut [&mut Screen<C>]),
+    {
+        let tag = self.screens.focus.workspace.tag.clone();
+        let mut screens: Vec<&mut Screen<C>> = self.screens.iter_mut().collect();
+        screens.sort_by_key(|s| s.index);
+        f(&mut screens);
+
+        let index = self
+            .screens
+            .iter()
+            .find(|s| s.workspace.tag == tag)
+            .map(|s| s.index);
+
+        if let Some(index) = index {
+            self.focus_screen(index);
+        }
+    }
+
+    /// If the current [Stack] is [None], return `default` otherwise
+    /// apply the function to it to generate a value
+    pub fn with<T, F>(&self, default: T, f: F) -> T
+    where
+        F: Fn(&Stack<C>) -> T,
+    {
+        self.current_stack().map(f).unwrap_or_else(|| default)
+    }
+
+    /// Apply a function to modify the current [Stack] if there is one
+    /// or compute and inject a default value if it is currently [None]
+    pub fn modify<F>(&mut self, f: F)
+    where
+        F: FnOnce(Option<Stack<C>>) -> Option<Stack<C>>,
+    {
+        self.screens.focus.workspace.stack = f(take(&mut self.screens.focus.workspace.stack));
     }
 
     /// Apply a function to modify the current [Stack] if it is non-empty
@@ -767,11 +1438,20 @@ impl StackSet<Xid> {
             client_set: self.clone(),
             config: Default::default(),
             extensions: anymap::AnyMap::new(),
+            client_data: Default::default(),
+            published: anymap::AnyMap::new(),
             root: Xid(0),
             mapped: Default::default(),
             pending_unmap: Default::default(),
             current_event: None,
             diff: Default::default(),
+            urgency: Default::default(),
+            focus: Default::default(),
+            layout_overrides: Default::default(),
+            client_timeouts: Default::default(),
+            screen_details: Default::default(),
+            bindings: Default::default(),
+            deferred: Default::default(),
         };
 
         s.visible_client_positions(&crate::x::StubXConn)
@@ -788,10 +1468,13 @@ impl StackSet<Xid> {
 impl StackSet<Xid> {
     /// Record a known client as floating, giving its preferred screen position.
     ///
+    /// The position may be given as an absolute [Rect] or relative to the screen the client
+    /// is on (such as a [MonitorRect][crate::pure::geometry::MonitorRect]).
+    ///
     /// # Errors
     /// This method with return [Error::UnknownClient] if the given client is
     /// not already managed in this stack_set.
-    pub fn float(&mut self, client: Xid, r: Rect) -> Result<()> {
+    pub fn float<R: RelativeTo>(&mut self, client: Xid, r: R) -> Result<()> {
         if !self.contains(&client) {
             return Err(Error::UnknownClient(client));
         }
@@ -823,7 +1506,10 @@ impl StackSet<Xid> {
             // We have more screens now: pull in hidden workspaces to fill them
             // If we run out of workspaces we backfill using generated defaults
             Ordering::Greater => {
-                let padding = self.take_from_hidden(n_new - n_old);
+                for index in n_old..n_new {
+                    self.ensure_per_screen_workspaces(index);
+                }
+                let padding = self.take_for_new_screens(n_new - n_old);
                 for (n, w) in padding.into_iter().enumerate() {
                     self.screens.insert_at(
                         Position::Tail,
@@ -841,11 +1527,19 @@ impl StackSet<Xid> {
             Ordering::Less => {
                 let mut raw = take(&mut self.screens).flatten();
                 let removed = raw.split_off(n_new);
+                let mut detached: Vec<String> =
+                    removed.iter().map(|s| s.workspace.tag.clone()).collect();
+                detached.append(&mut self.detached_tags);
+                self.detached_tags = detached;
                 self.hidden.extend(removed.into_iter().map(|s| s.workspace));
                 self.screens = Stack::from_iter_unchecked(raw);
             }
         }
 
+        if self.primary_screen.is_some_and(|i| i >= n_new) {
+            self.primary_screen = None;
+        }
+
         // self.screens.len() is now correct so update the screen dimensions
         for (s, r) in self.screens.iter_mut().zip(rects) {
             s.r = r;
@@ -854,6 +1548,38 @@ impl StackSet<Xid> {
         Ok(())
     }
 
+    // Workspaces that were showing on screens that have since been disconnected are
+    // returned to those screens when they are reconnected (so long as they have not been
+    // focused elsewhere in the meantime) with any remaining screens being filled from the
+    // hidden workspaces as normal.
+    fn take_for_new_screens(&mut self, n: usize) -> Vec<Workspace<Xid>> {
+        if self.is_per_screen() {
+            self.detached_tags.clear();
+            return self.take_from_hidden(n);
+        }
+
+        let n_detached = n.min(self.detached_tags.len());
+        let mut restored: Vec<Option<Workspace<Xid>>> = self
+            .detached_tags
+            .drain(..n_detached)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|tag| {
+                let ix = self.hidden.iter().position(|w| w.tag == tag)?;
+                self.hidden.remove(ix)
+            })
+            .collect();
+        restored.resize_with(n, || None);
+
+        let n_missing = restored.iter().filter(|w| w.is_none()).count();
+        let mut padding = self.take_from_hidden(n_missing).into_iter();
+
+        restored
+            .into_iter()
+            .flat_map(|w| w.or_else(|| padding.next()))
+            .collect()
+    }
+
     // This is a little fiddly...
     // Rather than hard erroring if we end up with new screens being detected that
     // push us over the number of available workspaces, we pad the workspace set
@@ -861,6 +1587,10 @@ impl StackSet<Xid> {
     // that any _invisible_ workspaces are kept to one side so that they do not end
     // up focused on a screen by mistake.
     fn take_from_hidden(&mut self, n: usize) -> Vec<Workspace<Xid>> {
+        if self.is_per_screen() {
+            return self.take_from_hidden_per_screen(n);
+        }
+
         let next_id = self.workspaces().map(|w| w.id).max().unwrap_or(0) + 1;
         let mut tmp = Vec::with_capacity(self.hidden.len());
         let mut hidden = VecDeque::new();
@@ -894,6 +1624,53 @@ impl StackSet<Xid> {
 
         tmp
     }
+
+    // When running with per-screen workspaces, a newly connected screen needs its own
+    // set of workspaces. Any that are already known (from the screen being connected
+    // previously) are left as they are.
+    fn ensure_per_screen_workspaces(&mut self, index: usize) {
+        if !self.is_per_screen() {
+            return;
+        }
+
+        let mut next_id = self.workspaces().map(|w| w.id).max().unwrap_or(0) + 1;
+        for local in self.screen_local_tags.clone() {
+            let tag = per_screen_tag(index, &local);
+            if self.contains_tag(&tag) {
+                continue;
+            }
+
+            let layouts = self.screens.focus.workspace.layouts.clone();
+            self.tag_screens.insert(tag.clone(), index);
+            self.hidden
+                .push_back(Workspace::new(next_id, tag, layouts, None));
+            next_id += 1;
+        }
+    }
+
+    // Take the lowest id workspace owned by each of the next `n` screens.
+    fn take_from_hidden_per_screen(&mut self, n: usize) -> Vec<Workspace<Xid>> {
+        let n_old = self.screens.len();
+
+        (n_old..n_old + n)
+            .map(|index| {
+                let id = self
+                    .hidden
+                    .iter()
+                    .filter(|w| self.tag_screens.get(&w.tag) == Some(&index))
+                    .map(|w| w.id)
+                    .min()
+                    .expect("per-screen workspaces to have been generated");
+
+                pop_where!(self, hidden, |w: &Workspace<Xid>| w.id == id)
+                    .expect("workspace to be hidden")
+            })
+            .collect()
+    }
+}
+
+fn per_screen_tag(index: usize, tag: &str) -> String {
+    format!("{index}:{tag}")
 }
 
 impl<C> StackSet<C>
@@ -929,6 +1706,43 @@ where
         }
         true
     }
+
+    /// Move focus from the current element up the [Stack], wrapping to the bottom if focus
+    /// is already at the top. Boring clients (see [StackSet::toggle_boring]) are skipped.
+    /// This is a no-op if the current stack is empty.
+    pub fn focus_up(&mut self) {
+        self.cycle_focus(|s| {
+            s.focus_up();
+        })
+    }
+
+    /// Move focus from the current element down the [Stack], wrapping to the top if focus
+    /// is already at the bottom. Boring clients (see [StackSet::toggle_boring]) are skipped.
+    /// This is a no-op if the current stack is empty.
+    pub fn focus_down(&mut self) {
+        self.cycle_focus(|s| {
+            s.focus_down();
+        })
+    }
+
+    // Step focus through the current stack until it lands on a client that is neither a bar
+    // nor boring, or returns to the client that was originally focused.
+    fn cycle_focus(&mut self, step: impl Fn(&mut Stack<C>)) {
+        let (bars, boring) = (&self.bars, &self.boring);
+        let stack = match self.screens.focus.workspace.stack {
+            Some(ref mut stack) => stack,
+            None => return,
+        };
+
+        let start = stack.focus.clone();
+        loop {
+            step(stack);
+            let c = &stack.focus;
+            if *c == start || !(bars.contains_key(c) || boring.contains(c)) {
+                break;
+            }
+        }
+    }
 }
 macro_rules! defer_to_current_stack {
     ($(
@@ -958,14 +1772,6 @@ macro_rules! defer_to_current_stack {
 }
 
 defer_to_current_stack!(
-    /// Move focus from the current element up the [Stack], wrapping to
-    /// the bottom if focus is already at the top.
-    /// This is a no-op if the current stack is empty.
-    focus_up,
-    /// Move focus from the current element down the [Stack], wrapping to
-    /// the top if focus is already at the bottom.
-    /// This is a no-op if the current stack is empty.
-    focus_down,
     /// Swap the position of the focused element with one above it.
     /// The currently focused element is maintained by this operation.
     /// This is a no-op if the current stack is empty.
@@ -982,6 +1788,14 @@ defer_to_current_stack!(
     /// The currently focused position in the stack is maintained by this operation.
     /// This is a no-op if the current stack is empty.
     rotate_down,
+    /// Rotate all elements of the stack forward, wrapping from top to bottom.
+    /// The currently focused position in the stack is maintained by this operation.
+    /// This is a no-op if the current stack is empty.
+    rotate_contents_up,
+    /// Rotate all elements of the stack back, wrapping from bottom to top.
+    /// The currently focused position in the stack is maintained by this operation.
+    /// This is a no-op if the current stack is empty.
+    rotate_contents_down,
     /// Rotate the Stack until the current focused element is in the head position.
     /// This is a no-op if the current stack is empty.
     rotate_focus_to_head,
@@ -997,6 +1811,7 @@ defer_to_current_stack!(
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::pure::geometry::MonitorRect;
     use simple_test_case::test_case;
 
     fn _test_stack_set<C>(n_tags: usize, n_screens: usize) -> StackSet<C>
@@ -1242,6 +2057,56 @@ pub mod tests {
         assert_eq!(s.screens.focus.index, expected_index);
     }
 
+    fn tags_by_screen<C: Clone + Eq + Hash>(s: &StackSet<C>) -> Vec<&str> {
+        let mut screens: Vec<_> = s.screens().collect();
+        screens.sort_by_key(|s| s.index);
+
+        screens.iter().map(|s| s.workspace.tag.as_str()).collect()
+    }
+
+    #[test_case(0, 2, vec!["3", "2", "1"], 2; "focused screen")]
+    #[test_case(2, 0, vec!["3", "2", "1"], 2; "argument order does not matter")]
+    #[test_case(1, 2, vec!["1", "3", "2"], 0; "unfocused screens")]
+    #[test_case(1, 1, vec!["1", "2", "3"], 0; "same screen")]
+    #[test_case(0, 3, vec!["1", "2", "3"], 0; "missing screen")]
+    #[test]
+    fn swap_screen_workspaces(a: usize, b: usize, expected: Vec<&str>, expected_index: usize) {
+        let mut s = test_stack_set(5, 3);
+        s.swap_screen_workspaces(a, b);
+
+        assert_eq!(tags_by_screen(&s), expected);
+        assert_eq!(s.current_tag(), "1");
+        assert_eq!(s.screens.focus.index, expected_index);
+    }
+
+    #[test_case(true, vec!["3", "1", "2"], 1; "forward")]
+    #[test_case(false, vec!["2", "3", "1"], 2; "backward")]
+    #[test]
+    fn rotate_screen_workspaces(forward: bool, expected: Vec<&str>, expected_index: usize) {
+        let mut s = test_stack_set(5, 3);
+        s.previous_tag = "PREVIOUS".to_owned();
+
+        if forward {
+            s.rotate_screen_workspaces_forward();
+        } else {
+            s.rotate_screen_workspaces_backward();
+        }
+
+        assert_eq!(tags_by_screen(&s), expected);
+        assert_eq!(s.current_tag(), "1");
+        assert_eq!(s.screens.focus.index, expected_index);
+        assert_eq!(s.previous_tag, "PREVIOUS");
+    }
+
+    #[test]
+    fn swapping_screen_workspaces_is_a_no_op_with_per_screen_workspaces() {
+        let mut s = per_screen_stack_set(2);
+        s.swap_screen_workspaces(0, 1);
+        s.rotate_screen_workspaces_forward();
+
+        assert_eq!(tags_by_screen(&s), vec!["0:1", "1:1"]);
+    }
+
     #[test_case(1, true; "single screen forward")]
     #[test_case(1, false; "single screen backward")]
     #[test_case(2, true; "two screens forward")]
@@ -1357,6 +2222,509 @@ pub mod tests {
         assert_eq!(ss.workspaces().count(), expected);
     }
 
+    #[test_case("1", &["3", "2"], &["2", "3", "4", "5"]; "focused workspace")]
+    #[test_case("2", &["1", "3"], &["1", "3", "4", "5"]; "visible on other screen")]
+    #[test_case("4", &["1", "2"], &["1", "2", "3", "5"]; "hidden")]
+    #[test]
+    fn remove_workspace(tag: &str, visible: &[&str], remaining: &[&str]) {
+        let mut s = test_stack_set(5, 2);
+
+        let removed = s.remove_workspace(tag).expect("workspace to be removed");
+        let visible_tags: Vec<&str> = s.screens().map(|s| s.workspace.tag.as_ref()).collect();
+
+        assert_eq!(removed.tag, tag);
+        assert_eq!(visible_tags, visible);
+        assert_eq!(s.ordered_tags(), remaining);
+    }
+
+    #[test]
+    fn remove_workspace_with_clients_is_an_error() {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!(1)), None], 1);
+
+        let res = s.remove_workspace("1");
+
+        assert!(matches!(res, Err(Error::NonEmptyWorkspace { .. })));
+        assert!(s.contains_tag("1"));
+    }
+
+    #[test]
+    fn remove_workspace_needed_for_a_screen_is_an_error() {
+        let mut s = test_stack_set(2, 2);
+
+        let res = s.remove_workspace("1");
+
+        assert!(matches!(res, Err(Error::InsufficientWorkspaces { .. })));
+        assert!(s.contains_tag("1"));
+    }
+
+    #[test]
+    fn remove_unknown_workspace_is_an_error() {
+        let mut s = test_stack_set(2, 1);
+
+        let res = s.remove_workspace("unknown");
+
+        assert!(matches!(res, Err(Error::UnknownWorkspace { .. })));
+    }
+
+    #[test]
+    fn rename_workspace_updates_previous_tag() {
+        let mut s = test_stack_set(5, 1);
+        s.focus_tag("3");
+
+        s.rename_workspace("1", "one").expect("rename to succeed");
+        s.toggle_tag();
+
+        assert_eq!(s.current_tag(), "one");
+        assert!(!s.contains_tag("1"));
+    }
+
+    #[test_case("2"; "existing tag")]
+    #[test_case("unknown"; "unknown tag")]
+    #[test]
+    fn rename_workspace_errors(target: &str) {
+        let mut s = test_stack_set(5, 1);
+        let (from, to) = if target == "unknown" {
+            (target, "new")
+        } else {
+            ("1", target)
+        };
+
+        assert!(s.rename_workspace(from, to).is_err());
+        assert_eq!(s.ordered_tags(), vec!["1", "2", "3", "4", "5"]);
+    }
+
+    // 0 1
+    // 2
+    fn l_shaped_stack_set() -> StackSet<Xid> {
+        let screens = vec![
+            Rect::new(0, 0, 1920, 1080),
+            Rect::new(1920, 0, 2560, 1440),
+            Rect::new(0, 1080, 1920, 1080),
+        ];
+
+        StackSet::try_new(LayoutStack::default(), ["1", "2", "3", "4"], screens).unwrap()
+    }
+
+    #[test]
+    fn floating_positions_are_relative_to_the_clients_screen() {
+        let mut s = l_shaped_stack_set();
+        s.focus_screen(1);
+        s.insert(Xid(1));
+        s.float(Xid(1), MonitorRect::new(100, 100, 400, 300))
+            .unwrap();
+        s.focus_screen(0);
+
+        assert_eq!(s.sink(&Xid(1)), Some(Rect::new(2020, 100, 400, 300)));
+    }
+
+    #[test_case(0, Direction::Right, Some(1); "right")]
+    #[test_case(0, Direction::Down, Some(2); "down")]
+    #[test_case(0, Direction::Left, None; "nothing left")]
+    #[test_case(0, Direction::Up, None; "nothing up")]
+    #[test_case(1, Direction::Left, Some(0); "prefers in line screen")]
+    #[test_case(2, Direction::Up, Some(0); "up")]
+    #[test_case(2, Direction::Right, Some(1); "diagonal")]
+    #[test]
+    fn screen_in_direction(focused: usize, d: Direction, expected: Option<usize>) {
+        let mut s = l_shaped_stack_set();
+        s.focus_screen(focused);
+
+        assert_eq!(s.screen_in_direction(d), expected);
+    }
+
+    #[test]
+    fn focus_screen_in_direction_updates_previous_tag() {
+        let mut s = l_shaped_stack_set();
+        s.focus_screen_in_direction(Direction::Down);
+
+        assert_eq!(s.current_screen().index(), 2);
+        assert_eq!(s.current_tag(), "3");
+
+        s.toggle_tag();
+        assert_eq!(s.current_tag(), "1");
+    }
+
+    #[test_case(None, 0; "unknown")]
+    #[test_case(Some(2), 2; "known")]
+    #[test_case(Some(5), 0; "missing screen")]
+    #[test]
+    fn focus_primary_screen(primary: Option<usize>, expected: usize) {
+        let mut s = test_xid_stack_set(5, 3);
+        s.focus_screen(1);
+        s.set_primary_screen(primary);
+        s.focus_primary_screen();
+
+        assert_eq!(s.primary_screen(), expected);
+        assert_eq!(s.current_screen().index(), expected);
+        assert_eq!(s.previous_tag, "2");
+    }
+
+    #[test_case(ScreenTarget::Primary, Some(1); "primary")]
+    #[test_case(ScreenTarget::Index(2), Some(2); "index")]
+    #[test_case(ScreenTarget::Index(3), None; "missing index")]
+    #[test]
+    fn screen_index_for(target: ScreenTarget, expected: Option<usize>) {
+        let mut s = test_xid_stack_set(5, 3);
+        s.set_primary_screen(Some(1));
+
+        assert_eq!(s.screen_index_for(target), expected);
+    }
+
+    #[test]
+    fn removing_the_primary_screen_clears_it() {
+        let mut s = test_xid_stack_set(5, 3);
+        s.set_primary_screen(Some(2));
+        s.update_screens(vec![Rect::new(0, 0, 1000, 1000)]).unwrap();
+
+        assert_eq!(s.primary_screen, None);
+        assert_eq!(s.primary_screen(), 0);
+    }
+
+    fn per_screen_stack_set(n_screens: usize) -> StackSet<Xid> {
+        let screens: Vec<Rect> = (0..(n_screens as u32))
+            .map(|k| Rect::new(k * 1000, 0, 1000, 2000))
+            .collect();
+
+        StackSet::try_new_per_screen(LayoutStack::default(), ["1", "2", "3"], screens).unwrap()
+    }
+
+    #[test]
+    fn per_screen_workspaces_start_on_their_own_screen() {
+        let s = per_screen_stack_set(2);
+
+        assert_eq!(s.tag_for_screen(0), Some("0:1"));
+        assert_eq!(s.tag_for_screen(1), Some("1:1"));
+        assert_eq!(s.workspaces().count(), 6);
+    }
+
+    #[test_case("0:2", 0, &["0:2", "1:1"]; "hidden tag owned by current screen")]
+    #[test_case("1:1", 1, &["0:1", "1:1"]; "visible tag owned by other screen")]
+    #[test_case("1:3", 1, &["0:1", "1:3"]; "hidden tag owned by other screen")]
+    #[test]
+    fn per_screen_focus_tag_stays_on_owning_screen(tag: &str, screen: usize, visible: &[&str]) {
+        let mut s = per_screen_stack_set(2);
+
+        s.focus_tag(tag);
+        let mut visible_tags: Vec<&str> = s.screens().map(|s| s.workspace.tag.as_ref()).collect();
+        visible_tags.sort();
+
+        assert_eq!(s.current_tag(), tag);
+        assert_eq!(s.current_screen().index(), screen);
+        assert_eq!(visible_tags, visible);
+    }
+
+    #[test]
+    fn per_screen_pull_tag_to_screen_does_not_move_tags() {
+        let mut s = per_screen_stack_set(2);
+
+        s.pull_tag_to_screen("1:2");
+
+        assert_eq!(s.tag_for_screen(0), Some("0:1"));
+        assert_eq!(s.tag_for_screen(1), Some("1:2"));
+    }
+
+    #[test_case(0, Some("0:2"); "first screen")]
+    #[test_case(1, Some("1:2"); "second screen")]
+    #[test]
+    fn tag_for_current_screen(screen: usize, expected: Option<&str>) {
+        let mut s = per_screen_stack_set(2);
+        s.focus_screen(screen);
+
+        assert_eq!(s.tag_for_current_screen(1).as_deref(), expected);
+        assert_eq!(s.tag_for_current_screen(3), None);
+    }
+
+    #[test]
+    fn per_screen_update_screens_generates_workspaces_for_new_screens() {
+        let mut s = per_screen_stack_set(1);
+
+        s.update_screens(vec![Rect::default(); 2]).unwrap();
+
+        assert_eq!(s.tag_for_screen(1), Some("1:1"));
+        assert_eq!(s.screen_for_tag("1:3"), Some(1));
+        assert_eq!(s.workspaces().count(), 6);
+    }
+
+    #[test_case(1, Position::Tail, stack!([2], 3, [4, 5, 1]); "unfocused to tail")]
+    #[test_case(5, Position::Head, stack!([5, 1, 2], 3, [4]); "unfocused to head")]
+    #[test_case(3, Position::After, stack!([1, 2], 4, [3, 5]); "focused after new focus")]
+    #[test_case(3, Position::Focus, stack!([1, 2], 3, [4, 5]); "focused to focus")]
+    #[test_case(9, Position::Head, stack!([1, 2], 3, [4, 5]); "unknown client")]
+    #[test]
+    fn move_client_to_position(client: u8, pos: Position, expected: Stack<u8>) {
+        let mut s = test_stack_set(5, 1);
+        s.modify(|_| Some(stack!([1, 2], 3, [4, 5])));
+
+        s.move_client_to_position(&client, pos);
+
+        assert_eq!(s.current_stack(), Some(&expected));
+    }
+
+    #[test]
+    fn set_transient_for_ignores_cycles() {
+        let mut s = test_stack_set(5, 1);
+        s.set_transient_for(2, 1, false);
+        s.set_transient_for(3, 2, false);
+
+        s.set_transient_for(1, 3, false);
+        s.set_transient_for(4, 4, false);
+
+        assert_eq!(s.transient_parent(&1), None);
+        assert_eq!(s.transient_parent(&4), None);
+        assert_eq!(s.transient_parent(&3), Some(&2));
+    }
+
+    #[test]
+    fn kill_all_on_current_workspace_only_kills_current_clients() {
+        let mut s = test_stack_set(5, 1);
+        for n in 1..=4 {
+            s.insert(n);
+        }
+        s.move_client_to_tag(&4, "2");
+
+        s.kill_all_on_current_workspace();
+        s.killed_clients.sort();
+
+        assert_eq!(s.killed_clients, vec![1, 2, 3]);
+        assert!(s.current_workspace().is_empty());
+        assert!(s.contains(&4));
+    }
+
+    #[test]
+    fn sync_transients_kills_transients_of_removed_clients() {
+        let mut s = test_stack_set(5, 1);
+        for n in 1..=3 {
+            s.insert(n);
+        }
+        s.set_transient_for(2, 1, false);
+
+        s.remove_client(&1);
+        s.sync_transients();
+
+        assert!(!s.contains(&2));
+        assert_eq!(s.killed_clients, vec![2]);
+        assert_eq!(s.transient_parent(&2), None);
+    }
+
+    #[test]
+    fn sync_transients_moves_transients_with_their_parent() {
+        let mut s = test_stack_set(5, 1);
+        for n in 1..=3 {
+            s.insert(n);
+        }
+        s.set_transient_for(2, 1, false);
+
+        s.move_client_to_tag(&1, "3");
+        s.sync_transients();
+
+        assert_eq!(s.tag_for_client(&2), Some("3"));
+        assert_eq!(s.tag_for_client(&3), Some("1"));
+    }
+
+    #[test_case(true, 2; "modal")]
+    #[test_case(false, 1; "not modal")]
+    #[test]
+    fn sync_transients_focuses_modal_transients(modal: bool, expected: u8) {
+        let mut s = test_stack_set(5, 1);
+        for n in 1..=3 {
+            s.insert(n);
+        }
+        s.set_transient_for(2, 1, modal);
+
+        s.focus_client(&1);
+        s.sync_transients();
+
+        assert_eq!(s.current_client(), Some(&expected));
+    }
+
+    #[test]
+    fn raise_transients_stacks_transients_above_their_parent() {
+        let mut s = test_stack_set(5, 1);
+        s.set_transient_for(1, 3, false);
+        s.set_transient_for(2, 3, false);
+        let r = Rect::default();
+        let mut positions = vec![(1, r), (2, r), (3, r), (4, r)];
+
+        s.raise_transients(&mut positions);
+        let ids: Vec<u8> = positions.iter().map(|&(id, _)| id).collect();
+
+        assert_eq!(ids, vec![3, 1, 2, 4]);
+    }
+
+    #[test_case(2, 4, stack!([1, 4], 3, [2, 5]), stack!(6, [7]); "same workspace")]
+    #[test_case(3, 4, stack!([1, 2, 4], 3, [5]), stack!(6, [7]); "focus follows client")]
+    #[test_case(3, 7, stack!([1, 2], 7, [4, 5]), stack!([6], 3); "across workspaces")]
+    #[test_case(3, 42, stack!([1, 2], 3, [4, 5]), stack!(6, [7]); "unknown client")]
+    #[test_case(3, 3, stack!([1, 2], 3, [4, 5]), stack!(6, [7]); "same client")]
+    #[test]
+    fn swap_clients(a: u8, b: u8, expected_1: Stack<u8>, expected_2: Stack<u8>) {
+        let mut s = test_stack_set_with_stacks(
+            vec![Some(stack!([1, 2], 3, [4, 5])), Some(stack!(6, [7]))],
+            1,
+        );
+        s.swap_clients(&a, &b);
+
+        let stack_for = |tag: &str| s.workspaces().find(|w| w.tag == tag).unwrap().stack.clone();
+
+        assert_eq!(stack_for("1"), Some(expected_1));
+        assert_eq!(stack_for("2"), Some(expected_2));
+        assert_eq!(s.current_client(), Some(&3));
+    }
+
+    #[test_case(None, None; "empty stack")]
+    #[test_case(Some(stack!(1)), Some(stack!(1)); "only focused")]
+    #[test_case(Some(stack!([1, 2], 3, [4])), Some(stack!(3, [2, 1, 4])); "focus in stack")]
+    #[test_case(Some(stack!([1], 2)), Some(stack!(2, [1])); "focus at bottom")]
+    #[test_case(Some(stack!(1, [2, 3])), Some(stack!(2, [1, 3])); "focus already main")]
+    #[test]
+    fn swap_focused_with_main(stack: Option<Stack<u8>>, expected: Option<Stack<u8>>) {
+        let mut s = test_stack_set_with_stacks(vec![stack], 1);
+        s.swap_focused_with_main();
+
+        assert_eq!(s.screens.focus.workspace.stack, expected);
+    }
+
+    #[test_case(None, None, None, None; "empty stack")]
+    #[test_case(Some(stack!(1)), Some(2), Some(stack!(1)), None; "only focused")]
+    #[test_case(Some(stack!([1, 2], 3, [4])), None, Some(stack!(3, [2, 1, 4])), Some(1); "focus in stack")]
+    #[test_case(Some(stack!(1, [2, 3, 4])), Some(3), Some(stack!(3, [2, 1, 4])), Some(1); "focus main with previous")]
+    #[test_case(Some(stack!(1, [2, 3])), Some(9), Some(stack!(2, [1, 3])), Some(1); "focus main with unknown previous")]
+    #[test_case(Some(stack!(1, [2, 3])), None, Some(stack!(2, [1, 3])), Some(1); "focus main without previous")]
+    #[test]
+    fn swap_with_main(
+        stack: Option<Stack<u8>>,
+        previous: Option<u8>,
+        expected: Option<Stack<u8>>,
+        displaced: Option<u8>,
+    ) {
+        let mut s = test_stack_set_with_stacks(vec![stack], 1);
+        let res = s.swap_with_main(previous.as_ref());
+
+        assert_eq!(s.screens.focus.workspace.stack, expected);
+        assert_eq!(res, displaced);
+    }
+
+    #[test]
+    fn swap_with_main_toggles_between_last_two_main_clients() {
+        let mut s = test_stack_set_with_stacks(vec![Some(stack!([1, 2], 3, [4]))], 1);
+
+        let prev = s.swap_with_main(None);
+        assert_eq!(s.current_client(), Some(&3));
+        let prev = s.swap_with_main(prev.as_ref());
+        assert_eq!(s.current_client(), Some(&1));
+        s.swap_with_main(prev.as_ref());
+        assert_eq!(s.current_client(), Some(&3));
+        assert_eq!(s.current_stack().unwrap().head(), &3);
+    }
+
+    #[test_case(Xid(1), true; "known client")]
+    #[test_case(Xid(42), false; "unknown client")]
+    #[test]
+    fn toggle_maximized(id: Xid, expected: bool) {
+        let mut s = test_xid_stack_set(1, 1);
+        s.insert(Xid(1));
+
+        assert_eq!(s.toggle_maximized(&id), expected);
+        assert_eq!(s.is_maximized(&id), expected);
+        assert!(!s.toggle_maximized(&id));
+        assert!(!s.is_maximized(&id));
+    }
+
+    #[test]
+    fn removing_a_client_clears_maximized_state() {
+        let mut s = test_xid_stack_set(1, 1);
+        s.insert(Xid(1));
+        s.toggle_maximized(&Xid(1));
+
+        s.remove_client(&Xid(1));
+
+        assert!(!s.is_maximized(&Xid(1)));
+    }
+
+    #[test_case(&[], true, Xid(3); "none boring down")]
+    #[test_case(&[], false, Xid(1); "none boring up")]
+    #[test_case(&[3], true, Xid(2); "skip one down")]
+    #[test_case(&[1], false, Xid(2); "skip one up wrapping")]
+    #[test_case(&[2, 3], true, Xid(1); "skip several down wrapping")]
+    #[test_case(&[1, 2, 3], true, Xid(4); "all others boring")]
+    #[test]
+    fn focus_cycling_skips_boring_clients(boring: &[u32], down: bool, expected: Xid) {
+        let mut s = test_xid_stack_set(1, 1);
+        for n in 1..=4 {
+            s.insert(Xid(n));
+        }
+        for &n in boring {
+            s.mark_boring(&Xid(n));
+        }
+
+        if down {
+            s.focus_down();
+        } else {
+            s.focus_up();
+        }
+
+        assert_eq!(s.current_client(), Some(&expected));
+    }
+
+    #[test]
+    fn boring_clients_can_be_focused_directly() {
+        let mut s = test_xid_stack_set(1, 1);
+        for n in 1..=3 {
+            s.insert(Xid(n));
+        }
+        s.toggle_boring(&Xid(1));
+
+        s.focus_client(&Xid(1));
+
+        assert_eq!(s.current_client(), Some(&Xid(1)));
+    }
+
+    #[test]
+    fn boring_clients_can_be_toggled_and_restored() {
+        let mut s = test_xid_stack_set(1, 1);
+        s.insert(Xid(1));
+        s.insert(Xid(2));
+
+        assert!(s.toggle_boring(&Xid(1)));
+        assert!(!s.toggle_boring(&Xid(42)), "unknown client");
+
+        let boring = s.take_boring();
+        s.focus_down();
+        assert_eq!(s.current_client(), Some(&Xid(1)));
+
+        s.remove_client(&Xid(1));
+        s.restore_boring(boring);
+        assert!(!s.is_boring(&Xid(1)), "removed clients are not restored");
+    }
+
+    #[test_case(None, vec!["1", "2", "3"]; "detached workspaces restored")]
+    #[test_case(Some("2"), vec!["2", "1", "3"]; "workspace focused elsewhere")]
+    #[test_case(Some("4"), vec!["4", "2", "3"]; "hidden workspace focused")]
+    #[test]
+    fn update_screens_restores_workspaces_when_reconnecting(
+        focus_while_detached: Option<&str>,
+        tags_after: Vec<&str>,
+    ) {
+        let mut ss: StackSet<Xid> = StackSet::try_new(
+            LayoutStack::default(),
+            ["1", "2", "3", "4", "5"],
+            vec![Rect::default(); 3],
+        )
+        .expect("enough workspaces to cover the number of initial screens");
+
+        ss.update_screens(vec![Rect::default()]).unwrap();
+        assert_eq!(focused_tags(&ss), vec!["1"]);
+
+        if let Some(tag) = focus_while_detached {
+            ss.focus_tag(tag);
+        }
+
+        ss.update_screens(vec![Rect::default(); 3]).unwrap();
+
+        assert_eq!(focused_tags(&ss), tags_after);
+        assert!(ss.detached_tags.is_empty());
+    }
+
     #[test]
     fn update_screens_with_empty_vec_is_an_error() {
         let mut ss: StackSet<Xid> =
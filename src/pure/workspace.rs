@@ -14,6 +14,8 @@ pub struct Workspace<T> {
     pub(crate) tag: String,
     pub(crate) layouts: LayoutStack,
     pub(crate) stack: Option<Stack<T>>,
+    pub(crate) gaps: bool,
+    pub(crate) borders: bool,
 }
 
 impl<T> Default for Workspace<T> {
@@ -23,6 +25,8 @@ impl<T> Default for Workspace<T> {
             tag: Default::default(),
             layouts: Default::default(),
             stack: Default::default(),
+            gaps: true,
+            borders: true,
         }
     }
 }
@@ -54,6 +58,8 @@ impl<T> Workspace<T> {
             tag: tag.into(),
             layouts,
             stack,
+            gaps: true,
+            borders: true,
         }
     }
 
@@ -139,6 +145,43 @@ impl<T> Workspace<T> {
         layouts
     }
 
+    /// Whether or not gaps are applied to the clients on this workspace.
+    pub fn gaps_enabled(&self) -> bool {
+        self.gaps
+    }
+
+    /// Enable or disable the gaps applied to clients on this workspace by any [Gaps][0]
+    /// transformers in its layouts.
+    ///
+    /// The change is applied the next time the layout for this workspace is run.
+    ///
+    ///   [0]: crate::builtin::layout::transformers::Gaps
+    pub fn set_gaps_enabled(&mut self, enabled: bool) {
+        self.gaps = enabled;
+    }
+
+    /// Toggle whether or not gaps are applied to the clients on this workspace.
+    pub fn toggle_gaps(&mut self) {
+        self.set_gaps_enabled(!self.gaps);
+    }
+
+    /// Whether or not borders are drawn for the clients on this workspace.
+    pub fn borders_enabled(&self) -> bool {
+        self.borders
+    }
+
+    /// Enable or disable drawing borders for the clients on this workspace. When disabled,
+    /// this takes precedence over both the configured border width and any border width
+    /// requested by the active layout.
+    pub fn set_borders_enabled(&mut self, enabled: bool) {
+        self.borders = enabled;
+    }
+
+    /// Toggle whether or not borders are drawn for the clients on this workspace.
+    pub fn toggle_borders(&mut self) {
+        self.borders = !self.borders;
+    }
+
     /// Attempt to set the active [Layout][0] by name if it is available.
     ///
     /// > Note that some layouts have a dynamically set name and this method will fail to
@@ -0,0 +1,542 @@
+//! An in memory [XConn] implementation that does not require a running X server.
+use crate::{
+    core::bindings::{KeyCode, Keymap, MouseState},
+    pure::geometry::{Point, Rect},
+    testing::headless_keycodes,
+    x::{
+        atom::Atom,
+        event::{ClientMessage, XEvent},
+        property::{MapState, Prop, WindowAttributes, WindowClass, WmState},
+        ClientAttr, ClientConfig, Output, OutputConfig, XConn,
+    },
+    Color, Error, Result, Xid,
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, VecDeque},
+};
+
+/// The geometry given to newly created windows.
+pub const DEFAULT_WINDOW_GEOMETRY: Rect = Rect::new(0, 0, 640, 480);
+
+const ROOT: Xid = Xid(0);
+const FIRST_ATOM: u32 = 1;
+const FIRST_WINDOW: u32 = 0x100000;
+
+/// The state of a window known to a [HeadlessConn].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadlessWindow {
+    /// The current geometry of the window
+    pub geometry: Rect,
+    /// Whether or not the window is currently mapped
+    pub mapped: bool,
+    /// Whether or not the window bypasses the window manager
+    pub override_redirect: bool,
+    /// The ICCCM WM_STATE of the window
+    pub wm_state: Option<WmState>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    screens: Vec<Rect>,
+    outputs: Vec<Output>,
+    windows: BTreeMap<Xid, HeadlessWindow>,
+    props: HashMap<Xid, HashMap<String, Prop>>,
+    atoms: Vec<String>,
+    focus: Option<Xid>,
+    pointer: Point,
+    events: VecDeque<XEvent>,
+    sent_messages: Vec<ClientMessage>,
+    outline: Option<Rect>,
+    pointer_grabbed: bool,
+    grabbed_keys: Vec<KeyCode>,
+    wm_selection: bool,
+    keymap: Option<Keymap>,
+    keyboard_layouts: Vec<String>,
+    keyboard_layout: u8,
+    next_id: u32,
+}
+
+/// An [XConn] that holds all X server state in memory.
+///
+/// Windows are created by the test code driving the window manager (see
+/// [HeadlessConn::create_window]) and the X requests made by the window manager are applied to
+/// this in memory state. Where a real X server would generate an event in response to a
+/// request that the window manager relies on (such as `UnmapNotify` when unmapping a window or
+/// `DestroyNotify` when a client is killed), the event is queued and can be retrieved using
+/// [HeadlessConn::pop_event].
+#[derive(Debug)]
+pub struct HeadlessConn {
+    inner: RefCell<Inner>,
+}
+
+impl Default for HeadlessConn {
+    fn default() -> Self {
+        Self::new(vec![Rect::new(0, 0, 1920, 1080)])
+    }
+}
+
+impl HeadlessConn {
+    /// Create a new connection with the given screens.
+    pub fn new(screens: Vec<Rect>) -> Self {
+        Self {
+            inner: RefCell::new(Inner {
+                screens,
+                next_id: FIRST_WINDOW,
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Create a new unmapped window with the given WM_CLASS, using it as the window title as
+    /// well.
+    ///
+    /// The window is not managed until a `MapRequest` event for it is handled.
+    pub fn create_window(&self, class: &str) -> Xid {
+        let mut inner = self.inner.borrow_mut();
+        let id = Xid(inner.next_id);
+        inner.next_id += 1;
+
+        inner.windows.insert(
+            id,
+            HeadlessWindow {
+                geometry: DEFAULT_WINDOW_GEOMETRY,
+                mapped: false,
+                override_redirect: false,
+                wm_state: None,
+            },
+        );
+
+        let class_prop = Prop::UTF8String(vec![class.to_string(), class.to_string()]);
+        let name_prop = Prop::UTF8String(vec![class.to_string()]);
+        let props = inner.props.entry(id).or_default();
+        props.insert(Atom::WmClass.as_ref().to_string(), class_prop);
+        props.insert(Atom::WmName.as_ref().to_string(), name_prop.clone());
+        props.insert(Atom::NetWmName.as_ref().to_string(), name_prop);
+
+        id
+    }
+
+    /// Remove a window as if its client had closed it, queueing the events that the X server
+    /// would send to the window manager.
+    pub fn destroy_window(&self, id: Xid) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(w) = inner.windows.remove(&id) {
+            if w.mapped {
+                inner.events.push_back(XEvent::UnmapNotify(id));
+            }
+            inner.events.push_back(XEvent::Destroy(id));
+        }
+        inner.props.remove(&id);
+        if inner.focus == Some(id) {
+            inner.focus = None;
+        }
+    }
+
+    /// Replace the connected screens, queueing a RandR notification as the X server would when
+    /// a monitor is plugged in or unplugged.
+    pub fn set_screens(&self, screens: Vec<Rect>) {
+        let mut inner = self.inner.borrow_mut();
+        inner.screens = screens;
+        inner.events.push_back(XEvent::RandrNotify);
+    }
+
+    /// Replace the known outputs, queueing a RandR notification.
+    ///
+    /// The connected screens are set to the regions shown on each of the enabled outputs. By
+    /// default a [HeadlessConn] has no outputs and its screens can not be configured using
+    /// [XConn::configure_outputs].
+    pub fn set_outputs(&self, outputs: Vec<Output>) {
+        let mut inner = self.inner.borrow_mut();
+        inner.screens = outputs.iter().flat_map(|o| o.r).collect();
+        inner.outputs = outputs;
+        inner.events.push_back(XEvent::RandrNotify);
+    }
+
+    /// Replace the keyboard mapping, queueing a `MappingNotify` event as the X server would
+    /// after running `setxkbmap` or `xmodmap`.
+    ///
+    /// By default the keymap is built from [headless_keycodes].
+    pub fn set_keymap(&self, keymap: Keymap) {
+        let mut inner = self.inner.borrow_mut();
+        inner.keymap = Some(keymap);
+        inner.events.push_back(XEvent::MappingNotify);
+    }
+
+    /// Replace the configured keyboard layouts, resetting the active layout to the first one
+    /// and queueing a `MappingNotify` event as the X server would after running `setxkbmap`.
+    ///
+    /// By default a [HeadlessConn] has no keyboard layouts.
+    pub fn set_keyboard_layouts(&self, names: Vec<String>) {
+        let mut inner = self.inner.borrow_mut();
+        inner.keyboard_layouts = names;
+        inner.keyboard_layout = 0;
+        inner.events.push_back(XEvent::MappingNotify);
+    }
+
+    /// Switch the active keyboard layout as if the user had used an XKB shortcut, queueing a
+    /// `KeyboardLayoutChange` event.
+    pub fn switch_keyboard_layout(&self, group: u8) {
+        let mut inner = self.inner.borrow_mut();
+        inner.keyboard_layout = group;
+        inner.events.push_back(XEvent::KeyboardLayoutChange(group));
+    }
+
+    /// The current state of the given window.
+    pub fn window(&self, id: Xid) -> Option<HeadlessWindow> {
+        self.inner.borrow().windows.get(&id).cloned()
+    }
+
+    /// The ids of all windows that currently exist.
+    pub fn windows(&self) -> Vec<Xid> {
+        self.inner.borrow().windows.keys().copied().collect()
+    }
+
+    /// The window that currently has input focus, if any.
+    pub fn focused(&self) -> Option<Xid> {
+        self.inner.borrow().focus
+    }
+
+    /// The client messages sent by the window manager so far.
+    pub fn sent_messages(&self) -> Vec<ClientMessage> {
+        self.inner.borrow().sent_messages.clone()
+    }
+
+    /// The region currently outlined using [XConn::show_outline], if any.
+    pub fn outline(&self) -> Option<Rect> {
+        self.inner.borrow().outline
+    }
+
+    /// Whether or not the pointer is currently grabbed using [XConn::grab_pointer].
+    pub fn pointer_grabbed(&self) -> bool {
+        self.inner.borrow().pointer_grabbed
+    }
+
+    /// The key codes grabbed by the most recent call to [XConn::grab].
+    pub fn grabbed_keys(&self) -> Vec<KeyCode> {
+        self.inner.borrow().grabbed_keys.clone()
+    }
+
+    /// Whether or not the window manager currently owns the manager selection (see
+    /// [XConn::acquire_wm_selection]).
+    pub fn owns_wm_selection(&self) -> bool {
+        self.inner.borrow().wm_selection
+    }
+
+    /// Queue an event to be returned by [HeadlessConn::pop_event].
+    pub fn push_event(&self, event: XEvent) {
+        self.inner.borrow_mut().events.push_back(event);
+    }
+
+    /// Remove the next queued event, if there is one.
+    pub fn pop_event(&self) -> Option<XEvent> {
+        self.inner.borrow_mut().events.pop_front()
+    }
+
+    fn with_window<F>(&self, id: Xid, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut HeadlessWindow),
+    {
+        match self.inner.borrow_mut().windows.get_mut(&id) {
+            Some(w) => {
+                f(w);
+                Ok(())
+            }
+            None => Err(Error::UnknownClient(id)),
+        }
+    }
+
+    fn check_known(&self, id: Xid) -> Result<()> {
+        if id == ROOT || self.inner.borrow().windows.contains_key(&id) {
+            Ok(())
+        } else {
+            Err(Error::UnknownClient(id))
+        }
+    }
+}
+
+impl XConn for HeadlessConn {
+    fn root(&self) -> Xid {
+        ROOT
+    }
+
+    fn screen_details(&self) -> Result<Vec<Rect>> {
+        Ok(self.inner.borrow().screens.clone())
+    }
+
+    fn outputs(&self) -> Result<Vec<Output>> {
+        Ok(self.inner.borrow().outputs.clone())
+    }
+
+    fn configure_outputs(&self, configs: &[OutputConfig]) -> Result<()> {
+        let mut outputs = self.outputs()?;
+        for conf in configs {
+            if !outputs.iter().any(|o| o.name == conf.name && o.connected) {
+                return Err(Error::Randr(format!("unknown output: {}", conf.name)));
+            }
+        }
+
+        for o in outputs.iter_mut() {
+            let conf = configs.iter().find(|c| c.name == o.name);
+            o.r = conf.map(|c| c.r);
+            o.primary = conf.is_some_and(|c| c.primary);
+        }
+        self.set_outputs(outputs);
+
+        Ok(())
+    }
+
+    fn cursor_position(&self) -> Result<Point> {
+        Ok(self.inner.borrow().pointer)
+    }
+
+    fn keymap(&self) -> Result<Keymap> {
+        let keymap = self.inner.borrow().keymap.clone();
+
+        Ok(keymap.unwrap_or_else(|| {
+            Keymap::new(headless_keycodes().into_iter().map(|(n, c)| (c, vec![n])))
+        }))
+    }
+
+    fn keyboard_layouts(&self) -> Result<Vec<String>> {
+        Ok(self.inner.borrow().keyboard_layouts.clone())
+    }
+
+    fn keyboard_layout(&self) -> Result<u8> {
+        Ok(self.inner.borrow().keyboard_layout)
+    }
+
+    fn set_keyboard_layout(&self, group: u8) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.keyboard_layout != group {
+            inner.keyboard_layout = group;
+            inner.events.push_back(XEvent::KeyboardLayoutChange(group));
+        }
+
+        Ok(())
+    }
+
+    fn grab(&self, key_codes: &[KeyCode], _: &[MouseState]) -> Result<()> {
+        self.inner.borrow_mut().grabbed_keys = key_codes.to_vec();
+
+        Ok(())
+    }
+
+    fn acquire_wm_selection(&self) -> Result<()> {
+        self.inner.borrow_mut().wm_selection = true;
+
+        Ok(())
+    }
+
+    fn release_wm_selection(&self) -> Result<()> {
+        self.inner.borrow_mut().wm_selection = false;
+
+        Ok(())
+    }
+
+    fn next_event(&self) -> Result<XEvent> {
+        self.pop_event()
+            .ok_or_else(|| Error::Custom("no pending events".to_string()))
+    }
+
+    fn flush(&self) {}
+
+    fn intern_atom(&self, atom: &str) -> Result<Xid> {
+        let mut inner = self.inner.borrow_mut();
+        let ix = match inner.atoms.iter().position(|a| a == atom) {
+            Some(ix) => ix,
+            None => {
+                inner.atoms.push(atom.to_string());
+                inner.atoms.len() - 1
+            }
+        };
+
+        Ok(Xid(FIRST_ATOM + ix as u32))
+    }
+
+    fn atom_name(&self, xid: Xid) -> Result<String> {
+        let inner = self.inner.borrow();
+        xid.checked_sub(FIRST_ATOM)
+            .and_then(|ix| inner.atoms.get(ix as usize).cloned())
+            .ok_or_else(|| Error::Custom(format!("unknown atom: {xid}")))
+    }
+
+    fn client_geometry(&self, client: Xid) -> Result<Rect> {
+        let inner = self.inner.borrow();
+        if client == ROOT {
+            return inner.screens.first().copied().ok_or(Error::NoScreens);
+        }
+
+        inner
+            .windows
+            .get(&client)
+            .map(|w| w.geometry)
+            .ok_or(Error::UnknownClient(client))
+    }
+
+    fn existing_clients(&self) -> Result<Vec<Xid>> {
+        Ok(self.windows())
+    }
+
+    fn map(&self, client: Xid) -> Result<()> {
+        self.with_window(client, |w| w.mapped = true)
+    }
+
+    fn unmap(&self, client: Xid) -> Result<()> {
+        let mut was_mapped = false;
+        self.with_window(client, |w| {
+            was_mapped = w.mapped;
+            w.mapped = false;
+        })?;
+
+        if was_mapped {
+            self.push_event(XEvent::UnmapNotify(client));
+        }
+
+        Ok(())
+    }
+
+    fn kill(&self, client: Xid) -> Result<()> {
+        self.check_known(client)?;
+        self.destroy_window(client);
+
+        Ok(())
+    }
+
+    fn focus(&self, client: Xid) -> Result<()> {
+        self.check_known(client)?;
+        self.inner.borrow_mut().focus = Some(client);
+
+        Ok(())
+    }
+
+    fn get_prop(&self, client: Xid, prop_name: &str) -> Result<Option<Prop>> {
+        self.check_known(client)?;
+        let inner = self.inner.borrow();
+
+        Ok(inner
+            .props
+            .get(&client)
+            .and_then(|props| props.get(prop_name))
+            .cloned())
+    }
+
+    fn list_props(&self, client: Xid) -> Result<Vec<String>> {
+        self.check_known(client)?;
+        let inner = self.inner.borrow();
+
+        Ok(inner
+            .props
+            .get(&client)
+            .map(|props| props.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn get_wm_state(&self, client: Xid) -> Result<Option<WmState>> {
+        self.window(client)
+            .map(|w| w.wm_state)
+            .ok_or(Error::UnknownClient(client))
+    }
+
+    fn get_window_attributes(&self, client: Xid) -> Result<WindowAttributes> {
+        let w = self.window(client).ok_or(Error::UnknownClient(client))?;
+        let map_state = if w.mapped {
+            MapState::Viewable
+        } else {
+            MapState::Unmapped
+        };
+
+        Ok(WindowAttributes::new(
+            w.override_redirect,
+            map_state,
+            WindowClass::InputOutput,
+        ))
+    }
+
+    fn set_wm_state(&self, client: Xid, wm_state: WmState) -> Result<()> {
+        self.with_window(client, |w| w.wm_state = Some(wm_state))
+    }
+
+    fn set_prop(&self, client: Xid, name: &str, val: Prop) -> Result<()> {
+        self.check_known(client)?;
+        let mut inner = self.inner.borrow_mut();
+        inner
+            .props
+            .entry(client)
+            .or_default()
+            .insert(name.to_string(), val);
+
+        Ok(())
+    }
+
+    fn delete_prop(&self, client: Xid, prop_name: &str) -> Result<()> {
+        self.check_known(client)?;
+        if let Some(props) = self.inner.borrow_mut().props.get_mut(&client) {
+            props.remove(prop_name);
+        }
+
+        Ok(())
+    }
+
+    fn set_client_attributes(&self, client: Xid, _: &[ClientAttr]) -> Result<()> {
+        self.check_known(client)
+    }
+
+    fn set_client_config(&self, client: Xid, data: &[ClientConfig]) -> Result<()> {
+        self.check_known(client)?;
+        for conf in data {
+            match *conf {
+                ClientConfig::Position(r) => self.with_window(client, |w| w.geometry = r)?,
+                // Rects can not represent windows positioned above or to the left of the screen
+                ClientConfig::Move(x, y) => self.with_window(client, |w| {
+                    w.geometry.x = x.max(0) as u32;
+                    w.geometry.y = y.max(0) as u32;
+                })?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_client_message(&self, msg: ClientMessage) -> Result<()> {
+        self.inner.borrow_mut().sent_messages.push(msg);
+
+        Ok(())
+    }
+
+    fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
+        let r = self.client_geometry(id)?;
+        let p = Point::new(
+            (r.x as i32 + x as i32).max(0) as u32,
+            (r.y as i32 + y as i32).max(0) as u32,
+        );
+        self.inner.borrow_mut().pointer = p;
+
+        Ok(())
+    }
+
+    fn show_outline(&self, r: Rect, _: u32, _: Color) -> Result<()> {
+        self.inner.borrow_mut().outline = Some(r);
+
+        Ok(())
+    }
+
+    fn hide_outline(&self) -> Result<()> {
+        self.inner.borrow_mut().outline = None;
+
+        Ok(())
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        self.inner.borrow_mut().pointer_grabbed = true;
+
+        Ok(())
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.inner.borrow_mut().pointer_grabbed = false;
+
+        Ok(())
+    }
+}
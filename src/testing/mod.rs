@@ -0,0 +1,661 @@
+//! Utilities for writing end to end tests of window manager behaviour without an X server.
+//!
+//! A [Scenario] drives a [WindowManager] that is running on top of a [HeadlessConn] (an in
+//! memory X connection), allowing you to open windows, press keys and then make assertions
+//! about the resulting state. This is useful for testing that your key bindings, rules and
+//! layouts behave in the way that you expect without needing to restart your window manager.
+//!
+//! For integration tests against a real X server see the [xephyr] module, which is available
+//! when both the `testing` and `x11rb` features are enabled.
+//!
+//! This module is available when the `testing` feature is enabled.
+//!
+//! ```
+//! use penrose::{
+//!     builtin::actions::modify_with,
+//!     core::{bindings::KeyEventHandler, Config},
+//!     map,
+//!     testing::{HeadlessConn, Scenario},
+//! };
+//! use std::collections::HashMap;
+//!
+//! let bindings: HashMap<String, Box<dyn KeyEventHandler<HeadlessConn>>> = map! {
+//!     map_keys: |k: &str| k.to_string();
+//!
+//!     "M-j" => modify_with(|cs| cs.focus_down()),
+//!     "M-k" => modify_with(|cs| cs.focus_up()),
+//!     "M-S-2" => modify_with(|cs| cs.move_focused_to_tag("2")),
+//! };
+//!
+//! Scenario::with_bindings(Config::default(), bindings)
+//!     .unwrap()
+//!     .given_windows(["firefox", "alacritty"])
+//!     .expect_focus("alacritty")
+//!     .press("M-j")
+//!     .expect_focus("firefox")
+//!     .press("M-S-2")
+//!     .expect_on_workspace("firefox", "2")
+//!     .expect_visible(["alacritty"]);
+//! ```
+use crate::{
+    core::{
+        bindings::{parse_binding, BindingMode, KeyBindings, KeyChord, KeyCode, KeyEventHandler},
+        Config, State, WindowManager,
+    },
+    pure::geometry::Rect,
+    x::{XConnExt, XEvent},
+    Result, Xid,
+};
+use std::{collections::HashMap, thread, time::Duration};
+
+mod headless;
+#[cfg(feature = "x11rb")]
+pub mod xephyr;
+
+pub use headless::{HeadlessConn, HeadlessWindow, DEFAULT_WINDOW_GEOMETRY};
+
+// The maximum number of events processed after a single step of a scenario before we assume
+// that the window manager is stuck in a loop.
+const MAX_EVENTS_PER_STEP: usize = 1000;
+
+// The X server allocates keycodes in the range 8..=255
+const FIRST_KEYCODE: u8 = 8;
+
+const NAMED_KEYS: &[&str] = &[
+    "Return",
+    "space",
+    "Tab",
+    "Escape",
+    "BackSpace",
+    "Delete",
+    "Insert",
+    "Home",
+    "End",
+    "Prior",
+    "Next",
+    "Left",
+    "Right",
+    "Up",
+    "Down",
+    "comma",
+    "period",
+    "slash",
+    "backslash",
+    "semicolon",
+    "apostrophe",
+    "grave",
+    "minus",
+    "equal",
+    "bracketleft",
+    "bracketright",
+    "Print",
+    "XF86AudioRaiseVolume",
+    "XF86AudioLowerVolume",
+    "XF86AudioMute",
+    "XF86AudioPlay",
+    "XF86AudioNext",
+    "XF86AudioPrev",
+    "XF86MonBrightnessUp",
+    "XF86MonBrightnessDown",
+];
+
+/// The key names known to a [HeadlessConn] along with their keycodes.
+///
+/// This covers the lower case letters, digits, function keys `F1` to `F12` and common named
+/// keys (using the same names as `xmodmap`). The codes themselves are arbitrary and do not
+/// match those of any real keyboard.
+pub fn headless_keycodes() -> HashMap<String, u8> {
+    let letters = ('a'..='z').map(String::from);
+    let digits = ('0'..='9').map(String::from);
+    let function_keys = (1..=12).map(|n| format!("F{n}"));
+    let named = NAMED_KEYS.iter().map(|s| s.to_string());
+
+    letters
+        .chain(digits)
+        .chain(function_keys)
+        .chain(named)
+        .zip(FIRST_KEYCODE..)
+        .collect()
+}
+
+/// Parse string format key bindings (such as `"M-S-j"`) into [KeyBindings] using the keycodes
+/// from [headless_keycodes].
+pub fn parse_keybindings<S>(
+    str_bindings: HashMap<S, Box<dyn KeyEventHandler<HeadlessConn>>>,
+) -> Result<KeyBindings<HeadlessConn>>
+where
+    S: AsRef<str>,
+{
+    let codes = headless_keycodes();
+
+    str_bindings
+        .into_iter()
+        .map(|(s, v)| parse_binding(s.as_ref(), &codes).map(|k| (k, v)))
+        .collect()
+}
+
+/// Parse string format key bindings into a [KeyChord] using the keycodes from
+/// [headless_keycodes].
+pub fn parse_key_chord<S>(
+    str_bindings: HashMap<S, Box<dyn KeyEventHandler<HeadlessConn>>>,
+) -> Result<KeyChord<HeadlessConn>>
+where
+    S: AsRef<str>,
+{
+    KeyChord::try_from_str_bindings(str_bindings, &headless_keycodes())
+}
+
+/// Parse string format key bindings into a named [BindingMode] using the keycodes from
+/// [headless_keycodes].
+pub fn parse_binding_mode<S>(
+    name: impl Into<String>,
+    str_bindings: HashMap<S, Box<dyn KeyEventHandler<HeadlessConn>>>,
+) -> Result<BindingMode<HeadlessConn>>
+where
+    S: AsRef<str>,
+{
+    BindingMode::try_from_str_bindings(name, str_bindings, &headless_keycodes())
+}
+
+/// A readable, chainable way of driving a [WindowManager] and asserting on its behaviour.
+///
+/// Windows are referred to by the name they were opened with. Each step runs to completion,
+/// processing any events generated by the window manager's requests to the [HeadlessConn],
+/// before the next one starts. Expectations panic with a description of the mismatch if they
+/// do not hold.
+#[derive(Debug)]
+pub struct Scenario {
+    wm: WindowManager<HeadlessConn>,
+    codes: HashMap<String, u8>,
+    windows: Vec<(String, Xid)>,
+}
+
+impl Scenario {
+    /// Start a new scenario, running the startup logic of the given [WindowManager].
+    ///
+    /// Key bindings should be parsed using [parse_keybindings] in order for them to match the
+    /// keys sent by [Scenario::press].
+    pub fn new(mut wm: WindowManager<HeadlessConn>) -> Result<Self> {
+        wm.start_without_event_loop()?;
+
+        let mut s = Self {
+            wm,
+            codes: headless_keycodes(),
+            windows: Vec::new(),
+        };
+        s.process_events();
+
+        Ok(s)
+    }
+
+    /// Start a new scenario using a [WindowManager] with the given config and string format key
+    /// bindings, running on a single 1920x1080 screen.
+    pub fn with_bindings<S>(
+        config: Config<HeadlessConn>,
+        str_bindings: HashMap<S, Box<dyn KeyEventHandler<HeadlessConn>>>,
+    ) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let key_bindings = parse_keybindings(str_bindings)?;
+        let wm = WindowManager::new(
+            config,
+            key_bindings,
+            HashMap::new(),
+            HeadlessConn::default(),
+        )?;
+
+        Self::new(wm)
+    }
+
+    /// The current state of the window manager.
+    pub fn state(&self) -> &State<HeadlessConn> {
+        &self.wm.state
+    }
+
+    /// The underlying [HeadlessConn].
+    pub fn conn(&self) -> &HeadlessConn {
+        self.wm.conn()
+    }
+
+    /// The id of the window opened with the given name.
+    ///
+    /// # Panics
+    /// Panics if there is no window with this name.
+    pub fn id(&self, name: &str) -> Xid {
+        match self.windows.iter().find(|(n, _)| n == name) {
+            Some(&(_, id)) => id,
+            None => panic!("no window named '{name}' has been opened in this scenario"),
+        }
+    }
+
+    fn name(&self, id: Xid) -> String {
+        self.windows
+            .iter()
+            .find(|&&(_, wid)| wid == id)
+            .map(|(n, _)| n.clone())
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    fn process_events(&mut self) {
+        for _ in 0..MAX_EVENTS_PER_STEP {
+            match self.wm.conn().pop_event() {
+                Some(event) => self.wm.handle_event_without_event_loop(event),
+                None => return,
+            }
+        }
+
+        panic!("more than {MAX_EVENTS_PER_STEP} events were generated by a single step");
+    }
+
+    /// Send an event to the window manager and process any resulting events.
+    pub fn send(mut self, event: XEvent) -> Self {
+        self.wm.conn().push_event(event);
+        self.process_events();
+
+        self
+    }
+
+    /// Open a window with the given name and WM_CLASS.
+    ///
+    /// # Panics
+    /// Panics if a window with the given name has already been opened.
+    pub fn open_window(mut self, name: &str, class: &str) -> Self {
+        if self.windows.iter().any(|(n, _)| n == name) {
+            panic!("a window named '{name}' has already been opened in this scenario");
+        }
+
+        let id = self.wm.conn().create_window(class);
+        self.windows.push((name.to_string(), id));
+
+        self.send(XEvent::MapRequest(id))
+    }
+
+    /// Open a window for each of the given names in order, using the name as the WM_CLASS.
+    pub fn given_windows<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        names
+            .into_iter()
+            .fold(self, |s, name| s.open_window(name.as_ref(), name.as_ref()))
+    }
+
+    /// Close the named window as if the user had closed it from inside the client.
+    pub fn close(self, name: &str) -> Self {
+        let id = self.id(name);
+        self.wm.conn().destroy_window(id);
+
+        self.send_queued()
+    }
+
+    fn send_queued(mut self) -> Self {
+        self.process_events();
+        self
+    }
+
+    /// Press the given key combination, specified in the same format as key bindings
+    /// (e.g. `"M-S-j"`).
+    ///
+    /// # Panics
+    /// Panics if the key combination can not be parsed.
+    pub fn press(self, keys: &str) -> Self {
+        let code: KeyCode = match parse_binding(keys, &self.codes) {
+            Ok(code) => code,
+            Err(e) => panic!("invalid key combination '{keys}': {e}"),
+        };
+
+        self.send(XEvent::KeyPress(code))
+    }
+
+    /// Release the given key combination, specified in the same format as key bindings. The
+    /// modifiers given are those still held when the key is released.
+    ///
+    /// # Panics
+    /// Panics if the key combination can not be parsed.
+    pub fn release(self, keys: &str) -> Self {
+        let code: KeyCode = match parse_binding(keys, &self.codes) {
+            Ok(code) => code,
+            Err(e) => panic!("invalid key combination '{keys}': {e}"),
+        };
+
+        self.send(XEvent::KeyRelease(code))
+    }
+
+    /// Run an arbitrary function against the window manager state, refreshing the X state
+    /// afterwards.
+    pub fn act<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut State<HeadlessConn>, &HeadlessConn) -> Result<()>,
+    {
+        let (state, x) = self.wm.state_and_conn();
+        if let Err(e) = f(state, x).and_then(|_| x.refresh(state)) {
+            panic!("error running scenario action: {e}");
+        }
+
+        self.send_queued()
+    }
+
+    /// Wait for the given duration and then run any timers that have expired (see
+    /// [State::run_after]) along with any other delayed work that is now due, such as
+    /// chord timeouts and delayed focus changes.
+    pub fn wait(mut self, duration: Duration) -> Self {
+        thread::sleep(duration);
+        self.wm.run_timers_without_event_loop();
+
+        self.send_queued()
+    }
+
+    /// Expect the named window to have focus.
+    pub fn expect_focus(self, name: &str) -> Self {
+        let expected = self.id(name);
+        let focus = self.state().client_set.current_client().copied();
+        assert_eq!(
+            focus.map(|id| self.name(id)),
+            Some(self.name(expected)),
+            "focused window"
+        );
+
+        self
+    }
+
+    /// Expect no window to have focus.
+    pub fn expect_no_focus(self) -> Self {
+        let focus = self.state().client_set.current_client().copied();
+        assert_eq!(focus.map(|id| self.name(id)), None, "focused window");
+
+        self
+    }
+
+    /// Expect the given workspace to be focused.
+    pub fn expect_workspace(self, tag: &str) -> Self {
+        assert_eq!(
+            self.state().client_set.current_tag(),
+            tag,
+            "focused workspace"
+        );
+
+        self
+    }
+
+    /// Expect the named window to be on the given workspace.
+    pub fn expect_on_workspace(self, name: &str, tag: &str) -> Self {
+        let id = self.id(name);
+        assert_eq!(
+            self.state().client_set.tag_for_client(&id),
+            Some(tag),
+            "workspace for '{name}'"
+        );
+
+        self
+    }
+
+    /// Expect the named windows, in stack order, to be the clients of the focused workspace.
+    pub fn expect_stack<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let expected: Vec<String> = names.into_iter().map(|s| s.as_ref().to_string()).collect();
+        let stack: Vec<String> = self
+            .state()
+            .client_set
+            .current_workspace()
+            .clients()
+            .map(|&id| self.name(id))
+            .collect();
+        assert_eq!(stack, expected, "clients on the focused workspace");
+
+        self
+    }
+
+    /// Expect exactly the named windows to be mapped (visible) on the screen.
+    pub fn expect_visible<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut expected: Vec<String> = names.into_iter().map(|s| s.as_ref().to_string()).collect();
+        expected.sort();
+
+        let mut visible: Vec<String> = self
+            .windows
+            .iter()
+            .filter(|(_, id)| self.conn().window(*id).is_some_and(|w| w.mapped))
+            .map(|(n, _)| n.clone())
+            .collect();
+        visible.sort();
+
+        assert_eq!(visible, expected, "visible windows");
+
+        self
+    }
+
+    /// Expect the focused workspace to be using the named layout.
+    pub fn expect_layout(self, name: &str) -> Self {
+        let layout = self.state().client_set.current_workspace().layout_name();
+        assert_eq!(layout, name, "layout of the focused workspace");
+
+        self
+    }
+
+    /// Expect the named window to be floating.
+    pub fn expect_floating(self, name: &str) -> Self {
+        let id = self.id(name);
+        assert!(
+            self.state().client_set.floating.contains_key(&id),
+            "expected '{name}' to be floating"
+        );
+
+        self
+    }
+
+    /// Expect the named window to be tiled.
+    pub fn expect_tiled(self, name: &str) -> Self {
+        let id = self.id(name);
+        assert!(
+            !self.state().client_set.floating.contains_key(&id),
+            "expected '{name}' to be tiled"
+        );
+
+        self
+    }
+
+    /// Expect the named window to have been positioned at the given geometry.
+    pub fn expect_geometry(self, name: &str, r: Rect) -> Self {
+        let id = self.id(name);
+        let geometry = self.conn().window(id).map(|w| w.geometry);
+        assert_eq!(geometry, Some(r), "geometry of '{name}'");
+
+        self
+    }
+
+    /// Expect the named window to have been closed.
+    pub fn expect_closed(self, name: &str) -> Self {
+        let id = self.id(name);
+        assert!(
+            !self.state().client_set.contains(&id) && self.conn().window(id).is_none(),
+            "expected '{name}' to be closed"
+        );
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builtin::{
+            actions::{key_handler, modify_with},
+            layout::{MainAndStack, Monocle},
+        },
+        map,
+        pure::geometry::Rotation,
+        stack,
+        x::Output,
+    };
+    use simple_test_case::test_case;
+    use std::{cell::Cell, rc::Rc};
+
+    fn scenario() -> Scenario {
+        let bindings: HashMap<&str, Box<dyn KeyEventHandler<HeadlessConn>>> = map! {
+            "M-j" => modify_with(|cs| cs.focus_down()),
+            "M-k" => modify_with(|cs| cs.focus_up()),
+            "M-Tab" => modify_with(|cs| cs.toggle_tag()),
+            "M-space" => modify_with(|cs| cs.next_layout()),
+            "M-2" => modify_with(|cs| cs.focus_tag("2")),
+            "M-S-2" => modify_with(|cs| cs.move_focused_to_tag("2")),
+            "M-S-q" => key_handler(|state, x: &HeadlessConn| x.kill_focused(state)),
+        };
+
+        let config = Config {
+            default_layouts: stack!(MainAndStack::boxed_default(), Monocle::boxed()),
+            ..Config::default()
+        };
+
+        Scenario::with_bindings(config, bindings).unwrap()
+    }
+
+    #[test_case("a", Some(FIRST_KEYCODE); "first letter")]
+    #[test_case("0", Some(FIRST_KEYCODE + 26); "first digit")]
+    #[test_case("Return", Some(FIRST_KEYCODE + 26 + 10 + 12); "first named key")]
+    #[test_case("NotAKey", None; "unknown")]
+    #[test]
+    fn headless_keycodes_are_stable(name: &str, expected: Option<u8>) {
+        assert_eq!(headless_keycodes().get(name).copied(), expected);
+    }
+
+    #[test]
+    fn new_windows_are_focused_and_visible() {
+        scenario()
+            .expect_no_focus()
+            .given_windows(["firefox", "alacritty"])
+            .expect_focus("alacritty")
+            .expect_stack(["alacritty", "firefox"])
+            .expect_visible(["firefox", "alacritty"]);
+    }
+
+    #[test]
+    fn key_bindings_move_focus() {
+        scenario()
+            .given_windows(["a", "b", "c"])
+            .press("M-j")
+            .expect_focus("b")
+            .press("M-k")
+            .press("M-k")
+            .expect_focus("a");
+    }
+
+    #[test]
+    fn moving_clients_between_workspaces_updates_visibility() {
+        scenario()
+            .given_windows(["firefox", "alacritty"])
+            .press("M-S-2")
+            .expect_on_workspace("alacritty", "2")
+            .expect_visible(["firefox"])
+            .press("M-2")
+            .expect_workspace("2")
+            .expect_focus("alacritty")
+            .expect_visible(["alacritty"])
+            .press("M-Tab")
+            .expect_workspace("1")
+            .expect_visible(["firefox"]);
+    }
+
+    #[test]
+    fn killed_and_closed_clients_are_unmanaged() {
+        scenario()
+            .given_windows(["a", "b", "c"])
+            .press("M-S-q")
+            .expect_closed("c")
+            .expect_focus("b")
+            .close("a")
+            .expect_closed("a")
+            .expect_stack(["b"]);
+    }
+
+    #[test]
+    fn layouts_can_be_cycled() {
+        scenario()
+            .expect_layout("Side")
+            .press("M-space")
+            .expect_layout("Mono");
+    }
+
+    #[test]
+    fn tiled_clients_are_positioned_on_screen() {
+        let s = scenario().given_windows(["a"]);
+        let r = s.conn().window(s.id("a")).unwrap().geometry;
+
+        assert_ne!(r, DEFAULT_WINDOW_GEOMETRY);
+        s.expect_tiled("a").expect_geometry("a", r);
+    }
+
+    #[test]
+    fn screen_changes_rearrange_clients_and_run_the_hook() {
+        let n_calls = Rc::new(Cell::new(0));
+        let calls = Rc::clone(&n_calls);
+        let mut config = Config::default();
+        config.compose_or_set_screens_changed_hook(
+            move |_: &mut State<HeadlessConn>, _: &HeadlessConn| {
+                calls.set(calls.get() + 1);
+                Ok(())
+            },
+        );
+
+        let s = Scenario::with_bindings(config, HashMap::<&str, _>::new())
+            .unwrap()
+            .given_windows(["a"]);
+        let r = Rect::new(0, 0, 1280, 720);
+        let before = s.conn().window(s.id("a")).unwrap().geometry;
+
+        s.conn().set_screens(vec![r]);
+        let s = s.send_queued();
+        let after = s.conn().window(s.id("a")).unwrap().geometry;
+
+        assert_ne!(after, before);
+        assert!(r.contains(&after));
+        assert_eq!(n_calls.get(), 1);
+
+        // Repeated notifications without a change in geometry are ignored
+        s.send(XEvent::RandrNotify);
+        assert_eq!(n_calls.get(), 1);
+    }
+
+    #[test]
+    fn primary_output_changes_are_tracked() {
+        let screens = [Rect::new(0, 0, 1280, 720), Rect::new(1280, 0, 1920, 1080)];
+        let outputs = |primary: usize| {
+            let output = |i: usize| Output {
+                name: format!("DP-{i}"),
+                connected: true,
+                r: Some(screens[i]),
+                primary: i == primary,
+                rotation: Rotation::Normal,
+            };
+
+            vec![output(0), output(1)]
+        };
+
+        let s = scenario();
+        s.conn().set_outputs(outputs(1));
+        let s = s.send_queued();
+
+        assert_eq!(s.state().client_set.primary_screen(), 1);
+
+        // Changing the primary output does not change the screen geometry
+        s.conn().set_outputs(outputs(0));
+        let s = s.send_queued();
+
+        assert_eq!(s.state().client_set.primary_screen(), 0);
+        assert_eq!(s.state().client_set.primary_screen, Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "focused window")]
+    fn failed_expectations_panic() {
+        scenario().given_windows(["a", "b"]).expect_focus("a");
+    }
+}
@@ -0,0 +1,637 @@
+//! Integration testing against a real (nested) X server using Xephyr.
+//!
+//! An [Xephyr] session starts a new `Xephyr` process on a free display, runs your window
+//! manager against it in a background thread and then lets you open client windows and make
+//! assertions about the EWMH properties that the window manager sets. As this uses a real X
+//! server (and the real x11rb backend) it is slower than a [Scenario][0] but exercises the full
+//! stack, making it suitable for testing backends and complete configs in CI.
+//!
+//! `Xephyr` needs to be installed and on your `PATH` (along with `xterm` if you want to use
+//! [Xephyr::spawn_xterm]). Under CI there is no need for a running X server: Xephyr will happily
+//! run inside of `xvfb-run`.
+//!
+//! All expectations are polled until they pass or the session timeout expires, as the window
+//! manager is running concurrently with your test.
+//!
+//! ```no_run
+//! use penrose::{
+//!     core::{bindings::KeyBindings, Config, WindowManager},
+//!     extensions::hooks::add_ewmh_hooks,
+//!     testing::xephyr::Xephyr,
+//! };
+//! use std::collections::HashMap;
+//!
+//! Xephyr::spawn(1280, 720)
+//!     .unwrap()
+//!     .run_wm(|conn| {
+//!         let config = add_ewmh_hooks(Config::default());
+//!         WindowManager::new(config, KeyBindings::new(), HashMap::new(), conn)
+//!     })
+//!     .open_client("first", "TestClient")
+//!     .open_client("second", "TestClient")
+//!     .expect_active("second")
+//!     .expect_client_list(["first", "second"])
+//!     .close("second")
+//!     .expect_active("first");
+//! ```
+//!
+//!   [0]: crate::testing::Scenario
+use crate::{core::WindowManager, x11rb::RustConn, Error, Result, Xid};
+use std::{
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tracing::{error, warn};
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{
+        AtomEnum, ClientMessageData, ClientMessageEvent, ConnectionExt as _, CreateWindowAux,
+        EventMask, MapState, PropMode, WindowClass, CLIENT_MESSAGE_EVENT,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+};
+
+/// The default amount of time to wait for the X server, window manager or an expectation.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Displays below this are left alone to avoid clashing with a real X server
+const FIRST_DISPLAY: u32 = 90;
+const LAST_DISPLAY: u32 = 999;
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// The first display number that has no lock file or socket.
+fn free_display<F>(exists: F) -> Option<u32>
+where
+    F: Fn(&Path) -> bool,
+{
+    (FIRST_DISPLAY..=LAST_DISPLAY).find(|n| {
+        let lock = format!("/tmp/.X{n}-lock");
+        let socket = format!("/tmp/.X11-unix/X{n}");
+
+        !exists(Path::new(&lock)) && !exists(Path::new(&socket))
+    })
+}
+
+// Poll a condition until it holds or the timeout expires.
+fn wait_until<F>(timeout: Duration, mut f: F) -> bool
+where
+    F: FnMut() -> bool,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        if f() {
+            return true;
+        } else if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[derive(Debug)]
+enum Client {
+    // A window created by us on its own connection
+    Bundled { conn: Box<RustConnection>, id: u32 },
+    // A window belonging to an external program
+    Process { child: Child, id: u32 },
+}
+
+impl Client {
+    fn id(&self) -> Xid {
+        match self {
+            Self::Bundled { id, .. } | Self::Process { id, .. } => Xid::from(*id),
+        }
+    }
+
+    // Errors are ignored here as the window manager may have already killed the client.
+    fn close(self) {
+        match self {
+            Self::Bundled { conn, id } => {
+                let _ = conn.destroy_window(id);
+                let _ = conn.flush();
+            }
+            Self::Process { mut child, .. } => {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+/// A running Xephyr server that a window manager and test clients can be run against.
+///
+/// The server, window manager and any clients are shut down when this is dropped.
+#[derive(Debug)]
+pub struct Xephyr {
+    display: String,
+    server: Child,
+    conn: RustConnection,
+    root: u32,
+    wm: Option<JoinHandle<()>>,
+    clients: Vec<(String, Client)>,
+    timeout: Duration,
+}
+
+impl Drop for Xephyr {
+    fn drop(&mut self) {
+        for (_, client) in self.clients.drain(..) {
+            client.close();
+        }
+
+        if let Err(e) = self.server.kill() {
+            warn!(%e, display = %self.display, "unable to kill Xephyr");
+        }
+        let _ = self.server.wait();
+    }
+}
+
+impl Xephyr {
+    /// Start a new Xephyr server with a single screen of the given size on the first free
+    /// display, waiting for it to accept connections.
+    pub fn spawn(w: u32, h: u32) -> Result<Self> {
+        let n = free_display(|p| p.exists())
+            .ok_or_else(|| Error::Custom("no free X display available".to_string()))?;
+        let display = format!(":{n}");
+
+        let mut server = Command::new("Xephyr")
+            .args([
+                &display,
+                "-screen",
+                &format!("{w}x{h}"),
+                "-ac",
+                "-br",
+                "-noreset",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| Error::Custom(format!("unable to run Xephyr: {e}")))?;
+
+        let mut conn = None;
+        let ready = wait_until(DEFAULT_TIMEOUT, || {
+            if matches!(server.try_wait(), Ok(Some(_))) {
+                return true;
+            }
+            conn = RustConnection::connect(Some(&display)).ok();
+            conn.is_some()
+        });
+
+        let (conn, screen) = match conn {
+            Some(conn) => conn,
+            None => {
+                let _ = server.kill();
+                let _ = server.wait();
+                let reason = if ready { "exited" } else { "timed out" };
+                return Err(Error::Custom(format!("Xephyr {reason} on {display}")));
+            }
+        };
+        let root = conn.setup().roots[screen].root;
+
+        Ok(Self {
+            display,
+            server,
+            conn,
+            root,
+            wm: None,
+            clients: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// The display that the server is running on, such as `":90"`.
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    /// Set the amount of time to wait for the window manager to start and for expectations
+    /// to pass.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run the [WindowManager] returned by `f` in a background thread, waiting until it has
+    /// started managing the root window.
+    ///
+    /// # Panics
+    /// Panics if a window manager is already running, if the window manager can not be
+    /// created or if it does not start within the session timeout.
+    pub fn run_wm<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RustConn) -> Result<WindowManager<RustConn>> + Send + 'static,
+    {
+        if self.wm.is_some() {
+            panic!("a window manager is already running on {}", self.display);
+        }
+
+        let d = self.display.clone();
+        let handle = thread::spawn(move || {
+            let res = RustConn::new_for_display(&d)
+                .and_then(f)
+                .and_then(|wm| wm.run());
+
+            if let Err(e) = res {
+                error!(%e, display = %d, "window manager exited with an error");
+            }
+        });
+
+        let root = self.root;
+        let conn = &self.conn;
+        let started = wait_until(self.timeout, || {
+            handle.is_finished()
+                || conn
+                    .get_window_attributes(root)
+                    .ok()
+                    .and_then(|cookie| cookie.reply().ok())
+                    .is_some_and(|r| r.all_event_masks.contains(EventMask::SUBSTRUCTURE_REDIRECT))
+        });
+
+        if handle.is_finished() {
+            panic!("the window manager exited while starting up");
+        } else if !started {
+            panic!("the window manager did not start within {:?}", self.timeout);
+        }
+
+        self.wm = Some(handle);
+
+        self
+    }
+
+    /// The id of the client opened with the given name.
+    ///
+    /// # Panics
+    /// Panics if there is no client with this name.
+    pub fn id(&self, name: &str) -> Xid {
+        match self.clients.iter().find(|(n, _)| n == name) {
+            Some((_, c)) => c.id(),
+            None => panic!("no client named '{name}' has been opened in this session"),
+        }
+    }
+
+    fn name(&self, id: Xid) -> String {
+        self.clients
+            .iter()
+            .find(|(_, c)| c.id() == id)
+            .map(|(n, _)| n.clone())
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    fn check_new_name(&self, name: &str) {
+        if self.clients.iter().any(|(n, _)| n == name) {
+            panic!("a client named '{name}' has already been opened in this session");
+        }
+    }
+
+    /// Open a minimal client window with the given name and WM_CLASS, using its own
+    /// connection to the X server.
+    ///
+    /// # Panics
+    /// Panics if a client with the given name has already been opened or the window can not
+    /// be created.
+    pub fn open_client(mut self, name: &str, class: &str) -> Self {
+        self.check_new_name(name);
+
+        let (conn, id) = match create_test_window(&self.display, name, class) {
+            Ok(res) => res,
+            Err(e) => panic!("unable to open client '{name}': {e}"),
+        };
+        self.clients.push((
+            name.to_string(),
+            Client::Bundled {
+                conn: Box::new(conn),
+                id,
+            },
+        ));
+
+        self.expect_mapped(name)
+    }
+
+    /// Run `xterm` with the given WM_CLASS, waiting for its window to be created.
+    ///
+    /// # Panics
+    /// Panics if a client with the given name has already been opened, if `xterm` can not be
+    /// run or if its window does not appear within the session timeout.
+    pub fn spawn_xterm(mut self, name: &str, class: &str) -> Self {
+        self.check_new_name(name);
+
+        let known: Vec<u32> = self.clients.iter().map(|(_, c)| *c.id()).collect();
+        let mut child = match Command::new("xterm")
+            .args(["-class", class, "-title", name])
+            .env("DISPLAY", &self.display)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => panic!("unable to run xterm: {e}"),
+        };
+
+        let mut id = None;
+        wait_until(self.timeout, || {
+            id = self
+                .top_level_windows()
+                .into_iter()
+                .filter(|w| !known.contains(w))
+                .find(|&w| self.wm_class(w).as_deref() == Some(class));
+            id.is_some()
+        });
+
+        match id {
+            Some(id) => {
+                self.clients
+                    .push((name.to_string(), Client::Process { child, id }));
+                self.expect_mapped(name)
+            }
+            None => {
+                let _ = child.kill();
+                let _ = child.wait();
+                panic!("xterm window for '{name}' did not appear");
+            }
+        }
+    }
+
+    /// Close the named client as if the user had closed it from inside the client.
+    pub fn close(mut self, name: &str) -> Self {
+        let id = self.id(name);
+        if let Some(ix) = self.clients.iter().position(|(n, _)| n == name) {
+            let (_, client) = self.clients.remove(ix);
+            client.close();
+        }
+
+        self.expect(
+            |s| !s.top_level_windows().contains(&id),
+            |_| format!("expected '{name}' to be closed"),
+        )
+    }
+
+    /// Request that the window manager activate the named client using `_NET_ACTIVE_WINDOW`,
+    /// as a pager would.
+    pub fn request_activate(self, name: &str) -> Self {
+        let id = *self.id(name);
+        if let Err(e) = self.send_root_message(id, "_NET_ACTIVE_WINDOW", [2, 0, 0, 0, 0]) {
+            panic!("unable to request activation of '{name}': {e}");
+        }
+
+        self
+    }
+
+    /// Request that the window manager switch to the given desktop using
+    /// `_NET_CURRENT_DESKTOP`, as a pager would.
+    pub fn request_desktop(self, n: u32) -> Self {
+        if let Err(e) = self.send_root_message(self.root, "_NET_CURRENT_DESKTOP", [n, 0, 0, 0, 0]) {
+            panic!("unable to request desktop {n}: {e}");
+        }
+
+        self
+    }
+
+    /// The 32 bit values of the named property on the given window, if it is set.
+    pub fn prop(&self, id: Xid, name: &str) -> Option<Vec<u32>> {
+        let atom = self.intern(name).ok()?;
+        let reply = self
+            .conn
+            .get_property(false, *id, atom, AtomEnum::ANY, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        reply.value32().map(|vals| vals.collect())
+    }
+
+    /// Expect the named client to be the active window (`_NET_ACTIVE_WINDOW`).
+    pub fn expect_active(self, name: &str) -> Self {
+        let expected = *self.id(name);
+
+        self.expect(
+            |s| s.root_prop("_NET_ACTIVE_WINDOW").first() == Some(&expected),
+            |s| {
+                let active = s.root_prop("_NET_ACTIVE_WINDOW");
+                let active: Vec<_> = active.iter().map(|&id| s.name(Xid::from(id))).collect();
+                format!("active window: expected '{name}', got {active:?}")
+            },
+        )
+    }
+
+    /// Expect the managed clients (`_NET_CLIENT_LIST`) to be exactly the named clients, in any
+    /// order.
+    pub fn expect_client_list<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut expected: Vec<String> = names.into_iter().map(|s| s.as_ref().to_string()).collect();
+        expected.sort();
+
+        let client_list = |s: &Self| {
+            let mut names: Vec<String> = s
+                .root_prop("_NET_CLIENT_LIST")
+                .into_iter()
+                .map(|id| s.name(Xid::from(id)))
+                .collect();
+            names.sort();
+            names
+        };
+
+        self.expect(
+            |s| client_list(s) == expected,
+            |s| {
+                format!(
+                    "client list: expected {expected:?}, got {:?}",
+                    client_list(s)
+                )
+            },
+        )
+    }
+
+    /// Expect the given desktop to be current (`_NET_CURRENT_DESKTOP`).
+    pub fn expect_current_desktop(self, n: u32) -> Self {
+        self.expect(
+            |s| s.root_prop("_NET_CURRENT_DESKTOP").first() == Some(&n),
+            |s| {
+                let current = s.root_prop("_NET_CURRENT_DESKTOP");
+                format!("current desktop: expected {n}, got {current:?}")
+            },
+        )
+    }
+
+    /// Expect the named client to be on the given desktop (`_NET_WM_DESKTOP`).
+    pub fn expect_desktop(self, name: &str, n: u32) -> Self {
+        let id = self.id(name);
+
+        self.expect(
+            |s| s.prop(id, "_NET_WM_DESKTOP").unwrap_or_default().first() == Some(&n),
+            |s| {
+                let desktop = s.prop(id, "_NET_WM_DESKTOP");
+                format!("desktop of '{name}': expected {n}, got {desktop:?}")
+            },
+        )
+    }
+
+    /// Expect the named client to be mapped.
+    pub fn expect_mapped(self, name: &str) -> Self {
+        let id = self.id(name);
+
+        self.expect(
+            |s| s.is_mapped(id),
+            |_| format!("expected '{name}' to be mapped"),
+        )
+    }
+
+    /// Expect the named client to be unmapped.
+    pub fn expect_unmapped(self, name: &str) -> Self {
+        let id = self.id(name);
+
+        self.expect(
+            |s| !s.is_mapped(id),
+            |_| format!("expected '{name}' to be unmapped"),
+        )
+    }
+
+    // Poll the given condition, panicking with the given message if it has not passed by
+    // the time the session timeout expires.
+    fn expect<F, M>(self, mut f: F, msg: M) -> Self
+    where
+        F: FnMut(&Self) -> bool,
+        M: FnOnce(&Self) -> String,
+    {
+        if !wait_until(self.timeout, || f(&self)) {
+            panic!("{}", msg(&self));
+        }
+
+        self
+    }
+
+    fn intern(&self, name: &str) -> Result<u32> {
+        Ok(self.conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+    }
+
+    fn root_prop(&self, name: &str) -> Vec<u32> {
+        self.prop(Xid::from(self.root), name).unwrap_or_default()
+    }
+
+    fn is_mapped(&self, id: Xid) -> bool {
+        self.conn
+            .get_window_attributes(*id)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|r| r.map_state == MapState::VIEWABLE)
+    }
+
+    fn top_level_windows(&self) -> Vec<u32> {
+        self.conn
+            .query_tree(self.root)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|r| r.children)
+            .unwrap_or_default()
+    }
+
+    // The class (second) component of WM_CLASS for the given window
+    fn wm_class(&self, id: u32) -> Option<String> {
+        let reply = self
+            .conn
+            .get_property(false, id, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+
+        let raw = String::from_utf8(reply.value).ok()?;
+        raw.split('\0').nth(1).map(|s| s.to_string())
+    }
+
+    fn send_root_message(&self, window: u32, name: &str, data: [u32; 5]) -> Result<()> {
+        let event = ClientMessageEvent {
+            response_type: CLIENT_MESSAGE_EVENT,
+            format: 32,
+            sequence: 0,
+            window,
+            type_: self.intern(name)?,
+            data: ClientMessageData::from(data),
+        };
+        let mask = EventMask::SUBSTRUCTURE_NOTIFY | EventMask::SUBSTRUCTURE_REDIRECT;
+
+        self.conn.send_event(false, self.root, mask, event)?;
+        self.conn.flush()?;
+
+        Ok(())
+    }
+}
+
+// Create and map a window on a new connection so that the window manager sees it as a
+// separate client.
+fn create_test_window(display: &str, name: &str, class: &str) -> Result<(RustConnection, u32)> {
+    let (conn, screen) = RustConnection::connect(Some(display))?;
+    let root = conn.setup().roots[screen].root;
+    let id = conn.generate_id()?;
+
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        id,
+        root,
+        0,
+        0,
+        640,
+        480,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new(),
+    )?;
+
+    let wm_class = format!("{name}\0{class}\0");
+    conn.change_property8(
+        PropMode::REPLACE,
+        id,
+        AtomEnum::WM_CLASS,
+        AtomEnum::STRING,
+        wm_class.as_bytes(),
+    )?;
+    conn.change_property8(
+        PropMode::REPLACE,
+        id,
+        AtomEnum::WM_NAME,
+        AtomEnum::STRING,
+        name.as_bytes(),
+    )?;
+    conn.map_window(id)?;
+    conn.flush()?;
+
+    Ok((conn, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case(&[], Some(FIRST_DISPLAY); "nothing running")]
+    #[test_case(&["/tmp/.X90-lock"], Some(91); "lock file")]
+    #[test_case(&["/tmp/.X11-unix/X90"], Some(91); "socket")]
+    #[test_case(&["/tmp/.X90-lock", "/tmp/.X11-unix/X91"], Some(92); "multiple")]
+    #[test]
+    fn free_display_skips_existing_displays(existing: &[&str], expected: Option<u32>) {
+        let display = free_display(|p| existing.iter().any(|e| Path::new(e) == p));
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn free_display_is_none_when_all_displays_are_taken() {
+        assert_eq!(free_display(|_| true), None);
+    }
+
+    #[test]
+    fn wait_until_times_out() {
+        let mut calls = 0;
+        let passed = wait_until(Duration::from_millis(30), || {
+            calls += 1;
+            false
+        });
+
+        assert!(!passed);
+        assert!(calls > 1);
+    }
+}
@@ -4,18 +4,101 @@ use crate::{
     pure::{geometry::Rect, Stack},
     Result, Xid,
 };
+#[cfg(feature = "wm")]
+use nix::sys::signal::{signal, SigHandler, Signal};
+#[cfg(feature = "wm")]
+use std::{io::Write, os::unix::process::CommandExt, process::Child, thread};
 use std::{
-    io::Read,
+    io::{self, Read},
     process::{Command, Stdio},
 };
 use tracing::debug;
 
+// Runs the command given as positional arguments and then reports its exit status on a final
+// line of stdout.
+#[cfg(feature = "wm")]
+const STATUS_WRAPPER: &str = r#""$@"; printf '\n%s' "$?""#;
+
+// Refuse to run commands that are not permitted by the CommandWhitelist of the running
+// window manager.
+#[cfg(feature = "wm")]
+fn check_whitelist(cmd: &str, args: &[&str]) -> Result<()> {
+    crate::core::check_command(cmd, args)
+}
+
+#[cfg(not(feature = "wm"))]
+fn check_whitelist(_: &str, _: &[&str]) -> Result<()> {
+    Ok(())
+}
+
+fn check_whitelist_io(cmd: &str, args: &[&str]) -> io::Result<()> {
+    check_whitelist(cmd, args).map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e))
+}
+
+/// Spawn a fully configured [Command], checking it against the whitelist of the running window
+/// manager in the same way as the other spawning functions in this module.
+///
+/// This is for commands that need more control over how they are run than the other
+/// functions in this module allow, such as writing to their stdin.
+#[cfg(feature = "wm")]
+pub(crate) fn spawn_command(cmd: &mut Command) -> Result<Child> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy()).collect();
+    let args: Vec<&str> = args.iter().map(|a| a.as_ref()).collect();
+    check_whitelist(&program, &args)?;
+
+    Ok(cmd.spawn()?)
+}
+
+/// Run one of the helper programs used internally by penrose with the specified command line
+/// arguments.
+///
+/// Helpers are exempt from the whitelist of the running window manager as they are never
+/// chosen by the user. This redirects the process stdout and stderr to /dev/null.
+#[cfg(feature = "wm")]
+pub(crate) fn spawn_helper_with_args(cmd: &str, args: &[&str]) -> Result<()> {
+    Command::new(cmd)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    Ok(())
+}
+
+/// Run one of the helper programs used internally by penrose with the specified command line
+/// arguments, returning its output.
+///
+/// Helpers are exempt from the whitelist of the running window manager as they are never
+/// chosen by the user.
+pub(crate) fn helper_output_with_args(cmd: &str, args: &[&str]) -> io::Result<String> {
+    let mut child = Command::new(cmd)
+        .stdout(Stdio::piped())
+        .args(args)
+        .spawn()?;
+
+    let mut buff = String::new();
+    child
+        .stdout
+        .take()
+        .expect("to have output")
+        .read_to_string(&mut buff)
+        .map(|_| buff)
+}
+
 /// Run an external command
 ///
 /// This redirects the process stdout and stderr to /dev/null.
+///
+/// If a [CommandWhitelist][0] has been set for the running window manager then commands that
+/// it does not permit are rejected, as they are for all of the spawning functions in this
+/// module.
+///
+///   [0]: crate::core::CommandWhitelist
 pub fn spawn<S: Into<String>>(cmd: S) -> Result<()> {
     let s = cmd.into();
     let parts: Vec<&str> = s.split_whitespace().collect();
+    check_whitelist(&s, &[])?;
     let result = if parts.len() > 1 {
         Command::new(parts[0])
             .args(&parts[1..])
@@ -39,7 +122,9 @@ pub fn spawn<S: Into<String>>(cmd: S) -> Result<()> {
 ///
 /// This redirects the process stdout and stderr to /dev/null.
 pub fn spawn_with_args<S: Into<String>>(cmd: S, args: &[&str]) -> Result<()> {
-    let result = Command::new(cmd.into())
+    let cmd = cmd.into();
+    check_whitelist(&cmd, args)?;
+    let result = Command::new(cmd)
         .args(args)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
@@ -60,6 +145,7 @@ pub fn spawn_for_output<S: Into<String>>(cmd: S) -> std::io::Result<String> {
     let cmd = cmd.into();
     debug!(?cmd, "spawning subprocess for output");
     let parts: Vec<&str> = cmd.split_whitespace().collect();
+    check_whitelist_io(&cmd, &[])?;
     let result = if parts.len() > 1 {
         Command::new(parts[0])
             .stdout(Stdio::piped())
@@ -92,19 +178,108 @@ pub fn spawn_for_output_with_args<S: Into<String>>(
     let cmd = cmd.into();
 
     debug!(?cmd, ?args, "spawning subprocess for output");
-    let mut child = Command::new(&cmd)
-        .stdout(Stdio::piped())
-        .args(args)
-        .spawn()?;
+    check_whitelist_io(&cmd, args)?;
+
+    helper_output_with_args(&cmd, args)
+}
+
+/// The captured result of running an external command using [spawn_for_output_with_status].
+#[cfg(feature = "wm")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Everything that the command wrote to stdout
+    pub stdout: String,
+    /// The exit status of the command.
+    ///
+    /// As with `sh`, commands that were killed by a signal have an exit status of 128 plus the
+    /// signal number and commands that could not be found have an exit status of 127.
+    pub status: i32,
+}
+
+#[cfg(feature = "wm")]
+impl CommandOutput {
+    /// Whether or not the command exited successfully.
+    pub fn success(&self) -> bool {
+        self.status == 0
+    }
+}
 
-    debug!(?cmd, ?args, "reading output");
+/// Run an external command to completion and return its output along with its exit status,
+/// writing `input` to its stdin if provided.
+///
+/// > As penrose ignores `SIGCHLD`, the exit status of a process spawned directly by penrose
+/// > can not be collected. The command is run via `sh` (with the default `SIGCHLD` handling
+/// > restored) so that its exit status can be reported.
+///
+/// This blocks until the command exits: see [State::spawn_and_capture][0] for running a
+/// command without blocking the window manager.
+///
+///   [0]: crate::core::State::spawn_and_capture
+#[cfg(feature = "wm")]
+pub fn spawn_for_output_with_status<S: Into<String>>(
+    cmd: S,
+    input: Option<String>,
+) -> std::io::Result<CommandOutput> {
+    let cmd = cmd.into();
+    debug!(?cmd, "spawning subprocess for output and exit status");
+    let parts: Vec<&str> = cmd.split_whitespace().collect();
+    check_whitelist_io(&cmd, &[])?;
+    let stdin = if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+
+    let mut command = Command::new("sh");
+    command
+        .args(["-c", STATUS_WRAPPER, "sh"])
+        .args(&parts)
+        .stdin(stdin)
+        .stdout(Stdio::piped());
+
+    // SAFETY: resetting a signal disposition is async-signal-safe.
+    unsafe {
+        command.pre_exec(|| {
+            signal(Signal::SIGCHLD, SigHandler::SigDfl)
+                .map(|_| ())
+                .map_err(Into::into)
+        });
+    }
+
+    let mut child = command.spawn()?;
+
+    // Writing from another thread avoids a deadlock if the command fills its stdout pipe
+    // before it has finished reading all of its input.
+    if let (Some(input), Some(mut child_stdin)) = (input, child.stdin.take()) {
+        thread::spawn(move || child_stdin.write_all(input.as_bytes()));
+    }
+
+    debug!(?cmd, "reading output");
     let mut buff = String::new();
     child
         .stdout
         .take()
-        .unwrap()
-        .read_to_string(&mut buff)
-        .map(|_| buff)
+        .expect("to have output")
+        .read_to_string(&mut buff)?;
+
+    // This fails when SIGCHLD is being ignored but the exit status has already been captured
+    let _ = child.wait();
+
+    parse_status_output(buff).ok_or_else(|| {
+        std::io::Error::other(format!("unable to determine the exit status of '{cmd}'"))
+    })
+}
+
+#[cfg(feature = "wm")]
+fn parse_status_output(mut raw: String) -> Option<CommandOutput> {
+    let ix = raw.rfind('\n')?;
+    let status = raw[ix + 1..].parse().ok()?;
+    raw.truncate(ix);
+
+    Some(CommandOutput {
+        stdout: raw,
+        status,
+    })
 }
 
 /// Use `notify-send` to display a message to the user
@@ -20,6 +20,9 @@ pub enum Atom {
     /// MANAGER
     #[strum(serialize = "MANAGER")]
     Manager,
+    /// _MOTIF_WM_HINTS
+    #[strum(serialize = "_MOTIF_WM_HINTS")]
+    MotifWmHints,
     /// UTF8_STRING
     #[strum(serialize = "UTF8_STRING")]
     UTF8String,
@@ -59,6 +62,9 @@ pub enum Atom {
     /// _NET_CLIENT_LIST
     #[strum(serialize = "_NET_CLIENT_LIST_STACKING")]
     NetClientListStacking,
+    /// _NET_CLOSE_WINDOW
+    #[strum(serialize = "_NET_CLOSE_WINDOW")]
+    NetCloseWindow,
     /// _NET_CURRENT_DESKTOP
     #[strum(serialize = "_NET_CURRENT_DESKTOP")]
     NetCurrentDesktop,
@@ -89,9 +95,15 @@ pub enum Atom {
     /// _NET_SYSTEM_TRAY_S0
     #[strum(serialize = "_NET_SYSTEM_TRAY_S0")]
     NetSystemTrayS0,
+    /// _NET_WORKAREA
+    #[strum(serialize = "_NET_WORKAREA")]
+    NetWorkarea,
     /// _NET_WM_DESKTOP
     #[strum(serialize = "_NET_WM_DESKTOP")]
     NetWmDesktop,
+    /// _NET_WM_MOVERESIZE
+    #[strum(serialize = "_NET_WM_MOVERESIZE")]
+    NetWmMoveresize,
     /// _NET_WM_NAME
     #[strum(serialize = "_NET_WM_NAME")]
     NetWmName,
@@ -101,6 +113,9 @@ pub enum Atom {
     /// _NET_WM_STRUT
     #[strum(serialize = "_NET_WM_STRUT")]
     NetWmStrut,
+    /// _NET_WM_STRUT_PARTIAL
+    #[strum(serialize = "_NET_WM_STRUT_PARTIAL")]
+    NetWmStrutPartial,
     /// _NET_WM_STATE_DEMANDS_ATTENTION
     #[strum(serialize = "_NET_WM_STATE_DEMANDS_ATTENTION")]
     NetWmStateDemandsAttention,
@@ -110,9 +125,27 @@ pub enum Atom {
     /// _NET_WM_STATE_HIDDEN
     #[strum(serialize = "_NET_WM_STATE_HIDDEN")]
     NetWmStateHidden,
+    /// _NET_WM_STATE_MAXIMIZED_HORZ
+    #[strum(serialize = "_NET_WM_STATE_MAXIMIZED_HORZ")]
+    NetWmStateMaximizedHorz,
+    /// _NET_WM_STATE_MAXIMIZED_VERT
+    #[strum(serialize = "_NET_WM_STATE_MAXIMIZED_VERT")]
+    NetWmStateMaximizedVert,
+    /// _NET_WM_STATE_MODAL
+    #[strum(serialize = "_NET_WM_STATE_MODAL")]
+    NetWmStateModal,
+    /// _NET_WM_STATE_SKIP_PAGER
+    #[strum(serialize = "_NET_WM_STATE_SKIP_PAGER")]
+    NetWmStateSkipPager,
+    /// _NET_WM_STATE_SKIP_TASKBAR
+    #[strum(serialize = "_NET_WM_STATE_SKIP_TASKBAR")]
+    NetWmStateSkipTaskbar,
     /// _NET_WM_WINDOW_TYPE
     #[strum(serialize = "_NET_WM_WINDOW_TYPE")]
     NetWmWindowType,
+    /// _NET_WM_WINDOW_OPACITY
+    #[strum(serialize = "_NET_WM_WINDOW_OPACITY")]
+    NetWmWindowOpacity,
     /// _XEMBED
     #[strum(serialize = "_XEMBED")]
     XEmbed,
@@ -34,6 +34,11 @@ pub enum XEvent {
     Destroy(Xid),
     /// A grabbed key combination has been entered by the user
     KeyPress(KeyCode),
+    /// A grabbed key has been released. The mask holds the modifiers that were still held
+    /// at the point the key was released.
+    KeyRelease(KeyCode),
+    /// The active keyboard layout (XKB group) has changed to the group with the given index
+    KeyboardLayoutChange(u8),
     /// The mouse pointer has left the current client window
     Leave(PointerChange),
     /// Keybindings have changed
@@ -67,6 +72,8 @@ impl std::fmt::Display for XEvent {
             FocusIn(_) => write!(f, "FocusIn"),
             Destroy(_) => write!(f, "Destroy"),
             KeyPress(_) => write!(f, "KeyPress"),
+            KeyRelease(_) => write!(f, "KeyRelease"),
+            KeyboardLayoutChange(_) => write!(f, "KeyboardLayoutChange"),
             Leave(_) => write!(f, "Leave"),
             MappingNotify => write!(f, "MappingNotify"),
             MapRequest(_) => write!(f, "MapRequest"),
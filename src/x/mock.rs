@@ -224,10 +224,16 @@ where
     }
 }
 
-/// A stub XConn implementation that doesn't implement _any_ methods.
+/// A stub XConn implementation that doesn't implement _any_ methods other than
+/// reporting a single 1920x1080 screen.
 ///
 /// Only usable for passing to test code that requires an XConn due to
-/// type signatures but is not making use of it.
+/// type signatures (or to create a [State][crate::core::State]) but is not
+/// otherwise making use of it.
 #[derive(Debug, Default, Clone, Copy)]
 pub struct StubXConn;
-impl MockXConn for StubXConn {}
+impl MockXConn for StubXConn {
+    fn mock_screen_details(&self) -> Result<Vec<Rect>> {
+        Ok(vec![Rect::new(0, 0, 1920, 1080)])
+    }
+}
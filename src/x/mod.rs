@@ -1,18 +1,28 @@
 //! Logic for interacting with the X server
 use crate::{
-    builtin::layout::messages::Hide,
+    builtin::{actions::floating::drag_in_progress, layout::messages::Hide},
     core::{
-        bindings::{KeyCode, MouseState},
+        bindings::{KeyCode, Keymap, MouseState},
         ClientSet, Config, State,
     },
-    pure::geometry::{Point, Rect},
-    x::{atom::AUTO_FLOAT_WINDOW_TYPES, event::ClientMessage, property::WmState},
-    Color, Result, Xid,
+    pure::geometry::{Point, Rect, Rotation},
+    x::{
+        atom::AUTO_FLOAT_WINDOW_TYPES,
+        event::{ClientMessage, ClientMessageKind},
+        property::{MotifWmHints, WmHints, WmState},
+        query::FixedSize,
+    },
+    Color, Error, Result, Xid,
 };
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use tracing::{error, trace};
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    os::fd::RawFd,
+    time::Instant,
+};
+use tracing::{error, trace, warn};
 
 pub mod atom;
 pub mod event;
@@ -51,6 +61,10 @@ pub enum ClientConfig {
     BorderPx(u32),
     /// Absolute size and position on the screen as a [Rect]
     Position(Rect),
+    /// Absolute position of the top left corner of the window without changing its size.
+    /// Unlike [ClientConfig::Position] this allows for windows to be placed partially (or
+    /// entirely) off screen.
+    Move(i32, i32),
     /// Mark this window as stacking below the given Xid
     StackBelow(Xid),
     /// Mark this window as stacking on top of its peer
@@ -75,6 +89,34 @@ pub enum ClientAttr {
     RootEventMask,
 }
 
+/// A physical output (such as a monitor connector) known to the X server.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Output {
+    /// The name of the output as reported by the X server (e.g. `eDP-1`)
+    pub name: String,
+    /// Whether or not a monitor is currently connected to this output
+    pub connected: bool,
+    /// The region of the root window shown on this output if it is currently enabled
+    pub r: Option<Rect>,
+    /// Whether or not this is the primary output
+    pub primary: bool,
+    /// The rotation applied to this output
+    pub rotation: Rotation,
+}
+
+/// The requested position, resolution and primary status of an enabled [Output].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OutputConfig {
+    /// The name of the output to enable
+    pub name: String,
+    /// The position and resolution to use for the output
+    pub r: Rect,
+    /// Whether or not this output should be the primary output
+    pub primary: bool,
+}
+
 /// A handle on a running X11 connection that we can use for issuing X requests.
 ///
 /// XConn is intended as an abstraction layer to allow for communication with the underlying
@@ -87,14 +129,81 @@ pub trait XConn {
     fn root(&self) -> Xid;
     /// Ask the X server for the dimensions of each currently available screen.
     fn screen_details(&self) -> Result<Vec<Rect>>;
+    /// Ask the X server for the DPI of each currently available screen, in the same order as
+    /// [XConn::screen_details]. `None` is returned for screens without a known physical size.
+    ///
+    /// The default implementation reports all screens as having an unknown DPI.
+    fn screen_dpi(&self) -> Result<Vec<Option<f64>>> {
+        Ok(self.screen_details()?.iter().map(|_| None).collect())
+    }
+    /// Ask the X server for the details of each output that it knows about, whether or not
+    /// it is currently connected.
+    ///
+    /// The default implementation reports no outputs.
+    fn outputs(&self) -> Result<Vec<Output>> {
+        Ok(Vec::new())
+    }
+    /// Enable each of the given outputs with the requested position and resolution, disabling
+    /// all other outputs.
+    ///
+    /// The default implementation returns an error as outputs can not be configured.
+    #[allow(unused_variables)]
+    fn configure_outputs(&self, outputs: &[OutputConfig]) -> Result<()> {
+        Err(Error::Randr(
+            "configuring outputs is not supported".to_string(),
+        ))
+    }
     /// Ask the X server for the current (x, y) coordinate of the mouse cursor.
     fn cursor_position(&self) -> Result<Point>;
 
+    /// Ask the X server for the current keyboard mapping.
+    ///
+    /// The default implementation returns an empty [Keymap], in which case key bindings are
+    /// not updated when the keyboard mapping changes.
+    fn keymap(&self) -> Result<Keymap> {
+        Ok(Keymap::default())
+    }
+
+    /// Ask the X server for the names of the configured keyboard layouts (XKB groups), in
+    /// group order.
+    ///
+    /// The default implementation reports no layouts.
+    fn keyboard_layouts(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Ask the X server for the index of the active keyboard layout (XKB group).
+    ///
+    /// The default implementation always reports the first layout as being active.
+    fn keyboard_layout(&self) -> Result<u8> {
+        Ok(0)
+    }
+
+    /// Switch to the keyboard layout (XKB group) with the given index.
+    ///
+    /// The default implementation does nothing.
+    #[allow(unused_variables)]
+    fn set_keyboard_layout(&self, group: u8) -> Result<()> {
+        Ok(())
+    }
+
     /// Grab the specified key and mouse states, intercepting them for processing within
-    /// the window manager itself.
+    /// the window manager itself. Any previously grabbed key and mouse states are released.
     fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()>;
     /// Block and wait for the next event from the X server so it can be processed.
     fn next_event(&self) -> Result<XEvent>;
+
+    /// Wait for the next event from the X server, giving up once `deadline` has passed or
+    /// once the file descriptor `wake` becomes readable.
+    ///
+    /// `Ok(None)` is returned if no event was received before the deadline, if the wait was
+    /// woken or if it was interrupted by a signal. The default implementation ignores both
+    /// the deadline and `wake`, blocking on [XConn::next_event]: with it, timers scheduled
+    /// using [State::run_after] are only run once the next event has been received.
+    #[allow(unused_variables)]
+    fn next_event_until(&self, deadline: Option<Instant>, wake: RawFd) -> Result<Option<XEvent>> {
+        self.next_event().map(Some)
+    }
     /// Flush any pending events to the X server.
     fn flush(&self);
 
@@ -114,6 +223,14 @@ pub trait XConn {
     fn unmap(&self, client: Xid) -> Result<()>;
     /// Kill the given client window, closing it.
     fn kill(&self, client: Xid) -> Result<()>;
+    /// Forcibly disconnect the client owning the given window from the X server, without
+    /// first asking it to close.
+    ///
+    /// This is used when a client fails to close after a call to [XConn::kill]. The default
+    /// implementation calls [XConn::kill].
+    fn force_kill(&self, client: Xid) -> Result<()> {
+        self.kill(client)
+    }
     /// Set X input focus to be held by the given client window.
     fn focus(&self, client: Xid) -> Result<()>;
 
@@ -143,18 +260,78 @@ pub trait XConn {
     /// This method should not be called directly: use `warp_pointer_to_window` or `warp_pointer_to_screen`
     /// instead.
     fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()>;
+
+    /// Take ownership of the ICCCM `WM_S0` manager selection, announcing to other clients
+    /// that a window manager is now running.
+    ///
+    /// The default implementation does nothing.
+    fn acquire_wm_selection(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Release the manager selection taken using [XConn::acquire_wm_selection] so that
+    /// other clients can see that the window manager has exited.
+    ///
+    /// The default implementation does nothing.
+    fn release_wm_selection(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Draw (or move) an outline of the given region above all other windows, used to preview
+    /// the result of resizing a window without resizing the window itself.
+    ///
+    /// The default implementation does not draw anything.
+    #[allow(unused_variables)]
+    fn show_outline(&self, r: Rect, width: u32, color: Color) -> Result<()> {
+        Ok(())
+    }
+
+    /// Remove the outline drawn by [XConn::show_outline] if there is one.
+    ///
+    /// The default implementation does nothing.
+    fn hide_outline(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Actively grab the pointer so that all pointer motion and button release events are
+    /// reported to the window manager until [XConn::ungrab_pointer] is called, regardless of
+    /// any mouse bindings. This is used to drive moves and resizes requested by clients.
+    ///
+    /// The default implementation does nothing.
+    fn grab_pointer(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Release a grab made using [XConn::grab_pointer].
+    ///
+    /// The default implementation does nothing.
+    fn ungrab_pointer(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Extended functionality for [XConn] impls in order to run the window manager.
 pub trait XConnExt: XConn + Sized {
     /// Kill the focused client if there is one
+    ///
+    /// If [Config::kill_timeout] is set then the client is forcibly killed if it is still
+    /// running once the timeout has elapsed.
     fn kill_focused(&self, state: &mut State<Self>) -> Result<()> {
         if let Some(&id) = state.client_set.current_client() {
-            self.kill(id)?;
+            kill_with_timeout(id, state, self)?;
         }
 
         Ok(())
     }
+
+    /// Kill the given client
+    ///
+    /// As with [XConnExt::kill_focused], the client is forcibly killed if it is still running
+    /// once [Config::kill_timeout] has elapsed (if set).
+    fn kill_client(&self, client: Xid, state: &mut State<Self>) -> Result<()> {
+        kill_with_timeout(client, state, self)
+    }
+
     /// Establish the window manager state for the given client window and refresh the
     /// current X state.
     fn manage(&self, id: Xid, state: &mut State<Self>) -> Result<()> {
@@ -167,6 +344,8 @@ pub trait XConnExt: XConn + Sized {
     /// current X state.
     fn unmanage(&self, client: Xid, state: &mut State<Self>) -> Result<()> {
         trace!(?client, "removing client");
+        state.client_data.remove(&client);
+        state.forget_hidden_client(client);
         self.modify_and_refresh(state, |cs| {
             cs.remove_client(&client);
         })
@@ -221,30 +400,25 @@ pub trait XConnExt: XConn + Sized {
         F: FnMut(&mut ClientSet),
     {
         f(&mut state.client_set); // mutating the existing state
+        state.client_set.sync_transients();
 
         let ss = state.position_and_snapshot(self);
         state.diff.update(ss);
 
-        notify_killed(self, state)?;
-        set_window_props(self, state)?;
-        notify_hidden_workspaces(state);
-        self.position_clients(state.config.border_width, &state.diff.after.positions)?;
-        set_window_visibility(self, state)?;
-        set_focus(self, state)?;
-        handle_pointer_change(self, state)?;
-
-        // TODO: clear enterWindow events from the event queue if this was because of mouse focus (?)
-
-        let mut hook = state.config.refresh_hook.take();
-        if let Some(ref mut h) = hook {
-            trace!("running user refresh hook");
-            if let Err(e) = h.call(state, self) {
-                error!(%e, "error returned from user refresh hook");
-            }
+        apply_refresh(self, state, None)
+    }
+
+    /// Mark the given client as urgent (or clear its urgent status), running the user urgency
+    /// hook and refreshing if this results in a change to the set of urgent clients.
+    ///
+    /// Requests to mark the currently focused client (or an unknown client) as urgent are ignored.
+    fn set_client_urgency(&self, client: Xid, urgent: bool, state: &mut State<Self>) -> Result<()> {
+        if !state.update_urgency(client, urgent) {
+            return Ok(());
         }
-        state.config.refresh_hook = hook;
 
-        Ok(())
+        run_urgency_hook(self, state);
+        self.refresh(state)
     }
 
     /// Refresh the current X server state based on a diff of the current state against the state
@@ -253,8 +427,46 @@ pub trait XConnExt: XConn + Sized {
         self.modify_and_refresh(state, |_| ())
     }
 
-     /// Check whether or not the given client should be assigned bar status or not.
-     fn client_should_bar(&self, client: Xid, bar_classes: &[String]) -> Result<bool> {
+    /// Refresh the X server state after changes that only affect the screen with the given
+    /// index, re-running the layout and repositioning clients on that screen alone.
+    ///
+    /// Clients on all other screens are left where they were following the last refresh, so
+    /// this should only be used when nothing has been changed on those screens: if in doubt,
+    /// use [refresh][XConnExt::refresh] instead. Focus, visibility and the user refresh hook
+    /// are handled in the same way as for a full refresh.
+    fn refresh_screen(&self, state: &mut State<Self>, index: usize) -> Result<()> {
+        state.client_set.sync_transients();
+        let ss = state.position_screen_and_snapshot(self, Some(index));
+        state.diff.update(ss);
+
+        let on_screen = state.clients_on_screen(index);
+
+        apply_refresh(self, state, Some(&on_screen))
+    }
+
+    /// Refresh the X server state after changes that only affect the workspace with the
+    /// given tag.
+    ///
+    /// If the workspace is visible this is equivalent to calling
+    /// [refresh_screen][XConnExt::refresh_screen] for the screen it is on. Otherwise no
+    /// clients are repositioned.
+    fn refresh_workspace(&self, state: &mut State<Self>, tag: &str) -> Result<()> {
+        let screen = state.client_set.screens().find(|s| s.workspace.tag == tag);
+
+        match screen.map(|s| s.index) {
+            Some(index) => self.refresh_screen(state, index),
+            None => {
+                state.client_set.sync_transients();
+                let ss = state.position_screen_and_snapshot(self, None);
+                state.diff.update(ss);
+
+                apply_refresh(self, state, Some(&HashSet::new()))
+            }
+        }
+    }
+
+    /// Check whether or not the given client should be assigned bar status or not.
+    fn client_should_bar(&self, client: Xid, bar_classes: &[String]) -> Result<bool> {
         trace!(%client, "fetching WmClass prop");
         if let Some(Prop::UTF8String(strs)) = self.get_prop(client, Atom::WmClass.as_ref())? {
             if strs.iter().any(|c| bar_classes.contains(c)) {
@@ -324,6 +536,11 @@ pub trait XConnExt: XConn + Sized {
             r = hints.apply_to(r);
         }
 
+        if r.is_degenerate() {
+            let bounds = Rect::new(r.x, r.y, r.w.max(1), r.h.max(1));
+            r = invalid_position(client, r, &bounds);
+        }
+
         trace!(%client, ?r, "positioning client");
         self.set_client_config(client, &[ClientConfig::Position(r)])
     }
@@ -425,6 +642,98 @@ pub trait XConnExt: XConn + Sized {
         }
     }
 
+    /// Check whether the given `_NET_WM_STATE` atom is currently set for a client window.
+    fn has_net_wm_state(&self, id: Xid, atom: Atom) -> Result<bool> {
+        match self.get_prop(id, Atom::NetWmState.as_ref())? {
+            Some(Prop::Atom(atoms)) => Ok(atoms.iter().any(|a| a == atom.as_ref())),
+            Some(Prop::Cardinal(vals)) => Ok(vals.contains(&*self.intern_atom(atom.as_ref())?)),
+            _ => Ok(false),
+        }
+    }
+
+    /// Add or remove the given atom from the `_NET_WM_STATE` of a client window, leaving any
+    /// other states it has set in place.
+    fn set_net_wm_state(&self, id: Xid, atom: Atom, enabled: bool) -> Result<()> {
+        let net_wm_state = Atom::NetWmState.as_ref();
+        let mut atoms = match self.get_prop(id, net_wm_state)? {
+            Some(Prop::Atom(atoms)) => atoms,
+            Some(Prop::Cardinal(vals)) => vals
+                .into_iter()
+                .map(|v| self.atom_name(Xid(v)))
+                .collect::<Result<Vec<_>>>()?,
+            _ => vec![],
+        };
+
+        atoms.retain(|a| a != atom.as_ref());
+        if enabled {
+            atoms.push(atom.as_ref().to_string());
+        }
+
+        self.set_prop(id, net_wm_state, Prop::Atom(atoms))
+    }
+
+    /// Whether a client window should be left out of taskbars, window switchers and window
+    /// title displays, as requested by setting `_NET_WM_STATE_SKIP_TASKBAR`.
+    ///
+    /// Clients whose state can not be read are assumed to not skip the taskbar.
+    fn skips_taskbar(&self, id: Xid) -> bool {
+        self.has_net_wm_state(id, Atom::NetWmStateSkipTaskbar)
+            .unwrap_or(false)
+    }
+
+    /// Whether a client window should be left out of pagers, as requested by setting
+    /// `_NET_WM_STATE_SKIP_PAGER`.
+    ///
+    /// Clients whose state can not be read are assumed to not skip the pager.
+    fn skips_pager(&self, id: Xid) -> bool {
+        self.has_net_wm_state(id, Atom::NetWmStateSkipPager)
+            .unwrap_or(false)
+    }
+
+    /// Fetch the `_MOTIF_WM_HINTS` set by the given client, if there are any.
+    fn motif_hints(&self, id: Xid) -> Result<Option<MotifWmHints>> {
+        match self.get_prop(id, Atom::MotifWmHints.as_ref())? {
+            Some(Prop::MotifWmHints(hints)) => Ok(Some(hints)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Determine the ICCCM [InputModel] of the given client from its `WM_HINTS` and
+    /// `WM_PROTOCOLS` properties.
+    fn input_model(&self, id: Xid) -> Result<InputModel> {
+        let accepts_input = match self.get_prop(id, Atom::WmHints.as_ref())? {
+            Some(Prop::WmHints(WmHints { accepts_input, .. })) => accepts_input,
+            _ => true,
+        };
+        let take_focus = self.client_supports_protocol(id, Atom::WmTakeFocus.as_ref())?;
+
+        Ok(InputModel::new(accepts_input, take_focus))
+    }
+
+    /// Give keyboard focus to the given client following its ICCCM [InputModel].
+    ///
+    /// Input focus is only set directly for clients that accept input and clients that support
+    /// `WM_TAKE_FOCUS` are sent a message asking them to take focus. Focus is moved to the root
+    /// window for clients that never accept keyboard input.
+    fn set_client_focus(&self, id: Xid) -> Result<()> {
+        // Clients we are unable to query are assumed to follow the default model
+        let model = self.input_model(id).unwrap_or_default();
+        trace!(%id, ?model, "setting client focus");
+
+        match model {
+            InputModel::NoInput => self.focus(self.root())?,
+            InputModel::Passive | InputModel::LocallyActive => self.focus(id)?,
+            InputModel::GloballyActive => (),
+        }
+
+        if model.takes_focus() {
+            let msg = ClientMessageKind::TakeFocus(id).as_message(self)?;
+            self.send_client_message(msg)?;
+        }
+
+        Ok(())
+    }
+
     /// Request a window's PID via the _NET_WM_PID property.
     ///
     /// **NOTE**: Not all programs set this property.
@@ -461,28 +770,28 @@ pub(crate) fn manage_without_refresh<X: XConn>(
     state: &mut State<X>,
     x: &X,
 ) -> Result<()> {
-    trace!(%id, "fetching WmTransientFor prop");
-    let (owned_tag, transient_for) = match x.get_prop(id, Atom::WmTransientFor.as_ref())? {
-        Some(Prop::Window(ids)) => {
-            let parent = ids[0];
-            let owned_tag = state
-                .client_set
-                .tag_for_client(&parent)
-                .or(tag)
-                .map(|t| t.to_string());
-
-            (owned_tag, Some(parent))
-        }
-
-        _ => (tag.map(|t| t.to_string()), None),
-    };
+    let transient_for = transient_for(id, x)?;
+    let owned_tag = transient_for
+        .and_then(|parent| state.client_set.tag_for_client(&parent))
+        .or(tag)
+        .map(|t| t.to_string());
 
     let should_float =
         transient_for.is_some() || x.client_should_float(id, &state.config.floating_classes)?;
+    let fixed_size =
+        !should_float && state.config.float_fixed_size_clients && x.query_or(false, &FixedSize, id);
 
-    match owned_tag {
-        Some(tag) => state.client_set.insert_as_focus_for(tag.as_ref(), id),
-        None => state.client_set.insert(id),
+    let tag = owned_tag.unwrap_or_else(|| state.client_set.current_tag().to_string());
+    let prev_focus = state
+        .client_set
+        .workspace(&tag)
+        .and_then(|w| w.focus().copied());
+    let pos = state.config.insert_position;
+    state.client_set.insert_at_for(&tag, pos, id);
+
+    if let Some(parent) = transient_for.filter(|p| state.client_set.contains(p)) {
+        let modal = x.has_net_wm_state(id, Atom::NetWmStateModal)?;
+        state.client_set.set_transient_for(id, parent, modal);
     }
 
     if x.client_should_bar(id, &state.config.bar_classes)? {
@@ -492,13 +801,21 @@ pub(crate) fn manage_without_refresh<X: XConn>(
         }
     }
 
-    if should_float {
-        let r = floating_client_position(id, transient_for, state, x)?;
+    if should_float || fixed_size {
+        // Fixed size clients are always centered so that they are not letterboxed
+        let placement = if fixed_size {
+            FloatingPlacement::Center
+        } else {
+            state.config.floating_placement
+        };
+        let r = floating_client_position(id, placement, state, x)?;
         if state.client_set.float(id, r).is_err() {
             error!(%id, "attempted to float client which was not in state");
         }
     }
 
+    update_motif_hints(id, state, x)?;
+
     let mut hook = state.config.manage_hook.take();
     if let Some(ref mut h) = hook {
         trace!("running user manage hook");
@@ -508,15 +825,202 @@ pub(crate) fn manage_without_refresh<X: XConn>(
     }
     state.config.manage_hook = hook;
 
+    // This is done after running the manage hook so that any hook repositioning the client
+    // is relative to the previously focused client.
+    apply_new_client_focus(id, &tag, prev_focus, transient_for, state, x);
+
     Ok(())
 }
 
-/// When positioning a floating client we try to position them in priority order of:
-///   - centered in their parent's screen (if transient)
-///   - centered in the focused screen
-fn floating_client_position<X: XConn>(
+// Focus a newly managed client or restore focus to the client that was focused on its
+// workspace before it was inserted, depending on the configured focus policy.
+fn apply_new_client_focus<X: XConn>(
     id: Xid,
+    tag: &str,
+    prev_focus: Option<Xid>,
     transient_for: Option<Xid>,
+    state: &mut State<X>,
+    x: &X,
+) {
+    let cs = &mut state.client_set;
+    let policy = state.config.new_client_focus;
+    let client_tag = cs.tag_for_client(&id);
+    let is_dialog_of_focused = transient_for.is_some() && transient_for == prev_focus;
+
+    if is_dialog_of_focused || policy.allows_focus(client_tag == Some(cs.current_tag())) {
+        cs.focus_client_on_its_workspace(&id);
+        return;
+    }
+
+    trace!(%id, ?policy, "not focusing new client");
+    if let Some(prev) = prev_focus.filter(|_| client_tag == Some(tag)) {
+        cs.focus_client_on_its_workspace(&prev);
+    }
+
+    if policy == FocusPolicy::MarkUrgent && state.update_urgency(id, true) {
+        run_urgency_hook(x, state);
+    }
+}
+
+/// How newly managed floating clients are positioned on the screen.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FloatingPlacement {
+    /// Use the position requested by the client
+    AsRequested,
+    /// Center the client on its screen: the screen of the parent for transient clients,
+    /// otherwise the focused screen
+    #[default]
+    Center,
+    /// Center transient clients over their parent window, falling back to [Center][0] for
+    /// clients that are not transient
+    ///
+    ///   [0]: FloatingPlacement::Center
+    CenterOverParent,
+    /// Center the client on the current position of the mouse pointer
+    UnderPointer,
+    /// Place the client where it overlaps the least with the other floating clients on its
+    /// workspace
+    Smart,
+}
+
+/// When the mouse pointer should be warped following a change in focus.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PointerWarp {
+    /// Never warp the pointer
+    Never,
+    /// Warp the pointer to the center of the newly focused client
+    Client,
+    /// Warp the pointer to the newly focused screen (to the center of its focused client if
+    /// there is one, otherwise to the center of the screen) when focus moves between screens
+    Screen,
+    /// Warp to the newly focused client, or to the newly focused screen if it has no clients
+    ClientAndScreen,
+    /// Behave as [ClientAndScreen][0] if [Config::focus_follow_mouse][1] is enabled and as
+    /// [Never][2] otherwise
+    ///
+    ///   [0]: PointerWarp::ClientAndScreen
+    ///   [1]: crate::core::Config::focus_follow_mouse
+    ///   [2]: PointerWarp::Never
+    #[default]
+    FocusFollowsMouse,
+}
+
+impl PointerWarp {
+    /// The warping behaviour to use given the current focus follows mouse setting.
+    pub fn resolve(&self, focus_follow_mouse: bool) -> Self {
+        match self {
+            Self::FocusFollowsMouse if focus_follow_mouse => Self::ClientAndScreen,
+            Self::FocusFollowsMouse => Self::Never,
+            warp => *warp,
+        }
+    }
+
+    /// Whether or not the pointer should be warped to a newly focused client.
+    pub fn warps_to_client(&self) -> bool {
+        matches!(self, Self::Client | Self::ClientAndScreen)
+    }
+
+    /// Whether or not the pointer should be warped to a newly focused screen.
+    pub fn warps_to_screen(&self) -> bool {
+        matches!(self, Self::Screen | Self::ClientAndScreen)
+    }
+}
+
+/// Whether or not clients are given focus when they are first mapped or when they request
+/// focus using `_NET_ACTIVE_WINDOW`.
+///
+/// This can be used to prevent applications such as web browsers and chat clients from
+/// stealing focus while you are working in another window.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FocusPolicy {
+    /// Always give the client focus
+    #[default]
+    Always,
+    /// Never give the client focus
+    Never,
+    /// Only give the client focus if it is on the current workspace
+    CurrentWorkspace,
+    /// Mark the client as urgent rather than giving it focus
+    MarkUrgent,
+}
+
+impl FocusPolicy {
+    /// Whether or not this policy allows focus to be given to a client, based on whether or
+    /// not that client is on the current workspace.
+    pub fn allows_focus(&self, on_current_workspace: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::CurrentWorkspace => on_current_workspace,
+            Self::Never | Self::MarkUrgent => false,
+        }
+    }
+}
+
+/// How a client expects to be given keyboard focus, as described in the ICCCM [spec][1].
+///
+/// The input model is determined by the input field of the client's `WM_HINTS` and whether or
+/// not it lists `WM_TAKE_FOCUS` in its `WM_PROTOCOLS`. Clients that do not set an input hint
+/// are treated as accepting input.
+///
+/// [1]: https://www.x.org/releases/X11R7.6/doc/xorg-docs/specs/ICCCM/icccm.html#input_focus
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InputModel {
+    /// The client never expects keyboard input
+    NoInput,
+    /// The client expects the window manager to give it input focus
+    #[default]
+    Passive,
+    /// The client expects the window manager to give it input focus and may move focus
+    /// between its own windows in response to `WM_TAKE_FOCUS`
+    LocallyActive,
+    /// The client sets input focus itself in response to `WM_TAKE_FOCUS`, possibly on a
+    /// different window (such as a Java focus proxy)
+    GloballyActive,
+}
+
+impl InputModel {
+    /// Determine the input model from a client's input hint and whether or not it supports
+    /// the `WM_TAKE_FOCUS` protocol.
+    pub fn new(accepts_input: bool, take_focus: bool) -> Self {
+        match (accepts_input, take_focus) {
+            (false, false) => Self::NoInput,
+            (true, false) => Self::Passive,
+            (true, true) => Self::LocallyActive,
+            (false, true) => Self::GloballyActive,
+        }
+    }
+
+    /// Whether or not the window manager should set input focus on the client directly.
+    pub fn accepts_input(&self) -> bool {
+        matches!(self, Self::Passive | Self::LocallyActive)
+    }
+
+    /// Whether or not the client should be sent `WM_TAKE_FOCUS` when it is focused.
+    pub fn takes_focus(&self) -> bool {
+        matches!(self, Self::LocallyActive | Self::GloballyActive)
+    }
+}
+
+fn transient_for<X: XConn>(id: Xid, x: &X) -> Result<Option<Xid>> {
+    trace!(%id, "fetching WmTransientFor prop");
+    match x.get_prop(id, Atom::WmTransientFor.as_ref())? {
+        Some(Prop::Window(ids)) => Ok(ids.first().copied()),
+        _ => Ok(None),
+    }
+}
+
+/// Compute the position of a floating client using the given [FloatingPlacement].
+///
+/// If the client can not be placed as requested (for example because it is too large to
+/// fit on the screen) we fall back to centering it on the focused screen, and failing that
+/// the position requested by the client.
+pub fn floating_client_position<X: XConn>(
+    id: Xid,
+    placement: FloatingPlacement,
     state: &State<X>,
     x: &X,
 ) -> Result<Rect> {
@@ -525,12 +1029,54 @@ fn floating_client_position<X: XConn>(
     if state.client_set.bars.contains_key(&id) {
         return Ok(r_initial);
     }
-    let r_screen = transient_for
-        .and_then(|parent| state.client_set.screen_for_client(&parent))
-        .unwrap_or(&state.client_set.screens.focus)
+
+    let cs = &state.client_set;
+    let parent = transient_for(id, x)?;
+    let r_screen = parent
+        .and_then(|parent| cs.screen_for_client(&parent))
+        .or_else(|| cs.screen_for_client(&id))
+        .unwrap_or(&cs.screens.focus)
         .r;
 
-    Ok(r_initial.centered_in(&r_screen).unwrap_or_else(|| {
+    let placed = match placement {
+        FloatingPlacement::AsRequested => return Ok(r_initial),
+
+        FloatingPlacement::Center => r_initial.centered_in(&r_screen),
+
+        FloatingPlacement::CenterOverParent => match parent {
+            Some(parent) => x.client_geometry(parent).ok().and_then(|r_parent| {
+                r_initial.centered_on_point_in(r_parent.midpoint(), &r_screen)
+            }),
+            None => r_initial.centered_in(&r_screen),
+        },
+
+        FloatingPlacement::UnderPointer => {
+            let p = x.cursor_position()?;
+            let r_screen = cs
+                .screens()
+                .map(|s| s.r)
+                .find(|r| r.contains_point(p))
+                .unwrap_or(r_screen);
+
+            r_initial.centered_on_point_in(p, &r_screen)
+        }
+
+        FloatingPlacement::Smart => {
+            let others: Vec<Rect> = cs
+                .tag_for_client(&id)
+                .and_then(|tag| cs.workspace(tag))
+                .into_iter()
+                .flat_map(|w| w.clients())
+                .filter(|&&c| c != id)
+                .filter_map(|c| cs.floating.get(c))
+                .map(|rr| rr.applied_to(&r_screen))
+                .collect();
+
+            r_initial.least_overlapping_in(&r_screen, &others)
+        }
+    };
+
+    Ok(placed.unwrap_or_else(|| {
         r_initial
             .centered_in(&state.client_set.screens.focus.r)
             .unwrap_or(r_initial)
@@ -538,8 +1084,40 @@ fn floating_client_position<X: XConn>(
 }
 
 fn notify_killed<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
-    for &c in state.diff.killed_clients() {
-        x.kill(c)?;
+    let killed: Vec<Xid> = state.diff.killed_clients().copied().collect();
+    for c in killed {
+        kill_with_timeout(c, state, x)?;
+    }
+
+    Ok(())
+}
+
+// Record the current _MOTIF_WM_HINTS of a client as client data so that they are available
+// when laying out the client (and to manage hooks) without needing to query the X server.
+pub(crate) fn update_motif_hints<X: XConn>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    match x.motif_hints(id)? {
+        Some(hints) => {
+            state.set_client_data(id, hints);
+        }
+        None => {
+            state.remove_client_data::<MotifWmHints>(&id);
+        }
+    }
+
+    Ok(())
+}
+
+// Ask the client to close, scheduling it to be forcibly killed if it is still alive once the
+// configured kill timeout has elapsed.
+fn kill_with_timeout<X: XConn>(id: Xid, state: &mut State<X>, x: &X) -> Result<()> {
+    x.kill(id)?;
+
+    if let Some(timeout) = state.config.kill_timeout {
+        state
+            .client_timeouts
+            .kills
+            .insert(id, Instant::now() + timeout);
+        state.wake_after(timeout);
     }
 
     Ok(())
@@ -580,35 +1158,248 @@ fn notify_hidden_workspaces<X: XConn>(state: &mut State<X>) {
         .for_each(|ws| ws.broadcast_message(Hide));
 }
 
+// Apply the current diff to the X server state and run the user refresh hook. If `only` is
+// provided then only the clients it contains are restacked and repositioned.
+fn apply_refresh<X: XConn>(x: &X, state: &mut State<X>, only: Option<&HashSet<Xid>>) -> Result<()> {
+    notify_killed(x, state)?;
+    set_window_props(x, state)?;
+    notify_hidden_workspaces(state);
+    position_clients(x, state, only)?;
+    set_window_visibility(x, state)?;
+    set_focus(x, state)?;
+    clear_stale_urgency(x, state);
+    state.update_focus_history();
+    handle_pointer_change(x, state)?;
+    record_pointer_position(x, state)?;
+
+    let mut hook = state.config.refresh_hook.take();
+    if let Some(ref mut h) = hook {
+        trace!("running user refresh hook");
+        if let Err(e) = h.call(state, x) {
+            error!(%e, "error returned from user refresh hook");
+        }
+    }
+    state.config.refresh_hook = hook;
+
+    Ok(())
+}
+
+// Restack and position all visible clients (or only those in `only`), updating the border width
+// of any clients that need it as a result of a layout override (or the removal of one).
+fn position_clients<X: XConn>(
+    x: &X,
+    state: &mut State<X>,
+    only: Option<&HashSet<Xid>>,
+) -> Result<()> {
+    let default = state.config.border_width;
+    let client_set = &state.client_set;
+    state
+        .layout_overrides
+        .applied_borders
+        .retain(|c, _| client_set.contains(c));
+    let screens: Vec<Rect> = client_set.screens().map(|s| s.r).collect();
+
+    let positions: Vec<(Xid, Rect)> = state
+        .diff
+        .after
+        .positions
+        .iter()
+        .filter(|(c, _)| match only {
+            Some(ids) => ids.contains(c),
+            None => true,
+        })
+        .copied()
+        .collect();
+
+    x.restack(positions.iter().map(|(id, _)| id))?;
+
+    for &(c, r) in positions.iter() {
+        let bw = state.border_width_for(c);
+        let current = state
+            .layout_overrides
+            .applied_borders
+            .get(&c)
+            .copied()
+            .unwrap_or(default);
+
+        if bw != current {
+            trace!(%c, bw, "updating client border width");
+            x.set_client_config(c, &[ClientConfig::BorderPx(bw)])?;
+        }
+
+        if bw == default {
+            state.layout_overrides.applied_borders.remove(&c);
+        } else {
+            state.layout_overrides.applied_borders.insert(c, bw);
+        }
+
+        let mut r = r.shrink_in(bw);
+        if !screens.iter().any(|s| r.overlap(s) > 0) {
+            let cs = &state.client_set;
+            // Floating positions come from clients themselves and borders can shrink a tiled
+            // position to nothing, but layouts should never place a client off screen.
+            debug_assert!(
+                r.is_degenerate() || cs.floating.contains_key(&c),
+                "layout positioned client {c} off screen: {r:?}"
+            );
+            let home = cs.screen_for_client(&c).unwrap_or(&cs.screens.focus).r;
+            r = invalid_position(c, r, &home);
+        }
+
+        x.position_client(c, r)?;
+    }
+
+    Ok(())
+}
+
+// Positions that are empty or entirely off screen confuse clients so they are never sent to
+// the X server. They can be produced from client supplied geometry (size hints or requested
+// floating positions) so they are clamped to the given bounds rather than treated as a bug.
+fn invalid_position(client: Xid, r: Rect, bounds: &Rect) -> Rect {
+    warn!(%client, ?r, ?bounds, "clamping invalid client position");
+    r.clamped_to(bounds)
+}
+
+// Run the user urgency change hook for each client whose urgency has changed, followed by the
+// user urgency hook.
+fn run_urgency_hook<X: XConn>(x: &X, state: &mut State<X>) {
+    let changes = mem::take(&mut state.urgency.changes);
+    schedule_urgency_timeouts(state, &changes);
+
+    let mut hook = state.config.urgency_change_hook.take();
+    if let Some(ref mut h) = hook {
+        for &(id, urgent) in changes.iter() {
+            trace!(%id, urgent, "running user urgency change hook");
+            if let Err(e) = h.call(id, urgent, state, x) {
+                error!(%e, "error returned from user urgency change hook");
+            }
+        }
+    }
+    state.config.urgency_change_hook = hook;
+
+    let mut hook = state.config.urgency_hook.take();
+    if let Some(ref mut h) = hook {
+        trace!("running user urgency hook");
+        if let Err(e) = h.call(state, x) {
+            error!(%e, "error returned from user urgency hook");
+        }
+    }
+    state.config.urgency_hook = hook;
+}
+
+// Wake the event loop once the urgency timeout of newly urgent clients has elapsed.
+fn schedule_urgency_timeouts<X: XConn>(state: &mut State<X>, changes: &[(Xid, bool)]) {
+    match state.config.urgency_timeout {
+        Some(timeout) if changes.iter().any(|&(_, urgent)| urgent) => state.wake_after(timeout),
+        _ => (),
+    }
+}
+
+// Clients stop being urgent once they are focused or removed from the client set, or (if
+// the user has asked for it) once their workspace becomes visible.
+fn clear_stale_urgency<X: XConn>(x: &X, state: &mut State<X>) {
+    let focused = state.client_set.current_client().copied();
+    let client_set = &state.client_set;
+    let newly_visible: Vec<&str> = if state.config.clear_urgency_on_view {
+        let previous = state.diff.previous_visible_tags();
+        state
+            .diff
+            .current_visible_tags()
+            .into_iter()
+            .filter(|t| !previous.contains(t))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let stale: Vec<Xid> = state
+        .urgency
+        .clients
+        .iter()
+        .filter(|&id| {
+            Some(*id) == focused
+                || !client_set.contains(id)
+                || client_set
+                    .tag_for_client(id)
+                    .is_some_and(|t| newly_visible.contains(&t))
+        })
+        .copied()
+        .collect();
+
+    for id in stale.iter() {
+        state.update_urgency(*id, false);
+    }
+
+    if !stale.is_empty() {
+        run_urgency_hook(x, state);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum WarpTarget {
+    Client(Xid),
+    Screen(usize),
+}
+
+// Determine where (if anywhere) the pointer should be warped to following a diff.
+fn warp_target(
+    warp: PointerWarp,
+    focused: Option<Xid>,
+    focus_changed: bool,
+    focused_client_moved: bool,
+    new_screen: Option<usize>,
+) -> Option<WarpTarget> {
+    match (focused, new_screen) {
+        // NOTE: Some of the behaviour here is based on looking at whether or
+        //       not the focused client has changed position as part of this
+        //       diff. Mouse based window movement is handled by skipping
+        //       this check entirely while a drag is in progress.
+        (Some(id), _) if warp.warps_to_client() && (focus_changed || focused_client_moved) => {
+            Some(WarpTarget::Client(id))
+        }
+        (Some(_), Some(index)) if warp == PointerWarp::Screen => Some(WarpTarget::Screen(index)),
+        (None, Some(index)) if warp.warps_to_screen() => Some(WarpTarget::Screen(index)),
+        _ => None,
+    }
+}
+
 // Warp the cursor if this diff resulted in a focus change
 fn handle_pointer_change<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
-    if !state.config.focus_follow_mouse {
+    if matches!(state.current_event, Some(XEvent::Enter(_))) || drag_in_progress(state) {
         return Ok(());
     }
 
-    trace!("checking if focus should change");
-    if !matches!(state.current_event, Some(XEvent::Enter(_))) {
-        if let Some(id) = state.diff.focused_client() {
-            trace!("focused client changed");
-            // NOTE: Some of the behaviour here is based on looking at whether or
-            //       not the focused client has changed position as part of this
-            //       diff. That is going to cause issues if and when mouse based
-            //       window movement is implemented.
-            let focus_changed = state.diff.focused_client_changed();
-            let focused_client_moved = state.diff.client_changed_position(&id);
-
-            if focus_changed || focused_client_moved {
-                trace!(
-                    focus_changed,
-                    focused_client_moved,
-                    "warping to focused client"
-                );
-                x.warp_pointer_to_window(id)?;
-            }
-        } else if let Some(index) = state.diff.newly_focused_screen() {
+    trace!("checking if the pointer should be warped");
+    let focused = state.diff.focused_client();
+    let target = warp_target(
+        state
+            .config
+            .pointer_warp
+            .resolve(state.config.focus_follow_mouse),
+        focused,
+        state.diff.focused_client_changed(),
+        focused.is_some_and(|id| state.diff.client_changed_position(&id)),
+        state.diff.newly_focused_screen(),
+    );
+
+    match target {
+        Some(WarpTarget::Client(id)) => {
+            trace!(%id, "warping to focused client");
+            x.warp_pointer_to_window(id)
+        }
+        Some(WarpTarget::Screen(index)) => {
             trace!(index, "screen changed: warping to screen");
-            x.warp_pointer_to_screen(state, index)?;
+            x.warp_pointer_to_screen(state, index)
         }
+        None => Ok(()),
+    }
+}
+
+// Track where the pointer was at the end of each refresh so that enter events caused by
+// clients moving under a stationary pointer can be ignored.
+fn record_pointer_position<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
+    if state.config.focus_follow_mouse && state.config.focus_follow_mouse_requires_motion {
+        state.focus.pointer_at_refresh = Some(x.cursor_position()?);
     }
 
     Ok(())
@@ -636,7 +1427,7 @@ fn set_window_visibility<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
 fn set_focus<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
     if let Some(&id) = state.client_set.current_client() {
         if !is_bar(id, state) {
-            x.focus(id)
+            x.set_client_focus(id)
         } else {
             x.focus(state.root)
         }
@@ -648,7 +1439,23 @@ fn set_focus<X: XConn>(x: &X, state: &mut State<X>) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{map, Error, Result};
+    use crate::{
+        core::{hooks::ManageHook, Config, WindowManager},
+        extensions::hooks::manage::InsertPosition,
+        map,
+        pure::{Position, Stack},
+        stack,
+        testing::HeadlessConn,
+        testing::Scenario,
+        x::{
+            event::PropertyEvent,
+            property::{
+                MotifDecorations, MotifWmHintsFlags, WmHintsFlags, WmNormalHints,
+                WmNormalHintsFlags,
+            },
+        },
+        Error, Result,
+    };
     use simple_test_case::test_case;
     use std::collections::HashMap;
 
@@ -686,6 +1493,119 @@ mod tests {
         }
     }
 
+    #[test_case(Xid(1), true, true, &[3, 1]; "mark unfocused client")]
+    #[test_case(Xid(3), true, false, &[3]; "already urgent")]
+    #[test_case(Xid(2), true, false, &[3]; "focused client")]
+    #[test_case(Xid(4), true, false, &[3]; "unknown client")]
+    #[test_case(Xid(3), false, true, &[]; "clear urgent client")]
+    #[test_case(Xid(1), false, false, &[3]; "clear non-urgent client")]
+    #[test]
+    fn update_urgency(id: Xid, urgent: bool, expected_changed: bool, expected: &[u32]) {
+        let conn = TransientXConn::default();
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        for n in 1..=3 {
+            state.client_set.insert(Xid(n));
+        }
+        state.client_set.focus_client(&Xid(2));
+        state.update_urgency(Xid(3), true);
+
+        let changed = state.update_urgency(id, urgent);
+        let urgent: Vec<u32> = state.urgent_clients().iter().map(|id| **id).collect();
+
+        assert_eq!(changed, expected_changed);
+        assert_eq!(urgent, expected);
+    }
+
+    #[test]
+    fn focus_history_is_most_recent_first_and_pruned() {
+        let conn = TransientXConn::default();
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        for n in 1..=3 {
+            state.client_set.insert(Xid(n));
+        }
+
+        for n in [1, 2, 3, 1] {
+            state.client_set.focus_client(&Xid(n));
+            state.update_focus_history();
+        }
+        assert_eq!(state.focus_history(), &[Xid(1), Xid(3), Xid(2)]);
+        assert_eq!(state.last_focused_client(), Some(Xid(3)));
+
+        state.client_set.remove_client(&Xid(3));
+        state.update_focus_history();
+        assert_eq!(state.focus_history(), &[Xid(1), Xid(2)]);
+        assert_eq!(state.last_focused_client(), Some(Xid(2)));
+    }
+
+    #[test]
+    fn focus_history_survives_workspace_switches() {
+        let conn = TransientXConn::default();
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        state.client_set.insert(Xid(1));
+        state.update_focus_history();
+
+        state.client_set.focus_tag("3");
+        state.client_set.insert(Xid(2));
+        state.update_focus_history();
+
+        state.client_set.focus_tag("5");
+        state.update_focus_history();
+
+        assert_eq!(state.focus_history(), &[Xid(2), Xid(1)]);
+        assert_eq!(state.last_focused_client(), Some(Xid(2)));
+    }
+
+    // Atoms are interned as their position in this list
+    const STATE_ATOMS: [Atom; 2] = [Atom::NetWmStateFullscreen, Atom::NetWmStateSkipTaskbar];
+
+    #[derive(Default)]
+    struct NetWmStateXConn {
+        net_wm_state: std::cell::RefCell<Option<Prop>>,
+    }
+
+    impl MockXConn for NetWmStateXConn {
+        fn mock_intern_atom(&self, atom: &str) -> Result<Xid> {
+            let i = STATE_ATOMS.iter().position(|a| a.as_ref() == atom).unwrap();
+            Ok(Xid(i as u32))
+        }
+
+        fn mock_atom_name(&self, xid: Xid) -> Result<String> {
+            Ok(STATE_ATOMS[*xid as usize].as_ref().to_string())
+        }
+
+        fn mock_get_prop(&self, _: Xid, _: &str) -> Result<Option<Prop>> {
+            Ok(self.net_wm_state.borrow().clone())
+        }
+
+        fn mock_set_prop(&self, _: Xid, _: &str, val: Prop) -> Result<()> {
+            *self.net_wm_state.borrow_mut() = Some(val);
+            Ok(())
+        }
+    }
+
+    #[test_case(None, false; "no existing state")]
+    #[test_case(Some(Prop::Atom(vec!["_NET_WM_STATE_FULLSCREEN".to_string()])), true; "existing atoms")]
+    #[test_case(Some(Prop::Cardinal(vec![0])), true; "existing cardinals")]
+    #[test]
+    fn set_net_wm_state_preserves_other_states(initial: Option<Prop>, fullscreen: bool) {
+        let conn = NetWmStateXConn {
+            net_wm_state: std::cell::RefCell::new(initial),
+        };
+        let has_state = |atom| conn.has_net_wm_state(Xid(1), atom).unwrap();
+
+        assert!(!conn.skips_taskbar(Xid(1)));
+
+        conn.set_net_wm_state(Xid(1), Atom::NetWmStateSkipTaskbar, true)
+            .unwrap();
+        assert!(conn.skips_taskbar(Xid(1)));
+        assert_eq!(has_state(Atom::NetWmStateFullscreen), fullscreen);
+
+        conn.set_net_wm_state(Xid(1), Atom::NetWmStateSkipTaskbar, false)
+            .unwrap();
+        assert!(!conn.skips_taskbar(Xid(1)));
+        assert_eq!(has_state(Atom::NetWmStateFullscreen), fullscreen);
+    }
+
     #[test_case(
         Rect::new(0, 0, 600, 400),
         Rect::new(0, 0, 20, 20),
@@ -738,4 +1658,309 @@ mod tests {
 
         assert_eq!(r, expected, "client position is as expected");
     }
+
+    #[test_case(FloatingPlacement::AsRequested, Rect::new(10, 10, 20, 20); "as requested")]
+    #[test_case(FloatingPlacement::Center, Rect::new(502, 374, 20, 20); "center")]
+    #[test_case(FloatingPlacement::CenterOverParent, Rect::new(290, 190, 20, 20); "over parent")]
+    #[test]
+    fn manage_without_refresh_floating_placement(placement: FloatingPlacement, expected: Rect) {
+        let conn = TransientXConn {
+            transient_ids: map! {
+                Xid(1) => Xid(2),
+            },
+            geometry: map! {
+                Xid(1) => Rect::new(10, 10, 20, 20),
+                Xid(2) => Rect::new(0, 0, 600, 400),
+            },
+        };
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        state.config.floating_placement = placement;
+        state.client_set.insert(Xid(2));
+
+        manage_without_refresh(Xid(1), None, &mut state, &conn).expect("refresh");
+
+        let r = state.client_set.floating[&Xid(1)].applied_to(&TEST_SCREEN);
+
+        assert_eq!(r, expected);
+    }
+
+    #[test_case(true, (300, 200), true; "fixed size")]
+    #[test_case(true, (600, 400), false; "resizable")]
+    #[test_case(false, (300, 200), false; "disabled")]
+    #[test]
+    fn manage_without_refresh_fixed_size(enabled: bool, (max_w, max_h): (u32, u32), floats: bool) {
+        let conn = HeadlessConn::new(vec![TEST_SCREEN]);
+        let id = conn.create_window("splash");
+        let hints = WmNormalHints::new(
+            WmNormalHintsFlags::P_MIN_SIZE | WmNormalHintsFlags::P_MAX_SIZE,
+            None,
+            Some(Rect::new(0, 0, 300, 200)),
+            Some(Rect::new(0, 0, max_w, max_h)),
+            None,
+        );
+        conn.set_prop(id, Atom::WmNormalHints.as_ref(), Prop::WmNormalHints(hints))
+            .unwrap();
+
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        state.config.float_fixed_size_clients = enabled;
+        state.config.floating_placement = FloatingPlacement::AsRequested;
+
+        manage_without_refresh(id, None, &mut state, &conn).expect("manage");
+
+        match state.client_set.floating.get(&id) {
+            Some(r) => {
+                assert!(floats, "client should be tiled");
+                // Fixed size clients are centered regardless of the placement policy
+                assert_eq!(r.applied_to(&TEST_SCREEN), Rect::new(192, 144, 640, 480));
+            }
+            None => assert!(!floats, "client should be floating"),
+        }
+    }
+
+    #[test_case(false, false, InputModel::NoInput; "no input")]
+    #[test_case(true, false, InputModel::Passive; "passive")]
+    #[test_case(true, true, InputModel::LocallyActive; "locally active")]
+    #[test_case(false, true, InputModel::GloballyActive; "globally active")]
+    #[test]
+    fn input_model_new(accepts_input: bool, take_focus: bool, expected: InputModel) {
+        assert_eq!(InputModel::new(accepts_input, take_focus), expected);
+    }
+
+    #[test_case(None, false, true, false; "no hints")]
+    #[test_case(Some(true), false, true, false; "passive")]
+    #[test_case(Some(true), true, true, true; "locally active")]
+    #[test_case(Some(false), true, false, true; "globally active")]
+    #[test_case(Some(false), false, false, false; "no input")]
+    #[test]
+    fn refresh_follows_the_icccm_input_model(
+        input: Option<bool>,
+        take_focus: bool,
+        focused: bool,
+        sent_take_focus: bool,
+    ) {
+        let conn = HeadlessConn::new(vec![TEST_SCREEN]);
+        let id = conn.create_window("a");
+        if let Some(accepts_input) = input {
+            let hints = WmHints::new(
+                WmHintsFlags::INPUT_HINT,
+                accepts_input,
+                WmState::Normal,
+                0,
+                Xid(0),
+                Point::default(),
+                0,
+                0,
+            );
+            conn.set_prop(id, Atom::WmHints.as_ref(), Prop::WmHints(hints))
+                .unwrap();
+        }
+        if take_focus {
+            let protocols = vec![Atom::WmTakeFocus.as_ref().to_string()];
+            conn.set_prop(id, Atom::WmProtocols.as_ref(), Prop::Atom(protocols))
+                .unwrap();
+        }
+
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        manage_without_refresh(id, None, &mut state, &conn).expect("manage");
+        conn.refresh(&mut state).expect("refresh");
+
+        let take_focus_atom = *conn.intern_atom(Atom::WmTakeFocus.as_ref()).unwrap();
+        let sent = conn.sent_messages().iter().any(|m| {
+            m.id == id && m.dtype == "WM_PROTOCOLS" && m.data.as_u32()[0] == take_focus_atom
+        });
+
+        assert_eq!(conn.focused() == Some(id), focused);
+        assert_eq!(sent, sent_take_focus);
+    }
+
+    fn motif_hints(decorations: MotifDecorations) -> Prop {
+        let hints = MotifWmHints::new(MotifWmHintsFlags::DECORATIONS, 0, decorations);
+
+        Prop::MotifWmHints(hints)
+    }
+
+    #[test_case("dmenu", Some(MotifDecorations::empty()), 0; "floating undecorated")]
+    #[test_case("dmenu", Some(MotifDecorations::BORDER), 2; "floating with border")]
+    #[test_case("dmenu", None, 2; "floating without hints")]
+    #[test_case("a", Some(MotifDecorations::empty()), 2; "tiled undecorated")]
+    #[test]
+    fn floating_clients_honour_motif_border_hints(
+        class: &str,
+        decorations: Option<MotifDecorations>,
+        expected: u32,
+    ) {
+        let conn = HeadlessConn::new(vec![TEST_SCREEN]);
+        let id = conn.create_window(class);
+        if let Some(d) = decorations {
+            conn.set_prop(id, Atom::MotifWmHints.as_ref(), motif_hints(d))
+                .unwrap();
+        }
+
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        manage_without_refresh(id, None, &mut state, &conn).expect("manage");
+        conn.refresh(&mut state).expect("refresh");
+
+        assert_eq!(state.border_width_for(id), expected);
+    }
+
+    #[test]
+    fn motif_hint_changes_are_tracked() {
+        let conn = HeadlessConn::new(vec![TEST_SCREEN]);
+        let wm = WindowManager::new(Config::default(), HashMap::new(), HashMap::new(), conn)
+            .expect("valid window manager");
+        let s = Scenario::new(wm).unwrap().given_windows(["dmenu"]);
+        let id = s.id("dmenu");
+        assert_eq!(s.state().border_width_for(id), 2);
+
+        s.conn()
+            .set_prop(
+                id,
+                Atom::MotifWmHints.as_ref(),
+                motif_hints(MotifDecorations::empty()),
+            )
+            .unwrap();
+        let s = s.send(XEvent::PropertyNotify(PropertyEvent {
+            id,
+            atom: Atom::MotifWmHints.as_ref().to_string(),
+            is_root: false,
+        }));
+
+        assert_eq!(s.state().border_width_for(id), 0);
+        assert!(s.state().client_data::<MotifWmHints>(&id).is_some());
+    }
+
+    #[test]
+    fn off_screen_floating_positions_are_clamped() {
+        let conn = HeadlessConn::new(vec![TEST_SCREEN]);
+        let id = conn.create_window("a");
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        manage_without_refresh(id, None, &mut state, &conn).expect("manage");
+        state
+            .client_set
+            .float_unchecked(id, Rect::new(2000, 2000, 100, 100));
+
+        conn.refresh(&mut state).expect("refresh to succeed");
+
+        let r = conn.window(id).unwrap().geometry;
+        assert!(r.overlap(&TEST_SCREEN) > 0, "{r:?} is off screen");
+    }
+
+    #[test_case(Position::Focus, None, stack!([3], 4, [2, 1]); "focus")]
+    #[test_case(Position::Before, None, stack!([3], 4, [2, 1]); "before")]
+    #[test_case(Position::After, None, stack!([3, 2], 4, [1]); "after")]
+    #[test_case(Position::Head, None, stack!(4, [3, 2, 1]); "head")]
+    #[test_case(Position::Tail, None, stack!([3, 2, 1], 4); "tail")]
+    #[test_case(Position::Focus, Some(Position::Tail), stack!([3, 2, 1], 4); "hook overrides focus")]
+    #[test_case(Position::Tail, Some(Position::After), stack!([3, 2], 4, [1]); "hook overrides tail")]
+    #[test]
+    fn manage_without_refresh_insert_position(
+        pos: Position,
+        hook_pos: Option<Position>,
+        expected: Stack<u32>,
+    ) {
+        let conn = TransientXConn::default();
+        let config = Config {
+            insert_position: pos,
+            manage_hook: hook_pos.map(|p| Box::new(InsertPosition(p)) as Box<dyn ManageHook<_>>),
+            ..Default::default()
+        };
+        let mut state = State::try_new(config, &conn).expect("test state");
+        for n in 1..=3 {
+            state.client_set.insert(Xid(n));
+        }
+        state.client_set.focus_client(&Xid(2));
+
+        manage_without_refresh(Xid(4), None, &mut state, &conn).expect("refresh");
+
+        let s = state.client_set.current_stack().expect("a stack").clone();
+        assert_eq!(s.map(|id| id.0), expected);
+    }
+
+    #[test_case(FocusPolicy::Always, None, 1, false; "always")]
+    #[test_case(FocusPolicy::Never, None, 10, false; "never")]
+    #[test_case(FocusPolicy::CurrentWorkspace, None, 1, false; "current workspace")]
+    #[test_case(FocusPolicy::CurrentWorkspace, Some("2"), 20, false; "other workspace")]
+    #[test_case(FocusPolicy::MarkUrgent, None, 10, true; "mark urgent")]
+    #[test]
+    fn manage_without_refresh_focus_policy(
+        policy: FocusPolicy,
+        tag: Option<&str>,
+        expected_focus: u32,
+        urgent: bool,
+    ) {
+        let conn = TransientXConn::default();
+        let config = Config {
+            new_client_focus: policy,
+            ..Default::default()
+        };
+        let mut state = State::try_new(config, &conn).expect("test state");
+        state.client_set.insert(Xid(20));
+        state.client_set.move_client_to_tag(&Xid(20), "2");
+        state.client_set.insert(Xid(10));
+
+        manage_without_refresh(Xid(1), tag, &mut state, &conn).expect("refresh");
+
+        let cs = &state.client_set;
+        let w = cs.workspace(cs.tag_for_client(&Xid(1)).unwrap()).unwrap();
+        assert_eq!(w.focus(), Some(&Xid(expected_focus)));
+        assert_eq!(state.urgent_clients().contains(&Xid(1)), urgent);
+    }
+
+    #[test]
+    fn dialogs_of_the_focused_client_are_always_focused() {
+        let conn = TransientXConn {
+            transient_ids: map! {
+                Xid(1) => Xid(2),
+            },
+            geometry: map! {
+                Xid(1) => Rect::new(10, 10, 20, 20),
+                Xid(2) => Rect::new(0, 0, 600, 400),
+            },
+        };
+        let mut state = State::try_new(Default::default(), &conn).expect("test state");
+        state.config.new_client_focus = FocusPolicy::Never;
+        state.client_set.insert(Xid(2));
+
+        manage_without_refresh(Xid(1), None, &mut state, &conn).expect("refresh");
+
+        assert_eq!(state.client_set.current_client(), Some(&Xid(1)));
+    }
+
+    const CLIENT: Option<WarpTarget> = Some(WarpTarget::Client(Xid(1)));
+    const SCREEN: Option<WarpTarget> = Some(WarpTarget::Screen(1));
+
+    #[test_case(PointerWarp::Never, Some(1), true, Some(1), None; "never")]
+    #[test_case(PointerWarp::Client, Some(1), true, None, CLIENT; "client focus changed")]
+    #[test_case(PointerWarp::Client, Some(1), false, None, None; "client unchanged")]
+    #[test_case(PointerWarp::Client, Some(1), true, Some(1), CLIENT; "client new screen")]
+    #[test_case(PointerWarp::Client, None, false, Some(1), None; "client empty screen")]
+    #[test_case(PointerWarp::Screen, Some(1), true, None, None; "screen same screen")]
+    #[test_case(PointerWarp::Screen, Some(1), true, Some(1), SCREEN; "screen new screen")]
+    #[test_case(PointerWarp::Screen, None, false, Some(1), SCREEN; "screen empty screen")]
+    #[test_case(PointerWarp::ClientAndScreen, Some(1), true, Some(1), CLIENT; "both new screen")]
+    #[test_case(PointerWarp::ClientAndScreen, None, false, Some(1), SCREEN; "both empty screen")]
+    #[test]
+    fn warp_target_respects_pointer_warp(
+        warp: PointerWarp,
+        focused: Option<u32>,
+        focus_changed: bool,
+        new_screen: Option<usize>,
+        expected: Option<WarpTarget>,
+    ) {
+        let target = warp_target(warp, focused.map(Xid), focus_changed, false, new_screen);
+
+        assert_eq!(target, expected);
+    }
+
+    #[test_case(PointerWarp::FocusFollowsMouse, true, PointerWarp::ClientAndScreen; "ffm enabled")]
+    #[test_case(PointerWarp::FocusFollowsMouse, false, PointerWarp::Never; "ffm disabled")]
+    #[test_case(PointerWarp::Screen, false, PointerWarp::Screen; "explicit warp")]
+    #[test]
+    fn pointer_warp_resolves_using_focus_follow_mouse(
+        warp: PointerWarp,
+        focus_follow_mouse: bool,
+        expected: PointerWarp,
+    ) {
+        assert_eq!(warp.resolve(focus_follow_mouse), expected);
+    }
 }
@@ -26,6 +26,8 @@ pub enum Prop {
     WmHints(WmHints),
     /// The WmNormalHints properties for this window
     WmNormalHints(WmNormalHints),
+    /// The MotifWmHints properties for this window
+    MotifWmHints(MotifWmHints),
 }
 
 bitflags! {
@@ -81,6 +83,45 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Possible flags that can be set in a MotifWmHints client property, marking which of its
+    /// fields are in use
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[derive(Default, Debug, PartialEq, Eq, Hash, Clone)]
+    pub struct MotifWmHintsFlags: u32 {
+        /// The functions field is set
+        const FUNCTIONS   = 0b0001;
+        /// The decorations field is set
+        const DECORATIONS = 0b0010;
+        /// The input mode field is set
+        const INPUT_MODE  = 0b0100;
+        /// The status field is set
+        const STATUS      = 0b1000;
+    }
+}
+
+bitflags! {
+    /// Window decorations that can be requested in a MotifWmHints client property
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[derive(Default, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+    pub struct MotifDecorations: u32 {
+        /// All decorations other than those explicitly listed
+        const ALL           = 0b0000001;
+        /// A border around the window
+        const BORDER        = 0b0000010;
+        /// Handles for resizing the window
+        const RESIZE_HANDLE = 0b0000100;
+        /// A title bar
+        const TITLE         = 0b0001000;
+        /// A window menu button
+        const MENU          = 0b0010000;
+        /// A minimize button
+        const MINIMIZE      = 0b0100000;
+        /// A maximize button
+        const MAXIMIZE      = 0b1000000;
+    }
+}
+
 /// Possible valid values for setting the `WM_STATE` property on a client.
 ///
 /// See the [ICCCM docs][1] for more information.
@@ -224,7 +265,9 @@ impl WmHints {
 /// data fromat but note that Penrose does not honour the following hints:
 ///   - gravity
 ///   - increment
-///   - aspect ratio
+///
+/// Aspect ratio hints are only honoured when resizing floating windows (see
+/// [WmNormalHints::apply_aspect_ratio]).
 ///
 /// [1]: https://www.x.org/releases/X11R7.6/doc/xorg-docs/specs/ICCCM/icccm.html#wm_normal_hints_property
 /// [2]: https://tronche.com/gui/x/xlib/ICC/client-to-window-manager/wm-normal-hints.html
@@ -236,6 +279,8 @@ pub struct WmNormalHints {
     pub(crate) min: Option<Rect>,
     pub(crate) max: Option<Rect>,
     pub(crate) user_specified: Option<Rect>,
+    pub(crate) min_aspect: Option<(u32, u32)>,
+    pub(crate) max_aspect: Option<(u32, u32)>,
 }
 
 impl WmNormalHints {
@@ -253,6 +298,57 @@ impl WmNormalHints {
             min,
             max,
             user_specified,
+            min_aspect: None,
+            max_aspect: None,
+        }
+    }
+
+    /// Set the minimum and maximum aspect ratios for these hints as (numerator, denominator)
+    /// pairs of width to height.
+    pub fn with_aspect_ratios(
+        mut self,
+        min_aspect: Option<(u32, u32)>,
+        max_aspect: Option<(u32, u32)>,
+    ) -> Self {
+        self.min_aspect = min_aspect;
+        self.max_aspect = max_aspect;
+
+        self
+    }
+
+    /// Constrain a given [Rect] to the aspect ratio limits in these hints (if there are any),
+    /// keeping the top left corner of the [Rect] fixed.
+    ///
+    /// If `keep_width` is true then the height of the [Rect] is adjusted to satisfy the hints,
+    /// otherwise the width is adjusted.
+    pub fn apply_aspect_ratio(&self, mut r: Rect, keep_width: bool) -> Rect {
+        if r.h == 0 {
+            return r;
+        }
+
+        let as_ratio = |(n, d): (u32, u32)| n as f64 / d as f64;
+        let ratio = r.w as f64 / r.h as f64;
+        let target = match (self.min_aspect.map(as_ratio), self.max_aspect.map(as_ratio)) {
+            (Some(min), _) if ratio < min => min,
+            (_, Some(max)) if ratio > max => max,
+            _ => return r,
+        };
+
+        if keep_width {
+            r.h = std::cmp::max(1, (r.w as f64 / target).round() as u32);
+        } else {
+            r.w = std::cmp::max(1, (r.h as f64 * target).round() as u32);
+        }
+
+        r
+    }
+
+    /// Whether or not these hints describe a window that can not be resized: one with a
+    /// minimum size that is equal to its maximum size.
+    pub fn is_fixed_size(&self) -> bool {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => (min.w, min.h) == (max.w, max.h),
+            _ => false,
         }
     }
 
@@ -319,8 +415,16 @@ impl WmNormalHints {
         let (max_w, max_h) = (raw[7], raw[8]);
         let (base_w, base_h) = (raw[15], raw[16]);
 
-        // ignoring increment, aspect ratio, gravity as they are not used in
-        // the main WindowManager logic
+        let aspect_set = flags.contains(WmNormalHintsFlags::P_ASPECT);
+        let if_aspect_set = |n, d| {
+            if aspect_set && n > 0 && d > 0 {
+                Some((n, d))
+            } else {
+                None
+            }
+        };
+
+        // ignoring increment and gravity as they are not used in the main WindowManager logic
 
         let if_set = |x, y, w, h| {
             if w > 0 && h > 0 {
@@ -336,6 +440,99 @@ impl WmNormalHints {
             min: if_set(x, y, min_w, min_h),
             max: if_set(x, y, max_w, max_h),
             user_specified: if_set(x, y, user_w, user_h),
+            min_aspect: if_aspect_set(raw[11], raw[12]),
+            max_aspect: if_aspect_set(raw[13], raw[14]),
+        })
+    }
+}
+
+/// Client requested hints about the decorations and functions the window manager should
+/// provide, set using the `_MOTIF_WM_HINTS` property.
+///
+/// This is not part of the ICCCM or EWMH specs but is widely used by toolkits and applications
+/// that draw their own decorations (such as those using client side decorations) to ask not to
+/// be decorated by the window manager. Only the decorations field is currently honoured by
+/// penrose: floating clients that do not request a border are not given one.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct MotifWmHints {
+    pub(crate) flags: MotifWmHintsFlags,
+    pub(crate) functions: u32,
+    pub(crate) decorations: MotifDecorations,
+    pub(crate) input_mode: u32,
+    pub(crate) status: u32,
+}
+
+impl MotifWmHints {
+    /// Create a new instance from component parts
+    pub fn new(flags: MotifWmHintsFlags, functions: u32, decorations: MotifDecorations) -> Self {
+        Self {
+            flags,
+            functions,
+            decorations,
+            input_mode: 0,
+            status: 0,
+        }
+    }
+
+    /// The decorations requested by the client, if it has specified any.
+    ///
+    /// If [MotifDecorations::ALL] is set by the client then this is every decoration other than
+    /// those the client explicitly listed.
+    pub fn decorations(&self) -> Option<MotifDecorations> {
+        if !self.flags.contains(MotifWmHintsFlags::DECORATIONS) {
+            return None;
+        }
+
+        let d = self.decorations;
+        if d.contains(MotifDecorations::ALL) {
+            Some(MotifDecorations::all().difference(d))
+        } else {
+            Some(d)
+        }
+    }
+
+    /// Whether or not the client requests the given decorations. Clients that have not
+    /// specified their decorations are assumed to want all of them.
+    pub fn requests_decoration(&self, d: MotifDecorations) -> bool {
+        self.decorations().is_none_or(|ds| ds.contains(d))
+    }
+
+    /// Whether or not the client has asked not to be decorated at all.
+    pub fn is_undecorated(&self) -> bool {
+        self.decorations() == Some(MotifDecorations::empty())
+    }
+
+    /// Try to construct a [MotifWmHints] instance from raw bytes.
+    ///
+    /// This method expects a slice of 5 u32s corresponding to the C struct layout shown below,
+    /// although older clients setting only the first 3 or 4 fields are also accepted.
+    ///
+    /// ```C
+    /// typedef struct {
+    ///     unsigned long flags;        /* marks which fields in this structure are defined */
+    ///     unsigned long functions;
+    ///     unsigned long decorations;
+    ///     long          input_mode;
+    ///     unsigned long status;
+    /// } PropMotifWmHints;
+    /// ```
+    pub fn try_from_bytes(raw: &[u32]) -> Result<Self> {
+        if !(3..=5).contains(&raw.len()) {
+            return Err(Error::InvalidHints {
+                reason: format!(
+                    "raw bytes should be [u32; 5] for MotifWmHints, got [u32; {}]",
+                    raw.len()
+                ),
+            });
+        }
+
+        Ok(Self {
+            flags: MotifWmHintsFlags::from_bits_truncate(raw[0]),
+            functions: raw[1],
+            decorations: MotifDecorations::from_bits_truncate(raw[2]),
+            input_mode: raw.get(3).copied().unwrap_or(0),
+            status: raw.get(4).copied().unwrap_or(0),
         })
     }
 }
@@ -386,3 +583,96 @@ impl WindowAttributes {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    fn aspect_hints() -> WmNormalHints {
+        WmNormalHints::new(WmNormalHintsFlags::P_ASPECT, None, None, None, None)
+            .with_aspect_ratios(Some((4, 3)), Some((16, 9)))
+    }
+
+    #[test_case(Rect::new(0, 0, 400, 300), true, Rect::new(0, 0, 400, 300); "within limits")]
+    #[test_case(Rect::new(0, 0, 1600, 300), true, Rect::new(0, 0, 1600, 900); "too wide keep width")]
+    #[test_case(Rect::new(0, 0, 1600, 300), false, Rect::new(0, 0, 533, 300); "too wide keep height")]
+    #[test_case(Rect::new(10, 20, 400, 600), true, Rect::new(10, 20, 400, 300); "too tall keep width")]
+    #[test_case(Rect::new(10, 20, 400, 600), false, Rect::new(10, 20, 800, 600); "too tall keep height")]
+    #[test]
+    fn apply_aspect_ratio(r: Rect, keep_width: bool, expected: Rect) {
+        assert_eq!(aspect_hints().apply_aspect_ratio(r, keep_width), expected);
+    }
+
+    #[test]
+    fn apply_aspect_ratio_without_hints_is_a_no_op() {
+        let hints = WmNormalHints::new(WmNormalHintsFlags::empty(), None, None, None, None);
+        let r = Rect::new(0, 0, 1600, 300);
+
+        assert_eq!(hints.apply_aspect_ratio(r, true), r);
+    }
+
+    #[test_case(Some((300, 200)), Some((300, 200)), true; "min equals max")]
+    #[test_case(Some((300, 200)), Some((600, 400)), false; "resizable")]
+    #[test_case(Some((300, 200)), None, false; "no max")]
+    #[test_case(None, Some((300, 200)), false; "no min")]
+    #[test]
+    fn is_fixed_size(min: Option<(u32, u32)>, max: Option<(u32, u32)>, expected: bool) {
+        let r = |(w, h)| Rect::new(0, 0, w, h);
+        let flags = WmNormalHintsFlags::P_MIN_SIZE | WmNormalHintsFlags::P_MAX_SIZE;
+        let hints = WmNormalHints::new(flags, None, min.map(r), max.map(r), None);
+
+        assert_eq!(hints.is_fixed_size(), expected);
+    }
+
+    #[test_case(&[0, 0, 0, 0, 0], None; "decorations not set")]
+    #[test_case(&[2, 0, 0, 0, 0], Some(MotifDecorations::empty()); "no decorations")]
+    #[test_case(&[2, 0, 0b1010], Some(MotifDecorations::BORDER | MotifDecorations::TITLE); "short")]
+    #[test_case(
+        &[2, 0, 0b1001, 0],
+        Some(MotifDecorations::all() - MotifDecorations::ALL - MotifDecorations::TITLE);
+        "all but title"
+    )]
+    #[test]
+    fn motif_decorations_are_parsed(raw: &[u32], expected: Option<MotifDecorations>) {
+        let hints = MotifWmHints::try_from_bytes(raw).unwrap();
+
+        assert_eq!(hints.decorations(), expected);
+    }
+
+    #[test_case(&[2, 0]; "too short")]
+    #[test_case(&[2, 0, 0, 0, 0, 0]; "too long")]
+    #[test]
+    fn invalid_motif_hints_are_an_error(raw: &[u32]) {
+        assert!(MotifWmHints::try_from_bytes(raw).is_err());
+    }
+
+    #[test_case(MotifWmHintsFlags::empty(), MotifDecorations::empty(), false; "unset")]
+    #[test_case(MotifWmHintsFlags::DECORATIONS, MotifDecorations::empty(), true; "none")]
+    #[test_case(MotifWmHintsFlags::DECORATIONS, MotifDecorations::BORDER, false; "border")]
+    #[test_case(MotifWmHintsFlags::DECORATIONS, MotifDecorations::ALL, false; "all")]
+    #[test]
+    fn is_undecorated(flags: MotifWmHintsFlags, decorations: MotifDecorations, expected: bool) {
+        let hints = MotifWmHints::new(flags, 0, decorations);
+
+        assert_eq!(hints.is_undecorated(), expected);
+    }
+
+    #[test_case(WmNormalHintsFlags::P_ASPECT, Some((4, 3)), Some((16, 9)); "aspect set")]
+    #[test_case(WmNormalHintsFlags::empty(), None, None; "aspect not set")]
+    #[test]
+    fn aspect_ratios_are_parsed(
+        flags: WmNormalHintsFlags,
+        min: Option<(u32, u32)>,
+        max: Option<(u32, u32)>,
+    ) {
+        let mut raw = [0; 18];
+        raw[0] = flags.bits();
+        raw[11..15].copy_from_slice(&[4, 3, 16, 9]);
+
+        let hints = WmNormalHints::try_from_bytes(&raw).unwrap();
+
+        assert_eq!(hints.min_aspect, min);
+        assert_eq!(hints.max_aspect, max);
+    }
+}
@@ -18,7 +18,7 @@ impl<X: XConn> fmt::Debug for Box<dyn Query<X>> {
     }
 }
 
-pub(super) fn str_prop<X>(prop: impl AsRef<str>, id: Xid, x: &X) -> Result<Option<Vec<String>>>
+pub(crate) fn str_prop<X>(prop: impl AsRef<str>, id: Xid, x: &X) -> Result<Option<Vec<String>>>
 where
     X: XConn,
 {
@@ -37,18 +37,56 @@ where
     X: XConn,
 {
     fn run(&self, id: Xid, x: &X) -> Result<bool> {
-        let strs = str_prop(Atom::WmName, id, x)
-            .ok()
-            .or_else(|| str_prop(Atom::NetWmName, id, x).ok())
-            .flatten();
-
-        match strs {
+        match title(id, x) {
             Some(strs) if !strs.is_empty() => Ok(strs[0] == self.0),
             _ => Ok(false),
         }
     }
 }
 
+/// The title of a window, falling back to `_NET_WM_NAME` if `WM_NAME` is not set.
+pub(crate) fn title<X: XConn>(id: Xid, x: &X) -> Option<Vec<String>> {
+    str_prop(Atom::WmName, id, x)
+        .ok()
+        .flatten()
+        .or_else(|| str_prop(Atom::NetWmName, id, x).ok().flatten())
+}
+
+/// A [Query] for identifying windows that can not be resized: those whose WM_NORMAL_HINTS
+/// specify a minimum size equal to their maximum size (such as splash screens and fixed size
+/// dialogs).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FixedSize;
+
+impl<X> Query<X> for FixedSize
+where
+    X: XConn,
+{
+    fn run(&self, id: Xid, x: &X) -> Result<bool> {
+        match x.get_prop(id, Atom::WmNormalHints.as_ref())? {
+            Some(Prop::WmNormalHints(hints)) => Ok(hints.is_fixed_size()),
+            _ => Ok(false),
+        }
+    }
+}
+
+/// A [Query] for identifying windows that have asked not to be decorated by the window manager
+/// using `_MOTIF_WM_HINTS` (typically because they draw their own decorations).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Undecorated;
+
+impl<X> Query<X> for Undecorated
+where
+    X: XConn,
+{
+    fn run(&self, id: Xid, x: &X) -> Result<bool> {
+        match x.get_prop(id, Atom::MotifWmHints.as_ref())? {
+            Some(Prop::MotifWmHints(hints)) => Ok(hints.is_undecorated()),
+            _ => Ok(false),
+        }
+    }
+}
+
 /// A [Query] for fetching a window's application name (the first string returned
 /// under the WM_CLASS property).
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -17,21 +17,30 @@ use tracing::warn;
 use x11rb::{
     connection::Connection,
     protocol::{
-        xproto::{ClientMessageEvent, KeyButMask, ModMask},
+        xkb::StatePart,
+        xproto::{ClientMessageEvent, KeyButMask, Mapping, ModMask},
         ErrorKind, Event,
     },
     x11_utils::X11Error,
 };
 
+// The active XKB group is reported in bits 13 and 14 of the state of key events. Key bindings
+// are independent of the active keyboard layout so these are ignored.
+const XKB_GROUP_MASK: u16 = 0x6000;
+
 pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Result<Option<XEvent>> {
     match event {
         Event::RandrNotify(_) => Ok(Some(XEvent::RandrNotify)),
 
         Event::RandrScreenChangeNotify(_) => Ok(Some(XEvent::ScreenChange)),
 
+        // Changes to the pointer button mapping do not affect our bindings
+        Event::MappingNotify(event) if event.request == Mapping::POINTER => Ok(None),
+        Event::MappingNotify(_) => Ok(Some(XEvent::MappingNotify)),
+
         Event::ButtonPress(event) => Ok(to_mouse_state(event.detail, event.state).map(|state| {
             XEvent::MouseEvent(MouseEvent::new(
-                Xid(event.event),
+                mouse_event_window(event.event, event.child),
                 event.root_x,
                 event.root_y,
                 event.event_x,
@@ -43,7 +52,7 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
 
         Event::ButtonRelease(event) => Ok(to_mouse_state(event.detail, event.state).map(|state| {
             XEvent::MouseEvent(MouseEvent::new(
-                Xid(event.event),
+                mouse_event_window(event.event, event.child),
                 event.root_x,
                 event.root_y,
                 event.event_x,
@@ -56,7 +65,7 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
         // FIXME: The 5 is due to https://github.com/sminez/penrose/issues/113
         Event::MotionNotify(event) => Ok(to_mouse_state(5, event.state).map(|state| {
             XEvent::MouseEvent(MouseEvent::new(
-                Xid(event.event),
+                mouse_event_window(event.event, event.child),
                 event.root_x,
                 event.root_y,
                 event.event_x,
@@ -73,10 +82,25 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
             };
             let numlock = ModMask::M2;
             Ok(Some(XEvent::KeyPress(
-                code.ignoring_modifier(numlock.into()),
+                code.ignoring_modifier(u16::from(numlock) | XKB_GROUP_MASK),
             )))
         }
 
+        Event::KeyRelease(event) => {
+            let code = KeyCode {
+                mask: event.state.into(),
+                code: event.detail,
+            };
+            let numlock = ModMask::M2;
+            Ok(Some(XEvent::KeyRelease(
+                code.ignoring_modifier(u16::from(numlock) | XKB_GROUP_MASK),
+            )))
+        }
+
+        Event::XkbStateNotify(event) if event.changed.contains(StatePart::GROUP_STATE) => {
+            Ok(Some(XEvent::KeyboardLayoutChange(event.group.into())))
+        }
+
         Event::MapRequest(event) => Ok(Some(XEvent::MapRequest(Xid(event.window)))),
 
         Event::UnmapNotify(event) => Ok(Some(XEvent::UnmapNotify(Xid(event.window)))),
@@ -160,6 +184,16 @@ pub(crate) fn convert_event<C: Connection>(conn: &Conn<C>, event: Event) -> Resu
     }
 }
 
+// Mouse events for grabbed buttons are reported against the root window, so we use the top
+// level child window under the pointer (if there is one) in its place.
+fn mouse_event_window(event: u32, child: u32) -> Xid {
+    if child == x11rb::NONE {
+        Xid(event)
+    } else {
+        Xid(child)
+    }
+}
+
 fn to_mouse_state(detail: u8, state: KeyButMask) -> Option<MouseState> {
     fn is_held(key: &ModifierKey, mask: u16) -> bool {
         mask & u16::from(*key) > 0
@@ -170,6 +204,8 @@ fn to_mouse_state(detail: u8, state: KeyButMask) -> Option<MouseState> {
         3 => MouseButton::Right,
         4 => MouseButton::ScrollUp,
         5 => MouseButton::ScrollDown,
+        6 => MouseButton::ScrollLeft,
+        7 => MouseButton::ScrollRight,
         _ => {
             warn!(button = detail, "dropping unknown mouse button event");
             return None;
@@ -0,0 +1,438 @@
+//! Mirroring the contents of a window onto an overlay using the Composite, Damage and Render
+//! extensions.
+//!
+//! A [Mirror] runs on its own X connection and thread so that it is able to redraw the overlay
+//! as soon as the source window is updated without needing to hook into the main event loop.
+use crate::{pure::geometry::Rect, Error, Result, Xid};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+use tracing::{debug, error, info};
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    protocol::{
+        composite::{self, ConnectionExt as _, Redirect},
+        damage::{self, ConnectionExt as _, ReportLevel},
+        render::{self, ConnectionExt as _, CreatePictureAux, PictOp, Pictformat, Transform},
+        xfixes::{self, ConnectionExt as _},
+        xproto::{
+            ChangeWindowAttributesAux, ClientMessageData, ClientMessageEvent, ConnectionExt as _,
+            CreateWindowAux, EventMask, MapState, Visualid, WindowClass, CLIENT_MESSAGE_EVENT,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    NONE,
+};
+
+/// A scaled copy of a window's contents shown in an overlay window.
+///
+/// The overlay is an override redirect window covering the target [Rect] and the contents of
+/// the source window are scaled to fit inside it while preserving their aspect ratio. The
+/// mirror is kept up to date until [Mirror::stop] is called, the [Mirror] is dropped or the
+/// source window is destroyed.
+///
+/// The source window needs to be mapped in order for its contents to be mirrored: while it is
+/// unmapped (for example when its workspace is not visible) the overlay is left blank.
+pub struct Mirror {
+    conn: Arc<RustConnection>,
+    source: Xid,
+    overlay: u32,
+    stopping: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for Mirror {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mirror")
+            .field("source", &self.source)
+            .field("overlay", &self.overlay)
+            .field("running", &self.is_running())
+            .finish()
+    }
+}
+
+impl Mirror {
+    /// Start mirroring the given window onto an overlay covering `target`.
+    ///
+    /// # Errors
+    /// Returns an error if the X server does not support the required extensions or the
+    /// overlay can not be created.
+    pub fn start(source: Xid, target: Rect) -> Result<Self> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        let conn = Arc::new(conn);
+        let state = MirrorState::new(Arc::clone(&conn), screen_num, *source, target)?;
+        let overlay = state.overlay;
+
+        let stopping = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stopping);
+        let handle = thread::spawn(move || state.run(flag));
+
+        info!(%source, ?target, "started mirroring client");
+
+        Ok(Self {
+            conn,
+            source,
+            overlay,
+            stopping,
+            handle: Some(handle),
+        })
+    }
+
+    /// The window being mirrored.
+    pub fn source(&self) -> Xid {
+        self.source
+    }
+
+    /// Whether or not the mirror is still running.
+    ///
+    /// This will be false once the source window has been destroyed.
+    pub fn is_running(&self) -> bool {
+        matches!(&self.handle, Some(h) if !h.is_finished())
+    }
+
+    /// Stop mirroring, removing the overlay window.
+    pub fn stop(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        let handle = match self.handle.take() {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        self.stopping.store(true, Ordering::SeqCst);
+
+        // Wake the mirror thread if it is blocked waiting for the next event
+        let event = ClientMessageEvent {
+            response_type: CLIENT_MESSAGE_EVENT,
+            format: 32,
+            sequence: 0,
+            window: self.overlay,
+            type_: NONE,
+            data: ClientMessageData::from([0u32; 5]),
+        };
+        let res = self
+            .conn
+            .send_event(false, self.overlay, EventMask::NO_EVENT, event)
+            .and_then(|_| self.conn.flush());
+
+        if let Err(e) = res {
+            error!(%e, "unable to wake mirror thread");
+            return;
+        }
+
+        if handle.join().is_err() {
+            error!("mirror thread panicked");
+        }
+    }
+}
+
+impl Drop for Mirror {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+// The X resources used by a running Mirror.
+struct MirrorState {
+    conn: Arc<RustConnection>,
+    source: u32,
+    overlay: u32,
+    target: Rect,
+    damage: u32,
+    dst_picture: u32,
+    src_format: Pictformat,
+    // The currently named pixmap of the source window along with the picture wrapping it
+    // and the region of the overlay it is drawn to.
+    src: Option<(u32, u32, Rect)>,
+}
+
+impl MirrorState {
+    fn new(
+        conn: Arc<RustConnection>,
+        screen_num: usize,
+        source: u32,
+        target: Rect,
+    ) -> Result<Self> {
+        for name in [
+            composite::X11_EXTENSION_NAME,
+            damage::X11_EXTENSION_NAME,
+            render::X11_EXTENSION_NAME,
+            xfixes::X11_EXTENSION_NAME,
+        ] {
+            if conn.extension_information(name)?.is_none() {
+                return Err(Error::Custom(format!(
+                    "the {name} extension is required for mirroring windows"
+                )));
+            }
+        }
+
+        // Versions need to be negotiated before the extensions can be used
+        conn.xfixes_query_version(5, 0)?.reply()?;
+        conn.damage_query_version(1, 1)?.reply()?;
+        conn.composite_query_version(0, 4)?.reply()?;
+        conn.render_query_version(0, 11)?.reply()?;
+
+        let screen = &conn.setup().roots[screen_num];
+        let (root, root_visual, black) = (screen.root, screen.root_visual, screen.black_pixel);
+        let src_visual = conn.get_window_attributes(source)?.reply()?.visual;
+        let formats = conn.render_query_pict_formats()?.reply()?;
+        let format_for = |visual: Visualid| {
+            formats
+                .screens
+                .iter()
+                .flat_map(|s| s.depths.iter())
+                .flat_map(|d| d.visuals.iter())
+                .find(|v| v.visual == visual)
+                .map(|v| v.format)
+                .ok_or_else(|| Error::Custom(format!("no picture format for visual {visual}")))
+        };
+        let (src_format, dst_format) = (format_for(src_visual)?, format_for(root_visual)?);
+
+        conn.composite_redirect_window(source, Redirect::AUTOMATIC)?;
+        conn.change_window_attributes(
+            source,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+
+        let damage = conn.generate_id()?;
+        conn.damage_create(damage, source, ReportLevel::NON_EMPTY)?;
+
+        let overlay = conn.generate_id()?;
+        let Rect { x, y, w, h } = target;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            overlay,
+            root,
+            x as i16,
+            y as i16,
+            w as u16,
+            h as u16,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new()
+                .override_redirect(1)
+                .background_pixel(black)
+                .event_mask(EventMask::EXPOSURE),
+        )?;
+
+        let dst_picture = conn.generate_id()?;
+        conn.render_create_picture(dst_picture, overlay, dst_format, &CreatePictureAux::new())?;
+        conn.map_window(overlay)?;
+        conn.flush()?;
+
+        Ok(Self {
+            conn,
+            source,
+            overlay,
+            target,
+            damage,
+            dst_picture,
+            src_format,
+            src: None,
+        })
+    }
+
+    fn run(mut self, stopping: Arc<AtomicBool>) {
+        if let Err(e) = self.redraw() {
+            debug!(%e, "unable to draw mirrored window");
+        }
+
+        loop {
+            let event = match self.conn.wait_for_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    error!(%e, "error reading events for mirrored window");
+                    break;
+                }
+            };
+
+            if stopping.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let res = match event {
+                Event::DamageNotify(_) => self.repair(),
+                Event::Expose(e) if e.window == self.overlay && e.count == 0 => self.redraw(),
+                Event::ConfigureNotify(e) if e.window == self.source => self.reset(),
+                Event::MapNotify(e) if e.window == self.source => self.reset(),
+                Event::UnmapNotify(e) if e.window == self.source => {
+                    self.release_source();
+                    Ok(())
+                }
+                Event::DestroyNotify(e) if e.window == self.source => {
+                    info!(source = self.source, "mirrored window destroyed");
+                    break;
+                }
+                Event::Error(e) => {
+                    debug!(?e, "X error while mirroring window");
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+
+            if let Err(e) = res {
+                debug!(%e, "unable to draw mirrored window");
+            }
+        }
+
+        self.cleanup();
+    }
+
+    // Acknowledge the damage to the source window and redraw it.
+    fn repair(&mut self) -> Result<()> {
+        self.conn.damage_subtract(self.damage, NONE, NONE)?;
+
+        self.redraw()
+    }
+
+    // Drop the current pixmap of the source window so that a new one is named on the
+    // next redraw, clearing any letterboxing left over from the old size.
+    fn reset(&mut self) -> Result<()> {
+        self.release_source();
+        self.conn.clear_area(false, self.overlay, 0, 0, 0, 0)?;
+
+        self.redraw()
+    }
+
+    fn release_source(&mut self) {
+        if let Some((pixmap, picture, _)) = self.src.take() {
+            let _ = self.conn.render_free_picture(picture);
+            let _ = self.conn.free_pixmap(pixmap);
+        }
+    }
+
+    fn redraw(&mut self) -> Result<()> {
+        if self.src.is_none() {
+            self.src = self.name_source_pixmap()?;
+        }
+
+        if let Some((_, picture, r)) = self.src {
+            self.conn.render_composite(
+                PictOp::SRC,
+                picture,
+                NONE,
+                self.dst_picture,
+                0,
+                0,
+                0,
+                0,
+                r.x as i16,
+                r.y as i16,
+                r.w as u16,
+                r.h as u16,
+            )?;
+            self.conn.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn name_source_pixmap(&self) -> Result<Option<(u32, u32, Rect)>> {
+        if self
+            .conn
+            .get_window_attributes(self.source)?
+            .reply()?
+            .map_state
+            != MapState::VIEWABLE
+        {
+            return Ok(None);
+        }
+
+        let geom = self.conn.get_geometry(self.source)?.reply()?;
+        let (w, h) = (geom.width as u32, geom.height as u32);
+        let r = fit_within(w, h, self.target.w, self.target.h);
+        if r.w == 0 || r.h == 0 {
+            return Ok(None);
+        }
+
+        let pixmap = self.conn.generate_id()?;
+        self.conn
+            .composite_name_window_pixmap(self.source, pixmap)?;
+        let picture = self.conn.generate_id()?;
+        self.conn.render_create_picture(
+            picture,
+            pixmap,
+            self.src_format,
+            &CreatePictureAux::new(),
+        )?;
+
+        // The transform maps points in the overlay back to points in the source window
+        let scale = to_fixed(w as f64 / r.w as f64);
+        let one = to_fixed(1.0);
+        self.conn.render_set_picture_transform(
+            picture,
+            Transform {
+                matrix11: scale,
+                matrix12: 0,
+                matrix13: 0,
+                matrix21: 0,
+                matrix22: scale,
+                matrix23: 0,
+                matrix31: 0,
+                matrix32: 0,
+                matrix33: one,
+            },
+        )?;
+        self.conn
+            .render_set_picture_filter(picture, b"bilinear", &[])?;
+
+        Ok(Some((pixmap, picture, r)))
+    }
+
+    fn cleanup(&mut self) {
+        self.release_source();
+        let _ = self.conn.render_free_picture(self.dst_picture);
+        let _ = self.conn.damage_destroy(self.damage);
+        let _ = self.conn.destroy_window(self.overlay);
+        let _ = self
+            .conn
+            .composite_unredirect_window(self.source, Redirect::AUTOMATIC);
+        let _ = self.conn.flush();
+    }
+}
+
+// Convert to the 16.16 fixed point format used by Render.
+fn to_fixed(f: f64) -> i32 {
+    (f * 65536.0).round() as i32
+}
+
+// The largest region with the same aspect ratio as a (w, h) window that fits within an
+// overlay of size (max_w, max_h), centered within the overlay.
+fn fit_within(w: u32, h: u32, max_w: u32, max_h: u32) -> Rect {
+    if w == 0 || h == 0 {
+        return Rect::default();
+    }
+
+    let scale = f64::min(max_w as f64 / w as f64, max_h as f64 / h as f64);
+    let (fw, fh) = (
+        ((w as f64 * scale).round() as u32).min(max_w),
+        ((h as f64 * scale).round() as u32).min(max_h),
+    );
+
+    Rect::new((max_w - fw) / 2, (max_h - fh) / 2, fw, fh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case(1920, 1080, 1920, 1080, Rect::new(0, 0, 1920, 1080); "same size")]
+    #[test_case(960, 540, 1920, 1080, Rect::new(0, 0, 1920, 1080); "scaled up")]
+    #[test_case(1920, 1080, 1280, 1024, Rect::new(0, 152, 1280, 720); "letterboxed")]
+    #[test_case(1000, 2000, 1920, 1080, Rect::new(690, 0, 540, 1080); "pillarboxed")]
+    #[test_case(0, 100, 1920, 1080, Rect::default(); "empty window")]
+    #[test]
+    fn fit_within_works(w: u32, h: u32, max_w: u32, max_h: u32, expected: Rect) {
+        assert_eq!(fit_within(w, h, max_w, max_h), expected);
+    }
+}
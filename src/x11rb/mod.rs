@@ -17,30 +17,43 @@
 //! [1]: https://www.x.org/releases/X11R7.6/doc/xproto/x11protocol.html
 //! [2]: https://gitlab.freedesktop.org/xorg/proto/randrproto/-/blob/master/randrproto.txt
 use crate::{
-    core::bindings::{KeyCode, MouseState},
-    pure::geometry::{Point, Rect},
+    core::bindings::{KeyCode, Keymap, MouseState},
+    pure::geometry::{Point, Rect, Rotation},
     x::{
         self,
         atom::Atom,
         event::{ClientEventMask, ClientMessage, ClientMessageKind},
-        property::{Prop, WindowAttributes, WmHints, WmNormalHints, WmState},
+        property::{MotifWmHints, Prop, WindowAttributes, WmHints, WmNormalHints, WmState},
         ClientAttr, ClientConfig, WinType, XConn, XConnExt, XEvent,
     },
-    Error, Result, Xid,
+    Color, Error, Result, Xid,
+};
+use nix::{
+    errno::Errno,
+    libc::c_int,
+    poll::{poll, PollFd, PollFlags},
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    os::fd::{AsRawFd, RawFd},
+    str::FromStr,
+    time::Instant,
 };
-use std::{collections::HashMap, str::FromStr};
 use strum::IntoEnumIterator;
-use tracing::error;
+use tracing::{error, warn};
 use x11rb::{
     connection::Connection,
     protocol::{
         randr::{self, ConnectionExt as _, NotifyMask},
+        xkb::{self, ConnectionExt as _},
         xproto::{
-            AtomEnum, ChangeWindowAttributesAux, ClientMessageData, ClientMessageEvent,
-            ColormapAlloc, ConfigureWindowAux, ConnectionExt as _, CreateWindowAux, EventMask,
-            GrabMode, InputFocus, MapState, ModMask, PropMode, StackMode, WindowClass,
-            CLIENT_MESSAGE_EVENT,
+            AtomEnum, ButtonIndex, ChangeWindowAttributesAux, ClientMessageData,
+            ClientMessageEvent, ColormapAlloc, ConfigureWindowAux, ConnectionExt as _,
+            CreateWindowAux, EventMask, GrabMode, GrabStatus, InputFocus, MapState, ModMask,
+            PropMode, StackMode, WindowClass, CLIENT_MESSAGE_EVENT,
         },
+        Event,
     },
     rust_connection::RustConnection,
     wrapper::ConnectionExt as _,
@@ -51,10 +64,16 @@ use x11rb::{
 use x11rb::xcb_ffi::XCBConnection;
 
 pub mod conversions;
+#[cfg(feature = "mirror")]
+pub mod mirror;
+pub mod transition;
 
 use conversions::convert_event;
 
 const RANDR_VER: (u32, u32) = (1, 2);
+const XKB_VER: (u16, u16) = (1, 0);
+// We only manage the first screen of the display
+const WM_SELECTION: &str = "WM_S0";
 
 #[derive(Debug)]
 pub(crate) struct Atoms {
@@ -94,8 +113,13 @@ impl Atoms {
 #[derive(Debug)]
 pub struct Conn<C: Connection> {
     conn: C,
+    fd: RawFd,
     root: u32,
     atoms: Atoms,
+    wm_selection: Cell<Option<u32>>,
+    outline: Cell<Option<[u32; 4]>>,
+    peeked_event: RefCell<Option<Event>>,
+    xkb: bool,
 }
 
 /// A pure rust based connection to the X server using a [RustConnection].
@@ -106,11 +130,76 @@ impl Conn<RustConnection> {
     /// [x11rb::rust_connection::RustConnection].
     pub fn new() -> Result<Self> {
         let (conn, _) = RustConnection::connect(None).map_err(Error::from)?;
+        let fd = conn.stream().as_raw_fd();
 
-        Self::new_for_connection(conn)
+        Self::new_for_connection(conn, fd)
+    }
+
+    /// Construct an X11rbConnection connected to the given display (such as `":1"`) rather
+    /// than the one named by the `DISPLAY` environment variable.
+    pub fn new_for_display(display: &str) -> Result<Self> {
+        let (conn, _) = RustConnection::connect(Some(display)).map_err(Error::from)?;
+        let fd = conn.stream().as_raw_fd();
+
+        Self::new_for_connection(conn, fd)
     }
 }
 
+// Reflections are ignored as they do not change the orientation of the output
+fn to_rotation(rotation: randr::Rotation) -> Rotation {
+    if rotation.contains(randr::Rotation::ROTATE90) {
+        Rotation::Left
+    } else if rotation.contains(randr::Rotation::ROTATE180) {
+        Rotation::Inverted
+    } else if rotation.contains(randr::Rotation::ROTATE270) {
+        Rotation::Right
+    } else {
+        Rotation::Normal
+    }
+}
+
+// Enable the XKB extension, selecting events for changes to the active keyboard layout. If
+// XKB is not available then keyboard layouts are reported as unsupported rather than failing.
+fn init_xkb(conn: &impl Connection) -> Result<bool> {
+    let supported = match conn.xkb_use_extension(XKB_VER.0, XKB_VER.1) {
+        Ok(cookie) => cookie.reply().map(|r| r.supported).unwrap_or(false),
+        Err(_) => false,
+    };
+
+    if !supported {
+        warn!("XKB is not supported by the X server: keyboard layouts can not be switched");
+        return Ok(false);
+    }
+
+    let details = xkb::SelectEventsAux::new().state_notify(xkb::SelectEventsAuxStateNotify {
+        affect_state: xkb::StatePart::GROUP_STATE,
+        state_details: xkb::StatePart::GROUP_STATE,
+    });
+    conn.xkb_select_events(
+        xkb::ID::USE_CORE_KBD.into(),
+        xkb::EventType::from(0u16),
+        xkb::EventType::from(0u16),
+        xkb::MapPart::from(0u16),
+        xkb::MapPart::from(0u16),
+        &details,
+    )?;
+
+    // Report a single release once a held key is let go rather than a release before each
+    // auto-repeated press so that held keys can be tracked reliably.
+    let flag = xkb::PerClientFlag::DETECTABLE_AUTO_REPEAT;
+    let zero = xkb::BoolCtrl::from(0u32);
+    let detectable = conn
+        .xkb_per_client_flags(xkb::ID::USE_CORE_KBD.into(), flag, flag, zero, zero, zero)?
+        .reply()
+        .map(|r| r.value.contains(flag))
+        .unwrap_or(false);
+    if !detectable {
+        warn!("detectable auto-repeat is not supported: held keys may stop repeating early");
+    }
+
+    Ok(true)
+}
+
 #[cfg(feature = "x11rb-xcb")]
 /// An C based connection to the X server using an [XCBConnection].
 pub type XcbConn = Conn<XCBConnection>;
@@ -121,8 +210,9 @@ impl Conn<XCBConnection> {
     /// [x11rb::xcb_ffi::XCBConnection].
     pub fn new() -> Result<Self> {
         let (conn, _) = XCBConnection::connect(None).map_err(Error::from)?;
+        let fd = conn.as_raw_fd();
 
-        Self::new_for_connection(conn)
+        Self::new_for_connection(conn, fd)
     }
 }
 
@@ -130,7 +220,91 @@ impl<C> Conn<C>
 where
     C: Connection,
 {
-    fn new_for_connection(conn: C) -> Result<Self> {
+    fn set_crtc_config(
+        &self,
+        crtc: randr::Crtc,
+        config_timestamp: u32,
+        x: i16,
+        y: i16,
+        mode: randr::Mode,
+        outputs: &[randr::Output],
+    ) -> Result<()> {
+        let reply = self
+            .conn
+            .randr_set_crtc_config(
+                crtc,
+                CURRENT_TIME,
+                config_timestamp,
+                x,
+                y,
+                mode,
+                randr::Rotation::ROTATE0,
+                outputs,
+            )?
+            .reply()?;
+
+        if reply.status != randr::SetConfig::SUCCESS {
+            return Err(Error::Randr(format!(
+                "unable to configure CRTC {crtc}: {:?}",
+                reply.status
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Block until the X server has sent us something to read, returning false if the deadline
+    // passes, the wake fd becomes readable or we are interrupted by a signal first.
+    fn wait_for_readable(&self, deadline: Option<Instant>, wake: RawFd) -> Result<bool> {
+        // Requests need to reach the server before we can expect anything back
+        self.conn.flush()?;
+
+        let timeout = match deadline {
+            // Round up so that we do not wake just before the deadline and then spin
+            Some(d) => {
+                let remaining = d.saturating_duration_since(Instant::now());
+                remaining.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int
+            }
+            None => -1,
+        };
+
+        let mut fds = [
+            PollFd::new(self.fd, PollFlags::POLLIN),
+            PollFd::new(wake, PollFlags::POLLIN),
+        ];
+        let ready = match poll(&mut fds, timeout) {
+            Ok(n) => n,
+            Err(Errno::EINTR) => return Ok(false),
+            Err(e) => return Err(std::io::Error::from(e).into()),
+        };
+        let woken = fds[1].revents().is_some_and(|r| !r.is_empty());
+
+        Ok(ready > 0 && !woken)
+    }
+
+    // Auto-repeated keys are reported as a release immediately followed by a press of the same
+    // key with the same timestamp. Dropping the release means that key release bindings only
+    // run once the key is actually released.
+    fn drop_auto_repeat_release(&self, event: Event) -> Result<Event> {
+        let release = match event {
+            Event::KeyRelease(ref e) => e,
+            _ => return Ok(event),
+        };
+
+        match self.conn.poll_for_event()? {
+            Some(Event::KeyPress(press))
+                if press.detail == release.detail && press.time == release.time =>
+            {
+                Ok(Event::KeyPress(press))
+            }
+            next => {
+                *self.peeked_event.borrow_mut() = next;
+                Ok(event)
+            }
+        }
+    }
+
+    fn new_for_connection(conn: C, fd: RawFd) -> Result<Self> {
         let root = conn.setup().roots[0].root;
         conn.prefetch_extension_information(randr::X11_EXTENSION_NAME)?;
         let atoms = Atoms::new(&conn)?;
@@ -154,7 +328,18 @@ where
         let mask = NotifyMask::OUTPUT_CHANGE | NotifyMask::CRTC_CHANGE | NotifyMask::SCREEN_CHANGE;
         conn.randr_select_input(root, mask)?;
 
-        let xconn = Self { conn, root, atoms };
+        let xkb = init_xkb(&conn)?;
+
+        let xconn = Self {
+            conn,
+            fd,
+            root,
+            atoms,
+            wm_selection: Cell::new(None),
+            outline: Cell::new(None),
+            peeked_event: RefCell::new(None),
+            xkb,
+        };
 
         xconn.set_client_attributes(Xid(root), &[ClientAttr::RootEventMask])?;
 
@@ -275,12 +460,249 @@ where
         Ok(rects)
     }
 
+    // The DPI of each screen is calculated from the physical width of the first output
+    // connected to its CRTC. Projectors and some virtual outputs report a width of 0.
+    fn screen_dpi(&self) -> Result<Vec<Option<f64>>> {
+        let resources = self.conn.randr_get_screen_resources(self.root)?.reply()?;
+
+        let crtcs = resources
+            .crtcs
+            .iter()
+            .map(|c| {
+                self.conn
+                    .randr_get_crtc_info(*c, 0)
+                    .map_err(|err| err.into())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let dpi = crtcs
+            .into_iter()
+            .flat_map(|cookie| cookie.reply().ok())
+            .filter(|reply| reply.width > 0)
+            .map(|reply| {
+                let output = reply.outputs.first()?;
+                let info = self
+                    .conn
+                    .randr_get_output_info(*output, resources.config_timestamp)
+                    .ok()?
+                    .reply()
+                    .ok()?;
+
+                if info.mm_width == 0 {
+                    return None;
+                }
+
+                Some(reply.width as f64 * 25.4 / info.mm_width as f64)
+            })
+            .collect();
+
+        Ok(dpi)
+    }
+
+    fn outputs(&self) -> Result<Vec<x::Output>> {
+        let resources = self.conn.randr_get_screen_resources(self.root)?.reply()?;
+        let ts = resources.config_timestamp;
+        let primary = self
+            .conn
+            .randr_get_output_primary(self.root)?
+            .reply()?
+            .output;
+
+        let mut outputs = Vec::with_capacity(resources.outputs.len());
+        for &id in resources.outputs.iter() {
+            let info = self.conn.randr_get_output_info(id, ts)?.reply()?;
+            let (r, rotation) = if info.crtc == x11rb::NONE {
+                (None, Rotation::Normal)
+            } else {
+                let crtc = self.conn.randr_get_crtc_info(info.crtc, ts)?.reply()?;
+                let (x, y, w, h) = (crtc.x as u32, crtc.y as u32, crtc.width, crtc.height);
+
+                (
+                    (w > 0).then(|| Rect::new(x, y, w as u32, h as u32)),
+                    to_rotation(crtc.rotation),
+                )
+            };
+
+            outputs.push(x::Output {
+                name: String::from_utf8(info.name)?,
+                connected: info.connection == randr::Connection::CONNECTED,
+                r,
+                primary: id == primary,
+                rotation,
+            });
+        }
+
+        Ok(outputs)
+    }
+
+    // Outputs are matched to a mode with the requested resolution and a CRTC that is able to
+    // drive them (preferring the one that they are already using). CRTCs that are no longer
+    // needed or that would fall outside of the new screen size are disabled before resizing
+    // the screen, in the same way that xrandr does.
+    fn configure_outputs(&self, outputs: &[x::OutputConfig]) -> Result<()> {
+        let resources = self.conn.randr_get_screen_resources(self.root)?.reply()?;
+        let ts = resources.config_timestamp;
+
+        let infos = resources
+            .outputs
+            .iter()
+            .map(|&id| {
+                let info = self.conn.randr_get_output_info(id, ts)?.reply()?;
+                let name = String::from_utf8(info.name.clone())?;
+
+                Ok((id, name, info))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let in_profile = |name: &str| outputs.iter().any(|o| o.name == name);
+        let mut assigned: Vec<(randr::Crtc, randr::Output, randr::Mode, Rect)> = Vec::new();
+
+        for conf in outputs {
+            let (id, _, info) = infos
+                .iter()
+                .find(|(_, name, _)| *name == conf.name)
+                .ok_or_else(|| Error::Randr(format!("unknown output: {}", conf.name)))?;
+
+            let mode = info
+                .modes
+                .iter()
+                .copied()
+                .find(|&m| {
+                    resources.modes.iter().any(|mi| {
+                        mi.id == m && mi.width as u32 == conf.r.w && mi.height as u32 == conf.r.h
+                    })
+                })
+                .ok_or_else(|| {
+                    Error::Randr(format!(
+                        "{} does not support a resolution of {}x{}",
+                        conf.name, conf.r.w, conf.r.h
+                    ))
+                })?;
+
+            // A CRTC is free if it has not been assigned yet and is not currently driving
+            // another output that is being kept enabled
+            let is_free = |c: randr::Crtc| {
+                !assigned.iter().any(|&(a, _, _, _)| a == c)
+                    && !infos
+                        .iter()
+                        .any(|(other, name, i)| other != id && i.crtc == c && in_profile(name))
+            };
+
+            let crtc = Some(info.crtc)
+                .filter(|&c| c != x11rb::NONE && is_free(c))
+                .or_else(|| info.crtcs.iter().copied().find(|&c| is_free(c)))
+                .ok_or_else(|| Error::Randr(format!("no free CRTC for {}", conf.name)))?;
+
+            assigned.push((crtc, *id, mode, conf.r));
+        }
+
+        let (w, h) = outputs.iter().fold((0, 0), |(w, h), o| {
+            (w.max(o.r.x + o.r.w), h.max(o.r.y + o.r.h))
+        });
+        if w == 0 || h == 0 {
+            return Err(Error::Randr(
+                "at least one output must be enabled".to_string(),
+            ));
+        }
+
+        for &crtc in resources.crtcs.iter() {
+            let info = self.conn.randr_get_crtc_info(crtc, ts)?.reply()?;
+            if info.mode == x11rb::NONE {
+                continue;
+            }
+
+            let fits =
+                info.x as u32 + info.width as u32 <= w && info.y as u32 + info.height as u32 <= h;
+            if !fits || !assigned.iter().any(|&(c, _, _, _)| c == crtc) {
+                self.set_crtc_config(crtc, ts, 0, 0, x11rb::NONE, &[])?;
+            }
+        }
+
+        // The physical size of the screen is calculated at 96 DPI, matching xrandr
+        let mm = |px: u32| (px as f64 * 25.4 / 96.0).round() as u32;
+        self.conn
+            .randr_set_screen_size(self.root, w as u16, h as u16, mm(w), mm(h))?
+            .check()?;
+
+        for (crtc, output, mode, r) in assigned.iter().copied() {
+            self.set_crtc_config(crtc, ts, r.x as i16, r.y as i16, mode, &[output])?;
+        }
+
+        if let Some(i) = outputs.iter().position(|o| o.primary) {
+            let (_, output, _, _) = assigned[i];
+            self.conn
+                .randr_set_output_primary(self.root, output)?
+                .check()?;
+        }
+        self.flush();
+
+        Ok(())
+    }
+
     fn cursor_position(&self) -> Result<Point> {
         let reply = self.conn.query_pointer(self.root)?.reply()?;
 
         Ok(Point::new(reply.root_x as u32, reply.root_y as u32))
     }
 
+    // Keysym names are resolved by xmodmap as it has access to the full set of names known
+    // to Xlib (see Keymap::from_xmodmap).
+    fn keymap(&self) -> Result<Keymap> {
+        Keymap::from_xmodmap()
+    }
+
+    fn keyboard_layouts(&self) -> Result<Vec<String>> {
+        if !self.xkb {
+            return Ok(Vec::new());
+        }
+
+        let reply = self
+            .conn
+            .xkb_get_names(xkb::ID::USE_CORE_KBD.into(), xkb::NameDetail::GROUP_NAMES)?
+            .reply()?;
+
+        reply
+            .value_list
+            .groups
+            .unwrap_or_default()
+            .into_iter()
+            .map(|atom| self.atom_name(Xid(atom)))
+            .collect()
+    }
+
+    fn keyboard_layout(&self) -> Result<u8> {
+        if !self.xkb {
+            return Ok(0);
+        }
+
+        let reply = self
+            .conn
+            .xkb_get_state(xkb::ID::USE_CORE_KBD.into())?
+            .reply()?;
+
+        Ok(reply.group.into())
+    }
+
+    fn set_keyboard_layout(&self, group: u8) -> Result<()> {
+        if !self.xkb {
+            return Ok(());
+        }
+
+        let none = ModMask::from(0u16);
+        self.conn.xkb_latch_lock_state(
+            xkb::ID::USE_CORE_KBD.into(),
+            none,
+            none,
+            true,
+            group.into(),
+            none,
+            false,
+            0,
+        )?;
+
+        Ok(())
+    }
+
     fn grab(&self, key_codes: &[KeyCode], mouse_states: &[MouseState]) -> Result<()> {
         // Release any grabbed keys that we currently have before attempting to grab
         // the requested key codes.
@@ -288,6 +710,12 @@ where
         if let Err(e) = self.conn.ungrab_key(0, self.root, ModMask::ANY) {
             error!(%e, "unable to ungrab keys");
         };
+        if let Err(e) = self
+            .conn
+            .ungrab_button(ButtonIndex::ANY, self.root, ModMask::ANY)
+        {
+            error!(%e, "unable to ungrab mouse buttons");
+        };
 
         // We need to explicitly grab NumLock as an additional modifier and then drop it later on
         // when we are passing events through to the WindowManager as NumLock alters the modifier
@@ -333,13 +761,34 @@ where
 
     fn next_event(&self) -> Result<XEvent> {
         loop {
-            let event = self.conn.wait_for_event()?;
+            let event = match self.peeked_event.take() {
+                Some(event) => event,
+                None => self.conn.wait_for_event()?,
+            };
+            let event = self.drop_auto_repeat_release(event)?;
             if let Some(event) = convert_event(self, event)? {
                 return Ok(event);
             }
         }
     }
 
+    fn next_event_until(&self, deadline: Option<Instant>, wake: RawFd) -> Result<Option<XEvent>> {
+        loop {
+            let event = match self.peeked_event.take() {
+                Some(event) => event,
+                None => match self.conn.poll_for_event()? {
+                    Some(event) => event,
+                    None if self.wait_for_readable(deadline, wake)? => continue,
+                    None => return Ok(None),
+                },
+            };
+            let event = self.drop_auto_repeat_release(event)?;
+            if let Some(event) = convert_event(self, event)? {
+                return Ok(Some(event));
+            }
+        }
+    }
+
     fn flush(&self) {
         self.conn.flush().unwrap_or(());
     }
@@ -412,6 +861,13 @@ where
         Ok(())
     }
 
+    fn force_kill(&self, client: Xid) -> Result<()> {
+        self.conn.kill_client(*client)?;
+        self.flush();
+
+        Ok(())
+    }
+
     fn focus(&self, id: Xid) -> Result<()> {
         self.conn
             .set_input_focus(InputFocus::PARENT, *id, CURRENT_TIME)?;
@@ -502,6 +958,16 @@ where
                     .collect::<Vec<_>>(),
             )?),
 
+            "_MOTIF_WM_HINTS" => Prop::MotifWmHints(MotifWmHints::try_from_bytes(
+                &r.value32()
+                    .ok_or_else(|| Error::InvalidPropertyData {
+                        id,
+                        prop: prop_name.to_owned(),
+                        ty: prop_type.to_owned(),
+                    })?
+                    .collect::<Vec<_>>(),
+            )?),
+
             // Default to returning the raw bytes as u32s which the user can then
             // convert as needed if the prop type is not one we recognise
             _ => Prop::Bytes(match r.format {
@@ -618,9 +1084,9 @@ where
 
             Prop::Window(ids) => (AtomEnum::WINDOW, ids.into_iter().map(|id| *id).collect()),
 
-            // FIXME: handle changing WmHints and WmNormalHints correctly in change_prop
-            Prop::Bytes(_) | Prop::WmHints(_) | Prop::WmNormalHints(_) => {
-                panic!("unable to change Prop, WmHints or WmNormalHints properties");
+            // FIXME: handle changing WmHints, WmNormalHints and MotifWmHints correctly in change_prop
+            Prop::Bytes(_) | Prop::WmHints(_) | Prop::WmNormalHints(_) | Prop::MotifWmHints(_) => {
+                panic!("unable to change Prop, WmHints, WmNormalHints or MotifWmHints properties");
             }
         };
 
@@ -642,6 +1108,7 @@ where
         let root_event_mask = EventMask::PROPERTY_CHANGE
             | EventMask::SUBSTRUCTURE_REDIRECT
             | EventMask::SUBSTRUCTURE_NOTIFY
+            | EventMask::BUTTON_PRESS
             | EventMask::BUTTON_MOTION;
 
         let mut aux = ChangeWindowAttributesAux::new();
@@ -666,6 +1133,7 @@ where
                 ClientConfig::Position(r) => {
                     aux = aux.x(r.x as i32).y(r.y as i32).width(r.w).height(r.h);
                 }
+                ClientConfig::Move(x, y) => aux = aux.x(*x).y(*y),
                 ClientConfig::StackBelow(s) => aux = aux.sibling(s.0).stack_mode(StackMode::BELOW),
                 ClientConfig::StackAbove(s) => aux = aux.sibling(s.0).stack_mode(StackMode::ABOVE),
                 ClientConfig::StackBottom => aux = aux.stack_mode(StackMode::BELOW),
@@ -703,9 +1171,148 @@ where
         Ok(())
     }
 
+    fn acquire_wm_selection(&self) -> Result<()> {
+        let selection = *self.intern_atom(WM_SELECTION)?;
+        let id = self.create_window(WinType::InputOnly, Rect::new(0, 0, 1, 1), false)?;
+        self.conn
+            .set_selection_owner(*id, selection, CURRENT_TIME)?;
+
+        let owner = self.conn.get_selection_owner(selection)?.reply()?.owner;
+        if owner != *id {
+            self.destroy_window(id)?;
+            return Err(Error::Custom(format!("{WM_SELECTION} is owned by {owner}")));
+        }
+        self.wm_selection.set(Some(*id));
+
+        // ICCCM 2.8: let other clients know that the selection has a new owner
+        let event = ClientMessageEvent::new(
+            32,
+            self.root,
+            self.atoms.known_atom(Atom::Manager),
+            [CURRENT_TIME, selection, *id, 0, 0],
+        );
+        self.conn
+            .send_event(false, self.root, EventMask::STRUCTURE_NOTIFY, event)?;
+        self.flush();
+
+        Ok(())
+    }
+
+    fn release_wm_selection(&self) -> Result<()> {
+        let id = match self.wm_selection.take() {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+
+        let selection = *self.intern_atom(WM_SELECTION)?;
+        self.conn
+            .set_selection_owner(x11rb::NONE, selection, CURRENT_TIME)?;
+        self.destroy_window(Xid(id))?;
+        self.flush();
+
+        Ok(())
+    }
+
     fn warp_pointer(&self, id: Xid, x: i16, y: i16) -> Result<()> {
         self.conn.warp_pointer(x11rb::NONE, *id, 0, 0, 0, 0, x, y)?;
 
         Ok(())
     }
+
+    // The outline is made up of four unmanaged windows (one per edge) filled with the requested
+    // color so that the contents of the windows underneath are left visible.
+    fn show_outline(&self, r: Rect, width: u32, color: Color) -> Result<()> {
+        let ids = match self.outline.get() {
+            Some(ids) => ids,
+            None => {
+                let mut ids = [0; 4];
+                for id in ids.iter_mut() {
+                    *id = self.conn.generate_id()?;
+                    self.conn.create_window(
+                        x11rb::COPY_DEPTH_FROM_PARENT,
+                        *id,
+                        self.root,
+                        0,
+                        0,
+                        1,
+                        1,
+                        0,
+                        WindowClass::INPUT_OUTPUT,
+                        x11rb::COPY_FROM_PARENT,
+                        &CreateWindowAux::new().override_redirect(1),
+                    )?;
+                }
+                self.outline.set(Some(ids));
+
+                ids
+            }
+        };
+
+        let background = ChangeWindowAttributesAux::new().background_pixel(color.argb_u32());
+        for (&id, edge) in ids.iter().zip(r.outline(width)) {
+            if edge.w == 0 || edge.h == 0 {
+                self.conn.unmap_window(id)?;
+                continue;
+            }
+
+            let aux = ConfigureWindowAux::new()
+                .x(edge.x as i32)
+                .y(edge.y as i32)
+                .width(edge.w)
+                .height(edge.h)
+                .stack_mode(StackMode::ABOVE);
+
+            self.conn.change_window_attributes(id, &background)?;
+            self.conn.configure_window(id, &aux)?;
+            self.conn.map_window(id)?;
+            self.conn.clear_area(false, id, 0, 0, 0, 0)?;
+        }
+        self.flush();
+
+        Ok(())
+    }
+
+    fn hide_outline(&self) -> Result<()> {
+        if let Some(ids) = self.outline.take() {
+            for id in ids {
+                self.conn.destroy_window(id)?;
+            }
+            self.flush();
+        }
+
+        Ok(())
+    }
+
+    fn grab_pointer(&self) -> Result<()> {
+        let mask = EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION;
+        let reply = self
+            .conn
+            .grab_pointer(
+                false,           // report events relative to the root window
+                self.root,       // the window to grab: in this case the root window
+                mask,            // which events are reported to the client
+                GrabMode::ASYNC, // don't lock pointer input while grabbing
+                GrabMode::ASYNC, // don't lock keyboard input while grabbing
+                x11rb::NONE,     // don't confine the cursor to a specific window
+                x11rb::NONE,     // don't change the cursor type
+                CURRENT_TIME,
+            )?
+            .reply()?;
+
+        if reply.status != GrabStatus::SUCCESS {
+            return Err(Error::Custom(format!(
+                "unable to grab pointer: {:?}",
+                reply.status
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn ungrab_pointer(&self) -> Result<()> {
+        self.conn.ungrab_pointer(CURRENT_TIME)?;
+        self.flush();
+
+        Ok(())
+    }
 }
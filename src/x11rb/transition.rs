@@ -0,0 +1,323 @@
+//! Animated transitions between the old and new arrangement of clients on a screen.
+//!
+//! A [Transition] copies the current contents of a screen into a pixmap and covers the screen
+//! with an overlay window showing that snapshot. The arrangement of clients on the screen can
+//! then be updated underneath the overlay before [Transition::run] animates the overlay away,
+//! revealing the new arrangement. As with window mirroring, the animation runs on its own X
+//! connection and thread so that the main event loop is free to handle clients mapping and
+//! redrawing themselves while the transition is in progress.
+use crate::{pure::geometry::Rect, Result};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tracing::{debug, error};
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{
+        AtomEnum, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as _, CreateGCAux,
+        CreateWindowAux, PropMode, SubwindowMode, WindowClass,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    NONE,
+};
+
+// The time between frames of a running transition (roughly 60fps).
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// How the snapshot of the old arrangement is animated away.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TransitionEffect {
+    /// Slide the old arrangement off the left hand side of the screen
+    #[default]
+    SlideLeft,
+    /// Slide the old arrangement off the right hand side of the screen
+    SlideRight,
+    /// Fade out the old arrangement.
+    ///
+    /// This requires a compositing manager that supports `_NET_WM_WINDOW_OPACITY`: without
+    /// one the old arrangement is shown for the duration of the transition and then removed.
+    Fade,
+}
+
+/// A snapshot of a screen shown in an overlay window that can be animated away to reveal
+/// the current contents of the screen.
+///
+/// The overlay is removed once the animation completes, or when the [Transition] is stopped
+/// or dropped.
+pub struct Transition {
+    overlay: Option<Overlay>,
+    stopping: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transition")
+            .field("screen", &self.overlay.as_ref().map(|o| o.screen))
+            .field("running", &self.is_running())
+            .finish()
+    }
+}
+
+impl Transition {
+    /// Capture the current contents of the given region of the screen and cover it with an
+    /// overlay showing the snapshot.
+    ///
+    /// This blocks until the overlay has been mapped so that any changes made once it
+    /// returns are hidden until the transition is run.
+    pub fn capture(screen: Rect) -> Result<Self> {
+        let overlay = Overlay::new(screen)?;
+
+        Ok(Self {
+            overlay: Some(overlay),
+            stopping: Arc::new(AtomicBool::new(false)),
+            handle: None,
+        })
+    }
+
+    /// Animate the overlay away using the given effect, taking `duration` to complete.
+    ///
+    /// Calling this method on a transition that has already been run has no effect.
+    pub fn run(&mut self, effect: TransitionEffect, duration: Duration) {
+        let overlay = match self.overlay.take() {
+            Some(overlay) => overlay,
+            None => return,
+        };
+
+        let flag = Arc::clone(&self.stopping);
+        self.handle = Some(thread::spawn(move || {
+            overlay.animate(effect, duration, flag)
+        }));
+    }
+
+    /// Whether or not the transition is still being animated.
+    pub fn is_running(&self) -> bool {
+        matches!(&self.handle, Some(h) if !h.is_finished())
+    }
+
+    /// Skip the remainder of the transition, removing the overlay.
+    pub fn stop(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        if let Some(overlay) = self.overlay.take() {
+            overlay.destroy();
+        }
+
+        if let Some(handle) = self.handle.take() {
+            self.stopping.store(true, Ordering::SeqCst);
+            if handle.join().is_err() {
+                error!("transition thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Transition {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+// The X resources used for showing a snapshot of the screen.
+struct Overlay {
+    conn: RustConnection,
+    screen: Rect,
+    window: u32,
+    pixmap: u32,
+    gc: u32,
+}
+
+impl Overlay {
+    fn new(screen: Rect) -> Result<Self> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        let s = &conn.setup().roots[screen_num];
+        let (root, depth) = (s.root, s.root_depth);
+        let Rect { x, y, w, h } = screen;
+
+        let pixmap = conn.generate_id()?;
+        conn.create_pixmap(depth, pixmap, root, w as u16, h as u16)?;
+
+        // Including inferiors copies the contents of client windows rather than just the
+        // root window background
+        let gc = conn.generate_id()?;
+        conn.create_gc(
+            gc,
+            root,
+            &CreateGCAux::new()
+                .subwindow_mode(SubwindowMode::INCLUDE_INFERIORS)
+                .graphics_exposures(0),
+        )?;
+        conn.copy_area(
+            root, pixmap, gc, x as i16, y as i16, 0, 0, w as u16, h as u16,
+        )?;
+
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            x as i16,
+            y as i16,
+            w as u16,
+            h as u16,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new()
+                .override_redirect(1)
+                .background_pixmap(pixmap),
+        )?;
+        conn.map_window(window)?;
+
+        // Wait for the server to process our requests so that the overlay is in place before
+        // the caller starts rearranging clients
+        conn.get_input_focus()?.reply()?;
+
+        Ok(Self {
+            conn,
+            screen,
+            window,
+            pixmap,
+            gc,
+        })
+    }
+
+    fn animate(self, effect: TransitionEffect, duration: Duration, stopping: Arc<AtomicBool>) {
+        if let Err(e) = self.run_frames(effect, duration, &stopping) {
+            debug!(%e, "error running transition");
+        }
+
+        self.destroy();
+    }
+
+    fn run_frames(
+        &self,
+        effect: TransitionEffect,
+        duration: Duration,
+        stopping: &AtomicBool,
+    ) -> Result<()> {
+        let n_frames = (duration.as_millis() / FRAME_INTERVAL.as_millis()).max(1) as u32;
+        let opacity = self
+            .conn
+            .intern_atom(false, b"_NET_WM_WINDOW_OPACITY")?
+            .reply()?
+            .atom;
+
+        // Slides redraw the snapshot themselves as the overlay is resized
+        if effect != TransitionEffect::Fade {
+            self.conn.change_window_attributes(
+                self.window,
+                &ChangeWindowAttributesAux::new().background_pixmap(NONE),
+            )?;
+        }
+
+        for n in 1..n_frames {
+            if stopping.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let progress = ease_out(n as f64 / n_frames as f64);
+
+            if effect == TransitionEffect::Fade {
+                let value = ((1.0 - progress) * u32::MAX as f64) as u32;
+                self.conn.change_property32(
+                    PropMode::REPLACE,
+                    self.window,
+                    opacity,
+                    AtomEnum::CARDINAL,
+                    &[value],
+                )?;
+            } else {
+                let (offset, w, src_x) = slide_frame(effect, self.screen.w, progress);
+                if w == 0 {
+                    break;
+                }
+
+                let Rect { x, y, h, .. } = self.screen;
+                self.conn.configure_window(
+                    self.window,
+                    &ConfigureWindowAux::new()
+                        .x((x + offset) as i32)
+                        .y(y as i32)
+                        .width(w),
+                )?;
+                self.conn.copy_area(
+                    self.pixmap,
+                    self.window,
+                    self.gc,
+                    src_x as i16,
+                    0,
+                    0,
+                    0,
+                    w as u16,
+                    h as u16,
+                )?;
+            }
+
+            self.conn.flush()?;
+            thread::sleep(FRAME_INTERVAL);
+        }
+
+        Ok(())
+    }
+
+    fn destroy(self) {
+        let _ = self.conn.destroy_window(self.window);
+        let _ = self.conn.free_gc(self.gc);
+        let _ = self.conn.free_pixmap(self.pixmap);
+        let _ = self.conn.flush();
+    }
+}
+
+// Decelerate towards the end of the transition.
+fn ease_out(t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+
+    1.0 - (1.0 - t).powi(3)
+}
+
+// The x offset and width of the overlay part way through a slide of a screen with width `w`,
+// along with the x offset into the snapshot that should be drawn at its left edge.
+fn slide_frame(effect: TransitionEffect, w: u32, progress: f64) -> (u32, u32, u32) {
+    let moved = ((w as f64 * progress).round() as u32).min(w);
+
+    match effect {
+        TransitionEffect::SlideRight => (moved, w - moved, 0),
+        _ => (0, w - moved, moved),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case(0.0, 0.0; "start")]
+    #[test_case(0.5, 0.875; "halfway")]
+    #[test_case(1.0, 1.0; "end")]
+    #[test_case(2.0, 1.0; "clamped")]
+    #[test]
+    fn ease_out_works(t: f64, expected: f64) {
+        assert_eq!(ease_out(t), expected);
+    }
+
+    #[test_case(TransitionEffect::SlideLeft, 0.0, (0, 1000, 0); "left start")]
+    #[test_case(TransitionEffect::SlideLeft, 0.25, (0, 750, 250); "left part way")]
+    #[test_case(TransitionEffect::SlideLeft, 1.0, (0, 0, 1000); "left end")]
+    #[test_case(TransitionEffect::SlideRight, 0.0, (0, 1000, 0); "right start")]
+    #[test_case(TransitionEffect::SlideRight, 0.25, (250, 750, 0); "right part way")]
+    #[test_case(TransitionEffect::SlideRight, 1.0, (1000, 0, 0); "right end")]
+    #[test]
+    fn slide_frame_works(effect: TransitionEffect, progress: f64, expected: (u32, u32, u32)) {
+        assert_eq!(slide_frame(effect, 1000, progress), expected);
+    }
+}